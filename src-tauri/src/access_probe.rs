@@ -0,0 +1,218 @@
+//! 开始大任务前的章节访问探测：抓一本书的一章，判断可以直接阅读、付费章节未订阅，还是需要登录。
+//!
+//! 下载预览和扫榜流水线的第 1 阶段各探测一次，结果经 [`ACCESS_EVENT`] 推送并随预览 / 榜单快照返回，
+//! 让用户先登录或放弃，而不是等几十本书的章节逐一失败。判断依据是各平台的登录墙和锁页提示文字
+//! （[`crate::spiders::login_markers`]、[`crate::spiders::vip_markers`]），只在正文明显偏短时查找，
+//! 避免正文里偶尔出现"登录"误判。
+//!
+//! 探测与下载走同一个 [`NovelSource`]，同样受爬虫窗口预算和熔断器约束；平台熔断冷却中时不探测。
+//! 最近成功读到过付费章节正文的平台视为已有登录状态（[`SESSION_TTL`] 内），跳过探测。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::download::Catalog;
+use crate::resources;
+use crate::spiders::{self, NovelSource, SpiderError};
+
+/// 探测结果事件
+pub const ACCESS_EVENT: &str = "access-probe";
+/// 读到付费章节正文后，视为该平台已登录的时长
+pub const SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+/// 正文不超过最少字数的这个倍数时才查找提示文字
+const MARKER_PAGE_FACTOR: usize = 3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// 可以直接读到正文
+    Free,
+    /// 付费章节未订阅
+    VipGated,
+    /// 需要登录
+    LoginRequired,
+    /// 抓取失败或无法判断
+    Unknown,
+}
+
+impl Access {
+    pub fn label(self) -> String {
+        match self {
+            Access::Free => resources::text("access.free"),
+            Access::VipGated => resources::text("access.vip_gated"),
+            Access::LoginRequired => resources::text("access.login_required"),
+            Access::Unknown => resources::text("access.unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessProbe {
+    pub platform: String,
+    pub novel_title: String,
+    /// 探测的目录序号；跳过探测时为 None
+    pub chapter_index: Option<usize>,
+    pub chapter_title: Option<String>,
+    pub access: Access,
+    /// 判断依据或跳过的原因
+    pub reason: Option<String>,
+    /// 未发请求（已有登录状态、平台熔断中或目录为空）
+    pub skipped: bool,
+    pub probed_at: String,
+}
+
+impl AccessProbe {
+    /// 一行说明，用于进度消息
+    pub fn summary(&self) -> String {
+        let mut text = format!("《{}》{}", self.novel_title, self.access.label());
+        if let Some(title) = &self.chapter_title {
+            text.push_str(&format!("（探测 {}）", title));
+        }
+        if let Some(reason) = &self.reason {
+            text.push_str(&format!("：{}", reason));
+        }
+        if self.access == Access::LoginRequired {
+            text.push_str(&resources::text("access.login_hint"));
+        }
+        text
+    }
+}
+
+fn sessions() -> &'static Mutex<HashMap<String, Instant>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 读到了付费章节的正文：记为该平台已有登录状态
+pub fn note_session(platform: &str) {
+    sessions().lock().unwrap_or_else(|e| e.into_inner()).insert(platform.to_string(), Instant::now());
+}
+
+/// [`SESSION_TTL`] 内是否读到过该平台付费章节的正文
+pub fn session_active(platform: &str) -> bool {
+    sessions().lock().unwrap_or_else(|e| e.into_inner()).get(platform).is_some_and(|at| at.elapsed() < SESSION_TTL)
+}
+
+fn find_marker<'a>(markers: &[&'a str], text: &str) -> Option<&'a str> {
+    markers.iter().find(|m| text.contains(**m)).copied()
+}
+
+/// 按抓取结果判断访问状态，返回 (状态, 依据)
+pub fn classify(platform: &str, is_vip: bool, min_chars: usize, result: &Result<String, SpiderError>) -> (Access, Option<String>) {
+    let text = match result {
+        Ok(body) => body.clone(),
+        Err(SpiderError::ChapterUnavailable { final_url, .. }) if final_url.contains("login") => {
+            return (Access::LoginRequired, Some(format!("章节页跳转到登录页 {}", final_url)));
+        }
+        Err(e @ (SpiderError::CircuitOpen { .. } | SpiderError::WafBlocked(_) | SpiderError::Timeout(_))) => {
+            return (Access::Unknown, Some(e.to_string()));
+        }
+        Err(e) => e.to_string(),
+    };
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    if result.is_ok() && chars > min_chars * MARKER_PAGE_FACTOR {
+        return (Access::Free, None);
+    }
+    if let Some(marker) = find_marker(spiders::login_markers(platform), &text) {
+        return (Access::LoginRequired, Some(format!("页面提示“{}”", marker)));
+    }
+    if let Some(marker) = find_marker(spiders::vip_markers(platform), &text) {
+        return (Access::VipGated, Some(format!("页面提示“{}”", marker)));
+    }
+    match result {
+        Ok(_) if chars >= min_chars => (Access::Free, None),
+        Ok(_) if is_vip => (Access::VipGated, Some(format!("付费章节正文只有 {} 字", chars))),
+        Ok(_) => (Access::Unknown, Some(format!("正文只有 {} 字", chars))),
+        Err(e) => (Access::Unknown, Some(e.to_string())),
+    }
+}
+
+/// 在 planned（目录序号）中选一章探测：有付费章节时取第一个付费章节，否则取第一个正文章节
+fn pick_chapter(catalog: &Catalog, planned: &[usize]) -> Option<usize> {
+    let entry = |i: usize| catalog.chapters.get(i.checked_sub(1)?);
+    planned
+        .iter()
+        .copied()
+        .find(|&i| entry(i).is_some_and(|e| e.is_vip))
+        .or_else(|| planned.iter().copied().find(|&i| entry(i).is_some_and(|e| !e.is_extra)))
+        .or_else(|| planned.iter().copied().find(|&i| entry(i).is_some()))
+}
+
+/// 探测 catalog 中计划下载的一章；planned 为空时从整个目录中选
+pub async fn probe<S: NovelSource>(source: &S, platform: &str, catalog: &Catalog, planned: &[usize], min_chars: usize) -> AccessProbe {
+    let mut result = AccessProbe {
+        platform: platform.to_string(),
+        novel_title: catalog.novel_title.clone(),
+        chapter_index: None,
+        chapter_title: None,
+        access: Access::Unknown,
+        reason: None,
+        skipped: true,
+        probed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    if session_active(platform) {
+        result.access = Access::Free;
+        result.reason = Some("已检测到登录状态，未探测".to_string());
+        return result;
+    }
+    if let Some(wait) = spiders::circuit::cooldown_remaining(platform) {
+        result.reason = Some(format!("平台熔断冷却中（{} 秒），未探测", wait.as_secs()));
+        return result;
+    }
+    let all: Vec<usize> = (1..=catalog.chapters.len()).collect();
+    let Some(index) = pick_chapter(catalog, if planned.is_empty() { &all } else { planned }) else {
+        result.reason = Some("目录为空，未探测".to_string());
+        return result;
+    };
+    let entry = &catalog.chapters[index - 1];
+    let fetched = source.download_chapter(platform, &entry.url, false).await.map(|(_, body, _)| body);
+    let (access, reason) = classify(platform, entry.is_vip, min_chars, &fetched);
+    if access == Access::Free && entry.is_vip {
+        note_session(platform);
+    }
+    AccessProbe {
+        chapter_index: Some(index),
+        chapter_title: Some(entry.title.clone()),
+        access,
+        reason,
+        skipped: false,
+        ..result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_and_login_pages_are_told_apart_from_prose() {
+        let prose = Ok("他推开门，".repeat(100));
+        assert_eq!(classify("qidian", true, 100, &prose), (Access::Free, None));
+        // 正文里提到"请先登录"不算登录墙
+        let long_with_marker = Ok(format!("{}请先登录", "正文".repeat(400)));
+        assert_eq!(classify("qidian", false, 100, &long_with_marker).0, Access::Free);
+
+        let login = Ok("本章内容需要请先登录后阅读".to_string());
+        assert_eq!(classify("qidian", false, 100, &login).0, Access::LoginRequired);
+        let locked = Ok("第一段……订阅本章继续阅读".to_string());
+        assert_eq!(classify("qidian", true, 100, &locked).0, Access::VipGated);
+        let short_vip = Ok("第一段".to_string());
+        assert_eq!(classify("fanqie", true, 100, &short_vip).0, Access::VipGated);
+        assert_eq!(classify("fanqie", false, 100, &short_vip).0, Access::Unknown);
+
+        let redirected = Err(SpiderError::ChapterUnavailable { url: "u".into(), final_url: "https://passport.qidian.com/login".into() });
+        assert_eq!(classify("qidian", false, 100, &redirected).0, Access::LoginRequired);
+        let blocked = Err(SpiderError::WafBlocked("请先登录".into()));
+        assert_eq!(classify("qidian", false, 100, &blocked).0, Access::Unknown, "WAF 拦截不当作登录墙");
+    }
+
+    #[test]
+    fn session_is_remembered_per_platform() {
+        assert!(!session_active("test-platform"));
+        note_session("test-platform");
+        assert!(session_active("test-platform"));
+        assert!(!session_active("other-platform"));
+    }
+}
@@ -0,0 +1,399 @@
+//! 活动周报：汇总一段时间内的下载、分析和榜单变化，写入 `<workspace>/result/digest_<截止日期>.md`。
+//!
+//! 数据来源：下载记录（[`crate::download_history`]）、分析索引（按批次的章节数和 token 消耗）、
+//! 榜单快照（[`crate::rank_snapshots`]，取窗口开始前最后一份与窗口内最后一份比对）。
+//! 某个来源不存在时省略对应小节，并在概览中注明。设置中开启 `activity_digest.auto` 后，
+//! 调度器在指定的星期几生成上一周（前 7 天）的周报。
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::analysis_batch::{self, IndexEntry, RESULT_DIR};
+use crate::download_history::{self, DownloadRecord};
+use crate::rank_snapshots::{self, RankDiff, RANKS_DIR};
+
+const DIGEST_PREFIX: &str = "digest_";
+/// 名次变化达到这个幅度才列入周报
+const NOTABLE_RANK_DELTA: i64 = 5;
+/// 每个榜单最多列出的变化条数
+const MAX_RANK_LINES: usize = 10;
+
+pub const SECTION_DOWNLOADS: &str = "下载记录";
+pub const SECTION_ANALYSIS: &str = "分析索引";
+pub const SECTION_RANKS: &str = "榜单快照";
+
+/// 自动生成周报的设置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DigestSettings {
+    /// 由调度器在 weekday 自动生成
+    pub auto: bool,
+    /// 星期几生成，1 = 周一 … 7 = 周日
+    pub weekday: u32,
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self { auto: false, weekday: 1 }
+    }
+}
+
+impl DigestSettings {
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        self.auto && today.weekday().number_from_monday() == self.weekday.clamp(1, 7)
+    }
+}
+
+/// 统计窗口（含首尾两天）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestWindow {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+}
+
+fn parse_date(raw: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d").map_err(|_| format!("无效的日期: {}（格式为 YYYY-MM-DD）", raw))
+}
+
+/// 时间戳（`%Y-%m-%d %H:%M:%S` 或 RFC 3339）的日期部分
+fn date_of(timestamp: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(timestamp.get(..10)?, "%Y-%m-%d").ok()
+}
+
+impl DigestWindow {
+    /// today 之前的 7 天
+    pub fn last_week(today: NaiveDate) -> Self {
+        Self { since: today - chrono::Days::new(7), until: today - chrono::Days::new(1) }
+    }
+
+    /// 缺省截止到 today 前一天；缺省起始为截止日期前 6 天（共 7 天）
+    pub fn parse(since: Option<&str>, until: Option<&str>, today: NaiveDate) -> Result<Self, String> {
+        let until = match until.filter(|s| !s.trim().is_empty()) {
+            Some(raw) => parse_date(raw)?,
+            None => today - chrono::Days::new(1),
+        };
+        let since = match since.filter(|s| !s.trim().is_empty()) {
+            Some(raw) => parse_date(raw)?,
+            None => until - chrono::Days::new(6),
+        };
+        if since > until {
+            return Err(format!("起始日期 {} 晚于截止日期 {}", since, until));
+        }
+        Ok(Self { since, until })
+    }
+
+    fn contains(&self, timestamp: &str) -> bool {
+        date_of(timestamp).is_some_and(|d| self.since <= d && d <= self.until)
+    }
+}
+
+/// 周报的标题数字，供界面直接展示
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct DigestSummary {
+    pub since: String,
+    pub until: String,
+    /// 有新章节下载的书
+    pub novels_downloaded: usize,
+    pub chapters_downloaded: usize,
+    /// 其中已有章节、又下载到新章节的连载
+    pub serials_updated: usize,
+    pub analysis_batches: usize,
+    pub chapters_analyzed: usize,
+    /// 分析批次记录的 token 消耗
+    pub total_tokens: u64,
+    /// 新上榜和名次明显变化的条数
+    pub rank_movements: usize,
+    /// 数据源缺失而省略的小节
+    pub omitted: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ActivityDigest {
+    /// 相对工作区
+    pub path: String,
+    pub summary: DigestSummary,
+}
+
+pub fn digest_path(workspace_root: &Path, until: NaiveDate) -> PathBuf {
+    workspace_root.join(RESULT_DIR).join(format!("{}{}.md", DIGEST_PREFIX, until.format("%Y-%m-%d")))
+}
+
+#[derive(Default)]
+struct NovelDownloads {
+    runs: usize,
+    chapters: usize,
+    failed: usize,
+    update: bool,
+}
+
+#[derive(Default)]
+struct NovelAnalysis {
+    batches: usize,
+    chapters: usize,
+    tokens: u64,
+}
+
+fn downloads_in(records: Vec<DownloadRecord>, window: &DigestWindow) -> BTreeMap<String, NovelDownloads> {
+    let mut novels: BTreeMap<String, NovelDownloads> = BTreeMap::new();
+    for record in records.into_iter().filter(|r| window.contains(&r.at)) {
+        let novel = novels.entry(record.novel_title.clone()).or_default();
+        novel.runs += 1;
+        novel.chapters += record.downloaded;
+        novel.failed += record.failed;
+        novel.update |= record.is_update();
+    }
+    novels
+}
+
+fn analysis_in(index: Vec<IndexEntry>, window: &DigestWindow) -> BTreeMap<String, NovelAnalysis> {
+    let mut novels: BTreeMap<String, NovelAnalysis> = BTreeMap::new();
+    for entry in index.into_iter().filter(|e| window.contains(&e.completed_at)) {
+        let novel = novels.entry(entry.novel_title).or_default();
+        novel.batches += 1;
+        novel.chapters += entry.chapters;
+        // 外部导入的结果没有经过本应用的 AI 接口
+        if !entry.external {
+            novel.tokens += entry.total_tokens;
+        }
+    }
+    novels
+}
+
+/// 每个榜单：窗口开始前的最后一份快照（没有时取窗口内第一份）与窗口内最后一份比对
+fn rank_diffs(workspace_root: &Path, window: &DigestWindow) -> Option<Vec<(String, RankDiff)>> {
+    let mut ids: Vec<String> = fs::read_dir(workspace_root.join(RANKS_DIR))
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    ids.sort();
+    let (since, until) = (window.since.format("%Y-%m-%d").to_string(), window.until.format("%Y-%m-%d").to_string());
+    let mut diffs = Vec::new();
+    for id in ids {
+        let dates = rank_snapshots::list(workspace_root, &id);
+        let Some(latest) = dates.iter().rfind(|d| **d >= since && **d <= until) else { continue };
+        let baseline = dates.iter().rfind(|d| **d < since).or_else(|| dates.iter().find(|d| **d >= since));
+        let Some(baseline) = baseline.filter(|b| *b != latest) else { continue };
+        let (Ok(a), Ok(b)) = (rank_snapshots::load(workspace_root, &id, baseline), rank_snapshots::load(workspace_root, &id, latest)) else {
+            continue;
+        };
+        diffs.push((b.rank_url.clone(), rank_snapshots::diff(&id, baseline, &a.entries, latest, &b.entries)));
+    }
+    (!diffs.is_empty()).then_some(diffs)
+}
+
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// 汇总窗口内的活动并写入周报
+pub fn generate(workspace_root: &Path, window: DigestWindow) -> Result<ActivityDigest, String> {
+    let mut summary = DigestSummary {
+        since: window.since.format("%Y-%m-%d").to_string(),
+        until: window.until.format("%Y-%m-%d").to_string(),
+        ..Default::default()
+    };
+    let mut body = String::new();
+
+    match download_history::load(workspace_root) {
+        Some(records) => {
+            let novels = downloads_in(records, &window);
+            let active: Vec<_> = novels.iter().filter(|(_, n)| n.chapters > 0).collect();
+            summary.novels_downloaded = active.len();
+            summary.chapters_downloaded = active.iter().map(|(_, n)| n.chapters).sum();
+            summary.serials_updated = active.iter().filter(|(_, n)| n.update).count();
+            body.push_str(&format!("\n## 下载（{} 本，{} 章）\n\n", summary.novels_downloaded, summary.chapters_downloaded));
+            if novels.is_empty() {
+                body.push_str("期间没有下载任务。\n");
+            } else {
+                body.push_str("| 书名 | 新章节 | 类型 | 任务数 | 失败章节 |\n| --- | --- | --- | --- | --- |\n");
+                for (title, n) in &novels {
+                    let kind = if n.update { "连载更新" } else if n.chapters > 0 { "新下载" } else { "无新章节" };
+                    body.push_str(&format!("| {} | {} | {} | {} | {} |\n", cell(title), n.chapters, kind, n.runs, n.failed));
+                }
+            }
+        }
+        None => summary.omitted.push(SECTION_DOWNLOADS.to_string()),
+    }
+
+    if workspace_root.join(RESULT_DIR).join(analysis_batch::INDEX_FILE).is_file() {
+        let novels = analysis_in(analysis_batch::load_index(workspace_root), &window);
+        summary.analysis_batches = novels.values().map(|n| n.batches).sum();
+        summary.chapters_analyzed = novels.values().map(|n| n.chapters).sum();
+        summary.total_tokens = novels.values().map(|n| n.tokens).sum();
+        body.push_str(&format!(
+            "\n## 分析（{} 个批次，{} 章，{} tokens）\n\n",
+            summary.analysis_batches, summary.chapters_analyzed, summary.total_tokens
+        ));
+        if novels.is_empty() {
+            body.push_str("期间没有完成的分析批次。\n");
+        } else {
+            body.push_str("| 书名 | 批次 | 章节 | tokens |\n| --- | --- | --- | --- |\n");
+            for (title, n) in &novels {
+                body.push_str(&format!("| {} | {} | {} | {} |\n", cell(title), n.batches, n.chapters, n.tokens));
+            }
+        }
+    } else {
+        summary.omitted.push(SECTION_ANALYSIS.to_string());
+    }
+
+    match rank_diffs(workspace_root, &window) {
+        Some(diffs) => {
+            let mut section = String::new();
+            for (rank_url, diff) in &diffs {
+                let mut lines: Vec<String> = diff.entered.iter().map(|e| format!("- 新上榜 #{} {}", e.position, e.title)).collect();
+                let mut moves: Vec<_> = diff.moved.iter().filter(|m| m.delta.abs() >= NOTABLE_RANK_DELTA).collect();
+                moves.sort_by_key(|m| std::cmp::Reverse(m.delta.abs()));
+                lines.extend(moves.iter().map(|m| {
+                    let arrow = if m.delta > 0 { "↑" } else { "↓" };
+                    format!("- {}{} {}（{} → {}）", arrow, m.delta.abs(), m.title, m.from, m.to)
+                }));
+                summary.rank_movements += lines.len();
+                section.push_str(&format!("\n### {}\n\n{} → {}，落榜 {} 本\n\n", rank_url, diff.date_a, diff.date_b, diff.exited.len()));
+                if lines.is_empty() {
+                    section.push_str("没有明显变化。\n");
+                } else {
+                    let hidden = lines.len().saturating_sub(MAX_RANK_LINES);
+                    lines.truncate(MAX_RANK_LINES);
+                    section.push_str(&lines.join("\n"));
+                    section.push('\n');
+                    if hidden > 0 {
+                        section.push_str(&format!("- ……另有 {} 条\n", hidden));
+                    }
+                }
+            }
+            body.push_str(&format!("\n## 榜单变化（{} 条）\n{}", summary.rank_movements, section));
+        }
+        None => summary.omitted.push(SECTION_RANKS.to_string()),
+    }
+
+    let mut doc = format!("# 活动周报 {} ~ {}\n\n## 概览\n\n", summary.since, summary.until);
+    if !summary.omitted.contains(&SECTION_DOWNLOADS.to_string()) {
+        doc.push_str(&format!(
+            "- 下载：{} 本书，{} 章，其中连载更新 {} 本\n",
+            summary.novels_downloaded, summary.chapters_downloaded, summary.serials_updated
+        ));
+    }
+    if !summary.omitted.contains(&SECTION_ANALYSIS.to_string()) {
+        doc.push_str(&format!(
+            "- 分析：{} 个批次，{} 章，消耗 {} tokens\n",
+            summary.analysis_batches, summary.chapters_analyzed, summary.total_tokens
+        ));
+    }
+    if !summary.omitted.contains(&SECTION_RANKS.to_string()) {
+        doc.push_str(&format!("- 榜单：{} 条新上榜或名次明显变化\n", summary.rank_movements));
+    }
+    if !summary.omitted.is_empty() {
+        doc.push_str(&format!("- 未找到{}，相应小节已省略\n", summary.omitted.join("、")));
+    }
+    doc.push_str(&body);
+
+    let path = digest_path(workspace_root, window.until);
+    if let Some(dir) = path.parent() {
+        crate::storage::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    crate::storage::write_text(&path, &doc).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok(ActivityDigest {
+        path: crate::paths::to_relative(workspace_root, &path).unwrap_or_else(|| path.display().to_string()),
+        summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank_snapshots::RankSnapshot;
+    use crate::spiders::{RankEntry, RankSource};
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    fn record(at: &str, title: &str, downloaded: usize, skipped: usize) -> DownloadRecord {
+        DownloadRecord { at: at.into(), novel_title: title.into(), downloaded, skipped, ..Default::default() }
+    }
+
+    fn snapshot(root: &Path, day: &str, titles: &[&str]) {
+        let entries = titles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| RankEntry { position: i + 1, title: t.to_string(), url: format!("https://www.qidian.com/book/{}/", t), score: None, author: None })
+            .collect();
+        let snap = RankSnapshot { rank_url: "https://www.qidian.com/rank/yuepiao/".into(), taken_at: day.into(), source: RankSource::Html, entries, access: None };
+        let dir = root.join(RANKS_DIR).join("www.qidian.com_rank_yuepiao");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{}.json", day)), serde_json::to_string(&snap).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn window_defaults_to_the_previous_seven_days() {
+        let monday = date("2026-03-09");
+        assert_eq!(DigestWindow::parse(None, None, monday).unwrap(), DigestWindow::last_week(monday));
+        assert_eq!(DigestWindow::last_week(monday), DigestWindow { since: date("2026-03-02"), until: date("2026-03-08") });
+        assert!(DigestWindow::parse(Some("2026-03-09"), Some("2026-03-01"), monday).is_err());
+        assert!(DigestSettings { auto: true, weekday: 1 }.is_due(monday));
+        assert!(!DigestSettings { auto: false, weekday: 1 }.is_due(monday));
+        assert!(!DigestSettings { auto: true, weekday: 3 }.is_due(monday));
+    }
+
+    #[test]
+    fn digest_aggregates_sources_and_omits_missing_ones() {
+        let root = std::env::temp_dir().join(format!("test_activity_digest_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let window = DigestWindow { since: date("2026-03-02"), until: date("2026-03-08") };
+
+        let empty = generate(&root, window).unwrap();
+        assert_eq!(empty.summary.omitted, vec![SECTION_DOWNLOADS, SECTION_ANALYSIS, SECTION_RANKS]);
+        assert_eq!(empty.path, "result/digest_2026-03-08.md");
+
+        for r in [
+            record("2026-03-01 23:59:59", "旧书", 50, 0),
+            record("2026-03-03 10:00:00", "新书", 20, 0),
+            record("2026-03-05 10:00:00", "连载", 3, 120),
+            record("2026-03-06 10:00:00", "连载", 2, 123),
+            record("2026-03-07 10:00:00", "完结", 0, 300),
+        ] {
+            download_history::append(&root, &r);
+        }
+        let entry = |title: &str, at: &str, chapters, tokens| IndexEntry {
+            novel_title: title.into(),
+            batch_id: at.into(),
+            model: "m".into(),
+            chapters,
+            total_tokens: tokens,
+            duration_ms: 0,
+            completed_at: at.into(),
+            outputs: Vec::new(),
+            external: false,
+        };
+        analysis_batch::save_index(
+            &root,
+            &[entry("新书", "2026-03-04 08:00:00", 10, 5000), entry("新书", "2026-03-09 08:00:00", 10, 7000)],
+        )
+        .unwrap();
+        let titles: Vec<String> = (1..=10).map(|i| format!("书{}", i)).collect();
+        let mut before: Vec<&str> = titles.iter().map(String::as_str).collect();
+        snapshot(&root, "2026-02-28", &before);
+        before.rotate_right(1); // 书10 从第 10 名升到第 1 名，其余下降 1 名
+        before[5] = "黑马";
+        snapshot(&root, "2026-03-07", &before);
+
+        let digest = generate(&root, window).unwrap();
+        let s = &digest.summary;
+        assert!(s.omitted.is_empty());
+        assert_eq!((s.novels_downloaded, s.chapters_downloaded, s.serials_updated), (2, 25, 1));
+        assert_eq!((s.analysis_batches, s.chapters_analyzed, s.total_tokens), (1, 10, 5000));
+        assert_eq!(s.rank_movements, 2);
+        let doc = fs::read_to_string(root.join(&digest.path)).unwrap();
+        assert!(doc.contains("| 连载 | 5 | 连载更新 | 2 | 0 |"));
+        assert!(doc.contains("- 新上榜 #6 黑马"));
+        assert!(doc.contains("- ↑9 书10（10 → 1）"));
+        assert!(!doc.contains("旧书"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,239 @@
+//! 分析请求的小说背景：info.json 中的题材 / 风格 / 金手指，以及上一章已保存的细纲。
+//!
+//! 开启 `include_context` 后，背景块用明确的分隔标记放在正文之前。背景计入请求体上限，
+//! 超出时先截短上一章细纲，仍超出再去掉题材信息；正文本身不截断（超限由请求体检查报错）。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::analysis_batch;
+
+const CONTEXT_BEGIN: &str = "【背景信息】（供理解本章参考，不要分析或复述这部分）";
+const CONTEXT_END: &str = "【背景信息结束】";
+const CONTENT_BEGIN: &str = "【正文】";
+/// 截短后的细纲末尾标记
+const TRIMMED_MARK: &str = "…（已截断）";
+/// 请求体中除提示词、背景和正文外的 JSON 结构开销
+const BODY_OVERHEAD_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ContextSources {
+    /// 题材 / 风格 / 金手指
+    pub metadata: bool,
+    /// 上一章的细纲
+    pub previous_outline: bool,
+}
+
+impl Default for ContextSources {
+    fn default() -> Self {
+        ContextSources { metadata: true, previous_outline: true }
+    }
+}
+
+/// 命令参数：include_context 为 true 时生效，未给出 context_sources 时两项都启用
+pub fn sources(include_context: Option<bool>, context_sources: Option<ContextSources>) -> Option<ContextSources> {
+    include_context.unwrap_or(false).then(|| context_sources.unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NovelContext {
+    pub metadata: Option<String>,
+    pub previous_outline: Option<String>,
+}
+
+fn text_field<'a>(map: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    map.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// info.json 中 AI 分析得到的题材 / 风格 / 金手指；没有 AI 分析时用标签代替题材
+pub fn metadata_summary(info: &Map<String, Value>) -> Option<String> {
+    let analysis = info.get("ai_analysis").and_then(Value::as_object);
+    let tags = info
+        .get("tags")
+        .and_then(Value::as_array)
+        .map(|tags| tags.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("、"))
+        .filter(|t| !t.is_empty());
+    let genre = analysis.and_then(|a| text_field(a, "genre")).map(str::to_string).or(tags);
+
+    let mut lines = Vec::new();
+    if let Some(genre) = genre {
+        lines.push(format!("题材：{}", genre));
+    }
+    if let Some(style) = analysis.and_then(|a| text_field(a, "style")) {
+        lines.push(format!("风格：{}", style));
+    }
+    if let Some(goldfinger) = analysis.and_then(|a| text_field(a, "goldfinger")) {
+        lines.push(format!("金手指：{}", goldfinger));
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// 结果文件名（`05.md`、`04-06.md`）覆盖的章节范围
+fn output_range(stem: &str) -> Option<(usize, usize)> {
+    match stem.split_once('-') {
+        Some((a, b)) => Some((a.parse().ok()?, b.parse().ok()?)),
+        None => stem.parse().ok().map(|n| (n, n)),
+    }
+}
+
+/// 章节文件（如 `05.txt`）上一章已保存的分析结果：覆盖上一章且范围最小的结果文件
+pub fn previous_outline(workspace_root: &Path, novel_title: &str, chapter_file: &str) -> Option<String> {
+    let current: usize = chapter_file.trim_end_matches(".txt").parse().ok()?;
+    let previous = current.checked_sub(1).filter(|&p| p > 0)?;
+    let dir = analysis_batch::result_dir(workspace_root, novel_title);
+    let best = fs::read_dir(&dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let ext = path.extension()?.to_str()?;
+            if ext != "md" && ext != "json" {
+                return None;
+            }
+            let (from, to) = output_range(path.file_stem()?.to_str()?)?;
+            (from <= previous && previous <= to).then_some((to - from, path))
+        })
+        .min_by_key(|(span, _)| *span)?;
+    let text = crate::storage::read_to_string(&best.1).ok()?;
+    Some(crate::provenance::strip_front_matter(&text).trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// 读取启用的背景来源
+pub fn load(
+    workspace_root: &Path,
+    novel_dir: &Path,
+    chapter_file: Option<&str>,
+    sources: ContextSources,
+) -> NovelContext {
+    let metadata = if sources.metadata {
+        crate::novel_info::read_info(novel_dir).ok().and_then(|info| metadata_summary(&info))
+    } else {
+        None
+    };
+    let previous_outline = match (sources.previous_outline, chapter_file) {
+        (true, Some(file)) => {
+            let title = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            previous_outline(workspace_root, &title, file)
+        }
+        _ => None,
+    };
+    NovelContext { metadata, previous_outline }
+}
+
+impl NovelContext {
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_none() && self.previous_outline.is_none()
+    }
+
+    /// 背景块（不含正文），为空时返回 None
+    pub fn render(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut block = String::from(CONTEXT_BEGIN);
+        if let Some(metadata) = &self.metadata {
+            block.push('\n');
+            block.push_str(metadata);
+        }
+        if let Some(outline) = &self.previous_outline {
+            block.push_str("\n上一章细纲：\n");
+            block.push_str(outline);
+        }
+        block.push('\n');
+        block.push_str(CONTEXT_END);
+        Some(block)
+    }
+
+    /// 把背景放在正文之前，总字节数不超过 budget。超出时先截短上一章细纲，再去掉题材信息。
+    pub fn compose(mut self, content: &str, budget: usize) -> (String, NovelContext) {
+        let fixed = content.len() + CONTENT_BEGIN.len() + 4;
+        let rendered_len = |ctx: &NovelContext| ctx.render().map_or(0, |b| b.len());
+        if fixed + rendered_len(&self) > budget {
+            if let Some(outline) = self.previous_outline.take() {
+                let without = rendered_len(&NovelContext { previous_outline: Some(String::new()), ..self.clone() });
+                let room = budget.saturating_sub(fixed + without + TRIMMED_MARK.len());
+                if room > 0 {
+                    let cut = outline.char_indices().map(|(i, _)| i).take_while(|&i| i <= room).last().unwrap_or(0);
+                    if cut > 0 {
+                        self.previous_outline = Some(format!("{}{}", &outline[..cut], TRIMMED_MARK));
+                    }
+                }
+            }
+        }
+        if fixed + rendered_len(&self) > budget {
+            self.metadata = None;
+        }
+        let composed = match self.render() {
+            Some(block) => format!("{}\n\n{}\n{}", block, CONTENT_BEGIN, content),
+            None => content.to_string(),
+        };
+        (composed, self)
+    }
+}
+
+/// 背景可用的请求体预算：请求体上限减去提示词和结构开销
+pub fn body_budget(prompt: &str) -> usize {
+    body_budget_within(crate::ai::max_body_bytes(), prompt)
+}
+
+/// 同 [`body_budget`]，请求体上限取自单本书的 AI 配置（见 [`crate::ai::AiConfig::body_limit`]）
+pub fn body_budget_within(limit: usize, prompt: &str) -> usize {
+    limit.saturating_sub(prompt.len() + BODY_OVERHEAD_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> NovelContext {
+        NovelContext { metadata: Some("题材：玄幻".to_string()), previous_outline: Some("上一章发生了很多事".repeat(20)) }
+    }
+
+    #[test]
+    fn metadata_prefers_ai_analysis_and_falls_back_to_tags() {
+        let info = serde_json::json!({
+            "tags": ["都市", "系统"],
+            "ai_analysis": {"style": "轻松", "goldfinger": "签到系统"}
+        });
+        let summary = metadata_summary(info.as_object().unwrap()).unwrap();
+        assert_eq!(summary, "题材：都市、系统\n风格：轻松\n金手指：签到系统");
+        assert_eq!(metadata_summary(&Map::new()), None);
+    }
+
+    #[test]
+    fn compose_prepends_context_within_budget() {
+        let (full, kept) = context().compose("正文", 100_000);
+        assert!(full.starts_with(CONTEXT_BEGIN));
+        assert!(full.ends_with("【正文】\n正文"));
+        assert_eq!(kept, context());
+
+        // 预算不足：先截短细纲，题材保留
+        let budget = "正文".len() + 200;
+        let (full, kept) = context().compose("正文", budget);
+        assert!(full.len() <= budget);
+        assert!(kept.previous_outline.as_deref().is_none_or(|o| o.ends_with(TRIMMED_MARK)));
+        assert!(kept.metadata.is_some());
+
+        // 连题材也放不下时只剩正文
+        let (full, kept) = context().compose("正文", 10);
+        assert_eq!(full, "正文");
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn previous_outline_uses_smallest_covering_result() {
+        let root = std::env::temp_dir().join(format!("test_ai_context_{}", std::process::id()));
+        let dir = analysis_batch::result_dir(&root, "书名");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01-03.md"), "分组细纲").unwrap();
+        fs::write(dir.join("03.md"), "第三章细纲").unwrap();
+        fs::write(dir.join("batch_x.json"), "{}").unwrap();
+        assert_eq!(previous_outline(&root, "书名", "04.txt").as_deref(), Some("第三章细纲"));
+        assert_eq!(previous_outline(&root, "书名", "03.txt").as_deref(), Some("分组细纲"));
+        assert_eq!(previous_outline(&root, "书名", "01.txt"), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
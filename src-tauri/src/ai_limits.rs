@@ -0,0 +1,303 @@
+//! 按服务端声明的限额调度 AI 请求。
+//!
+//! OpenAI 兼容接口在响应头中返回 `x-ratelimit-remaining-requests` / `x-ratelimit-remaining-tokens`
+//! 以及对应的 `x-ratelimit-reset-*`。每次响应后按（接口地址, key）记下最新额度；批量分析派发下一个
+//! 请求前调用 [`pace`]，额度将尽时等到预计恢复的时间再发，而不是撞上 429 再等重试。
+//! 没有这些响应头的服务端不做任何等待，与原来一致。
+//!
+//! 同时在途的 AI 请求不超过资源档位的 `ai_slots`（见 [`crate::limits`]），超出时按任务优先级排队（见 [`crate::priority`]），
+//! 用户当场发起的分析排在批量分析、重试队列之前。`get_ai_queue_status` 返回 [`queue_status`]。
+
+use chrono::{DateTime, Local};
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::priority::{GateMetrics, GatePermit, PriorityGate};
+
+/// 剩余 token 低于该值（上限较小时改为上限的 1/20）时暂停派发
+const LOW_TOKENS: u64 = 2_000;
+/// 单次等待的上限，防止异常的重置时间让批次无限期挂起
+const MAX_WAIT: Duration = Duration::from_secs(10 * 60);
+
+/// 一次响应中声明的限额，缺少的字段为 None
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub limit_requests: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub reset_requests: Option<Duration>,
+    pub reset_tokens: Option<Duration>,
+}
+
+impl RateLimits {
+    /// 解析响应头，没有任何剩余额度字段时返回 None
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+        let number = |name: &str| text(name).and_then(|v| v.parse::<u64>().ok());
+        let limits = RateLimits {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_requests: text("x-ratelimit-reset-requests").and_then(parse_reset),
+            reset_tokens: text("x-ratelimit-reset-tokens").and_then(parse_reset),
+        };
+        (limits.remaining_requests.is_some() || limits.remaining_tokens.is_some()).then_some(limits)
+    }
+
+    /// 距观测已过 elapsed 时还需等待多久，以及原因。重置时间未知的额度不等待
+    fn delay(&self, elapsed: Duration) -> Option<(Duration, &'static str)> {
+        let remaining = |reset: Option<Duration>| reset.map(|r| r.saturating_sub(elapsed)).filter(|d| !d.is_zero());
+        let requests = self
+            .remaining_requests
+            .filter(|&r| r == 0)
+            .and_then(|_| remaining(self.reset_requests))
+            .map(|d| (d, "请求数额度已用完"));
+        let token_floor = self.limit_tokens.map_or(LOW_TOKENS, |limit| LOW_TOKENS.min(limit / 20));
+        let tokens = self
+            .remaining_tokens
+            .filter(|&t| t < token_floor)
+            .and_then(|_| remaining(self.reset_tokens))
+            .map(|d| (d, "token 额度将尽"));
+        [requests, tokens].into_iter().flatten().max_by_key(|(d, _)| *d).map(|(d, reason)| (d.min(MAX_WAIT), reason))
+    }
+}
+
+/// 解析 `1s`、`6m0s`、`20ms`、`1h2m3.5s` 或纯秒数
+pub fn parse_reset(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if let Ok(secs) = text.parse::<f64>() {
+        return (secs >= 0.0).then(|| Duration::from_secs_f64(secs));
+    }
+    let mut total = 0.0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let value: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        total += value
+            * match &rest[..unit_end] {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => return None,
+            };
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+struct Budget {
+    api_base: String,
+    key_hint: String,
+    limits: RateLimits,
+    observed_at: Instant,
+    observed_wall: DateTime<Local>,
+}
+
+fn budgets() -> &'static Mutex<HashMap<String, Budget>> {
+    static BUDGETS: OnceLock<Mutex<HashMap<String, Budget>>> = OnceLock::new();
+    BUDGETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 同一接口的不同 key 额度分开记；表里不保存 key 本身
+fn budget_key(url: &str, api_key: &str) -> String {
+    format!("{}#{}", url, crate::storage::content_hash(api_key.as_bytes()))
+}
+
+fn key_hint(api_key: &str) -> String {
+    let tail: String = api_key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("…{}", tail)
+}
+
+/// 记录一次响应声明的额度（post_chat 每次收到响应后调用）
+pub fn observe(url: &str, api_key: &str, headers: &HeaderMap) {
+    let Some(limits) = RateLimits::from_headers(headers) else { return };
+    let mut map = budgets().lock().unwrap_or_else(|e| e.into_inner());
+    map.insert(
+        budget_key(url, api_key),
+        Budget {
+            api_base: url.to_string(),
+            key_hint: key_hint(api_key),
+            limits,
+            observed_at: Instant::now(),
+            observed_wall: Local::now(),
+        },
+    );
+}
+
+/// 派发下一个请求前需要等待的时间和原因；没有记录或额度充足时为 None
+pub fn dispatch_delay(config: &crate::ai::AiConfig) -> Option<(Duration, &'static str)> {
+    let key = budget_key(&crate::ai::chat_url(&config.api_base), &config.api_key);
+    let map = budgets().lock().unwrap_or_else(|e| e.into_inner());
+    let budget = map.get(&key)?;
+    budget.limits.delay(budget.observed_at.elapsed())
+}
+
+/// 预计恢复的时刻，格式 `HH:MM:SS`
+pub fn resume_time(wait: Duration) -> String {
+    let wait = chrono::Duration::from_std(wait).unwrap_or_default();
+    (Local::now() + wait).format("%H:%M:%S").to_string()
+}
+
+/// 额度将尽时先调用 on_wait（上报 waiting 状态），再等到预计恢复的时间
+pub async fn pace(config: &crate::ai::AiConfig, on_wait: impl FnOnce(Duration, &str)) {
+    if let Some((wait, reason)) = dispatch_delay(config) {
+        on_wait(wait, reason);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn slots() -> &'static PriorityGate {
+    static SLOTS: OnceLock<PriorityGate> = OnceLock::new();
+    SLOTS.get_or_init(|| PriorityGate::new(crate::limits::current().ai_slots))
+}
+
+/// 调整同时在途的 AI 请求上限（所有接口合计）
+pub fn set_slots(limit: usize) {
+    slots().set_limit(limit);
+}
+
+/// 按当前任务的优先级取得一个请求槽位，请求（含流式响应）结束前持有。需要排队时先调用一次 on_queued
+pub async fn acquire_slot(on_queued: impl FnOnce(usize)) -> GatePermit<'static> {
+    slots().acquire(crate::priority::current(), on_queued).await
+}
+
+/// 一个（接口, key）最近一次声明的额度
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ProviderBudget {
+    pub api_base: String,
+    /// key 的末四位
+    pub key_hint: String,
+    pub limit_requests: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub observed_at: String,
+    /// 正在暂停派发时的预计恢复时刻和原因
+    pub resume_at: Option<String>,
+    pub pause_reason: Option<String>,
+}
+
+/// 已记录的各接口额度，按接口地址排序
+pub fn status() -> Vec<ProviderBudget> {
+    let map = budgets().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<ProviderBudget> = map
+        .values()
+        .map(|b| {
+            let pause = b.limits.delay(b.observed_at.elapsed());
+            ProviderBudget {
+                api_base: b.api_base.clone(),
+                key_hint: b.key_hint.clone(),
+                limit_requests: b.limits.limit_requests,
+                remaining_requests: b.limits.remaining_requests,
+                limit_tokens: b.limits.limit_tokens,
+                remaining_tokens: b.limits.remaining_tokens,
+                observed_at: b.observed_wall.format("%Y-%m-%d %H:%M:%S").to_string(),
+                resume_at: pause.map(|(wait, _)| resume_time(wait)),
+                pause_reason: pause.map(|(_, reason)| reason.to_string()),
+            }
+        })
+        .collect();
+    list.sort_by(|a, b| a.api_base.cmp(&b.api_base).then_with(|| a.key_hint.cmp(&b.key_hint)));
+    list
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// 请求槽位的占用和排队，按交互 / 后台分开
+    pub slots: GateMetrics,
+    pub providers: Vec<ProviderBudget>,
+}
+
+pub fn queue_status() -> QueueStatus {
+    QueueStatus { slots: slots().metrics(), providers: status() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn reset_durations_in_openai_format() {
+        assert_eq!(parse_reset("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_reset("1h2m3.5s"), Some(Duration::from_secs_f64(3723.5)));
+        assert_eq!(parse_reset("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_reset("soon"), None);
+    }
+
+    #[test]
+    fn low_budget_delays_until_reset() {
+        let limits = RateLimits::from_headers(&headers(&[
+            ("x-ratelimit-limit-tokens", "100000"),
+            ("x-ratelimit-remaining-tokens", "1500"),
+            ("x-ratelimit-reset-tokens", "30s"),
+            ("x-ratelimit-remaining-requests", "40"),
+            ("x-ratelimit-reset-requests", "1s"),
+        ]))
+        .unwrap();
+        assert_eq!(limits.delay(Duration::from_secs(10)), Some((Duration::from_secs(20), "token 额度将尽")));
+        assert_eq!(limits.delay(Duration::from_secs(31)), None, "重置时间已过");
+
+        let exhausted = RateLimits::from_headers(&headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "2h"),
+        ]))
+        .unwrap();
+        assert_eq!(exhausted.delay(Duration::ZERO), Some((MAX_WAIT, "请求数额度已用完")));
+    }
+
+    #[test]
+    fn missing_headers_keep_current_behavior() {
+        assert_eq!(RateLimits::from_headers(&headers(&[("content-type", "application/json")])), None);
+        // 额度耗尽但不知道何时恢复：不等待
+        let unknown = RateLimits::from_headers(&headers(&[("x-ratelimit-remaining-requests", "0")])).unwrap();
+        assert_eq!(unknown.delay(Duration::ZERO), None);
+
+        let config = crate::ai::AiConfig {
+            api_base: "https://never-observed.test/v1".into(),
+            api_key: "sk-test".into(),
+            model: "m".into(),
+            ..Default::default()
+        };
+        assert_eq!(dispatch_delay(&config), None);
+    }
+
+    #[test]
+    fn status_reports_learned_limits_without_the_key() {
+        let url = crate::ai::chat_url("https://limits.test/v1/");
+        observe(
+            &url,
+            "sk-secret-abcd",
+            &headers(&[("x-ratelimit-remaining-requests", "0"), ("x-ratelimit-reset-requests", "5s")]),
+        );
+        let config = crate::ai::AiConfig {
+            api_base: "https://limits.test/v1/".into(),
+            api_key: "sk-secret-abcd".into(),
+            model: "m".into(),
+            ..Default::default()
+        };
+        assert!(dispatch_delay(&config).is_some_and(|(wait, _)| wait <= Duration::from_secs(5)));
+        let entry = status().into_iter().find(|b| b.api_base == url).unwrap();
+        assert_eq!(entry.key_hint, "…abcd");
+        assert_eq!(entry.remaining_requests, Some(0));
+        assert_eq!(entry.pause_reason.as_deref(), Some("请求数额度已用完"));
+    }
+}
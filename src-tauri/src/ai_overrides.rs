@@ -0,0 +1,179 @@
+//! 单本书的 AI 配置覆盖：章节很长的书用长上下文模型，其余书用默认模型。
+//!
+//! 覆盖项保存在 info.json 的 `user.ai_overrides`（`{profile, max_tokens, chunk_size}`），只能经
+//! [`crate::novel_info::set_user_fields`] 写入。`profile` 引用设置中的 [`Settings::ai_profiles`]。
+//! 每一项按 命令参数 > 书的覆盖 > 全局配置 的顺序取值，缺的项落到下一层，见 [`resolve`]。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::ai::AiConfig;
+use crate::settings::Settings;
+
+/// `user` 中保存覆盖项的键
+pub const OVERRIDES_KEY: &str = "ai_overrides";
+
+/// 设置中的命名 AI 配置，未填的项沿用全局配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AiProfile {
+    pub api_base: Option<String>,
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+}
+
+/// 一层覆盖：命令参数或书的 `user.ai_overrides`，未填的项不覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AiOverrides {
+    pub profile: Option<String>,
+    pub max_tokens: Option<u32>,
+    /// 单次请求体的字节上限，见 [`AiConfig::chunk_size`]
+    pub chunk_size: Option<usize>,
+}
+
+impl AiOverrides {
+    /// 从 `user` 字段读取；缺失或格式不对时视为没有覆盖
+    pub fn from_user(user: &Map<String, Value>) -> Self {
+        user.get(OVERRIDES_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn load(novel_dir: &std::path::Path) -> Self {
+        crate::novel_info::read_user_fields(novel_dir).map(|user| Self::from_user(&user)).unwrap_or_default()
+    }
+}
+
+/// 某一项最终取自哪一层
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    /// 命令参数
+    Explicit,
+    /// 书的 `user.ai_overrides`
+    Novel,
+    Global,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LayerSources {
+    pub profile: Layer,
+    pub model: Layer,
+    pub api_base: Layer,
+    pub max_tokens: Layer,
+    pub chunk_size: Layer,
+}
+
+/// 解析后的配置和每项的来源，供 get_effective_ai_config 排查优先级
+#[derive(Clone, Serialize)]
+pub struct EffectiveAiConfig {
+    pub profile: Option<String>,
+    pub model: String,
+    pub api_base: String,
+    pub max_tokens: Option<u32>,
+    /// 实际使用的请求体上限（未覆盖时为全局上限）
+    pub body_limit: usize,
+    pub sources: LayerSources,
+    #[serde(skip)]
+    pub config: AiConfig,
+}
+
+fn pick<T: Clone>(explicit: &Option<T>, novel: &Option<T>, global: &Option<T>) -> (Option<T>, Layer) {
+    match (explicit, novel) {
+        (Some(v), _) => (Some(v.clone()), Layer::Explicit),
+        (None, Some(v)) => (Some(v.clone()), Layer::Novel),
+        (None, None) => (global.clone(), Layer::Global),
+    }
+}
+
+/// 按 命令参数 > 书的覆盖 > 全局配置 逐项合并。profile 不存在时报错，避免悄悄退回默认模型。
+pub fn resolve(global: &AiConfig, settings: &Settings, novel: &AiOverrides, explicit: &AiOverrides) -> Result<EffectiveAiConfig, String> {
+    let (profile, profile_layer) = pick(&explicit.profile, &novel.profile, &None);
+    let profile = profile.filter(|p| !p.trim().is_empty());
+    let preset = match &profile {
+        Some(name) => Some(settings.ai_profiles.get(name).ok_or_else(|| format!("设置中没有名为 {} 的 AI 配置", name))?),
+        None => None,
+    };
+    let from_profile = |value: Option<&String>, fallback: &String| match value {
+        Some(v) => (v.clone(), profile_layer),
+        None => (fallback.clone(), Layer::Global),
+    };
+    let (model, model_layer) = from_profile(preset.and_then(|p| p.model.as_ref()), &global.model);
+    let (api_base, base_layer) = from_profile(preset.and_then(|p| p.api_base.as_ref()), &global.api_base);
+    let (api_key, _) = from_profile(preset.and_then(|p| p.api_key.as_ref()), &global.api_key);
+    let (max_tokens, tokens_layer) = pick(&explicit.max_tokens, &novel.max_tokens, &global.max_tokens);
+    let (chunk_size, chunk_layer) = pick(&explicit.chunk_size, &novel.chunk_size, &global.chunk_size);
+
+    let config = AiConfig { api_base: api_base.clone(), api_key, model: model.clone(), max_tokens, chunk_size };
+    Ok(EffectiveAiConfig {
+        profile,
+        model,
+        api_base,
+        max_tokens,
+        body_limit: config.body_limit(),
+        sources: LayerSources {
+            profile: profile_layer,
+            model: model_layer,
+            api_base: base_layer,
+            max_tokens: tokens_layer,
+            chunk_size: chunk_layer,
+        },
+        config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn global() -> AiConfig {
+        AiConfig { api_base: "https://default/v1".into(), api_key: "sk-default".into(), model: "cheap".into(), ..Default::default() }
+    }
+
+    fn settings() -> Settings {
+        let mut s = Settings::default();
+        s.ai_profiles.insert("long".into(), AiProfile { model: Some("long-ctx".into()), ..Default::default() });
+        s.ai_profiles.insert("other".into(), AiProfile { api_base: Some("https://other/v1".into()), api_key: Some("sk-other".into()), model: Some("o".into()) });
+        s
+    }
+
+    #[test]
+    fn global_layer_when_nothing_is_overridden() {
+        let r = resolve(&global(), &settings(), &AiOverrides::default(), &AiOverrides::default()).unwrap();
+        assert_eq!((r.model.as_str(), r.profile, r.max_tokens), ("cheap", None, None));
+        assert_eq!(r.body_limit, crate::ai::max_body_bytes());
+        assert_eq!(r.sources.model, Layer::Global);
+        assert_eq!(r.sources.chunk_size, Layer::Global);
+    }
+
+    #[test]
+    fn novel_overrides_beat_global_and_keep_missing_fields() {
+        let user = json!({"rating": 9, "ai_overrides": {"profile": "long", "chunk_size": 2_000_000}});
+        let novel = AiOverrides::from_user(user.as_object().unwrap());
+        let r = resolve(&global(), &settings(), &novel, &AiOverrides::default()).unwrap();
+        assert_eq!(r.model, "long-ctx");
+        assert_eq!(r.sources.model, Layer::Novel);
+        // profile 没填的接口地址 / 密钥沿用全局
+        assert_eq!((r.config.api_base.as_str(), r.config.api_key.as_str()), ("https://default/v1", "sk-default"));
+        assert_eq!(r.sources.api_base, Layer::Global);
+        assert_eq!((r.body_limit, r.sources.chunk_size), (2_000_000, Layer::Novel));
+        assert_eq!((r.max_tokens, r.sources.max_tokens), (None, Layer::Global));
+    }
+
+    #[test]
+    fn explicit_parameters_beat_novel_overrides_per_field() {
+        let novel = AiOverrides { profile: Some("long".into()), max_tokens: Some(8000), chunk_size: Some(1_000_000) };
+        let explicit = AiOverrides { profile: Some("other".into()), max_tokens: Some(1000), chunk_size: None };
+        let r = resolve(&global(), &settings(), &novel, &explicit).unwrap();
+        assert_eq!((r.profile.as_deref(), r.model.as_str(), r.config.api_key.as_str()), (Some("other"), "o", "sk-other"));
+        assert_eq!(r.sources.api_base, Layer::Explicit);
+        assert_eq!((r.max_tokens, r.sources.max_tokens), (Some(1000), Layer::Explicit));
+        assert_eq!((r.body_limit, r.sources.chunk_size), (1_000_000, Layer::Novel));
+
+        let missing = AiOverrides { profile: Some("gone".into()), ..Default::default() };
+        assert!(resolve(&global(), &settings(), &missing, &AiOverrides::default()).is_err());
+        assert_eq!(AiOverrides::from_user(json!({"ai_overrides": "坏数据"}).as_object().unwrap()), AiOverrides::default());
+    }
+}
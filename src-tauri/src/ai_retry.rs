@@ -0,0 +1,364 @@
+//! AI 分析重试队列：`<workspace>/ai_retry_queue.json`。
+//!
+//! 批次中某组因网络、限流、服务端 5xx 等可重试的原因失败时（见 [`crate::ai::AiError::is_retryable`]），
+//! 连同重跑所需的参数（批次、分组、提示词、模型、接口）记入队列；鉴权失败、请求不合法等不入队。
+//! [`process`] 逐条经 [`crate::ai_limits::pace`] 重新分析，成功的移出队列，失败的累加次数，
+//! 达到上限后放弃。调度器可按设置中的间隔自动处理，见 [`RetrySettings`]。
+//!
+//! 密钥不写入队列文件：重试时取接口地址相同的命名 AI 配置的密钥，没有时用全局密钥。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::ai::{AiConfig, AiError};
+use crate::analysis_batch::{self, BatchManifest, EntryError, EntryStatus};
+use crate::settings::Settings;
+use crate::{ai_limits, paths, storage};
+
+pub const QUEUE_FILE: &str = "ai_retry_queue.json";
+/// 设置未指定时每条最多尝试的次数（含首次失败）
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// 设置未指定时一次处理的条数
+pub const DEFAULT_BATCH_LIMIT: usize = 10;
+
+/// 读-改-写队列文件时加锁，避免批次入队与重试处理互相覆盖
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 设置中的 `ai_retry`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// 由调度器按 interval_minutes 自动处理队列
+    pub auto: bool,
+    pub interval_minutes: u64,
+    pub max_attempts: u32,
+    /// 每次处理的条数
+    pub batch_limit: usize,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            auto: false,
+            interval_minutes: 30,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            batch_limit: DEFAULT_BATCH_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetryEntry {
+    pub novel_title: String,
+    /// 正文目录，相对工作区
+    pub novel_dir: String,
+    pub batch_id: String,
+    /// 分组在批次清单中的序号
+    pub entry_index: usize,
+    pub chapters: Vec<String>,
+    pub prompt: String,
+    pub model: String,
+    pub api_base: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+    /// 已失败的次数（含批次中的首次失败）
+    pub attempts: u32,
+    pub last_error: String,
+    pub enqueued_at: String,
+    pub last_attempt_at: String,
+}
+
+impl RetryEntry {
+    pub fn new(
+        workspace_root: &Path,
+        novel_dir: &Path,
+        manifest: &BatchManifest,
+        i: usize,
+        config: &AiConfig,
+        prompt: &str,
+        error: &AiError,
+    ) -> Self {
+        let now = now();
+        RetryEntry {
+            novel_title: manifest.novel_title.clone(),
+            novel_dir: paths::to_relative(workspace_root, novel_dir)
+                .unwrap_or_else(|| novel_dir.to_string_lossy().to_string()),
+            batch_id: manifest.id.clone(),
+            entry_index: i,
+            chapters: manifest.entries[i].chapters.clone(),
+            prompt: prompt.to_string(),
+            model: config.model.clone(),
+            api_base: config.api_base.clone(),
+            max_tokens: config.max_tokens,
+            chunk_size: config.chunk_size,
+            attempts: 1,
+            last_error: error.to_string(),
+            enqueued_at: now.clone(),
+            last_attempt_at: now,
+        }
+    }
+
+    fn same_target(&self, other: &RetryEntry) -> bool {
+        self.novel_title == other.novel_title && self.batch_id == other.batch_id && self.entry_index == other.entry_index
+    }
+
+    /// 重试用的配置：接口、模型等取入队时的值，密钥见模块说明
+    fn config(&self, global: &AiConfig, settings: &Settings) -> AiConfig {
+        let api_key = if self.api_base == global.api_base {
+            global.api_key.clone()
+        } else {
+            settings
+                .ai_profiles
+                .values()
+                .find(|p| p.api_base.as_deref() == Some(self.api_base.as_str()))
+                .and_then(|p| p.api_key.clone())
+                .unwrap_or_else(|| global.api_key.clone())
+        };
+        AiConfig {
+            api_base: self.api_base.clone(),
+            api_key,
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            chunk_size: self.chunk_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ProcessReport {
+    pub attempted: usize,
+    pub succeeded: usize,
+    /// 失败但仍留在队列中
+    pub failed: usize,
+    /// 达到次数上限、遇到不可重试的错误或批次已不需要而移出队列
+    pub dropped: usize,
+    /// 因批次正在运行而跳过
+    pub skipped: usize,
+    pub remaining: usize,
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn load_unlocked(workspace_root: &Path) -> Vec<RetryEntry> {
+    let path = workspace_root.join(QUEUE_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("[AiRetry] 解析 {} 失败: {}", path.display(), e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_unlocked(workspace_root: &Path, entries: &[RetryEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(entries).map_err(|e| format!("序列化重试队列失败: {}", e))?;
+    storage::write_atomic(&workspace_root.join(QUEUE_FILE), content.as_bytes()).map_err(|e| format!("写入重试队列失败: {}", e))
+}
+
+fn update<R>(workspace_root: &Path, f: impl FnOnce(&mut Vec<RetryEntry>) -> R) -> Result<R, String> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load_unlocked(workspace_root);
+    let result = f(&mut entries);
+    save_unlocked(workspace_root, &entries)?;
+    Ok(result)
+}
+
+/// 队列中的全部条目，按入队顺序
+pub fn list(workspace_root: &Path) -> Vec<RetryEntry> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_unlocked(workspace_root)
+}
+
+/// 入队。同一批次的同一分组已在队列中时更新参数和错误，保留已失败的次数并加一
+pub fn enqueue(workspace_root: &Path, entry: RetryEntry) -> Result<(), String> {
+    update(workspace_root, |entries| match entries.iter_mut().find(|e| e.same_target(&entry)) {
+        Some(existing) => {
+            let attempts = existing.attempts + 1;
+            let enqueued_at = std::mem::take(&mut existing.enqueued_at);
+            *existing = RetryEntry { attempts, enqueued_at, ..entry };
+        }
+        None => entries.push(entry),
+    })
+}
+
+fn remove(workspace_root: &Path, target: &RetryEntry) -> Result<(), String> {
+    update(workspace_root, |entries| entries.retain(|e| !e.same_target(target)))
+}
+
+fn record_failure(workspace_root: &Path, target: &RetryEntry, error: &str) -> Result<(), String> {
+    update(workspace_root, |entries| {
+        if let Some(e) = entries.iter_mut().find(|e| e.same_target(target)) {
+            e.attempts += 1;
+            e.last_error = error.to_string();
+            e.last_attempt_at = now();
+        }
+    })
+}
+
+/// 条目对应的分组仍需重跑时返回批次清单；批次已删除、该组已完成或分组已变化时返回 None
+fn still_needed(workspace_root: &Path, entry: &RetryEntry) -> Option<BatchManifest> {
+    let manifest = analysis_batch::load(workspace_root, &entry.novel_title, &entry.batch_id).ok()?;
+    let group = manifest.entries.get(entry.entry_index)?;
+    (group.status != EntryStatus::Completed && group.chapters == entry.chapters).then_some(manifest)
+}
+
+/// 按入队顺序重试最多 limit 条。每条最多尝试 max_attempts 次，达到上限后移出队列并记日志。
+/// 所在批次正在运行的条目跳过，留待下次。
+pub async fn process(
+    workspace_root: &Path,
+    global: &AiConfig,
+    settings: &Settings,
+    limit: usize,
+    max_attempts: u32,
+) -> Result<ProcessReport, String> {
+    let mut report = ProcessReport::default();
+    let pending = list(workspace_root);
+    for entry in pending {
+        if report.attempted >= limit {
+            break;
+        }
+        let Some(mut manifest) = still_needed(workspace_root, &entry) else {
+            remove(workspace_root, &entry)?;
+            report.dropped += 1;
+            continue;
+        };
+        let Ok(_guard) = analysis_batch::claim(&entry.novel_title, &entry.batch_id) else {
+            report.skipped += 1;
+            continue;
+        };
+        let novel_dir = paths::resolve(workspace_root, &entry.novel_dir)?;
+        let config = entry.config(global, settings);
+        ai_limits::pace(&config, |wait, reason| {
+            eprintln!("[AiRetry] AI 接口{}，{} 恢复派发", reason, ai_limits::resume_time(wait));
+        })
+        .await;
+        report.attempted += 1;
+        let result = analysis_batch::analyze_entry_once(
+            workspace_root,
+            &novel_dir,
+            &mut manifest,
+            entry.entry_index,
+            &config,
+            &entry.prompt,
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                remove(workspace_root, &entry)?;
+                report.succeeded += 1;
+                if manifest.next_pending().is_none() && manifest.completed_at.is_none() {
+                    analysis_batch::finish(workspace_root, &mut manifest)?;
+                }
+                crate::log_to_file_with_root(
+                    &format!("[AiRetry] {} 批次 {} 第 {} 组重试成功", entry.novel_title, entry.batch_id, entry.entry_index + 1),
+                    Some(workspace_root),
+                );
+            }
+            Err(EntryError::Ai(e)) if e.is_retryable() && entry.attempts + 1 < max_attempts => {
+                record_failure(workspace_root, &entry, &e.to_string())?;
+                report.failed += 1;
+            }
+            Err(e) => {
+                remove(workspace_root, &entry)?;
+                report.dropped += 1;
+                crate::log_to_file_with_root(
+                    &format!(
+                        "[AiRetry] {} 批次 {} 第 {} 组放弃重试（已尝试 {} 次）: {}",
+                        entry.novel_title,
+                        entry.batch_id,
+                        entry.entry_index + 1,
+                        entry.attempts + 1,
+                        e
+                    ),
+                    Some(workspace_root),
+                );
+            }
+        }
+    }
+    report.remaining = list(workspace_root).len();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis_batch::BatchParams;
+
+    fn temp_root(tag: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_ai_retry_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn manifest() -> BatchManifest {
+        let params = BatchParams {
+            prompt_hash: analysis_batch::prompt_hash("p"),
+            model: "m".to_string(),
+            group_size: 1,
+            chapters: vec!["01.txt".to_string(), "02.txt".to_string()],
+            context: None,
+            normalize: None,
+            arc: None,
+            version_policy: None,
+            output_language: None,
+        };
+        BatchManifest::new("20260101000000000".to_string(), "书", params)
+    }
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        assert!(AiError::Network("timeout".into()).is_retryable());
+        assert!(AiError::Http { status: 429, body: String::new() }.is_retryable());
+        assert!(AiError::Http { status: 503, body: String::new() }.is_retryable());
+        assert!(!AiError::Http { status: 401, body: String::new() }.is_retryable());
+        assert!(!AiError::Http { status: 400, body: String::new() }.is_retryable());
+        assert!(!AiError::BadRequest("bad".into()).is_retryable());
+    }
+
+    #[test]
+    fn enqueue_upserts_and_counts_attempts() {
+        let root = temp_root("enqueue");
+        let m = manifest();
+        let config = AiConfig { api_base: "https://a".into(), model: "m".into(), ..Default::default() };
+        let error = AiError::Network("timeout".into());
+        enqueue(&root, RetryEntry::new(&root, &root.join("downloads/书"), &m, 1, &config, "p", &error)).unwrap();
+        let error = AiError::Http { status: 502, body: "bad gateway".into() };
+        enqueue(&root, RetryEntry::new(&root, &root.join("downloads/书"), &m, 1, &config, "p", &error)).unwrap();
+        enqueue(&root, RetryEntry::new(&root, &root.join("downloads/书"), &m, 0, &config, "p", &error)).unwrap();
+
+        let queue = list(&root);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].entry_index, 1);
+        assert_eq!(queue[0].attempts, 2);
+        assert!(queue[0].last_error.contains("502"));
+        assert_eq!(queue[0].novel_dir, "downloads/书");
+        assert!(!fs::read_to_string(root.join(QUEUE_FILE)).unwrap().contains("api_key"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn entries_no_longer_needed_are_dropped() {
+        let root = temp_root("process");
+        let mut m = manifest();
+        m.entries[0].status = EntryStatus::Completed;
+        analysis_batch::save(&root, &mut m).unwrap();
+        let config = AiConfig { api_base: "https://a".into(), model: "m".into(), ..Default::default() };
+        let error = AiError::Network("timeout".into());
+        enqueue(&root, RetryEntry::new(&root, &root.join("downloads/书"), &m, 0, &config, "p", &error)).unwrap();
+        let mut gone = RetryEntry::new(&root, &root.join("downloads/书"), &m, 1, &config, "p", &error);
+        gone.batch_id = "20250101000000000".to_string();
+        enqueue(&root, gone).unwrap();
+
+        let report = process(&root, &config, &Settings::default(), 10, DEFAULT_MAX_ATTEMPTS).await.unwrap();
+        assert_eq!(report, ProcessReport { dropped: 2, ..Default::default() });
+        assert!(list(&root).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,711 @@
+//! 整本书的分组 AI 分析批次。
+//!
+//! 每次 `analyze_novel` 对应一个批次，清单写在 `result/<小说>/batch_<id>.json`：记录参数
+//! （提示词哈希、模型、分组大小、章节列表）和逐组状态。中断后用 `resume_batch_id` 续跑，
+//! 参数不一致时拒绝续跑，避免同一批结果混用不同提示词或分组。完成的批次把 token 用量和耗时
+//! 汇总进 `result/analysis_index.json`。
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::analysis_versions::{self, VersionPolicy, WriteTarget};
+use crate::arcs::StoryArc;
+use crate::provenance::{self, AnalysisOutput, Provenance, SourceChapter};
+use crate::text_normalize::{self, NormalizeOptions};
+use crate::{ai, ai_context, ai_limits, storage};
+
+pub const RESULT_DIR: &str = "result";
+pub const INDEX_FILE: &str = "analysis_index.json";
+const MANIFEST_PREFIX: &str = "batch_";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchParams {
+    pub prompt_hash: String,
+    pub model: String,
+    pub group_size: usize,
+    /// 章节文件（如 `01.txt`，分目录存放时为 `chapters/000/01.txt`），按分析顺序
+    pub chapters: Vec<String>,
+    /// 附带的小说背景，None 表示不附带
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<ai_context::ContextSources>,
+    /// 送入 AI 前对正文做的规范化（繁简、标点、空行），None 表示原样
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<NormalizeOptions>,
+    /// 只分析某个故事弧时的弧；结果文件名带弧名前缀，正文前加弧标题
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arc: Option<StoryArc>,
+    /// 已有结果时的处理方式，None 按设置和默认规则，见 [`analysis_versions`]。续跑时以本次指定的为准
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_policy: Option<VersionPolicy>,
+    /// 要求 AI 使用的输出语言代码（见 [`crate::prompts::output_language`]），None 表示不指定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_language: Option<String>,
+}
+
+impl BatchParams {
+    /// 与另一组参数不同的字段名
+    fn differences(&self, other: &BatchParams) -> Vec<&'static str> {
+        let mut diff = Vec::new();
+        if self.prompt_hash != other.prompt_hash {
+            diff.push("提示词");
+        }
+        if self.model != other.model {
+            diff.push("模型");
+        }
+        if self.group_size != other.group_size {
+            diff.push("分组大小");
+        }
+        if self.chapters != other.chapters {
+            diff.push("章节列表");
+        }
+        if self.context != other.context {
+            diff.push("背景信息");
+        }
+        if self.normalize != other.normalize {
+            diff.push("正文规范化");
+        }
+        if self.arc != other.arc {
+            diff.push("故事弧");
+        }
+        if self.output_language != other.output_language {
+            diff.push("输出语言");
+        }
+        diff
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchEntry {
+    pub chapters: Vec<String>,
+    pub status: EntryStatus,
+    /// 结果文件，相对工作区
+    #[serde(default)]
+    pub output_file: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub tokens: Option<u64>,
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// 分析时各章节文件的内容哈希，用于判断结果是否过期
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceChapter>,
+    /// 已有同一模型和提示词的结果，未重新分析；output_file 指向已有结果
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchManifest {
+    pub id: String,
+    pub novel_title: String,
+    pub params: BatchParams,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    pub entries: Vec<BatchEntry>,
+}
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl BatchManifest {
+    pub fn new(id: String, novel_title: &str, params: BatchParams) -> Self {
+        let entries = params
+            .chapters
+            .chunks(params.group_size.max(1))
+            .map(|group| BatchEntry {
+                chapters: group.to_vec(),
+                status: EntryStatus::Pending,
+                output_file: None,
+                error: None,
+                tokens: None,
+                duration_ms: 0,
+                sources: Vec::new(),
+                skipped: false,
+            })
+            .collect();
+        let created_at = now();
+        BatchManifest {
+            id,
+            novel_title: novel_title.to_string(),
+            params,
+            updated_at: created_at.clone(),
+            created_at,
+            completed_at: None,
+            entries,
+        }
+    }
+
+    /// 第一个未完成的分组
+    pub fn next_pending(&self) -> Option<usize> {
+        self.entries.iter().position(|e| e.status != EntryStatus::Completed)
+    }
+
+    /// 追加一个单章分组（边下载边分析时章节逐个到达）
+    pub fn push_chapter(&mut self, file: String) {
+        self.params.chapters.push(file.clone());
+        self.entries.push(BatchEntry {
+            chapters: vec![file],
+            status: EntryStatus::Pending,
+            output_file: None,
+            error: None,
+            tokens: None,
+            duration_ms: 0,
+            sources: Vec::new(),
+            skipped: false,
+        });
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.entries.iter().filter_map(|e| e.tokens).sum()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.entries.iter().map(|e| e.duration_ms).sum()
+    }
+
+    /// 已完成分组的结果文件及其来源章节（跳过的分组沿用已有结果，不计入）
+    pub fn outputs(&self) -> Vec<AnalysisOutput> {
+        self.entries
+            .iter()
+            .filter(|e| e.status == EntryStatus::Completed && !e.skipped)
+            .filter_map(|e| {
+                Some(AnalysisOutput {
+                    output_file: e.output_file.clone()?,
+                    sources: e.sources.clone(),
+                    output_language: self.params.output_language.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// 提示词哈希，只用于判断两次批次的提示词是否相同
+pub fn prompt_hash(prompt: &str) -> String {
+    storage::content_hash(prompt.as_bytes())
+}
+
+pub fn result_dir(workspace_root: &Path, novel_title: &str) -> PathBuf {
+    workspace_root.join(RESULT_DIR).join(novel_title)
+}
+
+fn manifest_path(workspace_root: &Path, novel_title: &str, id: &str) -> PathBuf {
+    result_dir(workspace_root, novel_title).join(format!("{}{}.json", MANIFEST_PREFIX, id))
+}
+
+fn check_batch_id(id: &str) -> Result<(), String> {
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("无效的批次 ID: {}", id))
+    }
+}
+
+pub fn load(workspace_root: &Path, novel_title: &str, id: &str) -> Result<BatchManifest, String> {
+    check_batch_id(id)?;
+    let path = manifest_path(workspace_root, novel_title, id);
+    let content = fs::read_to_string(&path).map_err(|_| format!("批次 {} 不存在", id))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析批次 {} 失败: {}", id, e))
+}
+
+pub fn save(workspace_root: &Path, manifest: &mut BatchManifest) -> Result<(), String> {
+    manifest.updated_at = now();
+    let path = manifest_path(workspace_root, &manifest.novel_title, &manifest.id);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(manifest).map_err(|e| format!("序列化批次失败: {}", e))?;
+    storage::write_atomic(&path, content.as_bytes()).map_err(|e| format!("写入批次清单失败: {}", e))
+}
+
+pub fn new_batch_id() -> String {
+    Local::now().format("%Y%m%d%H%M%S%3f").to_string()
+}
+
+/// 新建批次，或校验参数后载入要续跑的批次。
+pub fn prepare(
+    workspace_root: &Path,
+    novel_title: &str,
+    params: BatchParams,
+    resume_batch_id: Option<&str>,
+    force_new_batch: bool,
+) -> Result<BatchManifest, String> {
+    if params.chapters.is_empty() {
+        return Err("没有可分析的章节".to_string());
+    }
+    if let Some(id) = resume_batch_id.map(str::trim).filter(|id| !id.is_empty()) {
+        let mut manifest = load(workspace_root, novel_title, id)?;
+        let diff = manifest.params.differences(&params);
+        if diff.is_empty() {
+            if manifest.next_pending().is_none() {
+                return Err(format!("批次 {} 已全部完成", id));
+            }
+            manifest.params.version_policy = params.version_policy;
+            return Ok(manifest);
+        }
+        if !force_new_batch {
+            return Err(format!(
+                "批次 {} 的参数已改变（{}），无法续跑；如需按新参数重新分析请设置 force_new_batch",
+                id,
+                diff.join("、")
+            ));
+        }
+    }
+    let mut manifest = BatchManifest::new(new_batch_id(), novel_title, params);
+    save(workspace_root, &mut manifest)?;
+    Ok(manifest)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub completed_at: Option<String>,
+    pub model: String,
+    pub group_size: usize,
+    pub chapters: usize,
+    pub groups: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub resumable: bool,
+    pub total_tokens: u64,
+}
+
+/// 某本书的全部批次，从新到旧。
+pub fn list_batches(workspace_root: &Path, novel_title: &str) -> Vec<BatchSummary> {
+    let Ok(entries) = fs::read_dir(result_dir(workspace_root, novel_title)) else {
+        return Vec::new();
+    };
+    let mut batches: Vec<BatchSummary> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let id = name.strip_prefix(MANIFEST_PREFIX)?.strip_suffix(".json")?.to_string();
+            load(workspace_root, novel_title, &id).ok()
+        })
+        .map(|m| {
+            let count = |status| m.entries.iter().filter(|e| e.status == status).count();
+            BatchSummary {
+                resumable: m.next_pending().is_some(),
+                total_tokens: m.total_tokens(),
+                completed: count(EntryStatus::Completed),
+                failed: count(EntryStatus::Failed),
+                groups: m.entries.len(),
+                chapters: m.params.chapters.len(),
+                group_size: m.params.group_size,
+                model: m.params.model,
+                completed_at: m.completed_at,
+                updated_at: m.updated_at,
+                created_at: m.created_at,
+                id: m.id,
+            }
+        })
+        .collect();
+    batches.sort_by(|a, b| b.id.cmp(&a.id));
+    batches
+}
+
+/// analysis_index.json 中的一条批次汇总
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub novel_title: String,
+    pub batch_id: String,
+    pub model: String,
+    pub chapters: usize,
+    pub total_tokens: u64,
+    pub duration_ms: u64,
+    pub completed_at: String,
+    /// 各结果文件引用的章节及其内容哈希
+    #[serde(default)]
+    pub outputs: Vec<AnalysisOutput>,
+    /// 从外部导入的结果（见 [`crate::analysis_import`]），不计入 token 用量统计
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub external: bool,
+}
+
+pub fn load_index(workspace_root: &Path) -> Vec<IndexEntry> {
+    fs::read_to_string(workspace_root.join(RESULT_DIR).join(INDEX_FILE))
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// 把已完成批次的统计写入（或替换）分析索引。
+pub fn record_in_index(workspace_root: &Path, manifest: &BatchManifest) -> Result<(), String> {
+    let mut index = load_index(workspace_root);
+    index.retain(|e| !(e.novel_title == manifest.novel_title && e.batch_id == manifest.id));
+    index.push(IndexEntry {
+        novel_title: manifest.novel_title.clone(),
+        batch_id: manifest.id.clone(),
+        model: manifest.params.model.clone(),
+        chapters: manifest.params.chapters.len(),
+        total_tokens: manifest.total_tokens(),
+        duration_ms: manifest.duration_ms(),
+        completed_at: manifest.completed_at.clone().unwrap_or_else(now),
+        outputs: manifest.outputs(),
+        external: false,
+    });
+    save_index(workspace_root, &index)
+}
+
+pub fn save_index(workspace_root: &Path, index: &[IndexEntry]) -> Result<(), String> {
+    let path = workspace_root.join(RESULT_DIR).join(INDEX_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(index).map_err(|e| format!("序列化分析索引失败: {}", e))?;
+    storage::write_atomic(&path, content.as_bytes()).map_err(|e| format!("写入分析索引失败: {}", e))
+}
+
+/// 小说目录下的章节文件（相对小说目录，分目录存放时带 `chapters/NNN/` 前缀），按章节序号排序
+pub fn chapter_files(novel_dir: &Path) -> Vec<String> {
+    crate::sharding::chapter_files(novel_dir).into_iter().map(|(_, file)| file).collect()
+}
+
+/// 正在运行的批次（`<小说>/<id>`），同一批次不允许并发续跑
+fn running() -> &'static Mutex<HashSet<String>> {
+    static RUNNING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+pub struct RunningGuard(String);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        if let Ok(mut set) = running().lock() {
+            set.remove(&self.0);
+        }
+    }
+}
+
+pub fn claim(novel_title: &str, id: &str) -> Result<RunningGuard, String> {
+    let key = format!("{}/{}", novel_title, id);
+    let mut set = running().lock().unwrap_or_else(|e| e.into_inner());
+    if !set.insert(key.clone()) {
+        return Err(format!("批次 {} 正在运行", id));
+    }
+    Ok(RunningGuard(key))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchProgress {
+    pub batch_id: String,
+    pub novel_title: String,
+    pub done: usize,
+    pub total: usize,
+    /// "progress" | "waiting" | "error" | "done"
+    pub status: String,
+    pub message: String,
+    /// waiting 时按接口限额预计恢复派发的时刻（HH:MM:SS）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_at: Option<String>,
+}
+
+fn output_name(arc: Option<&StoryArc>, chapters: &[String]) -> String {
+    let stem = |name: &String| {
+        let name = name.rsplit('/').next().unwrap_or(name);
+        name.trim_end_matches(".txt").to_string()
+    };
+    let name = match (chapters.first(), chapters.last()) {
+        (Some(first), Some(last)) if chapters.len() > 1 => format!("{}-{}.md", stem(first), stem(last)),
+        (Some(first), _) => format!("{}.md", stem(first)),
+        _ => "empty.md".to_string(),
+    };
+    match arc {
+        Some(arc) => format!("{}_{}", arc.file_prefix(), name),
+        None => name,
+    }
+}
+
+/// 单组分析失败的原因：AI 调用失败时保留 [`ai::AiError`]，供重试队列判断是否值得重试
+#[derive(Debug)]
+pub enum EntryError {
+    Ai(ai::AiError),
+    Other(String),
+}
+
+impl std::fmt::Display for EntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryError::Ai(e) => write!(f, "{}", e),
+            EntryError::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl From<String> for EntryError {
+    fn from(msg: String) -> Self {
+        EntryError::Other(msg)
+    }
+}
+
+/// 分析第 i 组并保存清单。AI 调用失败时该组标记为 failed 并返回错误，之后可续跑；
+/// 可重试的失败（见 [`ai::AiError::is_retryable`]）同时进入 [`crate::ai_retry`] 队列。
+pub async fn analyze_entry(
+    workspace_root: &Path,
+    novel_dir: &Path,
+    manifest: &mut BatchManifest,
+    i: usize,
+    config: &ai::AiConfig,
+    prompt: &str,
+) -> Result<(), String> {
+    match analyze_entry_once(workspace_root, novel_dir, manifest, i, config, prompt).await {
+        Ok(()) => Ok(()),
+        Err(EntryError::Ai(e)) => {
+            if e.is_retryable() {
+                let entry = crate::ai_retry::RetryEntry::new(workspace_root, novel_dir, manifest, i, config, prompt, &e);
+                if let Err(err) = crate::ai_retry::enqueue(workspace_root, entry) {
+                    eprintln!("[Batch] 加入重试队列失败: {}", err);
+                }
+            }
+            Err(e.to_string())
+        }
+        Err(EntryError::Other(e)) => Err(e),
+    }
+}
+
+/// [`analyze_entry`] 的单次尝试，不入重试队列
+pub async fn analyze_entry_once(
+    workspace_root: &Path,
+    novel_dir: &Path,
+    manifest: &mut BatchManifest,
+    i: usize,
+    config: &ai::AiConfig,
+    prompt: &str,
+) -> Result<(), EntryError> {
+    let chapters = manifest.entries[i].chapters.clone();
+    let arc = manifest.params.arc.as_ref();
+    let base = result_dir(workspace_root, &manifest.novel_title).join(output_name(arc, &chapters));
+    let params = &manifest.params;
+    let path = match analysis_versions::plan_write(
+        workspace_root,
+        &base,
+        params.version_policy,
+        Some(&params.model),
+        Some(&params.prompt_hash),
+    ) {
+        WriteTarget::Write(path) => path,
+        WriteTarget::Skip(existing) => {
+            let entry = &mut manifest.entries[i];
+            entry.status = EntryStatus::Completed;
+            entry.skipped = true;
+            entry.output_file = crate::paths::to_relative(workspace_root, &existing);
+            entry.error = None;
+            return Ok(save(workspace_root, manifest)?);
+        }
+    };
+    let mut content = String::new();
+    let mut sources = Vec::new();
+    for file in &chapters {
+        let raw = fs::read_to_string(novel_dir.join(file)).map_err(|e| format!("读取 {} 失败: {}", file, e))?;
+        sources.push(SourceChapter::of(file, raw.as_bytes()));
+        let text = storage::strip_bom(&raw).to_string();
+        let text = match manifest.params.normalize {
+            Some(options) => text_normalize::normalize_chapter(&text, options),
+            None => text,
+        };
+        let label = file.rsplit('/').next().unwrap_or(file).trim_end_matches(".txt");
+        content.push_str(&format!("\n\n--- {} ---\n\n{}", label, text));
+    }
+    // 原文放进分隔块，提示词附加安全说明；prompt_hash 仍按用户提示词计算
+    let (prompt, mut content) = ai::analysis_messages(prompt, &content);
+    if let Some(sources) = manifest.params.context {
+        let context = ai_context::load(workspace_root, novel_dir, chapters.first().map(String::as_str), sources);
+        content = context.compose(&content, ai_context::body_budget_within(config.body_limit(), &prompt)).0;
+    }
+
+    let started = Instant::now();
+    let result = ai::call_ai_with_usage(config.clone(), prompt, content, false).await;
+    let entry = &mut manifest.entries[i];
+    entry.duration_ms = started.elapsed().as_millis() as u64;
+    let outcome = match result {
+        Ok((text, tokens)) => {
+            let arc = manifest.params.arc.as_ref();
+            let provenance = Provenance::new(&manifest.novel_title, Some(&manifest.id), sources.clone())
+                .generated_by(Some(&manifest.params.model), Some(&manifest.params.prompt_hash))
+                .in_language(manifest.params.output_language.as_deref());
+            ai::save_raw_output(workspace_root, &path, &text);
+            let body = ai::sanitize_markdown(&text);
+            let body = match arc {
+                Some(arc) => format!("# {}\n\n{}", arc.heading(), body),
+                None => body,
+            };
+            let text = provenance::with_front_matter(&provenance, &body);
+            storage::write_text(&path, &text).map_err(|e| format!("写入分析结果失败: {}", e))?;
+            entry.sources = sources;
+            entry.status = EntryStatus::Completed;
+            entry.output_file = crate::paths::to_relative(workspace_root, &path);
+            entry.tokens = tokens;
+            entry.error = None;
+            Ok(())
+        }
+        Err(e) => {
+            entry.status = EntryStatus::Failed;
+            entry.error = Some(e.to_string());
+            Err(EntryError::Ai(e))
+        }
+    };
+    save(workspace_root, manifest)?;
+    outcome
+}
+
+/// 全部分组完成后标记完成时间并汇总进分析索引
+pub fn finish(workspace_root: &Path, manifest: &mut BatchManifest) -> Result<(), String> {
+    manifest.completed_at = Some(now());
+    save(workspace_root, manifest)?;
+    record_in_index(workspace_root, manifest)
+}
+
+/// 从第一个未完成的分组开始逐组分析。某组失败时记录错误并停止，之后可续跑该批次。
+pub async fn run(
+    app: &tauri::AppHandle,
+    workspace_root: &Path,
+    novel_dir: &Path,
+    mut manifest: BatchManifest,
+    config: ai::AiConfig,
+    prompt: String,
+    _guard: RunningGuard,
+) -> Result<(), String> {
+    let total = manifest.entries.len();
+    let emit_with = |manifest: &BatchManifest, status: &str, message: String, resume_at: Option<String>| {
+        let done = manifest.entries.iter().filter(|e| e.status == EntryStatus::Completed).count();
+        crate::events::emit_and_buffer(
+            app,
+            "analysis-batch-progress",
+            BatchProgress {
+                batch_id: manifest.id.clone(),
+                novel_title: manifest.novel_title.clone(),
+                done,
+                total,
+                status: status.to_string(),
+                message,
+                resume_at,
+            },
+        );
+    };
+    let emit = |manifest: &BatchManifest, status: &str, message: String| emit_with(manifest, status, message, None);
+
+    while let Some(i) = manifest.next_pending() {
+        ai_limits::pace(&config, |wait, reason| {
+            let resume_at = ai_limits::resume_time(wait);
+            emit_with(&manifest, "waiting", format!("AI 接口{}，{} 恢复派发", reason, resume_at), Some(resume_at));
+        })
+        .await;
+        let chapters = manifest.entries[i].chapters.join(", ");
+        emit(&manifest, "progress", format!("分析第 {}/{} 组: {}", i + 1, total, chapters));
+        if let Err(e) = analyze_entry(workspace_root, novel_dir, &mut manifest, i, &config, &prompt).await {
+            emit(&manifest, "error", format!("批次 {} 中断: {}，可稍后续跑", manifest.id, e));
+            return Err(e);
+        }
+    }
+
+    finish(workspace_root, &mut manifest)?;
+    emit(
+        &manifest,
+        "done",
+        format!("批次 {} 完成，共 {} tokens，耗时 {} 秒", manifest.id, manifest.total_tokens(), manifest.duration_ms() / 1000),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(prompt: &str, group_size: usize) -> BatchParams {
+        BatchParams {
+            prompt_hash: prompt_hash(prompt),
+            model: "m".to_string(),
+            group_size,
+            chapters: (1..=5).map(|i| format!("{:02}.txt", i)).collect(),
+            context: None,
+            normalize: None,
+            arc: None,
+            version_policy: None,
+            output_language: None,
+        }
+    }
+
+    fn temp_root(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_batch_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn groups_chapters_and_finds_first_unfinished() {
+        let mut m = BatchManifest::new("1".to_string(), "书", params("p", 2));
+        assert_eq!(m.entries.len(), 3);
+        assert_eq!(m.entries[2].chapters, vec!["05.txt"]);
+        m.entries[0].status = EntryStatus::Completed;
+        m.entries[1].status = EntryStatus::Failed;
+        assert_eq!(m.next_pending(), Some(1));
+        assert_eq!(output_name(None, &m.entries[0].chapters), "01-02.md");
+        assert_eq!(output_name(None, &m.entries[2].chapters), "05.md");
+        let arc = StoryArc { name: "开篇".to_string(), start_index: 1, end_index: 15 };
+        assert_eq!(output_name(Some(&arc), &m.entries[0].chapters), "开篇_01-02.md");
+
+        m.push_chapter("06.txt".to_string());
+        assert_eq!(m.params.chapters.len(), 6);
+        assert_eq!(m.entries.last().unwrap().chapters, vec!["06.txt"]);
+    }
+
+    #[test]
+    fn resume_requires_matching_params() {
+        let root = temp_root("resume");
+        let first = prepare(&root, "书", params("p", 2), None, false).unwrap();
+
+        let resumed = prepare(&root, "书", params("p", 2), Some(&first.id), false).unwrap();
+        assert_eq!(resumed.id, first.id);
+
+        let err = prepare(&root, "书", params("q", 3), Some(&first.id), false).unwrap_err();
+        assert!(err.contains("提示词") && err.contains("分组大小"), "{}", err);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let forced = prepare(&root, "书", params("q", 3), Some(&first.id), true).unwrap();
+        assert_ne!(forced.id, first.id);
+
+        let batches = list_batches(&root, "书");
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].id, forced.id);
+        assert!(batches.iter().all(|b| b.resumable && b.completed == 0));
+        assert!(prepare(&root, "书", params("p", 2), Some("../x"), false).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn completed_batch_rolls_into_index() {
+        let root = temp_root("index");
+        let mut m = BatchManifest::new("7".to_string(), "书", params("p", 5));
+        m.entries[0].status = EntryStatus::Completed;
+        m.entries[0].tokens = Some(1200);
+        m.entries[0].duration_ms = 3000;
+        m.completed_at = Some(now());
+        record_in_index(&root, &m).unwrap();
+        record_in_index(&root, &m).unwrap();
+
+        let index: Vec<IndexEntry> =
+            serde_json::from_str(&fs::read_to_string(root.join(RESULT_DIR).join(INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!((index[0].total_tokens, index[0].duration_ms, index[0].chapters), (1200, 3000, 5));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -4,9 +4,12 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use chrono::Local;
-use tauri::{Emitter, Manager};
+use tauri::Manager;
 use tokio::sync::Semaphore;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
+
+use crate::events::EventSink;
+use crate::spiders::{LiveSource, NovelSource, RankEntry, RankScan};
 
 /// 流水线模式：榜单批量 vs 单本拆解。
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -19,7 +22,7 @@ pub enum PipelineMode {
 #[derive(Serialize, Clone)]
 pub struct PipelineProgress {
     pub phase: u8,                          // 1=Producer, 2=Fetch, 3=AI Outline, 4=Multi-Agent
-    pub status: String,                     // "started" | "completed" | "failed"
+    pub status: String,                     // "started" | "completed" | "failed" | "access"（Phase 1 末尾的访问探测）
     pub message: String,
     pub progress: Option<(usize, usize)>,   // (done, total)
 }
@@ -31,7 +34,8 @@ fn emit_pipeline_progress(
     message: impl Into<String>,
     progress: Option<(usize, usize)>,
 ) {
-    let _ = app.emit(
+    crate::events::emit_and_buffer(
+        app,
         "pipeline-progress",
         PipelineProgress {
             phase,
@@ -48,6 +52,11 @@ pub struct NovelRankInfo {
     pub title: String,
     pub url: String,
     pub rank: usize,
+    /// 榜单上显示的月票 / 热度值
+    #[serde(default)]
+    pub score: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
     pub last_rank: Option<usize>,
     pub rank_change: i32,
     pub is_new: bool,
@@ -75,6 +84,20 @@ impl HistoryManager {
         fs::write(file_path, content).map_err(|e| e.to_string())
     }
 
+    /// 保存本次扫榜的原始榜单（含名次和分值），覆盖上一次的 rank_report.json。
+    /// source 记录榜单来自接口还是页面解析，便于定位哪条路径失效。
+    pub fn save_rank_report(&self, rank_url: &str, scan: &RankScan) -> Result<(), String> {
+        let report = serde_json::json!({
+            "rank_url": rank_url,
+            "generated_at": Local::now().to_rfc3339(),
+            "source": scan.source,
+            "entries": scan.entries,
+        });
+        let content = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        crate::storage::write_atomic(&self.base_dir.join("rank_report.json"), content.as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
     pub fn load_snapshot(&self, date_str: &str) -> Option<Vec<NovelRankInfo>> {
         let file_path = self.base_dir.join(format!("snapshot_{}.json", date_str));
         if let Ok(content) = fs::read_to_string(file_path) {
@@ -91,7 +114,7 @@ impl HistoryManager {
     }
 }
 
-pub fn calculate_velocity(current_list: &mut Vec<NovelRankInfo>, last_list: Option<Vec<NovelRankInfo>>) {
+pub fn calculate_velocity(current_list: &mut [NovelRankInfo], last_list: Option<Vec<NovelRankInfo>>) {
     let last_map: HashMap<String, usize> = last_list
         .map(|list| list.into_iter().map(|n| (n.book_id, n.rank)).collect())
         .unwrap_or_default();
@@ -109,53 +132,42 @@ pub fn calculate_velocity(current_list: &mut Vec<NovelRankInfo>, last_list: Opti
     }
 }
 
-// ---------- AI 提纯提示词：输出 JSON 细纲 ----------
-const OUTLINE_ANALYSIS_PROMPT: &str = r#"你是一个专业网文拆解助手。
-请将以下小说章节内容拆解为细纲，严格按照原文叙事顺序。
-
-对于每个关键情节节点，输出一个 JSON 对象数组：
-
-[
-  {
-    "event": "剧情概括（一句话描述该节点发生的事件）",
-    "purpose": "写作目的（如：制造冲突、拉高期待、压抑情绪、展示金手指、打脸爽点、埋下伏笔、转换地图、引入新角色等）",
-    "emotion": "该段落的主要情绪基调（如：紧张、兴奋、压抑、爽快、悬疑、温馨、热血等）",
-    "highlight": "核心看点/吸引点（读者为什么会被这段吸引）"
-  }
-]
-
-要求：
-1. 严格按原文顺序
-2. 每个节点 1-3 句话
-3. 只输出 JSON 数组，不要任何额外文字
-4. 如果无法拆解请输出 []"#;
-
-const MAX_CONCURRENCY: usize = 3;
 const TARGET_CHAPTERS: usize = 3;
+/// 扫榜时相邻两本书元数据请求的间隔，避免连续打开浏览器蜘蛛触发 WAF
+const NOVEL_INTERVAL: Duration = Duration::from_millis(500);
+/// 一次扫榜中最多因平台熔断暂停几次，超过后结束扫榜
+const MAX_CIRCUIT_PAUSES: usize = 3;
 
 // ========================================================================
-//  Phase 1: Producer — 扫榜分发, 只取 book_id + 书名 + URL
+//  Phase 1: Producer — 扫榜分发, 只取 book_id + 书名 + URL（附带榜单名次）
 // ========================================================================
-async fn producer_scan_rank(
-    app: &tauri::AppHandle,
-    rank_url: &str,
-    platform: &str,
-) -> Result<Vec<(i64, String, String, String)>, String> {
-    eprintln!("[Producer] 扫榜: {}", rank_url);
-
-    let novel_links = match platform {
-        "qidian" => crate::spiders::qidian::fetch_rank_list(app, rank_url, false).await?,
-        "fanqie" => return Err("番茄榜单暂未实现".to_string()),
-        _ => return Err("不支持的平台".to_string()),
-    };
+type ProducedBook = (i64, String, String, String);
 
+/// 抓取榜单，最多取 PIPELINE_MAX_BOOKS（默认 30）本
+pub(crate) async fn fetch_rank<S: NovelSource>(spider: &S, rank_url: &str, platform: &str) -> Result<RankScan, String> {
     let max_books = std::env::var("PIPELINE_MAX_BOOKS").ok()
         .and_then(|s| s.parse::<usize>().ok())
         .unwrap_or(30);
-    let limit = std::cmp::min(novel_links.len(), max_books);
-    if limit == 0 {
+    let RankScan { mut entries, source } = spider.fetch_rank_list(platform, rank_url, max_books).await?;
+    eprintln!("[Producer] 榜单来源: {:?}，{} 本", source, entries.len());
+    entries.truncate(max_books);
+    if entries.is_empty() {
         return Err("榜单中没有找到小说".to_string());
     }
+    Ok(RankScan { entries, source })
+}
+
+/// 扫榜并逐本抓取书籍信息写入 novels 表。榜单和书籍信息经 spider 抓取，等待状态经 events 推送
+async fn producer_scan_rank<E: EventSink + Sync + ?Sized, S: NovelSource>(
+    events: &E,
+    spider: &S,
+    rank_url: &str,
+    platform: &str,
+) -> Result<(Vec<ProducedBook>, RankScan), String> {
+    eprintln!("[Producer] 扫榜: {}", rank_url);
+
+    let RankScan { entries, source } = fetch_rank(spider, rank_url, platform).await?;
+    let limit = entries.len();
 
     // 扫榜成功后创建报告（避免空报告）
     let db_conn = crate::db::get_conn().ok();
@@ -167,38 +179,60 @@ async fn producer_scan_rank(
         }
     }
 
-    let client = reqwest::Client::new();
     let mut results = Vec::new();
+    let mut waiter = crate::progress::WaitReporter::new(format!("扫榜 {}", rank_url));
 
-    for (idx, url) in novel_links.iter().enumerate().take(limit) {
-        let book_id = url.split("/book/")
-            .last()
-            .unwrap_or(url)
-            .trim_end_matches('/')
-            .to_string();
+    let mut circuit_pauses = 0;
+
+    'books: for (idx, entry) in entries.iter().enumerate() {
+        let url = &entry.url;
+        if idx > 0 {
+            waiter.sleep(events, NOVEL_INTERVAL, "书籍间隔").await;
+        }
+        // 起点 /book/<id>/，番茄 /page/<id>
+        let book_id = url.split('/').rfind(|s| !s.is_empty()).unwrap_or(url).to_string();
 
         let (title, author, tags) = match platform {
-            "qidian" => {
-                match crate::spiders::qidian::fetch_novel_metadata(&client, url, app, false).await {
-                    Ok(meta) => (meta.title.clone(), "未知".to_string(), meta.tags.join(",")),
+            "qidian" => loop {
+                // 平台熔断时整批暂停等冷却，而不是让剩下的书逐本快速失败
+                while let Some(wait) = crate::spiders::circuit::cooldown_remaining(platform) {
+                    if circuit_pauses >= MAX_CIRCUIT_PAUSES {
+                        eprintln!("[Producer] {} 已熔断 {} 次，停止扫榜，保留已扫到的 {} 本", platform, circuit_pauses, results.len());
+                        break 'books;
+                    }
+                    circuit_pauses += 1;
+                    waiter.sleep(events, wait, "平台熔断冷却").await;
+                }
+                match spider.fetch_metadata(platform, url, false).await {
+                    Ok(meta) => break (meta.title.clone(), fallback_author(entry), meta.tags.join(",")),
+                    Err(e) if crate::spiders::circuit::cooldown_remaining(platform).is_some() => {
+                        eprintln!("[Producer] 平台熔断，冷却后重试 [{}]: {}", url, e);
+                    }
                     Err(e) => {
                         eprintln!("[Producer] 获取元数据失败 [{}]: {}", url, e);
-                        (format!("未知书籍-{}", idx + 1), "未知".to_string(), String::new())
+                        break (fallback_title(entry), fallback_author(entry), String::new());
                     }
                 }
-            }
-            _ => (format!("未知书籍-{}", idx + 1), "未知".to_string(), String::new()),
+            },
+            "fanqie" => match spider.fetch_metadata(platform, url, false).await {
+                Ok(meta) => (meta.title.clone(), fallback_author(entry), meta.tags.join(",")),
+                Err(e) => {
+                    eprintln!("[Producer] 获取元数据失败 [{}]: {}", url, e);
+                    (fallback_title(entry), fallback_author(entry), String::new())
+                }
+            },
+            _ => (fallback_title(entry), fallback_author(entry), String::new()),
         };
 
         if let Some(ref conn) = db_conn {
             match crate::db::upsert_novel(conn, &book_id, platform, &title, &author, &tags, 0) {
                 Ok(nid) => {
                     if let Some(rid) = report_id_opt {
-                        let change_str = format!("+{}", idx + 1);
-                        let _ = crate::db::insert_rank_history(conn, rid, nid, (idx + 1) as i64, &change_str);
+                        let change_str = format!("+{}", entry.position);
+                        let _ = crate::db::insert_rank_history(conn, rid, nid, entry.position as i64, &change_str);
                     }
                     results.push((nid, book_id.clone(), title.clone(), url.clone()));
-                    eprintln!("[Producer] #{}/{} id={} title={}", idx + 1, limit, book_id, title);
+                    eprintln!("[Producer] #{}/{} id={} title={}", entry.position, limit, book_id, title);
                 }
                 Err(e) => eprintln!("[Producer] DB 写入失败: {}", e),
             }
@@ -206,7 +240,20 @@ async fn producer_scan_rank(
     }
 
     eprintln!("[Producer] 完成: 扫到 {} 本书", results.len());
-    Ok(results)
+    Ok((results, RankScan { entries, source }))
+}
+
+/// 元数据抓取失败时用榜单上的书名，榜单也没有时才用占位名。
+pub(crate) fn fallback_title(entry: &RankEntry) -> String {
+    if entry.title.is_empty() {
+        format!("未知书籍-{}", entry.position)
+    } else {
+        entry.title.clone()
+    }
+}
+
+pub(crate) fn fallback_author(entry: &RankEntry) -> String {
+    entry.author.clone().unwrap_or_else(|| "未知".to_string())
 }
 
 // ========================================================================
@@ -221,8 +268,7 @@ async fn producer_single_book(
 
     let book_id = novel_url
         .split('/')
-        .filter(|s| !s.is_empty())
-        .last()
+        .rfind(|s| !s.is_empty())
         .unwrap_or(novel_url)
         .to_string();
 
@@ -261,22 +307,34 @@ async fn run_fetch_workers(
         return Ok((0, 0));
     }
 
-    eprintln!("[Fetch Worker] {} 本待抓取, Semaphore({}) 并发", books.len(), MAX_CONCURRENCY);
+    eprintln!("[Fetch Worker] {} 本待抓取, Semaphore({}) 并发", books.len(), crate::limits::current().pipeline_workers);
 
-    let download_dir = workspace_root.join("downloads");
+    let download_dir = crate::library::downloads_dir(workspace_root);
     let mut handles = Vec::new();
 
     for (novel_id, title, novel_url) in books {
+        let existing_dir = crate::library::novel_dir_in(&download_dir, &title);
+        if crate::library::is_unrecognized(&existing_dir) {
+            eprintln!("[Fetch Worker] 同名位置是未识别项目，跳过: {}", title);
+            continue;
+        }
+        if crate::novel_info::is_archived(&existing_dir) {
+            eprintln!("[Fetch Worker] 已归档，跳过: {}", title);
+            continue;
+        }
+        if crate::source_check::is_removed(&existing_dir) {
+            eprintln!("[Fetch Worker] 来源已下架，跳过: {}", title);
+            continue;
+        }
         let permit = semaphore.clone().acquire_owned().await.map_err(|e| e.to_string())?;
         let app = app.clone();
         let d_dir = download_dir.clone();
         let plat = platform.to_string();
 
-        handles.push(tokio::spawn(async move {
+        handles.push(tokio::spawn(crate::priority::scope(crate::priority::current(), async move {
             let _permit = permit;
-            let safe_title = title.replace("/", "_").replace("\\", "_");
-            let novel_dir = d_dir.join(&safe_title);
-            let _ = fs::create_dir_all(&novel_dir);
+            let novel_dir = crate::library::novel_dir_in(&d_dir, &title);
+            let _ = crate::storage::create_dir_all(&novel_dir);
 
             let chapters = match plat.as_str() {
                 "qidian" => crate::spiders::qidian::fetch_chapter_list(&app, &novel_url, false).await,
@@ -293,33 +351,60 @@ async fn run_fetch_workers(
 
             let mut success = 0usize;
             let mut fail = 0usize;
+            let mut waiter = crate::progress::WaitReporter::new(title.clone());
             let target = std::cmp::min(chapters.len(), TARGET_CHAPTERS);
+            let mut index_file = crate::chapter_index::ChapterIndex::load(&novel_dir);
+            let catalog_synced = index_file.sync(|index| {
+                index.update_catalog(chapters.iter().take(target).enumerate().map(|(i, (t, u))| {
+                    crate::chapter_index::ChapterRecord { index: i + 1, title: t.clone(), url: u.clone(), ..Default::default() }
+                }));
+                Ok(())
+            });
+            if let Err(e) = catalog_synced {
+                eprintln!("[Fetch Worker] {}", e);
+            }
 
             for i in 0..target {
                 if i >= chapters.len() { break; }
                 let (ch_title, ch_url) = &chapters[i];
-                let filename = format!("{:02}.txt", i + 1);
-                let file_path = novel_dir.join(&filename);
+                let file_path = index_file.chapter_path(i + 1);
 
-                if file_path.exists() {
-                    success += 1;
-                    continue;
+                match index_file.sync(|index| Ok(index.check(i + 1))) {
+                    Ok(check) if check.can_skip() => {
+                        success += 1;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("[Fetch Worker] {}", e),
                 }
 
                 let download = match plat.as_str() {
-                    "qidian" => crate::spiders::qidian::download_chapter(&app, ch_url, false).await,
+                    "qidian" => crate::spiders::qidian::download_chapter(&app, ch_url, false).await.map_err(String::from),
                     _ => Err("不支持的平台".to_string()),
                 };
 
                 match download {
                     Ok((_, content)) => {
-                        let full = format!("标题: {}\n链接: {}\n{}\n\n{}", ch_title, ch_url, "=".repeat(50), content);
-                        let _ = fs::write(&file_path, &full);
-
-                        if let Ok(conn) = crate::db::get_conn() {
-                            let _ = crate::db::upsert_chapter(&conn, novel_id, (i + 1) as i64, ch_title, &content, None);
+                        let full = crate::library::render_chapter_file(ch_title, ch_url, &content);
+                        let full = crate::storage::encode_text(&file_path, &full);
+                        let written = index_file.sync(|index| {
+                            file_path
+                                .parent()
+                                .map_or(Ok(()), crate::storage::create_dir_all)
+                                .and_then(|()| crate::storage::write_atomic(&file_path, &full))
+                                .map_err(|e| e.to_string())?;
+                            index.mark_downloaded(i + 1, &full);
+                            Ok(())
+                        });
+                        if let Err(e) = written {
+                            eprintln!("[Fetch Worker] 写入章节失败 {}: {}", ch_title, e);
+                            fail += 1;
+                        } else {
+                            if let Ok(conn) = crate::db::get_conn() {
+                                let _ = crate::db::upsert_chapter(&conn, novel_id, (i + 1) as i64, ch_title, &content, None);
+                            }
+                            success += 1;
                         }
-                        success += 1;
                     }
                     Err(e) => {
                         eprintln!("[Fetch Worker] 下载章节失败 {}: {}", ch_title, e);
@@ -327,12 +412,12 @@ async fn run_fetch_workers(
                     }
                 }
 
-                sleep(Duration::from_millis(200)).await;
+                waiter.sleep(&app, Duration::from_millis(200), "章节间隔").await;
             }
 
             eprintln!("[Fetch Worker] {} 完成: 成功{} 失败{}", title, success, fail);
             (success, fail)
-        }));
+        })));
     }
 
     let mut total_ok = 0usize;
@@ -375,9 +460,10 @@ async fn run_ai_workers(
         return Ok(0);
     }
 
-    eprintln!("[AI Worker] {} 章节待提纯, Semaphore({}) 并发", pending.len(), MAX_CONCURRENCY);
+    eprintln!("[AI Worker] {} 章节待提纯, Semaphore({}) 并发", pending.len(), crate::limits::current().pipeline_workers);
 
-    let prompt = OUTLINE_ANALYSIS_PROMPT.to_string();
+    // AI 提纯提示词：输出 JSON 细纲
+    let prompt = crate::resources::text("prompt.pipeline_outline");
     let mut handles = Vec::new();
 
     for (ch_id, title, content) in pending {
@@ -385,7 +471,7 @@ async fn run_ai_workers(
         let config = ai_config.clone();
         let prompt = prompt.clone();
 
-        handles.push(tokio::spawn(async move {
+        handles.push(tokio::spawn(crate::priority::scope(crate::priority::current(), async move {
             let _permit = permit;
 
             // 按 char 边界安全截断中文内容
@@ -399,6 +485,11 @@ async fn run_ai_workers(
                 content
             };
 
+            crate::ai_limits::pace(&config, |wait, reason| {
+                eprintln!("[AI Worker] {}，{} 秒后派发 {}", reason, wait.as_secs(), title);
+            })
+            .await;
+            let (prompt, truncated) = crate::ai::analysis_messages(&prompt, &truncated);
             match crate::ai::call_ai(config, prompt, truncated, true).await {
                 Ok(json_str) => {
                     match serde_json::from_str::<serde_json::Value>(&json_str) {
@@ -423,7 +514,7 @@ async fn run_ai_workers(
                     0usize
                 }
             }
-        }));
+        })));
     }
 
     let mut total = 0usize;
@@ -451,7 +542,7 @@ async fn run_multi_agent_phase(
     eprintln!(
         "[Multi-Agent] {} 本书待评估, Semaphore({}) 并发",
         books.len(),
-        MAX_CONCURRENCY
+        crate::limits::current().pipeline_workers
     );
 
     let mut handles = Vec::new();
@@ -462,7 +553,7 @@ async fn run_multi_agent_phase(
         let title = title.clone();
         let cfg = ai_config.clone();
 
-        handles.push(tokio::spawn(async move {
+        handles.push(tokio::spawn(crate::priority::scope(crate::priority::current(), async move {
             let _permit = permit;
 
             // 取 outline_blob + tags
@@ -515,7 +606,7 @@ async fn run_multi_agent_phase(
                     false
                 }
             }
-        }));
+        })));
     }
 
     let mut ok = 0usize;
@@ -613,6 +704,31 @@ async fn generate_report(rank_url: &str) -> Result<String, String> {
     Ok(report)
 }
 
+/// 探测榜单第一本书的一章（见 [`crate::access_probe`]），结果经 pipeline-progress 和 access-probe 事件推送。
+/// 目录抓取失败时返回 None，交给 Phase 2 照常处理
+async fn probe_first_book(
+    app: &tauri::AppHandle,
+    workspace_root: &Path,
+    platform: &str,
+    books: &[ProducedBook],
+) -> Option<crate::access_probe::AccessProbe> {
+    let (_, _, title, url) = books.first()?;
+    let source = LiveSource::new(app);
+    let catalog = match crate::download::get_catalog_cached(app, &source, workspace_root, url, platform, false, || {}).await {
+        Ok((catalog, _)) => catalog,
+        Err(e) => {
+            eprintln!("[Producer] 探测《{}》的访问状态失败: {}", title, e);
+            return None;
+        }
+    };
+    let min_chars = crate::settings::load(workspace_root).min_chapter_chars(platform);
+    let probe = crate::access_probe::probe(&source, platform, &catalog, &[], min_chars).await;
+    eprintln!("[Producer] 访问探测: {}", probe.summary());
+    emit_pipeline_progress(app, 1, "access", probe.summary(), None);
+    crate::events::emit_and_buffer(app, crate::access_probe::ACCESS_EVENT, &probe);
+    Some(probe)
+}
+
 // ========================================================================
 //  核心公开 API — 三段式管线 + 全局 AI 配置
 // ========================================================================
@@ -630,10 +746,10 @@ pub async fn run_full_analysis_pipeline(
     let ai_config = {
         let state = app.state::<crate::ai::GlobalAiConfig>();
         let guard = state.0.lock().map_err(|e| format!("获取 AI 配置失败: {}", e))?;
-        guard.clone().ok_or_else(|| "AI 配置未设置，请在设置中配置 API Key".to_string())?
+        guard.clone().ok_or_else(|| crate::resources::text("message.ai_not_configured"))?
     };
     let db_conn = crate::db::get_conn().ok();
-    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENCY));
+    let semaphore = Arc::new(Semaphore::new(crate::limits::current().pipeline_workers));
 
     // ------ Phase 1: Producer ------
     eprintln!("[Pipeline 1/4] Producer...");
@@ -643,15 +759,15 @@ pub async fn run_full_analysis_pipeline(
     }, None);
 
     let books = match mode {
-        PipelineMode::Rank => producer_scan_rank(app, target_url, platform).await,
-        PipelineMode::Single => producer_single_book(app, target_url, platform).await,
+        PipelineMode::Rank => producer_scan_rank(app, &LiveSource::new(app), target_url, platform).await.map(|(b, scan)| (b, Some(scan))),
+        PipelineMode::Single => producer_single_book(app, target_url, platform).await.map(|b| (b, None)),
     };
-    let books = match books {
-        Ok(b) if !b.is_empty() => {
+    let (books, rank_scan) = match books {
+        Ok((b, scan)) if !b.is_empty() => {
             emit_pipeline_progress(app, 1, "completed",
                 format!("Phase 1 完成：{} 本", b.len()),
                 Some((b.len(), b.len())));
-            b
+            (b, scan)
         }
         Ok(_) => {
             emit_pipeline_progress(app, 1, "failed", "Producer 未扫到有效书籍".to_string(), None);
@@ -663,6 +779,21 @@ pub async fn run_full_analysis_pipeline(
         }
     };
 
+    // 大批抓取前先探测第一本书的一章，需要登录时就此停下，不让整批章节逐一失败
+    let access = match mode {
+        PipelineMode::Rank => probe_first_book(app, workspace_root, platform, &books).await,
+        PipelineMode::Single => None,
+    };
+    if let Some(probe) = access.as_ref().filter(|p| p.access == crate::access_probe::Access::LoginRequired) {
+        if let Some(scan) = rank_scan.as_ref() {
+            if let Err(e) = crate::rank_snapshots::save(workspace_root, target_url, scan, Some(probe)) {
+                eprintln!("[Pipeline] 保存榜单快照失败: {}", e);
+            }
+        }
+        emit_pipeline_progress(app, 2, "failed", probe.summary(), None);
+        return Err(probe.summary());
+    }
+
     // ------ Phase 2: Fetch Workers (Semaphore=3) ------
     eprintln!("[Pipeline 2/4] Fetch Workers...");
     emit_pipeline_progress(app, 2, "started",
@@ -717,12 +848,24 @@ pub async fn run_full_analysis_pipeline(
     {
         let history = HistoryManager::new(workspace_root);
         let last = history.load_snapshot(&history.get_yesterday_date());
+        let rank_entries = rank_scan.as_ref().map(|s| s.entries.as_slice()).unwrap_or_default();
+        if let Some(scan) = rank_scan.as_ref().filter(|s| !s.entries.is_empty()) {
+            if let Err(e) = history.save_rank_report(target_url, scan) {
+                eprintln!("[Pipeline] 保存 rank_report.json 失败: {}", e);
+            }
+            if let Err(e) = crate::rank_snapshots::save(workspace_root, target_url, scan, access.as_ref()) {
+                eprintln!("[Pipeline] 保存榜单快照失败: {}", e);
+            }
+        }
         let mut current: Vec<NovelRankInfo> = books.iter().map(|(_, bid, title, url)| {
+            let entry = rank_entries.iter().find(|e| &e.url == url);
             NovelRankInfo {
                 book_id: bid.clone(),
                 title: title.clone(),
                 url: url.clone(),
-                rank: 0,
+                rank: entry.map(|e| e.position).unwrap_or(0),
+                score: entry.and_then(|e| e.score.clone()),
+                author: entry.and_then(|e| e.author.clone()),
                 last_rank: None,
                 rank_change: 0,
                 is_new: false,
@@ -0,0 +1,231 @@
+//! 导入应用外生成的分析结果（协作者发来的细纲等），与本应用的结果放在同一个 `result/<书名>/` 下。
+//!
+//! 内容须为 UTF-8 文本，经 [`ai::sanitize_markdown`] 清理后写成 `N.md`（已有结果时另存为新版本，不覆盖），
+//! front matter 记录外部来源和导入时间。章节文件存在时同样记录来源哈希，并以 external 条目记入分析索引：
+//! 计入分析覆盖率和报告，不计入 token 用量。
+//!
+//! 整个目录导入时按文件名中的第一段数字推断章节序号（`05.md`、`第12章细纲.md`），先 dry_run 查看对应关系，
+//! 推断不出或序号重复的文件不导入。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::analysis_versions::{self, VersionPolicy, WriteTarget};
+use crate::provenance::{self, AnalysisOutput, Provenance, SourceChapter};
+use crate::{ai, analysis_batch, library, paths, sharding, storage};
+
+/// 单个导入文件的大小上限
+pub const MAX_IMPORT_BYTES: usize = 2 * 1024 * 1024;
+/// 目录导入时识别的扩展名
+const IMPORT_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ImportedAnalysis {
+    pub chapter_index: usize,
+    /// 写入的结果文件，相对工作区
+    pub output_file: String,
+    /// 是否记录了来源章节（章节文件不存在时不记录，也不计入覆盖率）
+    pub tracked: bool,
+}
+
+/// 目录中一个文件推断出的章节
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FileMapping {
+    pub file: String,
+    pub chapter_index: Option<usize>,
+    /// 不导入的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ImportFailure {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct DirectoryImport {
+    pub dry_run: bool,
+    pub mappings: Vec<FileMapping>,
+    pub imported: Vec<ImportedAnalysis>,
+    pub failed: Vec<ImportFailure>,
+}
+
+/// 校验导入内容是文本：UTF-8、没有 NUL 和成段的控制字符、不为空、不超过大小上限
+pub fn validate_text(bytes: &[u8]) -> Result<String, String> {
+    if bytes.len() > MAX_IMPORT_BYTES {
+        return Err(format!("内容超过 {} MB", MAX_IMPORT_BYTES / 1024 / 1024));
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| "内容不是 UTF-8 文本".to_string())?;
+    let text = text.strip_prefix(storage::BOM).unwrap_or(text);
+    let control = text.chars().filter(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')).count();
+    if text.contains('\0') || control * 100 > text.chars().count() {
+        return Err("内容看起来是二进制文件".to_string());
+    }
+    if text.trim().is_empty() {
+        return Err("内容为空".to_string());
+    }
+    Ok(text.to_string())
+}
+
+fn novel_dir(workspace_root: &Path, novel_title: &str) -> Result<std::path::PathBuf, String> {
+    let dir = library::downloads_dir(workspace_root).join(novel_title);
+    if dir.is_dir() { Ok(dir) } else { Err(format!("书库中没有《{}》", novel_title)) }
+}
+
+/// 导入一章的结果
+pub fn import_text(
+    workspace_root: &Path,
+    novel_title: &str,
+    chapter_index: usize,
+    text: &str,
+    source_label: &str,
+) -> Result<ImportedAnalysis, String> {
+    if chapter_index == 0 {
+        return Err("章节序号从 1 开始".to_string());
+    }
+    let source_dir = novel_dir(workspace_root, novel_title)?;
+    let result_dir = analysis_batch::result_dir(workspace_root, novel_title);
+    let base = result_dir.join(format!("{}.md", chapter_index));
+    let path = match analysis_versions::plan_write(workspace_root, &base, Some(VersionPolicy::KeepVersions), None, None) {
+        WriteTarget::Write(path) => path,
+        WriteTarget::Skip(existing) => existing,
+    };
+    let chapter_file = sharding::relative_chapter_path(&source_dir, chapter_index);
+    let source = fs::read(source_dir.join(&chapter_file)).ok().map(|bytes| SourceChapter::of(&chapter_file, &bytes));
+    let record = Provenance::new(novel_title, Some(provenance::EXTERNAL_BATCH_ID), source.iter().cloned().collect())
+        .imported_from(source_label);
+    let content = provenance::with_front_matter(&record, &ai::sanitize_markdown(text));
+    storage::create_dir_all(&result_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    storage::write_text(&path, &content).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+
+    let output_file = paths::to_relative(workspace_root, &path).unwrap_or_else(|| path.display().to_string());
+    let output = AnalysisOutput { output_file: output_file.clone(), sources: source.into_iter().collect(), output_language: None };
+    let tracked = !output.sources.is_empty();
+    provenance::record_import(workspace_root, novel_title, output)?;
+    Ok(ImportedAnalysis { chapter_index, output_file, tracked })
+}
+
+/// 文件名中的第一段数字作为章节序号
+fn chapter_in_name(name: &str) -> Option<usize> {
+    let stem = Path::new(name).file_stem()?.to_str()?;
+    let start = stem.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = stem[start..].chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok().filter(|&n| n > 0)
+}
+
+/// 目录下各文件推断出的章节，按文件名排序
+pub fn plan_directory(dir: &Path) -> Result<Vec<FileMapping>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("读取目录 {} 失败: {}", dir.display(), e))?;
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| {
+            let ext = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+            IMPORT_EXTENSIONS.contains(&ext.as_str())
+        })
+        .collect();
+    names.sort();
+    let chapters: Vec<Option<usize>> = names.iter().map(|name| chapter_in_name(name)).collect();
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for chapter in chapters.iter().flatten() {
+        *counts.entry(*chapter).or_default() += 1;
+    }
+    Ok(names
+        .into_iter()
+        .zip(chapters)
+        .map(|(file, chapter_index)| {
+            let skipped = match chapter_index {
+                None => Some("文件名中没有章节序号".to_string()),
+                Some(n) if counts[&n] > 1 => Some(format!("多个文件对应第 {} 章", n)),
+                Some(_) => None,
+            };
+            FileMapping { file, chapter_index, skipped }
+        })
+        .collect())
+}
+
+/// 导入目录下的结果文件；dry_run 时只返回推断的对应关系
+pub fn import_directory(
+    workspace_root: &Path,
+    novel_title: &str,
+    dir: &Path,
+    source_label: &str,
+    dry_run: bool,
+) -> Result<DirectoryImport, String> {
+    novel_dir(workspace_root, novel_title)?;
+    let mappings = plan_directory(dir)?;
+    let mut report = DirectoryImport { dry_run, ..Default::default() };
+    if !dry_run {
+        for mapping in mappings.iter().filter(|m| m.skipped.is_none()) {
+            let chapter_index = mapping.chapter_index.expect("未跳过的文件都有章节序号");
+            let imported = fs::read(dir.join(&mapping.file))
+                .map_err(|e| format!("读取失败: {}", e))
+                .and_then(|bytes| validate_text(&bytes))
+                .and_then(|text| import_text(workspace_root, novel_title, chapter_index, &text, source_label));
+            match imported {
+                Ok(imported) => report.imported.push(imported),
+                Err(error) => report.failed.push(ImportFailure { file: mapping.file.clone(), error }),
+            }
+        }
+    }
+    report.mappings = mappings;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_are_versioned_indexed_as_external_and_counted_for_coverage() {
+        let root = std::env::temp_dir().join(format!("test_analysis_import_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let novel_dir = library::downloads_dir(&root).join("书");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::write(novel_dir.join("01.txt"), "第1章\n\n正文").unwrap();
+        fs::write(novel_dir.join("02.txt"), "第2章\n\n正文").unwrap();
+        let incoming = root.join("incoming");
+        fs::create_dir_all(&incoming).unwrap();
+        fs::write(incoming.join("第1章细纲.md"), "# 细纲\n<script>x</script>").unwrap();
+        fs::write(incoming.join("02.md"), "### 1. [开场]").unwrap();
+        fs::write(incoming.join("002_copy.md"), "重复").unwrap();
+        fs::write(incoming.join("notes.md"), "无序号").unwrap();
+        fs::write(incoming.join("cover.png"), [0u8, 1, 2]).unwrap();
+
+        let preview = import_directory(&root, "书", &incoming, "小组", true).unwrap();
+        assert!(preview.imported.is_empty() && !root.join("result").exists());
+        let planned: Vec<_> = preview.mappings.iter().map(|m| (m.file.as_str(), m.chapter_index, m.skipped.is_some())).collect();
+        assert_eq!(
+            planned,
+            vec![("002_copy.md", Some(2), true), ("02.md", Some(2), true), ("notes.md", None, true), ("第1章细纲.md", Some(1), false)]
+        );
+
+        let report = import_directory(&root, "书", &incoming, "小组", false).unwrap();
+        assert_eq!(report.imported, vec![ImportedAnalysis { chapter_index: 1, output_file: "result/书/1.md".into(), tracked: true }]);
+        let text = fs::read_to_string(root.join("result/书/1.md")).unwrap();
+        assert!(text.contains("external_source: 小组") && text.contains("imported_at: "));
+        assert!(text.contains("### 细纲") && !text.contains("<script>"), "经过 markdown 清理");
+
+        // 已有结果时另存新版本；没有章节文件时不记录来源
+        let again = import_text(&root, "书", 1, "新的细纲", "小组").unwrap();
+        assert_eq!(again.output_file, "result/书/1_v2.md");
+        assert!(!import_text(&root, "书", 9, "细纲", "小组").unwrap().tracked);
+
+        let index = analysis_batch::load_index(&root);
+        assert_eq!(index.len(), 1);
+        assert!(index[0].external && index[0].total_tokens == 0);
+        assert_eq!(index[0].outputs.len(), 3);
+        assert_eq!(provenance::analyzed_files(&root, "书").into_iter().collect::<Vec<_>>(), vec!["01.txt".to_string()]);
+
+        assert!(validate_text(&[0xff, 0xfe, 0x00]).is_err());
+        assert!(validate_text(b"a\0b").is_err());
+        assert!(validate_text(b"  \n").is_err());
+        assert!(import_text(&root, "没有的书", 1, "x", "小组").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,130 @@
+//! 从一章的分析结果中取出单个细纲节点或本章核心总结，供复制到剪贴板或分享。
+//!
+//! 结果文件取该章最新修改的版本（见 [`crate::analysis_versions::list_versions`]），去掉 front matter 后
+//! 用 [`crate::outline`] 解析。旧结果可能不是细纲格式，解析不出节点时返回整篇并置 `fallback`，不报错；
+//! 是细纲但没有请求的节点时报错并列出已有节点。需要时把片段另存到 `result/snippets/` 下带时间戳的文件。
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::{analysis_batch, analysis_versions, outline, paths, provenance, storage};
+
+pub const SNIPPETS_DIR: &str = "snippets";
+
+/// 要取的部分：节点序号，或 `summary`（本章核心总结）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKey {
+    Node(usize),
+    Summary,
+}
+
+impl SectionKey {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        if raw.eq_ignore_ascii_case("summary") {
+            return Ok(SectionKey::Summary);
+        }
+        match raw.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(SectionKey::Node(n)),
+            _ => Err(format!("无效的节点序号: {:?}（应为正整数或 summary）", raw)),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            SectionKey::Node(n) => format!("node{}", n),
+            SectionKey::Summary => "summary".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct AnalysisSection {
+    /// 来源结果文件，相对工作区
+    pub source: String,
+    pub markdown: String,
+    pub plain_text: String,
+    /// 解析不出细纲结构，返回的是整篇
+    pub fallback: bool,
+    /// 另存的片段文件，相对工作区；未保存时为 None
+    pub snippet_path: Option<String>,
+}
+
+/// 该章最新修改的分析结果
+fn latest_result(workspace_root: &Path, novel_title: &str, chapter_index: usize) -> Result<String, String> {
+    analysis_versions::list_versions(workspace_root, novel_title, chapter_index)
+        .into_iter()
+        .max_by_key(|v| fs::metadata(workspace_root.join(&v.file)).and_then(|m| m.modified()).ok())
+        .map(|v| v.file)
+        .ok_or_else(|| format!("《{}》第 {} 章还没有分析结果", novel_title, chapter_index))
+}
+
+/// 从结果正文中取出指定部分；返回 (markdown, 是否整篇回退)
+fn select(body: &str, key: SectionKey) -> Result<(String, bool), String> {
+    let Some(parsed) = outline::parse(body) else {
+        return Ok((body.to_string(), true));
+    };
+    let markdown = match key {
+        SectionKey::Summary => parsed.summary.ok_or_else(|| format!("该分析没有{}", outline::SUMMARY_HEADING))?,
+        SectionKey::Node(n) => match parsed.nodes.iter().find(|node| node.number == n) {
+            Some(node) => node.markdown.clone(),
+            None => {
+                let numbers: Vec<String> = parsed.nodes.iter().map(|node| node.number.to_string()).collect();
+                return Err(format!("没有节点 {}（已有节点：{}）", n, numbers.join("、")));
+            }
+        },
+    };
+    Ok((markdown, false))
+}
+
+pub fn extract(workspace_root: &Path, novel_title: &str, chapter_index: usize, key: SectionKey, save: bool) -> Result<AnalysisSection, String> {
+    let source = latest_result(workspace_root, novel_title, chapter_index)?;
+    let text = storage::read_to_string(&workspace_root.join(&source)).map_err(|e| format!("读取 {} 失败: {}", source, e))?;
+    let (markdown, fallback) = select(provenance::strip_front_matter(&text).trim(), key)?;
+    let snippet_path = if save {
+        let dir = analysis_batch::result_dir(workspace_root, SNIPPETS_DIR);
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let path = dir.join(format!("{}_{}_{}_{}.md", novel_title, chapter_index, key.label(), stamp));
+        storage::create_dir_all(&dir).map_err(|e| format!("创建 {} 失败: {}", dir.display(), e))?;
+        storage::write_text(&path, &format!("{}\n", markdown)).map_err(|e| format!("写入片段失败: {}", e))?;
+        Some(paths::to_relative(workspace_root, &path).unwrap_or_else(|| path.display().to_string()))
+    } else {
+        None
+    };
+    Ok(AnalysisSection { source, plain_text: outline::plain_text(&markdown), markdown, fallback, snippet_path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_summary_and_unstructured_fallback() {
+        let root = std::env::temp_dir().join(format!("test_analysis_snippets_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = analysis_batch::result_dir(&root, "书");
+        fs::create_dir_all(&dir).unwrap();
+        let body = "### 1. [开场]\n> **概括**: 醒来\n> **目的**: 悬念\n\n### 2. [对峙]\n> **概括**: 争吵\n\n### 💡 本章核心总结\n一句话";
+        fs::write(dir.join("3.md"), format!("---\nmodel: m\n---\n{}", body)).unwrap();
+
+        let node = extract(&root, "书", 3, SectionKey::parse("2").unwrap(), true).unwrap();
+        assert_eq!((node.markdown.as_str(), node.plain_text.as_str()), ("### 2. [对峙]\n> **概括**: 争吵", "2. 对峙\n概括: 争吵"));
+        assert!(!node.fallback);
+        let saved = node.snippet_path.unwrap();
+        assert!(saved.starts_with("result/snippets/书_3_node2_"));
+        assert_eq!(storage::read_to_string(&root.join(&saved)).unwrap(), "### 2. [对峙]\n> **概括**: 争吵\n");
+
+        let summary = extract(&root, "书", 3, SectionKey::parse("summary").unwrap(), false).unwrap();
+        assert_eq!((summary.plain_text.as_str(), summary.snippet_path), ("💡 本章核心总结\n一句话", None));
+        assert!(extract(&root, "书", 3, SectionKey::Node(5), false).unwrap_err().contains("已有节点：1、2"));
+        assert!(SectionKey::parse("0").is_err());
+
+        fs::write(dir.join("4.md"), "这一章节奏很快。").unwrap();
+        let whole = extract(&root, "书", 4, SectionKey::Node(1), false).unwrap();
+        assert!(whole.fallback);
+        assert_eq!(whole.markdown, "这一章节奏很快。");
+        assert!(extract(&root, "书", 9, SectionKey::Summary, false).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,247 @@
+//! 分析结果的版本：换了提示词或模型重跑分析时，不再悄悄覆盖旧结果。
+//!
+//! 写入结果前按 [`VersionPolicy`] 决定：覆盖原文件、另存为 `N_v2.md`、`N_v3.md`……，或已有结果时
+//! 跳过。调用方未指定时使用设置中的 `analysis_version_policy`；仍未设置则比较最新版本 front matter
+//! 里记录的模型和提示词哈希：不同（或任一方没有记录）时另存新版本，相同时跳过。
+//! 基础文件 `N.md` 视为第 1 版。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::analysis_batch;
+use crate::provenance;
+use crate::versions::{self, ChapterDiff};
+use crate::{library, paths, settings};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionPolicy {
+    /// 直接覆盖原结果（旧行为）
+    Overwrite,
+    /// 另存为下一个 `_vN` 版本
+    KeepVersions,
+    /// 已有结果时不重新分析
+    SkipIfExists,
+}
+
+impl VersionPolicy {
+    /// 解析命令参数，空值表示未指定
+    pub fn parse(value: Option<&str>) -> Result<Option<Self>, String> {
+        match value.map(str::trim).filter(|v| !v.is_empty()) {
+            None => Ok(None),
+            Some("overwrite") => Ok(Some(VersionPolicy::Overwrite)),
+            Some("keep_versions") => Ok(Some(VersionPolicy::KeepVersions)),
+            Some("skip_if_exists") => Ok(Some(VersionPolicy::SkipIfExists)),
+            Some(other) => Err(format!("未知的版本策略: {}（可选 overwrite / keep_versions / skip_if_exists）", other)),
+        }
+    }
+}
+
+/// 写入前的决定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteTarget {
+    /// 写入这个路径
+    Write(PathBuf),
+    /// 保留已有结果，不再分析
+    Skip(PathBuf),
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct AnalysisVersion {
+    /// 相对工作区
+    pub file: String,
+    pub version: u32,
+    pub model: Option<String>,
+    pub prompt_hash: Option<String>,
+    /// 要求的输出语言代码，未指定时为 None
+    pub output_language: Option<String>,
+    pub analyzed_at: Option<String>,
+    pub batch: Option<String>,
+    pub size: u64,
+}
+
+fn version_path(base: &Path, version: u32) -> PathBuf {
+    if version <= 1 {
+        return base.to_path_buf();
+    }
+    let stem = base.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    base.with_file_name(format!("{}_v{}.md", stem, version))
+}
+
+/// 基础文件 `stem.md` 的全部已有版本（含基础文件），按版本号升序
+fn existing_versions(base: &Path) -> Vec<(u32, PathBuf)> {
+    let (Some(dir), Some(stem)) = (base.parent(), base.file_stem().and_then(|s| s.to_str())) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let prefix = format!("{}_v", stem);
+    let mut found: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let version = if name == format!("{}.md", stem) {
+                1
+            } else {
+                let digits = name.strip_prefix(&prefix)?.strip_suffix(".md")?;
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                digits.parse().ok().filter(|v| *v > 1)?
+            };
+            Some((version, entry.path()))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// 已有结果是否由同一模型和提示词生成（任一方没有记录时视为不同）
+fn same_generator(path: &Path, model: Option<&str>, prompt_hash: Option<&str>) -> bool {
+    let Ok(text) = fs::read_to_string(path) else { return false };
+    let fields = provenance::front_matter_fields(&text);
+    let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    matches!((field("model"), model), (Some(a), Some(b)) if a == b)
+        && matches!((field("prompt_hash"), prompt_hash), (Some(a), Some(b)) if a == b)
+}
+
+/// 决定结果写到哪里。`requested` 为本次调用指定的策略，未指定时依次看设置和默认规则。
+pub fn plan_write(
+    workspace_root: &Path,
+    base: &Path,
+    requested: Option<VersionPolicy>,
+    model: Option<&str>,
+    prompt_hash: Option<&str>,
+) -> WriteTarget {
+    let existing = existing_versions(base);
+    let Some((latest, latest_path)) = existing.last().cloned() else {
+        return WriteTarget::Write(base.to_path_buf());
+    };
+    let policy = requested
+        .or(settings::load(workspace_root).analysis_version_policy)
+        .unwrap_or_else(|| {
+            if same_generator(&latest_path, model, prompt_hash) {
+                VersionPolicy::SkipIfExists
+            } else {
+                VersionPolicy::KeepVersions
+            }
+        });
+    match policy {
+        VersionPolicy::Overwrite => WriteTarget::Write(base.to_path_buf()),
+        VersionPolicy::SkipIfExists => WriteTarget::Skip(latest_path),
+        VersionPolicy::KeepVersions => WriteTarget::Write(version_path(base, latest + 1)),
+    }
+}
+
+/// 某章的全部分析结果版本。批次结果按章节文件名（`05.md`），手动导出按序号（`5.md`），两者都列出。
+pub fn list_versions(workspace_root: &Path, novel_title: &str, chapter_index: usize) -> Vec<AnalysisVersion> {
+    let dir = analysis_batch::result_dir(workspace_root, novel_title);
+    let padded = library::chapter_file_name(chapter_index).trim_end_matches(".txt").to_string();
+    let mut stems = vec![padded, chapter_index.to_string()];
+    stems.dedup();
+    stems
+        .iter()
+        .flat_map(|stem| existing_versions(&dir.join(format!("{}.md", stem))))
+        .map(|(version, path)| {
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            let fields = provenance::front_matter_fields(&text);
+            let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+            AnalysisVersion {
+                file: paths::to_relative(workspace_root, &path).unwrap_or_else(|| path.to_string_lossy().to_string()),
+                version,
+                model: field("model"),
+                prompt_hash: field("prompt_hash"),
+                output_language: field("output_language"),
+                analyzed_at: field("analyzed_at"),
+                batch: field("batch"),
+                size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+fn read_result(dir: &Path, file: &str) -> Result<String, String> {
+    let name = Path::new(file).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    if !name.ends_with(".md") {
+        return Err(format!("不是分析结果文件: {}", file));
+    }
+    fs::read_to_string(dir.join(name)).map_err(|e| format!("读取 {} 失败: {}", name, e))
+}
+
+/// 两个结果版本的逐行差异（忽略 front matter）。文件名可带 `result/<小说>/` 前缀。
+pub fn diff(workspace_root: &Path, novel_title: &str, old_file: &str, new_file: &str) -> Result<ChapterDiff, String> {
+    let dir = analysis_batch::result_dir(workspace_root, novel_title);
+    let old = read_result(&dir, old_file)?;
+    let new = read_result(&dir, new_file)?;
+    Ok(ChapterDiff {
+        old_file: old_file.to_string(),
+        new_file: new_file.to_string(),
+        hunks: versions::diff_lines(provenance::strip_front_matter(&old), provenance::strip_front_matter(&new)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::Provenance;
+
+    fn temp_root(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_analysis_versions_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(analysis_batch::result_dir(&dir, "书")).unwrap();
+        dir
+    }
+
+    fn write_result(path: &Path, model: &str, hash: &str, body: &str) {
+        let provenance = Provenance::new("书", None, Vec::new()).generated_by(Some(model), Some(hash));
+        fs::write(path, provenance::with_front_matter(&provenance, body)).unwrap();
+    }
+
+    #[test]
+    fn default_policy_keeps_versions_only_when_generator_changes() {
+        let root = temp_root("policy");
+        let base = analysis_batch::result_dir(&root, "书").join("05.md");
+        assert_eq!(plan_write(&root, &base, None, Some("m"), Some("h")), WriteTarget::Write(base.clone()));
+
+        write_result(&base, "m", "h", "第一版");
+        assert_eq!(plan_write(&root, &base, None, Some("m"), Some("h")), WriteTarget::Skip(base.clone()));
+        let v2 = base.with_file_name("05_v2.md");
+        assert_eq!(plan_write(&root, &base, None, Some("m2"), Some("h")), WriteTarget::Write(v2.clone()));
+
+        write_result(&v2, "m2", "h", "第二版");
+        fs::write(base.with_file_name("05_v2.raw.md"), "raw").unwrap();
+        assert_eq!(plan_write(&root, &base, None, Some("m2"), Some("h")), WriteTarget::Skip(v2.clone()));
+        assert_eq!(
+            plan_write(&root, &base, Some(VersionPolicy::Overwrite), Some("m3"), Some("h")),
+            WriteTarget::Write(base.clone())
+        );
+
+        let listed = list_versions(&root, "书", 5);
+        assert_eq!(listed.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(listed[1].model.as_deref(), Some("m2"));
+        assert_eq!(listed[0].file, "result/书/05.md");
+
+        let diff = diff(&root, "书", "05.md", "result/书/05_v2.md").unwrap();
+        assert_eq!(diff.hunks.len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn settings_policy_restores_plain_overwrite() {
+        let root = temp_root("settings");
+        let base = analysis_batch::result_dir(&root, "书").join("05.md");
+        write_result(&base, "m", "h", "旧");
+        settings::update(&root, |s| {
+            s.analysis_version_policy = Some(VersionPolicy::Overwrite);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(plan_write(&root, &base, None, Some("m2"), Some("h2")), WriteTarget::Write(base.clone()));
+        assert_eq!(
+            plan_write(&root, &base, Some(VersionPolicy::KeepVersions), Some("m2"), Some("h2")),
+            WriteTarget::Write(base.with_file_name("05_v2.md"))
+        );
+        assert!(VersionPolicy::parse(Some("replace")).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,212 @@
+//! 用户定义的故事弧（"第1-15章：开篇"、"16-42章：宗门篇"）。
+//!
+//! 弧保存在 info.json 的 `user.arcs` 中，和其他手动维护的字段一样不受爬虫刷新与 AI 合并影响。
+//! `analyze_novel`、`get_purpose_heatmap` 传入 arc 时只处理该弧的章节范围。弧只决定章节如何分组：
+//! 修改弧不会改动或作废已有的分析结果，各弧的分析覆盖率按分析来源记录实时计算。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::chapter_index::ChapterIndex;
+use crate::{analysis_batch, library, novel_info, provenance};
+
+/// `user` 中保存弧列表的字段
+pub const ARCS_KEY: &str = "arcs";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoryArc {
+    pub name: String,
+    /// 起止章节序号（从 1 开始，含两端），与 `NN.txt` 文件名一致
+    pub start_index: usize,
+    pub end_index: usize,
+}
+
+impl StoryArc {
+    pub fn contains(&self, index: usize) -> bool {
+        (self.start_index..=self.end_index).contains(&index)
+    }
+
+    /// 只保留弧内的章节文件
+    pub fn select_chapters(&self, files: Vec<String>) -> Vec<String> {
+        files.into_iter().filter(|f| chapter_number(f).is_some_and(|n| self.contains(n))).collect()
+    }
+
+    /// 结果文件名前缀，弧名中的路径分隔符等已替换
+    pub fn file_prefix(&self) -> String {
+        library::novel_dir_name(&self.name)
+    }
+
+    /// 报告标题，如 `开篇（第 1-15 章）`
+    pub fn heading(&self) -> String {
+        format!("{}（第 {}-{} 章）", self.name, self.start_index, self.end_index)
+    }
+}
+
+fn chapter_number(file: &str) -> Option<usize> {
+    library::chapter_index_of(file)
+}
+
+/// 章节总数：目录索引与已下载章节文件中较大的序号
+pub fn chapter_count(novel_dir: &Path) -> usize {
+    let catalog = ChapterIndex::load(novel_dir).records().map(|r| r.index).max().unwrap_or(0);
+    let downloaded = analysis_batch::chapter_files(novel_dir).iter().filter_map(|f| chapter_number(f)).max().unwrap_or(0);
+    catalog.max(downloaded)
+}
+
+/// 校验并按起始章节排序：弧名非空且不重复，范围在 1..=chapter_count 之内且互不重叠
+pub fn validate(arcs: Vec<StoryArc>, chapter_count: usize) -> Result<Vec<StoryArc>, String> {
+    let mut arcs: Vec<StoryArc> = arcs.into_iter().map(|a| StoryArc { name: a.name.trim().to_string(), ..a }).collect();
+    let mut names = HashSet::new();
+    for arc in &arcs {
+        if arc.name.is_empty() {
+            return Err("弧名不能为空".to_string());
+        }
+        if !names.insert(arc.name.clone()) {
+            return Err(format!("弧名重复: {}", arc.name));
+        }
+        if arc.start_index == 0 || arc.start_index > arc.end_index {
+            return Err(format!("弧「{}」的章节范围无效: {}-{}", arc.name, arc.start_index, arc.end_index));
+        }
+        if arc.end_index > chapter_count {
+            return Err(format!("弧「{}」超出章节总数 {}: {}-{}", arc.name, chapter_count, arc.start_index, arc.end_index));
+        }
+    }
+    arcs.sort_by_key(|a| a.start_index);
+    if let Some(pair) = arcs.windows(2).find(|w| w[1].start_index <= w[0].end_index) {
+        return Err(format!("弧「{}」与「{}」的章节范围重叠", pair[0].name, pair[1].name));
+    }
+    Ok(arcs)
+}
+
+/// 已保存的弧；没有 info.json 或字段无法解析时为空
+pub fn load(novel_dir: &Path) -> Vec<StoryArc> {
+    novel_info::read_user_fields(novel_dir)
+        .ok()
+        .and_then(|user| user.get(ARCS_KEY).cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+pub fn find(novel_dir: &Path, name: &str) -> Result<StoryArc, String> {
+    load(novel_dir)
+        .into_iter()
+        .find(|a| a.name == name.trim())
+        .ok_or_else(|| format!("未定义的弧: {}", name))
+}
+
+/// 写入已校验的弧列表（覆盖原有的全部弧），空列表删除该字段
+pub async fn save(novel_dir: &Path, arcs: &[StoryArc]) -> Result<(), String> {
+    let value = if arcs.is_empty() {
+        Value::Null
+    } else {
+        serde_json::to_value(arcs).map_err(|e| format!("序列化弧失败: {}", e))?
+    };
+    let mut fields = Map::new();
+    fields.insert(ARCS_KEY.to_string(), value);
+    novel_info::set_user_fields(novel_dir, &fields).await.map(|_| ())
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ArcSummary {
+    #[serde(flatten)]
+    pub arc: StoryArc,
+    /// 弧内的章节数
+    pub chapters: usize,
+    /// 其中已下载的章节数
+    pub downloaded: usize,
+    /// 其中至少被一份分析结果引用的章节数
+    pub analyzed: usize,
+    /// analyzed / chapters
+    pub coverage: f64,
+}
+
+/// 各弧的章节数与分析覆盖率。result_key 为 `result/` 下的书名目录
+pub fn summarize(workspace_root: &Path, novel_dir: &Path, result_key: &str) -> Vec<ArcSummary> {
+    let downloaded: Vec<usize> = analysis_batch::chapter_files(novel_dir).iter().filter_map(|f| chapter_number(f)).collect();
+    let analyzed: HashSet<usize> =
+        provenance::analyzed_files(workspace_root, result_key).iter().filter_map(|f| chapter_number(f)).collect();
+    load(novel_dir)
+        .into_iter()
+        .map(|arc| {
+            let chapters = arc.end_index + 1 - arc.start_index;
+            let analyzed = analyzed.iter().filter(|n| arc.contains(**n)).count();
+            ArcSummary {
+                chapters,
+                downloaded: downloaded.iter().filter(|n| arc.contains(**n)).count(),
+                analyzed,
+                coverage: analyzed as f64 / chapters as f64,
+                arc,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{AnalysisOutput, SourceChapter};
+    use std::fs;
+
+    fn arc(name: &str, start_index: usize, end_index: usize) -> StoryArc {
+        StoryArc { name: name.to_string(), start_index, end_index }
+    }
+
+    #[test]
+    fn validation_rejects_overlaps_and_out_of_range() {
+        let ok = validate(vec![arc("宗门篇", 16, 42), arc(" 开篇 ", 1, 15)], 42).unwrap();
+        assert_eq!(ok, vec![arc("开篇", 1, 15), arc("宗门篇", 16, 42)]);
+
+        let err = validate(vec![arc("开篇", 1, 15), arc("宗门篇", 15, 42)], 42).unwrap_err();
+        assert!(err.contains("重叠"), "{}", err);
+        assert!(validate(vec![arc("宗门篇", 16, 43)], 42).unwrap_err().contains("章节总数"));
+        assert!(validate(vec![arc("空", 0, 3)], 42).is_err());
+        assert!(validate(vec![arc("倒序", 9, 3)], 42).is_err());
+        assert!(validate(vec![arc("开篇", 1, 2), arc("开篇", 3, 4)], 42).unwrap_err().contains("重复"));
+        assert!(validate(vec![arc("  ", 1, 2)], 42).is_err());
+    }
+
+    #[test]
+    fn selects_chapter_files_in_range() {
+        let files = (1..=20).map(library::chapter_file_name).collect();
+        let selected = arc("宗门篇", 16, 42).select_chapters(files);
+        assert_eq!(selected, vec!["16.txt", "17.txt", "18.txt", "19.txt", "20.txt"]);
+        assert_eq!(arc("开/篇", 1, 2).file_prefix(), "开_篇");
+    }
+
+    #[tokio::test]
+    async fn summary_counts_coverage_and_survives_regrouping() {
+        let root = std::env::temp_dir().join(format!("test_arcs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let novel_dir = library::downloads_dir(&root).join("书名");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::write(novel_info::info_path(&novel_dir), r#"{"title":"书名"}"#).unwrap();
+        for n in 1..=6 {
+            fs::write(novel_dir.join(library::chapter_file_name(n)), format!("第{}章", n)).unwrap();
+        }
+        for n in [1, 2, 5] {
+            let file = library::chapter_file_name(n);
+            let sources = vec![SourceChapter::of(&file, b"x")];
+            let output = AnalysisOutput { output_file: format!("result/书名/{}.md", n), sources, output_language: None };
+            provenance::record_export(&root, "书名", output).unwrap();
+        }
+
+        let arcs = validate(vec![arc("开篇", 1, 4), arc("宗门篇", 5, 6)], chapter_count(&novel_dir)).unwrap();
+        save(&novel_dir, &arcs).await.unwrap();
+        let summary = summarize(&root, &novel_dir, "书名");
+        assert_eq!(summary.iter().map(|s| (s.chapters, s.downloaded, s.analyzed)).collect::<Vec<_>>(), vec![(4, 4, 2), (2, 2, 1)]);
+        assert_eq!(summary[0].coverage, 0.5);
+
+        // 重新划分只改变分组，已有分析照常计入
+        save(&novel_dir, &[arc("全书", 1, 6)]).await.unwrap();
+        assert_eq!(summarize(&root, &novel_dir, "书名")[0].analyzed, 3);
+        assert_eq!(find(&novel_dir, "全书").unwrap().end_index, 6);
+        assert!(find(&novel_dir, "开篇").is_err());
+
+        save(&novel_dir, &[]).await.unwrap();
+        assert!(load(&novel_dir).is_empty());
+        assert_eq!(novel_info::read_info(&novel_dir).unwrap()["title"], "书名");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,173 @@
+//! 按作者批量下载：抓取作者页的作品列表，逐本按 `count_per_novel` 章下载。
+//!
+//! 书库中已有的书（按书籍 id / 目录名判断）跳过；某本书失败或超过单本时限只记入结果，不影响其余作品。
+//! 作者名写入每本书的 info.json（`author`），书库检索、导出即可按作者归类。
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::download::{self, DownloadRequest, DownloadSummary};
+use crate::events::EventSink;
+use crate::progress::{emit_progress, WaitReporter};
+use crate::spiders::{qidian, AuthorWork, AuthorWorks, NovelSource};
+use crate::{library, novel_info, paths};
+
+/// 全部作品处理完时发出的事件，payload 为 [`AuthorDownloadSummary`]
+pub const COMPLETED_EVENT: &str = "author-download-completed";
+/// 相邻两本书之间的间隔，避免连续打开浏览器蜘蛛触发 WAF
+const NOVEL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthorDownloadRequest {
+    pub author_page_url: String,
+    pub platform: String,
+    /// 每本书下载的正文章节数
+    pub count_per_novel: usize,
+    /// 相对工作区的书库目录，缺省为 downloads
+    pub dir_name: Option<String>,
+    pub debug_visible: bool,
+    pub min_chapter_chars: Option<usize>,
+    /// 每本书的时限，None 为不限，见 [`download::DEFAULT_TIME_BUDGET`]
+    pub time_budget: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkOutcome {
+    Downloaded,
+    /// 书库中已有，未下载
+    Existing,
+    Failed,
+    /// 超过时限，只下载了一部分
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkResult {
+    #[serde(flatten)]
+    pub work: AuthorWork,
+    pub outcome: WorkOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<DownloadSummary>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuthorDownloadSummary {
+    pub author_page_url: String,
+    pub author: Option<String>,
+    pub works: Vec<WorkResult>,
+}
+
+/// 书库中书的标识：起点统一为书籍 id，其余平台用规范化 URL
+fn book_key(url: &str) -> String {
+    match qidian::extract_book_id(url) {
+        Some(id) => format!("qidian:{}", id),
+        None => download::canonical_url(url),
+    }
+}
+
+/// 书库目录下已有书籍的 (标识, 目录名)
+fn existing_books(library_dir: &Path) -> (HashSet<String>, HashSet<String>) {
+    let mut keys = HashSet::new();
+    let mut names = HashSet::new();
+    for dir in library::scan_library(library_dir).novels {
+        if let Some(name) = dir.file_name() {
+            names.insert(name.to_string_lossy().to_string());
+        }
+        let url = novel_info::read_info(&dir).ok().and_then(|info| info.get("url").and_then(|v| v.as_str()).map(str::to_string));
+        if let Some(url) = url.filter(|u| !u.is_empty()) {
+            keys.insert(book_key(&url));
+        }
+    }
+    (keys, names)
+}
+
+fn library_dir(workspace_root: &Path, dir_name: Option<&str>) -> Result<PathBuf, String> {
+    match dir_name.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(dir) => paths::resolve(workspace_root, dir),
+        None => Ok(library::downloads_dir(workspace_root)),
+    }
+}
+
+/// 抓取作者的作品列表，没有作品时报错
+pub async fn fetch_works<S: NovelSource>(source: &S, req: &AuthorDownloadRequest) -> Result<AuthorWorks, String> {
+    let works = source.fetch_author_works(&req.platform, &req.author_page_url, req.debug_visible).await?;
+    if works.works.is_empty() {
+        return Err(format!("作者页没有作品: {}", req.author_page_url));
+    }
+    Ok(works)
+}
+
+/// 逐本下载作者的作品。书库中已有的跳过，单本失败记入结果后继续下一本。
+pub async fn download_works<E: EventSink + Sync + ?Sized, S: NovelSource>(
+    events: &E,
+    source: &S,
+    workspace_root: &Path,
+    req: &AuthorDownloadRequest,
+    works: AuthorWorks,
+) -> Result<AuthorDownloadSummary, String> {
+    let library_dir = library_dir(workspace_root, req.dir_name.as_deref())?;
+    let (mut keys, mut names) = existing_books(&library_dir);
+    let author_patch = works.author.as_ref().map(|author| {
+        let mut patch = serde_json::Map::new();
+        patch.insert("author".into(), author.clone().into());
+        patch
+    });
+    let total = works.works.len();
+    let mut summary = AuthorDownloadSummary {
+        author_page_url: req.author_page_url.clone(),
+        author: works.author.clone(),
+        works: Vec::new(),
+    };
+    let mut waiter = WaitReporter::new(format!("作者 {}", works.author.as_deref().unwrap_or(&req.author_page_url)));
+
+    for (i, work) in works.works.into_iter().enumerate() {
+        let novel_dir = library::novel_dir_in(&library_dir, &work.title);
+        let dir_name = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let key = book_key(&work.url);
+        if keys.contains(&key) || names.contains(&dir_name) {
+            emit_progress(events, "skipped", format!("作者作品 {}/{}: 书库中已有《{}》，跳过", i + 1, total, work.title));
+            summary.works.push(WorkResult { work, outcome: WorkOutcome::Existing, error: None, summary: None });
+            continue;
+        }
+        if summary.works.iter().any(|w| w.outcome != WorkOutcome::Existing) {
+            waiter.sleep(events, NOVEL_INTERVAL, "书籍间隔").await;
+        }
+        emit_progress(events, "progress", format!("作者作品 {}/{}: 开始下载《{}》", i + 1, total, work.title));
+        let request = DownloadRequest {
+            url: work.url.clone(),
+            platform: req.platform.clone(),
+            debug_visible: req.debug_visible,
+            start_chapter: Some(1),
+            chapter_count: Some(req.count_per_novel.max(1)),
+            novel_dir: Some(novel_dir.clone()),
+            notify: true,
+            skip_extras: true,
+            min_chapter_chars: req.min_chapter_chars,
+            time_budget: req.time_budget,
+            ..Default::default()
+        };
+        let result = match download::process_novel_download(events, source, workspace_root, request).await {
+            Ok(download_summary) => {
+                if let Some(patch) = &author_patch {
+                    if let Err(e) = novel_info::merge_info(&novel_dir, patch).await {
+                        eprintln!("[Author] 写入作者失败《{}》: {}", work.title, e);
+                    }
+                }
+                keys.insert(key);
+                names.insert(dir_name);
+                let outcome = if download_summary.timed_out { WorkOutcome::TimedOut } else { WorkOutcome::Downloaded };
+                WorkResult { work, outcome, error: None, summary: Some(download_summary) }
+            }
+            Err(e) => {
+                emit_progress(events, "error", format!("作者作品《{}》下载失败: {}", work.title, e));
+                WorkResult { work, outcome: WorkOutcome::Failed, error: Some(e), summary: None }
+            }
+        };
+        summary.works.push(result);
+    }
+    Ok(summary)
+}
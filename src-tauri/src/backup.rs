@@ -0,0 +1,525 @@
+//! 工作区备份 / 恢复：把 downloads、result、设置等顶层条目打包为 tar.gz。
+//!
+//! 文件逐个流式写入归档，边写边算哈希，不整块读入内存。归档末尾是清单 `backup_manifest.json`，
+//! 记录每个顶层条目的文件数、字节数和哈希（按归档顺序对 路径 + 内容 做 [`ContentHasher`]）。
+//! 恢复分两遍：先完整读一遍归档重算哈希并与清单比对，被截断或损坏的归档在写出任何文件前就报错；
+//! 校验通过后再解压。`overwrite_policy` 为 skip 时已存在的文件保留不动，作为冲突返回。
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::storage::ContentHasher;
+use crate::{analysis_batch, clean_rules, library, settings};
+
+pub const MANIFEST_FILE: &str = "backup_manifest.json";
+pub const PROGRESS_EVENT: &str = "backup-progress";
+const MANIFEST_VERSION: u32 = 1;
+/// 每处理这么多个文件上报一次进度
+const PROGRESS_EVERY_FILES: usize = 100;
+
+fn yes() -> bool {
+    true
+}
+
+/// 备份哪些顶层条目，缺省全部。用户提示词模板保存在 settings.json 中，prompts 即备份该文件
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupInclude {
+    #[serde(default = "yes")]
+    pub downloads: bool,
+    #[serde(default = "yes")]
+    pub result: bool,
+    #[serde(default = "yes")]
+    pub settings: bool,
+    #[serde(default = "yes")]
+    pub prompts: bool,
+}
+
+impl Default for BackupInclude {
+    fn default() -> Self {
+        BackupInclude { downloads: true, result: true, settings: true, prompts: true }
+    }
+}
+
+impl BackupInclude {
+    /// 工作区下的顶层条目名（去重，保持顺序）
+    fn entries(&self) -> Vec<&'static str> {
+        let mut entries = Vec::new();
+        if self.downloads {
+            entries.push(library::DOWNLOADS_DIR);
+        }
+        if self.result {
+            entries.push(analysis_batch::RESULT_DIR);
+        }
+        if self.settings || self.prompts {
+            entries.push(settings::SETTINGS_FILE);
+        }
+        if self.settings {
+            entries.push(clean_rules::RULES_FILE);
+        }
+        entries
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    Skip,
+    Overwrite,
+}
+
+impl OverwritePolicy {
+    pub fn parse(policy: Option<&str>) -> Result<Self, String> {
+        match policy.map(|p| p.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("skip") => Ok(OverwritePolicy::Skip),
+            Some("overwrite") => Ok(OverwritePolicy::Overwrite),
+            Some(other) => Err(format!("不支持的覆盖策略: {}（可选 skip / overwrite）", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntrySummary {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: String,
+    pub entries: Vec<EntrySummary>,
+}
+
+impl BackupManifest {
+    /// 各条目的原始字节数之和，即解压后的大小
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupProgress {
+    /// "backup" | "verify" | "restore"
+    pub phase: &'static str,
+    pub files_done: usize,
+    /// 备份时为待打包的文件总数；恢复时未知
+    pub files_total: Option<usize>,
+    pub bytes_done: u64,
+    pub current: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub overwritten: usize,
+    /// skip 策略下因目标已存在而未恢复的文件（归档内路径）
+    pub conflicts: Vec<String>,
+    pub manifest: BackupManifest,
+}
+
+/// 顶层条目下的普通文件（相对工作区，按路径排序）；跳过符号链接和 skip 本身（备份文件写在工作区内时）
+fn collect_files(workspace_root: &Path, entry: &str, skip: &Path, out: &mut Vec<PathBuf>) {
+    fn walk(root: &Path, dir: &Path, skip: &Path, out: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+        paths.sort();
+        for path in paths {
+            let Ok(meta) = fs::symlink_metadata(&path) else { continue };
+            if meta.is_dir() {
+                walk(root, &path, skip, out);
+            } else if meta.is_file() && path != skip {
+                if let Ok(rel) = path.strip_prefix(root) {
+                    out.push(rel.to_path_buf());
+                }
+            }
+        }
+    }
+    let path = workspace_root.join(entry);
+    match fs::symlink_metadata(&path) {
+        Ok(meta) if meta.is_dir() => walk(workspace_root, &path, skip, out),
+        Ok(meta) if meta.is_file() => out.push(PathBuf::from(entry)),
+        _ => {}
+    }
+}
+
+/// 归档内的路径：`/` 分隔；含 `..`、绝对路径等非普通组成部分时返回 None
+fn archive_name(path: &Path) -> Option<String> {
+    let parts: Option<Vec<String>> = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    parts.filter(|p| !p.is_empty()).map(|p| p.join("/"))
+}
+
+/// 只接受普通文件和目录。符号链接、硬链接等条目可能指向工作区外，后续条目再经由它写出去
+fn is_plain_entry(entry_type: tar::EntryType) -> bool {
+    entry_type.is_file() || entry_type.is_dir()
+}
+
+/// 校验条目的路径和类型，返回归档内路径
+fn checked_name<R: Read>(entry: &tar::Entry<R>) -> Result<String, String> {
+    let path = entry.path().map_err(corrupted)?.into_owned();
+    let name = archive_name(&path).ok_or_else(|| format!("归档中有不安全的路径: {}", path.display()))?;
+    if !is_plain_entry(entry.header().entry_type()) {
+        return Err(format!("归档中的 {} 不是普通文件（链接等特殊条目不予恢复）", name));
+    }
+    Ok(name)
+}
+
+fn top_level(name: &str) -> &str {
+    name.split('/').next().unwrap_or(name)
+}
+
+/// 读取时顺带更新哈希
+struct HashingReader<'a, R> {
+    inner: R,
+    hasher: &'a mut ContentHasher,
+    bytes: u64,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+fn append_file(
+    builder: &mut tar::Builder<GzEncoder<BufWriter<File>>>,
+    path: &Path,
+    name: &str,
+    summary: &mut EntrySummary,
+    hasher: &mut ContentHasher,
+) -> io::Result<u64> {
+    let file = File::open(path)?;
+    let meta = file.metadata()?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(meta.len());
+    header.set_mode(0o644);
+    header.set_mtime(meta.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs()));
+    hasher.update(name.as_bytes());
+    hasher.update(&[0]);
+    // 读取期间文件被截短时 tar 会补零，按 header 中的长度读取保证归档结构完整
+    let mut reader = HashingReader { inner: BufReader::new(file).take(meta.len()), hasher, bytes: 0 };
+    builder.append_data(&mut header, name, &mut reader)?;
+    summary.files += 1;
+    summary.bytes += reader.bytes;
+    Ok(reader.bytes)
+}
+
+/// 待备份文件的原始总大小，用于开始前预检磁盘空间
+pub fn source_size(workspace_root: &Path, include: &BackupInclude) -> u64 {
+    let mut files = Vec::new();
+    for entry in include.entries() {
+        collect_files(workspace_root, entry, Path::new(""), &mut files);
+    }
+    files.iter().filter_map(|rel| fs::metadata(workspace_root.join(rel)).ok()).map(|m| m.len()).sum()
+}
+
+/// 打包到 dest（先写 `<dest>.partial`，完成后改名），返回清单
+pub fn backup(
+    workspace_root: &Path,
+    dest: &Path,
+    include: BackupInclude,
+    mut on_progress: impl FnMut(&BackupProgress),
+) -> Result<BackupManifest, String> {
+    let partial = PathBuf::from(format!("{}.partial", dest.display()));
+    let mut files: Vec<(&'static str, Vec<PathBuf>)> = Vec::new();
+    for entry in include.entries() {
+        let mut list = Vec::new();
+        collect_files(workspace_root, entry, &partial, &mut list);
+        list.retain(|rel| workspace_root.join(rel) != dest);
+        files.push((entry, list));
+    }
+    let files_total: usize = files.iter().map(|(_, list)| list.len()).sum();
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let result = (|| -> Result<BackupManifest, String> {
+        let file = File::create(&partial).map_err(|e| format!("创建 {} 失败: {}", partial.display(), e))?;
+        let mut builder = tar::Builder::new(GzEncoder::new(BufWriter::new(file), flate2::Compression::default()));
+        let mut manifest =
+            BackupManifest { version: MANIFEST_VERSION, created_at: chrono::Local::now().to_rfc3339(), entries: Vec::new() };
+        let mut progress = BackupProgress { phase: "backup", files_done: 0, files_total: Some(files_total), bytes_done: 0, current: String::new() };
+        for (entry, list) in &files {
+            let mut summary = EntrySummary { name: entry.to_string(), ..Default::default() };
+            let mut hasher = ContentHasher::new();
+            for rel in list {
+                let Some(name) = archive_name(rel) else { continue };
+                let bytes = append_file(&mut builder, &workspace_root.join(rel), &name, &mut summary, &mut hasher)
+                    .map_err(|e| format!("打包 {} 失败: {}", name, e))?;
+                progress.files_done += 1;
+                progress.bytes_done += bytes;
+                if progress.files_done.is_multiple_of(PROGRESS_EVERY_FILES) {
+                    progress.current = name;
+                    on_progress(&progress);
+                }
+            }
+            summary.hash = hasher.finish();
+            manifest.entries.push(summary);
+        }
+
+        let content = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("序列化清单失败: {}", e))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, MANIFEST_FILE, content.as_slice()).map_err(|e| format!("写入清单失败: {}", e))?;
+        let mut writer = builder
+            .into_inner()
+            .and_then(|gz| gz.finish())
+            .map_err(|e| format!("写入 {} 失败: {}", partial.display(), e))?;
+        writer.flush().map_err(|e| format!("写入 {} 失败: {}", partial.display(), e))?;
+        drop(writer);
+        crate::storage::rename(&partial, dest).map_err(|e| format!("重命名为 {} 失败: {}", dest.display(), e))?;
+        progress.current = MANIFEST_FILE.to_string();
+        on_progress(&progress);
+        Ok(manifest)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn open_archive(archive_path: &Path) -> Result<tar::Archive<GzDecoder<BufReader<File>>>, String> {
+    let file = File::open(archive_path).map_err(|e| format!("打开 {} 失败: {}", archive_path.display(), e))?;
+    Ok(tar::Archive::new(GzDecoder::new(BufReader::new(file))))
+}
+
+fn corrupted(e: io::Error) -> String {
+    format!("归档已损坏或不完整（可能被截断）: {}", e)
+}
+
+/// 完整读一遍归档，重算各顶层条目的哈希并与清单比对
+pub fn verify(archive_path: &Path, mut on_progress: impl FnMut(&BackupProgress)) -> Result<BackupManifest, String> {
+    let mut archive = open_archive(archive_path)?;
+    let mut computed: BTreeMap<String, (EntrySummary, ContentHasher)> = BTreeMap::new();
+    let mut manifest: Option<BackupManifest> = None;
+    let mut progress = BackupProgress { phase: "verify", files_done: 0, files_total: None, bytes_done: 0, current: String::new() };
+    for entry in archive.entries().map_err(corrupted)? {
+        let mut entry = entry.map_err(corrupted)?;
+        let name = checked_name(&entry)?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        if name == MANIFEST_FILE {
+            let mut content = String::new();
+            entry.read_to_string(&mut content).map_err(corrupted)?;
+            manifest = Some(serde_json::from_str(&content).map_err(|e| format!("清单格式错误: {}", e))?);
+            continue;
+        }
+        let top = top_level(&name).to_string();
+        let (summary, hasher) =
+            computed.entry(top.clone()).or_insert_with(|| (EntrySummary { name: top, ..Default::default() }, ContentHasher::new()));
+        hasher.update(name.as_bytes());
+        hasher.update(&[0]);
+        let bytes = io::copy(&mut HashingReader { inner: &mut entry, hasher, bytes: 0 }, &mut io::sink()).map_err(corrupted)?;
+        summary.files += 1;
+        summary.bytes += bytes;
+        progress.files_done += 1;
+        progress.bytes_done += bytes;
+        if progress.files_done.is_multiple_of(PROGRESS_EVERY_FILES) {
+            progress.current = name;
+            on_progress(&progress);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| format!("归档中没有 {}，可能被截断或不是本程序生成的备份", MANIFEST_FILE))?;
+    for expected in &manifest.entries {
+        let actual = computed.remove(&expected.name).map(|(mut summary, hasher)| {
+            summary.hash = hasher.finish();
+            summary
+        });
+        let actual = actual.unwrap_or_else(|| EntrySummary {
+            name: expected.name.clone(),
+            hash: ContentHasher::new().finish(),
+            ..Default::default()
+        });
+        if actual != *expected {
+            return Err(format!(
+                "{} 校验失败：清单记录 {} 个文件 / {} 字节 / 哈希 {}，归档中为 {} 个文件 / {} 字节 / 哈希 {}",
+                expected.name, expected.files, expected.bytes, expected.hash, actual.files, actual.bytes, actual.hash
+            ));
+        }
+    }
+    if let Some(extra) = computed.keys().next() {
+        return Err(format!("归档中的 {} 不在清单中", extra));
+    }
+    Ok(manifest)
+}
+
+/// 校验通过后解压到 dest_root
+pub fn restore(
+    archive_path: &Path,
+    dest_root: &Path,
+    policy: OverwritePolicy,
+    mut on_progress: impl FnMut(&BackupProgress),
+) -> Result<RestoreReport, String> {
+    let manifest = verify(archive_path, &mut on_progress)?;
+    restore_verified(archive_path, dest_root, policy, manifest, on_progress)
+}
+
+/// 解压已经过 [`verify`] 校验的归档，调用方可在两步之间按清单做检查（如磁盘空间）
+pub fn restore_verified(
+    archive_path: &Path,
+    dest_root: &Path,
+    policy: OverwritePolicy,
+    manifest: BackupManifest,
+    mut on_progress: impl FnMut(&BackupProgress),
+) -> Result<RestoreReport, String> {
+    let mut report = RestoreReport { restored: 0, overwritten: 0, conflicts: Vec::new(), manifest };
+    let mut progress = BackupProgress { phase: "restore", files_done: 0, files_total: None, bytes_done: 0, current: String::new() };
+    let mut archive = open_archive(archive_path)?;
+    for entry in archive.entries().map_err(corrupted)? {
+        let mut entry = entry.map_err(corrupted)?;
+        let name = checked_name(&entry)?;
+        if name == MANIFEST_FILE || entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let exists = dest_root.join(&name).exists();
+        if exists && policy == OverwritePolicy::Skip {
+            report.conflicts.push(name);
+            continue;
+        }
+        // unpack_in 会拒绝经由已有链接落到 dest_root 之外的路径
+        let unpacked = entry.unpack_in(dest_root).map_err(|e| format!("恢复 {} 失败: {}", name, e))?;
+        if !unpacked {
+            return Err(format!("归档中有不安全的路径: {}", name));
+        }
+        if exists {
+            report.overwritten += 1;
+        }
+        report.restored += 1;
+        progress.files_done += 1;
+        progress.bytes_done += entry.size();
+        if progress.files_done.is_multiple_of(PROGRESS_EVERY_FILES) {
+            progress.current = name;
+            on_progress(&progress);
+        }
+    }
+    on_progress(&progress);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(tag: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("test_backup_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn workspace(tag: &str) -> PathBuf {
+        let root = temp_root(tag);
+        let novel = library::downloads_dir(&root).join("书名");
+        fs::create_dir_all(&novel).unwrap();
+        fs::write(novel.join("01.txt"), "第一章正文").unwrap();
+        fs::write(novel.join("02.txt"), "第二章正文").unwrap();
+        fs::create_dir_all(root.join(analysis_batch::RESULT_DIR).join("书名")).unwrap();
+        fs::write(root.join(analysis_batch::RESULT_DIR).join("书名").join("01.md"), "# 细纲").unwrap();
+        fs::write(root.join(settings::SETTINGS_FILE), "{}").unwrap();
+        root
+    }
+
+    #[test]
+    fn backup_round_trips_and_reports_conflicts() {
+        let root = workspace("roundtrip");
+        let archive = root.join(analysis_batch::RESULT_DIR).join("backup.tar.gz");
+        let manifest = backup(&root, &archive, BackupInclude::default(), |_| {}).unwrap();
+        let names: Vec<_> = manifest.entries.iter().map(|e| (e.name.as_str(), e.files)).collect();
+        assert_eq!(names, vec![("downloads", 2), ("result", 1), ("settings.json", 1), ("clean_rules.json", 0)]);
+        assert!(!PathBuf::from(format!("{}.partial", archive.display())).exists());
+
+        let dest = temp_root("roundtrip_dest");
+        let report = restore(&archive, &dest, OverwritePolicy::Skip, |_| {}).unwrap();
+        assert_eq!(report.restored, 4);
+        assert_eq!(fs::read_to_string(dest.join("downloads/书名/02.txt")).unwrap(), "第二章正文");
+
+        fs::write(dest.join("downloads/书名/01.txt"), "本地修改").unwrap();
+        let report = restore(&archive, &dest, OverwritePolicy::Skip, |_| {}).unwrap();
+        assert_eq!(report.restored, 0);
+        assert!(report.conflicts.contains(&"downloads/书名/01.txt".to_string()));
+        assert_eq!(fs::read_to_string(dest.join("downloads/书名/01.txt")).unwrap(), "本地修改");
+
+        let report = restore(&archive, &dest, OverwritePolicy::Overwrite, |_| {}).unwrap();
+        assert_eq!(report.overwritten, 4);
+        assert_eq!(fs::read_to_string(dest.join("downloads/书名/01.txt")).unwrap(), "第一章正文");
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::remove_dir_all(&dest);
+    }
+
+    #[test]
+    fn truncated_archive_fails_before_extracting() {
+        let root = workspace("truncated");
+        let archive = root.join("backup.tar.gz");
+        let include = BackupInclude { result: false, ..Default::default() };
+        backup(&root, &archive, include, |_| {}).unwrap();
+        let bytes = fs::read(&archive).unwrap();
+        fs::write(&archive, &bytes[..bytes.len() / 2]).unwrap();
+
+        let dest = temp_root("truncated_dest");
+        let err = restore(&archive, &dest, OverwritePolicy::Overwrite, |_| {}).unwrap_err();
+        assert!(err.contains("截断"), "{}", err);
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::remove_dir_all(&dest);
+    }
+
+    fn crafted_archive(path: &Path, link_type: tar::EntryType) {
+        let file = File::create(path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(link_type);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder.append_link(&mut header, "downloads/x", std::env::temp_dir()).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "downloads/x/.bashrc", &b"evil"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn link_entries_are_rejected() {
+        let root = temp_root("links");
+        for (tag, link_type) in [("symlink", tar::EntryType::Symlink), ("hardlink", tar::EntryType::Link)] {
+            let archive = root.join(format!("{}.tar.gz", tag));
+            crafted_archive(&archive, link_type);
+            let err = verify(&archive, |_| {}).unwrap_err();
+            assert!(err.contains("downloads/x"), "{}", err);
+
+            // 跳过校验直接解压也不会创建链接
+            let dest = root.join(format!("{}_dest", tag));
+            fs::create_dir_all(&dest).unwrap();
+            let manifest = BackupManifest { version: MANIFEST_VERSION, created_at: String::new(), entries: Vec::new() };
+            assert!(restore_verified(&archive, &dest, OverwritePolicy::Overwrite, manifest, |_| {}).is_err());
+            assert!(dest.join("downloads/x").symlink_metadata().is_err());
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn overwrite_policy_parses() {
+        assert_eq!(OverwritePolicy::parse(None).unwrap(), OverwritePolicy::Skip);
+        assert_eq!(OverwritePolicy::parse(Some("Overwrite")).unwrap(), OverwritePolicy::Overwrite);
+        assert!(OverwritePolicy::parse(Some("merge")).is_err());
+    }
+}
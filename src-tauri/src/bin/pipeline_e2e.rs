@@ -28,7 +28,7 @@ fn main() {
     let handle = app.handle();
 
     handle.manage(fanqie_app_lib::ai::GlobalAiConfig(Mutex::new(
-        Some(fanqie_app_lib::ai::AiConfig { api_base, api_key, model })
+        Some(fanqie_app_lib::ai::AiConfig { api_base, api_key, model, ..Default::default() })
     )));
 
     let project_root = fanqie_app_lib::get_project_root();
@@ -56,10 +56,11 @@ fn main() {
     {
         let rt = tokio::runtime::Runtime::new().unwrap();
         let result = rt.block_on(async {
-            let state = handle.state::<fanqie_app_lib::ai::GlobalAiConfig>();
-            let guard = state.0.lock().unwrap();
-            let config = guard.clone().unwrap();
-            drop(guard);
+            let config = {
+                let state = handle.state::<fanqie_app_lib::ai::GlobalAiConfig>();
+                let guard = state.0.lock().unwrap();
+                guard.clone().unwrap()
+            };
             fanqie_app_lib::ai::call_ai(config,
                 "你是一个章节细纲提取助手。将以下内容拆解为 JSON 数组：[{\"event\":\"\",\"purpose\":\"\",\"emotion\":\"\",\"highlight\":\"\"}]".to_string(),
                 "这是一个测试章节内容，主角在街头遇到神秘老人，老人递给他一枚古玉后消失。".to_string(), true).await
@@ -0,0 +1,267 @@
+//! 阅读书签：标记某章某处的精彩段落（钩子、转折等），连同选中的摘录和笔记保存。
+//!
+//! 所有小说的书签都存放在 `<workspace>/bookmarks.json`，记录章节文件和字符偏移，阅读器据此跳回原处。
+//! 小说目录被删除后书签不删除（摘录本身仍有价值），列出时标记为 orphaned。
+//! [`export_digest`] 把全部书签按小说、章节汇总为一份 Markdown。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{library, paths, storage};
+
+pub const BOOKMARKS_FILE: &str = "bookmarks.json";
+pub const DIGEST_FILE: &str = "bookmarks.md";
+/// 摘录的最大字数，超出截断
+const MAX_EXCERPT_CHARS: usize = 2000;
+
+/// bookmarks.json 的读改写在锁内进行，避免并发添加 / 删除互相覆盖
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Bookmark {
+    pub id: String,
+    pub dir_name: String,
+    pub novel_name: String,
+    pub chapter_file: String,
+    /// 摘录在章节文件正文中的字符偏移（按字符而非字节计）
+    pub char_offset: usize,
+    pub excerpt: String,
+    #[serde(default)]
+    pub note: String,
+    pub created_at: String,
+    /// 小说目录已不存在，只在列出时计算，不写入文件
+    #[serde(default, skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub orphaned: bool,
+}
+
+/// list_bookmarks 的筛选条件，字段都为空时列出全部
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BookmarkScope {
+    pub dir_name: Option<String>,
+    pub novel_name: Option<String>,
+    /// 按空白分隔的关键词，摘录或笔记需包含全部关键词（不区分大小写）
+    pub query: Option<String>,
+}
+
+fn file_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(BOOKMARKS_FILE)
+}
+
+fn load(workspace_root: &Path) -> Result<Vec<Bookmark>, String> {
+    match storage::read_to_string(&file_path(workspace_root)) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", BOOKMARKS_FILE, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("读取 {} 失败: {}", BOOKMARKS_FILE, e)),
+    }
+}
+
+fn save(workspace_root: &Path, bookmarks: &[Bookmark]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(bookmarks).map_err(|e| format!("序列化失败: {}", e))?;
+    storage::write_atomic(&file_path(workspace_root), content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", BOOKMARKS_FILE, e))
+}
+
+fn new_id(existing: &[Bookmark]) -> String {
+    let base = format!("bm_{}", chrono::Local::now().format("%Y%m%d%H%M%S%3f"));
+    let mut id = base.clone();
+    let mut n = 1;
+    while existing.iter().any(|b| b.id == id) {
+        n += 1;
+        id = format!("{}_{}", base, n);
+    }
+    id
+}
+
+/// 添加书签。章节文件须存在，偏移不能超出章节长度；返回保存的书签
+pub fn add(
+    workspace_root: &Path,
+    dir_name: &str,
+    novel_name: &str,
+    chapter_file: &str,
+    char_offset: usize,
+    excerpt: &str,
+    note: &str,
+) -> Result<Bookmark, String> {
+    if !library::is_chapter_path(chapter_file) {
+        return Err(format!("无效的章节文件名: {}", chapter_file));
+    }
+    let path = paths::resolve_novel(workspace_root, dir_name, novel_name)?.join(chapter_file);
+    let text = storage::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", chapter_file, e))?;
+    let chars = text.chars().count();
+    if char_offset > chars {
+        return Err(format!("偏移 {} 超出章节长度（{} 字）", char_offset, chars));
+    }
+    let excerpt = excerpt.trim();
+    if excerpt.is_empty() {
+        return Err("摘录为空".to_string());
+    }
+
+    let _lock = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut bookmarks = load(workspace_root)?;
+    let bookmark = Bookmark {
+        id: new_id(&bookmarks),
+        dir_name: dir_name.to_string(),
+        novel_name: novel_name.to_string(),
+        chapter_file: chapter_file.to_string(),
+        char_offset,
+        excerpt: crate::spiders::truncate_chars(excerpt, MAX_EXCERPT_CHARS),
+        note: note.trim().to_string(),
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        orphaned: false,
+    };
+    bookmarks.push(bookmark.clone());
+    save(workspace_root, &bookmarks)?;
+    Ok(bookmark)
+}
+
+fn matches(bookmark: &Bookmark, scope: &BookmarkScope, terms: &[String]) -> bool {
+    let field = |filter: &Option<String>, value: &str| !filter.as_deref().is_some_and(|f| !f.is_empty() && f != value);
+    if !field(&scope.dir_name, &bookmark.dir_name) || !field(&scope.novel_name, &bookmark.novel_name) {
+        return false;
+    }
+    let haystack = format!("{}\n{}", bookmark.excerpt, bookmark.note).to_lowercase();
+    terms.iter().all(|t| haystack.contains(t))
+}
+
+/// 符合筛选条件的书签，从新到旧；小说目录已不存在的标记 orphaned
+pub fn list(workspace_root: &Path, scope: &BookmarkScope) -> Result<Vec<Bookmark>, String> {
+    let terms: Vec<String> =
+        scope.query.as_deref().unwrap_or_default().split_whitespace().map(str::to_lowercase).collect();
+    let mut bookmarks: Vec<Bookmark> = load(workspace_root)?
+        .into_iter()
+        .filter(|b| matches(b, scope, &terms))
+        .map(|b| {
+            let exists = paths::resolve_novel(workspace_root, &b.dir_name, &b.novel_name).is_ok_and(|p| p.is_dir());
+            Bookmark { orphaned: !exists, ..b }
+        })
+        .collect();
+    bookmarks.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.id.cmp(&a.id)));
+    Ok(bookmarks)
+}
+
+/// 删除书签，书签不存在时报错
+pub fn delete(workspace_root: &Path, id: &str) -> Result<(), String> {
+    let _lock = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut bookmarks = load(workspace_root)?;
+    let before = bookmarks.len();
+    bookmarks.retain(|b| b.id != id);
+    if bookmarks.len() == before {
+        return Err(format!("书签 {} 不存在", id));
+    }
+    save(workspace_root, &bookmarks)
+}
+
+/// 章节文件头部的标题，读不到时用文件名
+fn chapter_heading(workspace_root: &Path, bookmark: &Bookmark) -> String {
+    paths::resolve_novel(workspace_root, &bookmark.dir_name, &bookmark.novel_name)
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(&bookmark.chapter_file)).ok())
+        .and_then(|text| library::ChapterFile::parse(&text))
+        .map(|chapter| chapter.title)
+        .unwrap_or_else(|| bookmark.chapter_file.trim_end_matches(".txt").to_string())
+}
+
+/// 渲染全部书签：按小说分节，节内按章节和偏移排序
+pub fn render_digest(workspace_root: &Path, bookmarks: &[Bookmark]) -> String {
+    let mut sorted: Vec<&Bookmark> = bookmarks.iter().collect();
+    sorted.sort_by(|a, b| {
+        (&a.novel_name, &a.dir_name, &a.chapter_file, a.char_offset).cmp(&(&b.novel_name, &b.dir_name, &b.chapter_file, b.char_offset))
+    });
+
+    let mut out = format!(
+        "# 书签摘录\n\n导出于 {}，共 {} 条\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        bookmarks.len()
+    );
+    let mut current: Option<(&str, &str)> = None;
+    for b in sorted {
+        let novel = (b.dir_name.as_str(), b.novel_name.as_str());
+        if current != Some(novel) {
+            current = Some(novel);
+            let mark = if b.orphaned { "（小说已删除）" } else { "" };
+            out.push_str(&format!("\n## 《{}》{}\n", b.novel_name, mark));
+        }
+        out.push_str(&format!("\n### {} · 第 {} 字\n\n", chapter_heading(workspace_root, b), b.char_offset));
+        for line in b.excerpt.lines() {
+            out.push_str(&format!("> {}\n", line));
+        }
+        if !b.note.is_empty() {
+            out.push_str(&format!("\n笔记：{}\n", b.note));
+        }
+        out.push_str(&format!("\n<sub>{}</sub>\n", b.created_at));
+    }
+    out
+}
+
+/// 把全部书签导出为 `exports/bookmarks.md`，返回文件路径
+pub fn export_digest(workspace_root: &Path) -> Result<PathBuf, String> {
+    let bookmarks = list(workspace_root, &BookmarkScope::default())?;
+    let dir = workspace_root.join(crate::export::EXPORTS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let path = dir.join(DIGEST_FILE);
+    storage::write_text(&path, &render_digest(workspace_root, &bookmarks))
+        .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn novel(root: &Path, name: &str) -> PathBuf {
+        let dir = library::downloads_dir(root).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let content = library::render_chapter_file("第1章 钩子", "u", "他推开门，门后站着十年前死去的自己。");
+        fs::write(dir.join("01.txt"), content).unwrap();
+        dir
+    }
+
+    #[test]
+    fn bookmarks_are_added_filtered_and_deleted() {
+        let root = std::env::temp_dir().join(format!("test_bookmarks_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        novel(&root, "甲书");
+        novel(&root, "乙书");
+        let downloads = library::DOWNLOADS_DIR;
+
+        let hook = add(&root, downloads, "甲书", "01.txt", 12, " 门后站着十年前死去的自己 ", "开篇钩子").unwrap();
+        assert_eq!(hook.excerpt, "门后站着十年前死去的自己");
+        add(&root, downloads, "乙书", "01.txt", 0, "他推开门", "转折").unwrap();
+        assert!(add(&root, downloads, "甲书", "01.txt", 10_000, "x", "").unwrap_err().contains("超出章节长度"));
+        assert!(add(&root, downloads, "甲书", "../01.txt", 0, "x", "").is_err());
+        assert!(add(&root, downloads, "甲书", "01.txt", 0, "  ", "").is_err());
+
+        assert_eq!(list(&root, &BookmarkScope::default()).unwrap().len(), 2);
+        let scope = BookmarkScope { novel_name: Some("甲书".into()), ..Default::default() };
+        assert_eq!(list(&root, &scope).unwrap(), vec![hook.clone()]);
+        let scope = BookmarkScope { query: Some("转折 推开".into()), ..Default::default() };
+        assert_eq!(list(&root, &scope).unwrap().iter().map(|b| b.novel_name.as_str()).collect::<Vec<_>>(), vec!["乙书"]);
+
+        delete(&root, &hook.id).unwrap();
+        assert!(delete(&root, &hook.id).is_err());
+        assert_eq!(list(&root, &BookmarkScope::default()).unwrap().len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn bookmarks_of_deleted_novels_are_kept_and_flagged() {
+        let root = std::env::temp_dir().join(format!("test_bookmarks_orphan_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = novel(&root, "甲书");
+        add(&root, library::DOWNLOADS_DIR, "甲书", "01.txt", 3, "门后站着\n十年前死去的自己", "开篇钩子").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let listed = list(&root, &BookmarkScope::default()).unwrap();
+        assert!(listed[0].orphaned);
+        assert!(!fs::read_to_string(root.join(BOOKMARKS_FILE)).unwrap().contains("orphaned"));
+
+        let digest = fs::read_to_string(export_digest(&root).unwrap()).unwrap();
+        assert!(digest.contains("## 《甲书》（小说已删除）"));
+        assert!(digest.contains("### 01 · 第 3 字"));
+        assert!(digest.contains("> 门后站着\n> 十年前死去的自己\n"));
+        assert!(digest.contains("笔记：开篇钩子"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
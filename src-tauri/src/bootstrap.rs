@@ -0,0 +1,155 @@
+//! 首次使用时生成示例工作区：目录结构、默认设置、初始清洗规则、选择器覆盖文件、内置提示词的副本，
+//! 以及一本三章的示例小说（《桃花源记》，公版），不必先抓取就能试阅读、统计、AI 分析和导出。
+//!
+//! 只补缺不覆盖：已存在的文件和目录一律跳过（哪怕内容与默认值不同），报告中分别列出创建和跳过的路径。
+//! `prompts/` 下的文件只是内置模板的副本，程序实际使用的模板仍在代码和 settings.json 的 `prompt_templates` 中，
+//! 复制改写后粘贴为自定义模板即可。
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::{clean_rules, library, novel_info, paths, prompts, settings, spiders, storage};
+
+pub const PROMPTS_DIR: &str = "prompts";
+pub const LOGS_DIR: &str = "logs";
+pub const SAMPLE_TITLE: &str = "桃花源记（示例）";
+
+const SAMPLE_CHAPTERS: &[(&str, &str)] = &[
+    (
+        "第1章 桃花林",
+        "　　晋太元中，武陵人捕鱼为业。缘溪行，忘路之远近。忽逢桃花林，夹岸数百步，中无杂树，芳草鲜美，落英缤纷。渔人甚异之，复前行，欲穷其林。",
+    ),
+    (
+        "第2章 桃源",
+        "　　林尽水源，便得一山，山有小口，仿佛若有光。便舍船，从口入。初极狭，才通人。复行数十步，豁然开朗。土地平旷，屋舍俨然，有良田美池桑竹之属。阡陌交通，鸡犬相闻。其中往来种作，男女衣着，悉如外人。黄发垂髫，并怡然自乐。\n\n　　见渔人，乃大惊，问所从来。具答之。便要还家，设酒杀鸡作食。村中闻有此人，咸来问讯。自云先世避秦时乱，率妻子邑人来此绝境，不复出焉，遂与外人间隔。问今是何世，乃不知有汉，无论魏晋。此人一一为具言所闻，皆叹惋。余人各复延至其家，皆出酒食。停数日，辞去。此中人语云：\"不足为外人道也。\"",
+    ),
+    (
+        "第3章 迷津",
+        "　　既出，得其船，便扶向路，处处志之。及郡下，诣太守，说如此。太守即遣人随其往，寻向所志，遂迷，不复得路。\n\n　　南阳刘子骥，高尚士也，闻之，欣然规往。未果，寻病终。后遂无问津者。",
+    ),
+];
+
+/// 创建 / 跳过的路径（工作区相对路径，目录以 `/` 结尾）
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct BootstrapReport {
+    pub created: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl BootstrapReport {
+    fn note(&mut self, root: &Path, path: &Path, created: bool) {
+        let mut rel = paths::to_relative(root, path).unwrap_or_else(|| path.display().to_string());
+        if path.is_dir() {
+            rel.push('/');
+        }
+        if created { self.created.push(rel) } else { self.skipped.push(rel) }
+    }
+
+    fn dir(&mut self, root: &Path, dir: &Path) -> Result<(), String> {
+        let created = !dir.is_dir();
+        if created {
+            storage::create_dir_all(dir).map_err(|e| format!("创建 {} 失败: {}", dir.display(), e))?;
+        }
+        self.note(root, dir, created);
+        Ok(())
+    }
+
+    /// 文件不存在时写入 bytes
+    fn file(&mut self, root: &Path, path: &Path, bytes: impl FnOnce() -> Result<Vec<u8>, String>) -> Result<(), String> {
+        let created = !path.exists();
+        if created {
+            storage::write_atomic(path, &bytes()?).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+        }
+        self.note(root, path, created);
+        Ok(())
+    }
+}
+
+fn pretty(value: &impl Serialize) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| format!("序列化失败: {}", e))
+}
+
+fn sample_info() -> Value {
+    json!({
+        "title": SAMPLE_TITLE,
+        "author": "陶渊明",
+        "url": "",
+        "platform": "sample",
+        "tags": ["示例", "古文"],
+        "description": "示例工作区自带的公版短文，分为三章，可直接用来试阅读、统计、AI 分析和导出。",
+    })
+}
+
+fn sample_dir(workspace_root: &Path) -> PathBuf {
+    library::novel_dir_in(&library::downloads_dir(workspace_root), SAMPLE_TITLE)
+}
+
+/// 补齐示例工作区缺少的部分
+pub fn bootstrap(workspace_root: &Path) -> Result<BootstrapReport, String> {
+    let root = workspace_root;
+    let mut report = BootstrapReport::default();
+    report.dir(root, &library::downloads_dir(root))?;
+    report.dir(root, &root.join(LOGS_DIR))?;
+
+    let prompts_dir = root.join(PROMPTS_DIR);
+    report.dir(root, &prompts_dir)?;
+    for template in prompts::builtin_templates() {
+        let path = prompts_dir.join(format!("{}.md", template.name));
+        report.file(root, &path, || Ok(storage::encode_text(&path, &format!("# {}\n\n{}\n", template.label, template.content))))?;
+    }
+
+    report.file(root, &settings::settings_path(root), || pretty(&settings::Settings::default()))?;
+    report.file(root, &root.join(clean_rules::RULES_FILE), || pretty(&clean_rules::stock_rules()))?;
+    let selectors: Value = spiders::selectors::PLATFORMS.iter().map(|p| (p.to_string(), json!({}))).collect::<serde_json::Map<_, _>>().into();
+    report.file(root, &spiders::selectors::selectors_path(root), || pretty(&selectors))?;
+
+    let novel_dir = sample_dir(root);
+    report.dir(root, &novel_dir)?;
+    report.file(root, &novel_info::info_path(&novel_dir), || pretty(&sample_info()))?;
+    for (i, (title, body)) in SAMPLE_CHAPTERS.iter().enumerate() {
+        let path = novel_dir.join(library::chapter_file_name(i + 1));
+        report.file(root, &path, || Ok(storage::encode_text(&path, &library::render_chapter_file(title, "", body))))?;
+    }
+    Ok(report)
+}
+
+/// 工作区是否已初始化：有 settings.json，或书库中已有小说。前端据此决定是否提示生成示例工作区
+pub fn is_initialized(workspace_root: &Path) -> bool {
+    settings::settings_path(workspace_root).is_file()
+        || !library::scan_library(&library::downloads_dir(workspace_root)).novels.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn bootstrap_fills_gaps_and_never_overwrites() {
+        let root = std::env::temp_dir().join(format!("test_bootstrap_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        assert!(!is_initialized(&root));
+
+        let first = bootstrap(&root).unwrap();
+        assert!(first.skipped.is_empty());
+        assert!(first.created.contains(&"settings.json".to_string()));
+        assert!(is_initialized(&root));
+        let novel_dir = sample_dir(&root);
+        assert_eq!(crate::sharding::chapter_files(&novel_dir).len(), 3);
+        assert_eq!(novel_info::read_info(&novel_dir).unwrap()["title"], SAMPLE_TITLE);
+        let chapter = library::ChapterFile::parse(&storage::read_to_string(&novel_dir.join("01.txt")).unwrap()).unwrap();
+        assert_eq!(chapter.title, "第1章 桃花林");
+        assert!(clean_rules::compile(&clean_rules::load(&root)).is_ok());
+
+        // 用户改过的文件保留，删掉的文件补回
+        fs::write(root.join(clean_rules::RULES_FILE), "[]").unwrap();
+        fs::remove_file(novel_dir.join("03.txt")).unwrap();
+        let second = bootstrap(&root).unwrap();
+        assert_eq!(second.created, vec![paths::to_relative(&root, &novel_dir.join("03.txt")).unwrap()]);
+        assert_eq!(second.skipped.len(), first.created.len() - 1);
+        assert_eq!(fs::read_to_string(root.join(clean_rules::RULES_FILE)).unwrap(), "[]");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,20 +1,492 @@
 use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder, Listener};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::future::Future;
 use std::time::Duration;
-use tokio::sync::oneshot;
-use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone)]
-struct SpiderResult {
-    html: String,
+use crate::spiders::SpiderError;
+use crate::priority::{GatePermit, PriorityGate};
+
+// ========================================================================
+//  事件桥备用通道
+//
+//  部分 Linux / WebKitGTK 环境不会向外部页面注入 window.__TAURI__，init script 的 emit
+//  静默失败。此时 init script 把 document.title 设为 NO_BRIDGE_MARKER，Rust 侧通过
+//  title-changed 回调观察到后 eval FALLBACK_SCRIPT，页面把 HTML 经 encodeURIComponent
+//  分块写进 document.title 传回。备用通道在 BRIDGE_PROBE_TIMEOUT 内仍没拿到页面则
+//  报 EventBridgeUnavailable，不再等满 SPIDER_TIMEOUT。
+// ========================================================================
+
+const NO_BRIDGE_MARKER: &str = "__SPIDER_NO_BRIDGE__";
+const CHUNK_PREFIX: &str = "__SPIDER_CHUNK__:";
+const BRIDGE_PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+/// 前置动作的元素在页面就绪后最多等这么久才出现，超过则跳过该动作
+const PRE_ACTION_FIND_MS: u64 = 2000;
+
+const FALLBACK_SCRIPT: &str = r#"
+    (() => {
+        let sent = false;
+        const send = () => {
+            if (sent) return;
+            sent = true;
+            const html = document.documentElement?.outerHTML || '';
+            let encoded;
+            try {
+                encoded = encodeURIComponent(html);
+            } catch (e) {
+                encoded = encodeURIComponent(html.replace(/[\uD800-\uDFFF]/g, ''));
+            }
+            const size = 16000;
+            const total = Math.max(1, Math.ceil(encoded.length / size));
+            let i = 0;
+            const next = () => {
+                if (i >= total) return;
+                document.title = '__SPIDER_CHUNK__:' + i + ':' + total + ':' + encoded.slice(i * size, (i + 1) * size);
+                i += 1;
+                setTimeout(next, 20);
+            };
+            console.log('[Spider] Sending HTML via document.title,', total, 'chunks');
+            next();
+        };
+        if (document.readyState === 'complete') {
+            setTimeout(send, 2000);
+        } else {
+            window.addEventListener('load', () => setTimeout(send, 2000), { once: true });
+            setTimeout(send, 10000);
+        }
+    })();
+"#;
+
+/// 最近一次抓取实际使用的回传通道，供 run_diagnostics 展示
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeStatus {
+    /// "event" | "title_fallback" | "unavailable"
+    pub channel: String,
+    pub checked_at: String,
+    pub url: String,
 }
 
-pub async fn fetch_via_window(app: &AppHandle, url: &str, debug_visible: bool) -> Result<String, String> {
-    let label = "spider_worker";
-    
-    // Close existing if any
-    if let Some(w) = app.get_webview_window(label) {
-        let _ = w.close();
+fn last_bridge_status() -> &'static Mutex<Option<BridgeStatus>> {
+    static STATUS: OnceLock<Mutex<Option<BridgeStatus>>> = OnceLock::new();
+    STATUS.get_or_init(|| Mutex::new(None))
+}
+
+fn record_bridge(channel: &str, url: &str) {
+    if let Ok(mut guard) = last_bridge_status().lock() {
+        *guard = Some(BridgeStatus {
+            channel: channel.to_string(),
+            checked_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            url: url.to_string(),
+        });
+    }
+}
+
+pub fn bridge_status() -> Option<BridgeStatus> {
+    last_bridge_status().lock().ok().and_then(|g| g.clone())
+}
+
+// ========================================================================
+//  窗口预算
+//
+//  每次抓取打开一个独立的隐藏 webview 窗口。同时存在的窗口数受预算限制（设置
+//  spider_window_budget，缺省 DEFAULT_WINDOW_BUDGET），超出时排队等待并发出 queued
+//  进度事件，而不是继续创建窗口。许可随抓取结束（包括任务被取消、窗口被强制关闭）释放。
+//  排队按任务优先级放行，用户发起的抓取排在预取、定时扫榜之前，见 crate::priority。
+// ========================================================================
+
+pub const DEFAULT_WINDOW_BUDGET: usize = 2;
+const WINDOW_LABEL_PREFIX: &str = "spider_worker";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct WindowMetrics {
+    /// 当前持有许可（已打开或正在打开）的爬虫窗口数
+    pub open_windows: usize,
+    /// 等待窗口许可的抓取数
+    pub queue_length: usize,
+    pub budget: usize,
+    /// 其中后台任务（预取、定时扫榜等）持有的窗口数和排队数
+    pub background_windows: usize,
+    pub background_queued: usize,
+}
+
+pub struct WindowBudget {
+    gate: PriorityGate,
+}
+
+impl WindowBudget {
+    pub fn new(limit: usize) -> Self {
+        WindowBudget { gate: PriorityGate::new(limit) }
+    }
+
+    pub fn metrics(&self) -> WindowMetrics {
+        let m = self.gate.metrics();
+        WindowMetrics {
+            open_windows: m.running(),
+            queue_length: m.queued(),
+            budget: m.limit,
+            background_windows: m.background.running,
+            background_queued: m.background.queued,
+        }
+    }
+
+    /// 调整预算；调大时立即唤醒排队者，调小时已打开的窗口不受影响
+    pub fn set_limit(&self, limit: usize) {
+        self.gate.set_limit(limit);
+    }
+
+    /// 按当前任务的优先级取得一个窗口许可。需要排队时先调用一次 on_queued（参数为排队后的队列长度）。
+    pub async fn acquire(&self, on_queued: impl FnOnce(usize)) -> GatePermit<'_> {
+        self.gate.acquire(crate::priority::current(), on_queued).await
+    }
+}
+
+fn window_budget() -> &'static WindowBudget {
+    static BUDGET: OnceLock<WindowBudget> = OnceLock::new();
+    BUDGET.get_or_init(|| WindowBudget::new(DEFAULT_WINDOW_BUDGET))
+}
+
+/// 调整窗口预算，见 [`WindowBudget::set_limit`]
+pub fn set_window_budget(limit: usize) {
+    window_budget().set_limit(limit);
+}
+
+pub fn window_metrics() -> WindowMetrics {
+    window_budget().metrics()
+}
+
+/// 关闭所有爬虫窗口。进行中的抓取收到窗口销毁事件后立即结束并归还许可，返回关闭的窗口数。
+pub fn close_all_windows(app: &AppHandle) -> usize {
+    let windows: Vec<_> = app
+        .webview_windows()
+        .into_iter()
+        .filter(|(label, _)| label.starts_with(WINDOW_LABEL_PREFIX))
+        .collect();
+    for (label, window) in &windows {
+        if let Err(e) = window.destroy() {
+            eprintln!("[Spider] 关闭窗口 {} 失败: {}", label, e);
+        }
+    }
+    windows.len()
+}
+
+enum TitleSignal {
+    NoBridge,
+    Html(Result<String, SpiderError>),
+    /// 窗口被关闭（abort_all_tasks 或用户手动关闭调试窗口）
+    Closed,
+}
+
+/// 重组经 document.title 分块传回的页面。分块格式 `index:total:data`，data 为 URI 编码。
+struct ChunkAssembler {
+    chunks: Vec<Option<String>>,
+    /// 已收到的编码字节数
+    received: usize,
+    /// 解码后页面的字节上限
+    limit: usize,
+}
+
+impl ChunkAssembler {
+    fn new(limit: usize) -> Self {
+        ChunkAssembler { chunks: Vec::new(), received: 0, limit }
+    }
+
+    /// 收齐全部分块时返回解码后的 HTML；编码数据已明显超过上限（每字节至多编码为 3 字节）时提前拒绝。
+    fn push(&mut self, payload: &str) -> Option<Result<String, SpiderError>> {
+        let mut parts = payload.splitn(3, ':');
+        let index = parts.next()?.parse::<usize>().ok()?;
+        let total = parts.next()?.parse::<usize>().ok()?;
+        let data = parts.next()?;
+        if total == 0 || index >= total {
+            return None;
+        }
+        if self.chunks.len() != total {
+            self.chunks = vec![None; total];
+            self.received = 0;
+        }
+        if self.chunks[index].is_none() {
+            self.received += data.len();
+        }
+        if self.received > self.limit.saturating_mul(3) {
+            let received = self.received;
+            self.chunks = Vec::new();
+            self.received = 0;
+            return Some(Err(SpiderError::PageTooLarge { bytes: received / 3, limit: self.limit }));
+        }
+        self.chunks[index] = Some(data.to_string());
+        if self.chunks.iter().any(Option::is_none) {
+            return None;
+        }
+        let mut encoded = String::with_capacity(self.received);
+        for chunk in self.chunks.drain(..).flatten() {
+            encoded.push_str(&chunk);
+        }
+        self.received = 0;
+        let html = percent_decode(&encoded);
+        Some(check_html_size(html, self.limit))
+    }
+}
+
+fn check_html_size(html: String, limit: usize) -> Result<String, SpiderError> {
+    if html.len() > limit {
+        return Err(SpiderError::PageTooLarge { bytes: html.len(), limit });
+    }
+    Ok(html)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// ========================================================================
+//  事件通道分片
+//
+//  WebView2 上单条 IPC 消息过大时 emit 会被静默丢弃，表现为等满超时。页面超过
+//  EVENT_CHUNK_THRESHOLD 个字符时 init script 改为按 EVENT_CHUNK_CHARS 分片依次 emit，
+//  最后发一条 done 标记；Rust 侧按序号重组。每条消息带本次请求的 id，旧请求残留的分片直接丢弃；
+//  收到 done 时仍缺分片则报 PayloadIncomplete，而不是一直等到超时。
+// ========================================================================
+
+/// 超过这个字符数的页面分片回传
+const EVENT_CHUNK_THRESHOLD: usize = 512 * 1024;
+/// 每片的字符数（UTF-16 码元）
+const EVENT_CHUNK_CHARS: usize = 256 * 1024;
+
+// ========================================================================
+//  页面脚本日志
+//
+//  init script 的诊断信息在 webview 的控制台里，远程排查用户机器时看不到。脚本只在状态切换时
+//  记一行（重复检查同一状态不记），每行随即经事件通道发回，整页 / 结束标记里再带上完整的一份；
+//  总长不超过 MAX_SPIDER_LOG_CHARS。抓取失败时这些日志以 `[spider-js]` 前缀写入日志文件。
+// ========================================================================
+
+/// 页面脚本日志的字符数上限，超出后的行丢弃
+const MAX_SPIDER_LOG_CHARS: usize = 4 * 1024;
+const SPIDER_LOG_TRUNCATED: &str = "…（页面日志已截断）";
+
+/// 追加一行页面日志，总长超过上限后只补一条截断标记
+fn push_log(logs: &mut Vec<String>, line: String) {
+    if logs.last().is_some_and(|last| last == SPIDER_LOG_TRUNCATED) {
+        return;
     }
+    let used: usize = logs.iter().map(|l| l.chars().count()).sum();
+    if used + line.chars().count() > MAX_SPIDER_LOG_CHARS {
+        logs.push(SPIDER_LOG_TRUNCATED.to_string());
+    } else {
+        logs.push(line);
+    }
+}
+
+fn capped_logs(lines: Vec<String>) -> Vec<String> {
+    let mut logs = Vec::new();
+    for line in lines {
+        push_log(&mut logs, line);
+    }
+    logs
+}
+
+/// 抓取失败时把页面日志写入日志文件；超时、WAF 等只带字符串说明的错误附上页面最后的状态
+pub fn attach_page_logs(error: SpiderError, url: &str, logs: &[String]) -> SpiderError {
+    let Some(last) = logs.iter().rev().find(|l| l.as_str() != SPIDER_LOG_TRUNCATED) else {
+        return error;
+    };
+    crate::log_to_file(&format!("[spider-js] {} 抓取失败（{}），页面日志 {} 行:", url, error, logs.len()));
+    for line in logs {
+        crate::log_to_file(&format!("[spider-js] {}", line));
+    }
+    let hint = |detail: String| format!("{}；页面最后状态: {}", detail, last);
+    match error {
+        SpiderError::Timeout(detail) => SpiderError::Timeout(hint(detail)),
+        SpiderError::WafBlocked(detail) => SpiderError::WafBlocked(hint(detail)),
+        SpiderError::Other(detail) => SpiderError::Other(hint(detail)),
+        other => other,
+    }
+}
+
+/// init script 经事件通道发回的消息：整页、分片、分片结束标记或一行页面日志
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SpiderMessage {
+    Part { id: String, part: usize, total: usize, data: String },
+    Done {
+        id: String,
+        done: bool,
+        total: usize,
+        url: Option<String>,
+        #[serde(default)]
+        logs: Vec<String>,
+    },
+    Whole {
+        id: String,
+        html: String,
+        /// 页面的 location.href（跳转后的最终地址）
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        logs: Vec<String>,
+    },
+    Log { id: String, log: String },
+}
+
+/// 重组事件通道的分片，只接受本次请求 id 的消息
+struct PartAssembler {
+    request_id: String,
+    parts: Vec<Option<String>>,
+    received: usize,
+    limit: usize,
+    /// 逐行收到的页面日志，页面没能传回时用于排查
+    logs: Vec<String>,
+}
+
+impl PartAssembler {
+    fn new(request_id: String, limit: usize) -> Self {
+        PartAssembler { request_id, parts: Vec::new(), received: 0, limit, logs: Vec::new() }
+    }
+
+    /// 整页 / 结束标记带的日志是完整的一份，优先于逐行收到的
+    fn take_logs(&mut self, logs: Vec<String>) -> Vec<String> {
+        let collected = std::mem::take(&mut self.logs);
+        if logs.is_empty() {
+            collected
+        } else {
+            capped_logs(logs)
+        }
+    }
+
+    /// 处理一条原始事件 payload。得到整页（或确定失败）时返回结果，其余情况返回 None 继续等待。
+    fn accept(&mut self, raw: &str) -> Option<Result<FetchedPage, SpiderError>> {
+        // 超过上限的整页在反序列化前拒绝，避免再复制一份
+        if raw.len() > self.limit {
+            return Some(Err(SpiderError::PageTooLarge { bytes: raw.len(), limit: self.limit }));
+        }
+        let message = serde_json::from_str::<SpiderMessage>(raw).ok()?;
+        match message {
+            SpiderMessage::Whole { id, html, url, logs } if id == self.request_id => {
+                Some(Ok(FetchedPage { html, final_url: url.filter(|u| !u.is_empty()), logs: self.take_logs(logs) }))
+            }
+            SpiderMessage::Part { id, part, total, data } if id == self.request_id => {
+                if total == 0 || part >= total {
+                    return None;
+                }
+                if self.parts.len() != total {
+                    self.parts = vec![None; total];
+                    self.received = 0;
+                }
+                if self.parts[part].is_none() {
+                    self.received += data.len();
+                }
+                if self.received > self.limit {
+                    let received = self.received;
+                    self.parts = Vec::new();
+                    self.received = 0;
+                    return Some(Err(SpiderError::PageTooLarge { bytes: received, limit: self.limit }));
+                }
+                self.parts[part] = Some(data);
+                None
+            }
+            SpiderMessage::Done { id, done: true, total, url, logs } if id == self.request_id => {
+                if self.parts.len() != total {
+                    self.parts = vec![None; total];
+                }
+                let missing: Vec<usize> = (0..total).filter(|&i| self.parts[i].is_none()).collect();
+                if !missing.is_empty() {
+                    return Some(Err(SpiderError::PayloadIncomplete { missing, total }));
+                }
+                let mut html = String::with_capacity(self.received);
+                for part in self.parts.drain(..).flatten() {
+                    html.push_str(&part);
+                }
+                self.received = 0;
+                Some(Ok(FetchedPage { html, final_url: url.filter(|u| !u.is_empty()), logs: self.take_logs(logs) }))
+            }
+            SpiderMessage::Log { id, log } if id == self.request_id => {
+                push_log(&mut self.logs, log);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 蜘蛛窗口抓到的页面
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchedPage {
+    pub html: String,
+    /// 跳转后的最终地址；经 document.title 备用通道传回时未知
+    pub final_url: Option<String>,
+    /// 页面脚本的日志（状态切换），页面内容有问题时可经 [`attach_page_logs`] 附到错误上
+    pub logs: Vec<String>,
+}
+
+/// 浏览器页面抓取。真实实现是 [`fetch_via_window`] 的隐藏爬虫窗口，
+/// 测试中可换成按 URL 读取夹具文件的实现，起点的解析逻辑不必依赖 webview
+pub trait PageFetcher: Sync {
+    fn fetch_page(&self, url: &str, debug_visible: bool) -> impl Future<Output = Result<FetchedPage, SpiderError>> + Send;
+
+    /// 网络暂停时请求开始排队（waiting 为等待中的请求数），见 [`crate::network_mode`]
+    fn network_waiting(&self, _url: &str, _waiting: usize) {}
+}
+
+impl PageFetcher for AppHandle {
+    fn fetch_page(&self, url: &str, debug_visible: bool) -> impl Future<Output = Result<FetchedPage, SpiderError>> + Send {
+        fetch_via_window(self, url, debug_visible)
+    }
+
+    fn network_waiting(&self, url: &str, waiting: usize) {
+        crate::progress::emit_progress(self, "waiting", format!("网络访问已暂停，等待恢复（{} 个请求排队）: {}", waiting, url));
+    }
+}
+
+impl<T: PageFetcher + ?Sized> PageFetcher for &T {
+    fn fetch_page(&self, url: &str, debug_visible: bool) -> impl Future<Output = Result<FetchedPage, SpiderError>> + Send {
+        (**self).fetch_page(url, debug_visible)
+    }
+
+    fn network_waiting(&self, url: &str, waiting: usize) {
+        (**self).network_waiting(url, waiting)
+    }
+}
+
+pub async fn fetch_via_window(app: &AppHandle, url: &str, debug_visible: bool) -> Result<FetchedPage, SpiderError> {
+    // 网络暂停时在占用窗口许可之前排队，不占着窗口等
+    crate::network_mode::gate(url, |waiting| app.network_waiting(url, waiting)).await.map_err(SpiderError::Other)?;
+    let budget = window_budget();
+    let limit = crate::settings::load(&crate::get_workspace_root(app)).spider_window_budget();
+    if limit != budget.metrics().budget {
+        budget.set_limit(limit);
+    }
+    let _permit = budget
+        .acquire(|queued| {
+            crate::progress::emit_progress(
+                app,
+                "queued",
+                format!("爬虫窗口已达上限 {}，排队等待（队列 {}）: {}", limit, queued, url),
+            );
+        })
+        .await;
+
+    static NEXT_WINDOW: AtomicU64 = AtomicU64::new(1);
+    let id = NEXT_WINDOW.fetch_add(1, Ordering::Relaxed);
+    let label = format!("{}_{}", WINDOW_LABEL_PREFIX, id);
+    let label = label.as_str();
+    // 每个窗口用独立的事件名，并发抓取互不串台
+    let response_event = format!("spider_response_{}", id);
 
     // Prepare channel for async result
     let (tx, rx) = oneshot::channel();
@@ -29,44 +501,121 @@ pub async fn fetch_via_window(app: &AppHandle, url: &str, debug_visible: bool) -
     // We'll use `listen` and a unique event name per request or just generic.
     // For simplicity, generic event "spider_response".
     
-    let event_id = app.listen("spider_response", move |event| {
-        if let Ok(payload) = serde_json::from_str::<SpiderResult>(event.payload()) {
-            if let Ok(mut guard) = tx_clone.lock() {
-                if let Some(sender) = guard.take() {
-                    let _ = sender.send(Ok(payload.html));
-                }
+    let max_html_bytes = crate::spiders::max_html_bytes();
+    // 本次请求的 id：同一窗口重新加载时，只认这次注入脚本发出的消息
+    let request_id = format!("{}-{}", id, chrono::Local::now().timestamp_nanos_opt().unwrap_or_default());
+    let parts = std::sync::Arc::new(Mutex::new(PartAssembler::new(request_id.clone(), max_html_bytes)));
+    let listener_parts = parts.clone();
+    let event_id = app.listen(response_event.clone(), move |event| {
+        let Some(result) = listener_parts.lock().ok().and_then(|mut parts| parts.accept(event.payload())) else {
+            return;
+        };
+        if let Ok(mut guard) = tx_clone.lock() {
+            if let Some(sender) = guard.take() {
+                let _ = sender.send(result);
             }
         }
     });
 
+    // 事件桥缺失信号 / 备用通道分块，经 document.title 传回
+    let (title_tx, mut title_rx) = mpsc::unbounded_channel::<TitleSignal>();
+    let closed_tx = title_tx.clone();
+    let assembler = Mutex::new(ChunkAssembler::new(max_html_bytes));
+    let on_title = move |_window: tauri::WebviewWindow, title: String| {
+        if title == NO_BRIDGE_MARKER {
+            let _ = title_tx.send(TitleSignal::NoBridge);
+        } else if let Some(payload) = title.strip_prefix(CHUNK_PREFIX) {
+            let html = assembler.lock().ok().and_then(|mut a| a.push(payload));
+            if let Some(result) = html {
+                let _ = title_tx.send(TitleSignal::Html(result));
+            }
+        }
+    };
+
+    // 站点配置的前置动作（点掉年龄确认 / 同意条款遮罩），见 spiders::site_options
+    let options = crate::spiders::site_options::for_url(url);
+    let pre_actions = serde_json::to_string(&options.pre_actions).unwrap_or_else(|_| "[]".to_string());
+
     // Initialization script:
+    // - Detects a missing Tauri event API and signals it via document.title.
     // - Waits for DOM ready or load.
+    // - Runs the configured pre-actions (click through consent / age gates) before looking for content.
     // - Uses a hard fallback to avoid hanging if some resources block the load event.
     // - Emits once with the page HTML.
     let init_script = r#"
         (() => {
+            if (!window.__TAURI__?.event?.emit) {
+                console.warn('[Spider] Tauri event API unavailable, signalling via document.title');
+                const signal = () => { document.title = '__SPIDER_NO_BRIDGE__'; };
+                if (document.readyState === 'loading') {
+                    document.addEventListener('DOMContentLoaded', signal, { once: true });
+                } else {
+                    signal();
+                }
+                return;
+            }
+
             let sent = false;
-            const emitOnce = () => {
+            const id = '__SPIDER_REQUEST_ID__';
+            const emit = (payload) => window.__TAURI__.event.emit('__SPIDER_RESPONSE_EVENT__', payload);
+            // Log state transitions only (repeated checks of the same state are dropped). Each line is
+            // sent back right away and the whole buffer rides along with the page, capped in size.
+            const logs = [];
+            let logChars = 0;
+            let lastState = '';
+            const log = (state, detail) => {
+                if (state === lastState) return;
+                lastState = state;
+                const line = `+${Math.round(performance.now())}ms ${state}${detail === undefined ? '' : ' ' + detail}`;
+                console.log('[Spider]', line);
+                if (logChars + line.length > __SPIDER_LOG_CHARS__) return;
+                logChars += line.length;
+                logs.push(line);
+                emit({ id, log: line }).catch(() => {});
+            };
+            const emitOnce = async () => {
                 if (sent) return;
                 sent = true;
+                let html = '';
+                try {
+                    html = document.documentElement?.outerHTML || document.body?.outerHTML || '';
+                } catch (e) {
+                    log('html-error', String(e));
+                }
                 try {
-                    const html = document.documentElement?.outerHTML || document.body?.outerHTML || '';
-                    console.log('[Spider] Sending HTML, length:', html.length);
-                    window.__TAURI__?.event?.emit('spider_response', { html });
+                    if (html.length <= __SPIDER_CHUNK_THRESHOLD__) {
+                        log('sending', `${html.length} chars`);
+                        await emit({ id, html, url: location.href, logs });
+                        return;
+                    }
+                    // Oversized IPC messages are silently dropped on WebView2: send numbered parts
+                    // sequentially, never splitting a surrogate pair, then a done marker.
+                    const size = __SPIDER_CHUNK_CHARS__;
+                    const bounds = [];
+                    for (let start = 0; start < html.length;) {
+                        let end = Math.min(start + size, html.length);
+                        const code = html.charCodeAt(end - 1);
+                        if (end < html.length && code >= 0xD800 && code <= 0xDBFF) end -= 1;
+                        bounds.push([start, end]);
+                        start = end;
+                    }
+                    log('sending-parts', `${bounds.length} parts, ${html.length} chars`);
+                    for (let i = 0; i < bounds.length; i++) {
+                        await emit({ id, part: i, total: bounds.length, data: html.slice(bounds[i][0], bounds[i][1]) });
+                    }
+                    await emit({ id, done: true, total: bounds.length, url: location.href, logs });
                 } catch (e) {
-                    console.error('[Spider] Error getting HTML:', e);
-                    window.__TAURI__?.event?.emit('spider_response', { html: '' });
+                    log('send-error', String(e));
                 }
             };
 
             const scheduleSend = (delay) => {
-                console.log('[Spider] Scheduling send in', delay, 'ms');
+                log('scheduled', `${delay}ms`);
                 setTimeout(emitOnce, delay);
             };
 
             // Wait for Qidian specific elements, but don't wait forever
             const checkAndSend = () => {
-                 console.log('[Spider] Checking for Qidian elements...');
                  // Check for various Qidian page elements:
                  // - .y-list__item: Mobile catalog list items
                  // - .chapter-li-a: Desktop catalog links
@@ -80,59 +629,347 @@ pub async fn fetch_via_window(app: &AppHandle, url: &str, debug_visible: bool) -
                  const mainContent = document.querySelector('main.content');
                  
                  if (catalogMobile || catalogDesktop || chapterTitle || bookIntro || mainContent) {
-                     console.log('[Spider] Found Qidian element, scheduling send in 2s');
+                     log('elements-found');
                      scheduleSend(2000); // Wait 2s for full render after finding key elements
                  } else {
                      // Fallback: send after reasonable wait
-                     console.log('[Spider] No specific elements found, using fallback');
+                     log('elements-missing');
                      scheduleSend(5000);
                  }
             };
-            
-            console.log('[Spider] Init script loaded, readyState:', document.readyState);
+
+            // Pre-actions: poll briefly for each element (overlays are often injected after DOM ready),
+            // click it and wait. A missing element is logged and skipped, never fatal.
+            const preActions = __SPIDER_PRE_ACTIONS__;
+            const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+            const runPreActions = async () => {
+                for (let i = 0; i < preActions.length; i++) {
+                    const { selector, action, wait_ms } = preActions[i];
+                    let el = null;
+                    for (let waited = 0; !el && waited <= __SPIDER_PRE_ACTION_FIND_MS__; waited += 100) {
+                        el = document.querySelector(selector);
+                        if (!el) await sleep(100);
+                    }
+                    if (!el) {
+                        log(`pre-action-${i}-missing`, selector);
+                        continue;
+                    }
+                    try {
+                        if (action === 'click') el.click();
+                        log(`pre-action-${i}-done`, `${action} ${selector}`);
+                    } catch (e) {
+                        log(`pre-action-${i}-error`, `${selector} ${e}`);
+                    }
+                    await sleep(wait_ms || 0);
+                }
+            };
+            const onReady = async () => {
+                if (preActions.length) await runPreActions();
+                checkAndSend();
+            };
+
+            log('loaded', `${document.readyState} ${location.href}`);
             
             if (document.readyState === 'complete' || document.readyState === 'interactive') {
-                checkAndSend();
+                onReady();
             } else {
-                window.addEventListener('DOMContentLoaded', checkAndSend, { once: true });
+                window.addEventListener('DOMContentLoaded', onReady, { once: true });
             }
 
-            // Hard fallback in case nothing triggers
-            scheduleSend(10000);
+            // Hard fallback in case nothing triggers (pushed back by the time pre-actions may take)
+            const preActionBudget = preActions.reduce((sum, a) => sum + __SPIDER_PRE_ACTION_FIND_MS__ + (a.wait_ms || 0), 0);
+            scheduleSend(10000 + preActionBudget);
         })();
-    "#;
-
-    // 105. Create or Reuse window
-    if let Some(w) = app.get_webview_window(label) {
-        println!("[Spider] Reusing existing window for {}", url);
-        w.navigate(url.parse().map_err(|e: url::ParseError| e.to_string())?)
-            .map_err(|e| format!("Failed to navigate window: {}", e))?;
-    } else {
-        println!("[Spider] Creating new window for {}", url);
-        let window_builder = WebviewWindowBuilder::new(app, label, WebviewUrl::External(url.parse().map_err(|e: url::ParseError| e.to_string())?))
-            .title("Spider Worker")
-            .visible(debug_visible) 
-            .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-            .initialization_script(init_script);
+    "#
+    .replace("__SPIDER_RESPONSE_EVENT__", &response_event)
+    .replace("__SPIDER_REQUEST_ID__", &request_id)
+    .replace("__SPIDER_CHUNK_THRESHOLD__", &EVENT_CHUNK_THRESHOLD.to_string())
+    .replace("__SPIDER_CHUNK_CHARS__", &EVENT_CHUNK_CHARS.to_string())
+    .replace("__SPIDER_LOG_CHARS__", &MAX_SPIDER_LOG_CHARS.to_string())
+    .replace("__SPIDER_PRE_ACTION_FIND_MS__", &PRE_ACTION_FIND_MS.to_string())
+    .replace("__SPIDER_PRE_ACTIONS__", &pre_actions);
 
-        let _window = window_builder.build().map_err(|e| format!("Failed to create window: {}", e))?;
-    }
+    println!("[Spider] Creating window {} for {}", label, url);
+    let parsed_url: url::Url = match url.parse() {
+        Ok(u) => u,
+        Err(e) => {
+            app.unlisten(event_id);
+            return Err(SpiderError::Other(format!("无效的地址 {}: {}", url, e)));
+        }
+    };
+    let window_builder = WebviewWindowBuilder::new(app, label, WebviewUrl::External(parsed_url))
+        .title("Spider Worker")
+        .visible(debug_visible)
+        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .initialization_script(&init_script)
+        .on_document_title_changed(on_title);
+    let window = match window_builder.build() {
+        Ok(w) => w,
+        Err(e) => {
+            app.unlisten(event_id);
+            return Err(SpiderError::Other(format!("Failed to create window: {}", e)));
+        }
+    };
+    window.on_window_event(move |event| {
+        if matches!(event, tauri::WindowEvent::Destroyed) {
+            let _ = closed_tx.send(TitleSignal::Closed);
+        }
+    });
 
     // Wait for result with timeout (可配置)
     let timeout_secs = std::env::var("SPIDER_TIMEOUT")
         .ok()
         .and_then(|s| s.parse::<u64>().ok())
         .unwrap_or(60);
-    let result = tokio::select! {
-        res = rx => {
-            app.unlisten(event_id);
-            res.map_err(|_| "Channel closed".to_string())?
-        }
-        _ = tokio::time::sleep(Duration::from_secs(timeout_secs)) => {
-            app.unlisten(event_id);
-            Err(format!("Timeout waiting for spider ({}s)", timeout_secs))
+    let overall = tokio::time::sleep(Duration::from_secs(timeout_secs));
+    tokio::pin!(overall);
+    let mut rx = rx;
+    let mut probe_deadline: Option<tokio::time::Instant> = None;
+
+    let result = loop {
+        let probe = async {
+            match probe_deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            res = &mut rx => {
+                record_bridge("event", url);
+                break res.map_err(|_| SpiderError::Other("Channel closed".to_string())).and_then(|r| r);
+            }
+            Some(signal) = title_rx.recv() => match signal {
+                TitleSignal::NoBridge if probe_deadline.is_none() => {
+                    eprintln!("[Spider] 页面中没有 Tauri 事件 API，改用 document.title 备用通道: {}", url);
+                    crate::log_to_file(&format!("[Spider] event bridge unavailable, trying title fallback: {}", url));
+                    probe_deadline = Some(tokio::time::Instant::now() + BRIDGE_PROBE_TIMEOUT);
+                    if let Some(w) = app.get_webview_window(label) {
+                        let _ = w.eval(FALLBACK_SCRIPT);
+                    }
+                }
+                TitleSignal::NoBridge => {}
+                TitleSignal::Html(result) => {
+                    record_bridge("title_fallback", url);
+                    break result.map(|html| FetchedPage { html, ..Default::default() });
+                }
+                TitleSignal::Closed => {
+                    break Err(SpiderError::Other("爬虫窗口已被关闭".to_string()));
+                }
+            },
+            _ = probe => {
+                record_bridge("unavailable", url);
+                break Err(SpiderError::EventBridgeUnavailable);
+            }
+            _ = &mut overall => {
+                break Err(SpiderError::Timeout(format!("Timeout waiting for spider ({}s)", timeout_secs)));
+            }
         }
     };
-    
-    result
+    app.unlisten(event_id);
+    let _ = window.destroy();
+
+    let logs = parts.lock().map(|mut parts| std::mem::take(&mut parts.logs)).unwrap_or_default();
+    result.map_err(|e| attach_page_logs(e, url, &logs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_chunks_reassemble_out_of_order() {
+        let mut a = ChunkAssembler::new(1024);
+        assert_eq!(a.push("1:2:%E7%AB%A0%3C%2Fp%3E"), None);
+        assert_eq!(a.push("garbage"), None);
+        assert_eq!(a.push("0:2:%3Cp%3E%E7%AC%AC%E4%B8%80"), Some(Ok("<p>第一章</p>".to_string())));
+    }
+
+    /// 按 init script 的方式把页面切成事件消息（按 UTF-16 码元计数，不拆代理对）
+    fn event_parts(id: &str, html: &str) -> Vec<String> {
+        let units: Vec<u16> = html.encode_utf16().collect();
+        let mut bounds = Vec::new();
+        let mut start = 0;
+        while start < units.len() {
+            let mut end = (start + EVENT_CHUNK_CHARS).min(units.len());
+            if end < units.len() && (0xD800..=0xDBFF).contains(&units[end - 1]) {
+                end -= 1;
+            }
+            bounds.push((start, end));
+            start = end;
+        }
+        let total = bounds.len();
+        let mut messages: Vec<String> = bounds
+            .iter()
+            .enumerate()
+            .map(|(part, &(s, e))| {
+                let data = String::from_utf16(&units[s..e]).unwrap();
+                serde_json::json!({ "id": id, "part": part, "total": total, "data": data }).to_string()
+            })
+            .collect();
+        messages.push(serde_json::json!({ "id": id, "done": true, "total": total, "url": "https://example.com/big" }).to_string());
+        messages
+    }
+
+    #[test]
+    fn event_parts_reassemble_multi_megabyte_page() {
+        // 约 3 MB 的合成页面，含中文和代理对，写到本地文件再读回
+        let line = "<p>第一章 风起😀云涌 the quick brown fox</p>\n";
+        let page = format!("<html><body>{}</body></html>", line.repeat(3 * 1024 * 1024 / line.len()));
+        let file = std::env::temp_dir().join(format!("test_spider_big_page_{}.html", std::process::id()));
+        std::fs::write(&file, &page).unwrap();
+        let html = std::fs::read_to_string(&file).unwrap();
+        let _ = std::fs::remove_file(&file);
+        assert!(html.encode_utf16().count() > EVENT_CHUNK_THRESHOLD);
+
+        let messages = event_parts("7-1", &html);
+        assert!(messages.len() > 3);
+        let mut parts = PartAssembler::new("7-1".to_string(), 20 * 1024 * 1024);
+        // 旧请求残留的分片不影响本次
+        assert!(parts.accept(&serde_json::json!({ "id": "6-1", "part": 0, "total": 1, "data": "stale" }).to_string()).is_none());
+        assert!(parts.accept(&serde_json::json!({ "id": "6-1", "done": true, "total": 1 }).to_string()).is_none());
+        let (last, body) = messages.split_last().unwrap();
+        for message in body.iter().rev() {
+            assert!(parts.accept(message).is_none());
+        }
+        let page = parts.accept(last).unwrap().unwrap();
+        assert!(page.html.as_bytes() == html.as_bytes(), "重组后的页面与原页面逐字节一致");
+        assert_eq!(page.final_url.as_deref(), Some("https://example.com/big"));
+
+        // 丢了一片：收到结束标记时报错，不再等到超时
+        let mut parts = PartAssembler::new("7-1".to_string(), 20 * 1024 * 1024);
+        for (i, message) in messages.iter().enumerate() {
+            if i == 1 {
+                continue;
+            }
+            if let Some(result) = parts.accept(message) {
+                assert_eq!(result, Err(SpiderError::PayloadIncomplete { missing: vec![1], total: messages.len() - 1 }));
+                return;
+            }
+        }
+        panic!("缺片时应在结束标记处报错");
+    }
+
+    #[test]
+    fn whole_page_messages_check_request_id_and_size() {
+        let mut parts = PartAssembler::new("1-1".to_string(), 64);
+        let whole = |id: &str| serde_json::json!({ "id": id, "html": "<p>ok</p>", "url": "" }).to_string();
+        assert!(parts.accept(&whole("0-9")).is_none());
+        assert_eq!(
+            parts.accept(&whole("1-1")),
+            Some(Ok(FetchedPage { html: "<p>ok</p>".to_string(), ..Default::default() }))
+        );
+        let big = serde_json::json!({ "id": "1-1", "html": "x".repeat(100) }).to_string();
+        assert!(matches!(parts.accept(&big), Some(Err(SpiderError::PageTooLarge { limit: 64, .. }))));
+    }
+
+    #[test]
+    fn page_logs_are_collected_capped_and_attached_to_errors() {
+        let mut parts = PartAssembler::new("2-1".to_string(), 1024 * 1024);
+        let log = |id: &str, line: &str| serde_json::json!({ "id": id, "log": line }).to_string();
+        assert!(parts.accept(&log("2-1", "+5ms loaded complete")).is_none());
+        assert!(parts.accept(&log("0-9", "+1ms stale")).is_none());
+        assert_eq!(parts.logs, vec!["+5ms loaded complete"]);
+
+        // 整页带的日志是完整的一份，替换逐行收到的
+        let whole = serde_json::json!({ "id": "2-1", "html": "<p>ok</p>", "logs": ["+5ms loaded complete", "+2010ms sending 9 chars"] });
+        let page = parts.accept(&whole.to_string()).unwrap().unwrap();
+        assert_eq!(page.logs.len(), 2);
+        assert!(parts.logs.is_empty());
+
+        let long = "x".repeat(MAX_SPIDER_LOG_CHARS / 2 + 1);
+        let capped = capped_logs(vec![long.clone(), long.clone(), long]);
+        assert_eq!(capped.len(), 2);
+        assert_eq!(capped[1], SPIDER_LOG_TRUNCATED);
+
+        let error = attach_page_logs(SpiderError::Timeout("60s".to_string()), "https://example.com", &page.logs);
+        assert_eq!(error, SpiderError::Timeout("60s；页面最后状态: +2010ms sending 9 chars".to_string()));
+        assert_eq!(attach_page_logs(SpiderError::EventBridgeUnavailable, "https://example.com", &[]), SpiderError::EventBridgeUnavailable);
+    }
+
+    #[test]
+    fn oversized_pages_are_rejected() {
+        let mut a = ChunkAssembler::new(8);
+        assert_eq!(a.push("0:3:abcdefghij"), None);
+        assert_eq!(a.push("1:3:abcdefghij"), None);
+        assert!(matches!(a.push("2:3:abcdefghij"), Some(Err(SpiderError::PageTooLarge { limit: 8, .. }))));
+        // 编码未超限、解码后超限
+        let mut a = ChunkAssembler::new(8);
+        assert!(matches!(a.push("0:1:0123456789"), Some(Err(SpiderError::PageTooLarge { bytes: 10, limit: 8 }))));
+        assert_eq!(check_html_size("<p></p>".to_string(), 8), Ok("<p></p>".to_string()));
+    }
+
+    #[tokio::test]
+    async fn concurrent_fetches_never_exceed_window_budget() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
+
+        let budget: &'static WindowBudget = Box::leak(Box::new(WindowBudget::new(2)));
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let queued_events = Arc::new(AtomicUsize::new(0));
+        let mut handles = Vec::new();
+        for i in 0..24 {
+            let (active, peak, queued_events) = (active.clone(), peak.clone(), queued_events.clone());
+            handles.push(tokio::spawn(async move {
+                let _permit = budget.acquire(|_| {
+                    queued_events.fetch_add(1, Ordering::SeqCst);
+                }).await;
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                assert!(budget.metrics().open_windows <= 2);
+                // 模拟窗口加载一个 data: 页面
+                let page: url::Url = format!("data:text/html,<p>{}</p>", i).parse().unwrap();
+                assert_eq!(page.scheme(), "data");
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert!(queued_events.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            budget.metrics(),
+            WindowMetrics { open_windows: 0, queue_length: 0, budget: 2, background_windows: 0, background_queued: 0 }
+        );
+    }
+
+    #[tokio::test]
+    async fn aborted_fetches_release_permits_and_queue_slots() {
+        let budget: &'static WindowBudget = Box::leak(Box::new(WindowBudget::new(1)));
+        let holder = tokio::spawn(async move {
+            let _permit = budget.acquire(|_| {}).await;
+            std::future::pending::<()>().await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let waiter = tokio::spawn(async move {
+            let _permit = budget.acquire(|_| {}).await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(budget.metrics().queue_length, 1);
+
+        // 强制中止持有窗口的抓取：许可归还，排队的抓取随即拿到窗口
+        holder.abort();
+        let _ = holder.await;
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(budget.metrics().open_windows, 0);
+        assert_eq!(budget.metrics().queue_length, 0);
+
+        // 排队中被取消也要扣回队列长度
+        let _held = budget.acquire(|_| {}).await;
+        let queued = tokio::spawn(async move {
+            let _permit = budget.acquire(|_| {}).await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        queued.abort();
+        let _ = queued.await;
+        assert_eq!(budget.metrics().queue_length, 0);
+    }
+
+    #[test]
+    fn percent_decode_keeps_malformed_escapes() {
+        assert_eq!(percent_decode("100%25%20ok%"), "100% ok%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
 }
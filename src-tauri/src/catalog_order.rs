@@ -0,0 +1,719 @@
+//! 目录顺序校验：从标题中提取章节号（"第N章"，阿拉伯或中文数字），检查顺序、断档和重复。
+//!
+//! 起点移动端目录偶尔乱序，或把"作品相关"穿插在正文中间，而文件序号按遍历顺序分配，
+//! 导致下载结果与原书顺序不符。章节号可靠（绝大多数章节有号且无重复）时按章节号重排，
+//! 无号的条目排在最后；不可靠时保持原顺序，只报告异常。
+//!
+//! 有的书每卷各自从第 1 章编号（第一卷 第1章 … 第二卷 第1章）。目录带卷名时，卷名变化且章节号回到
+//! 第 1 章处分段；没有卷名时，章节号回到 1、上一段已有至少 [`MIN_CHAPTERS_BEFORE_RESET`] 章且下一章
+//! 是第 2 章时推断为新卷。顺序、重复和断档都在段内检查，全书顺序为 (卷, 章)，分卷重置单独列出，
+//! 不算作重复。
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::chapter_index::{ChapterIndex, CHAPTERS_FILE};
+use crate::library;
+
+/// 有章节号的条目占比不低于该值才认为章节号可靠
+const MIN_NUMBERED_RATIO: f64 = 0.8;
+/// 没有卷名时推断分卷：上一卷至少要有这么多章，避免把开头的误编号当成新卷
+pub const MIN_CHAPTERS_BEFORE_RESET: u64 = 3;
+
+fn chapter_number_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"第\s*([0-9０-９零〇一二两三四五六七八九十百千万]+)\s*[章回节]").unwrap())
+}
+
+/// 标题中的章节号
+pub fn chapter_number(title: &str) -> Option<u64> {
+    let raw = chapter_number_re().captures(title)?.get(1)?.as_str();
+    if raw.chars().all(|c| c.is_ascii_digit() || ('０'..='９').contains(&c)) {
+        let digits: String = raw
+            .chars()
+            .map(|c| if c.is_ascii_digit() { c } else { char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or('0') })
+            .collect();
+        return digits.parse().ok();
+    }
+    parse_chinese_number(raw)
+}
+
+/// 没有章节号且标题含任一关键词的条目视为公告类非正文（上架感言、请假条等）
+pub fn is_extra_title(title: &str, patterns: &[String]) -> bool {
+    chapter_number(title).is_none() && patterns.iter().any(|p| !p.trim().is_empty() && title.contains(p.trim()))
+}
+
+/// 标题的信息量：去掉"第N章"后剩余的文字数（数字、标点不算），有章节号再加一分。
+/// 抓不到标题时的占位链接为 0
+pub fn title_score(title: &str) -> usize {
+    let title = title.trim();
+    if title.starts_with("http://") || title.starts_with("https://") {
+        return 0;
+    }
+    let rest = chapter_number_re().replace(title, "");
+    let words = rest.chars().filter(|c| c.is_alphabetic()).count();
+    words * 2 + usize::from(chapter_number(title).is_some())
+}
+
+/// 目录标题和章节页标题中信息量更高的一个；持平时信任目录
+pub fn pick_title<'a>(catalog_title: &'a str, page_title: Option<&'a str>) -> &'a str {
+    match page_title.map(str::trim) {
+        Some(page) if title_score(page) > title_score(catalog_title) => page,
+        _ => catalog_title,
+    }
+}
+
+/// 章节文件头部使用的标题：目录标题（过长时按完整标题比较）与章节页标题取舍后的结果
+pub fn header_title(title: &str, full_title: Option<&str>, page_title: Option<&str>) -> String {
+    let catalog = full_title.unwrap_or(title);
+    let picked = pick_title(catalog, page_title);
+    if picked == catalog {
+        title.to_string()
+    } else {
+        crate::spiders::truncate_chars(picked, crate::spiders::MAX_TITLE_CHARS)
+    }
+}
+
+fn parse_chinese_number(s: &str) -> Option<u64> {
+    let (mut total, mut section, mut digit) = (0u64, 0u64, 0u64);
+    for c in s.chars() {
+        let d = match c {
+            '零' | '〇' => Some(0),
+            '一' => Some(1),
+            '二' | '两' => Some(2),
+            '三' => Some(3),
+            '四' => Some(4),
+            '五' => Some(5),
+            '六' => Some(6),
+            '七' => Some(7),
+            '八' => Some(8),
+            '九' => Some(9),
+            _ => None,
+        };
+        if let Some(d) = d {
+            digit = d;
+            continue;
+        }
+        let unit = match c {
+            '十' => 10,
+            '百' => 100,
+            '千' => 1000,
+            '万' => 10_000,
+            _ => return None,
+        };
+        if unit == 10_000 {
+            total = (total + section + digit) * unit;
+            section = 0;
+        } else {
+            // "十五" 中省略的"一"
+            section += digit.max(1) * unit;
+        }
+        digit = 0;
+    }
+    Some(total + section + digit)
+}
+
+/// 目录异常。position 为目录中的位置（从 1 开始）；volume 为分卷各自编号时所在的卷（从 1 开始），
+/// 章节号全书连续时为 None。
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CatalogAnomaly {
+    /// 章节号小于（同一卷内）前面已出现的最大章节号
+    OutOfOrder {
+        position: usize,
+        number: u64,
+        previous: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        volume: Option<usize>,
+    },
+    /// 同一章节号在同一卷内出现多次
+    Duplicate {
+        number: u64,
+        positions: Vec<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        volume: Option<usize>,
+    },
+    /// 缺少 [from, to] 章
+    Gap {
+        from: u64,
+        to: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        volume: Option<usize>,
+    },
+    /// 夹在正文章节之间、没有章节号的条目（如"作品相关"）
+    Unnumbered { position: usize, title: String },
+}
+
+fn volume_prefix(volume: Option<usize>) -> String {
+    volume.map(|v| format!("第{}卷", v)).unwrap_or_default()
+}
+
+impl CatalogAnomaly {
+    pub fn describe(&self) -> String {
+        match self {
+            CatalogAnomaly::OutOfOrder { position, number, previous, volume } => {
+                let v = volume_prefix(*volume);
+                format!("第 {} 条为{}第{}章，排在{}第{}章之后（乱序）", position, v, number, v, previous)
+            }
+            CatalogAnomaly::Duplicate { number, positions, volume } => {
+                format!("{}第{}章重复出现于 {:?}", volume_prefix(*volume), number, positions)
+            }
+            CatalogAnomaly::Gap { from, to, volume } if from == to => format!("缺少{}第{}章", volume_prefix(*volume), from),
+            CatalogAnomaly::Gap { from, to, volume } => format!("缺少{}第{}-{}章", volume_prefix(*volume), from, to),
+            CatalogAnomaly::Unnumbered { position, title } => format!("第 {} 条「{}」没有章节号，夹在正文之间", position, title),
+        }
+    }
+}
+
+/// 分卷信息从哪里来
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeSource {
+    /// 目录页上的卷名
+    Catalog,
+    /// 目录没有卷名，按章节号回到第 1 章推断
+    Inferred,
+}
+
+/// 分卷各自编号：从目录第 position 条起进入第 volume 卷，章节号重新从第 1 章开始。不是异常
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct VolumeReset {
+    pub position: usize,
+    pub volume: usize,
+    /// 上一卷的最大章节号
+    pub previous: u64,
+    /// 目录中的卷名，推断的分卷没有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl VolumeReset {
+    pub fn describe(&self) -> String {
+        match &self.title {
+            Some(title) => format!("第 {} 条起为「{}」，章节号从第1章重新开始（上一卷到第{}章）", self.position, title, self.previous),
+            None => format!("第 {} 条起章节号回到第1章（上一卷到第{}章），推断为第{}卷", self.position, self.previous, self.volume),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CatalogOrder {
+    /// 每条目录的章节号，与输入顺序一致
+    pub numbers: Vec<Option<u64>>,
+    /// 每条目录所在的卷（从 1 开始），与 numbers 组成全书顺序；没有分卷重置时全部为 1
+    pub volumes: Vec<usize>,
+    pub anomalies: Vec<CatalogAnomaly>,
+    /// 分卷各自编号的重置点，不计入 anomalies
+    pub volume_resets: Vec<VolumeReset>,
+    /// 有分卷重置时分卷信息的来源
+    pub volume_source: Option<VolumeSource>,
+    /// 章节号是否足以决定顺序
+    pub reliable: bool,
+}
+
+/// 切分分卷重置，返回每条所在的卷、重置点和来源。见模块说明
+fn split_volumes(numbers: &[Option<u64>], volume_titles: &[Option<&str>]) -> (Vec<usize>, Vec<VolumeReset>, Option<VolumeSource>) {
+    let from_catalog = volume_titles.iter().any(Option::is_some);
+    let mut volumes = Vec::with_capacity(numbers.len());
+    let mut resets = Vec::new();
+    let mut volume = 1;
+    let mut volume_max: Option<u64> = None;
+    let mut current_title: Option<&str> = None;
+    let mut title_changed = false;
+    for (i, number) in numbers.iter().enumerate() {
+        let title = volume_titles.get(i).copied().flatten();
+        if title.is_some() && title != current_title {
+            current_title = title;
+            title_changed = true;
+        }
+        if let Some(n) = *number {
+            if let Some(max) = volume_max {
+                let reset = if from_catalog {
+                    title_changed && n <= 1 && n <= max
+                } else {
+                    n == 1
+                        && max >= MIN_CHAPTERS_BEFORE_RESET
+                        && numbers[i + 1..].iter().flatten().next().is_none_or(|&next| next == 2)
+                };
+                if reset {
+                    volume += 1;
+                    volume_max = None;
+                    resets.push(VolumeReset {
+                        position: i + 1,
+                        volume,
+                        previous: max,
+                        title: current_title.map(str::to_string),
+                    });
+                }
+            }
+            volume_max = Some(volume_max.map_or(n, |max| max.max(n)));
+            title_changed = false;
+        }
+        volumes.push(volume);
+    }
+    let source = match (resets.is_empty(), from_catalog) {
+        (true, _) => None,
+        (false, true) => Some(VolumeSource::Catalog),
+        (false, false) => Some(VolumeSource::Inferred),
+    };
+    (volumes, resets, source)
+}
+
+/// 检查目录标题序列（没有卷名，分卷重置靠推断）
+pub fn analyze<S: AsRef<str>>(titles: &[S]) -> CatalogOrder {
+    analyze_volumes(titles, &[])
+}
+
+/// 检查目录标题序列，volume_titles 为每条所属的卷名（与 titles 对应，可以为空）
+pub fn analyze_volumes<S: AsRef<str>>(titles: &[S], volume_titles: &[Option<&str>]) -> CatalogOrder {
+    let numbers: Vec<Option<u64>> = titles.iter().map(|t| chapter_number(t.as_ref())).collect();
+    let (volumes, volume_resets, volume_source) = split_volumes(&numbers, volume_titles);
+    let tag = |volume: usize| volume_source.is_some().then_some(volume);
+    let mut anomalies = Vec::new();
+
+    let mut previous: Option<(usize, u64)> = None;
+    let mut positions: BTreeMap<(usize, u64), Vec<usize>> = BTreeMap::new();
+    for (i, number) in numbers.iter().enumerate() {
+        let Some(n) = *number else { continue };
+        let volume = volumes[i];
+        positions.entry((volume, n)).or_default().push(i + 1);
+        match previous {
+            Some((v, p)) if v == volume && n < p => {
+                anomalies.push(CatalogAnomaly::OutOfOrder { position: i + 1, number: n, previous: p, volume: tag(volume) })
+            }
+            _ => previous = Some((volume, n)),
+        }
+    }
+
+    let duplicates: Vec<CatalogAnomaly> = positions
+        .iter()
+        .filter(|(_, p)| p.len() > 1)
+        .map(|(&(volume, number), p)| CatalogAnomaly::Duplicate { number, positions: p.clone(), volume: tag(volume) })
+        .collect();
+    let has_duplicates = !duplicates.is_empty();
+    anomalies.extend(duplicates);
+
+    let (mut volume, mut expected) = (1, 1);
+    for &(v, n) in positions.keys() {
+        if v != volume {
+            (volume, expected) = (v, 1);
+        }
+        if n > expected {
+            anomalies.push(CatalogAnomaly::Gap { from: expected, to: n - 1, volume: tag(v) });
+        }
+        expected = n + 1;
+    }
+
+    let first = numbers.iter().position(Option::is_some);
+    let last = numbers.iter().rposition(Option::is_some);
+    if let (Some(first), Some(last)) = (first, last) {
+        for i in first..last {
+            if numbers[i].is_none() {
+                anomalies.push(CatalogAnomaly::Unnumbered { position: i + 1, title: titles[i].as_ref().to_string() });
+            }
+        }
+    }
+
+    let numbered = numbers.iter().flatten().count();
+    let reliable = numbered >= 2 && !has_duplicates && numbered as f64 >= numbers.len() as f64 * MIN_NUMBERED_RATIO;
+    CatalogOrder { numbers, volumes, anomalies, volume_resets, volume_source, reliable }
+}
+
+impl CatalogOrder {
+    /// 章节号可靠且与原顺序不同时，返回重排后的原下标序列（从 0 开始）：有号的按 (卷, 章节号)，无号的按原顺序排在最后。
+    pub fn numeric_order(&self) -> Option<Vec<usize>> {
+        if !self.reliable {
+            return None;
+        }
+        let mut numbered: Vec<(usize, u64, usize)> =
+            self.numbers.iter().enumerate().filter_map(|(i, n)| n.map(|n| (self.volume_of(i), n, i))).collect();
+        numbered.sort_unstable();
+        let mut order: Vec<usize> = numbered.into_iter().map(|(_, _, i)| i).collect();
+        order.extend(self.numbers.iter().enumerate().filter(|(_, n)| n.is_none()).map(|(i, _)| i));
+        (order.iter().enumerate().any(|(pos, &i)| pos != i)).then_some(order)
+    }
+
+    fn volume_of(&self, i: usize) -> usize {
+        self.volumes.get(i).copied().unwrap_or(1)
+    }
+
+    /// 分卷各自编号时每条所在的卷，写入 chapters.json；没有分卷重置时为 None
+    pub fn volume_at(&self, i: usize) -> Option<usize> {
+        self.volume_source.map(|_| self.volume_of(i))
+    }
+
+    /// 每条目录对应的异常说明（与输入顺序一致），写入 chapters.json
+    pub fn notes(&self) -> Vec<Vec<String>> {
+        let mut notes = vec![Vec::new(); self.numbers.len()];
+        let position_of = |number: u64, volume: Option<usize>| {
+            (0..self.numbers.len()).find(|&i| self.numbers[i] == Some(number) && self.volume_of(i) == volume.unwrap_or(1))
+        };
+        for anomaly in &self.anomalies {
+            let position = match anomaly {
+                CatalogAnomaly::OutOfOrder { position, .. } | CatalogAnomaly::Unnumbered { position, .. } => Some(*position - 1),
+                CatalogAnomaly::Duplicate { number, volume, .. } => position_of(*number, *volume),
+                // 断档记在断档前一章上，缺开头时记在该卷第一个有号的章节上
+                CatalogAnomaly::Gap { from, volume, .. } => position_of(from.saturating_sub(1), *volume).or_else(|| {
+                    (0..self.numbers.len()).find(|&i| self.numbers[i].is_some() && self.volume_of(i) == volume.unwrap_or(1))
+                }),
+            };
+            if let Some(note) = position.and_then(|p| notes.get_mut(p)) {
+                note.push(anomaly.describe());
+            }
+        }
+        notes
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TitleMismatch {
+    pub index: usize,
+    pub catalog_title: String,
+    pub file_title: String,
+}
+
+/// validate_catalog 的结果：目录本身的异常，以及目录、索引与磁盘文件之间的不一致
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CatalogValidation {
+    pub chapters: usize,
+    /// 位置为 chapters.json 中的目录序号
+    pub anomalies: Vec<CatalogAnomaly>,
+    /// 分卷各自编号的重置点（位置为目录序号），与 anomalies 中真正的重复分开列出
+    pub volume_resets: Vec<VolumeReset>,
+    pub volume_source: Option<VolumeSource>,
+    pub reliable_numbering: bool,
+    /// 标记为已下载但文件缺失
+    pub missing_files: Vec<usize>,
+    /// 文件存在但索引未标记为已下载
+    pub untracked_files: Vec<usize>,
+    /// 目录中没有对应序号的章节文件
+    pub orphan_files: Vec<String>,
+    /// 文件头部标题与目录标题不一致（文件序号与目录错位）
+    pub title_mismatches: Vec<TitleMismatch>,
+}
+
+fn file_title(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    content.lines().next()?.strip_prefix("标题:").map(|t| t.trim().to_string())
+}
+
+/// 按已保存的 chapters.json 和磁盘文件重新校验
+pub fn validate_stored(novel_dir: &Path) -> Result<CatalogValidation, String> {
+    if !novel_dir.join(CHAPTERS_FILE).is_file() {
+        return Err(format!("{} 不存在，请先重新获取目录", CHAPTERS_FILE));
+    }
+    let index = ChapterIndex::load(novel_dir);
+    let records: Vec<_> = index.records().collect();
+    let titles: Vec<&str> = records.iter().map(|r| r.full_title.as_deref().unwrap_or(&r.title)).collect();
+    let volume_titles: Vec<Option<&str>> = records.iter().map(|r| r.volume_title.as_deref()).collect();
+    let order = analyze_volumes(&titles, &volume_titles);
+    // analyze 的位置是列表下标，换算为目录序号
+    let to_index = |position: usize| records.get(position - 1).map_or(position, |r| r.index);
+    let anomalies = order
+        .anomalies
+        .into_iter()
+        .map(|a| match a {
+            CatalogAnomaly::OutOfOrder { position, number, previous, volume } => {
+                CatalogAnomaly::OutOfOrder { position: to_index(position), number, previous, volume }
+            }
+            CatalogAnomaly::Duplicate { number, positions, volume } => {
+                CatalogAnomaly::Duplicate { number, positions: positions.into_iter().map(to_index).collect(), volume }
+            }
+            CatalogAnomaly::Unnumbered { position, title } => CatalogAnomaly::Unnumbered { position: to_index(position), title },
+            gap => gap,
+        })
+        .collect();
+    let volume_resets = order
+        .volume_resets
+        .into_iter()
+        .map(|reset| VolumeReset { position: to_index(reset.position), ..reset })
+        .collect();
+
+    let mut report = CatalogValidation {
+        chapters: records.len(),
+        anomalies,
+        volume_resets,
+        volume_source: order.volume_source,
+        reliable_numbering: order.reliable,
+        ..Default::default()
+    };
+    let mut on_disk = BTreeSet::new();
+    for (i, name) in crate::sharding::chapter_files(novel_dir) {
+        if index.get(i).is_some() {
+            on_disk.insert(i);
+        } else {
+            report.orphan_files.push(name);
+        }
+    }
+    report.orphan_files.sort();
+
+    for record in &records {
+        let exists = on_disk.contains(&record.index);
+        match (record.downloaded, exists) {
+            (true, false) => report.missing_files.push(record.index),
+            (false, true) => report.untracked_files.push(record.index),
+            _ => {}
+        }
+        if !exists {
+            continue;
+        }
+        let Some(found) = file_title(&index.chapter_path(record.index)) else { continue };
+        let header = header_title(&record.title, record.full_title.as_deref(), record.page_title.as_deref());
+        if found != record.title && found != header && Some(found.as_str()) != record.full_title.as_deref() {
+            report.title_mismatches.push(TitleMismatch { index: record.index, catalog_title: record.title.clone(), file_title: found });
+        }
+    }
+    Ok(report)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TitleChange {
+    pub index: usize,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct RetitleReport {
+    /// 检查过的已下载章节数
+    pub checked: usize,
+    pub changed: Vec<TitleChange>,
+    /// 文件缺失或与索引哈希不一致、未改写的章节
+    pub skipped: Vec<usize>,
+    /// 改写记入的修改日志条目，没有改动时为 None
+    pub edit_id: Option<String>,
+}
+
+/// 按 chapters.json 中保存的目录标题和章节页标题重新取舍，改写文件头部（正文不变）并更新哈希。不重新下载。
+/// 旧文件记入修改日志，见 [`crate::edit_journal`]
+pub fn retitle_stored(novel_dir: &Path, history_max_bytes: u64) -> Result<RetitleReport, String> {
+    if !novel_dir.join(CHAPTERS_FILE).is_file() {
+        return Err(format!("{} 不存在，请先重新获取目录", CHAPTERS_FILE));
+    }
+    let mut report = RetitleReport::default();
+    let mut edits = crate::edit_journal::Recorder::begin(novel_dir, crate::edit_journal::EditKind::Retitle, history_max_bytes)?;
+    ChapterIndex::update(novel_dir, |index| {
+        let records: Vec<_> = index.records().filter(|r| r.downloaded).cloned().collect();
+        for record in records {
+            report.checked += 1;
+            let path = index.chapter_path(record.index);
+            let file = if index.check(record.index).can_skip() {
+                fs::read_to_string(&path).ok().and_then(|text| library::ChapterFile::parse(&text))
+            } else {
+                None
+            };
+            let Some(mut file) = file else {
+                report.skipped.push(record.index);
+                continue;
+            };
+            let title = header_title(&record.title, record.full_title.as_deref(), record.page_title.as_deref());
+            if file.title == title {
+                continue;
+            }
+            let from = std::mem::replace(&mut file.title, title.clone());
+            let bytes = crate::storage::encode_text(&path, &file.render());
+            edits
+                .write(&record.relative_path(), &bytes)
+                .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+            index.mark_downloaded(record.index, &bytes);
+            report.changed.push(TitleChange { index: record.index, from, to: title });
+        }
+        Ok(())
+    })?;
+    report.edit_id = edits.finish()?.map(|entry| entry.id);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extra_titles_need_pattern_and_no_number() {
+        let patterns: Vec<String> = ["上架感言", "请假"].iter().map(|s| s.to_string()).collect();
+        assert!(is_extra_title("上架感言", &patterns));
+        assert!(is_extra_title("今天请假一天", &patterns));
+        assert!(!is_extra_title("第十章 请假", &patterns));
+        assert!(!is_extra_title("新的开始", &patterns));
+        assert!(!is_extra_title("上架感言", &[]));
+    }
+
+    #[test]
+    fn more_informative_title_wins_in_both_directions() {
+        // 目录只有章节号，章节页有真正的标题
+        assert_eq!(pick_title("第123章", Some("第123章 血色黄昏")), "第123章 血色黄昏");
+        assert_eq!(pick_title("第一百二十三章", Some("血色黄昏")), "血色黄昏");
+        // 反过来：章节页标题只剩数字或抓取失败
+        assert_eq!(pick_title("第五章 风起云涌", Some("第5章")), "第五章 风起云涌");
+        assert_eq!(pick_title("第五章 风起云涌", Some("5")), "第五章 风起云涌");
+        assert_eq!(pick_title("第五章 风起云涌", Some("https://fanqienovel.com/reader/1")), "第五章 风起云涌");
+        assert_eq!(pick_title("第五章 风起云涌", None), "第五章 风起云涌");
+        // 信息量持平时信任目录
+        assert_eq!(pick_title("第8章 重逢", Some("第八章：重逢！")), "第8章 重逢");
+        assert!(title_score("第8章 重逢") > title_score("重") && title_score("第8章") > title_score("８"));
+    }
+
+    #[test]
+    fn retitle_rewrites_headers_from_stored_titles() {
+        let dir = std::env::temp_dir().join(format!("test_catalog_retitle_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=3).map(|i| crate::chapter_index::ChapterRecord {
+            index: i,
+            title: format!("第{}章", i),
+            url: format!("u{}", i),
+            ..Default::default()
+        }));
+        for i in 1..=3 {
+            let content = library::render_chapter_file(&format!("第{}章", i), "u", &"正文".repeat(20));
+            fs::write(dir.join(library::chapter_file_name(i)), &content).unwrap();
+            index.mark_downloaded(i, content.as_bytes());
+        }
+        index.set_page_title(1, Some("第1章 初入江湖".to_string()));
+        index.set_page_title(2, Some("2".to_string()));
+        index.set_page_title(3, Some("第3章 被改过的".to_string()));
+        index.save().unwrap();
+        fs::write(dir.join("03.txt"), library::render_chapter_file("第3章", "u", "被外部改写")).unwrap();
+
+        let report = retitle_stored(&dir, u64::MAX).unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.changed, vec![TitleChange { index: 1, from: "第1章".into(), to: "第1章 初入江湖".into() }]);
+        assert_eq!(report.skipped, vec![3]);
+        let file = library::ChapterFile::parse(&fs::read_to_string(dir.join("01.txt")).unwrap()).unwrap();
+        assert_eq!(file.title, "第1章 初入江湖");
+        assert_eq!(file.body, "正文".repeat(20));
+        // 新头部的哈希已记入索引，文件头部也不再算作标题不一致
+        assert_eq!(ChapterIndex::load(&dir).check(1), crate::chapter_index::ChapterCheck::Verified);
+        assert!(validate_stored(&dir).unwrap().title_mismatches.is_empty());
+        assert!(retitle_stored(&dir, u64::MAX).unwrap().changed.is_empty());
+        // 撤销后恢复原标题，哈希随之改回
+        crate::edit_journal::undo(&dir, &report.edit_id.unwrap()).unwrap();
+        let file = library::ChapterFile::parse(&fs::read_to_string(dir.join("01.txt")).unwrap()).unwrap();
+        assert_eq!(file.title, "第1章");
+        assert_eq!(ChapterIndex::load(&dir).check(1), crate::chapter_index::ChapterCheck::Verified);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn extracts_arabic_and_chinese_numbers() {
+        assert_eq!(chapter_number("第12章 风起"), Some(12));
+        assert_eq!(chapter_number("第 ３ 章"), Some(3));
+        assert_eq!(chapter_number("第十五章"), Some(15));
+        assert_eq!(chapter_number("第一百零五章 归来"), Some(105));
+        assert_eq!(chapter_number("第两千三百二十一章"), Some(2321));
+        assert_eq!(chapter_number("第一万零一章"), Some(10001));
+        assert_eq!(chapter_number("上架感言"), None);
+    }
+
+    #[test]
+    fn detects_disorder_gaps_duplicates_and_interleaving() {
+        let titles = ["第1章", "第3章", "第2章", "作品相关：上架感言", "第5章", "第5章 重发"];
+        let order = analyze(&titles);
+        assert!(!order.reliable);
+        assert!(order.anomalies.contains(&CatalogAnomaly::OutOfOrder { position: 3, number: 2, previous: 3, volume: None }));
+        assert!(order.anomalies.contains(&CatalogAnomaly::Duplicate { number: 5, positions: vec![5, 6], volume: None }));
+        assert!(order.anomalies.contains(&CatalogAnomaly::Gap { from: 4, to: 4, volume: None }));
+        assert!(order.anomalies.contains(&CatalogAnomaly::Unnumbered { position: 4, title: titles[3].to_string() }));
+        assert!(order.numeric_order().is_none());
+    }
+
+    #[test]
+    fn reliable_numbers_reorder_with_unnumbered_last() {
+        let titles = ["第1章", "第3章", "第2章", "第4章", "第5章", "单章：请假"];
+        let order = analyze(&titles);
+        assert!(order.reliable);
+        assert_eq!(order.numeric_order(), Some(vec![0, 2, 1, 3, 4, 5]));
+        assert_eq!(order.notes()[2].len(), 1);
+
+        let in_order = analyze(&["第1章", "第2章", "第3章"]);
+        assert!(in_order.anomalies.is_empty());
+        assert_eq!(in_order.numeric_order(), None);
+    }
+
+    #[test]
+    fn reset_numbered_fixture_catalog_is_not_reported_as_duplicates() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(crate::spiders::corpus::CORPUS_DIR);
+        let html = fs::read_to_string(dir.join("qidian_catalog_20250315.html")).unwrap();
+        let chapters = crate::spiders::qidian::parse_catalog(&html, 100);
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        let volume_titles: Vec<Option<&str>> = chapters.iter().map(|c| c.volume.as_deref()).collect();
+
+        let order = analyze_volumes(&titles, &volume_titles);
+        assert_eq!(order.volume_source, Some(VolumeSource::Catalog));
+        assert!(order.anomalies.is_empty(), "{:?}", order.anomalies);
+        assert!(order.reliable);
+        assert_eq!(order.volume_resets.len(), 2);
+        assert_eq!(order.volume_resets[0], VolumeReset { position: 6, volume: 2, previous: 4, title: Some("第二卷 江南".into()) });
+        assert_eq!(order.volume_at(0), Some(1), "作品相关排在第一卷之前");
+        assert_eq!(order.volume_at(5), Some(2));
+        assert_eq!(order.volume_at(9), Some(3));
+        assert_eq!(order.numeric_order(), Some((1..titles.len()).chain([0]).collect()));
+
+        // 同一份目录去掉卷名：按章节号回到第 1 章推断出同样的分卷
+        let inferred = analyze(&titles);
+        assert_eq!(inferred.volume_source, Some(VolumeSource::Inferred));
+        assert!(inferred.anomalies.is_empty());
+        assert_eq!(inferred.volumes, order.volumes);
+        assert_eq!(inferred.volume_resets[1].title, None);
+    }
+
+    #[test]
+    fn genuine_duplicates_are_kept_apart_from_volume_resets() {
+        // 第二卷里第2章重发了一次；第3卷之后一条单独的"第1章"后面接着第3章，不是新卷
+        let titles = ["第1章", "第2章", "第3章", "第1章", "第2章", "第2章 重发", "第3章", "第1章 旧稿", "第4章"];
+        let order = analyze(&titles);
+        assert_eq!(order.volume_resets.len(), 1);
+        assert_eq!(order.volume_resets[0].position, 4);
+        assert!(order.anomalies.contains(&CatalogAnomaly::Duplicate { number: 2, positions: vec![5, 6], volume: Some(2) }));
+        assert!(order.anomalies.contains(&CatalogAnomaly::Duplicate { number: 1, positions: vec![4, 8], volume: Some(2) }));
+        assert!(order.anomalies.contains(&CatalogAnomaly::OutOfOrder { position: 8, number: 1, previous: 3, volume: Some(2) }));
+        assert!(!order.reliable);
+        assert!(order.notes()[4][0].starts_with("第2卷第2章重复"));
+
+        // 上一卷太短时不推断分卷
+        let short = analyze(&["第1章", "第2章", "第1章", "第2章"]);
+        assert!(short.volume_resets.is_empty() && short.volume_source.is_none());
+        assert!(short.anomalies.iter().any(|a| matches!(a, CatalogAnomaly::Duplicate { volume: None, .. })));
+
+        // 卷名存在但编号全书连续：不分卷，跨卷的重复照常报告
+        let continuous = analyze_volumes(&["第1章", "第2章", "第3章", "第3章"], &[Some("一"), Some("一"), Some("二"), Some("二")]);
+        assert!(continuous.volume_resets.is_empty());
+        assert!(continuous.anomalies.contains(&CatalogAnomaly::Duplicate { number: 3, positions: vec![3, 4], volume: None }));
+    }
+
+    #[test]
+    fn validate_compares_index_with_files() {
+        let dir = std::env::temp_dir().join(format!("test_catalog_order_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=3).map(|i| crate::chapter_index::ChapterRecord {
+            index: i,
+            title: format!("第{}章", [1, 3, 2][i - 1]),
+            url: format!("u{}", i),
+            ..Default::default()
+        }));
+        for (i, title) in [(1, "第1章"), (2, "第2章")] {
+            let content = library::render_chapter_file(title, "u", &"正文".repeat(20));
+            fs::write(dir.join(library::chapter_file_name(i)), &content).unwrap();
+            index.mark_downloaded(i, content.as_bytes());
+        }
+        index.mark_downloaded(3, b"gone");
+        index.save().unwrap();
+        fs::write(dir.join("05.txt"), "x").unwrap();
+
+        let report = validate_stored(&dir).unwrap();
+        assert_eq!(report.chapters, 3);
+        assert!(report.anomalies.contains(&CatalogAnomaly::OutOfOrder { position: 3, number: 2, previous: 3, volume: None }));
+        assert_eq!(report.missing_files, vec![3]);
+        assert_eq!(report.orphan_files, vec!["05.txt"]);
+        assert_eq!(report.title_mismatches.len(), 1);
+        assert_eq!(report.title_mismatches[0].file_title, "第2章");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
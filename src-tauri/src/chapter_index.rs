@@ -0,0 +1,447 @@
+//! 小说目录下的 `chapters.json`：完整目录 + 每章的下载状态和内容哈希。
+//!
+//! 下载前的跳过判断以它为准，而不是"文件存在"：只有标记为已下载、且文件当前哈希与记录一致的
+//! 章节才跳过。索引里没有哈希的旧章节按 [`library::validate_chapter_content`] 校验一次，
+//! 合格的补记哈希，不合格的重新下载。
+//!
+//! 修改一律经 [`ChapterIndex::update`] / [`ChapterIndex::sync`]：在小说锁（[`storage::novel_lock`]）内
+//! 重新读取、修改、写回。下载等长时间持有索引的流程不能把开始时读到的快照整份写回，否则会冲掉期间
+//! 撤销、重写标题或预取写入的哈希。
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::segmentation::ChapterSegment;
+use crate::spiders::ChapterSource;
+use crate::{library, storage};
+
+pub const CHAPTERS_FILE: &str = "chapters.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChapterRecord {
+    /// 目录序号，从 1 开始，与 `NN.txt` 文件名一致
+    pub index: usize,
+    #[serde(default)]
+    pub title: String,
+    /// 标题过长被截断时的完整标题
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_title: Option<String>,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub is_vip: bool,
+    #[serde(default)]
+    pub downloaded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// 标题中的章节号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u64>,
+    /// 目录页上的卷名（作品相关卷除外）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_title: Option<String>,
+    /// 分卷各自从第 1 章编号时所在的卷（从 1 开始，目录卷名或推断），与 number 组成全书顺序；
+    /// 编号全书连续时为 None，见 [`crate::catalog_order`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<usize>,
+    /// 获取目录时发现的顺序异常（乱序、重复、断档等）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<String>,
+    /// 作品相关 / 公告类非正文，按数量下载时默认跳过，可通过勾选序号单独下载
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_extra: bool,
+    /// 章节链接跳转到了非章节页面（已下架），再次下载时跳过；目录中链接变化后清除
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unavailable: bool,
+    /// 章节页上抓到的标题，与目录标题比较后选信息量更高的写入文件头部，见 [`crate::catalog_order::header_title`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_title: Option<String>,
+    /// 最近一次下载正文走的路径（接口 / 页面解析），旧记录和补记哈希的章节没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ChapterSource>,
+    /// 多章合页拆出的章节：来自目录第几条链接的第几段，见 [`crate::segmentation`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment: Option<ChapterSegment>,
+    /// 分目录存放时章节文件相对小说目录的路径（如 `chapters/001/2001.txt`），平铺存放时为 None，
+    /// 见 [`crate::sharding`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// 最近一次下载失败的原因，下载成功后清除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// 用户手动删除了章节文件（delete_chapter），重新下载成功后清除
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// 正文检查判为疑似非小说内容（导航栏、评论区等），见 [`crate::content_check`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub suspect: bool,
+}
+
+impl ChapterRecord {
+    /// 章节文件相对小说目录的路径：记录的路径，没有时为平铺的 `NN.txt`
+    pub fn relative_path(&self) -> String {
+        self.path.clone().unwrap_or_else(|| library::chapter_file_name(self.index))
+    }
+}
+
+/// 已有章节文件的检查结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChapterCheck {
+    /// 文件不存在
+    Missing,
+    /// 哈希与索引一致
+    Verified,
+    /// 索引中没有哈希，内容校验通过并已补记
+    Backfilled,
+    /// 需要重新下载
+    Invalid(String),
+}
+
+impl ChapterCheck {
+    pub fn can_skip(&self) -> bool {
+        matches!(self, ChapterCheck::Verified | ChapterCheck::Backfilled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub index: usize,
+    pub file: String,
+    pub reason: String,
+}
+
+pub struct ChapterIndex {
+    novel_dir: PathBuf,
+    records: BTreeMap<usize, ChapterRecord>,
+    /// 旧章节内容校验的正文最少字数
+    min_body_chars: usize,
+}
+
+impl ChapterIndex {
+    /// 读取索引；文件不存在或无法解析时为空索引。
+    pub fn load(novel_dir: &Path) -> Self {
+        let records = fs::read_to_string(novel_dir.join(CHAPTERS_FILE))
+            .ok()
+            .and_then(|c| serde_json::from_str::<Vec<ChapterRecord>>(&c).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| (r.index, r))
+            .collect();
+        ChapterIndex { novel_dir: novel_dir.to_path_buf(), records, min_body_chars: library::MIN_CHAPTER_BODY_CHARS }
+    }
+
+    /// 按平台阈值校验旧章节，见 [`crate::settings::Settings::min_chapter_chars`]
+    pub fn with_min_body_chars(mut self, min_body_chars: usize) -> Self {
+        self.min_body_chars = min_body_chars;
+        self
+    }
+
+    /// 在小说锁内重新读取 chapters.json，apply 修改后写回；apply 出错时不写回。调用方不能已持有这本书的锁
+    pub fn update<T>(novel_dir: &Path, apply: impl FnOnce(&mut ChapterIndex) -> Result<T, String>) -> Result<T, String> {
+        let mut index = ChapterIndex {
+            novel_dir: novel_dir.to_path_buf(),
+            records: BTreeMap::new(),
+            min_body_chars: library::MIN_CHAPTER_BODY_CHARS,
+        };
+        index.sync(apply)
+    }
+
+    /// 同 [`ChapterIndex::update`]，修改的是这份索引本身：锁内先换成磁盘上的最新内容再 apply，
+    /// 写回后本地与文件一致
+    pub fn sync<T>(&mut self, apply: impl FnOnce(&mut ChapterIndex) -> Result<T, String>) -> Result<T, String> {
+        let lock = storage::novel_lock(&self.novel_dir);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.records = ChapterIndex::load(&self.novel_dir).records;
+        let output = apply(self)?;
+        self.save()?;
+        Ok(output)
+    }
+
+    /// 直接写回整份索引。只用于已持有小说锁的调用方，其余修改见 [`ChapterIndex::update`]
+    pub fn save(&self) -> Result<(), String> {
+        let records: Vec<&ChapterRecord> = self.records.values().collect();
+        let json = serde_json::to_string_pretty(&records).map_err(|e| format!("序列化目录失败: {}", e))?;
+        storage::write_atomic(&self.novel_dir.join(CHAPTERS_FILE), json.as_bytes())
+            .map_err(|e| format!("写入 {} 失败: {}", CHAPTERS_FILE, e))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ChapterRecord> {
+        self.records.get(&index)
+    }
+
+    /// 第 index 章的文件路径，见 [`ChapterRecord::relative_path`]
+    pub fn chapter_path(&self, index: usize) -> PathBuf {
+        match self.records.get(&index).and_then(|r| r.path.as_deref()) {
+            Some(path) => self.novel_dir.join(path),
+            None => self.novel_dir.join(library::chapter_file_name(index)),
+        }
+    }
+
+    pub fn set_path(&mut self, index: usize, path: Option<String>) {
+        let record = self.records.entry(index).or_insert_with(|| ChapterRecord { index, ..Default::default() });
+        record.path = path;
+    }
+
+    /// 按目录序号升序
+    pub fn records(&self) -> impl Iterator<Item = &ChapterRecord> {
+        self.records.values()
+    }
+
+    /// 用新抓取的目录更新标题和链接。链接不变的章节保留下载状态；链接变了说明目录重排，需重新下载。
+    pub fn update_catalog(&mut self, catalog: impl IntoIterator<Item = ChapterRecord>) {
+        for mut record in catalog {
+            if let Some(old) = self.records.get(&record.index) {
+                // 文件位置只跟序号有关，目录重排也不变
+                record.path = record.path.or_else(|| old.path.clone());
+                if old.url == record.url || old.url.is_empty() {
+                    record.downloaded = old.downloaded;
+                    record.content_hash = old.content_hash.clone();
+                    record.unavailable = old.unavailable;
+                    record.source = old.source;
+                    record.page_title = old.page_title.clone();
+                    record.last_error = old.last_error.clone();
+                    record.deleted = old.deleted;
+                }
+            }
+            self.records.insert(record.index, record);
+        }
+    }
+
+    /// 整体替换记录（多章合页拆分后章节编号全部重排）
+    pub fn replace_records(&mut self, records: impl IntoIterator<Item = ChapterRecord>) {
+        self.records = records.into_iter().map(|r| (r.index, r)).collect();
+    }
+
+    /// 章节写入成功后记录哈希
+    pub fn mark_downloaded(&mut self, index: usize, content: &[u8]) {
+        let record = self.records.entry(index).or_insert_with(|| ChapterRecord { index, ..Default::default() });
+        record.downloaded = true;
+        record.content_hash = Some(storage::content_hash(content));
+        record.unavailable = false;
+        record.last_error = None;
+        record.deleted = false;
+    }
+
+    /// 下载失败：记下原因，已有的下载标记不动（文件是否可用由 [`ChapterIndex::check`] 判断）
+    pub fn mark_failed(&mut self, index: usize, error: impl Into<String>) {
+        let record = self.records.entry(index).or_insert_with(|| ChapterRecord { index, ..Default::default() });
+        record.last_error = Some(error.into());
+    }
+
+    pub fn set_suspect(&mut self, index: usize, suspect: bool) {
+        if let Some(record) = self.records.get_mut(&index) {
+            record.suspect = suspect;
+        }
+    }
+
+    /// 用户删除了章节文件：清除下载标记和哈希，保留目录信息
+    pub fn mark_deleted(&mut self, index: usize) {
+        let record = self.records.entry(index).or_insert_with(|| ChapterRecord { index, ..Default::default() });
+        record.downloaded = false;
+        record.content_hash = None;
+        record.deleted = true;
+    }
+
+    pub fn set_source(&mut self, index: usize, source: ChapterSource) {
+        if let Some(record) = self.records.get_mut(&index) {
+            record.source = Some(source);
+        }
+    }
+
+    pub fn set_page_title(&mut self, index: usize, page_title: Option<String>) {
+        if let Some(record) = self.records.get_mut(&index) {
+            record.page_title = page_title;
+        }
+    }
+
+    /// 章节已下架 / 不可用，之后的下载不再重试
+    pub fn mark_unavailable(&mut self, index: usize) {
+        let record = self.records.entry(index).or_insert_with(|| ChapterRecord { index, ..Default::default() });
+        record.unavailable = true;
+    }
+
+    fn mark_invalid(&mut self, index: usize) {
+        if let Some(record) = self.records.get_mut(&index) {
+            record.downloaded = false;
+            record.content_hash = None;
+        }
+    }
+
+    /// 检查已有章节文件是否可以跳过，并按结果更新索引（补记哈希 / 清除下载标记）。
+    pub fn check(&mut self, index: usize) -> ChapterCheck {
+        let path = self.chapter_path(index);
+        let Ok(bytes) = fs::read(&path) else {
+            self.mark_invalid(index);
+            return ChapterCheck::Missing;
+        };
+
+        let stored = self.records.get(&index).filter(|r| r.downloaded).and_then(|r| r.content_hash.as_deref());
+        let result = match stored {
+            Some(hash) if hash == storage::content_hash(&bytes) => ChapterCheck::Verified,
+            Some(_) => ChapterCheck::Invalid("内容与 chapters.json 中的哈希不一致".to_string()),
+            None => match validate_legacy(&bytes, self.min_body_chars) {
+                Ok(()) => {
+                    self.mark_downloaded(index, &bytes);
+                    ChapterCheck::Backfilled
+                }
+                Err(reason) => ChapterCheck::Invalid(reason),
+            },
+        };
+        if matches!(result, ChapterCheck::Invalid(_)) {
+            self.mark_invalid(index);
+        }
+        result
+    }
+
+    /// 校验目录下所有章节文件和所有标记为已下载的章节，返回不一致的章节。
+    pub fn revalidate(&mut self) -> Vec<HashMismatch> {
+        let mut indices: Vec<usize> = self.records.values().filter(|r| r.downloaded).map(|r| r.index).collect();
+        indices.extend(crate::sharding::chapter_files(&self.novel_dir).into_iter().map(|(i, _)| i));
+        indices.sort_unstable();
+        indices.dedup();
+
+        let mut mismatches = Vec::new();
+        for index in indices {
+            let reason = match self.check(index) {
+                ChapterCheck::Missing => "章节文件缺失".to_string(),
+                ChapterCheck::Invalid(reason) => reason,
+                ChapterCheck::Verified | ChapterCheck::Backfilled => continue,
+            };
+            let file = self.records.get(&index).map(ChapterRecord::relative_path).unwrap_or_else(|| library::chapter_file_name(index));
+            mismatches.push(HashMismatch { index, file, reason });
+        }
+        mismatches
+    }
+}
+
+/// 没有哈希记录的旧章节：按大小和内容规则校验一次
+pub(crate) fn validate_legacy(bytes: &[u8], min_body_chars: usize) -> Result<(), String> {
+    if (bytes.len() as u64) < library::MIN_CHAPTER_FILE_BYTES {
+        return Err(format!("文件过小（{} 字节）", bytes.len()));
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| "不是有效的 UTF-8 文本".to_string())?;
+    library::validate_chapter_content(text, min_body_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_novel_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_chapter_index_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn good_chapter(n: usize) -> String {
+        library::render_chapter_file(&format!("第{}章", n), "https://example.com", &"正文内容".repeat(10))
+    }
+
+    #[test]
+    fn skips_only_when_hash_matches() {
+        let dir = temp_novel_dir("hash");
+        let mut index = ChapterIndex::load(&dir);
+        let content = good_chapter(1);
+        fs::write(dir.join("01.txt"), &content).unwrap();
+        index.mark_downloaded(1, content.as_bytes());
+        assert_eq!(index.check(1), ChapterCheck::Verified);
+
+        // 文件被改写（例如早期写入的 WAF 页面）后不再跳过
+        fs::write(dir.join("01.txt"), good_chapter(99)).unwrap();
+        assert!(matches!(index.check(1), ChapterCheck::Invalid(_)));
+        assert!(!index.get(1).unwrap().downloaded);
+        assert_eq!(index.check(2), ChapterCheck::Missing);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn legacy_files_are_validated_once_and_backfilled() {
+        let dir = temp_novel_dir("legacy");
+        fs::write(dir.join("01.txt"), good_chapter(1)).unwrap();
+        fs::write(dir.join("02.txt"), "<html>验证码</html>".repeat(10)).unwrap();
+
+        let mut index = ChapterIndex::load(&dir);
+        assert_eq!(index.check(1), ChapterCheck::Backfilled);
+        assert!(matches!(index.check(2), ChapterCheck::Invalid(_)));
+        index.save().unwrap();
+
+        let mut reloaded = ChapterIndex::load(&dir);
+        assert!(reloaded.get(1).unwrap().content_hash.is_some());
+        assert_eq!(reloaded.check(1), ChapterCheck::Verified);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn catalog_update_keeps_status_for_same_url() {
+        let dir = temp_novel_dir("catalog");
+        let mut index = ChapterIndex::load(&dir);
+        let record = |i: usize, url: &str| ChapterRecord { index: i, title: format!("第{}章", i), url: url.to_string(), ..Default::default() };
+        index.update_catalog([record(1, "a"), record(2, "b"), record(3, "e")]);
+        index.mark_downloaded(1, b"x");
+        index.set_source(1, ChapterSource::Api);
+        index.mark_downloaded(2, b"y");
+        index.mark_unavailable(3);
+        index.update_catalog([record(1, "a"), record(2, "c"), record(3, "e")]);
+        assert!(index.get(1).unwrap().downloaded);
+        assert_eq!(index.get(1).unwrap().source, Some(ChapterSource::Api));
+        assert!(!index.get(2).unwrap().downloaded);
+        assert!(index.get(3).unwrap().unavailable);
+        // 重排后链接变化，下架标记失效
+        index.update_catalog([record(3, "d")]);
+        assert!(!index.get(3).unwrap().unavailable);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn revalidate_reports_missing_and_tampered_chapters() {
+        let dir = temp_novel_dir("revalidate");
+        let mut index = ChapterIndex::load(&dir);
+        for i in 1..=3 {
+            let content = good_chapter(i);
+            fs::write(dir.join(library::chapter_file_name(i)), &content).unwrap();
+            index.mark_downloaded(i, content.as_bytes());
+        }
+        fs::remove_file(dir.join("02.txt")).unwrap();
+        fs::write(dir.join("03.txt"), good_chapter(30)).unwrap();
+
+        let mismatches = index.revalidate();
+        let reported: Vec<usize> = mismatches.iter().map(|m| m.index).collect();
+        assert_eq!(reported, vec![2, 3]);
+        assert_eq!(mismatches[0].reason, "章节文件缺失");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sync_keeps_changes_saved_by_other_writers() {
+        let dir = temp_novel_dir("sync");
+        let mut snapshot = ChapterIndex::load(&dir);
+        let threads: Vec<_> = (1..=8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    ChapterIndex::update(&dir, |index| {
+                        index.mark_downloaded(i, b"x");
+                        Ok(())
+                    })
+                    .unwrap()
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        // 长时间持有的旧快照写回时不能冲掉别人已保存的记录
+        snapshot
+            .sync(|index| {
+                index.mark_unavailable(9);
+                Ok(())
+            })
+            .unwrap();
+        let reloaded = ChapterIndex::load(&dir);
+        assert_eq!(reloaded.records().filter(|r| r.downloaded).count(), 8);
+        assert!(reloaded.get(9).unwrap().unavailable);
+        assert_eq!(snapshot.records().count(), 9);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,424 @@
+//! 章节正文全文检索。
+//!
+//! 默认逐章线性扫描。`build_search_index` 为某个书库目录建立倒排索引：正文按相邻两个字符切分
+//! （中文没有分词边界，二元组足以筛掉绝大多数章节），存放在 `search_index/<书库目录>.json`，
+//! 用过后常驻内存。检索时只读取索引给出的候选章节；修改时间、大小与索引不一致或尚未索引的章节
+//! 照常逐个扫描，所以有没有索引结果都一样，只是快慢不同。
+//! 下载结束后只重新索引该书有变化的章节，被替换的旧条目留作空位，重新建立索引时清理。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+use crate::{analysis_batch, library, library_search, novel_info, paths, storage};
+
+pub const INDEX_DIR: &str = "search_index";
+const INDEX_VERSION: u32 = 1;
+/// 缺省最多返回的命中数
+pub const DEFAULT_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChapterHit {
+    pub title: String,
+    /// 小说目录，相对工作区
+    pub path: String,
+    pub chapter_file: String,
+    /// 命中处前后的片段
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct IndexStatus {
+    /// 书库目录，相对工作区
+    pub dir_name: String,
+    /// 已索引的章节数
+    pub files: usize,
+    /// 不同二元组的个数
+    pub grams: usize,
+    /// 索引文件大小（字节）
+    pub size_bytes: u64,
+    pub built_at: String,
+    pub updated_at: String,
+    /// 修改过、新增或已删除、检索时需要逐个扫描的章节数
+    pub stale: usize,
+    /// 被替换的旧条目，重新建立索引时清理
+    pub vacant: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+struct Stamp {
+    modified_ms: u64,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    /// `<小说目录名>/<章节文件>`
+    path: String,
+    stamp: Stamp,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SearchIndex {
+    version: u32,
+    dir_name: String,
+    built_at: String,
+    updated_at: String,
+    /// 下标即文件 ID；被替换或删除的为 None
+    files: Vec<Option<IndexedFile>>,
+    postings: HashMap<String, Vec<u32>>,
+    #[serde(skip)]
+    by_path: HashMap<String, u32>,
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// 相邻两个字符组成的二元组（英文字母不区分大小写），含空白的跳过
+fn grams(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    chars
+        .windows(2)
+        .filter(|w| !w[0].is_whitespace() && !w[1].is_whitespace())
+        .map(|w| w.iter().collect())
+        .collect()
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    let modified_ms = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+    Some(Stamp { modified_ms, size: meta.len() })
+}
+
+impl SearchIndex {
+    fn new(dir_name: &str) -> Self {
+        let built_at = now();
+        SearchIndex {
+            version: INDEX_VERSION,
+            dir_name: dir_name.to_string(),
+            updated_at: built_at.clone(),
+            built_at,
+            ..Default::default()
+        }
+    }
+
+    fn rebuild_lookup(&mut self) {
+        self.by_path = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(id, f)| f.as_ref().map(|f| (f.path.clone(), id as u32)))
+            .collect();
+    }
+
+    fn add(&mut self, path: &str, stamp: Stamp, text: &str) {
+        self.remove(path);
+        let id = self.files.len() as u32;
+        self.files.push(Some(IndexedFile { path: path.to_string(), stamp }));
+        for gram in grams(text) {
+            self.postings.entry(gram).or_default().push(id);
+        }
+        self.by_path.insert(path.to_string(), id);
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some(id) = self.by_path.remove(path) {
+            self.files[id as usize] = None;
+        }
+    }
+
+    fn is_fresh(&self, path: &str, stamp: Option<Stamp>) -> bool {
+        self.by_path
+            .get(path)
+            .and_then(|&id| self.files[id as usize].as_ref())
+            .is_some_and(|f| Some(f.stamp) == stamp)
+    }
+
+    /// 可能包含 query 的文件 ID；query 不足两个字符时为 None（无法用索引筛选）
+    fn candidates(&self, query: &str) -> Option<HashSet<u32>> {
+        let mut lists: Vec<&[u32]> = Vec::new();
+        for gram in grams(query) {
+            lists.push(self.postings.get(&gram).map(Vec::as_slice).unwrap_or(&[]));
+        }
+        lists.sort_by_key(|l| l.len());
+        let (first, rest) = lists.split_first()?;
+        let mut ids: HashSet<u32> = first.iter().copied().collect();
+        for list in rest {
+            let set: HashSet<u32> = list.iter().copied().collect();
+            ids.retain(|id| set.contains(id));
+        }
+        Some(ids)
+    }
+
+    fn live_files(&self) -> usize {
+        self.by_path.len()
+    }
+}
+
+fn index_path(workspace_root: &Path, dir_name: &str) -> PathBuf {
+    workspace_root.join(INDEX_DIR).join(format!("{}.json", library::novel_dir_name(dir_name)))
+}
+
+/// 建立和刷新索引互斥，避免并发下载各自改一份副本后互相覆盖
+fn write_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, Arc<SearchIndex>>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<SearchIndex>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 内存中的索引，首次使用时从磁盘读取；没有索引或版本不符时为 None
+fn load(workspace_root: &Path, dir_name: &str) -> Option<Arc<SearchIndex>> {
+    let path = index_path(workspace_root, dir_name);
+    if let Some(index) = cache().lock().unwrap_or_else(|e| e.into_inner()).get(&path) {
+        return Some(index.clone());
+    }
+    let mut index: SearchIndex = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    if index.version != INDEX_VERSION {
+        return None;
+    }
+    index.rebuild_lookup();
+    let index = Arc::new(index);
+    cache().lock().unwrap_or_else(|e| e.into_inner()).insert(path, index.clone());
+    Some(index)
+}
+
+fn store(workspace_root: &Path, mut index: SearchIndex) -> Result<Arc<SearchIndex>, String> {
+    index.updated_at = now();
+    let path = index_path(workspace_root, &index.dir_name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let json = serde_json::to_string(&index).map_err(|e| format!("序列化检索索引失败: {}", e))?;
+    storage::write_atomic(&path, json.as_bytes()).map_err(|e| format!("写入检索索引失败: {}", e))?;
+    let index = Arc::new(index);
+    cache().lock().unwrap_or_else(|e| e.into_inner()).insert(path, index.clone());
+    Ok(index)
+}
+
+/// 书库中的全部章节：(`<小说目录名>/<章节文件>`, 小说目录, 章节文件)，按小说目录名和章节序号排序
+fn library_chapters(library_dir: &Path) -> Vec<(String, PathBuf, String)> {
+    let mut novels = library::scan_library(library_dir).novels;
+    novels.sort();
+    novels
+        .into_iter()
+        .flat_map(|novel_dir| {
+            let name = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            analysis_batch::chapter_files(&novel_dir)
+                .into_iter()
+                .map(move |file| (format!("{}/{}", name, file), novel_dir.clone(), file))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 书库目录相对工作区的写法，作为索引的名字
+fn library_key(workspace_root: &Path, library_dir: &Path) -> String {
+    paths::to_relative(workspace_root, library_dir).unwrap_or_else(|| library_dir.display().to_string())
+}
+
+/// 为书库目录重新建立索引（清理空位），返回索引状态
+pub fn build(workspace_root: &Path, library_dir: &Path) -> Result<IndexStatus, String> {
+    let _guard = write_lock().lock().unwrap_or_else(|e| e.into_inner());
+    let dir_name = library_key(workspace_root, library_dir);
+    let mut index = SearchIndex::new(&dir_name);
+    for (key, novel_dir, file) in library_chapters(library_dir) {
+        let path = novel_dir.join(&file);
+        if let (Some(stamp), Ok(text)) = (stamp(&path), fs::read_to_string(&path)) {
+            index.add(&key, stamp, &text);
+        }
+    }
+    let index = store(workspace_root, index)?;
+    Ok(status_of(workspace_root, library_dir, &index))
+}
+
+/// 下载结束后重新索引一本书中有变化的章节并去掉已删除的章节。所在书库没有索引时什么都不做，返回 false
+pub fn refresh_novel(workspace_root: &Path, novel_dir: &Path) -> Result<bool, String> {
+    let (Some(library_dir), Some(name)) = (novel_dir.parent(), novel_dir.file_name()) else {
+        return Ok(false);
+    };
+    let _guard = write_lock().lock().unwrap_or_else(|e| e.into_inner());
+    let Some(current) = load(workspace_root, &library_key(workspace_root, library_dir)) else {
+        return Ok(false);
+    };
+    let name = name.to_string_lossy().to_string();
+    let mut index = (*current).clone();
+    let mut changed = false;
+    let files = analysis_batch::chapter_files(novel_dir);
+    for file in &files {
+        let key = format!("{}/{}", name, file);
+        let path = novel_dir.join(file);
+        let file_stamp = stamp(&path);
+        if index.is_fresh(&key, file_stamp) {
+            continue;
+        }
+        if let (Some(file_stamp), Ok(text)) = (file_stamp, fs::read_to_string(&path)) {
+            index.add(&key, file_stamp, &text);
+            changed = true;
+        }
+    }
+    let prefix = format!("{}/", name);
+    let removed: Vec<String> = index
+        .by_path
+        .keys()
+        .filter(|k| k.strip_prefix(&prefix).is_some_and(|file| !files.iter().any(|f| f == file)))
+        .cloned()
+        .collect();
+    for key in &removed {
+        index.remove(key);
+    }
+    if changed || !removed.is_empty() {
+        store(workspace_root, index)?;
+    }
+    Ok(true)
+}
+
+/// 检索书库中所有章节的正文，按小说目录名和章节序号排序，最多 limit 条。
+/// 有索引时只扫描候选章节和索引之后有变化的章节，否则逐章扫描
+pub fn search(workspace_root: &Path, library_dir: &Path, query: &str, limit: usize) -> Vec<ChapterHit> {
+    let query = query.trim().to_ascii_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let index = load(workspace_root, &library_key(workspace_root, library_dir));
+    let candidates = index.as_ref().and_then(|i| i.candidates(&query).map(|ids| (i, ids)));
+
+    let mut hits = Vec::new();
+    let mut titles: HashMap<PathBuf, String> = HashMap::new();
+    for (key, novel_dir, file) in library_chapters(library_dir) {
+        if hits.len() >= limit {
+            break;
+        }
+        let path = novel_dir.join(&file);
+        if let Some((index, ids)) = &candidates {
+            let indexed = index.by_path.get(&key).copied();
+            if index.is_fresh(&key, stamp(&path)) && indexed.is_some_and(|id| !ids.contains(&id)) {
+                continue;
+            }
+        }
+        let Ok(text) = fs::read_to_string(&path) else { continue };
+        let Some(at) = text.to_ascii_lowercase().find(&query) else { continue };
+        let title = titles
+            .entry(novel_dir.clone())
+            .or_insert_with(|| {
+                novel_info::read_info(&novel_dir)
+                    .ok()
+                    .and_then(|info| info.get("title").and_then(|v| v.as_str()).map(str::to_string))
+                    .unwrap_or_else(|| key.split('/').next().unwrap_or_default().to_string())
+            })
+            .clone();
+        hits.push(ChapterHit {
+            title,
+            path: paths::to_relative(workspace_root, &novel_dir).unwrap_or_else(|| novel_dir.display().to_string()),
+            chapter_file: file,
+            snippet: library_search::snippet(&text, at, query.len()),
+        });
+    }
+    hits
+}
+
+fn status_of(workspace_root: &Path, library_dir: &Path, index: &SearchIndex) -> IndexStatus {
+    let chapters = library_chapters(library_dir);
+    let keys: HashSet<&str> = chapters.iter().map(|(key, _, _)| key.as_str()).collect();
+    let changed = chapters.iter().filter(|(key, dir, file)| !index.is_fresh(key, stamp(&dir.join(file)))).count();
+    let deleted = index.by_path.keys().filter(|k| !keys.contains(k.as_str())).count();
+    IndexStatus {
+        dir_name: index.dir_name.clone(),
+        files: index.live_files(),
+        grams: index.postings.len(),
+        size_bytes: fs::metadata(index_path(workspace_root, &index.dir_name)).map(|m| m.len()).unwrap_or(0),
+        built_at: index.built_at.clone(),
+        updated_at: index.updated_at.clone(),
+        stale: changed + deleted,
+        vacant: index.files.len() - index.live_files(),
+    }
+}
+
+/// 工作区中全部索引的大小和新鲜度
+pub fn status(workspace_root: &Path) -> Vec<IndexStatus> {
+    let Ok(entries) = fs::read_dir(workspace_root.join(INDEX_DIR)) else {
+        return Vec::new();
+    };
+    let mut dir_names: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| {
+            let text = fs::read_to_string(e.path()).ok()?;
+            let value: serde_json::Value = serde_json::from_str(&text).ok()?;
+            value.get("dir_name")?.as_str().map(str::to_string)
+        })
+        .collect();
+    dir_names.sort();
+    dir_names
+        .into_iter()
+        .filter_map(|dir_name| {
+            let index = load(workspace_root, &dir_name)?;
+            let library_dir = paths::resolve(workspace_root, &dir_name).ok()?;
+            Some(status_of(workspace_root, &library_dir, &index))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_chapter(novel_dir: &Path, n: usize, body: &str) {
+        fs::write(novel_dir.join(library::chapter_file_name(n)), library::render_chapter_file("章", "u", body)).unwrap();
+    }
+
+    #[test]
+    fn indexed_search_matches_linear_scan_and_tracks_changes() {
+        let root = std::env::temp_dir().join(format!("test_chapter_search_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let downloads = library::downloads_dir(&root);
+        let novel_dir = downloads.join("书");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::write(novel_info::info_path(&novel_dir), r#"{"title":"书名"}"#).unwrap();
+        write_chapter(&novel_dir, 1, "少年推开院门，看见远处的山脊。");
+        write_chapter(&novel_dir, 2, "风从北边来，带着雪的味道。");
+
+        let linear = search(&root, &downloads, "山脊", 10);
+        assert_eq!(linear.len(), 1);
+        assert_eq!((linear[0].title.as_str(), linear[0].chapter_file.as_str()), ("书名", "01.txt"));
+        assert_eq!(linear[0].path, "downloads/书");
+
+        let built = build(&root, &downloads).unwrap();
+        assert_eq!((built.dir_name.as_str(), built.files, built.stale, built.vacant), ("downloads", 2, 0, 0));
+        assert!(built.size_bytes > 0);
+        assert_eq!(search(&root, &downloads, "山脊", 10), linear);
+        assert!(search(&root, &downloads, "雪山", 10).is_empty());
+        // 单个字符无法用二元组筛选，按线性扫描
+        assert_eq!(search(&root, &downloads, "雪", 10)[0].chapter_file, "02.txt");
+
+        // 索引之后修改、新增的章节在刷新前也能搜到，刷新后变为新鲜
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_chapter(&novel_dir, 2, "风从北边来，翻过山脊。");
+        write_chapter(&novel_dir, 3, "第三章也提到山脊。");
+        assert_eq!(status(&root)[0].stale, 2);
+        assert_eq!(search(&root, &downloads, "山脊", 10).len(), 3);
+        assert!(refresh_novel(&root, &novel_dir).unwrap());
+        let refreshed = &status(&root)[0];
+        assert_eq!((refreshed.files, refreshed.stale, refreshed.vacant), (3, 0, 1));
+        assert_eq!(search(&root, &downloads, "山脊", 2).len(), 2);
+
+        fs::remove_file(novel_dir.join("03.txt")).unwrap();
+        assert!(refresh_novel(&root, &novel_dir).unwrap());
+        assert_eq!(status(&root)[0].files, 2);
+        assert_eq!(build(&root, &downloads).unwrap().vacant, 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn grams_ignore_whitespace_and_case() {
+        let grams = grams("斗罗 DL");
+        assert_eq!(grams, HashSet::from(["斗罗".to_string(), "dl".to_string()]));
+        assert!(super::grams("斗").is_empty());
+    }
+}
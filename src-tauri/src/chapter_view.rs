@@ -0,0 +1,155 @@
+//! 阅读界面的章节列表：把 chapters.json 与目录中的实际文件合并成每章一个状态，
+//! 前端直接按它渲染，不再从文件树里猜"没下载 / 下载失败 / 被删除"。
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::chapter_index::{self, ChapterIndex, ChapterRecord};
+use crate::{library, sharding, storage};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterStatus {
+    /// 文件存在且与索引哈希一致（旧章节为内容校验通过）
+    Downloaded,
+    /// 没有文件，最近一次下载失败
+    Failed,
+    /// 没有文件的 VIP 章节
+    VipLocked,
+    /// 用户删除了章节文件
+    Deleted,
+    /// 目录中有、从未下载
+    Missing,
+    /// 文件存在但内容不可用：哈希不一致、校验不通过或只是云端占位文件
+    Placeholder,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChapterView {
+    pub index: usize,
+    pub title: String,
+    /// 章节文件相对小说目录的路径（文件不存在时为应在的位置）
+    pub file: String,
+    pub status: ChapterStatus,
+    /// 文件大小（字节），没有文件时为 None
+    pub size: Option<u64>,
+    /// 是否被至少一份分析结果引用
+    pub analyzed: bool,
+    /// 下载失败 / 内容不可用的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChaptersView {
+    pub chapters: Vec<ChapterView>,
+    /// 各状态的章数
+    pub counts: BTreeMap<ChapterStatus, usize>,
+    pub analyzed: usize,
+}
+
+/// 单章状态。file 为章节文件内容，文件不存在时为 None
+pub fn status_of(record: Option<&ChapterRecord>, file: Option<&[u8]>, min_body_chars: usize) -> (ChapterStatus, Option<String>) {
+    let error = record.and_then(|r| r.last_error.clone());
+    let Some(bytes) = file else {
+        return match record {
+            Some(r) if r.deleted => (ChapterStatus::Deleted, None),
+            Some(r) if r.is_vip => (ChapterStatus::VipLocked, error),
+            Some(r) if r.unavailable || r.last_error.is_some() => (ChapterStatus::Failed, error),
+            _ => (ChapterStatus::Missing, None),
+        };
+    };
+    match record.filter(|r| r.downloaded).and_then(|r| r.content_hash.as_deref()) {
+        Some(hash) if hash == storage::content_hash(bytes) => (ChapterStatus::Downloaded, None),
+        Some(_) => (ChapterStatus::Placeholder, Some("内容与 chapters.json 中的哈希不一致".to_string())),
+        None => match chapter_index::validate_legacy(bytes, min_body_chars) {
+            Ok(()) => (ChapterStatus::Downloaded, None),
+            Err(reason) => (ChapterStatus::Placeholder, Some(reason)),
+        },
+    }
+}
+
+/// 合并 chapters.json 和目录中的章节文件。analyzed 为分析结果引用过的章节文件（相对路径）
+pub fn build(novel_dir: &Path, analyzed: &HashSet<String>) -> ChaptersView {
+    let index = ChapterIndex::load(novel_dir);
+    let files: BTreeMap<usize, String> = sharding::chapter_files(novel_dir).into_iter().collect();
+    let mut indices: Vec<usize> = index.records().map(|r| r.index).chain(files.keys().copied()).collect();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut chapters = Vec::with_capacity(indices.len());
+    for i in indices {
+        let record = index.get(i);
+        let file = files
+            .get(&i)
+            .cloned()
+            .or_else(|| record.map(ChapterRecord::relative_path))
+            .unwrap_or_else(|| library::chapter_file_name(i));
+        let path = novel_dir.join(&file);
+        let size = fs::metadata(&path).ok().filter(|m| m.is_file()).map(|m| m.len());
+        let (status, error) = if size.is_some() && storage::is_cloud_placeholder(&path) {
+            (ChapterStatus::Placeholder, Some("文件尚未从云端下载".to_string()))
+        } else {
+            let bytes = size.and_then(|_| fs::read(&path).ok());
+            status_of(record, bytes.as_deref(), library::MIN_CHAPTER_BODY_CHARS)
+        };
+        let title = record
+            .map(|r| r.full_title.clone().unwrap_or_else(|| r.title.clone()))
+            .filter(|t| !t.is_empty())
+            .or_else(|| {
+                let text = fs::read_to_string(&path).ok()?;
+                library::ChapterFile::parse(&text).map(|c| c.title)
+            })
+            .unwrap_or_default();
+        chapters.push(ChapterView { index: i, title, analyzed: analyzed.contains(&file), file, status, size, error });
+    }
+
+    let mut counts = BTreeMap::new();
+    for chapter in &chapters {
+        *counts.entry(chapter.status).or_insert(0) += 1;
+    }
+    let analyzed = chapters.iter().filter(|c| c.analyzed).count();
+    ChaptersView { chapters, counts, analyzed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_index_with_files_into_one_status_per_chapter() {
+        let dir = std::env::temp_dir().join(format!("test_chapter_view_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let good = |n: usize| library::render_chapter_file(&format!("第{}章", n), "https://example.com", &"正文内容".repeat(10));
+
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=6).map(|i| ChapterRecord { index: i, title: format!("第{}章", i), url: format!("u{}", i), is_vip: i == 5, ..Default::default() }));
+        for i in [1, 2] {
+            fs::write(dir.join(library::chapter_file_name(i)), good(i)).unwrap();
+            index.mark_downloaded(i, good(i).as_bytes());
+        }
+        // 2 被改写成验证页，3 下载失败，4 被删除，5 是 VIP，6 从未下载
+        fs::write(dir.join("02.txt"), "<html>验证码</html>").unwrap();
+        index.mark_failed(3, "超时");
+        index.mark_downloaded(4, good(4).as_bytes());
+        index.mark_deleted(4);
+        index.save().unwrap();
+        // 7 只有文件，没有索引记录
+        fs::write(dir.join("07.txt"), good(7)).unwrap();
+
+        let analyzed: HashSet<String> = ["01.txt".to_string()].into();
+        let view = build(&dir, &analyzed);
+        let statuses: Vec<ChapterStatus> = view.chapters.iter().map(|c| c.status).collect();
+        use ChapterStatus::*;
+        assert_eq!(statuses, vec![Downloaded, Placeholder, Failed, Deleted, VipLocked, Missing, Downloaded]);
+        assert_eq!(view.chapters[2].error.as_deref(), Some("超时"));
+        assert_eq!(view.chapters[6].title, "第7章");
+        assert_eq!(view.chapters[0].size, Some(good(1).len() as u64));
+        assert_eq!(view.chapters[5].size, None);
+        assert_eq!((view.analyzed, view.counts[&Downloaded]), (1, 2));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,248 @@
+//! 工作区的正文清洗规则 `clean_rules.json`：按顺序执行的正则替换，用于去掉站点插入的反爬文字。
+//!
+//! 下载时对每章正文执行；`preview_clean_rules` 用同一个 [`apply_traced`] 在样本章节上试跑，
+//! 只统计命中数和前后片段，不写文件。保存前逐条校验正则，出错的规则按序号返回。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const RULES_FILE: &str = "clean_rules.json";
+/// 每条规则最多返回的前后片段数
+const MAX_SAMPLES: usize = 5;
+/// 片段中命中位置前后各保留的字符数
+const CONTEXT_CHARS: usize = 20;
+pub const DEFAULT_SAMPLE_CHAPTERS: usize = 5;
+
+fn enabled_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanRule {
+    pub pattern: String,
+    /// 替换文本，支持 `$1` / `${name}` 引用分组；为空表示删除
+    #[serde(default)]
+    pub replacement: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RuleError {
+    /// 规则在列表中的序号（从 0 开始）
+    pub index: usize,
+    pub pattern: String,
+    pub error: String,
+}
+
+/// 编译后的规则，只含启用的规则
+pub struct CompiledRule {
+    pub index: usize,
+    regex: Regex,
+    replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Snippet {
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RuleStats {
+    pub index: usize,
+    pub pattern: String,
+    pub matches: usize,
+    pub samples: Vec<Snippet>,
+}
+
+/// 校验并编译全部规则；有任何一条出错时返回所有出错的规则
+pub fn compile(rules: &[CleanRule]) -> Result<Vec<CompiledRule>, Vec<RuleError>> {
+    let mut compiled = Vec::new();
+    let mut errors = Vec::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let result = if rule.pattern.is_empty() { Err("正则不能为空".to_string()) } else { Regex::new(&rule.pattern).map_err(|e| e.to_string()) };
+        match result {
+            Ok(regex) if rule.enabled => compiled.push(CompiledRule { index, regex, replacement: rule.replacement.clone() }),
+            Ok(_) => {}
+            Err(error) => errors.push(RuleError { index, pattern: rule.pattern.clone(), error }),
+        }
+    }
+    if errors.is_empty() { Ok(compiled) } else { Err(errors) }
+}
+
+/// 新工作区的初始规则（见 [`crate::bootstrap`]）：常见的翻页提示和站点推广语
+pub fn stock_rules() -> Vec<CleanRule> {
+    let rule = |pattern: &str, description: &str| CleanRule {
+        pattern: pattern.to_string(),
+        replacement: String::new(),
+        description: Some(description.to_string()),
+        enabled: true,
+    };
+    vec![
+        rule(r"(?m)^.*本章未完.*点击下一页.*$", "翻页提示"),
+        rule(r"(?m)^.*(请记住本书首发域名|最新章节请到|手机用户请浏览).*$", "站点推广语"),
+        rule(r"[（(]本章完[)）]", "章末标记"),
+    ]
+}
+
+pub fn load(workspace_root: &Path) -> Vec<CleanRule> {
+    let path = workspace_root.join(RULES_FILE);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("[CleanRules] 解析 {} 失败: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+/// 下载时使用的规则；文件中有无效正则时记录日志并不做清洗
+pub fn load_compiled(workspace_root: &Path) -> Vec<CompiledRule> {
+    compile(&load(workspace_root)).unwrap_or_else(|errors| {
+        for e in &errors {
+            eprintln!("[CleanRules] 规则 #{} 无效，本次不清洗: {}", e.index, e.error);
+        }
+        Vec::new()
+    })
+}
+
+/// 校验通过才写入，否则返回出错的规则，文件不变
+pub fn save(workspace_root: &Path, rules: &[CleanRule]) -> Result<Vec<RuleError>, String> {
+    if let Err(errors) = compile(rules) {
+        return Ok(errors);
+    }
+    let content = serde_json::to_string_pretty(rules).map_err(|e| format!("序列化清洗规则失败: {}", e))?;
+    crate::storage::write_atomic(&workspace_root.join(RULES_FILE), content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", RULES_FILE, e))?;
+    Ok(Vec::new())
+}
+
+fn tail_chars(s: &str, n: usize) -> &str {
+    let start = s.char_indices().rev().nth(n.saturating_sub(1)).map_or(0, |(i, _)| i);
+    if n == 0 { "" } else { &s[start..] }
+}
+
+fn record(text: &str, rule: &CompiledRule, stats: &mut RuleStats) {
+    for caps in rule.regex.captures_iter(text) {
+        let m = caps.get(0).expect("分组 0 总是存在");
+        stats.matches += 1;
+        if stats.samples.len() >= MAX_SAMPLES {
+            continue;
+        }
+        let prefix = tail_chars(&text[..m.start()], CONTEXT_CHARS);
+        let suffix = crate::spiders::char_prefix(&text[m.end()..], CONTEXT_CHARS);
+        let mut replaced = String::new();
+        caps.expand(&rule.replacement, &mut replaced);
+        stats.samples.push(Snippet {
+            before: format!("{}{}{}", prefix, m.as_str(), suffix),
+            after: format!("{}{}{}", prefix, replaced, suffix),
+        });
+    }
+}
+
+/// 每条启用规则一个空的统计项
+pub fn empty_stats(rules: &[CleanRule], compiled: &[CompiledRule]) -> Vec<RuleStats> {
+    compiled
+        .iter()
+        .map(|c| RuleStats { index: c.index, pattern: rules[c.index].pattern.clone(), matches: 0, samples: Vec::new() })
+        .collect()
+}
+
+/// 按顺序执行规则；传入 stats（与 compiled 一一对应）时累计每条规则的命中数和片段
+pub fn apply_traced(text: &str, rules: &[CompiledRule], mut stats: Option<&mut [RuleStats]>) -> String {
+    let mut text = text.to_string();
+    for (i, rule) in rules.iter().enumerate() {
+        if let Some(stats) = stats.as_deref_mut() {
+            record(&text, rule, &mut stats[i]);
+        }
+        if let std::borrow::Cow::Owned(replaced) = rule.regex.replace_all(&text, rule.replacement.as_str()) {
+            text = replaced;
+        }
+    }
+    text
+}
+
+pub fn apply(text: &str, rules: &[CompiledRule]) -> String {
+    apply_traced(text, rules, None)
+}
+
+/// 在章节中均匀抽取最多 count 章
+pub fn sample_files(files: &[String], count: usize) -> Vec<String> {
+    let count = count.min(files.len());
+    (0..count).map(|i| files[i * files.len() / count].clone()).collect()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreviewReport {
+    pub chapters: Vec<String>,
+    pub rules: Vec<RuleStats>,
+    /// 无效的规则；不为空时不做预览
+    pub errors: Vec<RuleError>,
+}
+
+/// 在样本章节的正文上试跑规则（章节头部不参与），不写任何文件
+pub fn preview(novel_dir: &Path, rules: &[CleanRule], sample_chapters: usize) -> Result<PreviewReport, String> {
+    let compiled = match compile(rules) {
+        Ok(compiled) => compiled,
+        Err(errors) => return Ok(PreviewReport { errors, ..Default::default() }),
+    };
+    let chapters = sample_files(&crate::analysis_batch::chapter_files(novel_dir), sample_chapters);
+    let mut stats = empty_stats(rules, &compiled);
+    for file in &chapters {
+        let text = fs::read_to_string(novel_dir.join(file)).map_err(|e| format!("读取 {} 失败: {}", file, e))?;
+        let body = &text[crate::text_normalize::header_len(&text)..];
+        apply_traced(body, &compiled, Some(&mut stats));
+    }
+    Ok(PreviewReport { chapters, rules: stats, errors: Vec::new() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: &str) -> CleanRule {
+        CleanRule { pattern: pattern.to_string(), replacement: replacement.to_string(), description: None, enabled: true }
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported_by_index() {
+        let rules = vec![rule("ok", ""), rule("(unclosed", ""), rule("", ""), CleanRule { enabled: false, ..rule("[", "") }];
+        let errors = compile(&rules).err().unwrap();
+        assert_eq!(errors.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let root = std::env::temp_dir().join(format!("test_clean_rules_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        assert_eq!(save(&root, &rules).unwrap().len(), 3);
+        assert!(!root.join(RULES_FILE).exists());
+        assert!(save(&root, &rules[..1]).unwrap().is_empty());
+        assert_eq!(load(&root), rules[..1].to_vec());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn traced_and_plain_apply_agree() {
+        let rules = vec![rule(r"本书首发\S+", ""), rule(r"(\d+)章", "第${1}章"), CleanRule { enabled: false, ..rule("他", "她") }];
+        let compiled = compile(&rules).unwrap();
+        let text = "他走了。本书首发某某网，请支持正版。\n见12章。";
+        let mut stats = empty_stats(&rules, &compiled);
+        let traced = apply_traced(text, &compiled, Some(&mut stats));
+        assert_eq!(traced, apply(text, &compiled));
+        assert_eq!(traced, "他走了。\n见第12章。");
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].matches, 1);
+        assert_eq!(stats[0].samples[0].after, "他走了。\n见12章。");
+        assert_eq!(stats[1].samples[0], Snippet { before: "他走了。\n见12章。".to_string(), after: "他走了。\n见第12章。".to_string() });
+    }
+
+    #[test]
+    fn samples_spread_across_the_book() {
+        let files: Vec<String> = (1..=10).map(crate::library::chapter_file_name).collect();
+        assert_eq!(sample_files(&files, 3), vec!["01.txt", "04.txt", "07.txt"]);
+        assert_eq!(sample_files(&files[..2], 5).len(), 2);
+    }
+}
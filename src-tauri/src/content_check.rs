@@ -0,0 +1,198 @@
+//! 章节正文的"像不像小说"检查：选择器漂移后匹配到导航栏、评论区时，下载照样"成功"，
+//! 文件里却全是界面文字。每章下载后按中文占比、平均行长、界面词汇、重复行打分，
+//! 低于阈值的章节在 chapters.json 中标为 `suspect`，同平台连续多章可疑时推送
+//! [`DEGRADED_EVENT`]，提示检查选择器。阈值和词汇可在设置的 `content_check` 中按平台覆盖。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::chapter_index::ChapterIndex;
+use crate::{library, sharding, storage};
+
+/// 同一平台连续可疑章节达到该数量时推送的事件
+pub const DEGRADED_EVENT: &str = "spider-degraded";
+/// 连续可疑多少章算"抓取退化"
+pub const DEGRADED_STREAK: u32 = 3;
+/// 含界面词汇的行只在不超过该字数时计入（正文里偶尔出现"登录"不算）
+const UI_LINE_MAX_CHARS: usize = 20;
+
+/// 通用的界面词汇，平台自己的见 [`crate::spiders::ui_vocabulary`]
+const COMMON_UI_VOCABULARY: &[&str] = &["上一章", "下一章", "加入书架", "登录", "注册", "目录", "返回顶部", "投推荐票", "发表评论", "APP"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ContentCheckConfig {
+    /// 总分低于它判为可疑（满分 1）
+    pub min_score: f64,
+    /// 非空白字符中中日韩文字的最低占比
+    pub min_cjk_ratio: f64,
+    /// 非空行的最低平均字数
+    pub min_avg_line_chars: f64,
+    /// 重复行（与前文某行完全相同）占非空行的最高比例
+    pub max_repeated_ratio: f64,
+    /// 界面词汇。设置中留空时用通用词汇加平台词汇（见 [`ContentCheckConfig::for_platform`]）
+    pub ui_vocabulary: Vec<String>,
+}
+
+impl Default for ContentCheckConfig {
+    fn default() -> Self {
+        ContentCheckConfig { min_score: 0.6, min_cjk_ratio: 0.5, min_avg_line_chars: 8.0, max_repeated_ratio: 0.3, ui_vocabulary: Vec::new() }
+    }
+}
+
+impl ContentCheckConfig {
+    /// 平台的缺省配置：通用阈值，词汇为通用词汇加平台词汇
+    pub fn for_platform(platform: &str) -> Self {
+        ContentCheckConfig::default().with_platform_vocabulary(platform)
+    }
+
+    /// 词汇为空时补上通用词汇和平台词汇
+    pub fn with_platform_vocabulary(mut self, platform: &str) -> Self {
+        if self.ui_vocabulary.is_empty() {
+            self.ui_vocabulary =
+                COMMON_UI_VOCABULARY.iter().chain(crate::spiders::ui_vocabulary(platform)).map(|s| s.to_string()).collect();
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ContentScore {
+    pub score: f64,
+    pub cjk_ratio: f64,
+    pub avg_line_chars: f64,
+    pub repeated_ratio: f64,
+    /// 命中的界面词汇（去重）
+    pub ui_hits: Vec<String>,
+    pub suspect: bool,
+    /// 扣分原因
+    pub reasons: Vec<String>,
+}
+
+/// [`DEGRADED_EVENT`] 的内容
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Degraded {
+    pub platform: String,
+    pub novel_title: String,
+    /// 连续可疑的章数
+    pub streak: u32,
+    /// 最近一章的扣分原因
+    pub reasons: Vec<String>,
+}
+
+/// verify_novel 的结果中的一章
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SuspectChapter {
+    pub index: usize,
+    pub file: String,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x3040..=0x30FF | 0xAC00..=0xD7AF | 0x20000..=0x2FA1F)
+}
+
+/// 给章节正文（不含标题头部）打分
+pub fn classify(body: &str, config: &ContentCheckConfig) -> ContentScore {
+    let lines: Vec<&str> = body.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let (cjk, visible) = body.chars().filter(|c| !c.is_whitespace()).fold((0usize, 0usize), |(k, v), c| (k + usize::from(is_cjk(c)), v + 1));
+    let cjk_ratio = if visible == 0 { 0.0 } else { cjk as f64 / visible as f64 };
+    let avg_line_chars = if lines.is_empty() { 0.0 } else { lines.iter().map(|l| l.chars().count()).sum::<usize>() as f64 / lines.len() as f64 };
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let repeated = lines.iter().filter(|l| !seen.insert(**l)).count();
+    let repeated_ratio = if lines.is_empty() { 0.0 } else { repeated as f64 / lines.len() as f64 };
+
+    let ui_hits: Vec<String> = config
+        .ui_vocabulary
+        .iter()
+        .filter(|word| lines.iter().any(|l| l.chars().count() <= UI_LINE_MAX_CHARS && l.contains(word.as_str())))
+        .cloned()
+        .collect();
+
+    let mut score = 1.0;
+    let mut reasons = Vec::new();
+    if cjk_ratio < config.min_cjk_ratio {
+        score -= 0.4;
+        reasons.push(format!("中文占比 {:.0}%", cjk_ratio * 100.0));
+    }
+    if avg_line_chars < config.min_avg_line_chars {
+        score -= 0.3;
+        reasons.push(format!("平均每行 {:.1} 字", avg_line_chars));
+    }
+    if repeated_ratio > config.max_repeated_ratio {
+        score -= 0.3;
+        reasons.push(format!("重复行 {:.0}%", repeated_ratio * 100.0));
+    }
+    if !ui_hits.is_empty() {
+        score -= (0.1 * ui_hits.len() as f64).min(0.4);
+        reasons.push(format!("界面文字: {}", ui_hits.join("、")));
+    }
+    let score = score.max(0.0);
+    ContentScore { score, cjk_ratio, avg_line_chars, repeated_ratio, ui_hits, suspect: score < config.min_score, reasons }
+}
+
+/// 重新检查已下载的全部章节，更新 chapters.json 中的 `suspect` 标记，返回可疑章节
+pub fn verify(novel_dir: &Path, config: &ContentCheckConfig) -> Result<Vec<SuspectChapter>, String> {
+    ChapterIndex::update(novel_dir, |index| {
+        let mut suspects = Vec::new();
+        for (i, file) in sharding::chapter_files(novel_dir) {
+            let Ok(text) = storage::read_to_string(&novel_dir.join(&file)) else { continue };
+            let body = library::ChapterFile::parse(&text).map(|c| c.body).unwrap_or(text);
+            let score = classify(&body, config);
+            index.set_suspect(i, score.suspect);
+            if score.suspect {
+                suspects.push(SuspectChapter { index: i, file, score: score.score, reasons: score.reasons });
+            }
+        }
+        Ok(suspects)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str = concat!(
+        "　　夜色渐深，青石镇的街道上只剩下零星几盏灯笼在风中摇晃。\n",
+        "　　林默背着药篓从山上下来，衣角被露水打得透湿，却顾不上擦一擦额头的汗。\n",
+        "　　“小默，这么晚才回来？”隔壁的王婶推开窗户，压低了声音喊他，“你爹又咳了一晚上，快去看看吧。”\n",
+        "　　林默应了一声，脚步更快了几分。他知道，今天采到的那株七叶灵芝，或许就是父亲最后的希望。\n",
+        "　　推开院门的那一刻，他忽然察觉到一股陌生的气息——屋里，有人。",
+    );
+
+    const NAV_BAR: &str = "上一章\n目录\n下一章\n加入书架\n投推荐票\n登录\n注册\n上一章\n目录\n下一章\n加入书架\n上一章\n下一章\n返回顶部\n\
+Copyright 2024 All Rights Reserved\n下载APP，免费读全本";
+
+    const COMMENTS: &str = "用户123456：写得真好\n用户123456：写得真好\n用户778899：催更催更\n用户778899：催更催更\n\
+发表评论\n登录后才能评论\n用户556677：打卡\n用户556677：打卡";
+
+    #[test]
+    fn real_prose_passes() {
+        let score = classify(GOOD, &ContentCheckConfig::for_platform("qidian"));
+        assert!(!score.suspect, "{:?}", score);
+        assert!(score.cjk_ratio > 0.7);
+        assert!(score.ui_hits.is_empty());
+        // 正文长句里出现界面词汇不计
+        let prose = format!("{}\n　　他想起三年前离开宗门时，师父让他登录名册的那个清晨，目录上自己的名字被朱笔圈了出来。", GOOD);
+        assert!(classify(&prose, &ContentCheckConfig::for_platform("qidian")).ui_hits.is_empty());
+    }
+
+    #[test]
+    fn navigation_and_comment_sections_are_suspect() {
+        let nav = classify(NAV_BAR, &ContentCheckConfig::for_platform("qidian"));
+        assert!(nav.suspect, "{:?}", nav);
+        assert!(nav.ui_hits.contains(&"加入书架".to_string()));
+        assert!(nav.repeated_ratio > 0.3);
+
+        let comments = classify(COMMENTS, &ContentCheckConfig::for_platform("fanqie"));
+        assert!(comments.suspect, "{:?}", comments);
+
+        // 阈值可按平台放宽
+        let lenient = ContentCheckConfig { min_score: 0.0, ..ContentCheckConfig::default() }.with_platform_vocabulary("qidian");
+        assert!(!classify(NAV_BAR, &lenient).suspect);
+        assert!(classify("", &ContentCheckConfig::default()).suspect);
+    }
+}
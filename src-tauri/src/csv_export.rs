@@ -0,0 +1,44 @@
+//! 导出给表格软件的 CSV：UTF-8 带 BOM（Excel 直接打开中文不乱码），CRLF 换行，按 RFC 4180 转义。
+
+/// 含逗号、引号或换行的字段加引号，内部引号双写
+pub fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 一行（含结尾的 CRLF），供逐行写出的导出使用
+pub fn line<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut out = fields.iter().map(|f| field(f.as_ref())).collect::<Vec<_>>().join(",");
+    out.push_str("\r\n");
+    out
+}
+
+/// 表头，带 BOM
+pub fn header(header: &[&str]) -> String {
+    format!("\u{feff}{}", line(header))
+}
+
+pub fn render<R: AsRef<[String]>>(header_fields: &[&str], rows: &[R]) -> String {
+    let mut out = header(header_fields);
+    for row in rows {
+        out.push_str(&line(row.as_ref()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_quoted_only_when_needed() {
+        assert_eq!(field("书名"), "书名");
+        assert_eq!(field("1,234"), "\"1,234\"");
+        assert_eq!(field("他说\"好\"\n"), "\"他说\"\"好\"\"\n\"");
+        let csv = render(&["a", "b"], &[vec!["1".to_string(), "x,y".to_string()]]);
+        assert_eq!(csv, "\u{feff}a,b\r\n1,\"x,y\"\r\n");
+    }
+}
@@ -230,17 +230,28 @@ pub fn load_novel_for_review(
 
     let mut blob = String::new();
     let mut chapter_count = 0usize;
-    for r in rows {
-        if let Ok((idx, ch_title, outline)) = r {
-            blob.push_str(&format!("\n## 第{}章 {}\n{}\n", idx, ch_title, outline));
-            chapter_count += 1;
-        }
+    for (idx, ch_title, outline) in rows.flatten() {
+        blob.push_str(&format!("\n## 第{}章 {}\n{}\n", idx, ch_title, outline));
+        chapter_count += 1;
     }
 
     Ok((title, tags, blob, chapter_count))
 }
 
 /// 写入 multi-agent 评估结果到 novels.ai_reviews_json 并刷新 updated_at
+/// 按书名取各章的 outline_json（未提纯的章节为 None），按章节序号升序。同名书取最近更新的一本。
+pub fn load_chapter_outlines(conn: &Connection, title: &str) -> Result<Vec<(usize, Option<String>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT chapter_index, outline_json FROM chapters
+         WHERE novel_id = (SELECT id FROM novels WHERE title = ?1 ORDER BY updated_at DESC LIMIT 1)
+         ORDER BY chapter_index ASC",
+    )?;
+    let rows = stmt.query_map(params![title], |row| {
+        Ok((row.get::<_, i64>(0)?.max(0) as usize, row.get::<_, Option<String>>(1)?))
+    })?;
+    Ok(rows.flatten().map(|(idx, outline)| (idx, outline.filter(|o| !o.trim().is_empty()))).collect())
+}
+
 pub fn update_ai_reviews(
     conn: &Connection,
     novel_id: i64,
@@ -258,16 +269,15 @@ pub fn update_ai_reviews(
 // =========================================================================
 
 /// 排序枚举（与前端 SortBy union type 对齐，serde rename snake_case）
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum NovelSortBy {
+    #[default]
     UpdatedDesc,
     LatestRankAsc,
     ScanCountDesc,
-}
-
-impl Default for NovelSortBy {
-    fn default() -> Self { NovelSortBy::UpdatedDesc }
+    /// 按字数（优先实际下载统计）降序
+    WordCountDesc,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
@@ -299,6 +309,16 @@ pub struct NovelListRow {
     pub latest_rank: Option<i64>,
     /// 累计上榜次数 = rank_history 中按该 novel 的行数。
     pub scan_count: i64,
+
+    /// 以下来自下载目录的 info.json，由 list_novels 命令补充；数据库中没有。
+    pub downloaded_chars: Option<u64>,
+    pub downloaded_chapters: Option<usize>,
+    /// 站点显示字数解析出的近似值
+    pub reported_chars: Option<u64>,
+    /// info.json 中 user.archived
+    pub archived: bool,
+    /// 最近一次来源链接检查的结果
+    pub source_status: Option<crate::source_check::SourceStatus>,
 }
 
 /// 查询书库卡片列表。
@@ -348,6 +368,8 @@ pub fn list_novels(conn: &Connection, filter: &NovelListFilter) -> Result<Vec<No
         NovelSortBy::UpdatedDesc => "ORDER BY n.updated_at DESC",
         NovelSortBy::LatestRankAsc => "ORDER BY latest_rank ASC NULLS LAST, n.updated_at DESC",
         NovelSortBy::ScanCountDesc => "ORDER BY scan_count DESC, n.updated_at DESC",
+        // 调用方补充下载统计后会再按实际字数排一次
+        NovelSortBy::WordCountDesc => "ORDER BY n.word_count DESC, n.updated_at DESC",
     };
 
     let sql = format!(
@@ -394,6 +416,11 @@ pub fn list_novels(conn: &Connection, filter: &NovelListFilter) -> Result<Vec<No
             ai_reviews,
             latest_rank: row.get(10)?,
             scan_count: row.get(11)?,
+            downloaded_chars: None,
+            downloaded_chapters: None,
+            reported_chars: None,
+            archived: false,
+            source_status: None,
         })
     })?;
 
@@ -0,0 +1,196 @@
+//! 大任务开始前的磁盘空间预检。
+//!
+//! 下载、整书导出、工作区备份 / 恢复在写第一个文件前估算需要的空间，和目标所在磁盘的可用空间比较：
+//! 不足时直接返回 [`ErrorCode::DiskSpaceInsufficient`]，不留下写了一半的文件；可用空间不到估算量的
+//! [`MARGIN_FACTOR`] 倍时发出 [`WARNING_EVENT`] 后照常进行。查询可用空间失败（网络盘等）时只记日志，不阻止任务。
+
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+use crate::errors::{AppError, ErrorCode};
+use crate::events::{self, EventSink};
+use crate::{paths, sharding};
+
+/// 可用空间偏紧时发出的事件，payload 为 [`SpaceCheck`]
+pub const WARNING_EVENT: &str = "disk-space-warning";
+/// 可用空间不到估算量的这个倍数时提醒
+pub const MARGIN_FACTOR: u64 = 2;
+/// 书中还没有章节文件可参考时，每章按这个大小估算（四千字左右的章节，UTF-8 约 12 KB，另留文件头余量）
+pub const DEFAULT_CHAPTER_BYTES: u64 = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceLevel {
+    Enough,
+    /// 够用，但不到估算量的 [`MARGIN_FACTOR`] 倍
+    Marginal,
+    Insufficient,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SpaceCheck {
+    /// 任务说明，如"下载《书名》"
+    pub task: String,
+    /// 检查的目标路径（不存在时按最近的已存在上级目录所在磁盘）
+    pub path: String,
+    pub required_bytes: u64,
+    pub available_bytes: u64,
+    pub level: SpaceLevel,
+}
+
+/// 工作区所在磁盘的空间，供 run_diagnostics 显示
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct VolumeSpace {
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// 字节数的可读形式，如 `1.5 GB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 目标路径还不存在时（新书目录、待创建的导出目录）取最近的已存在上级
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.exists())
+}
+
+/// 目标路径所在磁盘的可用空间（当前用户可用的部分）
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let existing = existing_ancestor(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "路径及其上级都不存在"))?;
+    fs2::available_space(paths::long_path(existing))
+}
+
+pub fn volume_space(path: &Path) -> io::Result<VolumeSpace> {
+    let existing = existing_ancestor(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "路径及其上级都不存在"))?;
+    let existing = paths::long_path(existing);
+    Ok(VolumeSpace { available_bytes: fs2::available_space(&existing)?, total_bytes: fs2::total_space(&existing)? })
+}
+
+pub fn level(required: u64, available: u64) -> SpaceLevel {
+    if available < required {
+        SpaceLevel::Insufficient
+    } else if available < required.saturating_mul(MARGIN_FACTOR) {
+        SpaceLevel::Marginal
+    } else {
+        SpaceLevel::Enough
+    }
+}
+
+/// 比较估算量和可用空间，空间不足时返回错误。查询失败时返回 None
+pub fn check(task: &str, target: &Path, required: u64) -> Result<Option<SpaceCheck>, AppError> {
+    let available = match available_space(target) {
+        Ok(available) => available,
+        Err(e) => {
+            crate::log_to_file(&format!("[DiskSpace] 无法查询 {} 的可用空间，跳过预检: {}", target.display(), e));
+            return Ok(None);
+        }
+    };
+    let check = SpaceCheck {
+        task: task.to_string(),
+        path: target.display().to_string(),
+        required_bytes: required,
+        available_bytes: available,
+        level: level(required, available),
+    };
+    if check.level == SpaceLevel::Insufficient {
+        return Err(AppError::new(
+            ErrorCode::DiskSpaceInsufficient,
+            format!(
+                "{}预计需要 {}，{} 所在磁盘仅剩 {}",
+                task,
+                format_bytes(required),
+                target.display(),
+                format_bytes(available)
+            ),
+        ));
+    }
+    Ok(Some(check))
+}
+
+/// [`check`] 之后，空间偏紧时发出 [`WARNING_EVENT`]
+pub fn ensure<E: EventSink + ?Sized>(events: &E, task: &str, target: &Path, required: u64) -> Result<(), AppError> {
+    if let Some(check) = check(task, target, required)? {
+        if check.level == SpaceLevel::Marginal {
+            crate::log_to_file(&format!(
+                "[DiskSpace] {}预计需要 {}，可用空间仅 {}",
+                task,
+                format_bytes(required),
+                format_bytes(check.available_bytes)
+            ));
+            events::emit_and_buffer(events, WARNING_EVENT, check);
+        }
+    }
+    Ok(())
+}
+
+/// 目录下普通文件的总大小（递归，不跟随符号链接）
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map_or(0, |m| m.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// 下载 chapters 章预计需要的空间：按书中已有章节文件的平均大小，没有时按 [`DEFAULT_CHAPTER_BYTES`]
+pub fn estimate_download(novel_dir: &Path, chapters: usize) -> u64 {
+    let sizes: Vec<u64> = sharding::chapter_files(novel_dir)
+        .into_iter()
+        .filter_map(|(_, file)| std::fs::metadata(novel_dir.join(file)).ok().map(|m| m.len()))
+        .collect();
+    let average = match sizes.len() {
+        0 => DEFAULT_CHAPTER_BYTES,
+        n => (sizes.iter().sum::<u64>() / n as u64).max(1),
+    };
+    average.saturating_mul(chapters as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_estimates_and_missing_targets() {
+        assert_eq!(level(100, 99), SpaceLevel::Insufficient);
+        assert_eq!(level(100, 150), SpaceLevel::Marginal);
+        assert_eq!(level(100, 200), SpaceLevel::Enough);
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MB");
+
+        let dir = std::env::temp_dir().join(format!("test_disk_space_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(estimate_download(&dir, 10), 10 * DEFAULT_CHAPTER_BYTES);
+        std::fs::write(dir.join("01.txt"), vec![b'a'; 1000]).unwrap();
+        std::fs::write(dir.join("02.txt"), vec![b'a'; 3000]).unwrap();
+        std::fs::write(dir.join("info.json"), vec![b'a'; 50_000]).unwrap();
+        assert_eq!(estimate_download(&dir, 10), 20_000);
+        assert_eq!(dir_size(&dir), 54_000);
+
+        // 还不存在的目标按上级目录所在磁盘查询
+        let missing = dir.join("新书").join("章节");
+        assert!(available_space(&missing).unwrap() > 0);
+        let err = check("测试", &missing, u64::MAX).unwrap_err();
+        assert_eq!(err.code, ErrorCode::DiskSpaceInsufficient);
+        assert!(check("测试", &missing, 1).unwrap().is_some_and(|c| c.level == SpaceLevel::Enough));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,1170 @@
+//! 单本下载：目录（catalog）获取 + 按区间或按勾选下载章节。
+//!
+//! 目录结果按规范化 URL 在内存中缓存（默认 15 分钟，`CATALOG_CACHE_TTL_SECS` 可覆盖）。
+//! 预览、勾选下载、更新和预取都经 [`get_catalog_cached`] 取目录，"先看目录再勾选章节"的流程里
+//! 下载直接复用预览刚拿到的目录；同一本书同时未命中时只抓取一次，其余调用方等待同一结果。
+//!
+//! 单本下载可以带时限（[`DownloadRequest::time_budget`]，批量下载缺省 [`DEFAULT_TIME_BUDGET`]）：超时后在下一章
+//! 开始前停止，已下载的章节照常记入 chapters.json 和下载记录（`timed_out`），并发出 [`TIMED_OUT_EVENT`]，
+//! 批量任务接着处理下一本。网络处于 paused 的时间不计入时限。之后用 update_novel 更新即从停下的地方继续。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::time::Duration;
+
+use crate::spiders::fanqie::NovelMetadata;
+use crate::errors::{AppError, ErrorCode};
+use crate::events::EventSink;
+use crate::spiders::{NovelSource, SpiderError};
+use crate::progress::{emit_coded_progress, emit_progress, emit_tagged_progress, WaitReporter};
+use crate::catalog_order::{self, CatalogAnomaly, VolumeReset, VolumeSource};
+use crate::chapter_index::{ChapterCheck, ChapterIndex, ChapterRecord};
+use crate::{
+    clean_rules, content_check, disk_space, hooks, library, novel_info, resources, segmentation, settings, sharding, source_check, storage,
+    text_normalize,
+};
+use crate::edit_journal;
+
+const DEFAULT_CATALOG_TTL: Duration = Duration::from_secs(15 * 60);
+pub(crate) const DEFAULT_CHAPTER_COUNT: usize = 3;
+/// 相邻两章之间的礼貌间隔
+const CHAPTER_INTERVAL: Duration = Duration::from_millis(200);
+/// 预取任务等待用户下载结束时的轮询间隔
+const PREFETCH_YIELD_INTERVAL: Duration = Duration::from_secs(2);
+pub const PREFETCH_TAG: &str = "prefetch";
+/// 批量下载中单本书的缺省时限
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(30 * 60);
+/// 单本下载超过时限而停止时发出的事件，payload 为 [`TimedOut`]
+pub const TIMED_OUT_EVENT: &str = "download-timed-out";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CatalogEntry {
+    /// 目录序号，从 1 开始，与 `NN.txt` 文件名一致
+    pub index: usize,
+    pub title: String,
+    /// 标题过长被截断时的完整标题
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_title: Option<String>,
+    pub url: String,
+    pub is_vip: bool,
+    /// 标题中的章节号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u64>,
+    /// 目录页上的卷名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_title: Option<String>,
+    /// 分卷各自编号时所在的卷，见 [`crate::catalog_order::CatalogOrder::volume_at`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<usize>,
+    /// 该章相关的目录顺序异常说明
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<String>,
+    /// 作品相关 / 公告类非正文
+    pub is_extra: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Catalog {
+    pub novel_title: String,
+    pub chapters: Vec<CatalogEntry>,
+    /// 目录顺序异常，位置为原始目录中的位置
+    pub anomalies: Vec<CatalogAnomaly>,
+    /// 分卷各自从第 1 章编号的重置点，不是异常
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub volume_resets: Vec<VolumeReset>,
+    #[serde(skip)]
+    metadata: Option<NovelMetadata>,
+}
+
+/// 已落盘（或校验通过而跳过）的章节，边下载边分析时发给分析队列
+#[derive(Debug, Clone)]
+pub struct ChapterReady {
+    pub novel_dir: PathBuf,
+    pub index: usize,
+}
+
+/// 一次下载任务的参数。`selected_indices` 存在时忽略 start/count。
+#[derive(Debug, Clone, Default)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub platform: String,
+    pub debug_visible: bool,
+    pub start_chapter: Option<usize>,
+    pub chapter_count: Option<usize>,
+    pub selected_indices: Option<Vec<usize>>,
+    /// 指定写入的小说目录（修复已有书籍时使用），缺省为 `downloads/<书名>`
+    pub novel_dir: Option<PathBuf>,
+    /// 已存在的章节也重新下载（内容有变化时按设置保留旧版本）
+    pub force: bool,
+    /// 每章就绪后通知（接收端关闭时忽略）
+    pub chapter_tx: Option<tokio::sync::mpsc::UnboundedSender<ChapterReady>>,
+    /// 置为 true 时在下一章开始前停止
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// 成功结束后执行设置中的下载完成通知
+    pub notify: bool,
+    /// 按 start/count 下载时跳过作品相关 / 公告类章节（"前 20 章"即 20 章正文），不影响勾选序号
+    pub skip_extras: bool,
+    /// 阅读时的后台预取：进度带 `prefetch` 标签，每章开始前让位于用户发起的下载
+    pub prefetch: bool,
+    /// 直接使用的目录（预取时取自 chapters.json），缺省时用缓存或重新获取
+    pub catalog: Option<Catalog>,
+    /// 本次下载的正文最少字数，缺省按设置中该平台的阈值
+    pub min_chapter_chars: Option<usize>,
+    /// 覆盖内容有变化的章节时是否记入修改日志，缺省按设置中的 keep_chapter_versions（批量重新下载时始终记录）
+    pub record_edits: Option<bool>,
+    /// start/count 从目录开头还是末尾数起，见 [`DownloadOrder`]
+    pub order: DownloadOrder,
+    /// 本书的时限，超过后在下一章开始前停止；None 为不限
+    pub time_budget: Option<Duration>,
+}
+
+impl DownloadRequest {
+    pub fn selection(&self) -> ChapterSelection<'_> {
+        ChapterSelection {
+            start_chapter: self.start_chapter,
+            chapter_count: self.chapter_count,
+            selected: self.selected_indices.as_deref(),
+            skip_extras: self.skip_extras,
+            order: self.order,
+        }
+    }
+}
+
+/// 按区间下载时 start/count 的方向
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadOrder {
+    /// 从第一章往后数（原来的行为）
+    #[default]
+    FromStart,
+    /// 从最新一章往前数：连载中的书取最近的 count 章，start_chapter 为 1 时即最新一章
+    FromEnd,
+}
+
+/// 要下载哪些章节：勾选序号优先，否则按 start/count 和方向取连续区间
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChapterSelection<'a> {
+    pub start_chapter: Option<usize>,
+    pub chapter_count: Option<usize>,
+    pub selected: Option<&'a [usize]>,
+    /// 按区间取时只数正文章节（作品相关 / 公告不计数也不下载），不影响勾选序号
+    pub skip_extras: bool,
+    pub order: DownloadOrder,
+}
+
+/// 下载预览中的"将下载"列表
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadPreview {
+    pub novel_title: String,
+    pub total: usize,
+    pub will_download: Vec<CatalogEntry>,
+    pub out_of_range: Vec<usize>,
+    pub skipped_extras: Vec<usize>,
+    /// 探测一章得到的访问状态（可读 / 付费 / 需登录），见 [`crate::access_probe`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access: Option<crate::access_probe::AccessProbe>,
+}
+
+impl DownloadPreview {
+    pub fn new(catalog: &Catalog, selection: &ChapterSelection) -> Self {
+        let plan = select_chapters(&catalog.chapters, selection);
+        DownloadPreview {
+            novel_title: catalog.novel_title.clone(),
+            total: catalog.chapters.len(),
+            will_download: plan.indices.iter().map(|&i| catalog.chapters[i - 1].clone()).collect(),
+            out_of_range: plan.out_of_range,
+            skipped_extras: plan.skipped_extras,
+            access: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChapterPlan {
+    /// 要下载的目录序号（从 1 开始，升序）
+    pub indices: Vec<usize>,
+    /// 超出目录范围的勾选序号
+    pub out_of_range: Vec<usize>,
+    /// 按区间取时跳过的非正文章节
+    pub skipped_extras: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadSummary {
+    pub success: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// 已下架 / 不可用（章节链接跳转到了非章节页面）的章节，不重试
+    pub unavailable: usize,
+    pub out_of_range: Vec<usize>,
+    /// 失败和已下架章节的原因
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<ChapterFailure>,
+    /// 开启多章合页拆分时：目录条数 → 实际章节数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segmented: Option<segmentation::SegmentStats>,
+    /// 超过时限而提前停止
+    pub timed_out: bool,
+}
+
+/// [`TIMED_OUT_EVENT`] 的内容
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TimedOut {
+    pub novel_title: String,
+    pub url: String,
+    pub budget_secs: u64,
+    /// 扣除网络暂停后实际用掉的秒数
+    pub elapsed_secs: u64,
+    pub downloaded: usize,
+    /// 还没轮到的章节数
+    pub remaining: usize,
+}
+
+/// 单本下载的计时：从开始算起的耗时，扣除其间网络处于 paused 的时长
+struct TimeBudget {
+    limit: Duration,
+    started: Instant,
+    paused_at_start: Duration,
+}
+
+impl TimeBudget {
+    fn start(limit: Duration) -> Self {
+        TimeBudget { limit, started: Instant::now(), paused_at_start: crate::network_mode::paused_time() }
+    }
+
+    fn elapsed(&self) -> Duration {
+        let paused = crate::network_mode::paused_time().saturating_sub(self.paused_at_start);
+        self.started.elapsed().saturating_sub(paused)
+    }
+
+    fn exceeded(&self) -> bool {
+        self.elapsed() >= self.limit
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChapterFailure {
+    pub index: usize,
+    pub error: String,
+}
+
+// ========================================================================
+//  目录缓存
+// ========================================================================
+
+/// 目录缓存的有效期，`CATALOG_CACHE_TTL_SECS` 可覆盖
+pub fn catalog_ttl() -> Duration {
+    std::env::var("CATALOG_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CATALOG_TTL)
+}
+
+struct CachedCatalog {
+    fetched_at: Instant,
+    platform: String,
+    catalog: Catalog,
+}
+
+#[derive(Default)]
+struct CatalogCache {
+    entries: HashMap<String, CachedCatalog>,
+    hits: u64,
+    misses: u64,
+}
+
+/// 目录缓存的命中统计，随 `get_spider_metrics` 返回
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct CatalogCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// 未过期的缓存条数
+    pub size: usize,
+    pub ttl_secs: u64,
+}
+
+fn catalog_cache() -> &'static Mutex<CatalogCache> {
+    static CACHE: OnceLock<Mutex<CatalogCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(CatalogCache::default()))
+}
+
+/// 同一本书正在进行的目录抓取，并发未命中的调用方共用一个
+type CatalogFetch = Arc<tokio::sync::OnceCell<Result<Catalog, AppError>>>;
+
+fn inflight() -> &'static Mutex<HashMap<String, CatalogFetch>> {
+    static INFLIGHT: OnceLock<Mutex<HashMap<String, CatalogFetch>>> = OnceLock::new();
+    INFLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 规范化书籍 URL 作为缓存 key：去掉 query/fragment 和末尾斜杠，移动站 `m.` 统一为 `www.`。
+pub fn canonical_url(raw: &str) -> String {
+    let trimmed = raw.trim();
+    let Ok(parsed) = url::Url::parse(trimmed) else {
+        return trimmed.trim_end_matches('/').to_string();
+    };
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    let host = match host.strip_prefix("m.") {
+        Some(rest) => format!("www.{}", rest),
+        None => host,
+    };
+    format!("https://{}{}", host, parsed.path().trim_end_matches('/'))
+}
+
+/// 查缓存并计入命中 / 未命中；平台不同的缓存视为未命中
+fn cached_catalog(key: &str, platform: &str) -> Option<Catalog> {
+    let mut cache = catalog_cache().lock().unwrap_or_else(|e| e.into_inner());
+    let ttl = catalog_ttl();
+    let found = match cache.entries.get(key) {
+        Some(entry) if entry.fetched_at.elapsed() < ttl && entry.platform == platform => Some(entry.catalog.clone()),
+        Some(entry) if entry.fetched_at.elapsed() >= ttl => {
+            cache.entries.remove(key);
+            None
+        }
+        _ => None,
+    };
+    if found.is_some() {
+        cache.hits += 1;
+    } else {
+        cache.misses += 1;
+    }
+    found
+}
+
+fn store_catalog(key: String, platform: &str, catalog: Catalog, fetched_at: Instant) {
+    let mut cache = catalog_cache().lock().unwrap_or_else(|e| e.into_inner());
+    let ttl = catalog_ttl();
+    cache.entries.retain(|_, entry| entry.fetched_at.elapsed() < ttl);
+    cache.entries.insert(key, CachedCatalog { fetched_at, platform: platform.to_string(), catalog });
+}
+
+/// 丢弃某本书的缓存目录，下次取目录时重新抓取。返回是否有缓存被丢弃
+pub fn invalidate_catalog(url: &str) -> bool {
+    let mut cache = catalog_cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.entries.remove(&canonical_url(url)).is_some()
+}
+
+pub fn catalog_cache_stats() -> CatalogCacheStats {
+    let cache = catalog_cache().lock().unwrap_or_else(|e| e.into_inner());
+    let ttl = catalog_ttl();
+    CatalogCacheStats {
+        hits: cache.hits,
+        misses: cache.misses,
+        size: cache.entries.values().filter(|e| e.fetched_at.elapsed() < ttl).count(),
+        ttl_secs: ttl.as_secs(),
+    }
+}
+
+/// 取目录：缓存有效时直接返回（第二项为 true），否则抓取并写入缓存。
+/// 同一本书并发未命中时只抓取一次，on_fetch 只在本调用方实际需要等待抓取时调用。
+pub async fn get_catalog_cached<E: EventSink + ?Sized, S: NovelSource>(
+    events: &E,
+    source: &S,
+    workspace_root: &Path,
+    url: &str,
+    platform: &str,
+    debug_visible: bool,
+    on_fetch: impl FnOnce(),
+) -> Result<(Catalog, bool), AppError> {
+    let key = canonical_url(url);
+    if let Some(catalog) = cached_catalog(&key, platform) {
+        return Ok((catalog, true));
+    }
+    on_fetch();
+    let cell = inflight().lock().unwrap_or_else(|e| e.into_inner()).entry(key.clone()).or_default().clone();
+    let result = cell
+        .get_or_init(|| fetch_catalog(events, source, workspace_root, url, platform, debug_visible))
+        .await
+        .clone();
+    // 抓取已结束（成功的已写入缓存），移除占位；失败时下一次调用重新抓取
+    let mut map = inflight().lock().unwrap_or_else(|e| e.into_inner());
+    if map.get(&key).is_some_and(|current| Arc::ptr_eq(current, &cell)) {
+        map.remove(&key);
+    }
+    result.map(|catalog| (catalog, false))
+}
+
+// ========================================================================
+//  目录获取
+// ========================================================================
+
+/// 抓取完整目录和书名，并写入缓存。
+pub async fn fetch_catalog<E: EventSink + ?Sized, S: NovelSource>(
+    events: &E,
+    source: &S,
+    workspace_root: &Path,
+    url: &str,
+    platform: &str,
+    debug_visible: bool,
+) -> Result<Catalog, AppError> {
+    let mut chapters = source.fetch_catalog(platform, url, debug_visible).await?;
+    let metadata = source.fetch_metadata(platform, url, debug_visible).await;
+
+    // 蜘蛛会多取一章：恰好等于上限的目录是完整的，只有真的超出才提示并截断
+    let max_chapters = crate::spiders::max_catalog_chapters();
+    if chapters.len() > max_chapters {
+        chapters.truncate(max_chapters);
+        emit_progress(
+            events,
+            "warning",
+            resources::format("download.catalog_capped", &[("max", &max_chapters.to_string())]),
+        );
+    }
+
+    let key = canonical_url(url);
+    let metadata = match metadata {
+        Ok(m) => Some(m),
+        Err(e) => {
+            eprintln!("[Download] 获取书籍信息失败，书名改用 URL: {}", e);
+            None
+        }
+    };
+    let novel_title = metadata
+        .as_ref()
+        .map(|m| m.title.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| resources::format("download.untitled", &[("id", key.rsplit('/').next().unwrap_or_default())]));
+
+    // 章节号可靠时按 (卷, 章节号) 编排文件序号，否则保持遍历顺序
+    let order = catalog_order::analyze_volumes(
+        &chapters.iter().map(|c| c.full_title.as_deref().unwrap_or(&c.title)).collect::<Vec<_>>(),
+        &chapters.iter().map(|c| c.volume.as_deref()).collect::<Vec<_>>(),
+    );
+    if let Some(source) = order.volume_source {
+        let how = match source {
+            VolumeSource::Catalog => resources::text("download.volume_source_catalog"),
+            VolumeSource::Inferred => resources::text("download.volume_source_inferred"),
+        };
+        emit_progress(
+            events,
+            "progress",
+            resources::format(
+                "download.volumes_restart",
+                &[("title", &novel_title), ("volumes", &(order.volume_resets.len() + 1).to_string()), ("how", &how)],
+            ),
+        );
+    }
+    if !order.anomalies.is_empty() {
+        let listed: Vec<String> = order.anomalies.iter().take(20).map(CatalogAnomaly::describe).collect();
+        emit_progress(
+            events,
+            "warning",
+            resources::format(
+                if order.anomalies.len() > listed.len() { "download.catalog_anomalies_truncated" } else { "download.catalog_anomalies" },
+                &[("title", &novel_title), ("count", &order.anomalies.len().to_string()), ("list", &listed.join("；"))],
+            ),
+        );
+    }
+    let notes = order.notes();
+    let patterns = settings::load(workspace_root).extra_chapter_patterns();
+    let mut entries: Vec<Option<CatalogEntry>> = chapters
+        .into_iter()
+        .zip(notes)
+        .zip(&order.numbers)
+        .enumerate()
+        .map(|(i, ((c, anomalies), number))| {
+            let is_extra = c.is_extra || catalog_order::is_extra_title(c.full_title.as_deref().unwrap_or(&c.title), &patterns);
+            Some(CatalogEntry {
+                index: 0,
+                title: c.title,
+                full_title: c.full_title,
+                url: c.url,
+                is_vip: c.is_vip,
+                number: *number,
+                volume_title: c.volume,
+                volume: order.volume_at(i),
+                anomalies,
+                is_extra,
+            })
+        })
+        .collect();
+    let sequence: Vec<usize> = match order.numeric_order() {
+        Some(sequence) => {
+            emit_progress(events, "warning", resources::format("download.reordered_by_number", &[("title", &novel_title)]));
+            sequence
+        }
+        None => (0..entries.len()).collect(),
+    };
+
+    let catalog = Catalog {
+        novel_title,
+        chapters: sequence
+            .into_iter()
+            .filter_map(|i| entries[i].take())
+            .enumerate()
+            .map(|(i, entry)| CatalogEntry { index: i + 1, ..entry })
+            .collect(),
+        anomalies: order.anomalies,
+        volume_resets: order.volume_resets,
+        metadata,
+    };
+    let extras: Vec<String> = catalog.chapters.iter().filter(|c| c.is_extra).map(|c| format!("{}.{}", c.index, c.title)).collect();
+    if !extras.is_empty() {
+        emit_progress(
+            events,
+            "progress",
+            resources::format(
+                "download.catalog_extras",
+                &[("title", &catalog.novel_title), ("count", &extras.len().to_string()), ("list", &extras.join("、"))],
+            ),
+        );
+    }
+    store_catalog(key, platform, catalog.clone(), Instant::now());
+    Ok(catalog)
+}
+
+// ========================================================================
+//  预取与优先级
+// ========================================================================
+
+/// 进行中的用户发起下载数；不为 0 时预取任务在章节之间等待
+static FOREGROUND: AtomicUsize = AtomicUsize::new(0);
+
+struct ForegroundGuard;
+
+impl ForegroundGuard {
+    fn new() -> Self {
+        FOREGROUND.fetch_add(1, Ordering::SeqCst);
+        ForegroundGuard
+    }
+}
+
+impl Drop for ForegroundGuard {
+    fn drop(&mut self) {
+        FOREGROUND.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn foreground_active() -> bool {
+    FOREGROUND.load(Ordering::SeqCst) > 0
+}
+
+/// 正在预取的小说目录 → 任务 id，同一本书不重复预取
+fn prefetching() -> &'static Mutex<HashMap<PathBuf, String>> {
+    static PREFETCHING: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
+    PREFETCHING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 登记预取任务；该书已在预取时返回已有任务的 id
+pub fn begin_prefetch(novel_dir: &Path, task_id: &str) -> Result<(), String> {
+    let mut map = prefetching().lock().unwrap_or_else(|e| e.into_inner());
+    match map.get(novel_dir) {
+        Some(existing) => Err(existing.clone()),
+        None => {
+            map.insert(novel_dir.to_path_buf(), task_id.to_string());
+            Ok(())
+        }
+    }
+}
+
+pub fn end_prefetch(novel_dir: &Path) {
+    if let Ok(mut map) = prefetching().lock() {
+        map.remove(novel_dir);
+    }
+}
+
+/// 预取的起始序号（最远的已下载章节的下一章），以及 chapters.json 中的目录是否还有 ahead 章可下。
+/// 不够时说明连载可能已更新，需要重新获取目录。
+pub fn prefetch_start(index: &ChapterIndex, ahead: usize) -> (usize, bool) {
+    let start = index.records().filter(|r| r.downloaded).map(|r| r.index).max().unwrap_or(0) + 1;
+    let known = index.records().filter(|r| r.index >= start && !r.url.is_empty()).count();
+    (start, known >= ahead)
+}
+
+/// 由 chapters.json 还原的目录，不含书籍信息（info.json 中已有的不会被覆盖）
+pub fn catalog_from_index(novel_title: &str, index: &ChapterIndex) -> Catalog {
+    Catalog {
+        novel_title: novel_title.to_string(),
+        chapters: index
+            .records()
+            .map(|r| CatalogEntry {
+                index: r.index,
+                title: r.title.clone(),
+                full_title: r.full_title.clone(),
+                url: r.url.clone(),
+                is_vip: r.is_vip,
+                number: r.number,
+                volume_title: r.volume_title.clone(),
+                volume: r.volume,
+                anomalies: r.anomalies.clone(),
+                is_extra: r.is_extra,
+            })
+            .collect(),
+        anomalies: Vec::new(),
+        volume_resets: Vec::new(),
+        metadata: None,
+    }
+}
+
+// ========================================================================
+//  下载
+// ========================================================================
+
+/// 计算要下载的目录序号。勾选序号存在时去重排序，越界的单独列出；否则在候选章节（skip_extras 时只含
+/// 正文）中按 start/count 取连续区间，FromEnd 时从末尾往前数。返回的始终是真实目录序号，
+/// 文件名和 chapters.json 与从头下载时一致。
+pub fn select_chapters(catalog: &[CatalogEntry], selection: &ChapterSelection) -> ChapterPlan {
+    if let Some(selected) = selection.selected {
+        let mut wanted = selected.to_vec();
+        wanted.sort_unstable();
+        wanted.dedup();
+        let (indices, out_of_range) = wanted.into_iter().partition(|&i| i >= 1 && i <= catalog.len());
+        return ChapterPlan { indices, out_of_range, skipped_extras: Vec::new() };
+    }
+
+    let (candidates, skipped_extras): (Vec<&CatalogEntry>, Vec<&CatalogEntry>) =
+        catalog.iter().partition(|c| !(selection.skip_extras && c.is_extra));
+    let skip = selection.start_chapter.unwrap_or(1).max(1) - 1;
+    let count = selection.chapter_count.unwrap_or(DEFAULT_CHAPTER_COUNT);
+    let range = match selection.order {
+        DownloadOrder::FromStart => {
+            let start = skip.min(candidates.len());
+            start..start.saturating_add(count).min(candidates.len())
+        }
+        DownloadOrder::FromEnd => {
+            let end = candidates.len().saturating_sub(skip);
+            end.saturating_sub(count)..end
+        }
+    };
+    ChapterPlan {
+        indices: candidates[range].iter().map(|c| c.index).collect(),
+        out_of_range: Vec::new(),
+        skipped_extras: skipped_extras.iter().map(|c| c.index).collect(),
+    }
+}
+
+/// 写入 info.json 的书籍信息
+pub fn info_patch(
+    novel_title: &str,
+    url: &str,
+    platform: &str,
+    metadata: Option<&NovelMetadata>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut patch = serde_json::Map::new();
+    patch.insert("title".into(), novel_title.into());
+    patch.insert("url".into(), url.into());
+    patch.insert("platform".into(), platform.into());
+    if let Some(meta) = metadata {
+        patch.insert("tags".into(), meta.tags.clone().into());
+        patch.insert("honors".into(), meta.honors.clone().into());
+        patch.insert("word_count".into(), meta.word_count.clone().into());
+        patch.insert("description".into(), meta.description.clone().into());
+        patch.insert("metadata_truncated".into(), meta.truncated.clone().into());
+        // 完整抓取成功时写入 null，清掉之前兜底留下的标记
+        patch.insert("metadata_source".into(), meta.source.clone().into());
+    }
+    patch
+}
+
+/// 下载一本书：目录优先用缓存，章节写入 `downloads/<书名>/NN.txt`（大书分目录存放，见 [`sharding`]），
+/// info.json 在锁内合并。
+/// 目录和正文都经 source 抓取，进度经 events 推送（应用内分别是 [`crate::spiders::LiveSource`] 和 AppHandle）。
+pub async fn process_novel_download<E: EventSink + Sync + ?Sized, S: NovelSource>(
+    events: &E,
+    source: &S,
+    workspace_root: &Path,
+    req: DownloadRequest,
+) -> Result<DownloadSummary, String> {
+    let tag = req.prefetch.then_some(PREFETCH_TAG);
+    let emit = |status: &str, message: String| emit_tagged_progress(events, status, message, tag);
+    let emit_failure = |status: &str, message: String, code: ErrorCode| emit_coded_progress(events, status, message, Some(code), tag);
+    let _foreground = (!req.prefetch).then(ForegroundGuard::new);
+    let budget = req.time_budget.map(TimeBudget::start);
+    let catalog = match req.catalog.clone() {
+        Some(c) => c,
+        None => {
+            let fetched = get_catalog_cached(events, source, workspace_root, &req.url, &req.platform, req.debug_visible, || {
+                emit("progress", resources::format("download.fetching_catalog", &[("url", &req.url)]));
+            })
+            .await;
+            match fetched {
+                Ok((c, true)) => {
+                    emit("progress", resources::format("download.cached_catalog", &[("title", &c.novel_title), ("chapters", &c.chapters.len().to_string())]));
+                    c
+                }
+                Ok((c, false)) => c,
+                Err(e) => {
+                    emit_failure("error", resources::format("download.catalog_failed", &[("error", &e.to_string())]), e.code);
+                    // 更新已有的书时确认作品已下架：记入 info.json，之后的定时抓取不再尝试
+                    if let (ErrorCode::SpiderNovelRemoved, Some(novel_dir)) = (e.code, &req.novel_dir) {
+                        if let Err(write_err) = source_check::record_removed(events, workspace_root, novel_dir, &e.detail).await {
+                            eprintln!("[Download] 记录下架状态失败: {}", write_err);
+                        }
+                    }
+                    return Err(e.to_string());
+                }
+            }
+        }
+    };
+
+    let ChapterPlan { indices: plan, out_of_range, skipped_extras } = select_chapters(&catalog.chapters, &req.selection());
+    if !skipped_extras.is_empty() {
+        emit(
+            "progress",
+            resources::format(
+                "download.extras_skipped",
+                &[
+                    ("count", &skipped_extras.len().to_string()),
+                    (
+                        "list",
+                        &skipped_extras.iter().map(|&i| format!("{}.{}", i, catalog.chapters[i - 1].title)).collect::<Vec<_>>().join("、"),
+                    ),
+                ],
+            ),
+        );
+    }
+    if req.order == DownloadOrder::FromEnd && req.selected_indices.is_none() {
+        if let (Some(first), Some(last)) = (plan.first(), plan.last()) {
+            emit(
+                "progress",
+                resources::format(
+                    "download.from_end",
+                    &[("count", &plan.len().to_string()), ("first", &first.to_string()), ("last", &last.to_string())],
+                ),
+            );
+        }
+    }
+    let mut summary = DownloadSummary { out_of_range, ..Default::default() };
+    if !summary.out_of_range.is_empty() {
+        emit(
+            "progress",
+            resources::format(
+                "download.out_of_range",
+                &[("chapters", &catalog.chapters.len().to_string()), ("indices", &format!("{:?}", summary.out_of_range))],
+            ),
+        );
+    }
+
+    let novel_dir = req.novel_dir.clone().unwrap_or_else(|| {
+        library::novel_dir_in(&library::downloads_dir(workspace_root), &catalog.novel_title)
+    });
+    storage::create_dir_all(&novel_dir).map_err(|e| resources::format("download.create_dir_failed", &[("error", &e.to_string())]))?;
+    let patch = info_patch(&catalog.novel_title, &req.url, &req.platform, catalog.metadata.as_ref());
+    let info = novel_info::merge_or_create_info(&novel_dir, &patch).await?;
+    // 小说设置了正文规范化时按规范化后的正文保存
+    let normalize = text_normalize::from_info(&info);
+    let clean = clean_rules::load_compiled(workspace_root);
+    let settings = settings::load(workspace_root);
+    let min_chapter_chars = req.min_chapter_chars.unwrap_or_else(|| settings.min_chapter_chars(&req.platform));
+    // 拆分多章合页的平台：原始页面存入 .pages（目录和下载状态也记在那里），下载结束后统一拆成章节文件
+    let segmenting = settings.segment_pages(&req.platform);
+    let check_config = settings.content_check(&req.platform);
+    let page_dir = if segmenting { novel_dir.join(segmentation::PAGES_DIR) } else { novel_dir.clone() };
+    storage::create_dir_all(&page_dir).map_err(|e| resources::format("download.create_dir_failed", &[("error", &e.to_string())]))?;
+    // 完整目录（含未截断的标题）和每章下载状态保存在 chapters.json
+    // 目录超过阈值的新书（以及已分目录的书）章节直接写进 chapters/NNN/，已有的平铺大书只提示迁移
+    let mut shard_per_dir = None;
+    if let Some(per_dir) = settings.chapters_per_dir().filter(|_| !segmenting) {
+        if sharding::should_shard(&novel_dir, catalog.chapters.len(), per_dir) {
+            if !sharding::is_sharded(&novel_dir) {
+                emit(
+                    "progress",
+                    resources::format(
+                        "download.sharding",
+                        &[
+                            ("chapters", &catalog.chapters.len().to_string()),
+                            ("per_dir", &per_dir.to_string()),
+                            ("dir", sharding::SHARDS_DIR),
+                        ],
+                    ),
+                );
+            }
+            shard_per_dir = Some(per_dir);
+        } else if catalog.chapters.len() > per_dir {
+            emit("progress", resources::format("download.shard_hint", &[("chapters", &catalog.chapters.len().to_string())]));
+        }
+    }
+    // 本地这份索引只用于读取；每次修改都经 sync 在锁内基于最新的 chapters.json 进行
+    let mut index_file = ChapterIndex::load(&page_dir).with_min_body_chars(min_chapter_chars);
+    let catalog_synced = index_file.sync(|index| {
+        index.update_catalog(catalog.chapters.iter().map(|c| ChapterRecord {
+            index: c.index,
+            title: c.title.clone(),
+            full_title: c.full_title.clone(),
+            url: c.url.clone(),
+            is_vip: c.is_vip,
+            number: c.number,
+            volume_title: c.volume_title.clone(),
+            volume: c.volume,
+            anomalies: c.anomalies.clone(),
+            is_extra: c.is_extra,
+            ..Default::default()
+        }));
+        if let Some(per_dir) = shard_per_dir {
+            sharding::assign_paths(index, per_dir);
+        }
+        Ok(())
+    });
+    if let Err(e) = catalog_synced {
+        eprintln!("[Download] {}", e);
+    }
+    // 写章节前预检磁盘空间，只算还要下载的章节
+    let pending = plan.iter().filter(|&&i| req.force || !index_file.get(i).is_some_and(|r| r.downloaded)).count();
+    let task = resources::format("task.download", &[("title", &catalog.novel_title), ("chapters", &pending.to_string())]);
+    if let Err(e) = disk_space::ensure(events, &task, &page_dir, disk_space::estimate_download(&page_dir, pending)) {
+        emit_failure("error", e.to_string(), e.code);
+        return Err(e.to_string());
+    }
+
+    let mut edits = if req.record_edits.unwrap_or(settings.keep_chapter_versions > 0) {
+        Some(edit_journal::Recorder::begin(&novel_dir, edit_journal::EditKind::Redownload, settings.edit_history_max_bytes())?)
+    } else {
+        None
+    };
+    let mut waiter = WaitReporter::new(catalog.novel_title.clone()).tagged(tag);
+    let send_ready = |index: usize| {
+        if let Some(tx) = &req.chapter_tx {
+            let _ = tx.send(ChapterReady { novel_dir: novel_dir.clone(), index });
+        }
+    };
+    // 拆分时页面序号不是章节序号，等拆分完成后再逐章通知
+    let notify = |index: usize| {
+        if !segmenting {
+            send_ready(index);
+        }
+    };
+    let mut cancelled = false;
+    let planned = plan.len();
+    for (position, index) in plan.into_iter().enumerate() {
+        // 时限只在章节之间检查，正在下载的一章不会被打断
+        if let Some(budget) = budget.as_ref().filter(|b| b.exceeded()) {
+            let timed_out = TimedOut {
+                novel_title: catalog.novel_title.clone(),
+                url: req.url.clone(),
+                budget_secs: budget.limit.as_secs(),
+                elapsed_secs: budget.elapsed().as_secs(),
+                downloaded: summary.success,
+                remaining: planned - position,
+            };
+            emit(
+                "warning",
+                resources::format(
+                    "download.timed_out",
+                    &[
+                        ("title", &catalog.novel_title),
+                        ("minutes", &(timed_out.budget_secs / 60).to_string()),
+                        ("remaining", &timed_out.remaining.to_string()),
+                    ],
+                ),
+            );
+            crate::events::emit_and_buffer(events, TIMED_OUT_EVENT, timed_out);
+            summary.timed_out = true;
+            break;
+        }
+        if req.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+            emit("warning", resources::format("download.cancelled", &[("title", &catalog.novel_title)]));
+            cancelled = true;
+            break;
+        }
+        // 预取让位于用户发起的下载，等其全部结束后再继续
+        while req.prefetch && foreground_active() {
+            waiter.sleep(events, PREFETCH_YIELD_INTERVAL, &resources::text("download.wait_foreground")).await;
+        }
+        let entry = &catalog.chapters[index - 1];
+        let file_path = index_file.chapter_path(index);
+        if !req.force && index_file.get(index).is_some_and(|r| r.unavailable) {
+            summary.unavailable += 1;
+            emit("skipped", resources::format("download.skip_unavailable", &[("chapter", &entry.title)]));
+            continue;
+        }
+        if !req.force {
+            let before = index_file.get(index).cloned();
+            let mut check = index_file.check(index);
+            // 补记了哈希或清除了下载标记：在锁内按最新的索引重新检查一次并写回
+            if index_file.get(index) != before.as_ref() {
+                match index_file.sync(|index_file| Ok(index_file.check(index))) {
+                    Ok(rechecked) => check = rechecked,
+                    Err(e) => eprintln!("[Download] {}", e),
+                }
+            }
+            match check {
+                check if check.can_skip() => {
+                    summary.skipped += 1;
+                    emit("skipped", resources::format("download.skip_existing", &[("chapter", &entry.title)]));
+                    notify(index);
+                    continue;
+                }
+                ChapterCheck::Invalid(reason) => {
+                    emit("progress", resources::format("download.redownload", &[("chapter", &entry.title), ("reason", &reason)]));
+                }
+                _ => {}
+            }
+        }
+
+        // 作品相关 / 公告本来就短，只做基本校验
+        let min_chars = if entry.is_extra { library::MIN_CHAPTER_BODY_CHARS } else { min_chapter_chars };
+        let downloaded = source.download_chapter(&req.platform, &entry.url, req.debug_visible).await.and_then(|(page_title, content, source)| {
+            let content = clean_rules::apply(&content, &clean);
+            let content = match normalize {
+                Some(options) => text_normalize::normalize_body(&content, options),
+                None => content,
+            };
+            // 目录标题只有"第N章"而章节页有完整标题时（或反过来），取信息量更高的一个
+            let page_title = Some(crate::spiders::clean_text(&page_title)).filter(|t| !t.is_empty() && *t != entry.url);
+            let title = catalog_order::header_title(&entry.title, entry.full_title.as_deref(), page_title.as_deref());
+            let full = library::render_chapter_file(&title, &entry.url, &content);
+            library::validate_chapter_content(&full, min_chars).map(|()| (full, page_title, source)).map_err(SpiderError::Other)
+        });
+        match downloaded {
+            Ok((full, page_title, source)) => {
+                let bytes = storage::encode_text(&file_path, &full);
+                // 选择器漂移后下载照样"成功"，按正文像不像小说及早发现
+                let body = library::ChapterFile::parse(&full).map(|c| c.body).unwrap_or_default();
+                let score = content_check::classify(&body, &check_config);
+                // 写文件和记哈希在同一次加锁内完成，撤销等修改不会插在两者之间
+                let archived = index_file.sync(|index_file| {
+                    let written = file_path.parent().map_or(Ok(()), storage::create_dir_all).and_then(|()| match edits.as_mut() {
+                        Some(recorder) => recorder.write(&crate::paths::to_relative(&novel_dir, &file_path).unwrap_or_default(), &bytes),
+                        None => storage::write_atomic(&file_path, &bytes).map(|()| false),
+                    });
+                    let archived = written.map_err(|e| resources::format("download.write_failed", &[("path", &file_path.display().to_string()), ("error", &e.to_string())]))?;
+                    index_file.mark_downloaded(index, &bytes);
+                    index_file.set_source(index, source);
+                    index_file.set_page_title(index, page_title);
+                    index_file.set_suspect(index, score.suspect);
+                    Ok(archived)
+                })?;
+                if entry.is_vip {
+                    crate::access_probe::note_session(&req.platform);
+                }
+                let streak = crate::spiders::circuit::record_suspect(&req.platform, score.suspect);
+                if score.suspect {
+                    emit("warning", resources::format("download.suspect_content", &[("chapter", &entry.title), ("reasons", &score.reasons.join("；"))]));
+                }
+                if streak == content_check::DEGRADED_STREAK {
+                    crate::log_to_file_with_root(
+                        &format!("[ContentCheck] {} 连续 {} 章正文可疑，选择器可能已失效", req.platform, streak),
+                        Some(workspace_root),
+                    );
+                    crate::events::emit_and_buffer(
+                        events,
+                        content_check::DEGRADED_EVENT,
+                        content_check::Degraded {
+                            platform: req.platform.clone(),
+                            novel_title: catalog.novel_title.clone(),
+                            streak,
+                            reasons: score.reasons.clone(),
+                        },
+                    );
+                }
+                summary.success += 1;
+                notify(index);
+                if archived {
+                    emit("progress", resources::format("download.saved_with_history", &[("chapter", &entry.title)]));
+                } else {
+                    emit("progress", resources::format("download.saved", &[("chapter", &entry.title)]));
+                }
+            }
+            Err(e @ SpiderError::ChapterUnavailable { .. }) => {
+                summary.unavailable += 1;
+                let marked = index_file.sync(|index_file| {
+                    index_file.mark_unavailable(index);
+                    index_file.mark_failed(index, e.to_string());
+                    Ok(())
+                });
+                if let Err(e) = marked {
+                    eprintln!("[Download] {}", e);
+                }
+                emit_failure("warning", resources::format("download.unavailable", &[("chapter", &entry.title), ("error", &e.to_string())]), ErrorCode::from(&e));
+                summary.failures.push(ChapterFailure { index, error: e.to_string() });
+            }
+            Err(e) => {
+                summary.failed += 1;
+                let marked = index_file.sync(|index_file| {
+                    index_file.mark_failed(index, e.to_string());
+                    Ok(())
+                });
+                if let Err(e) = marked {
+                    eprintln!("[Download] {}", e);
+                }
+                emit_failure("error", resources::format("download.chapter_failed", &[("chapter", &entry.title), ("error", &e.to_string())]), ErrorCode::from(&e));
+                summary.failures.push(ChapterFailure { index, error: e.to_string() });
+            }
+        }
+
+        waiter.sleep(events, CHAPTER_INTERVAL, &resources::text("download.wait_interval")).await;
+    }
+    // 覆盖过的旧章节记为一次可撤销的修改
+    if let Some(Err(e)) = edits.as_mut().map(edit_journal::Recorder::finish) {
+        eprintln!("[Download] 记录修改历史失败: {}", e);
+    }
+
+    if segmenting {
+        match segmentation::rebuild(&novel_dir, &index_file) {
+            Ok(stats) => {
+                emit(
+                    "progress",
+                    resources::format(
+                        "download.segmented",
+                        &[("entries", &stats.catalog_entries.to_string()), ("chapters", &stats.chapters.to_string())],
+                    ),
+                );
+                for index in 1..=stats.chapters {
+                    send_ready(index);
+                }
+                summary.segmented = Some(stats);
+            }
+            Err(e) => emit("error", resources::format("download.segment_failed", &[("error", &e)])),
+        }
+    }
+    if let Err(e) = novel_info::refresh_download_stats(&novel_dir).await {
+        eprintln!("[Download] 更新字数统计失败: {}", e);
+    }
+    // 书库建有全文索引时，只重新索引本书有变化的章节
+    if let Err(e) = crate::chapter_search::refresh_novel(workspace_root, &novel_dir) {
+        eprintln!("[Download] 更新检索索引失败: {}", e);
+    }
+    let mut downloaded_at = serde_json::Map::new();
+    downloaded_at.insert(
+        crate::library_export::DOWNLOADED_AT_KEY.to_string(),
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string().into(),
+    );
+    if let Err(e) = novel_info::merge_info(&novel_dir, &downloaded_at).await {
+        eprintln!("[Download] 记录下载时间失败: {}", e);
+    }
+
+    let segmented = summary
+        .segmented
+        .map(|s| resources::format("download.segmented_suffix", &[("entries", &s.catalog_entries.to_string()), ("chapters", &s.chapters.to_string())]))
+        .unwrap_or_default();
+    emit(
+        "completed",
+        resources::format(
+            if summary.timed_out { "download.summary_timed_out" } else { "download.summary" },
+            &[
+                ("title", &catalog.novel_title),
+                ("success", &summary.success.to_string()),
+                ("failed", &summary.failed.to_string()),
+                ("unavailable", &summary.unavailable.to_string()),
+                ("skipped", &summary.skipped.to_string()),
+                ("segmented", &segmented),
+            ],
+        ),
+    );
+
+    let relative_dir = crate::paths::to_relative(workspace_root, &novel_dir).unwrap_or_else(|| novel_dir.display().to_string());
+    crate::download_history::append(
+        workspace_root,
+        &crate::download_history::DownloadRecord::new(&catalog.novel_title, relative_dir, &req.url, &req.platform, &summary, cancelled),
+    );
+
+    if req.notify && !cancelled && !summary.timed_out && !settings.post_download.is_empty() {
+        let notice = hooks::CompletionNotice {
+            novel_title: catalog.novel_title.clone(),
+            novel_dir: crate::paths::to_relative(workspace_root, &novel_dir)
+                .unwrap_or_else(|| novel_dir.display().to_string()),
+            url: req.url.clone(),
+            platform: req.platform.clone(),
+            completed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            summary: summary.clone(),
+        };
+        for e in hooks::run(&settings.post_download, workspace_root, &novel_dir, &notice).await {
+            emit("warning", resources::format("download.notify_failed", &[("error", &e.to_string())]));
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_url_normalizes_mobile_and_query() {
+        assert_eq!(
+            canonical_url("https://m.qidian.com/book/1035420986/?source=pc#top"),
+            "https://www.qidian.com/book/1035420986"
+        );
+        assert_eq!(
+            canonical_url(" http://www.qidian.com/book/1035420986 "),
+            "https://www.qidian.com/book/1035420986"
+        );
+    }
+
+    fn catalog_with_extras(total: usize, extras: &[usize]) -> Vec<CatalogEntry> {
+        (1..=total)
+            .map(|i| CatalogEntry { index: i, title: format!("第{}章", i), is_extra: extras.contains(&i), ..Default::default() })
+            .collect()
+    }
+
+    fn range(start: Option<usize>, count: Option<usize>, order: DownloadOrder) -> ChapterSelection<'static> {
+        ChapterSelection { start_chapter: start, chapter_count: count, order, ..Default::default() }
+    }
+
+    #[test]
+    fn plan_uses_range_without_selection() {
+        let catalog = catalog_with_extras(10, &[]);
+        assert_eq!(select_chapters(&catalog, &range(None, None, DownloadOrder::FromStart)).indices, vec![1, 2, 3]);
+        assert_eq!(select_chapters(&catalog, &range(Some(9), Some(5), DownloadOrder::FromStart)).indices, vec![9, 10]);
+        assert!(select_chapters(&catalog, &range(Some(12), Some(5), DownloadOrder::FromStart)).indices.is_empty());
+    }
+
+    #[test]
+    fn plan_from_end_keeps_true_catalog_indices() {
+        let catalog = catalog_with_extras(10, &[]);
+        assert_eq!(select_chapters(&catalog, &range(None, Some(4), DownloadOrder::FromEnd)).indices, vec![7, 8, 9, 10]);
+        // start_chapter 从最新一章往前数
+        assert_eq!(select_chapters(&catalog, &range(Some(3), Some(2), DownloadOrder::FromEnd)).indices, vec![7, 8]);
+        assert_eq!(select_chapters(&catalog, &range(None, Some(30), DownloadOrder::FromEnd)).indices, (1..=10).collect::<Vec<_>>());
+        assert!(select_chapters(&catalog, &range(Some(11), Some(3), DownloadOrder::FromEnd)).indices.is_empty());
+    }
+
+    #[test]
+    fn plan_selected_skips_out_of_range() {
+        let catalog = catalog_with_extras(10, &[2]);
+        let selected = [5, 0, 2, 12, 2];
+        let selection = ChapterSelection { selected: Some(&selected), skip_extras: true, ..range(Some(3), Some(1), DownloadOrder::FromEnd) };
+        assert_eq!(
+            select_chapters(&catalog, &selection),
+            ChapterPlan { indices: vec![2, 5], out_of_range: vec![0, 12], skipped_extras: vec![] }
+        );
+    }
+
+    #[test]
+    fn story_plan_counts_only_non_extra_chapters() {
+        // 目录 1、2 为作品相关，5 为请假条
+        let catalog = catalog_with_extras(8, &[1, 2, 5]);
+        let story = |start, count, order| ChapterSelection { skip_extras: true, ..range(start, count, order) };
+        let plan = select_chapters(&catalog, &story(None, Some(3), DownloadOrder::FromStart));
+        assert_eq!(plan.indices, vec![3, 4, 6]);
+        assert_eq!(plan.skipped_extras, vec![1, 2, 5]);
+        assert_eq!(select_chapters(&catalog, &story(Some(4), Some(10), DownloadOrder::FromStart)).indices, vec![7, 8]);
+        assert_eq!(select_chapters(&catalog, &story(None, Some(3), DownloadOrder::FromEnd)).indices, vec![6, 7, 8]);
+        assert!(select_chapters(&[], &story(None, None, DownloadOrder::FromStart)).indices.is_empty());
+    }
+
+    #[test]
+    fn prefetch_starts_after_furthest_downloaded_chapter() {
+        let mut index = ChapterIndex::load(Path::new("/nonexistent/prefetch"));
+        index.update_catalog((1..=6).map(|i| ChapterRecord { index: i, url: format!("u{}", i), ..Default::default() }));
+        index.mark_downloaded(1, b"a");
+        index.mark_downloaded(3, b"c");
+        assert_eq!(prefetch_start(&index, 3), (4, true));
+        assert_eq!(prefetch_start(&index, 4), (4, false), "目录剩余章节不足时需重新获取");
+
+        let catalog = catalog_from_index("书", &index);
+        assert_eq!(catalog.chapters.iter().map(|c| c.index).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(prefetch_start(&ChapterIndex::load(Path::new("/nonexistent/empty")), 1), (1, false));
+    }
+
+    #[test]
+    fn cache_expires_after_ttl() {
+        let catalog = Catalog { novel_title: "测试".into(), chapters: vec![], anomalies: vec![], volume_resets: vec![], metadata: None };
+        let fresh = canonical_url("https://www.qidian.com/book/ttl-fresh/");
+        store_catalog(fresh.clone(), "qidian", catalog.clone(), Instant::now());
+        assert!(cached_catalog(&fresh, "qidian").is_some());
+        assert!(cached_catalog(&fresh, "fanqie").is_none(), "平台不同不复用");
+        assert!(catalog_cache_stats().hits >= 1 && catalog_cache_stats().misses >= 1);
+        assert!(invalidate_catalog("https://m.qidian.com/book/ttl-fresh?from=share"));
+        assert!(cached_catalog(&fresh, "qidian").is_none());
+
+        if let Some(stale) = Instant::now().checked_sub(catalog_ttl() + Duration::from_secs(1)) {
+            store_catalog("test://stale".into(), "qidian", catalog, stale);
+            assert!(cached_catalog("test://stale", "qidian").is_none());
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! 边下载边分析：`process_novel_download` 每写好一章就经 mpsc 发给分析队列，两边并行。
+//!
+//! 分析结果走 [`analysis_batch`] 的批次清单（每章一组），AI 失败只记为 failed，不阻塞下载；
+//! 之后可用 `analyze_novel` 的 `resume_batch_id` 重试。取消时下载在下一章前停止，
+//! 分析在当前章结束后停止，已下载但未分析的章节以 pending 状态留在清单中待续跑。
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::analysis_batch::{self, BatchManifest, BatchParams};
+use crate::download::{ChapterReady, DownloadRequest};
+use crate::spiders::LiveSource;
+use crate::{ai, ai_limits};
+
+pub const PROGRESS_EVENT: &str = "download-analysis-progress";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CombinedProgress {
+    pub task_id: String,
+    pub batch_id: Option<String>,
+    pub downloaded: usize,
+    pub analyzed: usize,
+    pub analysis_failed: usize,
+    /// "progress" | "waiting" | "error" | "cancelled" | "completed"
+    pub status: String,
+    pub message: String,
+}
+
+struct Counters {
+    downloaded: AtomicUsize,
+    analyzed: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+struct Reporter<'a> {
+    app: &'a tauri::AppHandle,
+    task_id: &'a str,
+    counters: &'a Counters,
+}
+
+impl Reporter<'_> {
+    fn emit(&self, batch_id: Option<&str>, status: &str, message: String) {
+        crate::events::emit_and_buffer(
+            self.app,
+            PROGRESS_EVENT,
+            CombinedProgress {
+                task_id: self.task_id.to_string(),
+                batch_id: batch_id.map(str::to_string),
+                downloaded: self.counters.downloaded.load(Ordering::Relaxed),
+                analyzed: self.counters.analyzed.load(Ordering::Relaxed),
+                analysis_failed: self.counters.failed.load(Ordering::Relaxed),
+                status: status.to_string(),
+                message,
+            },
+        );
+    }
+}
+
+/// 分析队列：按到达顺序逐章分析。批次清单在第一章到达时创建（此时才知道小说目录）。
+async fn analyze_queue(
+    reporter: &Reporter<'_>,
+    workspace_root: &Path,
+    mut rx: mpsc::UnboundedReceiver<ChapterReady>,
+    config: &ai::AiConfig,
+    prompt: &str,
+    cancel: &AtomicBool,
+) -> Result<Option<BatchManifest>, String> {
+    let mut batch: Option<(BatchManifest, PathBuf, analysis_batch::RunningGuard)> = None;
+    while let Some(ready) = rx.recv().await {
+        if batch.is_none() {
+            let novel_title = ready.novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let params = BatchParams {
+                prompt_hash: analysis_batch::prompt_hash(prompt),
+                model: config.model.clone(),
+                group_size: 1,
+                chapters: Vec::new(),
+                context: None,
+                normalize: None,
+                arc: None,
+                version_policy: None,
+                output_language: None,
+            };
+            let mut manifest = BatchManifest::new(analysis_batch::new_batch_id(), &novel_title, params);
+            analysis_batch::save(workspace_root, &mut manifest)?;
+            let guard = analysis_batch::claim(&novel_title, &manifest.id)?;
+            batch = Some((manifest, ready.novel_dir.clone(), guard));
+        }
+        let Some((manifest, novel_dir, _)) = batch.as_mut() else { break };
+
+        let file = crate::sharding::relative_chapter_path(&ready.novel_dir, ready.index);
+        manifest.push_chapter(file.clone());
+        if cancel.load(Ordering::Relaxed) {
+            // 已取消：只记入清单，留待续跑
+            continue;
+        }
+        ai_limits::pace(config, |wait, reason| {
+            let message = format!("AI 接口{}，{} 恢复派发", reason, ai_limits::resume_time(wait));
+            reporter.emit(Some(&manifest.id), "waiting", message);
+        })
+        .await;
+        let i = manifest.entries.len() - 1;
+        match analysis_batch::analyze_entry(workspace_root, novel_dir, manifest, i, config, prompt).await {
+            Ok(()) => {
+                reporter.counters.analyzed.fetch_add(1, Ordering::Relaxed);
+                reporter.emit(Some(&manifest.id), "progress", format!("已分析: {}", file));
+            }
+            Err(e) => {
+                reporter.counters.failed.fetch_add(1, Ordering::Relaxed);
+                reporter.emit(Some(&manifest.id), "error", format!("分析失败 {}: {}（可稍后续跑批次）", file, e));
+            }
+        }
+    }
+
+    let Some((mut manifest, _, _guard)) = batch else { return Ok(None) };
+    if manifest.next_pending().is_none() {
+        analysis_batch::finish(workspace_root, &mut manifest)?;
+    } else {
+        analysis_batch::save(workspace_root, &mut manifest)?;
+    }
+    Ok(Some(manifest))
+}
+
+/// 运行下载 + 分析，直到两边都结束。cancel 为 [`crate::tasks::register`] 返回的取消标记。
+pub async fn run(
+    app: &tauri::AppHandle,
+    workspace_root: &Path,
+    task_id: &str,
+    cancel: Arc<AtomicBool>,
+    mut req: DownloadRequest,
+    config: ai::AiConfig,
+    prompt: String,
+) {
+    let counters = Counters { downloaded: AtomicUsize::new(0), analyzed: AtomicUsize::new(0), failed: AtomicUsize::new(0) };
+    let reporter = Reporter { app, task_id, counters: &counters };
+
+    let (download_tx, mut download_rx) = mpsc::unbounded_channel::<ChapterReady>();
+    let (analysis_tx, analysis_rx) = mpsc::unbounded_channel::<ChapterReady>();
+    req.chapter_tx = Some(download_tx);
+    req.cancel = Some(cancel.clone());
+
+    let download = async {
+        let result = crate::download::process_novel_download(app, &LiveSource::new(app), workspace_root, req).await;
+        if let Err(e) = &result {
+            reporter.emit(None, "error", format!("下载失败: {}", e));
+        }
+        result
+    };
+    // 下载端计数后转给分析队列；下载结束时发送端被丢弃，分析队列处理完剩余章节后退出
+    let forward = async {
+        while let Some(ready) = download_rx.recv().await {
+            counters.downloaded.fetch_add(1, Ordering::Relaxed);
+            reporter.emit(None, "progress", format!("已下载第 {} 章", ready.index));
+            let _ = analysis_tx.send(ready);
+        }
+        drop(analysis_tx);
+    };
+    let analysis = analyze_queue(&reporter, workspace_root, analysis_rx, &config, &prompt, &cancel);
+
+    let (_, (), analysis) = tokio::join!(download, forward, analysis);
+    crate::tasks::unregister(task_id);
+
+    let cancelled = cancel.load(Ordering::Relaxed);
+    let (batch_id, message) = match analysis {
+        Ok(Some(manifest)) => {
+            let pending = manifest.entries.iter().filter(|e| e.status != analysis_batch::EntryStatus::Completed).count();
+            let note = if pending > 0 { format!("，{} 章待续跑（批次 {}）", pending, manifest.id) } else { String::new() };
+            (Some(manifest.id), note)
+        }
+        Ok(None) => (None, String::new()),
+        Err(e) => (None, format!("，分析清单写入失败: {}", e)),
+    };
+    let status = if cancelled { "cancelled" } else { "completed" };
+    reporter.emit(
+        batch_id.as_deref(),
+        status,
+        format!(
+            "{}: 下载 {} 章，分析 {} 章，失败 {} 章{}",
+            if cancelled { "已取消" } else { "边下载边分析完成" },
+            counters.downloaded.load(Ordering::Relaxed),
+            counters.analyzed.load(Ordering::Relaxed),
+            counters.failed.load(Ordering::Relaxed),
+            message
+        ),
+    );
+}
@@ -0,0 +1,75 @@
+//! 下载记录：`<workspace>/download_history.jsonl`，每次下载任务结束时追加一行。
+//!
+//! info.json 的 `downloaded_at` 只保留最近一次，回看"上周下了什么、哪些连载更新了"需要逐次的记录。
+//! 已有章节被跳过、又下载到新章节的任务视为连载更新。写入失败只记日志，不影响下载结果。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::download::DownloadSummary;
+
+pub const HISTORY_FILE: &str = "download_history.jsonl";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DownloadRecord {
+    /// 完成时间 `%Y-%m-%d %H:%M:%S`
+    pub at: String,
+    pub novel_title: String,
+    /// 工作区相对路径
+    pub novel_dir: String,
+    pub url: String,
+    pub platform: String,
+    /// 本次新下载的章节数
+    pub downloaded: usize,
+    /// 已有而跳过的章节数
+    pub skipped: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+    /// 超过单本时限而提前停止
+    pub timed_out: bool,
+}
+
+impl DownloadRecord {
+    pub fn new(novel_title: &str, novel_dir: String, url: &str, platform: &str, summary: &DownloadSummary, cancelled: bool) -> Self {
+        Self {
+            at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            novel_title: novel_title.to_string(),
+            novel_dir,
+            url: url.to_string(),
+            platform: platform.to_string(),
+            downloaded: summary.success,
+            skipped: summary.skipped,
+            failed: summary.failed,
+            cancelled,
+            timed_out: summary.timed_out,
+        }
+    }
+
+    /// 连载更新：已有章节、又下载到了新章节
+    pub fn is_update(&self) -> bool {
+        self.skipped > 0 && self.downloaded > 0
+    }
+
+    /// 完成日期（at 的日期部分）
+    pub fn date(&self) -> Option<chrono::NaiveDate> {
+        chrono::NaiveDate::parse_from_str(self.at.get(..10)?, "%Y-%m-%d").ok()
+    }
+}
+
+pub fn append(workspace_root: &Path, record: &DownloadRecord) {
+    let line = match serde_json::to_string(record) {
+        Ok(json) => json + "\n",
+        Err(e) => return eprintln!("[DownloadHistory] 序列化失败: {}", e),
+    };
+    if let Err(e) = crate::storage::append(&workspace_root.join(HISTORY_FILE), line.as_bytes()) {
+        crate::log_to_file_with_root(&format!("[DownloadHistory] 写入 {} 失败: {}", HISTORY_FILE, e), Some(workspace_root));
+    }
+}
+
+/// 全部记录，按写入顺序。文件不存在时为 None；无法解析的行跳过
+pub fn load(workspace_root: &Path) -> Option<Vec<DownloadRecord>> {
+    let content = fs::read_to_string(workspace_root.join(HISTORY_FILE)).ok()?;
+    Some(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
@@ -0,0 +1,420 @@
+//! 章节修改日志：改写已有章节文件的操作（标题重写、正文规范化、换行修复、重新下载）都经这里写入，
+//! 被覆盖的旧内容备份到 `<小说目录>/.history/<修改 id>/` 下，每次操作在 `.history/edits.json` 记一条。
+//!
+//! [`undo`] 把一次修改涉及的文件一起恢复：先把全部备份读进内存再逐个写回，中途失败时把已写回的文件
+//! 改回撤销前的内容。之后更新的、仍未撤销的修改动过同一文件时拒绝撤销，需先撤销更新的那次。
+//! 撤销全程持有 [`storage::novel_lock`]；这本书还有未结束的 [`Recorder`] 时拒绝撤销，免得恢复的文件
+//! 被进行中的修改覆盖，或它的日志条目在撤销之后才写入。
+//!
+//! 备份总大小超过上限（设置中的 `edit_history_max_mb`）时从最旧的修改删起，被删的条目标记 pruned，
+//! 仍留在日志中但不能再撤销；刚完成的修改总会保留。历史放在小说目录内，删除整本书时一并删除；
+//! 以点开头的目录不会被书库扫描或章节列表当成章节。
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use crate::chapter_index::ChapterIndex;
+use crate::{library, storage};
+
+pub const HISTORY_DIR: &str = ".history";
+pub const JOURNAL_FILE: &str = "edits.json";
+/// 每本书备份总大小的缺省上限
+pub const DEFAULT_MAX_HISTORY_MB: u64 = 200;
+
+/// 日志的读-改-写串行进行，下载和手动修复可能同时改同一本书
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// 每本书（按 [`storage::canonical_key`]）未结束的 [`Recorder`] 数
+static OPEN_RECORDERS: LazyLock<Mutex<HashMap<PathBuf, usize>>> = LazyLock::new(Default::default);
+
+fn open_recorders() -> std::sync::MutexGuard<'static, HashMap<PathBuf, usize>> {
+    OPEN_RECORDERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EditKind {
+    /// 按目录重写章节标题，见 [`crate::catalog_order::retitle_stored`]
+    Retitle,
+    /// 繁简转换 / 标点 / 空行规范化，见 [`crate::text_normalize::rewrite_chapters`]
+    Normalize,
+    /// 换行和 BOM 修复，见 [`crate::text_normalize::normalize_line_endings`]
+    LineEndings,
+    /// 重新下载覆盖了内容不同的章节
+    Redownload,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EditedFile {
+    /// 章节文件，相对小说目录
+    pub file: String,
+    /// 备份文件，相对小说目录
+    pub backup: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EditEntry {
+    pub id: String,
+    pub kind: EditKind,
+    /// `YYYY-MM-DD HH:MM:SS`
+    pub at: String,
+    pub chapters: Vec<usize>,
+    pub files: Vec<EditedFile>,
+    /// 备份占用的字节数
+    pub bytes: u64,
+    #[serde(default)]
+    pub undone: bool,
+    /// 备份已按保留上限删除，不能再撤销
+    #[serde(default)]
+    pub pruned: bool,
+}
+
+impl EditEntry {
+    fn restorable(&self) -> bool {
+        !self.undone && !self.pruned
+    }
+}
+
+pub fn history_dir(novel_dir: &Path) -> PathBuf {
+    novel_dir.join(HISTORY_DIR)
+}
+
+fn journal_path(novel_dir: &Path) -> PathBuf {
+    history_dir(novel_dir).join(JOURNAL_FILE)
+}
+
+/// 按时间顺序（从旧到新）的全部条目；日志不存在或损坏时为空
+fn load(novel_dir: &Path) -> Vec<EditEntry> {
+    let Ok(content) = fs::read_to_string(journal_path(novel_dir)) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("[EditJournal] 解析 {} 失败: {}", journal_path(novel_dir).display(), e);
+        Vec::new()
+    })
+}
+
+fn save(novel_dir: &Path, entries: &[EditEntry]) -> Result<(), String> {
+    let content = serde_json::to_vec_pretty(entries).map_err(|e| format!("序列化修改日志失败: {}", e))?;
+    storage::create_dir_all(&history_dir(novel_dir)).map_err(|e| format!("创建 {} 失败: {}", HISTORY_DIR, e))?;
+    storage::write_atomic(&journal_path(novel_dir), &content).map_err(|e| format!("写入修改日志失败: {}", e))
+}
+
+/// 从最旧的条目起删除备份，直到总大小不超过 max_bytes；最新一条不删
+fn prune(novel_dir: &Path, entries: &mut [EditEntry], max_bytes: u64) {
+    let mut total: u64 = entries.iter().filter(|e| !e.pruned).map(|e| e.bytes).sum();
+    let keep = entries.len().saturating_sub(1);
+    for entry in entries[..keep].iter_mut().filter(|e| !e.pruned) {
+        if total <= max_bytes {
+            break;
+        }
+        let _ = storage::remove(&history_dir(novel_dir).join(&entry.id));
+        total -= entry.bytes;
+        entry.pruned = true;
+    }
+}
+
+/// 一次进行中的修改：经 [`Recorder::write`] 写入的文件先备份旧内容，[`Recorder::finish`] 时记入日志。
+/// 调用方中途出错返回时，drop 会把已写入的部分照常记入日志
+pub struct Recorder {
+    novel_dir: PathBuf,
+    entry: EditEntry,
+    max_bytes: u64,
+    finished: bool,
+}
+
+impl Recorder {
+    /// 新建本次修改的备份目录；同一毫秒内开始的修改在 id 后加序号区分。
+    /// 在小说锁内登记，正在撤销的这本书要等撤销完成才能开始新的修改；调用方不能已持有这本书的锁
+    pub fn begin(novel_dir: &Path, kind: EditKind, max_bytes: u64) -> Result<Self, String> {
+        let now = Local::now();
+        let stamp = now.format("%Y%m%d%H%M%S%3f").to_string();
+        let history = history_dir(novel_dir);
+        storage::create_dir_all(&history).map_err(|e| format!("创建 {} 失败: {}", HISTORY_DIR, e))?;
+        let mut id = stamp.clone();
+        for n in 2.. {
+            match fs::create_dir(crate::paths::long_path(&history.join(&id))) {
+                Ok(()) => break,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => id = format!("{}_{}", stamp, n),
+                Err(e) => return Err(format!("创建备份目录失败: {}", e)),
+            }
+        }
+        let entry = EditEntry {
+            id,
+            kind,
+            at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            chapters: Vec::new(),
+            files: Vec::new(),
+            bytes: 0,
+            undone: false,
+            pruned: false,
+        };
+        {
+            let lock = storage::novel_lock(novel_dir);
+            let _novel = lock.lock().unwrap_or_else(|e| e.into_inner());
+            *open_recorders().entry(storage::canonical_key(novel_dir)).or_default() += 1;
+        }
+        Ok(Recorder { novel_dir: novel_dir.to_path_buf(), entry, max_bytes, finished: false })
+    }
+
+    /// 写入相对小说目录的章节文件。已有内容不同时先复制一份备份，返回是否产生了备份。
+    /// 旧文件始终留在原处，直到新内容经 [`storage::write_atomic`] 整体替换它；任一步失败，章节仍是旧内容
+    pub fn write(&mut self, file: &str, bytes: &[u8]) -> std::io::Result<bool> {
+        let path = self.novel_dir.join(file);
+        let backed_up = match fs::read(crate::paths::long_path(&path)) {
+            Ok(old) if old != bytes && !self.entry.files.iter().any(|f| f.file == file) => {
+                let backup = format!("{}/{}/{}", HISTORY_DIR, self.entry.id, file);
+                let backup_path = self.novel_dir.join(&backup);
+                if let Some(parent) = backup_path.parent() {
+                    storage::create_dir_all(parent)?;
+                }
+                storage::write_atomic(&backup_path, &old)?;
+                self.entry.bytes += old.len() as u64;
+                self.entry.files.push(EditedFile { file: file.to_string(), backup });
+                if let Some(n) = library::chapter_index_of(file) {
+                    self.entry.chapters.push(n);
+                }
+                true
+            }
+            _ => false,
+        };
+        storage::write_atomic(&path, bytes)?;
+        Ok(backed_up)
+    }
+
+    /// 记入日志并按保留上限清理旧备份；没有备份任何文件时不记录，返回 None
+    pub fn finish(&mut self) -> Result<Option<EditEntry>, String> {
+        if std::mem::replace(&mut self.finished, true) {
+            return Ok(None);
+        }
+        let recorded = self.record();
+        let key = storage::canonical_key(&self.novel_dir);
+        let mut open = open_recorders();
+        if let Some(count) = open.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&key);
+            }
+        }
+        recorded
+    }
+
+    fn record(&mut self) -> Result<Option<EditEntry>, String> {
+        if self.entry.files.is_empty() {
+            let _ = fs::remove_dir(history_dir(&self.novel_dir).join(&self.entry.id));
+            return Ok(None);
+        }
+        self.entry.chapters.sort_unstable();
+        self.entry.chapters.dedup();
+        let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = load(&self.novel_dir);
+        entries.push(self.entry.clone());
+        prune(&self.novel_dir, &mut entries, self.max_bytes);
+        save(&self.novel_dir, &entries)?;
+        Ok(Some(self.entry.clone()))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("[EditJournal] {}", e);
+        }
+    }
+}
+
+/// 全部修改，从新到旧
+pub fn list(novel_dir: &Path) -> Vec<EditEntry> {
+    let mut entries = load(novel_dir);
+    entries.reverse();
+    entries
+}
+
+/// 某章文件仍可用的备份（修改时间、备份文件相对小说目录的路径），从新到旧
+pub fn chapter_backups(novel_dir: &Path, chapter_file: &str) -> Vec<(String, String)> {
+    list(novel_dir)
+        .into_iter()
+        .filter(|e| !e.pruned)
+        .filter_map(|e| e.files.into_iter().find(|f| f.file == chapter_file).map(|f| (e.at, f.backup)))
+        .filter(|(_, backup)| novel_dir.join(backup).is_file())
+        .collect()
+}
+
+/// 撤销一次修改：恢复它备份的全部文件，更新 chapters.json 中的哈希并标记为已撤销
+pub fn undo(novel_dir: &Path, edit_id: &str) -> Result<EditEntry, String> {
+    let lock = storage::novel_lock(novel_dir);
+    let _novel = lock.lock().unwrap_or_else(|e| e.into_inner());
+    if open_recorders().contains_key(&storage::canonical_key(novel_dir)) {
+        return Err("这本书还有正在进行的修改（下载、标题重写或规范化），请等它完成后再撤销".to_string());
+    }
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load(novel_dir);
+    let position = entries.iter().position(|e| e.id == edit_id).ok_or_else(|| format!("没有修改记录 {}", edit_id))?;
+    let entry = &entries[position];
+    if entry.undone {
+        return Err(format!("修改 {} 已经撤销过", edit_id));
+    }
+    if entry.pruned {
+        return Err(format!("修改 {} 的备份已按保留上限删除，无法撤销", edit_id));
+    }
+    let touched = |later: &EditEntry| later.files.iter().any(|f| entry.files.iter().any(|g| g.file == f.file));
+    if let Some(later) = entries[position + 1..].iter().find(|later| later.restorable() && touched(later)) {
+        return Err(format!("{} 之后的修改 {} 改过相同的章节，请先撤销它", edit_id, later.id));
+    }
+
+    let mut restores = Vec::with_capacity(entry.files.len());
+    for file in &entry.files {
+        let backup = fs::read(novel_dir.join(&file.backup)).map_err(|e| format!("读取备份 {} 失败: {}", file.backup, e))?;
+        let current = fs::read(novel_dir.join(&file.file)).ok();
+        restores.push((file.file.as_str(), backup, current));
+    }
+    for (done, (file, backup, _)) in restores.iter().enumerate() {
+        if let Err(e) = storage::write_atomic(&novel_dir.join(file), backup) {
+            for (file, _, current) in &restores[..done] {
+                let path = novel_dir.join(file);
+                let _ = match current {
+                    Some(bytes) => storage::write_atomic(&path, bytes),
+                    None => storage::remove(&path),
+                };
+            }
+            return Err(format!("恢复 {} 失败，已改回撤销前的内容: {}", file, e));
+        }
+    }
+
+    // 已持有小说锁，不能再走 ChapterIndex::update
+    let mut index = ChapterIndex::load(novel_dir);
+    for (file, backup, _) in &restores {
+        if let Some(n) = library::chapter_index_of(file) {
+            index.mark_downloaded(n, backup);
+        }
+    }
+    index.save()?;
+
+    let _ = storage::remove(&history_dir(novel_dir).join(edit_id));
+    let entry = &mut entries[position];
+    entry.undone = true;
+    entry.bytes = 0;
+    let undone = entry.clone();
+    save(novel_dir, &entries)?;
+    Ok(undone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_novel_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_edit_journal_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn edit(dir: &Path, kind: EditKind, files: &[(&str, &str)], max_bytes: u64) -> Option<EditEntry> {
+        let mut recorder = Recorder::begin(dir, kind, max_bytes).unwrap();
+        for (file, text) in files {
+            recorder.write(file, text.as_bytes()).unwrap();
+        }
+        recorder.finish().unwrap()
+    }
+
+    #[test]
+    fn undo_restores_every_file_and_respects_newer_edits() {
+        let dir = temp_novel_dir("undo");
+        fs::write(dir.join("01.txt"), "一").unwrap();
+        fs::write(dir.join("02.txt"), "二").unwrap();
+        let first = edit(&dir, EditKind::Normalize, &[("01.txt", "一改"), ("02.txt", "二")], u64::MAX).unwrap();
+        assert_eq!((first.chapters.clone(), first.files.len()), (vec![1], 1), "内容没变的文件不备份");
+        let second = edit(&dir, EditKind::Retitle, &[("01.txt", "一再改")], u64::MAX).unwrap();
+        assert!(edit(&dir, EditKind::Retitle, &[("01.txt", "一再改")], u64::MAX).is_none());
+        assert_ne!(first.id, second.id);
+        assert_eq!(list(&dir).iter().map(|e| e.kind).collect::<Vec<_>>(), vec![EditKind::Retitle, EditKind::Normalize]);
+        assert_eq!(chapter_backups(&dir, "01.txt").len(), 2);
+
+        assert!(undo(&dir, &first.id).unwrap_err().contains(&second.id));
+        undo(&dir, &second.id).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), "一改");
+        assert!(undo(&dir, &second.id).is_err());
+        let undone = undo(&dir, &first.id).unwrap();
+        assert!(undone.undone);
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), "一");
+        assert_eq!(ChapterIndex::load(&dir).get(1).unwrap().content_hash, Some(storage::content_hash("一".as_bytes())));
+        assert!(!history_dir(&dir).join(&first.id).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn oldest_backups_are_pruned_beyond_the_size_limit() {
+        let dir = temp_novel_dir("prune");
+        fs::write(dir.join("01.txt"), "0000").unwrap();
+        let ids: Vec<String> = (1..=3)
+            .map(|i| edit(&dir, EditKind::LineEndings, &[("01.txt", &format!("{:04}", i))], 8).unwrap().id)
+            .collect();
+        let pruned: Vec<bool> = list(&dir).iter().rev().map(|e| e.pruned).collect();
+        assert_eq!(pruned, vec![true, false, false]);
+        assert!(!history_dir(&dir).join(&ids[0]).exists());
+        assert!(undo(&dir, &ids[0]).unwrap_err().contains("无法撤销"));
+        // 上限为 0 时只保留刚完成的一次
+        edit(&dir, EditKind::LineEndings, &[("01.txt", "0004")], 0).unwrap();
+        assert_eq!(list(&dir).iter().filter(|e| !e.pruned).count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn undo_waits_for_open_recorders_on_the_same_novel() {
+        let dir = temp_novel_dir("open");
+        fs::write(dir.join("01.txt"), "一").unwrap();
+        let first = edit(&dir, EditKind::Normalize, &[("01.txt", "一改")], u64::MAX).unwrap();
+        let mut running = Recorder::begin(&dir, EditKind::Redownload, u64::MAX).unwrap();
+        running.write("02.txt", "二".as_bytes()).unwrap();
+        assert!(undo(&dir.join("."), &first.id).unwrap_err().contains("正在进行"));
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), "一改");
+        drop(running);
+        undo(&dir, &first.id).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), "一");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recorders_register_only_after_a_running_undo_finishes() {
+        let dir = temp_novel_dir("register");
+        let key = storage::canonical_key(&dir);
+        // 撤销持有小说锁期间开始的修改要等锁释放后才登记、才能写入
+        let lock = storage::novel_lock(&dir);
+        let held = lock.lock().unwrap();
+        let starting = {
+            let dir = dir.clone();
+            std::thread::spawn(move || Recorder::begin(&dir, EditKind::Normalize, u64::MAX).unwrap())
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!open_recorders().contains_key(&key));
+        drop(held);
+        let recorder = starting.join().unwrap();
+        assert!(open_recorders().contains_key(&key));
+        drop(recorder);
+        assert!(!open_recorders().contains_key(&key));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_write_leaves_the_old_chapter_in_place() {
+        let dir = temp_novel_dir("failed_write");
+        fs::write(dir.join("01.txt"), "旧内容").unwrap();
+        let mut recorder = Recorder::begin(&dir, EditKind::Redownload, u64::MAX).unwrap();
+        // 备份目录被占成普通文件，备份写不进去
+        let backup_dir = dir.join(HISTORY_DIR).join(&recorder.entry.id);
+        fs::remove_dir(&backup_dir).unwrap();
+        fs::write(&backup_dir, "").unwrap();
+
+        assert!(recorder.write("01.txt", "新内容".as_bytes()).is_err());
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), "旧内容");
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        assert!(names.iter().all(|n| n == "01.txt" || n == HISTORY_DIR), "没有改名留下的版本文件: {:?}", names);
+        drop(recorder);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
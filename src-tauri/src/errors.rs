@@ -0,0 +1,249 @@
+//! 面向前端的错误类型。
+//!
+//! 命令边界返回的 [`AppError`] 序列化为 `{ code, message_zh, message_en, detail }`：`code` 是稳定的机器码，
+//! 前端据此分支和翻译；`message_*` 是该类错误的通用说明；`detail` 是本次出错的具体信息（路径、URL、服务端返回等）。
+//! 所有错误码都在 [`ErrorCode`] 中登记，已发布的码不改名、不复用；通用说明在资源包的 `error.<码>.zh/en` 键中。
+//! 进度事件的 error 状态携带同一个码。
+
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+use crate::ai::AiError;
+use crate::resources;
+use crate::spiders::SpiderError;
+
+macro_rules! error_codes {
+    ($($variant:ident => $code:literal;)+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($variant,)+
+        }
+
+        impl ErrorCode {
+            /// 全部错误码，顺序即登记顺序
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant,)+];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $code,)+
+                }
+            }
+
+            pub fn message_zh(self) -> String {
+                resources::error_message(self.as_str(), false)
+            }
+
+            pub fn message_en(self) -> String {
+                resources::error_message(self.as_str(), true)
+            }
+        }
+    };
+}
+
+error_codes! {
+    SpiderWafBlocked => "SPIDER_WAF_BLOCKED";
+    SpiderTimeout => "SPIDER_TIMEOUT";
+    SpiderCircuitOpen => "SPIDER_CIRCUIT_OPEN";
+    SpiderBridgeUnavailable => "SPIDER_BRIDGE_UNAVAILABLE";
+    SpiderPageTooLarge => "SPIDER_PAGE_TOO_LARGE";
+    SpiderPayloadIncomplete => "SPIDER_PAYLOAD_INCOMPLETE";
+    SpiderChapterUnavailable => "SPIDER_CHAPTER_UNAVAILABLE";
+    SpiderNovelRemoved => "SPIDER_NOVEL_REMOVED";
+    SpiderFailed => "SPIDER_FAILED";
+    AiNotConfigured => "AI_NOT_CONFIGURED";
+    AiBadRequest => "AI_BAD_REQUEST";
+    AiPayloadTooLarge => "AI_PAYLOAD_TOO_LARGE";
+    AiUnauthorized => "AI_UNAUTHORIZED";
+    AiRateLimited => "AI_RATE_LIMITED";
+    AiHttp => "AI_HTTP_ERROR";
+    AiNetwork => "AI_NETWORK";
+    AiParse => "AI_PARSE";
+    FileNotFound => "FILE_NOT_FOUND";
+    FilePermissionDenied => "FILE_PERMISSION_DENIED";
+    FileIo => "FILE_IO";
+    DiskSpaceInsufficient => "DISK_SPACE_INSUFFICIENT";
+    InvalidInput => "INVALID_INPUT";
+    Database => "DATABASE";
+    WorkspaceLocked => "WORKSPACE_LOCKED";
+    Internal => "INTERNAL";
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl From<&SpiderError> for ErrorCode {
+    fn from(e: &SpiderError) -> Self {
+        match e {
+            SpiderError::WafBlocked(_) => ErrorCode::SpiderWafBlocked,
+            SpiderError::Timeout(_) => ErrorCode::SpiderTimeout,
+            SpiderError::CircuitOpen { .. } => ErrorCode::SpiderCircuitOpen,
+            SpiderError::EventBridgeUnavailable => ErrorCode::SpiderBridgeUnavailable,
+            SpiderError::PageTooLarge { .. } => ErrorCode::SpiderPageTooLarge,
+            SpiderError::PayloadIncomplete { .. } => ErrorCode::SpiderPayloadIncomplete,
+            SpiderError::ChapterUnavailable { .. } => ErrorCode::SpiderChapterUnavailable,
+            SpiderError::NovelRemoved { .. } => ErrorCode::SpiderNovelRemoved,
+            SpiderError::Other(_) => ErrorCode::SpiderFailed,
+        }
+    }
+}
+
+impl From<&AiError> for ErrorCode {
+    fn from(e: &AiError) -> Self {
+        match e {
+            AiError::BadRequest(_) => ErrorCode::AiBadRequest,
+            AiError::Http { status: 413, .. } => ErrorCode::AiPayloadTooLarge,
+            AiError::Http { status: 401 | 403, .. } => ErrorCode::AiUnauthorized,
+            AiError::Http { status: 429, .. } => ErrorCode::AiRateLimited,
+            AiError::Http { .. } => ErrorCode::AiHttp,
+            AiError::Network(_) => ErrorCode::AiNetwork,
+            AiError::Parse(_) => ErrorCode::AiParse,
+        }
+    }
+}
+
+/// 命令返回给前端的错误：错误码 + 本次的具体信息。Display 输出 detail，可直接用于日志和旧的字符串接口
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub detail: String,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self { code, detail: detail.into() }
+    }
+
+    pub fn invalid_input(detail: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, detail)
+    }
+
+    pub fn ai_not_configured() -> Self {
+        Self::new(ErrorCode::AiNotConfigured, resources::text("message.ai_not_configured"))
+    }
+
+    /// 带上下文的文件错误，按 io::ErrorKind 区分不存在 / 无权限 / 其他
+    pub fn io(context: &str, e: &std::io::Error) -> Self {
+        let code = match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::FileNotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorCode::FilePermissionDenied,
+            _ => ErrorCode::FileIo,
+        };
+        Self::new(code, format!("{}: {}", context, e))
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.detail.is_empty() {
+            f.write_str(&self.code.message_zh())
+        } else {
+            f.write_str(&self.detail)
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Wire<'a> {
+            code: ErrorCode,
+            message_zh: String,
+            message_en: String,
+            detail: &'a str,
+        }
+        Wire { code: self.code, message_zh: self.code.message_zh(), message_en: self.code.message_en(), detail: &self.detail }
+            .serialize(serializer)
+    }
+}
+
+impl From<SpiderError> for AppError {
+    fn from(e: SpiderError) -> Self {
+        Self::new(ErrorCode::from(&e), e.to_string())
+    }
+}
+
+impl From<AiError> for AppError {
+    fn from(e: AiError) -> Self {
+        Self::new(ErrorCode::from(&e), e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        Self::io("读写文件失败", &e)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::new(ErrorCode::Database, e.to_string())
+    }
+}
+
+/// 尚未分类的字符串错误（大多数内部函数仍返回 String）
+impl From<String> for AppError {
+    fn from(detail: String) -> Self {
+        Self::new(ErrorCode::Internal, detail)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(detail: &str) -> Self {
+        Self::new(ErrorCode::Internal, detail)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn codes_are_unique_and_screaming_snake_case() {
+        let mut seen = HashSet::new();
+        for code in ErrorCode::ALL {
+            let s = code.as_str();
+            assert!(seen.insert(s), "重复的错误码: {}", s);
+            assert!(s.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'), "{}", s);
+            assert!(!code.message_zh().is_empty() && !code.message_en().is_empty(), "{} 缺少说明", s);
+        }
+    }
+
+    #[test]
+    fn serializes_code_messages_and_detail() {
+        let err = AppError::from(SpiderError::WafBlocked("https://m.qidian.com/book/1/catalog".into()));
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["code"], "SPIDER_WAF_BLOCKED");
+        assert_eq!(value["message_en"], "Blocked by the site's bot protection");
+        assert_eq!(value["detail"], "被 WAF 拦截: https://m.qidian.com/book/1/catalog");
+        assert_eq!(err.to_string(), "被 WAF 拦截: https://m.qidian.com/book/1/catalog");
+    }
+
+    #[test]
+    fn sources_map_to_specific_codes() {
+        let http = |status| AppError::from(AiError::Http { status, body: String::new() }).code;
+        assert_eq!(http(429), ErrorCode::AiRateLimited);
+        assert_eq!(http(401), ErrorCode::AiUnauthorized);
+        assert_eq!(http(500), ErrorCode::AiHttp);
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(AppError::io("读取 01.txt 失败", &missing).code, ErrorCode::FileNotFound);
+        assert_eq!(AppError::from("未分类".to_string()).code, ErrorCode::Internal);
+    }
+}
@@ -0,0 +1,220 @@
+//! 进度 / 状态事件的缓冲。
+//!
+//! 所有进度类事件经 [`emit_and_buffer`] 发出：事件带递增的 `seq` 和发出时间 `emitted_at`，
+//! 同时保留最近 [`BUFFER_CAPACITY`] 条。前端重新加载或新开窗口后先订阅实时事件，再用
+//! `get_recent_events` 补齐历史，实时事件中 `seq` 不大于已补齐最大值的丢弃即可去重。
+//! AI 流式输出（`ai-analysis`）逐字推送、量大且只对当前窗口有意义，不进缓冲。
+//!
+//! 推送统一经 [`emit_safely`]：主窗口已关闭（后台下载继续）或正在退出时没有可接收的窗口，
+//! 事件只进缓冲并记一条日志；推送出错甚至 panic 也不会传到任务里，后台任务照常写文件和历史，
+//! 窗口重新打开后用 `get_recent_events` 补齐。
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::{Emitter, Manager};
+
+pub const BUFFER_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BufferedEvent {
+    pub seq: u64,
+    /// 事件名，如 `download-progress`
+    pub kind: String,
+    pub emitted_at: String,
+    pub payload: serde_json::Value,
+}
+
+struct EventBuffer {
+    events: VecDeque<BufferedEvent>,
+    next_seq: u64,
+    capacity: usize,
+}
+
+impl EventBuffer {
+    fn new(capacity: usize) -> Self {
+        EventBuffer { events: VecDeque::with_capacity(capacity), next_seq: 1, capacity }
+    }
+
+    fn push(&mut self, kind: &str, payload: serde_json::Value) -> BufferedEvent {
+        let event = BufferedEvent {
+            seq: self.next_seq,
+            kind: kind.to_string(),
+            emitted_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            payload,
+        };
+        self.next_seq += 1;
+        if self.events.len() >= self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+        event
+    }
+
+    /// seq 大于 since_seq 的事件；kinds 为空时不过滤事件名
+    fn since(&self, kinds: &[String], since_seq: u64) -> Vec<BufferedEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.seq > since_seq)
+            .filter(|e| kinds.is_empty() || kinds.iter().any(|k| k == &e.kind))
+            .cloned()
+            .collect()
+    }
+}
+
+fn buffer() -> &'static Mutex<EventBuffer> {
+    static BUFFER: OnceLock<Mutex<EventBuffer>> = OnceLock::new();
+    BUFFER.get_or_init(|| Mutex::new(EventBuffer::new(BUFFER_CAPACITY)))
+}
+
+/// 对象载荷补上 `seq` / `emitted_at` 字段后实时推送；其他载荷原样推送。
+fn live_payload(event: &BufferedEvent) -> serde_json::Value {
+    let mut payload = event.payload.clone();
+    if let serde_json::Value::Object(map) = &mut payload {
+        map.insert("seq".to_string(), event.seq.into());
+        map.insert("emitted_at".to_string(), event.emitted_at.clone().into());
+    }
+    payload
+}
+
+/// 事件的推送目标，测试中可用不依赖窗口的假实现
+pub trait EventSink {
+    /// 是否还有能接收事件的窗口
+    fn has_target(&self) -> bool;
+    fn emit_value(&self, kind: &str, payload: serde_json::Value) -> Result<(), String>;
+}
+
+impl EventSink for tauri::AppHandle {
+    fn has_target(&self) -> bool {
+        !self.webview_windows().is_empty()
+    }
+
+    fn emit_value(&self, kind: &str, payload: serde_json::Value) -> Result<(), String> {
+        self.emit(kind, payload).map_err(|e| e.to_string())
+    }
+}
+
+impl<T: EventSink + ?Sized> EventSink for &T {
+    fn has_target(&self) -> bool {
+        (**self).has_target()
+    }
+
+    fn emit_value(&self, kind: &str, payload: serde_json::Value) -> Result<(), String> {
+        (**self).emit_value(kind, payload)
+    }
+}
+
+/// 上一次推送时是否没有窗口，用于只在状态切换时记日志
+static DETACHED: AtomicBool = AtomicBool::new(false);
+
+/// 推送事件，返回是否送达。没有窗口、推送出错或 panic 时只记日志，不向调用方传播。
+pub fn emit_safely<S: EventSink + ?Sized>(sink: &S, kind: &str, payload: serde_json::Value) -> bool {
+    if !sink.has_target() {
+        if !DETACHED.swap(true, Ordering::Relaxed) {
+            crate::log_to_file("[Events] 没有可用窗口，事件只写入缓冲，后台任务继续");
+        }
+        return false;
+    }
+    if DETACHED.swap(false, Ordering::Relaxed) {
+        crate::log_to_file("[Events] 窗口已恢复，继续推送事件");
+    }
+    match catch_unwind(AssertUnwindSafe(|| sink.emit_value(kind, payload))) {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            log::debug!("[Events] 推送 {} 失败: {}", kind, e);
+            false
+        }
+        Err(_) => {
+            crate::log_to_file(&format!("[Events] 推送 {} 时发生 panic，已忽略", kind));
+            false
+        }
+    }
+}
+
+/// 记入缓冲并推送事件。进度 / 状态类事件都应通过它发出。
+pub fn emit_and_buffer<S: EventSink + ?Sized, T: Serialize>(sink: &S, kind: &str, payload: T) {
+    let value = serde_json::to_value(payload).unwrap_or(serde_json::Value::Null);
+    let event = buffer().lock().unwrap_or_else(|e| e.into_inner()).push(kind, value);
+    emit_safely(sink, kind, live_payload(&event));
+}
+
+/// 缓冲中 seq 大于 since_seq 的事件，按 seq 升序
+pub fn recent_events(kinds: &[String], since_seq: u64) -> Vec<BufferedEvent> {
+    buffer().lock().unwrap_or_else(|e| e.into_inner()).since(kinds, since_seq)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_keeps_latest_events_with_increasing_seq() {
+        let mut buf = EventBuffer::new(3);
+        for i in 0..5 {
+            let kind = if i % 2 == 0 { "download-progress" } else { "ai-analysis-status" };
+            buf.push(kind, serde_json::json!({ "message": i }));
+        }
+        let all = buf.since(&[], 0);
+        assert_eq!(all.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(buf.since(&[], 4).len(), 1);
+
+        let progress = buf.since(&["download-progress".to_string()], 0);
+        assert_eq!(progress.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![3, 5]);
+        assert!(!progress[0].emitted_at.is_empty());
+    }
+
+    /// 假推送目标：windows 为 false 时模拟主窗口已关闭，panic 为 true 时模拟运行时在推送中 panic
+    pub(crate) struct MockSink {
+        pub windows: bool,
+        pub panic: bool,
+        pub delivered: Mutex<Vec<String>>,
+    }
+
+    impl MockSink {
+        pub(crate) fn new(windows: bool, panic: bool) -> Self {
+            MockSink { windows, panic, delivered: Mutex::new(Vec::new()) }
+        }
+    }
+
+    impl EventSink for MockSink {
+        fn has_target(&self) -> bool {
+            self.windows
+        }
+
+        fn emit_value(&self, kind: &str, _payload: serde_json::Value) -> Result<(), String> {
+            if self.panic {
+                panic!("webview 已销毁");
+            }
+            self.delivered.lock().unwrap().push(kind.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn emission_degrades_without_windows_and_never_panics() {
+        let live = MockSink::new(true, false);
+        assert!(emit_safely(&live, "test-live", serde_json::json!({})));
+        assert_eq!(*live.delivered.lock().unwrap(), vec!["test-live"]);
+
+        let closed = MockSink::new(false, false);
+        assert!(!emit_safely(&closed, "test-closed", serde_json::json!({})));
+        assert!(closed.delivered.lock().unwrap().is_empty());
+
+        let dying = MockSink::new(true, true);
+        emit_and_buffer(&dying, "test-dying", serde_json::json!({ "message": "仍然进缓冲" }));
+        let buffered = recent_events(&["test-dying".to_string()], 0);
+        assert_eq!(buffered.last().unwrap().payload["message"], "仍然进缓冲");
+    }
+
+    #[test]
+    fn live_payload_carries_seq_for_dedup() {
+        let mut buf = EventBuffer::new(2);
+        let event = buf.push("download-progress", serde_json::json!({ "message": "m" }));
+        let live = live_payload(&event);
+        assert_eq!(live["seq"], 1);
+        assert_eq!(live["message"], "m");
+        assert_eq!(live_payload(&buf.push("report-generated", serde_json::Value::Null)), serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,347 @@
+//! 整书导出，以及导出类长任务共用的进度机制。
+//!
+//! 导出任务在 [`crate::tasks`] 中登记（`list_active_tasks` 可见、`abort_all_tasks` 可取消），
+//! 通过 `export-progress` 事件上报阶段（reading / writing / compressing）和当前 / 总章节数。
+//! 输出先写到同目录的 `.part` 文件，完成后改名；失败或取消时删除半成品。
+//! 不论是否在后台运行，结束时都会发送一条带输出路径的 completed 事件。
+//! 章节和分析结果都经 [`stream_write`] 逐块拷贝到输出文件，内存占用与书的大小无关。
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::chapter_index::ChapterIndex;
+use crate::{analysis_batch, novel_info, stream_write};
+
+pub const PROGRESS_EVENT: &str = "export-progress";
+pub const EXPORTS_DIR: &str = "exports";
+/// 不超过该章数的书在命令内直接导出，超过的在后台导出并立即返回任务 ID
+pub const QUICK_EXPORT_CHAPTERS: usize = 200;
+const PART_SUFFIX: &str = ".part";
+const CANCELLED: &str = "导出已取消";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPhase {
+    Reading,
+    Writing,
+    /// 打包类导出（如 EPUB）压缩阶段
+    Compressing,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ExportProgress {
+    pub task_id: String,
+    pub phase: Option<ExportPhase>,
+    pub current: usize,
+    pub total: usize,
+    /// "progress" | "completed" | "cancelled" | "error"
+    pub status: String,
+    /// 完成时的输出文件，相对工作区
+    pub output_path: Option<String>,
+    pub message: String,
+}
+
+/// 导出命令的返回值：后台导出时 completed 为 false，结果看 export-progress 事件
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ExportStarted {
+    pub task_id: String,
+    pub output_path: String,
+    pub completed: bool,
+}
+
+/// 交给导出器的上下文：上报进度并检查取消
+pub struct ExportContext<'a> {
+    task_id: &'a str,
+    cancel: &'a AtomicBool,
+    emit: &'a dyn Fn(ExportProgress),
+}
+
+impl ExportContext<'_> {
+    /// 上报进度（大约每 1% 一次，避免两千章的书刷出上千条事件）；任务已取消时返回错误，导出器应直接用 `?` 退出
+    pub fn progress(&self, phase: ExportPhase, current: usize, total: usize) -> Result<(), String> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(CANCELLED.to_string());
+        }
+        let step = (total / 100).max(1);
+        if current == 0 || current == total || current.is_multiple_of(step) {
+            (self.emit)(ExportProgress {
+                task_id: self.task_id.to_string(),
+                phase: Some(phase),
+                current,
+                total,
+                status: "progress".to_string(),
+                output_path: None,
+                message: String::new(),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub fn new_task_id() -> String {
+    format!("export_{}", chrono::Local::now().format("%Y%m%d%H%M%S%3f"))
+}
+
+/// 导出文件的临时路径，完成后改名为 output
+fn part_path(output: &Path) -> PathBuf {
+    let mut part = output.as_os_str().to_owned();
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
+
+/// 执行一个已登记的导出任务：job 写出传入的临时文件，成功后改名为 output。
+/// 出错或取消时删除临时文件；结束时发送 completed / cancelled / error 事件并注销任务。
+pub fn run_task(
+    workspace_root: &Path,
+    task_id: &str,
+    output: &Path,
+    cancel: &AtomicBool,
+    emit: &dyn Fn(ExportProgress),
+    job: impl FnOnce(&ExportContext, &Path) -> Result<(), String>,
+) -> Result<PathBuf, String> {
+    let part = part_path(output);
+    let ctx = ExportContext { task_id, cancel, emit };
+    let result = job(&ctx, &part).and_then(|()| {
+        fs::rename(&part, output).map_err(|e| format!("写入 {} 失败: {}", output.display(), e))
+    });
+    crate::tasks::unregister(task_id);
+
+    let relative = crate::paths::to_relative(workspace_root, output).unwrap_or_else(|| output.display().to_string());
+    let (status, output_path, message) = match &result {
+        Ok(()) => ("completed", Some(relative), "导出完成".to_string()),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            (if e == CANCELLED { "cancelled" } else { "error" }, None, e.clone())
+        }
+    };
+    emit(ExportProgress {
+        task_id: task_id.to_string(),
+        phase: None,
+        current: 0,
+        total: 0,
+        status: status.to_string(),
+        output_path,
+        message,
+    });
+    result.map(|()| output.to_path_buf())
+}
+
+/// 整书导出的目标文件 `exports/<目录名>.txt`
+pub fn novel_output_path(workspace_root: &Path, novel_dir: &Path) -> PathBuf {
+    let name = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "novel".to_string());
+    workspace_root.join(EXPORTS_DIR).join(format!("{}.txt", name))
+}
+
+/// 把已下载章节按序号合并为一个 txt：书名 / 作者开头，每章以标题分隔
+pub fn export_novel(ctx: &ExportContext, novel_dir: &Path, part: &Path) -> Result<(), String> {
+    let chapters = analysis_batch::chapter_files(novel_dir);
+    if chapters.is_empty() {
+        return Err("没有已下载的章节".to_string());
+    }
+    let total = chapters.len();
+    ctx.progress(ExportPhase::Reading, 0, total)?;
+    let info = novel_info::read_info(novel_dir).unwrap_or_default();
+    let field = |key: &str| info.get(key).and_then(|v| v.as_str()).map(str::trim).filter(|s| !s.is_empty());
+    let title = field("title").map(str::to_string).unwrap_or_else(|| {
+        novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    });
+
+    let mut out = stream_write::create_text(part).map_err(|e| format!("创建 {} 失败: {}", part.display(), e))?;
+    let write_err = |e: std::io::Error| format!("写入 {} 失败: {}", part.display(), e);
+    writeln!(out, "《{}》", title).map_err(write_err)?;
+    if let Some(author) = field("author") {
+        writeln!(out, "作者: {}", author).map_err(write_err)?;
+    }
+    for (i, name) in chapters.iter().enumerate() {
+        let read_err = |e: std::io::Error| format!("读取 {} 失败: {}", name, e);
+        let mut reader = stream_write::open(&novel_dir.join(name)).map_err(read_err)?;
+        let heading = stream_write::read_chapter_header(&mut reader)
+            .map_err(read_err)?
+            .unwrap_or_else(|| name.trim_end_matches(".txt").to_string());
+        write!(out, "\n\n{}\n\n", heading).map_err(write_err)?;
+        stream_write::copy_trimmed(&mut reader, &mut out).map_err(write_err)?;
+        writeln!(out).map_err(write_err)?;
+        ctx.progress(ExportPhase::Writing, i + 1, total)?;
+    }
+    stream_write::finish_text(out).map_err(write_err)
+}
+
+/// 合并分析报告的目标文件 `exports/<书名>.analysis.md`
+pub fn report_output_path(workspace_root: &Path, novel_title: &str) -> PathBuf {
+    workspace_root.join(EXPORTS_DIR).join(format!("{}.analysis.md", novel_title))
+}
+
+/// `result/<书名>/` 下按章节序号命名的分析结果（`N.md`），按序号排列
+pub fn chapter_results(workspace_root: &Path, novel_title: &str) -> BTreeMap<usize, PathBuf> {
+    let Ok(entries) = fs::read_dir(analysis_batch::result_dir(workspace_root, novel_title)) else {
+        return BTreeMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let index = e.file_name().to_string_lossy().strip_suffix(".md")?.parse::<usize>().ok()?;
+            Some((index, e.path()))
+        })
+        .collect()
+}
+
+/// 把一本书的逐章分析结果合并为一个 markdown：目录只用文件名和 chapters.json 中的标题生成，
+/// 正文逐个文件流式拷贝（去掉 front matter），每章前加锚点供目录跳转
+pub fn export_analysis_report(
+    ctx: &ExportContext,
+    workspace_root: &Path,
+    novel_title: &str,
+    novel_dir: Option<&Path>,
+    part: &Path,
+) -> Result<(), String> {
+    let results = chapter_results(workspace_root, novel_title);
+    if results.is_empty() {
+        return Err(format!("《{}》没有逐章分析结果", novel_title));
+    }
+    let total = results.len();
+    ctx.progress(ExportPhase::Reading, 0, total)?;
+    let index = novel_dir.map(ChapterIndex::load);
+    let heading = |n: usize| {
+        let title = index
+            .as_ref()
+            .and_then(|idx| idx.get(n))
+            .map(|r| r.full_title.clone().unwrap_or_else(|| r.title.clone()))
+            .filter(|t| !t.trim().is_empty());
+        match title {
+            Some(title) => format!("{}. {}", n, title),
+            None => format!("第 {} 章", n),
+        }
+    };
+
+    let mut out = stream_write::create_text(part).map_err(|e| format!("创建 {} 失败: {}", part.display(), e))?;
+    let write_err = |e: std::io::Error| format!("写入 {} 失败: {}", part.display(), e);
+    writeln!(out, "# 《{}》分析报告\n\n## 目录\n", novel_title).map_err(write_err)?;
+    for &n in results.keys() {
+        writeln!(out, "- [{}](#chapter-{})", heading(n), n).map_err(write_err)?;
+    }
+    for (i, (&n, path)) in results.iter().enumerate() {
+        let read_err = |e: std::io::Error| format!("读取 {} 失败: {}", path.display(), e);
+        let mut reader = stream_write::open(path).map_err(read_err)?;
+        stream_write::skip_front_matter(&mut reader).map_err(read_err)?;
+        write!(out, "\n<a id=\"chapter-{}\"></a>\n\n## {}\n\n", n, heading(n)).map_err(write_err)?;
+        stream_write::copy_trimmed(&mut reader, &mut out).map_err(write_err)?;
+        writeln!(out).map_err(write_err)?;
+        ctx.progress(ExportPhase::Writing, i + 1, total)?;
+    }
+    stream_write::finish_text(out).map_err(write_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library;
+    use std::sync::Mutex;
+
+    fn novel(root: &Path, chapters: usize) -> PathBuf {
+        let dir = library::downloads_dir(root).join("书");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("info.json"), r#"{"title":"测试书","author":"某人"}"#).unwrap();
+        for n in 1..=chapters {
+            let content = library::render_chapter_file(&format!("第{}章", n), "u", &format!("第{}章的正文", n));
+            fs::write(dir.join(library::chapter_file_name(n)), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn novel_is_merged_and_completion_reports_path() {
+        let root = std::env::temp_dir().join(format!("test_export_novel_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = novel(&root, 3);
+        let output = novel_output_path(&root, &dir);
+        let events = Mutex::new(Vec::new());
+        let cancel = crate::tasks::register("export_test_ok", crate::tasks::TaskKind::Export, "书");
+        run_task(&root, "export_test_ok", &output, &cancel, &|p| events.lock().unwrap().push(p), |ctx, part| {
+            export_novel(ctx, &dir, part)
+        })
+        .unwrap();
+
+        let text = fs::read_to_string(&output).unwrap();
+        assert!(text.starts_with("《测试书》\n作者: 某人\n\n\n第1章\n\n第1章的正文\n"));
+        assert!(text.contains("第3章\n\n第3章的正文"));
+        let events = events.into_inner().unwrap();
+        let last = events.last().unwrap();
+        assert_eq!(last.status, "completed");
+        assert_eq!(last.output_path.as_deref(), Some("exports/书.txt"));
+        assert!(events.iter().any(|e| e.phase == Some(ExportPhase::Writing) && e.current == 3 && e.total == 3));
+        assert!(!crate::tasks::cancel("export_test_ok"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn cancelled_export_leaves_no_partial_file() {
+        let root = std::env::temp_dir().join(format!("test_export_cancel_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = novel(&root, 5);
+        let output = novel_output_path(&root, &dir);
+        let cancel = crate::tasks::register("export_test_cancel", crate::tasks::TaskKind::Export, "书");
+        let last = Mutex::new(None);
+        let emit = |p: ExportProgress| {
+            // 写完第 2 章时取消
+            if p.current == 2 {
+                crate::tasks::cancel("export_test_cancel");
+            }
+            *last.lock().unwrap() = Some(p);
+        };
+        let err = run_task(&root, "export_test_cancel", &output, &cancel, &emit, |ctx, part| export_novel(ctx, &dir, part))
+            .unwrap_err();
+        assert_eq!(err, CANCELLED);
+        assert!(!output.exists() && !part_path(&output).exists());
+        assert_eq!(last.into_inner().unwrap().unwrap().status, "cancelled");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn analysis_report_streams_large_books_with_bounded_memory() {
+        use crate::test_alloc::measure;
+
+        let root = std::env::temp_dir().join(format!("test_export_report_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = novel(&root, 2);
+        let results = analysis_batch::result_dir(&root, "书");
+        fs::create_dir_all(&results).unwrap();
+        // 400 章、每章约 48KB 的分析结果，合计约 19MB
+        let body = "　　细纲：主角下山历练，途中结识同伴。\n".repeat(800);
+        for n in 1..=400 {
+            fs::write(results.join(format!("{}.md", n)), format!("---\nmodel: m\n---\n\n# 第{}章细纲\n{}", n, body)).unwrap();
+        }
+        fs::write(results.join("1-10.md"), "区间结果不计入").unwrap();
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=2).map(|i| crate::chapter_index::ChapterRecord { index: i, title: format!("第{}章 标题", i), ..Default::default() }));
+        index.save().unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let ctx = ExportContext { task_id: "report", cancel: &cancel, emit: &|_| {} };
+        let output = report_output_path(&root, "书");
+        let (result, stats) = measure(|| export_analysis_report(&ctx, &root, "书", Some(&dir), &output));
+        result.unwrap();
+        let size = fs::metadata(&output).unwrap().len();
+        eprintln!("analysis report: {} 字节，峰值 {} 字节", size, stats.peak_bytes);
+        assert!(size > 15 * 1024 * 1024);
+        assert!(stats.peak_bytes < 2 * 1024 * 1024, "峰值 {} 字节", stats.peak_bytes);
+
+        let text = fs::read_to_string(&output).unwrap();
+        assert!(text.starts_with("# 《书》分析报告\n\n## 目录\n\n- [1. 第1章 标题](#chapter-1)\n- [2. 第2章 标题](#chapter-2)\n- [第 3 章](#chapter-3)\n"));
+        assert!(text.contains("<a id=\"chapter-400\"></a>\n\n## 第 400 章\n\n# 第400章细纲\n"));
+        assert!(!text.contains("model: m") && !text.contains("区间结果"));
+
+        // 整书导出同样按块拷贝
+        let long = "　　这是很长的一章正文。".repeat(20_000);
+        fs::write(dir.join(library::chapter_file_name(3)), library::render_chapter_file("第3章", "u", &long)).unwrap();
+        let novel_out = novel_output_path(&root, &dir);
+        let (result, stats) = measure(|| export_novel(&ctx, &dir, &novel_out));
+        result.unwrap();
+        assert!(stats.peak_bytes < long.len() / 2, "峰值 {} 字节", stats.peak_bytes);
+        assert!(fs::read_to_string(&novel_out).unwrap().ends_with(&format!("第3章\n\n{}\n", long)));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
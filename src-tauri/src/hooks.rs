@@ -0,0 +1,200 @@
+//! 下载完成后的通知：webhook（POST JSON）和 / 或在小说目录写入 `completed.flag`。
+//!
+//! 在设置的 `post_download` 中配置，由下载任务的 `notify` 参数逐任务开启。
+//! 通知失败只记日志，不影响下载结果。
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::download::DownloadSummary;
+
+pub const COMPLETED_FLAG_FILE: &str = "completed.flag";
+const DEFAULT_WEBHOOK_TIMEOUT_SECS: u64 = 10;
+/// 第一次失败后重试前的等待
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PostDownloadHooks {
+    /// 下载完成后 POST 通知的地址
+    pub webhook_url: Option<String>,
+    /// 单次请求超时，缺省 10 秒
+    pub webhook_timeout_secs: Option<u64>,
+    /// 在小说目录写入 completed.flag
+    pub write_completed_flag: bool,
+}
+
+impl PostDownloadHooks {
+    pub fn is_empty(&self) -> bool {
+        self.webhook_url.as_deref().is_none_or(|u| u.trim().is_empty()) && !self.write_completed_flag
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.webhook_timeout_secs.filter(|&s| s > 0).unwrap_or(DEFAULT_WEBHOOK_TIMEOUT_SECS))
+    }
+}
+
+/// webhook 请求体和 completed.flag 的内容
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionNotice {
+    pub novel_title: String,
+    /// 工作区相对路径
+    pub novel_dir: String,
+    pub url: String,
+    pub platform: String,
+    pub completed_at: String,
+    #[serde(flatten)]
+    pub summary: DownloadSummary,
+}
+
+/// POST 一次 JSON，非 2xx 视为失败
+async fn post_once(client: &Client, url: &str, body: &serde_json::Value, timeout: Duration) -> Result<u16, String> {
+    let response = client
+        .post(url)
+        .timeout(timeout)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    Ok(status.as_u16())
+}
+
+/// 发送 webhook，失败重试一次。返回最终的 HTTP 状态码。
+pub async fn send_webhook(url: &str, body: &serde_json::Value, timeout: Duration) -> Result<u16, String> {
+    let url = url.trim();
+    let parsed = url::Url::parse(url).map_err(|e| format!("无效的 webhook 地址 {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("webhook 地址只支持 http / https: {}", url));
+    }
+    let client = Client::new();
+    match post_once(&client, url, body, timeout).await {
+        Ok(status) => Ok(status),
+        Err(first) => {
+            eprintln!("[Hooks] webhook 失败，{} 秒后重试: {}", WEBHOOK_RETRY_DELAY.as_secs(), first);
+            tokio::time::sleep(WEBHOOK_RETRY_DELAY).await;
+            post_once(&client, url, body, timeout).await.map_err(|e| format!("重试后仍失败: {}", e))
+        }
+    }
+}
+
+fn write_flag(novel_dir: &Path, notice: &CompletionNotice) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(notice).map_err(|e| format!("序列化失败: {}", e))?;
+    crate::storage::write_atomic(&novel_dir.join(COMPLETED_FLAG_FILE), content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", COMPLETED_FLAG_FILE, e))
+}
+
+/// 执行配置的全部通知，返回失败信息（已写日志）。
+pub async fn run(hooks: &PostDownloadHooks, workspace_root: &Path, novel_dir: &Path, notice: &CompletionNotice) -> Vec<String> {
+    let mut errors = Vec::new();
+    if hooks.write_completed_flag {
+        if let Err(e) = write_flag(novel_dir, notice) {
+            errors.push(e);
+        }
+    }
+    if let Some(url) = hooks.webhook_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        let body = serde_json::to_value(notice).unwrap_or_default();
+        if let Err(e) = send_webhook(url, &body, hooks.timeout()).await {
+            errors.push(format!("webhook {}: {}", url, e));
+        }
+    }
+    for e in &errors {
+        eprintln!("[Hooks] 《{}》下载完成通知失败: {}", notice.novel_title, e);
+        crate::log_to_file_with_root(
+            &format!("[Hooks] 《{}》下载完成通知失败: {}", notice.novel_title, e),
+            Some(workspace_root),
+        );
+    }
+    errors
+}
+
+/// test_webhook 发送的示例请求体
+pub fn sample_notice() -> CompletionNotice {
+    CompletionNotice {
+        novel_title: "测试通知".to_string(),
+        novel_dir: format!("{}/测试通知", crate::library::DOWNLOADS_DIR),
+        url: String::new(),
+        platform: String::new(),
+        completed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        summary: DownloadSummary::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// 依次按给定状态码应答的本地 HTTP 服务，返回地址和收到的请求体
+    fn serve(statuses: Vec<u16>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = vec![0u8; 64 * 1024];
+                let mut request = String::new();
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.push_str(&String::from_utf8_lossy(&buf[..n]));
+                    if let Some(head_end) = request.find("\r\n\r\n") {
+                        let length = request[..head_end]
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() >= head_end + 4 + length {
+                            bodies.push(request[head_end + 4..].to_string());
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let _ = stream.write_all(format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes());
+            }
+            bodies
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn webhook_retries_once_after_failure() {
+        let (url, server) = serve(vec![500, 200]);
+        let body = serde_json::to_value(sample_notice()).unwrap();
+        assert_eq!(send_webhook(&url, &body, Duration::from_secs(5)).await, Ok(200));
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let received: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(received["novel_title"], "测试通知");
+        assert_eq!(received["success"], 0);
+
+        assert!(send_webhook("ftp://example.com", &body, Duration::from_secs(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn flag_is_written_and_failures_do_not_panic() {
+        let root = std::env::temp_dir().join(format!("test_hooks_{}", std::process::id()));
+        let novel_dir = root.join("downloads").join("书名");
+        std::fs::create_dir_all(&novel_dir).unwrap();
+        let hooks = PostDownloadHooks {
+            webhook_url: Some("http://127.0.0.1:9/unreachable".to_string()),
+            webhook_timeout_secs: Some(1),
+            write_completed_flag: true,
+        };
+        let errors = run(&hooks, &root, &novel_dir, &sample_notice()).await;
+        assert_eq!(errors.len(), 1);
+        let flag: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(novel_dir.join(COMPLETED_FLAG_FILE)).unwrap()).unwrap();
+        assert_eq!(flag["novel_title"], "测试通知");
+        assert!(PostDownloadHooks::default().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -4,21 +4,98 @@ pub mod browser_spider;
 pub mod scheduler;
 pub mod analysis_engine;
 pub mod db;
+pub mod storage;
+pub mod novel_info;
+pub mod library;
+pub mod download;
+pub mod progress;
+pub mod prompts;
+pub mod settings;
+pub mod versions;
+pub mod paths;
+pub mod analysis_batch;
+pub mod ai_context;
+pub mod ai_limits;
+pub mod bookmarks;
+pub mod provenance;
+pub mod chapter_index;
+pub mod catalog_order;
+pub mod download_analysis;
+pub mod events;
+pub mod errors;
+pub mod hooks;
+pub mod rank_metadata;
+pub mod text_normalize;
+pub mod csv_export;
+pub mod library_export;
+pub mod result_links;
+pub mod clean_rules;
+pub mod library_search;
+pub mod source_check;
+pub mod rank_snapshots;
+pub mod purpose_heatmap;
+pub mod backup;
+pub mod scratch;
+pub mod prompt_comparison;
+pub mod outline;
+pub mod analysis_snippets;
+pub mod analysis_import;
+pub mod tasks;
+pub mod export;
+pub mod arcs;
+pub mod redownload;
+pub mod chapter_search;
+pub mod analysis_versions;
+pub mod author_works;
+pub mod local_api;
+pub mod segmentation;
+pub mod disk_space;
+pub mod translation;
+pub mod sharding;
+pub mod offline_metadata;
+pub mod ai_overrides;
+pub mod chapter_view;
+pub mod network_mode;
+pub mod content_check;
+pub mod workspace_lock;
+pub mod rank_bookmarks;
+pub mod stream_write;
+pub mod ai_retry;
+pub mod download_history;
+pub mod activity_digest;
+pub mod priority;
+pub mod bootstrap;
+pub mod limits;
+pub mod move_journal;
+pub mod edit_journal;
+pub mod access_probe;
+pub mod resources;
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+mod test_alloc;
 
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::fs;
 use std::path::Path;
+#[cfg(not(test))]
 use std::sync::Mutex;
-use tauri::{Emitter, Manager};
+use tauri::Manager;
+#[cfg(not(test))]
 use tauri::menu::{Menu, MenuItem};
+#[cfg(not(test))]
 use tauri::tray::TrayIconBuilder;
 use chrono::Local;
+use errors::AppError;
+#[cfg(not(test))]
+use errors::ErrorCode;
+#[cfg(not(test))]
+use spiders::LiveSource;
 
 // ... (Keep existing ai logic)
 
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 async fn start_ai_analysis(
     app: tauri::AppHandle,
@@ -26,59 +103,87 @@ async fn start_ai_analysis(
     api_key: String,
     model: String,
     prompt: String,
-    content: String,
+    content: Option<String>,
     response_json: Option<bool>, // 是否强制要求 JSON 返回
-) -> Result<String, String> {
+    selection: Option<ai::TextSelection>, // 只分析选段（按字符计）
+    template: Option<String>, // prompt 为空时使用的模板名
+    novel: Option<NovelContext>, // 用于按题材 / 平台选择默认模板
+    include_context: Option<bool>, // 在正文前附带小说背景（需要 novel）
+    context_sources: Option<ai_context::ContextSources>,
+    source: Option<scratch::ContentSource>, // 正文来源（章节 / 临时文档 / 文本），优先于 content
+    output_language: Option<String>, // 要求 AI 使用的输出语言代码，如 en / ja
+    ai_overrides: Option<ai_overrides::AiOverrides>, // 本次调用的 profile / max_tokens / chunk_size，优先于书的覆盖
+) -> Result<String, AppError> {
     // ... (Keep existing implementation)
+    let output_language = prompts::output_language(output_language.as_deref()).map_err(AppError::invalid_input)?;
     let app_handle = app.clone();
+    let content = match (source, content) {
+        (Some(source), _) => source.read(&get_workspace_root(&app))?,
+        (None, Some(content)) => content,
+        (None, None) => return Err(AppError::invalid_input(resources::text("message.missing_content"))),
+    };
+
+    // 选段模式：先校验并截取，再包一层上下文说明；未指定 prompt 时改用选段分析模板
+    let (content, prompt, status_note) = match selection {
+        Some(sel) => {
+            let excerpt = ai::slice_selection(&content, &sel).map_err(AppError::invalid_input)?;
+            let note = resources::format("message.selection_note", &[("chars", &excerpt.chars().count().to_string())]);
+            let prompt = if prompt.trim().is_empty() && template.is_none() {
+                prompts::builtin(prompts::SCENE_ANALYSIS)
+                    .map(|t| t.content)
+                    .unwrap_or_default()
+            } else {
+                prompt
+            };
+            (resources::format("prompt.selection_context", &[("excerpt", &ai::wrap_untrusted(&excerpt))]), prompt, Some(note))
+        }
+        None => (ai::wrap_untrusted(&content), prompt, None),
+    };
     
+    // 未显式给出提示词时走模板注册表（模板参数 → 题材映射 → 设置默认 → 内置细纲还原）
     let final_prompt = if prompt.trim().is_empty() {
-        r#"你是一个拥有10年经验的网文主编，擅长拆解爆款小说的底层逻辑。
-请将用户提供的这一章小说内容，反向还原为【细纲/章纲】。
-
-要求：
-1. 必须严格按照原文的叙事顺序，将内容拆解为关键情节节点。
-2. 每个节点必须包含两个部分：
-   - 【剧情概括】：用简练的语言概括发生了什么（Who Did What）。
-   - 【写作目的】：深度分析作者写这一段的意图（例如：制造冲突、拉高期待、压抑情绪、制造危机、展示金手指、打脸爽点、埋下伏笔、转换地图等）。
-
-请使用以下格式输出：
-
-### 1. [剧情节点]
-> **概括**: ...
-> **目的**: (例如：制造冲突) ...
-
-### 2. [剧情节点]
-...
-
-### 💡 本章核心总结
-(一句话概括本章主旨)"#.to_string()
+        let root = get_workspace_root(&app);
+        let info = novel.as_ref().and_then(|n| n.info(&app).ok());
+        prompts::resolve(&settings::load(&root), template.as_deref(), info.as_ref())?.content
     } else {
         prompt
     };
+    let final_prompt = ai::guard_prompt(&prompts::with_output_language(&final_prompt, output_language));
 
-    let config = ai::AiConfig {
-        api_base,
-        api_key,
-        model,
+    // 前端传入的接口配置是全局层，书的 user.ai_overrides 与 ai_overrides 参数依次覆盖
+    let global = ai::AiConfig { api_base, api_key, model, ..Default::default() };
+    let config = resolve_ai_config(&app, global, novel.as_ref(), ai_overrides)?.config;
+
+    let content = match (&novel, ai_context::sources(include_context, context_sources)) {
+        (Some(novel), Some(sources)) => {
+            let background = novel.background(&app, sources)?;
+            background.compose(&content, ai_context::body_budget_within(config.body_limit(), &final_prompt)).0
+        }
+        _ => content,
     };
 
     let force_json = response_json.unwrap_or(false);
+    let request_id = format!("ai_{}", Local::now().format("%Y%m%d%H%M%S%3f"));
 
+    let id = request_id.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = ai::stream_analysis(app_handle.clone(), config, final_prompt, content, force_json).await {
-             let _ = app_handle.emit("ai-analysis-status", ai::Progress {
+        if let Err(e) = ai::stream_analysis(app_handle.clone(), config, final_prompt, content, force_json, status_note, id.clone()).await {
+             local_api::publish(&id, local_api::StreamKind::Error, e.to_string());
+             events::emit_and_buffer(&app_handle, "ai-analysis-status", ai::Progress {
                 message: format!("Error: {}", e),
-                status: "error".to_string()
+                status: "error".to_string(),
+                code: Some(ErrorCode::from(&e)),
             });
         }
     });
 
-    Ok("Analysis started".to_string())
+    // 返回 request_id，外部工具可用它订阅本地接口的 /stream/<request_id>
+    Ok(request_id)
 }
 
 // ... (Other existing commands) ...
 
+#[cfg(not(test))]
 #[tauri::command]
 async fn fetch_ai_models(
     api_base: String,
@@ -88,266 +193,2578 @@ async fn fetch_ai_models(
         api_base,
         api_key,
         model: "".to_string(), // Not needed for fetching models
+        ..Default::default()
     };
     ai::fetch_models(config).await
 }
 
 // New Command: Update Novel Metadata with AI Analysis
+#[cfg(not(test))]
 #[tauri::command]
-fn update_novel_metadata(
+async fn update_novel_metadata(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
     dir_name: String, 
     novel_name: String, 
     metadata: serde_json::Value // Use generic Value to allow flexible merging
 ) -> Result<String, String> {
     println!("Backend: update_novel_metadata called for {}", novel_name);
-    let novel_path = Path::new(&dir_name).join(&novel_name);
-    let info_path = novel_path.join("info.json");
-    
-    if !info_path.exists() {
-        return Err("info.json not found".to_string());
-    }
-    
-    // Read existing
-    let content = fs::read_to_string(&info_path).map_err(|e| e.to_string())?;
-    let mut current_meta: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    workspace_lock::ensure_writable(&resolve_workspace_root(&app, workspace_root.clone()))?;
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+
+    // Merge new metadata (assuming metadata is an object containing fields to update).
+    // 锁内重新读取 info.json 再合并，避免与其他写入者互相覆盖
+    let patch = metadata.as_object().cloned().unwrap_or_default();
+    novel_info::merge_info(&novel_path, &patch).await?;
     
-    // Merge new metadata (assuming metadata is an object containing fields to update)
-    if let Some(obj) = metadata.as_object() {
-        if let Some(current_obj) = current_meta.as_object_mut() {
-            for (k, v) in obj {
-                current_obj.insert(k.clone(), v.clone());
+    Ok("Metadata updated".to_string())
+}
+
+/// 只凭本地章节重建 info.json（来源失效、导入或 info.json 丢失的书），标记 metadata_source 为
+/// offline-rebuild，不改动 user。传入 ai_config 时再用自动分析提示词分析前几章。重建后登记到书库数据库。
+#[cfg(not(test))]
+#[tauri::command]
+async fn rebuild_metadata_offline(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    ai_config: Option<ai::AiConfig>,
+) -> Result<offline_metadata::RebuildReport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name).map_err(AppError::invalid_input)?;
+    let report = offline_metadata::rebuild(&novel_path, ai_config.as_ref()).await?;
+    match crate::db::get_conn() {
+        Ok(conn) => {
+            if let Err(e) = offline_metadata::register(&conn, &report.info) {
+                log_to_file_with_root(&format!("[Metadata] 《{}》登记到书库失败: {}", report.title, e), Some(&root));
             }
         }
+        Err(e) => log_to_file_with_root(&format!("[Metadata] DB 连接失败: {}", e), Some(&root)),
     }
-    
-    // Write back
-    let new_content = serde_json::to_string_pretty(&current_meta).unwrap_or_default();
-    fs::write(info_path, new_content).map_err(|e| e.to_string())?;
-    
-    Ok("Metadata updated".to_string())
+    log_to_file_with_root(
+        &format!("[Metadata] 《{}》已离线重建元数据：{} 章，{} 字{}", report.title, report.chapters, report.downloaded_chars, if report.analyzed { "，含 AI 分析" } else { "" }),
+        Some(&root),
+    );
+    Ok(report)
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct RepairNovelResult {
+    #[serde(flatten)]
+    report: crate::library::RepairReport,
+    /// 是否已按 info.json 中的链接把问题章节重新加入下载
+    redownload_started: bool,
+}
+
+/// 扫描残留 / 校验失败的章节文件。只有 confirm_delete 为 true 时才删除它们，否则只返回列表；
+/// redownload 为 true 且 info.json 有 url 时，立即在后台重新下载这些章节（未删除的旧内容由编辑记录留档）。
+#[cfg(not(test))]
+#[tauri::command]
+async fn repair_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    redownload: Option<bool>,
+    confirm_delete: Option<bool>,
+) -> Result<RepairNovelResult, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    let report = crate::library::repair_novel_dir(&novel_path, novel_min_chapter_chars(&root, &novel_path), confirm_delete.unwrap_or(false))?;
+    log_to_file(&format!(
+        "[Repair] {}: 检查 {} 个章节，{} 个有问题{}",
+        novel_name,
+        report.checked,
+        report.broken.len(),
+        if report.deleted { "，已删除" } else { "" }
+    ));
+
+    let mut redownload_started = false;
+    if redownload.unwrap_or(false) && !report.requeue.is_empty() {
+        let info = novel_info::read_info(&novel_path).unwrap_or_default();
+        if let Some(url) = info.get("url").and_then(|v| v.as_str()) {
+            let req = crate::download::DownloadRequest {
+                url: url.to_string(),
+                platform: info
+                    .get("platform")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| guess_platform(url)),
+                debug_visible: false,
+                start_chapter: None,
+                chapter_count: None,
+                selected_indices: Some(report.requeue.clone()),
+                novel_dir: Some(novel_path.clone()),
+                // 未删除的问题章节仍在磁盘上，不强制重下会被当作已下载跳过
+                force: !report.deleted,
+                ..Default::default()
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::download::process_novel_download(&app, &LiveSource::new(&app), &root, req).await {
+                    eprintln!("[Repair] 重新下载失败: {}", e);
+                }
+            });
+            redownload_started = true;
+        }
+    }
+
+    Ok(RepairNovelResult { report, redownload_started })
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct UpdateNovelResult {
+    /// revalidate 时与 chapters.json 哈希不一致的章节（会在本次更新中重新下载）
+    mismatches: Vec<chapter_index::HashMismatch>,
+}
+
+/// 按已保存的 chapters.json 重新检查目录顺序（乱序 / 断档 / 重复），并核对目录、索引与磁盘文件是否一致
+#[cfg(not(test))]
+#[tauri::command]
+fn validate_catalog(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<catalog_order::CatalogValidation, String> {
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    catalog_order::validate_stored(&novel_path)
+}
+
+/// 按 chapters.json 中保存的目录标题和章节页标题重新选定章节文件头部的标题，不重新下载
+#[cfg(not(test))]
+#[tauri::command]
+fn retitle_chapters(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<catalog_order::RetitleReport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    Ok(catalog_order::retitle_stored(&novel_path, settings::load(&root).edit_history_max_bytes())?)
+}
+
+/// 把平铺存放的章节迁移到 `chapters/NNN/` 子目录，chapters.json 记录每章的新路径。
+/// chapters_per_dir 缺省按设置（设置关闭分目录时用 [`sharding::DEFAULT_CHAPTERS_PER_DIR`]）。
+#[cfg(not(test))]
+#[tauri::command]
+fn shard_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    chapters_per_dir: Option<usize>,
+) -> Result<sharding::ShardReport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    let per_dir = chapters_per_dir
+        .filter(|n| *n > 0)
+        .or_else(|| settings::load(&root).chapters_per_dir())
+        .unwrap_or(sharding::DEFAULT_CHAPTERS_PER_DIR);
+    let report = sharding::shard_novel(&novel_path, per_dir)?;
+    // 章节路径变了，全文索引里的键随之更新
+    if let Err(e) = chapter_search::refresh_novel(&root, &novel_path) {
+        log_to_file_with_root(&format!("[Shard] 更新检索索引失败: {}", e), Some(&root));
+    }
+    log_to_file_with_root(
+        &format!("[Shard] {} 移动 {} 章到 {} 个子目录", novel_name, report.moved, report.shards),
+        Some(&root),
+    );
+    Ok(report)
+}
+
+/// 阅读界面的章节列表：chapters.json 与目录中的文件合并后每章一个状态（downloaded / failed /
+/// vip_locked / deleted / missing / placeholder），附文件大小和是否已被分析。
+#[cfg(not(test))]
+#[tauri::command]
+fn get_novel_chapters_view(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<chapter_view::ChaptersView, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    let analyzed = provenance::analyzed_files(&root, &novel_name);
+    Ok(chapter_view::build(&novel_path, &analyzed))
+}
+
+/// 删除一章的文件，并在 chapters.json 中标记为已删除（而不是仍记为下载成功），同时更新字数统计
+#[cfg(not(test))]
+#[tauri::command]
+async fn delete_chapter(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    chapter_index: usize,
+) -> Result<(), String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    chapter_index::ChapterIndex::update(&novel_path, |index| {
+        let file = index.chapter_path(chapter_index);
+        match storage::remove(&file) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(resources::format("message.delete_failed", &[("path", &file.display().to_string()), ("error", &e.to_string())])),
+        }
+        index.mark_deleted(chapter_index);
+        Ok(())
+    })?;
+    if novel_info::info_path(&novel_path).exists() {
+        novel_info::refresh_download_stats(&novel_path).await?;
+    }
+    log_to_file_with_root(&format!("[Chapter] {} 第 {} 章已删除", novel_name, chapter_index), Some(&root));
+    Ok(())
+}
+
+/// 按正文检查重新扫描一本书的全部章节，更新 chapters.json 的可疑标记并返回可疑章节。
+/// 阈值按 info.json 中的平台取设置，见 [`content_check`]。
+#[cfg(not(test))]
+#[tauri::command]
+fn verify_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<Vec<content_check::SuspectChapter>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    let platform = novel_info::read_info(&novel_path)
+        .ok()
+        .and_then(|info| info.get("platform").and_then(|v| v.as_str()).map(str::to_string))
+        .unwrap_or_default();
+    let config = settings::load(&root).content_check(&platform);
+    let suspects = content_check::verify(&novel_path, &config)?;
+    log_to_file_with_root(&format!("[ContentCheck] {} 有 {} 章正文可疑", novel_name, suspects.len()), Some(&root));
+    Ok(suspects)
+}
+
+/// 按 info.json 中的链接更新整本书：已下载且哈希与 chapters.json 一致的章节跳过，其余（重新）下载。
+/// revalidate 为 true 时先校验全部章节的哈希并返回不一致的章节。下载在后台进行，进度走 download-progress。
+#[cfg(not(test))]
+#[tauri::command]
+async fn update_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    revalidate: Option<bool>,
+) -> Result<UpdateNovelResult, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name).map_err(AppError::invalid_input)?;
+    let info = novel_info::read_info(&novel_path)?;
+    let url = info
+        .get("url")
+        .and_then(|v| v.as_str())
+        .filter(|u| !u.trim().is_empty())
+        .ok_or_else(|| AppError::invalid_input(resources::text("message.update_missing_url")))?
+        .to_string();
+
+    // 上次只拿到移动站兜底的元数据：丢弃缓存的目录，让这次更新重新尝试完整抓取
+    let fallback = info.get("metadata_source").and_then(|v| v.as_str()) == Some(crate::spiders::qidian::MOBILE_FALLBACK_SOURCE);
+    if fallback && crate::download::invalidate_catalog(&url) {
+        log_to_file_with_root(&format!("[Update] {}: 元数据来自移动站兜底，重新抓取详情页", novel_name), Some(&root));
+    }
+
+    let mut mismatches = Vec::new();
+    if revalidate.unwrap_or(false) {
+        let mut index =
+            chapter_index::ChapterIndex::load(&novel_path).with_min_body_chars(novel_min_chapter_chars(&root, &novel_path));
+        mismatches = index.sync(|index| Ok(index.revalidate()))?;
+        log_to_file_with_root(
+            &format!("[Update] {}: 校验发现 {} 个章节与索引不一致", novel_name, mismatches.len()),
+            Some(&root),
+        );
+    }
+
+    let req = crate::download::DownloadRequest {
+        platform: info
+            .get("platform")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| guess_platform(&url)),
+        url,
+        debug_visible: false,
+        start_chapter: Some(1),
+        chapter_count: Some(usize::MAX),
+        selected_indices: None,
+        novel_dir: Some(novel_path),
+        force: false,
+        ..Default::default()
+    };
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = crate::download::process_novel_download(&app, &LiveSource::new(&app), &root, req).await {
+            eprintln!("[Update] 更新失败: {}", e);
+        }
+    });
+    Ok(UpdateNovelResult { mismatches })
+}
+
+/// 阅读接近已下载的最后一章时调用：在后台下载最远已下载章节之后的 ahead 章。
+/// 优先用 chapters.json 中的目录，其中剩余章节不足 ahead 章时重新获取目录。
+/// 预取在每章开始前让位于用户发起的下载，进度走带 prefetch 标签的 download-progress；已归档的书不预取。
+/// 立即返回任务 id，该书已在预取时返回已有任务的 id。
+#[cfg(not(test))]
+#[tauri::command]
+async fn prefetch_next_chapters(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    ahead: usize,
+) -> Result<String, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name).map_err(AppError::invalid_input)?;
+    if novel_info::is_archived(&novel_path) {
+        return Err(AppError::invalid_input(resources::format("message.prefetch_archived", &[("title", &novel_name)])));
+    }
+    if source_check::is_removed(&novel_path) {
+        return Err(AppError::invalid_input(resources::format("message.prefetch_removed", &[("title", &novel_name)])));
+    }
+    let info = novel_info::read_info(&novel_path)?;
+    let url = info
+        .get("url")
+        .and_then(|v| v.as_str())
+        .filter(|u| !u.trim().is_empty())
+        .ok_or_else(|| AppError::invalid_input(resources::text("message.prefetch_missing_url")))?
+        .to_string();
+
+    let index = chapter_index::ChapterIndex::load(&novel_path);
+    let (start, catalog_fresh) = crate::download::prefetch_start(&index, ahead);
+    let title = info.get("title").and_then(|v| v.as_str()).unwrap_or(&novel_name);
+    let task_id = format!("prefetch_{}", Local::now().format("%Y%m%d%H%M%S%3f"));
+    if let Err(existing) = crate::download::begin_prefetch(&novel_path, &task_id) {
+        return Ok(existing);
+    }
+    let req = crate::download::DownloadRequest {
+        platform: info
+            .get("platform")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| guess_platform(&url)),
+        url,
+        start_chapter: Some(start),
+        chapter_count: Some(ahead),
+        novel_dir: Some(novel_path.clone()),
+        prefetch: true,
+        catalog: catalog_fresh.then(|| crate::download::catalog_from_index(title, &index)),
+        ..Default::default()
+    };
+    let id = task_id.clone();
+    tauri::async_runtime::spawn(priority::background(async move {
+        if let Err(e) = crate::download::process_novel_download(&app, &LiveSource::new(&app), &root, req).await {
+            eprintln!("[Prefetch] {} 预取失败: {}", id, e);
+        }
+        crate::download::end_prefetch(&novel_path);
+    }));
+    Ok(task_id)
+}
+
+/// 读取校验后的书籍信息（info.json 字段 + 下载统计、user 字段、来源状态、归档标记）。
+/// 前端读取书籍信息都走这里，get_file_content 只用于读取原始文本。没有 info.json 的目录返回 null。
+#[cfg(not(test))]
+#[tauri::command]
+fn get_novel_info(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<Option<novel_info::NovelInfo>, String> {
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    if !novel_info::info_path(&novel_path).exists() {
+        return Ok(None);
+    }
+    Ok(Some(novel_info::load_typed(&novel_path)))
+}
+
+/// 读取手动维护的 user 字段（info.json 中的 "user" 对象）
+#[cfg(not(test))]
+#[tauri::command]
+fn get_user_metadata(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<serde_json::Value, String> {
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    novel_info::read_user_fields(&novel_path).map(serde_json::Value::Object)
+}
+
+/// 合并写入 user 字段，值为 null 时删除该字段。爬虫与 AI 合并不会改动这些字段。
+#[cfg(not(test))]
+#[tauri::command]
+async fn set_user_metadata(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    fields: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    workspace_lock::ensure_writable(&resolve_workspace_root(&app, workspace_root.clone()))?;
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let fields = fields.as_object().cloned().ok_or_else(|| resources::text("message.fields_not_object"))?;
+    novel_info::set_user_fields(&novel_path, &fields).await.map(serde_json::Value::Object)
+}
+
+/// 设置故事弧（覆盖原有的全部弧），保存在 user.arcs。范围不能重叠、不能超出章节总数；
+/// 弧只影响章节分组，已有的分析结果不受影响。返回按起始章节排序后的弧
+#[cfg(not(test))]
+#[tauri::command]
+async fn set_novel_arcs(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    arcs: Vec<crate::arcs::StoryArc>,
+) -> Result<Vec<crate::arcs::StoryArc>, AppError> {
+    workspace_lock::ensure_writable(&resolve_workspace_root(&app, workspace_root.clone()))?;
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name).map_err(AppError::invalid_input)?;
+    let arcs = crate::arcs::validate(arcs, crate::arcs::chapter_count(&novel_path)).map_err(AppError::invalid_input)?;
+    crate::arcs::save(&novel_path, &arcs).await?;
+    Ok(arcs)
+}
+
+/// 已定义的故事弧，附各弧的章节数、已下载数和分析覆盖率
+#[cfg(not(test))]
+#[tauri::command]
+fn get_novel_arcs(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<Vec<arcs::ArcSummary>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let novel_path = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    Ok(arcs::summarize(&root, &novel_path, &novel_name))
+}
+
+/// 归档 / 取消归档，等同于 set_user_metadata 写入 user.archived
+#[cfg(not(test))]
+#[tauri::command]
+async fn archive_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    archived: bool,
+) -> Result<serde_json::Value, String> {
+    workspace_lock::ensure_writable(&resolve_workspace_root(&app, workspace_root.clone()))?;
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let mut fields = serde_json::Map::new();
+    fields.insert(novel_info::ARCHIVED_KEY.to_string(), archived.into());
+    novel_info::set_user_fields(&novel_path, &fields).await.map(serde_json::Value::Object)
+}
+
+// Default prompt for auto (front) analysis: moved to backend for single source of truth
+#[cfg(not(test))]
+#[tauri::command]
+fn get_auto_analysis_prompt() -> String {
+    prompts::builtin(prompts::AUTO_ANALYSIS)
+        .map(|t| t.content)
+        .unwrap_or_default()
+}
+
+/// 前端传入的小说上下文，用于读取 info.json 中的题材 / 平台
+#[cfg(not(test))]
+#[derive(serde::Deserialize)]
+struct NovelContext {
+    #[serde(default)]
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    /// 正在分析的章节文件（如 `05.txt`），附带上一章细纲时使用
+    #[serde(default)]
+    chapter_file: Option<String>,
+}
+
+#[cfg(not(test))]
+impl NovelContext {
+    fn dir(&self, app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+        novel_path(app, self.workspace_root.clone(), &self.dir_name, &self.novel_name)
+    }
+
+    fn info(&self, app: &tauri::AppHandle) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        novel_info::read_info(&self.dir(app)?)
+    }
+
+    /// 按启用的来源读取背景信息
+    fn background(&self, app: &tauri::AppHandle, sources: ai_context::ContextSources) -> Result<ai_context::NovelContext, String> {
+        let root = resolve_workspace_root(app, self.workspace_root.clone());
+        Ok(ai_context::load(&root, &self.dir(app)?, self.chapter_file.as_deref(), sources))
+    }
+}
+
+/// 按 命令参数 > 书的 user.ai_overrides > 全局配置 解析本次使用的 AI 配置
+#[cfg(not(test))]
+fn resolve_ai_config(
+    app: &tauri::AppHandle,
+    global: ai::AiConfig,
+    novel: Option<&NovelContext>,
+    explicit: Option<ai_overrides::AiOverrides>,
+) -> Result<ai_overrides::EffectiveAiConfig, AppError> {
+    let root = resolve_workspace_root(app, novel.and_then(|n| n.workspace_root.clone()));
+    let stored = match novel {
+        Some(novel) => ai_overrides::AiOverrides::load(&novel.dir(app)?),
+        None => ai_overrides::AiOverrides::default(),
+    };
+    ai_overrides::resolve(&global, &settings::load(&root), &stored, &explicit.unwrap_or_default()).map_err(AppError::invalid_input)
+}
+
+/// 调试用：显示解析后的 AI 配置（不含密钥）以及模型、max_tokens、chunk_size 等每项取自哪一层
+#[cfg(not(test))]
+#[tauri::command]
+fn get_effective_ai_config(
+    app: tauri::AppHandle,
+    novel: Option<NovelContext>,
+    ai_overrides: Option<ai_overrides::AiOverrides>,
+) -> Result<ai_overrides::EffectiveAiConfig, AppError> {
+    resolve_ai_config(&app, global_ai_config(&app)?, novel.as_ref(), ai_overrides)
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct EffectivePromptPreview {
+    #[serde(flatten)]
+    prompt: prompts::EffectivePrompt,
+    /// 放在正文前的背景块（未开启 include_context 或没有可用背景时为空）
+    context: Option<String>,
+    /// 传入 content 时，正文包进分隔块后的实际发送内容
+    wrapped_content: Option<String>,
+}
+
+/// 预览最终会发送的提示词（与 start_ai_analysis 的空 prompt 解析规则一致）。include_context 为 true 时
+/// 同时返回背景块；传入 content 时按请求体上限截短，与实际发送的一致。output_language 同 start_ai_analysis。
+/// 返回的提示词已附加正文安全说明，wrapped_content 为包进分隔块后的正文。
+#[cfg(not(test))]
+#[tauri::command]
+fn get_effective_prompt(
+    app: tauri::AppHandle,
+    novel: Option<NovelContext>,
+    template: Option<String>,
+    include_context: Option<bool>,
+    context_sources: Option<ai_context::ContextSources>,
+    content: Option<String>,
+    output_language: Option<String>,
+) -> Result<EffectivePromptPreview, String> {
+    let info = novel.as_ref().and_then(|n| n.info(&app).ok());
+    let mut prompt = prompts::resolve(&settings::load(&get_workspace_root(&app)), template.as_deref(), info.as_ref())?;
+    let language = prompts::output_language(output_language.as_deref())?;
+    prompt.content = ai::guard_prompt(&prompts::with_output_language(&prompt.content, language));
+    let wrapped_content = content.as_deref().map(ai::wrap_untrusted);
+    let context = match (novel, ai_context::sources(include_context, context_sources)) {
+        (Some(novel), Some(sources)) => {
+            let background = novel.background(&app, sources)?;
+            let budget = ai_context::body_budget(&prompt.content);
+            background.compose(wrapped_content.as_deref().unwrap_or_default(), budget).1.render()
+        }
+        _ => None,
+    };
+    Ok(EffectivePromptPreview { prompt, context, wrapped_content })
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn list_prompt_templates(app: tauri::AppHandle) -> Vec<prompts::TemplateEntry> {
+    prompts::list_templates(&settings::load(&get_workspace_root(&app)))
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn get_settings(app: tauri::AppHandle) -> settings::Settings {
+    settings::load(&get_workspace_root(&app))
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn update_settings(app: tauri::AppHandle, settings: settings::Settings) -> Result<(), String> {
+    if let Some(name) = settings.prompt_templates.keys().find(|n| prompts::builtin(n).is_some()) {
+        return Err(resources::format("message.template_name_taken", &[("name", name)]));
+    }
+    let root = get_workspace_root(&app);
+    workspace_lock::ensure_writable(&root)?;
+    let settings = settings::update(&root, |current| {
+        *current = settings;
+        Ok(current.clone())
+    })?;
+    storage::set_text_format(settings.text_files);
+    ai::set_request_options(settings.ai_requests.clone());
+    limits::apply(&settings);
+    if settings.network_mode != network_mode::mode() {
+        network_mode::set_mode(settings.network_mode);
+        log_to_file_with_root(&format!("[Network] 网络模式切换为 {:?}", settings.network_mode), Some(&root));
+    }
+    Ok(())
+}
+
+/// 某章的当前版本与修改历史中的旧版本
+#[cfg(not(test))]
+#[tauri::command]
+fn list_chapter_versions(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    chapter_file: String,
+) -> Result<Vec<versions::ChapterVersion>, String> {
+    versions::list_versions(&novel_path(&app, workspace_root, &dir_name, &novel_name)?, &chapter_file)
+}
+
+/// 当前章节与最近一个历史版本的逐行 diff
+#[cfg(not(test))]
+#[tauri::command]
+fn diff_chapter_versions(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    chapter_file: String,
+) -> Result<versions::ChapterDiff, String> {
+    versions::diff_latest(&novel_path(&app, workspace_root, &dir_name, &novel_name)?, &chapter_file)
+}
+
+/// 一本书的章节修改历史（`.history/edits.json`），从新到旧
+#[cfg(not(test))]
+#[tauri::command]
+fn list_edits(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<Vec<edit_journal::EditEntry>, String> {
+    Ok(edit_journal::list(&novel_path(&app, workspace_root, &dir_name, &novel_name)?))
+}
+
+/// 撤销一次修改：恢复它改过的全部章节文件并把日志条目标记为已撤销。之后有更新的修改动过同一章时拒绝
+#[cfg(not(test))]
+#[tauri::command]
+async fn undo_edit(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    edit_id: String,
+) -> Result<edit_journal::EditEntry, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    workspace_lock::ensure_writable(&root)?;
+    let novel_dir = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let entry = edit_journal::undo(&novel_dir, &edit_id)?;
+    if novel_info::info_path(&novel_dir).exists() {
+        novel_info::refresh_download_stats(&novel_dir).await?;
+    }
+    log_to_file_with_root(
+        &format!("[EditJournal] 《{}》已撤销修改 {}，恢复 {} 个文件", novel_name, edit_id, entry.files.len()),
+        Some(&root),
+    );
+    Ok(entry)
+}
+
+/// 某章分析结果的全部版本（`N.md` 为第 1 版，之后为 `N_v2.md`…），含各版本的模型、提示词哈希和分析时间
+#[cfg(not(test))]
+#[tauri::command]
+fn list_analysis_versions(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: String,
+    chapter_index: usize,
+) -> Result<Vec<analysis_versions::AnalysisVersion>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, analysis_batch::RESULT_DIR, &novel_title)?;
+    Ok(analysis_versions::list_versions(&root, &novel_title, chapter_index))
+}
+
+/// 两个分析结果版本的逐行 diff（忽略 front matter），文件名取自 list_analysis_versions
+#[cfg(not(test))]
+#[tauri::command]
+fn diff_analysis_versions(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: String,
+    old_file: String,
+    new_file: String,
+) -> Result<versions::ChapterDiff, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, analysis_batch::RESULT_DIR, &novel_title)?;
+    analysis_versions::diff(&root, &novel_title, &old_file, &new_file)
+}
+
+/// 某平台当前生效的选择器（含默认值、是否被覆盖、覆盖无效时的错误）
+#[cfg(not(test))]
+#[tauri::command]
+fn get_active_selectors(platform: String) -> Result<Vec<spiders::selectors::ActiveSelector>, String> {
+    spiders::selectors::active(&platform)
+}
+
+/// 写入 selectors.json 中的一条覆盖；selector 为空表示恢复默认
+#[cfg(not(test))]
+#[tauri::command]
+fn set_selector_override(
+    app: tauri::AppHandle,
+    platform: String,
+    purpose: String,
+    selector: Option<String>,
+) -> Result<Vec<spiders::selectors::ActiveSelector>, String> {
+    let root = get_workspace_root(&app);
+    workspace_lock::ensure_writable(&root)?;
+    spiders::selectors::set_override(&root, &platform, &purpose, selector.as_deref())?;
+    spiders::selectors::active(&platform)
+}
+
+/// 抓取页面并试运行选择器（缺省用当前生效的选择器），返回匹配数和示例文本
+#[cfg(not(test))]
+#[tauri::command]
+async fn test_selector(
+    app: tauri::AppHandle,
+    platform: String,
+    purpose: String,
+    url: String,
+    selector: Option<String>,
+) -> Result<spiders::selectors::SelectorTestResult, String> {
+    let selector = match selector.filter(|s| !s.trim().is_empty()) {
+        Some(s) => s,
+        None => spiders::selectors::active(&platform)?
+            .into_iter()
+            .find(|a| a.purpose == purpose)
+            .map(|a| a.selector)
+            .ok_or_else(|| resources::format("message.unknown_selector_purpose", &[("purpose", &purpose)]))?,
+    };
+    let html = match platform.as_str() {
+        "qidian" => spiders::qidian::fetch_page(&app, &url, false).await?,
+        "fanqie" => {
+            network_mode::gate(&url, |_| {}).await?;
+            reqwest::Client::new()
+                .get(&url)
+                .header("User-Agent", "Mozilla/5.0")
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())?
+        }
+        other => return Err(resources::format("message.unsupported_platform", &[("platform", other)])),
+    };
+    spiders::selectors::test_on_html(&html, &selector)
+}
+
+/// 把工作区内记录文件中的绝对路径改写为相对路径（移动工作区前后执行一次即可）
+#[cfg(not(test))]
+#[tauri::command]
+fn normalize_library_paths(app: tauri::AppHandle, workspace_root: Option<String>) -> Result<paths::NormalizeReport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let report = paths::normalize_library_paths(&root)?;
+    log_to_file_with_root(
+        &format!("[Paths] 路径迁移: 扫描 {} 个文件，改写 {} 处", report.files_scanned, report.paths_rewritten),
+        Some(&root),
+    );
+    Ok(report)
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct SpiderMetrics {
+    platforms: Vec<spiders::circuit::PlatformMetrics>,
+    windows: browser_spider::WindowMetrics,
+    catalog_cache: crate::download::CatalogCacheStats,
+}
+
+/// 各平台爬虫熔断器状态、爬虫窗口的占用和排队情况，以及目录缓存的命中统计
+#[cfg(not(test))]
+#[tauri::command]
+fn get_spider_metrics() -> SpiderMetrics {
+    SpiderMetrics {
+        platforms: spiders::circuit::metrics(),
+        windows: browser_spider::window_metrics(),
+        catalog_cache: crate::download::catalog_cache_stats(),
+    }
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct AbortReport {
+    /// 已通知取消的后台任务数（边下载边分析、导出）
+    cancelled_tasks: usize,
+    closed_windows: usize,
+}
+
+/// 取消所有后台任务并强制关闭爬虫窗口，进行中的抓取随之结束并归还窗口许可
+#[cfg(not(test))]
+#[tauri::command]
+fn abort_all_tasks(app: tauri::AppHandle) -> AbortReport {
+    let cancelled_tasks = tasks::cancel_all();
+    let closed_windows = browser_spider::close_all_windows(&app);
+    log_to_file(&format!("[Abort] 取消 {} 个任务，关闭 {} 个爬虫窗口", cancelled_tasks, closed_windows));
+    AbortReport { cancelled_tasks, closed_windows }
+}
+
+/// 进行中的后台任务（边下载边分析、导出）
+#[cfg(not(test))]
+#[tauri::command]
+fn list_active_tasks() -> Vec<tasks::ActiveTask> {
+    tasks::list()
+}
+
+/// 切换网络模式并保存到设置：paused 时访问站点和远程 AI 的请求排队等待，blocked 时直接失败，
+/// 切回 normal 后排队的请求继续。本机 AI 接口不受影响
+#[cfg(not(test))]
+#[tauri::command]
+fn set_network_mode(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    mode: String,
+) -> Result<network_mode::NetworkStatus, AppError> {
+    let mode = network_mode::NetworkMode::parse(&mode).map_err(AppError::invalid_input)?;
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    settings::update(&root, |s| {
+        s.network_mode = mode;
+        Ok(())
+    })?;
+    network_mode::set_mode(mode);
+    log_to_file_with_root(&format!("[Network] 网络模式切换为 {:?}", mode), Some(&root));
+    Ok(network_mode::status())
+}
+
+/// 处理书库中上次中断的批量移动（见 [`move_journal`]），并按操作补做索引收尾
+#[cfg(not(test))]
+fn recover_moves(root: &Path) -> Vec<move_journal::RecoveryReport> {
+    let mut reports = Vec::new();
+    for dir in move_journal::pending_dirs(&library::downloads_dir(root)) {
+        match move_journal::recover(&dir) {
+            Ok(Some(report)) => {
+                if report.operation == sharding::SHARD_OPERATION {
+                    if let Err(e) = sharding::sync_index_paths(&dir) {
+                        log_to_file_with_root(&format!("[MoveJournal] {} 更新章节索引失败: {}", report.base, e), Some(root));
+                    }
+                }
+                log_to_file_with_root(
+                    &format!(
+                        "[MoveJournal] {} 的 {} 中断于 {}/{}，已{}",
+                        report.base,
+                        report.operation,
+                        report.completed,
+                        report.total,
+                        if report.rolled_forward { "补完" } else { "回滚" }
+                    ),
+                    Some(root),
+                );
+                reports.push(report);
+            }
+            Ok(None) => {}
+            Err(e) => log_to_file_with_root(&format!("[MoveJournal] 恢复 {} 失败: {}", dir.display(), e), Some(root)),
+        }
+    }
+    reports
+}
+
+/// 检查书库中上次中断的批量移动（如分目录迁移），按实际完成情况补完或回滚，返回处理过的操作
+#[cfg(not(test))]
+#[tauri::command]
+fn recover_incomplete_operations(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+) -> Result<Vec<move_journal::RecoveryReport>, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    Ok(recover_moves(&root))
+}
+
+/// 切换资源档位（low / normal / high）并保存到设置。爬虫窗口和 AI 槽位立即调整，
+/// 调小时执行中的请求不受影响，随许可归还逐步收紧
+#[cfg(not(test))]
+#[tauri::command]
+fn set_resource_profile(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    profile: String,
+) -> Result<limits::ActiveLimits, AppError> {
+    let profile = limits::ResourceProfile::parse(&profile).map_err(AppError::invalid_input)?;
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let settings = settings::update(&root, |s| {
+        s.resource_profile = profile;
+        Ok(s.clone())
+    })?;
+    limits::apply(&settings);
+    log_to_file_with_root(&format!("[Limits] 资源档位切换为 {:?}", profile), Some(&root));
+    Ok(limits::active(&settings))
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct BackendCapabilities {
+    version: &'static str,
+    /// 生效中的资源档位和各项并发上限
+    limits: limits::ActiveLimits,
+    network: network_mode::NetworkStatus,
+}
+
+/// 后端版本、生效中的资源档位和具体并发上限，以及网络模式
+#[cfg(not(test))]
+#[tauri::command]
+fn get_backend_capabilities(app: tauri::AppHandle, workspace_root: Option<String>) -> BackendCapabilities {
+    BackendCapabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        limits: limits::active(&settings::load(&resolve_workspace_root(&app, workspace_root))),
+        network: network_mode::status(),
+    }
+}
+
+/// 当前网络模式和排队等待恢复的请求数（含其中后台任务的请求数）
+#[cfg(not(test))]
+#[tauri::command]
+fn get_network_status() -> network_mode::NetworkStatus {
+    network_mode::status()
+}
+
+/// AI 请求槽位的交互 / 后台占用和排队，各接口最近一次声明的限额，以及批量分析是否正因额度将尽而暂停派发
+#[cfg(not(test))]
+#[tauri::command]
+fn get_ai_queue_status() -> ai_limits::QueueStatus {
+    ai_limits::queue_status()
+}
+
+/// 重试队列中等待重新分析的分组及其上次的错误，见 [`ai_retry`]
+#[cfg(not(test))]
+#[tauri::command]
+fn get_ai_retry_queue(app: tauri::AppHandle, workspace_root: Option<String>) -> Vec<ai_retry::RetryEntry> {
+    ai_retry::list(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 立即处理重试队列，最多 limit 条（缺省取设置中的 `ai_retry.batch_limit`）。按后台优先级派发
+#[cfg(not(test))]
+#[tauri::command]
+async fn process_ai_retry_queue(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    limit: Option<usize>,
+) -> Result<ai_retry::ProcessReport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let global = global_ai_config(&app)?;
+    let settings = settings::load(&root);
+    let limit = limit.unwrap_or(settings.ai_retry.batch_limit);
+    let report = priority::background(ai_retry::process(&root, &global, &settings, limit, settings.ai_retry.max_attempts)).await?;
+    log_to_file_with_root(&format!("[AiRetry] 处理重试队列: {:?}", report), Some(&root));
+    Ok(report)
+}
+
+/// 汇总 since ~ until（含首尾，缺省为前 7 天）的下载、分析和榜单变化，写入 `result/digest_<until>.md`，
+/// 返回路径和标题数字
+#[cfg(not(test))]
+#[tauri::command]
+fn generate_activity_digest(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<activity_digest::ActivityDigest, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let window = activity_digest::DigestWindow::parse(since.as_deref(), until.as_deref(), Local::now().date_naive())
+        .map_err(AppError::invalid_input)?;
+    Ok(activity_digest::generate(&root, window)?)
+}
+
+/// 把已下载章节合并导出为 `exports/<书名>.txt`。章节数不超过 [`export::QUICK_EXPORT_CHAPTERS`] 时直接导出，
+/// 否则在后台导出并立即返回任务 ID；进度和结果都通过 export-progress 事件上报，可用 cancel_export 取消
+#[cfg(not(test))]
+#[tauri::command]
+async fn export_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<export::ExportStarted, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    let novel_dir = novel_path(&app, workspace_root, &dir_name, &novel_name).map_err(AppError::invalid_input)?;
+    let output = export::novel_output_path(&root, &novel_dir);
+    // 合并后的 txt 与章节文件总大小相当
+    disk_space::ensure(&app, &resources::format("task.export_novel", &[("title", &novel_name)]), &output, disk_space::dir_size(&novel_dir))?;
+    let task_id = export::new_task_id();
+    let cancel = tasks::register(&task_id, tasks::TaskKind::Export, &novel_name);
+    let quick = analysis_batch::chapter_files(&novel_dir).len() <= export::QUICK_EXPORT_CHAPTERS;
+    let started = export::ExportStarted {
+        task_id: task_id.clone(),
+        output_path: paths::to_relative(&root, &output).unwrap_or_else(|| output.display().to_string()),
+        completed: quick,
+    };
+
+    let task = tauri::async_runtime::spawn_blocking(move || {
+        let emit = |p: export::ExportProgress| events::emit_and_buffer(&app, export::PROGRESS_EVENT, p);
+        let result = export::run_task(&root, &task_id, &output, &cancel, &emit, |ctx, part| {
+            export::export_novel(ctx, &novel_dir, part)
+        });
+        match &result {
+            Ok(path) => log_to_file_with_root(&format!("[Export] 整书导出 -> {}", path.display()), Some(&root)),
+            Err(e) => log_to_file_with_root(&format!("[Export] 整书导出 {} 未完成: {}", novel_dir.display(), e), Some(&root)),
+        }
+        result
+    });
+    if quick {
+        task.await.map_err(|e| resources::format("message.export_task_failed", &[("error", &e.to_string())]))??;
+    }
+    Ok(started)
+}
+
+/// 把一本书的逐章分析结果合并导出为 `exports/<书名>.analysis.md`（目录 + 每章锚点），任务与进度同 export_novel。
+/// dir_name 为小说所在目录（缺省 downloads），用于从 chapters.json 取章节标题
+#[cfg(not(test))]
+#[tauri::command]
+async fn export_analysis_report(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: String,
+    dir_name: Option<String>,
+) -> Result<export::ExportStarted, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    let dir_name = dir_name.unwrap_or_else(|| library::DOWNLOADS_DIR.to_string());
+    let novel_dir = novel_path(&app, workspace_root, &dir_name, &novel_title).ok().filter(|d| d.is_dir());
+    let output = export::report_output_path(&root, &novel_title);
+    let results = export::chapter_results(&root, &novel_title);
+    let source_bytes = results.values().filter_map(|p| fs::metadata(p).ok()).map(|m| m.len()).sum();
+    disk_space::ensure(&app, &resources::format("task.export_report", &[("title", &novel_title)]), &output, source_bytes)?;
+    let task_id = export::new_task_id();
+    let cancel = tasks::register(&task_id, tasks::TaskKind::Export, &novel_title);
+    let quick = results.len() <= export::QUICK_EXPORT_CHAPTERS;
+    let started = export::ExportStarted {
+        task_id: task_id.clone(),
+        output_path: paths::to_relative(&root, &output).unwrap_or_else(|| output.display().to_string()),
+        completed: quick,
+    };
+
+    let task = tauri::async_runtime::spawn_blocking(move || {
+        let emit = |p: export::ExportProgress| events::emit_and_buffer(&app, export::PROGRESS_EVENT, p);
+        let result = export::run_task(&root, &task_id, &output, &cancel, &emit, |ctx, part| {
+            export::export_analysis_report(ctx, &root, &novel_title, novel_dir.as_deref(), part)
+        });
+        match &result {
+            Ok(path) => log_to_file_with_root(&format!("[Export] 分析报告导出 -> {}", path.display()), Some(&root)),
+            Err(e) => log_to_file_with_root(&format!("[Export] 《{}》分析报告导出未完成: {}", novel_title, e), Some(&root)),
+        }
+        result
+    });
+    if quick {
+        task.await.map_err(|e| resources::format("message.export_task_failed", &[("error", &e.to_string())]))??;
+    }
+    Ok(started)
+}
+
+/// 取消导出任务，任务已结束时返回 false
+#[cfg(not(test))]
+#[tauri::command]
+fn cancel_export(task_id: String) -> bool {
+    tasks::cancel(&task_id)
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct Diagnostics {
+    workspace_root: String,
+    workspace_writable: bool,
+    /// 最近一次抓取使用的回传通道；probe 时为本次探测结果
+    bridge: Option<browser_spider::BridgeStatus>,
+    probe_error: Option<String>,
+    spiders: Vec<spiders::circuit::PlatformMetrics>,
+    /// 找不到对应小说的 result 分析目录数，见 find_orphan_results
+    orphan_results: usize,
+    /// 工作区疑似位于同步盘（OneDrive / iCloud 等）时的提醒
+    cloud_sync_warning: Option<String>,
+    /// 工作区所在磁盘的可用 / 总空间，无法查询时为 None
+    workspace_disk: Option<disk_space::VolumeSpace>,
+    /// 工作区锁：被另一实例占用时本实例只读
+    workspace_lock: workspace_lock::LockStatus,
+}
+
+/// 环境自检。传入 probe_url 时用蜘蛛窗口实际加载该页，实测事件桥是否可用。
+#[cfg(not(test))]
+#[tauri::command]
+async fn run_diagnostics(app: tauri::AppHandle, probe_url: Option<String>) -> Diagnostics {
+    let root = get_workspace_root(&app);
+    let marker = root.join(".diagnostics_write_test");
+    let workspace_writable = std::fs::write(&marker, b"ok").is_ok();
+    let _ = std::fs::remove_file(&marker);
+
+    let probe_error = match probe_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        Some(url) => browser_spider::fetch_via_window(&app, url, false).await.err().map(String::from),
+        None => None,
+    };
+    Diagnostics {
+        workspace_root: root.to_string_lossy().to_string(),
+        workspace_writable,
+        bridge: browser_spider::bridge_status(),
+        probe_error,
+        spiders: spiders::circuit::metrics(),
+        orphan_results: result_links::find_orphans(&root).len(),
+        cloud_sync_warning: storage::cloud_sync_provider(&root).map(|provider| {
+            resources::format("message.cloud_sync_warning", &[("provider", provider)])
+        }),
+        workspace_disk: disk_space::volume_space(&root).ok(),
+        workspace_lock: workspace_lock::status(&root),
+    }
+}
+
+/// 工作区锁的状态；被另一实例占用时下载、调度和写入类命令返回 WORKSPACE_LOCKED
+#[cfg(not(test))]
+#[tauri::command]
+fn get_workspace_lock_status(app: tauri::AppHandle, workspace_root: Option<String>) -> workspace_lock::LockStatus {
+    workspace_lock::status(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 手动解除某个平台的熔断
+#[cfg(not(test))]
+#[tauri::command]
+fn reset_circuit(platform: String) -> Result<(), String> {
+    if !matches!(platform.as_str(), "qidian" | "fanqie") {
+        return Err(resources::format("message.unsupported_platform", &[("platform", &platform)]));
+    }
+    spiders::circuit::reset(&platform);
+    Ok(())
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn list_reports(workspace_root: String) -> Result<Vec<String>, String> {
+    let mut files: Vec<String> = Vec::new();
+    
+    // 搜索工作目录下的 reports
+    let ws_reports = Path::new(&workspace_root).join("reports");
+    collect_report_files(&ws_reports, &mut files);
+    
+    // 同时搜索项目根目录下的 reports（可能不同于工作目录）
+    let project_reports = get_project_root().join("reports");
+    if project_reports != ws_reports {
+        collect_report_files(&project_reports, &mut files);
+    }
+    
+    files.sort();
+    files.dedup();
+    files.sort_by(|a, b| b.cmp(a)); // 最新的排前面
+    Ok(files)
+}
+
+#[cfg(not(test))]
+fn collect_report_files(dir: &Path, files: &mut Vec<String>) {
+    if !dir.exists() { return; }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                if name.ends_with(".md") {
+                    files.push(name.to_string());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn read_report(workspace_root: String, filename: String) -> Result<String, AppError> {
+    // 优先从工作目录读，找不到就从项目根目录读
+    let read = |path: std::path::PathBuf| fs::read_to_string(path).map_err(|e| AppError::io(&resources::format("message.read_report_failed", &[("file", &filename)]), &e));
+    let ws_path = Path::new(&workspace_root).join("reports").join(&filename);
+    if ws_path.exists() {
+        return read(ws_path);
+    }
+    read(get_project_root().join("reports").join(&filename))
+}
+
+/// metadata_only 为 true 时只抓榜单上各书的元数据（不取目录、不下载章节、不生成报告），
+/// consolidate 汇总到 analysis_data/rank_metadata.json，csv 另导出 rank_metadata.csv。
+#[cfg(not(test))]
+#[tauri::command]
+async fn trigger_full_scan(
+    app: tauri::AppHandle,
+    target_url: Option<String>,
+    platform: Option<String>,
+    metadata_only: Option<bool>,
+    consolidate: Option<bool>,
+    csv: Option<bool>,
+) -> Result<(), String> {
+    workspace_lock::ensure_writable(&get_workspace_root(&app))?;
+    if metadata_only.unwrap_or(false) {
+        let targets = metadata_scan_targets(target_url, platform)?;
+        let root = get_workspace_root(&app);
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = crate::rank_metadata::run(
+                &app, &root, &targets, consolidate.unwrap_or(false), csv.unwrap_or(false),
+            ).await {
+                eprintln!("[RankMetadata] 任务失败: {}", e);
+            }
+        });
+        return Ok(());
+    }
+    // 异步执行，不阻塞前端
+    let app_clone = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = trigger_full_scan_internal(&app_clone, target_url, platform).await;
+    });
+    Ok(())
+}
+
+/// 保存榜单书签（同名覆盖，地址不变时保留上次扫描记录）。platform 缺省按地址推断，
+/// default_params 为一键重扫时的模式（full / metadata_only）和汇总选项
+#[cfg(not(test))]
+#[tauri::command]
+fn save_rank_bookmark(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    name: String,
+    url: String,
+    platform: Option<String>,
+    default_params: Option<rank_bookmarks::ScanParams>,
+) -> Result<rank_bookmarks::RankBookmarkView, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let url = url.trim().to_string();
+    if url.is_empty() {
+        return Err(AppError::invalid_input(resources::text("message.rank_url_empty")));
+    }
+    let bookmark = rank_bookmarks::RankBookmark {
+        platform: platform.filter(|p| !p.trim().is_empty()).unwrap_or_else(|| guess_platform(&url)),
+        url,
+        params: default_params.unwrap_or_default(),
+        ..Default::default()
+    };
+    rank_bookmarks::save(&root, &name, bookmark).map_err(AppError::invalid_input)
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn list_rank_bookmarks(app: tauri::AppHandle, workspace_root: Option<String>) -> Vec<rank_bookmarks::RankBookmarkView> {
+    rank_bookmarks::list(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 删除榜单书签；ranks/ 下的历史快照保留
+#[cfg(not(test))]
+#[tauri::command]
+fn delete_rank_bookmark(app: tauri::AppHandle, workspace_root: Option<String>, name: String) -> Result<(), AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    rank_bookmarks::delete(&root, &name).map_err(AppError::invalid_input)
+}
+
+/// 按书签的默认参数在后台重扫，完成后记录结果并推送 rank-bookmark-completed
+#[cfg(not(test))]
+#[tauri::command]
+fn rescan_bookmark(app: tauri::AppHandle, workspace_root: Option<String>, name: String) -> Result<(), AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    rank_bookmarks::get(&root, &name).map_err(AppError::invalid_input)?;
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = rank_bookmarks::rescan(&app, &root, &name).await {
+            log_to_file_with_root(&format!("[RankBookmark] {} 重扫失败: {}", name, e), Some(&root));
+        }
+    });
+    Ok(())
+}
+
+/// 仅元数据扫榜的 (榜单 URL, 平台)：指定了目标时只扫它，否则取 workflow_config.json 中的 rank_urls
+#[cfg(not(test))]
+fn metadata_scan_targets(target_url: Option<String>, platform: Option<String>) -> Result<Vec<(String, String)>, String> {
+    let platform_of = |url: &str| if url.contains("fanqie") { "fanqie".to_string() } else { "qidian".to_string() };
+    if let Some(target) = target_url.filter(|t| !t.trim().is_empty()) {
+        if target.contains("/book/") || target.contains("/info/") {
+            return Err(resources::text("message.metadata_scan_rank_only"));
+        }
+        let platform = platform.unwrap_or_else(|| platform_of(&target));
+        return Ok(vec![(target, platform)]);
+    }
+    let config_path = get_project_root().join("workflow_config.json");
+    let content = fs::read_to_string(&config_path).map_err(|e| resources::format("message.read_failed", &[("path", &config_path.display().to_string()), ("error", &e.to_string())]))?;
+    let config: serde_json::Value = serde_json::from_str(&content).map_err(|e| resources::format("message.workflow_config_invalid", &[("error", &e.to_string())]))?;
+    let targets: Vec<(String, String)> = config["rank_urls"]
+        .as_array()
+        .map(|urls| urls.iter().filter_map(|u| u.as_str()).map(|u| (u.to_string(), platform_of(u))).collect())
+        .unwrap_or_default();
+    if targets.is_empty() {
+        return Err(resources::text("message.workflow_config_no_rank_urls"));
+    }
+    Ok(targets)
+}
+
+#[cfg(not(test))]
+async fn trigger_full_scan_internal(app_handle: &tauri::AppHandle, target_url: Option<String>, platform_opt: Option<String>) -> Result<(), String> {
+    println!("Manual trigger from frontend/tray: scan started");
+    let project_root = get_project_root();
+    let config_path = project_root.join("workflow_config.json");
+
+    let content = std::fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
+    let config = serde_json::from_str::<serde_json::Value>(&content).map_err(|e| e.to_string())?;
+
+    // 从 Tauri State 读取工作目录（与前端选择一致）
+    let workspace_root = get_workspace_root(app_handle);
+    let mut aggregated_report = String::new();
+    let mut any_success = false;
+
+    if let Some(target) = target_url {
+        let platform = platform_opt.unwrap_or_else(|| {
+            if target.contains("fanqie") { "fanqie".to_string() } else { "qidian".to_string() }
+        });
+        // URL 含 /book/ 或 /info/ 视为单本，否则按榜单处理
+        let mode = if target.contains("/book/") || target.contains("/info/") {
+            crate::analysis_engine::PipelineMode::Single
+        } else {
+            crate::analysis_engine::PipelineMode::Rank
+        };
+        println!("Manual: Triggering analysis ({:?}) for target {} on platform {}", mode, target, platform);
+        match crate::analysis_engine::run_full_analysis_pipeline(
+            app_handle, &target, &platform, &workspace_root, mode
+        ).await {
+            Ok(partial) => {
+                any_success = true;
+                aggregated_report.push_str(&partial);
+                aggregated_report.push_str("\n\n---\n\n");
+            }
+            Err(e) => eprintln!("Manual: Pipeline failed for {}: {}", target, e),
+        }
+    } else {
+        if let Some(rank_urls) = config["rank_urls"].as_array() {
+            println!("Manual: Found {} rank URLs to process.", rank_urls.len());
+            for rank_url_val in rank_urls {
+                if let Some(rank_url) = rank_url_val.as_str() {
+                    let platform = if rank_url.contains("fanqie") { "fanqie" } else { "qidian" };
+                    println!("Manual: Triggering analysis for {}", rank_url);
+                    match crate::analysis_engine::run_full_analysis_pipeline(
+                        app_handle, rank_url, platform, &workspace_root,
+                        crate::analysis_engine::PipelineMode::Rank,
+                    ).await {
+                        Ok(partial) => {
+                            any_success = true;
+                            println!("Manual: Done for {}", rank_url);
+                            aggregated_report.push_str(&partial);
+                            aggregated_report.push_str("\n\n---\n\n");
+                        }
+                        Err(e) => {
+                            eprintln!("Manual: Pipeline failed for {}: {}", rank_url, e);
+                        }
+                    }
+                }
+            }
+        } else {
+            eprintln!("Manual: 'rank_urls' not found or not an array in config.");
+            return Err("No rank URLs found in config".to_string());
+        }
+    }
+    
+    if any_success {
+        let full_report = resources::format(
+            "report.manual_scan",
+            &[("time", &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()), ("report", &aggregated_report)],
+        );
+        let reports_dir = workspace_root.join("reports");
+        let _ = std::fs::create_dir_all(&reports_dir);
+        let report_path = reports_dir.join(format!("manual_report_{}.md", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+        let _ = storage::write_text(&report_path, &full_report);
+        println!("Manual pipeline: report saved.");
+    } else {
+        println!("Manual pipeline: all targets failed, no report saved.");
+    }
+
+    // 发送事件通知前端更新列表
+    events::emit_and_buffer(app_handle, "report-generated", ());
+
+    Ok(())
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+async fn set_workspace_root(app: tauri::AppHandle, root: String) -> Result<(), String> {
+    let state = app.state::<crate::ai::GlobalWorkspaceRoot>();
+    let previous = std::mem::replace(&mut *state.0.lock().map_err(|e| e.to_string())?, root.clone());
+    if previous != root {
+        workspace_lock::release(Path::new(&previous));
+    }
+    if let Err(owner) = workspace_lock::acquire(Path::new(&root)) {
+        log_to_file_with_root(&format!("[WorkspaceLock] 工作区被 pid {} 占用，本实例只读", owner.pid), Some(Path::new(&root)));
+    } else {
+        recover_moves(Path::new(&root));
+    }
+    spiders::selectors::reload(Path::new(&root));
+    spiders::site_options::reload(Path::new(&root));
+    resources::reload(Path::new(&root));
+    let workspace_settings = settings::load(Path::new(&root));
+    storage::set_text_format(workspace_settings.text_files);
+    ai::set_request_options(workspace_settings.ai_requests.clone());
+    limits::apply(&workspace_settings);
+    Ok(())
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+async fn update_ai_config(app: tauri::AppHandle, api_base: String, api_key: String, model: String) -> Result<(), String> {
+    let config = crate::ai::AiConfig { api_base, api_key, model, ..Default::default() };
+    let state = app.state::<crate::ai::GlobalAiConfig>();
+    *state.0.lock().map_err(|e| e.to_string())? = Some(config);
+    println!("AI config updated via frontend settings");
+    Ok(())
+}
+
+/// 手动触发单本小说的多 Agent 评估（任务二 Phase 4 的独立入口）。
+///
+/// 步骤：
+/// 1. 从全局 AI 配置读取 API 凭证
+/// 2. 从 DB 取该 novel 的 title/tags + 所有 chapters.outline_json 拼成 outline_blob
+/// 3. 调用 ai::multi_agent_review 触发三 Agent 并行
+/// 4. 把结果 UPDATE 到 novels.ai_reviews_json
+/// 5. 返回新生成的 JSON 字符串给前端
+///
+/// 失败语义：AI 配置缺失 / 没有 outline_json / 三 Agent 全挂 → Err
+#[cfg(not(test))]
+#[tauri::command]
+async fn evaluate_novel(app: tauri::AppHandle, novel_id: i64) -> Result<String, AppError> {
+    let ai_config = global_ai_config(&app)?;
+
+    let conn = crate::db::get_conn().map_err(|e| AppError::new(ErrorCode::Database, resources::format("message.db_connect_failed", &[("error", &e.to_string())])))?;
+    let (title, tags, outline_blob, chapter_count) = crate::db::load_novel_for_review(&conn, novel_id)
+        .map_err(|e| AppError::new(ErrorCode::Database, resources::format("message.novel_row_not_found", &[("id", &novel_id.to_string()), ("error", &e.to_string())])))?;
+
+    if chapter_count == 0 || outline_blob.trim().is_empty() {
+        return Err(AppError::invalid_input(resources::text("message.outline_missing")));
+    }
+
+    // 按字符截断到 6000 chars
+    let truncated: String = if outline_blob.chars().count() > 6000 {
+        outline_blob.chars().take(6000).collect()
+    } else {
+        outline_blob
+    };
+
+    let reviews_json = crate::ai::multi_agent_review(
+        ai_config,
+        &title,
+        &tags,
+        &truncated,
+        chapter_count,
+    )
+    .await?;
+
+    crate::db::update_ai_reviews(&conn, novel_id, &reviews_json)
+        .map_err(|e| AppError::new(ErrorCode::Database, resources::format("message.save_reviews_failed", &[("error", &e.to_string())])))?;
+
+    Ok(reviews_json)
+}
+
+/// 设置中的全局 AI 配置，未配置时返回 AI_NOT_CONFIGURED
+#[cfg(not(test))]
+fn global_ai_config(app: &tauri::AppHandle) -> Result<ai::AiConfig, AppError> {
+    let state = app.state::<crate::ai::GlobalAiConfig>();
+    let guard = state.0.lock().map_err(|e| resources::format("message.ai_config_lock_failed", &[("error", &e.to_string())]))?;
+    guard.clone().ok_or_else(AppError::ai_not_configured)
+}
+
+/// 分析目标：scratch_id 指定临时文档，否则为 downloads 中的小说。返回 (正文目录, 结果目录使用的书名)，
+/// 临时文档的结果保存在 result/scratch/<id>/
+#[cfg(not(test))]
+fn analysis_target(
+    root: &Path,
+    novel_title: Option<String>,
+    scratch_id: Option<String>,
+) -> Result<(std::path::PathBuf, String), String> {
+    match (scratch_id.filter(|id| !id.trim().is_empty()), novel_title) {
+        (Some(id), _) => Ok((scratch::dir(root, id.trim())?, scratch::result_key(id.trim()))),
+        (None, Some(title)) => Ok((paths::resolve_novel(root, crate::library::DOWNLOADS_DIR, &title)?, title)),
+        (None, None) => Err(resources::text("message.missing_analysis_target")),
+    }
+}
+
+/// 整本书分组分析，后台运行，进度通过 analysis-batch-progress 事件推送，返回批次 ID。
+/// 传 scratch_id 时分析临时文档。
+/// 传 resume_batch_id 时校验参数一致后从第一个未完成的分组续跑；参数不一致时拒绝，
+/// 除非 force_new_batch 为 true（此时按新参数另起批次）。include_context 为 true 时每组正文前附带
+/// 题材信息和上一章细纲（context_sources 可只选其一），该选项也属于批次参数。
+/// normalize 对送入 AI 的正文做繁简 / 标点 / 空行规范化，不改动章节文件。
+/// arc 为 set_novel_arcs 定义的故事弧名，只分析该弧的章节，结果文件名带弧名前缀。
+/// version_policy 为已有结果时的处理：overwrite / keep_versions（另存 `N_v2.md`…）/ skip_if_exists，
+/// 缺省按设置，设置也未指定时模型或提示词变了另存新版本、没变则跳过。
+/// output_language 要求 AI 用指定语言输出（en / ja 等），记入批次参数和分析索引。
+/// background 为 true 时按后台优先级派发，用户当场发起的分析和抓取优先，见 [`priority`]。
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn analyze_novel(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: Option<String>,
+    scratch_id: Option<String>,
+    prompt: Option<String>,
+    template: Option<String>,
+    group_size: Option<usize>,
+    max_chapters: Option<usize>,
+    resume_batch_id: Option<String>,
+    force_new_batch: Option<bool>,
+    include_context: Option<bool>,
+    context_sources: Option<ai_context::ContextSources>,
+    normalize: Option<text_normalize::NormalizeOptions>,
+    arc: Option<String>,
+    version_policy: Option<String>,
+    output_language: Option<String>,
+    ai_overrides: Option<ai_overrides::AiOverrides>,
+    background: Option<bool>,
+) -> Result<String, AppError> {
+    let version_policy = analysis_versions::VersionPolicy::parse(version_policy.as_deref()).map_err(AppError::invalid_input)?;
+    let output_language = prompts::output_language(output_language.as_deref()).map_err(AppError::invalid_input)?;
+    let root = resolve_workspace_root(&app, workspace_root);
+    let (novel_dir, novel_title) = analysis_target(&root, novel_title, scratch_id).map_err(AppError::invalid_input)?;
+    let ai_config = ai_overrides::resolve(
+        &global_ai_config(&app)?,
+        &settings::load(&root),
+        &ai_overrides::AiOverrides::load(&novel_dir),
+        &ai_overrides.unwrap_or_default(),
+    )
+    .map_err(AppError::invalid_input)?
+    .config;
+    let arc = match arc.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        Some(name) => Some(arcs::find(&novel_dir, name).map_err(AppError::invalid_input)?),
+        None => None,
+    };
+
+    let prompt = match prompt.filter(|p| !p.trim().is_empty()) {
+        Some(p) => p,
+        None => {
+            let info = novel_info::read_info(&novel_dir).ok();
+            prompts::resolve(&settings::load(&root), template.as_deref(), info.as_ref())?.content
+        }
+    };
+    let prompt = prompts::with_output_language(&prompt, output_language);
+    let mut chapters = analysis_batch::chapter_files(&novel_dir);
+    if let Some(arc) = &arc {
+        chapters = arc.select_chapters(chapters);
+    }
+    if let Some(max) = max_chapters.filter(|m| *m > 0) {
+        chapters.truncate(max);
+    }
+    let params = analysis_batch::BatchParams {
+        prompt_hash: analysis_batch::prompt_hash(&prompt),
+        model: ai_config.model.clone(),
+        group_size: group_size.unwrap_or(1).max(1),
+        chapters,
+        context: ai_context::sources(include_context, context_sources),
+        normalize: normalize.filter(|n| !n.is_empty()),
+        arc,
+        version_policy,
+        output_language: output_language.map(|l| l.code.to_string()),
+    };
+    let manifest = analysis_batch::prepare(
+        &root,
+        &novel_title,
+        params,
+        resume_batch_id.as_deref(),
+        force_new_batch.unwrap_or(false),
+    )?;
+    let guard = analysis_batch::claim(&novel_title, &manifest.id)?;
+    let batch_id = manifest.id.clone();
+    log_to_file_with_root(&format!("[Batch] {} 批次 {} 开始", novel_title, batch_id), Some(&root));
+
+    let task_priority = if background.unwrap_or(false) { priority::Priority::Background } else { priority::Priority::Interactive };
+    tauri::async_runtime::spawn(priority::scope(task_priority, async move {
+        if let Err(e) = analysis_batch::run(&app, &root, &novel_dir, manifest, ai_config, prompt, guard).await {
+            log_to_file_with_root(&format!("[Batch] {} 中断: {}", novel_title, e), Some(&root));
+        }
+    }));
+    Ok(batch_id)
+}
+
+/// 把一章译成 target_language（en / ja 等），保存为同目录下的 `NN.<语言>.txt`，返回译文文件名、分块数和
+/// token 用量。正文较长时按段落分块翻译（译文与原文篇幅相当，每块只用一半的请求体上限）。
+/// 传 scratch_id 时翻译临时文档的章节；ai_config 缺省使用设置中的 AI 配置。
+#[cfg(not(test))]
+#[tauri::command]
+async fn translate_chapter(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: Option<String>,
+    scratch_id: Option<String>,
+    chapter_index: usize,
+    target_language: String,
+    ai_config: Option<ai::AiConfig>,
+) -> Result<translation::TranslationResult, AppError> {
+    let language = prompts::output_language(Some(&target_language))
+        .map_err(AppError::invalid_input)?
+        .ok_or_else(|| AppError::invalid_input(resources::text("message.missing_target_language")))?;
+    let config = match ai_config {
+        Some(config) => config,
+        None => global_ai_config(&app)?,
+    };
+    let root = resolve_workspace_root(&app, workspace_root);
+    let (novel_dir, novel_title) = analysis_target(&root, novel_title, scratch_id).map_err(AppError::invalid_input)?;
+    let result = translation::translate_chapter(&novel_dir, chapter_index, &config, language).await?;
+    log_to_file_with_root(&format!("[Translate] {} 第 {} 章已译为 {}: {}", novel_title, chapter_index, language.name, result.file), Some(&root));
+    Ok(result)
+}
+
+/// 边下载边分析：每章下载完成即送入 AI 分析，结果写入 result/<小说>/，进度通过
+/// download-analysis-progress 事件推送（已下载 / 已分析 / 分析失败）。返回任务 ID，可用
+/// cancel_download_analysis 取消。dir_name 为 downloads 下已有的小说目录名，缺省按书名新建。
+/// ai_config 缺省使用设置中的 AI 配置。
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn download_and_analyze(
+    app: tauri::AppHandle,
+    url: String,
+    platform: Option<String>,
+    count: Option<usize>,
+    dir_name: Option<String>,
+    ai_config: Option<ai::AiConfig>,
+    prompt: Option<String>,
+    template: Option<String>,
+    workspace_root: Option<String>,
+) -> Result<String, AppError> {
+    let config = match ai_config {
+        Some(c) => c,
+        None => global_ai_config(&app)?,
+    };
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let novel_dir = match dir_name.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(d) => Some(paths::resolve_novel(&root, crate::library::DOWNLOADS_DIR, d).map_err(AppError::invalid_input)?),
+        None => None,
+    };
+    let prompt = match prompt.filter(|p| !p.trim().is_empty()) {
+        Some(p) => p,
+        None => {
+            let info = novel_dir.as_deref().and_then(|d| novel_info::read_info(d).ok());
+            prompts::resolve(&settings::load(&root), template.as_deref(), info.as_ref())?.content
+        }
+    };
+    let req = crate::download::DownloadRequest {
+        platform: platform.unwrap_or_else(|| guess_platform(&url)),
+        url,
+        start_chapter: Some(1),
+        chapter_count: count,
+        novel_dir,
+        skip_extras: true,
+        ..Default::default()
+    };
+
+    let task_id = format!("dla_{}", Local::now().format("%Y%m%d%H%M%S%3f"));
+    let id = task_id.clone();
+    let cancel = tasks::register(&task_id, tasks::TaskKind::DownloadAnalysis, &req.url);
+    tauri::async_runtime::spawn(async move {
+        download_analysis::run(&app, &root, &id, cancel, req, config, prompt).await;
+    });
+    Ok(task_id)
+}
+
+/// 取消边下载边分析任务，任务已结束时返回 false
+#[cfg(not(test))]
+#[tauri::command]
+fn cancel_download_analysis(task_id: String) -> bool {
+    tasks::cancel(&task_id)
+}
+
+/// 批量重新下载多本书中指定的章节（校验 / 修复发现的问题章节）。链接取自各书的 chapters.json，
+/// 没有记录链接的章节直接记为失败；旧文件至少保留一个历史版本。后台运行并立即返回任务 ID，
+/// 汇总进度经 redownload-progress 事件推送，逐书逐章的结果用 get_redownload_job 查询。
+/// options.resume_id 续跑被取消或有失败章节的任务，此时忽略 items。
+#[cfg(not(test))]
+#[tauri::command]
+fn redownload_chapters(
+    app: tauri::AppHandle,
+    items: Vec<crate::redownload::RedownloadItem>,
+    workspace_root: Option<String>,
+    options: Option<crate::redownload::RedownloadOptions>,
+) -> Result<String, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let options = options.unwrap_or_default();
+    let job = crate::redownload::prepare(&root, items, options.resume_id.as_deref()).map_err(AppError::invalid_input)?;
+    let job_id = job.id.clone();
+    let label = job.items.iter().map(|i| i.novel_name.as_str()).collect::<Vec<_>>().join("、");
+    let cancel = tasks::register(&job_id, tasks::TaskKind::Redownload, &label);
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        crate::redownload::run(&app, &LiveSource::new(&app), &root, job, cancel, &options).await;
+        tasks::unregister(&id);
+    });
+    Ok(job_id)
+}
+
+/// 重新下载任务的清单（逐书逐章的状态和失败原因）
+#[cfg(not(test))]
+#[tauri::command]
+fn get_redownload_job(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    job_id: String,
+) -> Result<crate::redownload::RedownloadJob, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    crate::redownload::load(&root, &job_id).map_err(AppError::invalid_input)
+}
+
+/// 取消重新下载任务：当前章节结束后停止，未开始的章节留待续跑。任务已结束时返回 false
+#[cfg(not(test))]
+#[tauri::command]
+fn cancel_redownload(job_id: String) -> bool {
+    tasks::cancel(&job_id)
+}
+
+/// 向 webhook 地址发送一条示例通知（失败重试一次），返回 HTTP 状态码
+#[cfg(not(test))]
+#[tauri::command]
+async fn test_webhook(url: String) -> Result<u16, String> {
+    let body = serde_json::to_value(hooks::sample_notice()).map_err(|e| e.to_string())?;
+    hooks::send_webhook(&url, &body, std::time::Duration::from_secs(10)).await
+}
+
+/// 最近缓冲的进度事件（seq 大于 since_seq），供重新加载的前端补齐错过的事件。kinds 缺省为全部事件。
+#[cfg(not(test))]
+#[tauri::command]
+fn get_recent_events(kinds: Option<Vec<String>>, since_seq: Option<u64>) -> Vec<events::BufferedEvent> {
+    events::recent_events(&kinds.unwrap_or_default(), since_seq.unwrap_or(0))
+}
+
+/// 列出来源章节在分析之后发生变化（重新下载、清洗或手工修改）的分析结果，提示需要重跑
+#[cfg(not(test))]
+#[tauri::command]
+fn check_analysis_freshness(
+    app: tauri::AppHandle,
+    novel_title: String,
+    workspace_root: Option<String>,
+) -> Result<provenance::FreshnessReport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, crate::library::DOWNLOADS_DIR, &novel_title)?;
+    Ok(provenance::check_freshness(&root, &novel_title))
+}
+
+/// 导出书库目录（csv / json）到 `<workspace>/result/library_catalog.<ext>`
+#[cfg(not(test))]
+#[tauri::command]
+fn export_library_catalog(
+    app: tauri::AppHandle,
+    dir_name: Option<String>,
+    workspace_root: Option<String>,
+    format: Option<String>,
+) -> Result<library_export::LibraryExport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let format = library_export::ExportFormat::parse(format.as_deref())?;
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    let (path, rows) = library_export::export(&root, &library_dir, format)?;
+    log_to_file_with_root(&format!("[Export] 书库目录 {} 本 -> {}", rows, path.display()), Some(&root));
+    Ok(library_export::LibraryExport {
+        path: paths::to_relative(&root, &path).unwrap_or_else(|| path.display().to_string()),
+        rows,
+    })
+}
+
+/// 按书名 / 作者 / 简介检索书库。fields 可选 title / author / description，缺省为全部；
+/// 书名另支持拼音首字母。结果中的 path 为相对工作区的小说目录。
+#[cfg(not(test))]
+#[tauri::command]
+fn search_library(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: Option<String>,
+    query: String,
+    fields: Option<Vec<String>>,
+) -> Result<Vec<library_search::SearchHit>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let fields = library_search::SearchField::parse_list(fields.as_deref())?;
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    Ok(library_search::search(&root, &library_dir, &query, &fields))
+}
+
+/// 在书库所有章节的正文中检索（英文不区分大小写），结果按小说和章节序号排序，最多 limit 条（缺省 200）。
+/// 书库建有索引（build_search_index）时只扫描候选章节，索引之后有变化的章节仍逐个扫描
+#[cfg(not(test))]
+#[tauri::command]
+async fn search_chapters(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: Option<String>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<chapter_search::ChapterHit>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    let limit = limit.unwrap_or(chapter_search::DEFAULT_LIMIT);
+    tauri::async_runtime::spawn_blocking(move || chapter_search::search(&root, &library_dir, &query, limit))
+        .await
+        .map_err(|e| resources::format("message.search_task_failed", &[("error", &e.to_string())]))
+}
+
+/// 为书库目录（缺省 downloads）重新建立全文索引，存放在 search_index/ 下；下载结束后自动增量更新
+#[cfg(not(test))]
+#[tauri::command]
+async fn build_search_index(
+    app: tauri::AppHandle,
+    dir_name: Option<String>,
+    workspace_root: Option<String>,
+) -> Result<chapter_search::IndexStatus, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    let (task_root, task_dir) = (root.clone(), library_dir.clone());
+    let status = tauri::async_runtime::spawn_blocking(move || chapter_search::build(&task_root, &task_dir))
+        .await
+        .map_err(|e| resources::format("message.index_task_failed", &[("error", &e.to_string())]))??;
+    log_to_file_with_root(
+        &format!("[Search] {} 已建立索引: {} 章，{} 字节", status.dir_name, status.files, status.size_bytes),
+        Some(&root),
+    );
+    Ok(status)
+}
+
+/// 各书库全文索引的大小和新鲜度（需要逐个扫描的章节数）
+#[cfg(not(test))]
+#[tauri::command]
+fn get_search_index_status(app: tauri::AppHandle, workspace_root: Option<String>) -> Vec<chapter_search::IndexStatus> {
+    chapter_search::status(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 请求 info.json 中的书籍链接（不走爬虫窗口），结果写入 info.json 的 source_status
+#[cfg(not(test))]
+#[tauri::command]
+async fn check_source_url(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<source_check::SourceStatus, String> {
+    let novel_path = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let (_, status) = source_check::check_novel(&reqwest::Client::new(), &novel_path).await?;
+    Ok(status)
+}
+
+/// 书库中来源已下架的书：来源检查返回 404 / 410，或更新时目录页提示作品不存在
+#[cfg(not(test))]
+#[tauri::command]
+fn list_removed_novels(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: Option<String>,
+) -> Result<Vec<source_check::SourceCheckRow>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    Ok(source_check::list_removed(&root, &library_dir))
+}
+
+/// 检查书库中所有未归档书籍的来源链接，返回各书结果和已下架（404 / 410）的书
+#[cfg(not(test))]
+#[tauri::command]
+async fn check_all_sources(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: Option<String>,
+) -> Result<source_check::SourceCheckSummary, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let library_dir = paths::resolve(&root, dir_name.as_deref().unwrap_or(crate::library::DOWNLOADS_DIR))?;
+    let summary = source_check::check_all(&root, &library_dir).await;
+    log_to_file_with_root(
+        &format!("[SourceCheck] 检查 {} 本，已下架 {} 本: {}", summary.checked, summary.removed.len(), summary.removed.join("、")),
+        Some(&root),
+    );
+    Ok(summary)
+}
+
+/// 某榜单已保存的快照日期（升序）。rank_id 也可直接传榜单 URL
+#[cfg(not(test))]
+#[tauri::command]
+fn list_rank_snapshots(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    rank_id: String,
+) -> Result<Vec<String>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    Ok(rank_snapshots::list(&root, &rank_snapshots::resolve_rank_id(&rank_id)?))
+}
+
+#[cfg(not(test))]
+#[derive(serde::Serialize)]
+struct RankDiffResult {
+    diff: rank_snapshots::RankDiff,
+    /// markdown 报告，相对工作区
+    markdown_path: String,
+}
+
+/// 比对同一榜单两天的快照（date_a 为较早的一天），并把 markdown 报告写入 result/
+#[cfg(not(test))]
+#[tauri::command]
+fn diff_rank_snapshots(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    rank_id: String,
+    date_a: String,
+    date_b: String,
+) -> Result<RankDiffResult, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let rank_id = rank_snapshots::resolve_rank_id(&rank_id)?;
+    let (diff, path) = rank_snapshots::diff_and_render(&root, &rank_id, &date_a, &date_b)?;
+    Ok(RankDiffResult { diff, markdown_path: paths::to_relative(&root, &path).unwrap_or_else(|| path.display().to_string()) })
+}
+
+/// 每章各写作目的标签的次数（章节 × 标签矩阵），没有细纲的章节为 null。传 arc 时只统计该故事弧
+#[cfg(not(test))]
+#[tauri::command]
+fn get_purpose_heatmap(
+    app: tauri::AppHandle,
+    novel_title: String,
+    workspace_root: Option<String>,
+    arc: Option<String>,
+) -> Result<purpose_heatmap::PurposeHeatmap, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, analysis_batch::RESULT_DIR, &novel_title)?;
+    let arc = match arc.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        Some(name) => {
+            let novel_dir = crate::library::novel_dir_in(&crate::library::downloads_dir(&root), &novel_title);
+            Some(arcs::find(&novel_dir, name)?)
+        }
+        None => None,
+    };
+    purpose_heatmap::load(&root, &novel_title, &settings::load(&root).purpose_tag_map, arc.as_ref())
+}
+
+/// 同一章多个提示词变体的结果对比，写入 `result/<书名>/<序号>_comparison.md`；找不到的变体列在 missing 中
+#[cfg(not(test))]
+#[tauri::command]
+fn build_prompt_comparison(
+    app: tauri::AppHandle,
+    novel_title: String,
+    chapter_index: usize,
+    variant_names: Vec<String>,
+    workspace_root: Option<String>,
+) -> Result<prompt_comparison::PromptComparison, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, analysis_batch::RESULT_DIR, &novel_title)?;
+    prompt_comparison::build(&root, &novel_title, chapter_index, &variant_names)
+}
+
+/// 取出一章分析结果中的单个细纲节点（node_number 为序号或 "summary"），返回 Markdown 和纯文本；
+/// save 为 true 时另存到 `result/snippets/`。旧结果不是细纲格式时返回整篇并置 fallback
+#[cfg(not(test))]
+#[tauri::command]
+fn extract_analysis_section(
+    app: tauri::AppHandle,
+    novel_title: String,
+    chapter_index: usize,
+    node_number: String,
+    save: Option<bool>,
+    workspace_root: Option<String>,
+) -> Result<analysis_snippets::AnalysisSection, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    paths::resolve_novel(&root, analysis_batch::RESULT_DIR, &novel_title).map_err(AppError::invalid_input)?;
+    let key = analysis_snippets::SectionKey::parse(&node_number).map_err(AppError::invalid_input)?;
+    let save = save.unwrap_or(false);
+    if save {
+        workspace_lock::ensure_writable(&root)?;
+    }
+    Ok(analysis_snippets::extract(&root, &novel_title, chapter_index, key, save)?)
+}
+
+/// 可以在工作区外的路径参数（备份、恢复、导入）：绝对路径原样使用，相对路径按工作区解析
+#[cfg(not(test))]
+fn external_path(root: &std::path::Path, path: &str) -> Result<std::path::PathBuf, String> {
+    let raw = std::path::Path::new(path.trim());
+    if raw.is_absolute() { Ok(raw.to_path_buf()) } else { paths::resolve(root, path) }
+}
+
+/// 导入外部生成的一章分析结果。file_path_or_content 为单行且指向已有文件时读取该文件（绝对路径或相对工作区），
+/// 否则视为内容本身。写入 `result/<书名>/N.md`（已有时另存新版本），以 external 条目记入分析索引
+#[cfg(not(test))]
+#[tauri::command]
+fn import_analysis(
+    app: tauri::AppHandle,
+    novel_title: String,
+    chapter_index: usize,
+    file_path_or_content: String,
+    source_label: Option<String>,
+    workspace_root: Option<String>,
+) -> Result<analysis_import::ImportedAnalysis, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    paths::resolve_novel(&root, crate::library::DOWNLOADS_DIR, &novel_title).map_err(AppError::invalid_input)?;
+    let file = Some(&file_path_or_content)
+        .filter(|raw| !raw.contains('\n'))
+        .and_then(|raw| external_path(&root, raw).ok())
+        .filter(|path| path.is_file());
+    let bytes = match file {
+        Some(path) => fs::read(&path).map_err(|e| AppError::io(&resources::text("message.read_import_failed"), &e))?,
+        None => file_path_or_content.into_bytes(),
+    };
+    let text = analysis_import::validate_text(&bytes).map_err(AppError::invalid_input)?;
+    let label = source_label.filter(|l| !l.trim().is_empty()).unwrap_or_else(|| "external".to_string());
+    Ok(analysis_import::import_text(&root, &novel_title, chapter_index, &text, label.trim())?)
+}
+
+/// 从目录批量导入外部分析结果，按文件名中的数字对应章节；dry_run（缺省 true）时只返回推断的对应关系
+#[cfg(not(test))]
+#[tauri::command]
+fn import_analysis_directory(
+    app: tauri::AppHandle,
+    novel_title: String,
+    dir_path: String,
+    source_label: Option<String>,
+    dry_run: Option<bool>,
+    workspace_root: Option<String>,
+) -> Result<analysis_import::DirectoryImport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let dry_run = dry_run.unwrap_or(true);
+    if !dry_run {
+        workspace_lock::ensure_writable(&root)?;
+    }
+    paths::resolve_novel(&root, crate::library::DOWNLOADS_DIR, &novel_title).map_err(AppError::invalid_input)?;
+    let dir = external_path(&root, &dir_path).map_err(AppError::invalid_input)?;
+    let label = source_label.filter(|l| !l.trim().is_empty()).unwrap_or_else(|| "external".to_string());
+    Ok(analysis_import::import_directory(&root, &novel_title, &dir, label.trim(), dry_run)?)
+}
+
+/// 把工作区的 downloads / result / 设置等打包为 tar.gz，进度通过 backup-progress 事件上报
+#[cfg(not(test))]
+#[tauri::command]
+async fn backup_workspace(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dest_path: String,
+    include: Option<backup::BackupInclude>,
+) -> Result<backup::BackupManifest, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let dest = external_path(&root, &dest_path)?;
+    let include = include.unwrap_or_default();
+    // 按未压缩的源文件大小估算，压缩后只会更小
+    disk_space::ensure(&app, &resources::text("task.backup_workspace"), &dest, backup::source_size(&root, &include))?;
+    let (task_root, task_dest, handle) = (root.clone(), dest.clone(), app.clone());
+    let manifest = tauri::async_runtime::spawn_blocking(move || {
+        backup::backup(&task_root, &task_dest, include, |p| events::emit_and_buffer(&handle, backup::PROGRESS_EVENT, p))
+    })
+    .await
+    .map_err(|e| resources::format("message.backup_task_failed", &[("error", &e.to_string())]))??;
+    let files: usize = manifest.entries.iter().map(|e| e.files).sum();
+    log_to_file_with_root(&format!("[Backup] {} 个文件 -> {}", files, dest.display()), Some(&root));
+    Ok(manifest)
+}
+
+/// 校验备份清单后解压到 dest_root。overwrite_policy 为 skip（缺省）时不覆盖已有文件，作为冲突返回
+#[cfg(not(test))]
+#[tauri::command]
+async fn restore_workspace(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    archive_path: String,
+    dest_root: String,
+    overwrite_policy: Option<String>,
+) -> Result<backup::RestoreReport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let archive = external_path(&root, &archive_path)?;
+    let dest = external_path(&root, &dest_root)?;
+    let policy = backup::OverwritePolicy::parse(overwrite_policy.as_deref())?;
+    let (task_archive, task_dest, handle) = (archive.clone(), dest.clone(), app.clone());
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<backup::RestoreReport, AppError> {
+        let mut on_progress = |p: &backup::BackupProgress| events::emit_and_buffer(&handle, backup::PROGRESS_EVENT, p);
+        let manifest = backup::verify(&task_archive, &mut on_progress)?;
+        // 校验通过后、解压前按清单中的原始大小预检
+        disk_space::ensure(&handle, &resources::text("task.restore_backup"), &task_dest, manifest.total_bytes())?;
+        Ok(backup::restore_verified(&task_archive, &task_dest, policy, manifest, on_progress)?)
+    })
+    .await
+    .map_err(|e| resources::format("message.restore_task_failed", &[("error", &e.to_string())]))??;
+    log_to_file_with_root(
+        &format!(
+            "[Backup] 从 {} 恢复 {} 个文件（覆盖 {}，冲突跳过 {}）-> {}",
+            archive.display(),
+            report.restored,
+            report.overwritten,
+            report.conflicts.len(),
+            dest.display()
+        ),
+        Some(&root),
+    );
+    Ok(report)
+}
+
+/// 保存粘贴的文本为临时文档，之后可用 scratch_id 交给 analyze_novel / export_chapter 等命令
+#[cfg(not(test))]
+#[tauri::command]
+fn create_scratch_document(
+    app: tauri::AppHandle,
+    title: String,
+    content: String,
+    workspace_root: Option<String>,
+) -> Result<scratch::ScratchDocument, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let doc = scratch::create(&root, &title, &content)?;
+    log_to_file_with_root(&format!("[Scratch] 新建临时文档 {}《{}》{} 字", doc.id, doc.title, doc.chars), Some(&root));
+    Ok(doc)
+}
+
+/// 全部临时文档，从新到旧
+#[cfg(not(test))]
+#[tauri::command]
+fn list_scratch_documents(app: tauri::AppHandle, workspace_root: Option<String>) -> Vec<scratch::ScratchDocument> {
+    scratch::list(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 删除临时文档；delete_results 为 true 时一并删除 result/scratch/<id>/
+#[cfg(not(test))]
+#[tauri::command]
+fn delete_scratch_document(
+    app: tauri::AppHandle,
+    id: String,
+    delete_results: Option<bool>,
+    workspace_root: Option<String>,
+) -> Result<(), String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    scratch::delete(&root, &id, delete_results.unwrap_or(false))?;
+    log_to_file_with_root(&format!("[Scratch] 已删除临时文档 {}", id), Some(&root));
+    Ok(())
+}
+
+/// 为章节中的一段摘录添加书签，char_offset 为摘录在章节文件中的字符偏移，供阅读器跳回
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+fn add_bookmark(
+    app: tauri::AppHandle,
+    dir_name: String,
+    novel_name: String,
+    chapter_file: String,
+    char_offset: usize,
+    excerpt: String,
+    note: Option<String>,
+    workspace_root: Option<String>,
+) -> Result<bookmarks::Bookmark, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    bookmarks::add(&root, &dir_name, &novel_name, &chapter_file, char_offset, &excerpt, note.as_deref().unwrap_or_default())
+}
+
+/// 按小说和关键词筛选书签，从新到旧；所属小说已删除的书签标记 orphaned
+#[cfg(not(test))]
+#[tauri::command]
+fn list_bookmarks(
+    app: tauri::AppHandle,
+    scope: Option<bookmarks::BookmarkScope>,
+    workspace_root: Option<String>,
+) -> Result<Vec<bookmarks::Bookmark>, String> {
+    bookmarks::list(&resolve_workspace_root(&app, workspace_root), &scope.unwrap_or_default())
+}
+
+#[cfg(not(test))]
+#[tauri::command]
+fn delete_bookmark(app: tauri::AppHandle, id: String, workspace_root: Option<String>) -> Result<(), String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    bookmarks::delete(&root, &id)
+}
+
+/// 全部书签汇总导出为 exports/bookmarks.md，返回相对工作区的路径
+#[cfg(not(test))]
+#[tauri::command]
+fn export_bookmarks(app: tauri::AppHandle, workspace_root: Option<String>) -> Result<String, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let path = bookmarks::export_digest(&root)?;
+    Ok(paths::to_relative(&root, &path).unwrap_or_else(|| path.display().to_string()))
+}
+
+/// 列出找不到对应小说的 result 分析目录
+#[cfg(not(test))]
+#[tauri::command]
+fn find_orphan_results(app: tauri::AppHandle, workspace_root: Option<String>) -> Vec<result_links::OrphanResult> {
+    result_links::find_orphans(&resolve_workspace_root(&app, workspace_root))
+}
+
+/// 手动移动 / 改名后，把 result 下的分析目录关联到 downloads 中的小说
+#[cfg(not(test))]
+#[tauri::command]
+fn relink_result(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    result_folder: String,
+    novel_name: String,
+) -> Result<result_links::RelinkReport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let report = result_links::relink(&root, &result_folder, &novel_name)?;
+    log_to_file_with_root(
+        &format!("[Result] 分析目录 {} 关联到《{}》: 索引 {} 条, 批次 {} 个", result_folder, novel_name, report.index_entries, report.manifests),
+        Some(&root),
+    );
+    Ok(report)
+}
+
+/// 在样本章节上试跑清洗规则，不写任何文件。rules_override 为尚未保存的规则，
+/// 不传时使用工作区中已保存的规则；sample_chapters 默认 5 章。
+#[cfg(not(test))]
+#[tauri::command]
+fn preview_clean_rules(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    rules_override: Option<Vec<clean_rules::CleanRule>>,
+    sample_chapters: Option<usize>,
+) -> Result<clean_rules::PreviewReport, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let novel_dir = paths::resolve_novel(&root, &dir_name, &novel_name)?;
+    let rules = rules_override.unwrap_or_else(|| clean_rules::load(&root));
+    clean_rules::preview(&novel_dir, &rules, sample_chapters.unwrap_or(clean_rules::DEFAULT_SAMPLE_CHAPTERS))
+}
+
+/// 保存清洗规则；有无效正则时不写入，按规则序号返回错误
+#[cfg(not(test))]
+#[tauri::command]
+fn save_clean_rules(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    rules: Vec<clean_rules::CleanRule>,
+) -> Result<Vec<clean_rules::RuleError>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let errors = clean_rules::save(&root, &rules)?;
+    if errors.is_empty() {
+        log_to_file_with_root(&format!("[CleanRules] 已保存 {} 条清洗规则", rules.len()), Some(&root));
+    }
+    Ok(errors)
+}
+
+/// 某本书的历史分析批次（含可续跑的未完成批次）
+#[cfg(not(test))]
+#[tauri::command]
+fn list_analysis_batches(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    novel_title: Option<String>,
+    scratch_id: Option<String>,
+) -> Result<Vec<analysis_batch::BatchSummary>, String> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let (_, novel_title) = analysis_target(&root, novel_title, scratch_id)?;
+    Ok(analysis_batch::list_batches(&root, &novel_title))
+}
+
+fn resolve_workspace_root(app: &tauri::AppHandle, workspace_root: Option<String>) -> std::path::PathBuf {
+    workspace_root
+        .filter(|r| !r.trim().is_empty())
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| get_workspace_root(app))
+}
+
+/// `<workspace>/<dir_name>/<novel_name>`；dir_name 为相对工作区的路径（如 "downloads"），
+/// 旧前端传入的绝对路径仍兼容，见 [`paths::resolve`]。
+#[cfg(not(test))]
+fn novel_path(
+    app: &tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: &str,
+    novel_name: &str,
+) -> Result<std::path::PathBuf, String> {
+    paths::resolve_novel(&resolve_workspace_root(app, workspace_root), dir_name, novel_name)
+}
+
+/// 小说的正文最少字数：按 info.json 中记录的平台（没有时按链接推断）取设置中的阈值，
+/// 下载、校验和修复共用。平台和链接都没有时用最宽松的 [`library::MIN_CHAPTER_BODY_CHARS`]。
+#[cfg(not(test))]
+fn novel_min_chapter_chars(workspace_root: &Path, novel_dir: &Path) -> usize {
+    let info = novel_info::read_info(novel_dir).unwrap_or_default();
+    let platform = info
+        .get("platform")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .or_else(|| info.get("url").and_then(|v| v.as_str()).map(guess_platform));
+    match platform {
+        Some(platform) => settings::load(workspace_root).min_chapter_chars(&platform),
+        None => crate::library::MIN_CHAPTER_BODY_CHARS,
+    }
+}
+
+pub(crate) fn guess_platform(url: &str) -> String {
+    if url.contains("fanqie") { "fanqie".to_string() } else { "qidian".to_string() }
+}
+
+/// 只获取目录不下载，供前端勾选章节。结果按 URL 缓存（默认 15 分钟），随后的下载、更新直接复用。
+#[cfg(not(test))]
+#[tauri::command]
+async fn fetch_catalog(
+    app: tauri::AppHandle,
+    url: String,
+    platform: Option<String>,
+    debug_spider_visible: Option<bool>,
+    workspace_root: Option<String>,
+) -> Result<crate::download::Catalog, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let platform = platform.unwrap_or_else(|| guess_platform(&url));
+    log_to_file_with_root(&format!("[Catalog] {} ({})", url, platform), Some(&root));
+    let source = LiveSource::new(&app);
+    let debug_visible = debug_spider_visible.unwrap_or(false);
+    crate::download::get_catalog_cached(&app, &source, &root, &url, &platform, debug_visible, || {})
+        .await
+        .map(|(catalog, _)| catalog)
 }
 
-// Default prompt for auto (front) analysis: moved to backend for single source of truth
+/// 丢弃某本书的缓存目录，下次预览、下载或更新时重新抓取。返回是否有缓存被丢弃
+#[cfg(not(test))]
 #[tauri::command]
-fn get_auto_analysis_prompt() -> String {
-    r#"你是一个专业的网文商业分析师。请阅读以上小说开篇内容（前5章），分析并以纯 JSON 格式返回以下信息（不要使用 Markdown 代码块）：
-{
-  "genre": "题材类型 (如：玄幻/系统/都市文)",
-  "style": "整体风格 (如：轻松搞笑/热血/暗黑)",
-  "goldfinger": "金手指设定 (简要概括主角的特殊能力或系统)",
-  "opening": "开篇故事梗概 (100字以内)",
-  "highlights": "核心看点与爽点分析 (50字以内)"
-}"#.to_string()
+fn invalidate_catalog(url: String) -> bool {
+    crate::download::invalidate_catalog(&url)
 }
 
+/// 录制一个页面作为解析器回归夹具（开发用）：抓取、去敏后存入 src-tauri/src/spiders/fixtures/corpus
+/// 并登记到 index.json，返回文件名。快照在下一次 `cargo test` 时生成
+#[cfg(not(test))]
 #[tauri::command]
-fn list_reports(workspace_root: String) -> Result<Vec<String>, String> {
-    let mut files: Vec<String> = Vec::new();
-    
-    // 搜索工作目录下的 reports
-    let ws_reports = Path::new(&workspace_root).join("reports");
-    collect_report_files(&ws_reports, &mut files);
-    
-    // 同时搜索项目根目录下的 reports（可能不同于工作目录）
-    let project_reports = get_project_root().join("reports");
-    if project_reports != ws_reports {
-        collect_report_files(&project_reports, &mut files);
+async fn record_fixture(
+    app: tauri::AppHandle,
+    url: String,
+    kind: spiders::corpus::FixtureKind,
+    platform: Option<String>,
+) -> Result<String, AppError> {
+    let dir = get_project_root().join("src-tauri").join(spiders::corpus::CORPUS_DIR);
+    if !dir.is_dir() {
+        return Err(AppError::invalid_input(resources::format("message.corpus_dir_missing", &[("path", &dir.display().to_string())])));
     }
-    
-    files.sort();
-    files.dedup();
-    files.sort_by(|a, b| b.cmp(a)); // 最新的排前面
-    Ok(files)
+    let platform = platform.unwrap_or_else(|| guess_platform(&url));
+    let file = spiders::corpus::record(&app, &dir, &platform, kind, &url).await?;
+    log_to_file(&format!("[Corpus] recorded {} -> {}", url, file));
+    Ok(file)
 }
 
-fn collect_report_files(dir: &Path, files: &mut Vec<String>) {
-    if !dir.exists() { return; }
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            if let Some(name) = entry.file_name().to_str() {
-                if name.ends_with(".md") {
-                    files.push(name.to_string());
-                }
-            }
-        }
+/// 下载前预览：按与 start_download 相同的参数返回将下载的目录条目（真实目录序号）和越界的勾选序号。
+/// probe_access（缺省 true）时再探测其中一章能否直接读到（可读 / 付费未订阅 / 需登录），结果放在 access 中
+/// 并发出 access-probe 事件，已检测到登录状态时不探测
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn preview_download(
+    app: tauri::AppHandle,
+    url: String,
+    platform: Option<String>,
+    workspace_root: Option<String>,
+    debug_spider_visible: Option<bool>,
+    start_chapter: Option<usize>,
+    chapter_count: Option<usize>,
+    selected_indices: Option<Vec<usize>>,
+    skip_extras: Option<bool>,
+    order: Option<crate::download::DownloadOrder>,
+    probe_access: Option<bool>,
+) -> Result<crate::download::DownloadPreview, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    let platform = platform.unwrap_or_else(|| guess_platform(&url));
+    let source = LiveSource::new(&app);
+    let (catalog, _) =
+        crate::download::get_catalog_cached(&app, &source, &root, &url, &platform, debug_spider_visible.unwrap_or(false), || {})
+            .await?;
+    let selection = crate::download::ChapterSelection {
+        start_chapter,
+        chapter_count,
+        selected: selected_indices.as_deref(),
+        skip_extras: skip_extras.unwrap_or(true),
+        order: order.unwrap_or_default(),
+    };
+    let mut preview = crate::download::DownloadPreview::new(&catalog, &selection);
+    if probe_access.unwrap_or(true) {
+        let planned: Vec<usize> = preview.will_download.iter().map(|c| c.index).collect();
+        let min_chars = settings::load(&root).min_chapter_chars(&platform);
+        let probe = access_probe::probe(&source, &platform, &catalog, &planned, min_chars).await;
+        log_to_file_with_root(&format!("[AccessProbe] {}", probe.summary()), Some(&root));
+        events::emit_and_buffer(&app, access_probe::ACCESS_EVENT, &probe);
+        preview.access = Some(probe);
     }
+    Ok(preview)
 }
 
-#[tauri::command]
-fn read_report(workspace_root: String, filename: String) -> Result<String, String> {
-    // 优先从工作目录读，找不到就从项目根目录读
-    let ws_path = Path::new(&workspace_root).join("reports").join(&filename);
-    if ws_path.exists() {
-        return fs::read_to_string(ws_path).map_err(|e| e.to_string());
+/// 命令参数中的单本时限（秒）：0 为不限，未传时用 default
+#[cfg(not(test))]
+fn time_budget(secs: Option<u64>, default: Option<std::time::Duration>) -> Option<std::time::Duration> {
+    match secs {
+        Some(0) => None,
+        Some(secs) => Some(std::time::Duration::from_secs(secs)),
+        None => default,
     }
-    let project_path = get_project_root().join("reports").join(&filename);
-    fs::read_to_string(project_path).map_err(|e| e.to_string())
 }
 
+/// 后台下载单本书。传 selected_indices 时只下载勾选的目录序号（从 1 开始），
+/// 否则从 start_chapter 起下载 chapter_count 章；order 为 from_end 时从最新一章往前数（连载中的书取最近几章），
+/// 文件仍按真实目录序号命名。force 为 true 时已下载的章节也重新下载。
+/// notify（缺省 true）为 true 时，成功结束后执行设置中的下载完成通知。进度通过 download-progress 事件推送。
+/// min_chapter_chars 覆盖本次下载的正文最少字数，缺省按设置中该平台的阈值；正文不足的章节计为失败、不写入。
+/// time_budget_secs 为本书的时限（缺省不限），超时后在下一章开始前停止并发出 download-timed-out 事件。
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
-async fn trigger_full_scan(app: tauri::AppHandle, target_url: Option<String>, platform: Option<String>) -> Result<(), String> {
-    // 异步执行，不阻塞前端
-    let app_clone = app.clone();
+async fn start_download(
+    app: tauri::AppHandle,
+    url: String,
+    platform: Option<String>,
+    workspace_root: Option<String>,
+    debug_spider_visible: Option<bool>,
+    start_chapter: Option<usize>,
+    chapter_count: Option<usize>,
+    selected_indices: Option<Vec<usize>>,
+    force: Option<bool>,
+    notify: Option<bool>,
+    skip_extras: Option<bool>,
+    min_chapter_chars: Option<usize>,
+    order: Option<crate::download::DownloadOrder>,
+    time_budget_secs: Option<u64>,
+) -> Result<(), AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let req = crate::download::DownloadRequest {
+        platform: platform.unwrap_or_else(|| guess_platform(&url)),
+        url,
+        debug_visible: debug_spider_visible.unwrap_or(false),
+        start_chapter,
+        chapter_count,
+        selected_indices,
+        novel_dir: None,
+        force: force.unwrap_or(false),
+        notify: notify.unwrap_or(true),
+        skip_extras: skip_extras.unwrap_or(true),
+        min_chapter_chars: min_chapter_chars.filter(|&n| n > 0),
+        order: order.unwrap_or_default(),
+        time_budget: time_budget(time_budget_secs, None),
+        ..Default::default()
+    };
     tauri::async_runtime::spawn(async move {
-        let _ = trigger_full_scan_internal(&app_clone, target_url, platform).await;
+        if let Err(e) = crate::download::process_novel_download(&app, &LiveSource::new(&app), &root, req).await {
+            eprintln!("[Download] 任务失败: {}", e);
+        }
     });
     Ok(())
 }
 
-async fn trigger_full_scan_internal(app_handle: &tauri::AppHandle, target_url: Option<String>, platform_opt: Option<String>) -> Result<(), String> {
-    println!("Manual trigger from frontend/tray: scan started");
-    let project_root = get_project_root();
-    let config_path = project_root.join("workflow_config.json");
-
-    let content = std::fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
-    let config = serde_json::from_str::<serde_json::Value>(&content).map_err(|e| e.to_string())?;
-
-    // 从 Tauri State 读取工作目录（与前端选择一致）
-    let workspace_root = get_workspace_root(app_handle);
-    let mut aggregated_report = String::new();
-    let mut any_success = false;
+/// 作者页上的全部作品（标题 / 链接 / 连载状态），作品列表分页时自动翻页
+#[cfg(not(test))]
+#[tauri::command]
+async fn fetch_author_works(
+    app: tauri::AppHandle,
+    author_page_url: String,
+    platform: Option<String>,
+    debug_spider_visible: Option<bool>,
+) -> Result<spiders::AuthorWorks, AppError> {
+    let platform = platform.unwrap_or_else(|| guess_platform(&author_page_url));
+    let source = LiveSource::new(&app);
+    Ok(spiders::NovelSource::fetch_author_works(&source, &platform, &author_page_url, debug_spider_visible.unwrap_or(false)).await?)
+}
 
-    if let Some(target) = target_url {
-        let platform = platform_opt.unwrap_or_else(|| {
-            if target.contains("fanqie") { "fanqie".to_string() } else { "qidian".to_string() }
-        });
-        // URL 含 /book/ 或 /info/ 视为单本，否则按榜单处理
-        let mode = if target.contains("/book/") || target.contains("/info/") {
-            crate::analysis_engine::PipelineMode::Single
-        } else {
-            crate::analysis_engine::PipelineMode::Rank
-        };
-        println!("Manual: Triggering analysis ({:?}) for target {} on platform {}", mode, target, platform);
-        match crate::analysis_engine::run_full_analysis_pipeline(
-            app_handle, &target, &platform, &workspace_root, mode
-        ).await {
-            Ok(partial) => {
-                any_success = true;
-                aggregated_report.push_str(&partial);
-                aggregated_report.push_str("\n\n---\n\n");
-            }
-            Err(e) => eprintln!("Manual: Pipeline failed for {}: {}", target, e),
-        }
-    } else {
-        if let Some(rank_urls) = config["rank_urls"].as_array() {
-            println!("Manual: Found {} rank URLs to process.", rank_urls.len());
-            for rank_url_val in rank_urls {
-                if let Some(rank_url) = rank_url_val.as_str() {
-                    let platform = if rank_url.contains("fanqie") { "fanqie" } else { "qidian" };
-                    println!("Manual: Triggering analysis for {}", rank_url);
-                    match crate::analysis_engine::run_full_analysis_pipeline(
-                        app_handle, rank_url, platform, &workspace_root,
-                        crate::analysis_engine::PipelineMode::Rank,
-                    ).await {
-                        Ok(partial) => {
-                            any_success = true;
-                            println!("Manual: Done for {}", rank_url);
-                            aggregated_report.push_str(&partial);
-                            aggregated_report.push_str("\n\n---\n\n");
-                        }
-                        Err(e) => {
-                            eprintln!("Manual: Pipeline failed for {}: {}", rank_url, e);
-                        }
-                    }
-                }
-            }
-        } else {
-            eprintln!("Manual: 'rank_urls' not found or not an array in config.");
-            return Err("No rank URLs found in config".to_string());
+/// 下载某作者的全部作品，每本取前 count_per_novel 章正文（缺省 3）。返回作者页上的作品列表后在后台下载：
+/// 书库（dir_name，缺省 downloads）中已有的书跳过，单本失败不影响其余作品，作者名写入各书的 info.json。
+/// 每本书的进度同 start_download，全部结束后发出 author-download-completed 事件（逐本结果）。
+/// time_budget_secs 为每本书的时限（缺省 30 分钟，0 为不限），超时的书记为 timed_out 后继续下一本。
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+async fn download_author_works(
+    app: tauri::AppHandle,
+    author_page_url: String,
+    platform: Option<String>,
+    count_per_novel: Option<usize>,
+    dir_name: Option<String>,
+    workspace_root: Option<String>,
+    debug_spider_visible: Option<bool>,
+    min_chapter_chars: Option<usize>,
+    time_budget_secs: Option<u64>,
+) -> Result<spiders::AuthorWorks, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root);
+    workspace_lock::ensure_writable(&root)?;
+    let req = author_works::AuthorDownloadRequest {
+        platform: platform.unwrap_or_else(|| guess_platform(&author_page_url)),
+        author_page_url,
+        count_per_novel: count_per_novel.filter(|&n| n > 0).unwrap_or(crate::download::DEFAULT_CHAPTER_COUNT),
+        dir_name,
+        debug_visible: debug_spider_visible.unwrap_or(false),
+        min_chapter_chars: min_chapter_chars.filter(|&n| n > 0),
+        time_budget: time_budget(time_budget_secs, Some(crate::download::DEFAULT_TIME_BUDGET)),
+    };
+    let works = author_works::fetch_works(&LiveSource::new(&app), &req).await?;
+    let listed = works.clone();
+    tauri::async_runtime::spawn(async move {
+        match author_works::download_works(&app, &LiveSource::new(&app), &root, &req, works).await {
+            Ok(summary) => events::emit_and_buffer(&app, author_works::COMPLETED_EVENT, summary),
+            Err(e) => eprintln!("[Author] 任务失败: {}", e),
         }
-    }
-    
-    if any_success {
-        let full_report = format!("# 手动全量扫榜深度报告 ({})\n\n{}",
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-            aggregated_report);
-        let reports_dir = workspace_root.join("reports");
-        let _ = std::fs::create_dir_all(&reports_dir);
-        let report_path = reports_dir.join(format!("manual_report_{}.md", chrono::Local::now().format("%Y%m%d_%H%M%S")));
-        let _ = std::fs::write(report_path, full_report);
-        println!("Manual pipeline: report saved.");
-    } else {
-        println!("Manual pipeline: all targets failed, no report saved.");
-    }
-
-    // 发送事件通知前端更新列表
-    let _ = app_handle.emit("report-generated", ());
-
-    Ok(())
+    });
+    Ok(listed)
 }
 
+/// 启动本地集成接口（只监听 127.0.0.1，需 Bearer token），端口被占用时报错
+#[cfg(not(test))]
 #[tauri::command]
-async fn set_workspace_root(app: tauri::AppHandle, root: String) -> Result<(), String> {
-    let state = app.state::<crate::ai::GlobalWorkspaceRoot>();
-    *state.0.lock().map_err(|e| e.to_string())? = root;
-    Ok(())
+async fn start_local_api(app: tauri::AppHandle, port: u16, token: String) -> Result<local_api::LocalApiStatus, String> {
+    local_api::start(app, port, token).await
 }
 
+/// 停止本地集成接口，返回之前是否在运行
+#[cfg(not(test))]
 #[tauri::command]
-async fn update_ai_config(app: tauri::AppHandle, api_base: String, api_key: String, model: String) -> Result<(), String> {
-    let config = crate::ai::AiConfig { api_base, api_key, model };
-    let state = app.state::<crate::ai::GlobalAiConfig>();
-    *state.0.lock().map_err(|e| e.to_string())? = Some(config);
-    println!("AI config updated via frontend settings");
-    Ok(())
+fn stop_local_api() -> bool {
+    local_api::stop()
 }
 
-/// 手动触发单本小说的多 Agent 评估（任务二 Phase 4 的独立入口）。
-///
-/// 步骤：
-/// 1. 从全局 AI 配置读取 API 凭证
-/// 2. 从 DB 取该 novel 的 title/tags + 所有 chapters.outline_json 拼成 outline_blob
-/// 3. 调用 ai::multi_agent_review 触发三 Agent 并行
-/// 4. 把结果 UPDATE 到 novels.ai_reviews_json
-/// 5. 返回新生成的 JSON 字符串给前端
-///
-/// 失败语义：AI 配置缺失 / 没有 outline_json / 三 Agent 全挂 → Err
+#[cfg(not(test))]
 #[tauri::command]
-async fn evaluate_novel(app: tauri::AppHandle, novel_id: i64) -> Result<String, String> {
-    let ai_config = {
-        let state = app.state::<crate::ai::GlobalAiConfig>();
-        let guard = state.0.lock().map_err(|e| format!("获取 AI 配置失败: {}", e))?;
-        guard.clone().ok_or("AI 配置未设置，请在设置中配置 API Key")?
-    };
-
-    let conn = crate::db::get_conn().map_err(|e| format!("DB 连接失败: {}", e))?;
-    let (title, tags, outline_blob, chapter_count) =
-        crate::db::load_novel_for_review(&conn, novel_id)
-            .map_err(|e| format!("未找到 novel_id={} 或读取失败: {}", novel_id, e))?;
+fn get_local_api_status() -> local_api::LocalApiStatus {
+    local_api::status()
+}
 
-    if chapter_count == 0 || outline_blob.trim().is_empty() {
-        return Err("该书没有 outline_json，请先跑流水线 Phase 3".to_string());
+/// 用 info.json 中的下载统计和来源检查结果补充书库行。word_count 优先取实际下载字数，其次是站点字数的解析值。
+/// 只读被识别为小说的目录
+fn fill_download_stats(row: &mut crate::db::NovelListRow, novel_dir: &Path) {
+    if !crate::library::is_novel_dir(novel_dir) {
+        return;
     }
-
-    // 按字符截断到 6000 chars
-    let truncated: String = if outline_blob.chars().count() > 6000 {
-        outline_blob.chars().take(6000).collect()
-    } else {
-        outline_blob
+    let Ok(info) = novel_info::read_info(novel_dir) else {
+        return;
     };
+    row.downloaded_chars = info.get("downloaded_chars").and_then(|v| v.as_u64());
+    row.downloaded_chapters = info.get("downloaded_chapters").and_then(|v| v.as_u64()).map(|n| n as usize);
+    row.reported_chars = info.get("reported_chars").and_then(|v| v.as_u64());
+    row.archived = novel_info::is_archived(novel_dir);
+    row.source_status = source_check::from_info(&info);
+    if let Some(chars) = row.downloaded_chars.filter(|&c| c > 0).or(row.reported_chars) {
+        row.word_count = Some(chars as i64);
+    }
+}
 
-    let reviews_json = crate::ai::multi_agent_review(
-        ai_config,
-        &title,
-        &tags,
-        &truncated,
-        chapter_count,
-    )
-    .await?;
-
-    crate::db::update_ai_reviews(&conn, novel_id, &reviews_json)
-        .map_err(|e| format!("写入 ai_reviews_json 失败: {}", e))?;
-
-    Ok(reviews_json)
+#[derive(serde::Serialize)]
+struct NovelListResponse {
+    novels: Vec<crate::db::NovelListRow>,
+    /// downloads 下不符合小说目录规则的条目，前端放在"未识别项目"分组
+    unrecognized: Vec<crate::library::UnrecognizedItem>,
 }
 
 /// library Tab 卡片列表查询（任务四a）：返回 novels + parsed ai_reviews + latest_rank + scan_count。
-/// filter 字段全部可选，传 null/缺省时返回全部书。
+/// filter 字段全部可选，传 null/缺省时返回全部未归档的书；include_archived 为 true 时包含已归档的书。
 #[tauri::command]
-fn list_novels(filter: Option<crate::db::NovelListFilter>) -> Result<Vec<crate::db::NovelListRow>, String> {
-    let conn = crate::db::get_conn().map_err(|e| format!("DB 连接失败: {}", e))?;
+fn list_novels(
+    app: tauri::AppHandle,
+    filter: Option<crate::db::NovelListFilter>,
+    include_archived: Option<bool>,
+) -> Result<NovelListResponse, String> {
+    let conn = crate::db::get_conn().map_err(|e| resources::format("message.db_connect_failed", &[("error", &e.to_string())]))?;
     let f = filter.unwrap_or_default();
-    crate::db::list_novels(&conn, &f).map_err(|e| format!("查询书库失败: {}", e))
+    let mut novels = crate::db::list_novels(&conn, &f).map_err(|e| resources::format("message.list_novels_failed", &[("error", &e.to_string())]))?;
+    let downloads_dir = crate::library::downloads_dir(&get_workspace_root(&app));
+    // 书名对应的位置被非小说条目占着时，这一行归到"未识别项目"，不参与统计和标签筛选
+    novels.retain(|row| !crate::library::is_unrecognized(&crate::library::novel_dir_in(&downloads_dir, &row.title)));
+    for row in novels.iter_mut() {
+        fill_download_stats(row, &crate::library::novel_dir_in(&downloads_dir, &row.title));
+    }
+    if !include_archived.unwrap_or(false) {
+        novels.retain(|row| !row.archived);
+    }
+    if matches!(f.sort_by, crate::db::NovelSortBy::WordCountDesc) {
+        novels.sort_by_key(|row| std::cmp::Reverse(row.word_count.unwrap_or(0)));
+    }
+    let unrecognized = crate::library::scan_library(&downloads_dir).unrecognized;
+    Ok(NovelListResponse { novels, unrecognized })
 }
 
 #[cfg(not(test))]
@@ -378,11 +2795,24 @@ pub fn run() {
             app.manage(ai::GlobalWorkspaceRoot(Mutex::new(
                 get_project_root().to_string_lossy().to_string()
             )));
+            spiders::selectors::reload(&get_project_root());
+            spiders::site_options::reload(&get_project_root());
+            resources::reload(&get_project_root());
+            let startup_settings = settings::load(&get_project_root());
+            network_mode::set_mode(startup_settings.network_mode);
+            storage::set_text_format(startup_settings.text_files);
+            ai::set_request_options(startup_settings.ai_requests.clone());
+            limits::apply(&startup_settings);
+            if let Err(owner) = workspace_lock::acquire(&get_project_root()) {
+                log_to_file(&format!("[WorkspaceLock] 工作区被 pid {}（启动于 {}）占用，本实例只读", owner.pid, owner.started_at));
+            } else {
+                recover_moves(&get_project_root());
+            }
 
             // 1. 创建托盘菜单
-            let quit_i = MenuItem::with_id(app, "quit", "退出应用", true, None::<&str>)?;
-            let show_i = MenuItem::with_id(app, "show", "显示主界面", true, None::<&str>)?;
-            let run_i = MenuItem::with_id(app, "run_now", "立即全量扫榜", true, None::<&str>)?;
+            let quit_i = MenuItem::with_id(app, "quit", resources::text("tray.quit"), true, None::<&str>)?;
+            let show_i = MenuItem::with_id(app, "show", resources::text("tray.show"), true, None::<&str>)?;
+            let run_i = MenuItem::with_id(app, "run_now", resources::text("tray.run_now"), true, None::<&str>)?;
             
             let tray_menu = Menu::with_items(app, &[
                 &show_i,
@@ -397,6 +2827,7 @@ pub fn run() {
                 .on_menu_event(|app, event| {
                     match event.id.as_ref() {
                         "quit" => {
+                            workspace_lock::release_all();
                             std::process::exit(0);
                         }
                         "show" => {
@@ -430,15 +2861,121 @@ pub fn run() {
             clear_log,
             export_chapter,
             update_novel_metadata,
+            rebuild_metadata_offline,
             get_auto_analysis_prompt,
             ensure_workspace_dirs,
+            bootstrap_workspace,
+            set_resource_profile,
+            recover_incomplete_operations,
+            get_backend_capabilities,
+            is_workspace_initialized,
             list_reports,
             read_report,
             trigger_full_scan,
             update_ai_config,
             set_workspace_root,
             evaluate_novel,
-            list_novels
+            analyze_novel,
+            translate_chapter,
+            list_analysis_batches,
+            download_and_analyze,
+            cancel_download_analysis,
+            redownload_chapters,
+            get_redownload_job,
+            cancel_redownload,
+            get_recent_events,
+            validate_catalog,
+            retitle_chapters,
+            shard_novel,
+            get_novel_chapters_view,
+            delete_chapter,
+            verify_novel,
+            check_analysis_freshness,
+            test_webhook,
+            set_novel_normalization,
+            normalize_line_endings,
+            abort_all_tasks,
+            export_library_catalog,
+            search_library,
+            search_chapters,
+            build_search_index,
+            get_search_index_status,
+            check_source_url,
+            check_all_sources,
+            list_removed_novels,
+            list_rank_snapshots,
+            diff_rank_snapshots,
+            save_rank_bookmark,
+            list_rank_bookmarks,
+            delete_rank_bookmark,
+            rescan_bookmark,
+            get_purpose_heatmap,
+            build_prompt_comparison,
+            extract_analysis_section,
+            import_analysis,
+            import_analysis_directory,
+            list_active_tasks,
+            get_ai_queue_status,
+            get_ai_retry_queue,
+            process_ai_retry_queue,
+            generate_activity_digest,
+            set_network_mode,
+            get_network_status,
+            export_novel,
+            export_analysis_report,
+            cancel_export,
+            backup_workspace,
+            restore_workspace,
+            create_scratch_document,
+            list_scratch_documents,
+            delete_scratch_document,
+            add_bookmark,
+            list_bookmarks,
+            delete_bookmark,
+            export_bookmarks,
+            archive_novel,
+            find_orphan_results,
+            relink_result,
+            preview_clean_rules,
+            save_clean_rules,
+            list_novels,
+            start_local_api,
+            stop_local_api,
+            get_local_api_status,
+            fetch_catalog,
+            preview_download,
+            invalidate_catalog,
+            record_fixture,
+            start_download,
+            fetch_author_works,
+            download_author_works,
+            get_novel_info,
+            get_user_metadata,
+            set_user_metadata,
+            set_novel_arcs,
+            get_novel_arcs,
+            repair_novel,
+            update_novel,
+            prefetch_next_chapters,
+            get_effective_prompt,
+            get_effective_ai_config,
+            list_prompt_templates,
+            get_settings,
+            update_settings,
+            get_spider_metrics,
+            normalize_library_paths,
+            run_diagnostics,
+            get_workspace_lock_status,
+            reset_circuit,
+            list_chapter_versions,
+            diff_chapter_versions,
+            list_edits,
+            undo_edit,
+            list_analysis_versions,
+            diff_analysis_versions,
+            get_active_selectors,
+            set_selector_override,
+            test_selector
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -478,6 +3015,15 @@ pub fn get_workspace_root(app: &tauri::AppHandle) -> std::path::PathBuf {
 
 // Helper for file logging (shared across modules)
 // Now accepts optional workspace_root parameter
+/// 未指定工作区时的日志文件。测试写到临时目录，不往源码树里追加
+fn fallback_log_path() -> std::path::PathBuf {
+    if cfg!(test) {
+        std::env::temp_dir().join(format!("fanqie_app_test_{}", std::process::id())).join("app.log")
+    } else {
+        get_project_root().join("app.log")
+    }
+}
+
 pub(crate) fn log_to_file(msg: &str) {
     log_to_file_with_root(msg, None);
 }
@@ -485,7 +3031,7 @@ pub(crate) fn log_to_file(msg: &str) {
 pub(crate) fn log_to_file_with_root(msg: &str, workspace_root: Option<&Path>) {
     let log_path = match workspace_root {
         Some(root) => root.join("logs").join("app.log"),
-        None => get_project_root().join("app.log"), // Fallback for backward compatibility
+        None => fallback_log_path(), // Fallback for backward compatibility
     };
 
     // Ensure logs directory exists
@@ -494,92 +3040,288 @@ pub(crate) fn log_to_file_with_root(msg: &str, workspace_root: Option<&Path>) {
     }
 
     let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-    let log_msg = format!("[{}] {}\n", timestamp, msg);
+    let log_msg = format!("[{}] {}\n", timestamp, escape_log_text(msg));
+
+    let _ = storage::append(&log_path, log_msg.as_bytes());
+}
 
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(log_path) {
-        let _ = file.write_all(log_msg.as_bytes());
+/// 转义控制字符和不可见的格式字符，保证一条日志只占一行、显示顺序与实际内容一致
+/// （标题等外部文本可能带换行、终端控制符或 U+202E 之类的双向覆盖符）。
+pub(crate) fn escape_log_text(msg: &str) -> String {
+    let mut out = String::with_capacity(msg.len());
+    for c in msg.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() || is_format_char(c) => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
     }
+    out
+}
+
+/// Unicode 格式字符（Cf）中常见的几类：软连字符、零宽字符、双向控制符和 BOM
+fn is_format_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{61c}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+    )
 }
 
 // New command: Ensure workspace directories exist
+#[cfg(not(test))]
 #[tauri::command]
 fn ensure_workspace_dirs(workspace_root: String) -> Result<String, String> {
     let root = Path::new(&workspace_root);
 
     // Create downloads subdirectory
-    let downloads_dir = root.join("downloads");
+    let downloads_dir = crate::library::downloads_dir(root);
     if !downloads_dir.exists() {
-        fs::create_dir_all(&downloads_dir).map_err(|e| format!("创建 downloads 目录失败: {}", e))?;
+        fs::create_dir_all(&downloads_dir).map_err(|e| resources::format("message.create_workspace_dir_failed", &[("dir", "downloads"), ("error", &e.to_string())]))?;
     }
 
     // Create logs subdirectory
     let logs_dir = root.join("logs");
     if !logs_dir.exists() {
-        fs::create_dir_all(&logs_dir).map_err(|e| format!("创建 logs 目录失败: {}", e))?;
+        fs::create_dir_all(&logs_dir).map_err(|e| resources::format("message.create_workspace_dir_failed", &[("dir", "logs"), ("error", &e.to_string())]))?;
     }
 
     Ok("Workspace directories created".to_string())
 }
 
+/// 生成示例工作区（目录、默认设置、初始清洗规则、选择器文件、提示词副本和一本示例小说），
+/// 已存在的文件一律跳过，返回创建和跳过的路径
+#[cfg(not(test))]
+#[tauri::command]
+fn bootstrap_workspace(workspace_root: String) -> Result<bootstrap::BootstrapReport, AppError> {
+    let root = Path::new(&workspace_root);
+    workspace_lock::ensure_writable(root)?;
+    let report = bootstrap::bootstrap(root)?;
+    log_to_file_with_root(
+        &format!("[Bootstrap] 示例工作区: 创建 {} 项，跳过 {} 项", report.created.len(), report.skipped.len()),
+        Some(root),
+    );
+    Ok(report)
+}
+
+/// 工作区是否已初始化（有 settings.json 或书库中已有小说），首次打开时前端据此提示生成示例工作区
+#[cfg(not(test))]
+#[tauri::command]
+fn is_workspace_initialized(workspace_root: String) -> bool {
+    bootstrap::is_initialized(Path::new(&workspace_root))
+}
+
+#[cfg(not(test))]
 #[tauri::command]
 fn read_log_file(workspace_root: Option<String>) -> Result<String, String> {
     let log_path = match workspace_root {
         Some(root) => Path::new(&root).join("logs").join("app.log"),
-        None => get_project_root().join("app.log"),
+        None => fallback_log_path(),
     };
 
     if log_path.exists() {
          // Read last 100kb to avoid huge files? For now just read all.
          fs::read_to_string(log_path).map_err(|e| e.to_string())
     } else {
-        Ok("暂无日志".to_string())
+        Ok(resources::text("message.log_empty"))
     }
 }
 
+#[cfg(not(test))]
 #[tauri::command]
 fn clear_log(workspace_root: Option<String>) -> Result<String, String> {
     println!("Backend: clear_log called");
     let log_path = match workspace_root {
-        Some(root) => Path::new(&root).join("logs").join("app.log"),
-        None => get_project_root().join("app.log"),
+        Some(root) => {
+            workspace_lock::ensure_writable(Path::new(&root))?;
+            Path::new(&root).join("logs").join("app.log")
+        }
+        None => fallback_log_path(),
     };
     // Write empty string to clear the log file
     fs::write(log_path, "").map_err(|e| e.to_string())?;
-    Ok("日志已清空".to_string())
+    Ok(resources::text("message.log_cleared"))
 }
 
+/// 保存一章的分析结果到 result/<小说>/<序号>.md。model / prompt 为生成该结果的模型和提示词，
+/// 记入 front matter；version_policy 同 analyze_novel，跳过时返回已有结果的路径。
+#[cfg(not(test))]
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
-fn export_chapter(novel_title: String, chapter_index: i32, content: String, workspace_root: Option<String>) -> Result<String, String> {
-    println!("Backend: export_chapter called for {}", novel_title);
+fn export_chapter(
+    novel_title: Option<String>,
+    chapter_index: i32,
+    content: String,
+    workspace_root: Option<String>,
+    scratch_id: Option<String>,
+    version_policy: Option<String>,
+    model: Option<String>,
+    prompt: Option<String>,
+    output_language: Option<String>, // 分析时要求的输出语言代码，记入 front matter 和分析索引
+) -> Result<String, AppError> {
+    let version_policy = analysis_versions::VersionPolicy::parse(version_policy.as_deref()).map_err(AppError::invalid_input)?;
+    let output_language = prompts::output_language(output_language.as_deref()).map_err(AppError::invalid_input)?.map(|l| l.code);
     // Create result directory structure: <project_root>/result/<novel_title>/ or <workspace_root>/result/<novel_title>/
-    let result_dir = if let Some(root) = &workspace_root {
-        Path::new(root).join("result").join(&novel_title)
-    } else {
-        get_project_root().join("result").join(&novel_title)
+    let root = workspace_root.as_ref().map(std::path::PathBuf::from).unwrap_or_else(get_project_root);
+    // 临时文档导出到 result/scratch/<id>/，来源章节为文档目录下的章节文件
+    let (source_dir, novel_title) = match scratch_id.filter(|id| !id.trim().is_empty()) {
+        Some(id) => analysis_target(&root, None, Some(id)).map_err(AppError::invalid_input)?,
+        None => {
+            let title = novel_title.ok_or_else(|| AppError::invalid_input(resources::text("message.missing_analysis_target")))?;
+            (crate::library::downloads_dir(&root).join(&title), title)
+        }
     };
+    println!("Backend: export_chapter called for {}", novel_title);
+    let result_dir = root.join("result").join(&novel_title);
     
     if !result_dir.exists() {
-        fs::create_dir_all(&result_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+        fs::create_dir_all(&result_dir).map_err(|e| AppError::io(&resources::text("message.create_dir_failed"), &e))?;
     }
     
-    // Filename: <chapter_index>.md
+    // Filename: <chapter_index>.md，按版本策略可能另存为 <chapter_index>_vN.md
     let filename = format!("{}.md", chapter_index);
-    let file_path = result_dir.join(&filename);
-    
+    let prompt_hash = prompt.as_deref().map(analysis_batch::prompt_hash);
+    let file_path = match analysis_versions::plan_write(
+        &root,
+        &result_dir.join(&filename),
+        version_policy,
+        model.as_deref(),
+        prompt_hash.as_deref(),
+    ) {
+        analysis_versions::WriteTarget::Write(path) => path,
+        analysis_versions::WriteTarget::Skip(existing) => {
+            return Ok(paths::to_relative(&root, &existing).unwrap_or_else(|| existing.to_string_lossy().to_string()));
+        }
+    };
+
+    // 记录来源章节的内容哈希（章节文件不存在时不记录）
+    let chapter_file = sharding::relative_chapter_path(&source_dir, chapter_index.max(0) as usize);
+    let source = fs::read(source_dir.join(&chapter_file))
+        .ok()
+        .map(|bytes| provenance::SourceChapter::of(&chapter_file, &bytes));
+    ai::save_raw_output(&root, &file_path, &content);
+    let content = ai::sanitize_markdown(&content);
+    let content = match &source {
+        Some(source) => {
+            let record = provenance::Provenance::new(&novel_title, None, vec![source.clone()])
+                .generated_by(model.as_deref(), prompt_hash.as_deref())
+                .in_language(output_language);
+            provenance::with_front_matter(&record, &content)
+        }
+        None => content,
+    };
+
     // Write content to file
-    fs::write(&file_path, content).map_err(|e| format!("写入文件失败: {}", e))?;
+    storage::write_text(&file_path, &content).map_err(|e| AppError::io(&resources::text("message.write_file_failed"), &e))?;
     
-    let path_str = file_path.to_string_lossy().to_string();
-    let workspace_path = workspace_root.as_ref().map(|r| Path::new(r));
+    // 工作区内返回相对路径（result/<小说>/<序号>.md）
+    let workspace_path = workspace_root.as_ref().map(Path::new);
+    let path_str = workspace_path
+        .and_then(|root| paths::to_relative(root, &file_path))
+        .unwrap_or_else(|| file_path.to_string_lossy().to_string());
     log_to_file_with_root(&format!("已导出章节到: {}", path_str), workspace_path);
+    if let Some(source) = source {
+        let output = provenance::AnalysisOutput {
+            output_file: paths::to_relative(&root, &file_path).unwrap_or_else(|| path_str.clone()),
+            sources: vec![source],
+            output_language: output_language.map(str::to_string),
+        };
+        if let Err(e) = provenance::record_export(&root, &novel_title, output) {
+            log_to_file_with_root(&format!("[Export] 写入分析索引失败: {}", e), Some(&root));
+        }
+    }
     
     Ok(path_str)
 }
 
+/// dir 为相对工作区的目录（如 "downloads"），filename 为其下的相对路径。
+/// normalize 只作用于 .txt 章节的正文，返回规范化后的文本，文件不变。
+#[cfg(not(test))]
+#[tauri::command]
+fn get_file_content(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir: String,
+    filename: String,
+    normalize: Option<text_normalize::NormalizeOptions>,
+) -> Result<String, AppError> {
+    let base = paths::resolve(&resolve_workspace_root(&app, workspace_root), &dir).map_err(AppError::invalid_input)?;
+    let path = paths::resolve(&base, &filename).map_err(AppError::invalid_input)?;
+    read_file_content(&path, &filename, normalize)
+}
+
+/// 读取已解析好的文件，`.txt` 按 normalize 规范化；本地接口在自行校验路径后也走这里
+pub(crate) fn read_file_content(
+    path: &Path,
+    filename: &str,
+    normalize: Option<text_normalize::NormalizeOptions>,
+) -> Result<String, AppError> {
+    let content = fs::read_to_string(path).map_err(|e| AppError::io(&resources::format("message.read_file_failed", &[("file", filename)]), &e))?;
+    match normalize {
+        Some(options) if path.extension().is_some_and(|e| e == "txt") => Ok(text_normalize::normalize_chapter(&content, options)),
+        _ => Ok(content),
+    }
+}
+
+/// 设置小说的正文规范化选项（写入 info.json，之后下载的章节按此保存）。
+/// apply_existing 为 true 时同时改写已下载的章节文件，返回改动的文件数。
+#[cfg(not(test))]
+#[tauri::command]
+async fn set_novel_normalization(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+    options: Option<text_normalize::NormalizeOptions>,
+    apply_existing: Option<bool>,
+) -> Result<usize, String> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    workspace_lock::ensure_writable(&root)?;
+    let novel_dir = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let options = options.filter(|o| !o.is_empty());
+    novel_info::update_info(&novel_dir, false, |info| match options {
+        Some(o) => {
+            info.insert(text_normalize::INFO_KEY.to_string(), serde_json::to_value(o).unwrap_or_default());
+        }
+        None => {
+            info.remove(text_normalize::INFO_KEY);
+        }
+    })
+    .await?;
+    match options {
+        Some(o) if apply_existing.unwrap_or(false) => text_normalize::rewrite_chapters(&novel_dir, o, settings::load(&root).edit_history_max_bytes()),
+        _ => Ok(0),
+    }
+}
+
+/// 按设置中的换行 / BOM 格式改写一本书已下载的章节和 `result/<书名>/` 下的分析结果，
+/// 修复早先写入的混合换行。章节的旧内容记入修改历史，可用 undo_edit 撤销
+#[cfg(not(test))]
 #[tauri::command]
-fn get_file_content(dir: String, filename: String) -> Result<String, String> {
-    let path = Path::new(&dir).join(&filename);
-    fs::read_to_string(path).map_err(|e| e.to_string())
+async fn normalize_line_endings(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    novel_name: String,
+) -> Result<text_normalize::LineEndingReport, AppError> {
+    let root = resolve_workspace_root(&app, workspace_root.clone());
+    workspace_lock::ensure_writable(&root)?;
+    let novel_dir = novel_path(&app, workspace_root, &dir_name, &novel_name)?;
+    let history_max_bytes = settings::load(&root).edit_history_max_bytes();
+    let report = text_normalize::normalize_line_endings(&novel_dir, &analysis_batch::result_dir(&root, &novel_name), history_max_bytes)?;
+    log_to_file_with_root(
+        &format!("[TextFiles] 《{}》换行修复: 检查 {} 个文件，改写章节 {} 个、分析结果 {} 个", novel_name, report.checked, report.chapters.len(), report.analyses.len()),
+        Some(&root),
+    );
+    Ok(report)
 }
 
 
@@ -596,33 +3338,31 @@ fn read_dir_recursive(base_path: &Path, relative_path: &Path) -> Vec<FileNode> {
     let mut nodes = Vec::new();
     
     if let Ok(entries) = fs::read_dir(target_path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                let name = entry.file_name().to_string_lossy().to_string();
-                let is_dir = path.is_dir();
-                let new_rel_path = relative_path.join(&name);
-                
-                // Filter: Only dirs or txt/json files
-                if !is_dir {
-                    let ext = path.extension().unwrap_or_default();
-                    if ext != "txt" && ext != "json" {
-                        continue;
-                    }
-                }
-
-                let mut children = Vec::new();
-                if is_dir {
-                    children = read_dir_recursive(base_path, &new_rel_path);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = path.is_dir();
+            let new_rel_path = relative_path.join(&name);
+            
+            // Filter: Only dirs or txt/json files
+            if !is_dir {
+                let ext = path.extension().unwrap_or_default();
+                if ext != "txt" && ext != "json" {
+                    continue;
                 }
+            }
 
-                nodes.push(FileNode {
-                    name,
-                    path: new_rel_path.to_string_lossy().to_string(),
-                    is_dir,
-                    children,
-                });
+            let mut children = Vec::new();
+            if is_dir {
+                children = read_dir_recursive(base_path, &new_rel_path);
             }
+
+            nodes.push(FileNode {
+                name,
+                path: new_rel_path.to_string_lossy().to_string(),
+                is_dir,
+                children,
+            });
         }
     }
     // Sort: Dirs first, then files
@@ -636,11 +3376,21 @@ fn read_dir_recursive(base_path: &Path, relative_path: &Path) -> Vec<FileNode> {
     nodes
 }
 
+/// only_novels 为 true 时，顶层只保留被 library 识别为小说的目录。
 #[tauri::command]
-fn get_file_tree(dir_name: String) -> Result<Vec<FileNode>, String> {
-    let path = Path::new(&dir_name);
+fn get_file_tree(
+    app: tauri::AppHandle,
+    workspace_root: Option<String>,
+    dir_name: String,
+    only_novels: Option<bool>,
+) -> Result<Vec<FileNode>, String> {
+    let path = &paths::resolve(&resolve_workspace_root(&app, workspace_root), &dir_name)?;
     if !path.exists() {
         return Ok(Vec::new());
     }
-    Ok(read_dir_recursive(path, Path::new("")))
+    let mut nodes = read_dir_recursive(path, Path::new(""));
+    if only_novels.unwrap_or(false) {
+        nodes.retain(|n| crate::library::is_novel_dir(&path.join(&n.path)));
+    }
+    Ok(nodes)
 }
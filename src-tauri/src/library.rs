@@ -0,0 +1,462 @@
+//! 书库目录识别：判断 downloads 下哪些条目是小说。
+//!
+//! 规则只在这里定义一次：目录内有 `info.json`，或至少有一个 `NN.txt` 章节文件（平铺或分目录存放，
+//! 见 [`crate::sharding`]），才算小说目录。其余条目（`_temp`、其他工具留下的零散 txt 等）一律归为"未识别项目"，
+//! 任何遍历书库的代码都应通过 [`scan_library`] / [`is_novel_dir`] 过滤。
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::novel_info::INFO_FILE;
+
+pub const DOWNLOADS_DIR: &str = "downloads";
+
+pub fn downloads_dir(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(DOWNLOADS_DIR)
+}
+
+/// 目录名最多保留的字数
+const MAX_DIR_NAME_CHARS: usize = 80;
+
+/// 书名转目录名：去掉路径分隔符和控制字符，过长的截断，`.` / `..` 等纯点号名称替换为 `_`。
+pub fn novel_dir_name(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .take(MAX_DIR_NAME_CHARS)
+        .collect();
+    if cleaned.trim_matches('.').trim().is_empty() {
+        return "_".to_string();
+    }
+    cleaned
+}
+
+/// 小说目录下最长的子路径（分隔符 + `.info.json.tmp-<pid>-<n>`、章节历史版本等）预留的长度
+const NOVEL_DIR_RESERVED: usize = 64;
+/// 工作区很深时目录名至少保留的长度，再短就难以辨认了（此时依赖扩展长度前缀）
+const MIN_DIR_NAME_CHARS: usize = 8;
+
+/// 早先只替换路径分隔符、不截断的目录名；`.` / `..` 等纯点号名称不是普通目录，返回 None
+fn legacy_dir_name(title: &str) -> Option<String> {
+    let name = title.replace(['/', '\\'], "_");
+    (!name.trim_matches('.').trim().is_empty()).then_some(name)
+}
+
+/// 书库目录 `library_dir` 下这本书的目录。目录名在 [`novel_dir_name`] 的基础上按书库路径的长度
+/// 再截断，让目录内的文件路径尽量不超过 Windows 的 260 字符上限；按旧规则命名的目录已存在时沿用。
+pub fn novel_dir_in(library_dir: &Path, title: &str) -> PathBuf {
+    if let Some(legacy) = legacy_dir_name(title).filter(|name| library_dir.join(name).is_dir()) {
+        return library_dir.join(legacy);
+    }
+    let full = novel_dir_name(title);
+    if library_dir.join(&full).is_dir() {
+        return library_dir.join(full);
+    }
+    let budget = crate::paths::name_budget(library_dir, NOVEL_DIR_RESERVED).max(MIN_DIR_NAME_CHARS);
+    let name = crate::paths::truncate_name(&full, budget);
+    library_dir.join(if name.trim_matches('.').trim().is_empty() { "_".to_string() } else { name })
+}
+
+/// 第 n 章（从 1 开始）的文件名，至少两位补零。
+pub fn chapter_file_name(n: usize) -> String {
+    format!("{:02}.txt", n)
+}
+
+/// 章节正文清洗规则的版本。修改下载时的正文提取 / 清洗规则时递增，已有分析会被标记为需要重跑。
+pub const CLEANING_RULES_VERSION: u32 = 1;
+
+/// 章节文件内容：标题 / 链接 / 分隔线头部 + 正文。
+pub fn render_chapter_file(title: &str, url: &str, content: &str) -> String {
+    format!("标题: {}\n链接: {}\n{}\n\n{}", title, url, "=".repeat(50), content)
+}
+
+/// 解析后的章节文件，改写头部（如重新选定标题）后用 [`ChapterFile::render`] 写回
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterFile {
+    pub title: String,
+    pub url: String,
+    pub body: String,
+}
+
+impl ChapterFile {
+    /// 按 [`render_chapter_file`] 的格式解析；缺少头部时为 None
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = crate::storage::strip_bom(text).splitn(4, '\n');
+        let title = lines.next()?.strip_prefix("标题:")?.trim().to_string();
+        let url = lines.next()?.strip_prefix("链接:")?.trim().to_string();
+        if !lines.next()?.starts_with("=====") {
+            return None;
+        }
+        let rest = lines.next().unwrap_or_default();
+        let body = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n')).unwrap_or(rest);
+        Some(ChapterFile { title, url, body: body.to_string() })
+    }
+
+    pub fn render(&self) -> String {
+        render_chapter_file(&self.title, &self.url, &self.body)
+    }
+}
+
+/// 小于该字节数的章节文件不可能完整（连头部加最短正文都放不下），视为残留。
+pub const MIN_CHAPTER_FILE_BYTES: u64 = 64;
+/// 正文少于该字数视为内容异常；各平台的阈值见 [`crate::settings::Settings::min_chapter_chars`]
+pub const MIN_CHAPTER_BODY_CHARS: usize = 20;
+
+/// 校验章节文件内容：必须有 标题/链接/分隔线 头部，且正文不少于 min_body_chars 字。
+pub fn validate_chapter_content(text: &str, min_body_chars: usize) -> Result<(), String> {
+    let mut lines = text.splitn(4, '\n');
+    let title = lines.next().unwrap_or_default();
+    let link = lines.next().unwrap_or_default();
+    let separator = lines.next().unwrap_or_default();
+    let body = lines.next().unwrap_or_default();
+
+    if !title.starts_with("标题:") || !link.starts_with("链接:") || !separator.starts_with("=====") {
+        return Err("缺少章节头部".to_string());
+    }
+    let body_chars = body.trim().chars().count();
+    if body_chars < min_body_chars {
+        return Err(format!("正文过短（{} 字，要求至少 {} 字）", body_chars, min_body_chars));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BrokenChapter {
+    pub file: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    /// 检查过的章节文件数
+    pub checked: usize,
+    /// 校验失败的章节
+    pub broken: Vec<BrokenChapter>,
+    /// 问题章节是否已删除（只在调用方确认后删除）
+    pub deleted: bool,
+    /// 需要重新下载的章节序号（从 1 开始）
+    pub requeue: Vec<usize>,
+}
+
+/// 扫描小说目录中的章节文件，找出残留和校验失败（正文少于 min_body_chars 字）的文件，返回需要重新下载的序号。
+/// 正文短不一定是坏文件（如单句的番外），所以只有 delete 为 true（用户已确认）时才删除它们。
+pub fn repair_novel_dir(novel_dir: &Path, min_body_chars: usize, delete: bool) -> Result<RepairReport, String> {
+    fs::read_dir(novel_dir).map_err(|e| format!("读取目录失败: {}", e))?;
+    let mut report = RepairReport::default();
+
+    for (index, name) in crate::sharding::chapter_files(novel_dir) {
+        let path = novel_dir.join(&name);
+        report.checked += 1;
+        let problem = match fs::read(&path) {
+            Ok(bytes) if (bytes.len() as u64) < MIN_CHAPTER_FILE_BYTES => Some(format!("文件过小（{} 字节）", bytes.len())),
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => validate_chapter_content(&text, min_body_chars).err(),
+                Err(_) => Some("不是有效的 UTF-8 文本".to_string()),
+            },
+            Err(e) => Some(format!("读取失败: {}", e)),
+        };
+        if let Some(reason) = problem {
+            if delete {
+                fs::remove_file(&path).map_err(|e| format!("删除 {} 失败: {}", name, e))?;
+            }
+            report.broken.push(BrokenChapter { file: name, reason });
+            report.requeue.push(index);
+        }
+    }
+    report.deleted = delete && !report.broken.is_empty();
+    Ok(report)
+}
+
+/// 站点显示的字数（"123.4万字"、"1.2亿"、"56789字"）解析为近似字数，无法解析（如"未知"）返回 None。
+pub fn parse_reported_chars(raw: &str) -> Option<u64> {
+    let s: String = raw.chars().filter(|c| !c.is_whitespace() && *c != ',').collect();
+    let s = s.trim_end_matches('字');
+    let (number, unit) = if let Some(n) = s.strip_suffix('亿') {
+        (n, 100_000_000.0)
+    } else if let Some(n) = s.strip_suffix('万') {
+        (n, 10_000.0)
+    } else {
+        (s, 1.0)
+    };
+    let value = number.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)?;
+    Some((value * unit).round() as u64)
+}
+
+/// 已下载章节的统计：章节数与正文字数（不含头部和空白）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadedStats {
+    pub chars: u64,
+    pub chapters: usize,
+}
+
+pub fn downloaded_stats(novel_dir: &Path) -> DownloadedStats {
+    let mut stats = DownloadedStats::default();
+    for (_, file) in crate::sharding::chapter_files(novel_dir) {
+        let Ok(text) = fs::read_to_string(novel_dir.join(file)) else { continue };
+        // 头部固定三行：标题 / 链接 / 分隔线
+        let body = text.splitn(4, '\n').nth(3).unwrap_or_default();
+        stats.chars += body.chars().filter(|c| !c.is_whitespace()).count() as u64;
+        stats.chapters += 1;
+    }
+    stats
+}
+
+/// 章节文件名：纯数字 + `.txt`，如 `01.txt`、`120.txt`。
+pub fn is_chapter_file_name(name: &str) -> bool {
+    match name.strip_suffix(".txt") {
+        Some(stem) => !stem.is_empty() && stem.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// 章节文件（平铺的 `NN.txt` 或分目录的 `chapters/NNN/NN.txt`）的序号
+pub fn chapter_index_of(file: &str) -> Option<usize> {
+    let name = file.rsplit(['/', '\\']).next()?;
+    if !is_chapter_file_name(name) {
+        return None;
+    }
+    name.strip_suffix(".txt")?.parse().ok()
+}
+
+/// 相对小说目录的章节文件路径：平铺的章节文件名，或分目录存放的 `chapters/<数字>/<章节文件名>`
+pub fn is_chapter_path(file: &str) -> bool {
+    let parts: Vec<&str> = file.split(['/', '\\']).collect();
+    match parts.as_slice() {
+        [name] => is_chapter_file_name(name),
+        [dir, shard, name] => {
+            *dir == crate::sharding::SHARDS_DIR
+                && !shard.is_empty()
+                && shard.chars().all(|c| c.is_ascii_digit())
+                && is_chapter_file_name(name)
+        }
+        _ => false,
+    }
+}
+
+/// 目录是否被识别为一本小说。
+pub fn is_novel_dir(path: &Path) -> bool {
+    if !path.is_dir() {
+        return false;
+    }
+    if path.join(INFO_FILE).is_file() {
+        return true;
+    }
+    !crate::sharding::chapter_files(path).is_empty()
+}
+
+/// 路径已被非小说条目占用（不满足 [`is_novel_dir`] 的目录或零散文件）。
+/// 书库统计和自动更新按书名定位目录时用它跳过，不往别的工具的文件夹里写书
+pub fn is_unrecognized(path: &Path) -> bool {
+    path.symlink_metadata().is_ok() && !is_novel_dir(path)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct UnrecognizedItem {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LibraryScan {
+    /// 识别出的小说目录（按目录名排序）
+    pub novels: Vec<PathBuf>,
+    /// 未识别项目（按名称排序）
+    pub unrecognized: Vec<UnrecognizedItem>,
+}
+
+/// 扫描 downloads 目录的第一层。以 `.` 开头的隐藏条目直接忽略。
+pub fn scan_library(downloads_dir: &Path) -> LibraryScan {
+    let mut scan = LibraryScan::default();
+    let Ok(entries) = fs::read_dir(downloads_dir) else {
+        return scan;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if is_novel_dir(&path) {
+            scan.novels.push(path);
+        } else {
+            scan.unrecognized.push(UnrecognizedItem { name, is_dir: path.is_dir() });
+        }
+    }
+
+    scan.novels.sort();
+    scan.unrecognized.sort_by(|a, b| a.name.cmp(&b.name));
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_downloads(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_library_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn chapter_file_name_pattern() {
+        assert!(is_chapter_file_name("01.txt"));
+        assert!(is_chapter_file_name("120.txt"));
+        assert!(!is_chapter_file_name(".txt"));
+        assert!(!is_chapter_file_name("notes.txt"));
+        assert!(!is_chapter_file_name("01.json"));
+        assert!(!is_chapter_file_name("第1章.txt"));
+        assert!(is_chapter_path("chapters/001/2001.txt"));
+        assert!(!is_chapter_path("chapters/../01.txt"));
+        assert!(!is_chapter_path("other/001/01.txt"));
+        assert_eq!(chapter_index_of("chapters/001/2001.txt"), Some(2001));
+        assert_eq!(chapter_index_of("05.en.txt"), None);
+    }
+
+    #[test]
+    fn scan_separates_novels_from_decoys() {
+        let root = temp_downloads("mixed");
+
+        let with_info = root.join("有信息的书");
+        fs::create_dir_all(&with_info).unwrap();
+        fs::write(with_info.join("info.json"), "{}").unwrap();
+
+        let with_chapter = root.join("只有章节的书");
+        fs::create_dir_all(&with_chapter).unwrap();
+        fs::write(with_chapter.join("01.txt"), "正文").unwrap();
+
+        let cover_only = root.join("只有封面");
+        fs::create_dir_all(&cover_only).unwrap();
+        fs::write(cover_only.join("cover.jpg"), [0u8; 4]).unwrap();
+
+        let temp = root.join("_temp");
+        fs::create_dir_all(temp.join("01.txt")).unwrap(); // 同名目录不算章节文件
+
+        fs::write(root.join("loose.txt"), "其他工具的输出").unwrap();
+        fs::write(root.join(".info.json.tmp-1-0"), "").unwrap();
+
+        let scan = scan_library(&root);
+        assert_eq!(scan.novels, vec![with_chapter, with_info.clone()]);
+        let names: Vec<_> = scan.unrecognized.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, vec!["_temp", "loose.txt", "只有封面"]);
+        assert!(scan.unrecognized.iter().find(|u| u.name == "loose.txt").map(|u| !u.is_dir).unwrap());
+        assert!(is_unrecognized(&cover_only) && is_unrecognized(&root.join("loose.txt")));
+        assert!(!is_unrecognized(&with_info) && !is_unrecognized(&root.join("还没下载的书")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn truncated_chapter_is_repaired_and_redownloaded() {
+        let dir = temp_downloads("repair");
+        let good = render_chapter_file("第一章 开端", "https://example.com/1", &"正文内容".repeat(20));
+        fs::write(dir.join("01.txt"), &good).unwrap();
+        // 写到一半被中断：只剩半截头部
+        fs::write(dir.join("02.txt"), &good.as_bytes()[..20]).unwrap();
+        // 头部完整但正文为空（抓到了 VIP 锁页）
+        fs::write(dir.join("03.txt"), render_chapter_file("第三章", "https://example.com/3", "")).unwrap();
+        fs::write(dir.join("04.txt"), "").unwrap();
+
+        let report = repair_novel_dir(&dir, MIN_CHAPTER_BODY_CHARS, true).unwrap();
+        assert_eq!(report.checked, 4);
+        assert_eq!(report.requeue, vec![2, 3, 4]);
+        assert!(report.deleted);
+        assert!(dir.join("01.txt").exists());
+        assert!(!dir.join("02.txt").exists() && !dir.join("03.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn short_chapters_are_reported_but_kept_without_confirmation() {
+        let dir = temp_downloads("repair_keep");
+        // 单句的番外：头部完整、内容有效，只是短于阈值
+        let short = render_chapter_file("番外 后记", "https://example.com/1", "谢谢大家一路相伴。");
+        fs::write(dir.join("01.txt"), &short).unwrap();
+
+        let report = repair_novel_dir(&dir, MIN_CHAPTER_BODY_CHARS, false).unwrap();
+        assert_eq!(report.requeue, vec![1]);
+        assert_eq!(report.broken[0].file, "01.txt");
+        assert!(!report.deleted);
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), short);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn short_body_error_states_threshold_and_length() {
+        let text = render_chapter_file("第一章", "https://example.com/1", &"字".repeat(800));
+        assert!(validate_chapter_content(&text, 300).is_ok());
+        assert_eq!(validate_chapter_content(&text, 1500).unwrap_err(), "正文过短（800 字，要求至少 1500 字）");
+    }
+
+    #[test]
+    fn hostile_titles_make_safe_dir_names() {
+        assert_eq!(novel_dir_name("a/b\\c"), "a_b_c");
+        assert_eq!(novel_dir_name(".."), "_");
+        assert_eq!(novel_dir_name("书名\n换行"), "书名_换行");
+        assert_eq!(novel_dir_name(&"长".repeat(1000)).chars().count(), MAX_DIR_NAME_CHARS);
+    }
+
+    #[test]
+    fn deep_workspaces_get_shorter_dir_names() {
+        let root = temp_downloads("deep");
+        let title = "长".repeat(MAX_DIR_NAME_CHARS);
+        assert_eq!(novel_dir_in(&root, &title), root.join(&title));
+
+        let deep = (0..4).fold(root.clone(), |p, _| p.join("层".repeat(50)));
+        let dir = novel_dir_in(&deep, &title);
+        let name = dir.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(name.chars().count(), crate::paths::name_budget(&deep, NOVEL_DIR_RESERVED).max(MIN_DIR_NAME_CHARS));
+        assert!(name.chars().count() < MAX_DIR_NAME_CHARS);
+
+        // 旧规则建好的目录继续使用
+        fs::create_dir_all(deep.join(&title)).unwrap();
+        assert_eq!(novel_dir_in(&deep, &title), deep.join(&title));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn downloads_made_before_name_cleaning_keep_their_dirs() {
+        let root = temp_downloads("legacy_names");
+        let long = format!("{}/下卷", "长".repeat(MAX_DIR_NAME_CHARS));
+        let tabbed = "书名\t副标题";
+        for title in [long.as_str(), tabbed] {
+            assert_ne!(novel_dir_in(&root, title), root.join(title.replace('/', "_")));
+            fs::create_dir_all(root.join(title.replace('/', "_"))).unwrap();
+            assert_eq!(novel_dir_in(&root, title), root.join(title.replace('/', "_")));
+        }
+        assert_eq!(novel_dir_in(&root, ".."), root.join("_"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn reported_word_counts_parse_to_numbers() {
+        assert_eq!(parse_reported_chars("123.4万字"), Some(1_234_000));
+        assert_eq!(parse_reported_chars("1.2亿"), Some(120_000_000));
+        assert_eq!(parse_reported_chars(" 56,789字 "), Some(56_789));
+        assert_eq!(parse_reported_chars("未知"), None);
+        assert_eq!(parse_reported_chars(""), None);
+    }
+
+    #[test]
+    fn downloaded_stats_count_body_chars_only() {
+        let dir = temp_downloads("stats");
+        fs::write(dir.join("01.txt"), render_chapter_file("第一章", "https://example.com/1", "正文 十个字\n\n还有五个字")).unwrap();
+        fs::write(dir.join("02.txt"), render_chapter_file("第二章", "https://example.com/2", "三个字")).unwrap();
+        fs::write(dir.join("01.txt.v20260101000000000"), "旧版本不计入").unwrap();
+        fs::write(dir.join("info.json"), "{}").unwrap();
+
+        assert_eq!(downloaded_stats(&dir), DownloadedStats { chars: 13, chapters: 2 });
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn scan_missing_dir_is_empty() {
+        let scan = scan_library(&std::env::temp_dir().join("test_library_does_not_exist"));
+        assert!(scan.novels.is_empty() && scan.unrecognized.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! 书库目录导出：按小说识别规则遍历下载目录，每本书一行，写出 CSV 或 JSON 供表格软件分析。
+//!
+//! 下载日期取 info.json 的 `downloaded_at`（每次下载结束时写入），没有时用最新章节文件的修改时间；
+//! 分析覆盖率按分析索引中引用过的章节文件数 / 当前章节数计算。已归档的书同样导出，并标出归档状态。
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::analysis_batch::{self, IndexEntry};
+use crate::{csv_export, library, novel_info, stream_write};
+
+/// info.json 中最近一次下载完成的时间
+pub const DOWNLOADED_AT_KEY: &str = "downloaded_at";
+const FILE_STEM: &str = "library_catalog";
+const CSV_HEADER: [&str; 12] = [
+    "书名", "作者", "平台", "标签", "站点字数", "站点字数（解析）", "已下载字数", "章节数", "下载日期", "分析覆盖率(%)", "已归档", "链接",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("csv") => Ok(ExportFormat::Csv),
+            Some("json") => Ok(ExportFormat::Json),
+            Some(other) => Err(format!("不支持的导出格式: {}（可选 csv / json）", other)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct LibraryCatalogRow {
+    /// 目录名
+    pub title: String,
+    pub author: Option<String>,
+    pub platform: Option<String>,
+    pub tags: Vec<String>,
+    /// 站点显示的字数原文，如 "123.4万字"
+    pub word_count: Option<String>,
+    pub reported_chars: Option<u64>,
+    /// 按章节文件实际统计的字数
+    pub downloaded_chars: u64,
+    pub chapters: usize,
+    pub downloaded_at: Option<String>,
+    pub analysis_coverage: f64,
+    pub archived: bool,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryExport {
+    /// 相对工作区
+    pub path: String,
+    pub rows: usize,
+}
+
+fn text<'a>(info: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    info.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// 最新章节文件的修改时间
+fn latest_chapter_mtime(novel_dir: &Path) -> Option<String> {
+    let newest = crate::sharding::chapter_files(novel_dir)
+        .into_iter()
+        .filter_map(|(_, file)| fs::metadata(novel_dir.join(file)).ok()?.modified().ok())
+        .max()?;
+    Some(chrono::DateTime::<chrono::Local>::from(newest).format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// 每本书在分析索引中被引用过的章节文件
+fn analyzed_files(index: Vec<IndexEntry>) -> HashMap<String, HashSet<String>> {
+    let mut analyzed: HashMap<String, HashSet<String>> = HashMap::new();
+    for entry in index {
+        let files = analyzed.entry(entry.novel_title).or_default();
+        files.extend(entry.outputs.into_iter().flat_map(|o| o.sources).map(|s| s.file));
+    }
+    analyzed
+}
+
+fn build_row(novel_dir: &Path, analyzed: &HashMap<String, HashSet<String>>) -> LibraryCatalogRow {
+    let title = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let info = novel_info::read_info(novel_dir).unwrap_or_default();
+    let stats = library::downloaded_stats(novel_dir);
+    let word_count = text(&info, "word_count").map(str::to_string);
+
+    let chapter_files = analysis_batch::chapter_files(novel_dir);
+    let covered = analyzed
+        .get(&title)
+        .map_or(0, |files| chapter_files.iter().filter(|f| files.contains(*f)).count());
+    let analysis_coverage = if chapter_files.is_empty() {
+        0.0
+    } else {
+        (covered as f64 * 1000.0 / chapter_files.len() as f64).round() / 10.0
+    };
+
+    LibraryCatalogRow {
+        author: text(&info, "author").map(str::to_string),
+        platform: text(&info, "platform").map(str::to_string),
+        tags: info
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default(),
+        reported_chars: word_count.as_deref().and_then(library::parse_reported_chars),
+        word_count,
+        downloaded_chars: stats.chars,
+        chapters: stats.chapters,
+        downloaded_at: text(&info, DOWNLOADED_AT_KEY).map(str::to_string).or_else(|| latest_chapter_mtime(novel_dir)),
+        analysis_coverage,
+        archived: novel_info::is_archived(novel_dir),
+        url: text(&info, "url").map(str::to_string),
+        title,
+    }
+}
+
+/// 收集书库中每本书的一行，按目录名排序
+pub fn collect_rows(workspace_root: &Path, library_dir: &Path) -> Vec<LibraryCatalogRow> {
+    let analyzed = analyzed_files(analysis_batch::load_index(workspace_root));
+    library::scan_library(library_dir).novels.iter().map(|dir| build_row(dir, &analyzed)).collect()
+}
+
+fn csv_record(row: &LibraryCatalogRow) -> Vec<String> {
+    vec![
+        row.title.clone(),
+        row.author.clone().unwrap_or_default(),
+        row.platform.clone().unwrap_or_default(),
+        row.tags.join("、"),
+        row.word_count.clone().unwrap_or_default(),
+        row.reported_chars.map(|n| n.to_string()).unwrap_or_default(),
+        row.downloaded_chars.to_string(),
+        row.chapters.to_string(),
+        row.downloaded_at.clone().unwrap_or_default(),
+        format!("{:.1}", row.analysis_coverage),
+        if row.archived { "是" } else { "" }.to_string(),
+        row.url.clone().unwrap_or_default(),
+    ]
+}
+
+pub fn to_csv(rows: &[LibraryCatalogRow]) -> String {
+    let records: Vec<Vec<String>> = rows.iter().map(csv_record).collect();
+    csv_export::render(&CSV_HEADER, &records)
+}
+
+/// 写入 `<workspace>/result/library_catalog.<ext>`，返回文件路径和行数。
+/// 每本书的行生成后立即写出，不在内存中拼出整个文件
+pub fn export(workspace_root: &Path, library_dir: &Path, format: ExportFormat) -> Result<(PathBuf, usize), String> {
+    let analyzed = analyzed_files(analysis_batch::load_index(workspace_root));
+    let novels = library::scan_library(library_dir).novels;
+    let path = workspace_root.join(analysis_batch::RESULT_DIR).join(format!("{}.{}", FILE_STEM, format.extension()));
+    stream_write::write_file(&path, |out| {
+        match format {
+            ExportFormat::Csv => out.write_all(csv_export::header(&CSV_HEADER).as_bytes())?,
+            ExportFormat::Json => out.write_all(b"[")?,
+        }
+        for (i, dir) in novels.iter().enumerate() {
+            let row = build_row(dir, &analyzed);
+            match format {
+                ExportFormat::Csv => out.write_all(csv_export::line(&csv_record(&row)).as_bytes())?,
+                ExportFormat::Json => {
+                    out.write_all(if i == 0 { "\n" } else { ",\n" }.as_bytes())?;
+                    serde_json::to_writer_pretty(&mut *out, &row).map_err(io::Error::from)?;
+                }
+            }
+        }
+        if format == ExportFormat::Json {
+            out.write_all(if novels.is_empty() { "]" } else { "\n]" }.as_bytes())?;
+        }
+        Ok(())
+    })
+    .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok((path, novels.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{self, AnalysisOutput, SourceChapter};
+
+    #[test]
+    fn rows_combine_info_stats_and_analysis_coverage() {
+        let root = std::env::temp_dir().join(format!("test_library_export_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let downloads = library::downloads_dir(&root);
+        let novel_dir = downloads.join("书名");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::create_dir_all(downloads.join("空目录")).unwrap();
+        for n in 1..=4 {
+            fs::write(novel_dir.join(library::chapter_file_name(n)), library::render_chapter_file("章", "u", "正文内容")).unwrap();
+        }
+        let info = serde_json::json!({
+            "title": "书名", "author": "作者", "platform": "qidian", "url": "https://book.qidian.com/info/1",
+            "tags": ["玄幻", "系统"], "word_count": "1.5万字", "downloaded_at": "2026-01-02 03:04:05",
+            "info_version": 2, "user": {"archived": true}
+        });
+        fs::write(novel_dir.join(novel_info::INFO_FILE), info.to_string()).unwrap();
+        let sources = vec![SourceChapter::of("01.txt", b""), SourceChapter::of("02.txt", b""), SourceChapter::of("99.txt", b"")];
+        provenance::record_export(&root, "书名", AnalysisOutput { output_file: "result/书名/01-02.md".to_string(), sources, output_language: None }).unwrap();
+
+        let rows = collect_rows(&root, &downloads);
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row.author.as_deref(), Some("作者"));
+        assert_eq!(row.reported_chars, Some(15_000));
+        assert_eq!((row.chapters, row.downloaded_chars), (4, 16));
+        assert_eq!(row.analysis_coverage, 50.0);
+        assert_eq!(row.downloaded_at.as_deref(), Some("2026-01-02 03:04:05"));
+
+        let csv = to_csv(&rows);
+        assert!(csv.starts_with('\u{feff}'));
+        assert!(csv.contains("\r\n书名,作者,qidian,玄幻、系统,1.5万字,15000,16,4,2026-01-02 03:04:05,50.0,是,https://book.qidian.com/info/1"));
+
+        let (path, count) = export(&root, &downloads, ExportFormat::Json).unwrap();
+        assert_eq!((path.file_name().unwrap().to_str(), count), (Some("library_catalog.json"), 1));
+        let exported: Vec<Value> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(exported[0]["title"], "书名");
+        let (path, _) = export(&root, &downloads, ExportFormat::Csv).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), csv);
+        assert!(ExportFormat::parse(Some("xlsx")).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
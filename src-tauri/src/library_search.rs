@@ -0,0 +1,236 @@
+//! 书库检索：按书名片段、作者、简介关键字在下载目录各书的 info.json 中查找。
+//!
+//! 排序：书名完全一致 > 书名前缀 > 书名 / 作者包含 > 书名拼音首字母（如 "dldl" 匹配 斗罗大陆）> 简介包含，
+//! 同一档内按书名排序。每本书只返回排名最高的一条命中。英文字母不区分大小写。
+
+use pinyin::ToPinyin;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::{library, novel_info};
+
+/// 片段中命中位置前后各保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 20;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    PinyinInitials,
+    Description,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Title,
+    Author,
+    Description,
+}
+
+impl SearchField {
+    pub const ALL: [SearchField; 3] = [SearchField::Title, SearchField::Author, SearchField::Description];
+
+    fn name(self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Author => "author",
+            SearchField::Description => "description",
+        }
+    }
+
+    /// 缺省或为空时检索全部字段
+    pub fn parse_list(fields: Option<&[String]>) -> Result<Vec<SearchField>, String> {
+        let Some(fields) = fields.filter(|f| !f.is_empty()) else {
+            return Ok(Self::ALL.to_vec());
+        };
+        fields
+            .iter()
+            .map(|f| match f.trim().to_ascii_lowercase().as_str() {
+                "title" => Ok(SearchField::Title),
+                "author" => Ok(SearchField::Author),
+                "description" => Ok(SearchField::Description),
+                other => Err(format!("不支持的检索字段: {}（可选 title / author / description）", other)),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SearchHit {
+    pub title: String,
+    /// 小说目录，相对工作区
+    pub path: String,
+    /// "title" | "author" | "description"
+    pub field: &'static str,
+    pub kind: MatchKind,
+    /// 命中处前后的片段；拼音首字母命中时为书名
+    pub snippet: String,
+    pub archived: bool,
+}
+
+struct Candidate {
+    title: String,
+    author: Option<String>,
+    description: Option<String>,
+}
+
+fn text(info: &serde_json::Map<String, Value>, key: &str) -> Option<String> {
+    info.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+/// 拼音首字母；英文字母和数字原样保留（小写），其余字符忽略
+pub fn pinyin_initials(s: &str) -> String {
+    s.chars()
+        .filter_map(|c| match c.to_pinyin() {
+            Some(p) => p.first_letter().chars().next(),
+            None if c.is_ascii_alphanumeric() => Some(c.to_ascii_lowercase()),
+            None => None,
+        })
+        .collect()
+}
+
+/// 命中位置前后各 [`SNIPPET_CONTEXT_CHARS`] 个字符
+pub(crate) fn snippet(text: &str, start: usize, len: usize) -> String {
+    let before: Vec<char> = text[..start].chars().rev().take(SNIPPET_CONTEXT_CHARS).collect();
+    let after = crate::spiders::char_prefix(&text[start + len..], SNIPPET_CONTEXT_CHARS);
+    let mut out: String = before.into_iter().rev().collect();
+    out.push_str(&text[start..start + len]);
+    out.push_str(after);
+    out
+}
+
+/// 在小写化后的文本中查找，返回原文中的字节位置。只小写 ASCII，字节位置不变。
+fn find(haystack: &str, query: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(query)
+}
+
+fn best_match(candidate: &Candidate, query: &str, fields: &[SearchField]) -> Option<(SearchField, MatchKind, String)> {
+    let mut best: Option<(SearchField, MatchKind, String)> = None;
+    let mut consider = |field, kind, snippet: String| {
+        if best.as_ref().is_none_or(|(_, k, _)| kind < *k) {
+            best = Some((field, kind, snippet));
+        }
+    };
+    for &field in fields {
+        match field {
+            SearchField::Title => {
+                let title = &candidate.title;
+                let lower = title.to_ascii_lowercase();
+                if lower == query {
+                    consider(field, MatchKind::Exact, title.clone());
+                } else if lower.starts_with(query) {
+                    consider(field, MatchKind::Prefix, title.clone());
+                } else if let Some(at) = lower.find(query) {
+                    consider(field, MatchKind::Substring, snippet(title, at, query.len()));
+                } else if query.chars().all(|c| c.is_ascii_alphanumeric()) && pinyin_initials(title).contains(query) {
+                    consider(field, MatchKind::PinyinInitials, title.clone());
+                }
+            }
+            SearchField::Author => {
+                if let Some(author) = &candidate.author {
+                    if let Some(at) = find(author, query) {
+                        consider(field, MatchKind::Substring, snippet(author, at, query.len()));
+                    }
+                }
+            }
+            SearchField::Description => {
+                if let Some(description) = &candidate.description {
+                    if let Some(at) = find(description, query) {
+                        consider(field, MatchKind::Description, snippet(description, at, query.len()));
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+/// 检索书库（含已归档的书），结果按命中档位和书名排序
+pub fn search(workspace_root: &Path, library_dir: &Path, query: &str, fields: &[SearchField]) -> Vec<SearchHit> {
+    let query = query.trim().to_ascii_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<SearchHit> = library::scan_library(library_dir)
+        .novels
+        .iter()
+        .filter_map(|novel_dir| {
+            let info = novel_info::read_info(novel_dir).unwrap_or_default();
+            let dir_name = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let candidate = Candidate {
+                title: text(&info, "title").unwrap_or(dir_name),
+                author: text(&info, "author"),
+                description: text(&info, "description"),
+            };
+            let (field, kind, snippet) = best_match(&candidate, &query, fields)?;
+            Some(SearchHit {
+                title: candidate.title,
+                path: crate::paths::to_relative(workspace_root, novel_dir).unwrap_or_else(|| novel_dir.display().to_string()),
+                field: field.name(),
+                kind,
+                snippet,
+                archived: novel_info::is_archived(novel_dir),
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.title.cmp(&b.title)));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn hits_are_ranked_by_match_kind() {
+        let root = std::env::temp_dir().join(format!("test_library_search_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let downloads = library::downloads_dir(&root);
+        let books = [
+            ("斗罗大陆", "唐家三少", "唐门外门弟子唐三穿越到斗罗大陆。"),
+            ("斗罗大陆II绝世唐门", "唐家三少", "斗罗大陆续作。"),
+            ("重生之斗罗", "某人", "无关简介"),
+            ("凡人修仙传", "忘语", "一个普通山村少年踏入斗罗大陆般的修仙世界。"),
+        ];
+        for (title, author, description) in books {
+            let dir = downloads.join(title);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(library::chapter_file_name(1)), library::render_chapter_file("章", "u", "正文内容")).unwrap();
+            let info = serde_json::json!({ "title": title, "author": author, "description": description });
+            fs::write(dir.join(novel_info::INFO_FILE), info.to_string()).unwrap();
+        }
+
+        let hits = search(&root, &downloads, " 斗罗大陆 ", &SearchField::ALL);
+        let ranked: Vec<(&str, MatchKind)> = hits.iter().map(|h| (h.title.as_str(), h.kind)).collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("斗罗大陆", MatchKind::Exact),
+                ("斗罗大陆II绝世唐门", MatchKind::Prefix),
+                ("凡人修仙传", MatchKind::Description),
+            ]
+        );
+        assert_eq!(hits[0].path, "downloads/斗罗大陆");
+        assert_eq!(hits[2].snippet, "一个普通山村少年踏入斗罗大陆般的修仙世界。");
+
+        let by_author = search(&root, &downloads, "忘语", &SearchField::ALL);
+        assert_eq!((by_author[0].field, by_author[0].kind), ("author", MatchKind::Substring));
+
+        let by_pinyin = search(&root, &downloads, "DLDL", &[SearchField::Title]);
+        assert_eq!(by_pinyin.len(), 2);
+        assert!(by_pinyin.iter().all(|h| h.kind == MatchKind::PinyinInitials));
+        assert!(search(&root, &downloads, "斗罗", &[SearchField::Author]).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn fields_default_to_all() {
+        assert_eq!(SearchField::parse_list(None).unwrap(), SearchField::ALL.to_vec());
+        assert_eq!(SearchField::parse_list(Some(&["Author".to_string()])).unwrap(), vec![SearchField::Author]);
+        assert!(SearchField::parse_list(Some(&["tags".to_string()])).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! 资源档位：`low` / `normal` / `high` 对应一组并发上限，各子系统从这里取值，不再各自写死缺省值。
+//!
+//! - 爬虫窗口预算：设置中显式的 `spider_window_budget` 仍然优先
+//! - AI 请求槽位：见 [`crate::ai_limits`]
+//! - 扫榜流水线（抓取 / 提纯 / 评估）和仅元数据扫榜的并发数
+//! - 来源链接批量检查的并发数
+//!
+//! 爬虫窗口和 AI 槽位是全局的，[`apply`] 立即调整上限：调大时排队者马上放行，调小时执行中的请求不受影响，
+//! 随许可归还逐步收紧。流水线和来源检查每次运行新建信号量，下一次运行起生效。
+//! 档位保存在设置的 `resource_profile` 中，启动和切换工作区时恢复。
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceProfile {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl ResourceProfile {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim() {
+            "low" => Ok(ResourceProfile::Low),
+            "normal" => Ok(ResourceProfile::Normal),
+            "high" => Ok(ResourceProfile::High),
+            other => Err(format!("未知的资源档位: {}（可选 low / normal / high）", other)),
+        }
+    }
+
+    pub fn limits(self) -> Limits {
+        match self {
+            ResourceProfile::Low => Limits { spider_windows: 1, ai_slots: 2, pipeline_workers: 1, source_checks: 2 },
+            ResourceProfile::Normal => Limits { spider_windows: 2, ai_slots: 4, pipeline_workers: 3, source_checks: 4 },
+            ResourceProfile::High => Limits { spider_windows: 4, ai_slots: 8, pipeline_workers: 6, source_checks: 8 },
+        }
+    }
+}
+
+/// 一个档位的具体上限
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Limits {
+    /// 同时存在的爬虫窗口数
+    pub spider_windows: usize,
+    /// 同时在途的 AI 请求数
+    pub ai_slots: usize,
+    /// 扫榜流水线每个阶段的并发数
+    pub pipeline_workers: usize,
+    /// 来源链接批量检查的并发数
+    pub source_checks: usize,
+}
+
+static PROFILE: RwLock<ResourceProfile> = RwLock::new(ResourceProfile::Normal);
+
+pub fn profile() -> ResourceProfile {
+    *PROFILE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 当前档位的上限
+pub fn current() -> Limits {
+    profile().limits()
+}
+
+/// 按设置切换档位，并立即调整爬虫窗口和 AI 槽位的上限
+pub fn apply(settings: &crate::settings::Settings) {
+    *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = settings.resource_profile;
+    crate::browser_spider::set_window_budget(settings.spider_window_budget());
+    crate::ai_limits::set_slots(current().ai_slots);
+}
+
+/// 生效中的档位与上限（爬虫窗口已计入设置中的显式预算）
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct ActiveLimits {
+    pub profile: ResourceProfile,
+    #[serde(flatten)]
+    pub limits: Limits,
+}
+
+pub fn active(settings: &crate::settings::Settings) -> ActiveLimits {
+    let mut limits = current();
+    limits.spider_windows = settings.spider_window_budget();
+    ActiveLimits { profile: profile(), limits }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_scale_every_limit() {
+        assert_eq!(ResourceProfile::parse(" low "), Ok(ResourceProfile::Low));
+        assert!(ResourceProfile::parse("max").is_err());
+        let (low, normal, high) = (ResourceProfile::Low.limits(), ResourceProfile::Normal.limits(), ResourceProfile::High.limits());
+        assert_eq!(normal.spider_windows, crate::browser_spider::DEFAULT_WINDOW_BUDGET);
+        for (a, b) in [(low, normal), (normal, high)] {
+            assert!(a.spider_windows <= b.spider_windows && a.ai_slots < b.ai_slots);
+            assert!(a.pipeline_workers < b.pipeline_workers && a.source_checks < b.source_checks);
+        }
+    }
+}
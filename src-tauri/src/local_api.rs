@@ -0,0 +1,449 @@
+//! 本地集成接口：供 Python 脚本、笔记本等外部工具只读访问书库和 AI 流式输出，不经过 Tauri IPC。
+//!
+//! 只监听 127.0.0.1，所有请求都要带 `Authorization: Bearer <token>`。接口都是 GET：
+//!
+//! - `/novels`（`?include_archived=true` 含已归档）：同 `list_novels`
+//! - `/novels/<小说>/files`：小说目录下的文件树，同 `get_file_tree`
+//! - `/novels/<小说>/chapters/<文件名>`：章节正文，同 `get_file_content`
+//! - `/results/<小说>`、`/results/<小说>/<文件名>`：分析结果的文件树和内容
+//! - `/stream/<request_id>`：SSE，转发 `start_ai_analysis` 返回的 request_id 对应的 `ai-analysis` 流，
+//!   事件为 `chunk` / `done` / `error`，结束后关闭连接
+//!
+//! 处理函数直接调用对应命令，不另写文件读取逻辑。路径段解码后不能含 `/`、`\`、盘符或 `.` / `..`，
+//! 文件路径只在对应小说目录内解析，不接受绝对路径。一次只运行一个服务，端口被占用时拒绝启动。
+
+use serde::Serialize;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+
+use crate::errors::AppError;
+use crate::{analysis_batch, library, paths, sharding};
+
+/// 请求头最多读取的字节数
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+/// SSE 连接空闲时发送注释行的间隔，用来发现已断开的客户端
+const SSE_KEEPALIVE: Duration = Duration::from_secs(15);
+/// token 最短长度，避免随手设置的弱口令
+const MIN_TOKEN_CHARS: usize = 8;
+/// 流式输出的广播缓冲；订阅方处理不过来时丢弃最早的片段
+const STREAM_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamKind {
+    Chunk,
+    Done,
+    Error,
+}
+
+/// 转发给 SSE 订阅方的一条 AI 流式输出
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamEvent {
+    pub request_id: String,
+    pub kind: StreamKind,
+    pub text: String,
+}
+
+fn stream_hub() -> &'static broadcast::Sender<StreamEvent> {
+    static HUB: OnceLock<broadcast::Sender<StreamEvent>> = OnceLock::new();
+    HUB.get_or_init(|| broadcast::channel(STREAM_CAPACITY).0)
+}
+
+/// AI 流式输出的每个片段、结束和出错都经这里转发；没有订阅方时直接丢弃
+pub fn publish(request_id: &str, kind: StreamKind, text: impl Into<String>) {
+    if stream_hub().receiver_count() == 0 {
+        return;
+    }
+    let _ = stream_hub().send(StreamEvent { request_id: request_id.to_string(), kind, text: text.into() });
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LocalApiStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub url: Option<String>,
+}
+
+struct Running {
+    port: u16,
+    shutdown: watch::Sender<bool>,
+}
+
+fn server() -> &'static Mutex<Option<Running>> {
+    static SERVER: OnceLock<Mutex<Option<Running>>> = OnceLock::new();
+    SERVER.get_or_init(|| Mutex::new(None))
+}
+
+pub fn status() -> LocalApiStatus {
+    let guard = server().lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_ref() {
+        Some(running) => LocalApiStatus {
+            running: true,
+            port: Some(running.port),
+            url: Some(format!("http://{}:{}", Ipv4Addr::LOCALHOST, running.port)),
+        },
+        None => LocalApiStatus { running: false, port: None, url: None },
+    }
+}
+
+/// 绑定 127.0.0.1 上的端口，端口已被占用时报告冲突
+async fn bind(port: u16) -> Result<TcpListener, String> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::AddrInUse => format!("端口 {} 已被其他程序占用，请换一个端口", port),
+        _ => format!("监听 127.0.0.1:{} 失败: {}", port, e),
+    })
+}
+
+/// 启动本地接口。已在运行、token 太短或端口被占用时报错。
+pub async fn start(app: tauri::AppHandle, port: u16, token: String) -> Result<LocalApiStatus, String> {
+    let token = token.trim().to_string();
+    if token.chars().count() < MIN_TOKEN_CHARS {
+        return Err(format!("token 至少需要 {} 个字符", MIN_TOKEN_CHARS));
+    }
+    if let Some(running) = server().lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Err(format!("本地接口已在端口 {} 运行，请先停止", running.port));
+    }
+    let listener = bind(port).await?;
+    let port = listener.local_addr().map(|a| a.port()).unwrap_or(port);
+    let (shutdown, mut stopped) = watch::channel(false);
+    {
+        let mut guard = server().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = guard.as_ref() {
+            return Err(format!("本地接口已在端口 {} 运行，请先停止", running.port));
+        }
+        *guard = Some(Running { port, shutdown });
+    }
+    crate::log_to_file(&format!("[LocalApi] 已在 127.0.0.1:{} 启动", port));
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = stopped.changed() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let (app, token, stopped) = (app.clone(), token.clone(), stopped.clone());
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = handle_connection(stream, &app, &token, stopped).await {
+                                log::debug!("[LocalApi] 连接处理失败: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => eprintln!("[LocalApi] 接受连接失败: {}", e),
+                },
+            }
+        }
+        crate::log_to_file(&format!("[LocalApi] 端口 {} 已停止", port));
+    });
+    Ok(status())
+}
+
+/// 停止本地接口，返回之前是否在运行。已建立的 SSE 连接随之关闭。
+pub fn stop() -> bool {
+    match server().lock().unwrap_or_else(|e| e.into_inner()).take() {
+        Some(running) => {
+            let _ = running.shutdown.send(true);
+            true
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Route {
+    Novels { include_archived: bool },
+    NovelFiles(String),
+    Chapter(String, String),
+    Results(String),
+    ResultFile(String, String),
+    Stream(String),
+    NotFound,
+}
+
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// 解码后的路径段只能是单层文件名：不含分隔符、盘符和控制字符，也不是 `.` / `..`
+fn is_plain_segment(segment: &str) -> bool {
+    !matches!(segment, "" | "." | "..")
+        && !segment.chars().any(|c| matches!(c, '/' | '\\' | ':') || c.is_control())
+}
+
+fn parse_route(target: &str) -> Route {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let Some(segments) = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_decode(s).filter(|s| is_plain_segment(s)))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Route::NotFound;
+    };
+    let flag = |name: &str| {
+        url::form_urlencoded::parse(query.as_bytes()).any(|(k, v)| k == name && (v == "true" || v == "1"))
+    };
+    match segments.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["novels"] => Route::Novels { include_archived: flag("include_archived") },
+        ["novels", novel, "files"] => Route::NovelFiles(novel.to_string()),
+        ["novels", novel, "chapters", file] => Route::Chapter(novel.to_string(), file.to_string()),
+        ["results", novel] => Route::Results(novel.to_string()),
+        ["results", novel, file] => Route::ResultFile(novel.to_string(), file.to_string()),
+        ["stream", id] => Route::Stream(id.to_string()),
+        _ => Route::NotFound,
+    }
+}
+
+/// 逐字节比较 token，耗时与匹配到第几位无关
+fn token_matches(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|h| h.trim().strip_prefix("Bearer ")).map(str::trim) else {
+        return false;
+    };
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+struct Request {
+    method: String,
+    target: String,
+    authorization: Option<String>,
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 2048];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_BYTES {
+            return Err("请求头过大".to_string());
+        }
+        let n = stream.read(&mut buf).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("连接已关闭".to_string());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (method, target) = (request_line.next().unwrap_or_default(), request_line.next().unwrap_or_default());
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim().to_string());
+    Ok(Request { method: method.to_string(), target: target.to_string(), authorization })
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+async fn respond_json<T: Serialize>(stream: &mut TcpStream, status: &str, value: &T) -> std::io::Result<()> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    respond(stream, status, "application/json; charset=utf-8", &body).await
+}
+
+async fn respond_error(stream: &mut TcpStream, status: &str, error: impl Serialize) -> std::io::Result<()> {
+    respond_json(stream, status, &serde_json::json!({ "error": error })).await
+}
+
+async fn respond_result<T: Serialize, E: Serialize>(stream: &mut TcpStream, result: Result<T, E>) -> std::io::Result<()> {
+    match result {
+        Ok(value) => respond_json(stream, "200 OK", &value).await,
+        Err(e) => respond_error(stream, "400 Bad Request", e).await,
+    }
+}
+
+async fn respond_text<E: Serialize>(stream: &mut TcpStream, result: Result<String, E>) -> std::io::Result<()> {
+    match result {
+        Ok(text) => respond(stream, "200 OK", "text/plain; charset=utf-8", text.as_bytes()).await,
+        Err(e) => respond_error(stream, "404 Not Found", e).await,
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    app: &tauri::AppHandle,
+    token: &str,
+    stopped: watch::Receiver<bool>,
+) -> std::io::Result<()> {
+    let request = match read_request(&mut stream).await {
+        Ok(request) => request,
+        Err(e) => return respond_error(&mut stream, "400 Bad Request", e).await,
+    };
+    if !token_matches(request.authorization.as_deref(), token) {
+        return respond_error(&mut stream, "401 Unauthorized", "缺少或错误的 Bearer token").await;
+    }
+    if request.method != "GET" {
+        return respond_error(&mut stream, "405 Method Not Allowed", "本地接口只读，只接受 GET").await;
+    }
+
+    let downloads = library::DOWNLOADS_DIR;
+    let results = analysis_batch::RESULT_DIR;
+    match parse_route(&request.target) {
+        Route::Novels { include_archived } => {
+            respond_result(&mut stream, crate::list_novels(app.clone(), None, Some(include_archived))).await
+        }
+        Route::NovelFiles(novel) => {
+            let tree = crate::get_file_tree(app.clone(), None, format!("{}/{}", downloads, novel), None);
+            respond_result(&mut stream, tree).await
+        }
+        Route::Chapter(novel, file) => respond_text(&mut stream, read_chapter(app, &novel, &file)).await,
+        Route::Results(novel) => {
+            let tree = crate::get_file_tree(app.clone(), None, format!("{}/{}", results, novel), None);
+            respond_result(&mut stream, tree).await
+        }
+        Route::ResultFile(novel, file) => respond_text(&mut stream, read_novel_file(app, results, &novel, &file)).await,
+        Route::Stream(request_id) => stream_events(stream, &request_id, stopped).await,
+        Route::NotFound => respond_error(&mut stream, "404 Not Found", format!("未知接口: {}", request.target)).await,
+    }
+}
+
+/// 读取 `downloads/<小说>/<章节文件名>`：`NN.txt` 按序号经 [`sharding::chapter_path`] 找到实际位置
+fn read_chapter(app: &tauri::AppHandle, novel: &str, file: &str) -> Result<String, AppError> {
+    let novel_dir = paths::resolve_novel(&crate::get_workspace_root(app), library::DOWNLOADS_DIR, novel)
+        .map_err(AppError::invalid_input)?;
+    let path = chapter_file_path(&novel_dir, file).map_err(AppError::invalid_input)?;
+    crate::read_file_content(&path, file, None)
+}
+
+/// 章节文件名对应的路径：分目录存放的书中章节在 `chapters/NNN/` 下，其余文件只在小说目录内解析
+fn chapter_file_path(novel_dir: &Path, file: &str) -> Result<PathBuf, String> {
+    match library::chapter_index_of(file) {
+        Some(index) => Ok(sharding::chapter_path(novel_dir, index)),
+        None => paths::resolve_within(novel_dir, file),
+    }
+}
+
+/// 读取 `<dir>/<小说>/<文件名>`：小说按单层目录名解析，文件只在小说目录内解析
+fn read_novel_file(app: &tauri::AppHandle, dir: &str, novel: &str, file: &str) -> Result<String, AppError> {
+    let novel_dir = paths::resolve_novel(&crate::get_workspace_root(app), dir, novel).map_err(AppError::invalid_input)?;
+    let path = paths::resolve_within(&novel_dir, file).map_err(AppError::invalid_input)?;
+    crate::read_file_content(&path, file, None)
+}
+
+/// SSE 转发某个 request_id 的 AI 输出，收到 done / error 或服务停止后关闭连接
+async fn stream_events(mut stream: TcpStream, request_id: &str, mut stopped: watch::Receiver<bool>) -> std::io::Result<()> {
+    let mut events = stream_hub().subscribe();
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream; charset=utf-8\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")
+        .await?;
+    loop {
+        let event = tokio::select! {
+            _ = stopped.changed() => break,
+            _ = tokio::time::sleep(SSE_KEEPALIVE) => {
+                stream.write_all(b": keepalive\n\n").await?;
+                continue;
+            }
+            received = events.recv() => match received {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let warning = serde_json::json!({ "skipped": skipped });
+                    stream.write_all(format!("event: lagged\ndata: {}\n\n", warning).as_bytes()).await?;
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if event.request_id != request_id {
+            continue;
+        }
+        let data = serde_json::to_string(&event).unwrap_or_default();
+        let name = serde_json::to_value(event.kind).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+        stream.write_all(format!("event: {}\ndata: {}\n\n", name, data).as_bytes()).await?;
+        if event.kind != StreamKind::Chunk {
+            break;
+        }
+    }
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chapter_index::{ChapterIndex, ChapterRecord};
+    use std::fs;
+
+    #[test]
+    fn routes_decode_chinese_segments_and_reject_unknown_paths() {
+        assert_eq!(parse_route("/novels"), Route::Novels { include_archived: false });
+        assert_eq!(parse_route("/novels?include_archived=true"), Route::Novels { include_archived: true });
+        assert_eq!(
+            parse_route("/novels/%E4%B9%A6%E5%90%8D/chapters/01.txt"),
+            Route::Chapter("书名".to_string(), "01.txt".to_string())
+        );
+        assert_eq!(parse_route("/results/书名/05_v2.md"), Route::ResultFile("书名".to_string(), "05_v2.md".to_string()));
+        assert_eq!(parse_route("/stream/ai_20240101"), Route::Stream("ai_20240101".to_string()));
+        assert_eq!(parse_route("/novels/%E4%B9"), Route::NotFound);
+        assert_eq!(parse_route("/settings"), Route::NotFound);
+    }
+
+    #[test]
+    fn decoded_segments_cannot_escape_the_novel_directory() {
+        assert_eq!(parse_route("/novels/%2Fetc/chapters/passwd"), Route::NotFound);
+        assert_eq!(parse_route("/results/%2E%2E/secret.md"), Route::NotFound);
+        assert_eq!(parse_route("/novels/%E4%B9%A6%E5%90%8D/chapters/..%5C..%5Cwin.ini"), Route::NotFound);
+        assert_eq!(parse_route("/novels/C%3A/chapters/01.txt"), Route::NotFound);
+        assert_eq!(parse_route("/novels/./files"), Route::NotFound);
+        assert!(paths::resolve_within(std::path::Path::new("/ws/downloads/书名"), "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn chapter_files_of_sharded_novels_are_found_by_index() {
+        let dir = std::env::temp_dir().join(format!("test_local_api_sharded_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=3).map(|i| ChapterRecord { index: i, url: format!("u{}", i), ..Default::default() }));
+        index.save().unwrap();
+        for i in 1..=3 {
+            fs::write(dir.join(library::chapter_file_name(i)), format!("第{}章", i)).unwrap();
+        }
+        assert_eq!(chapter_file_path(&dir, "02.txt").unwrap(), dir.join("02.txt"));
+
+        sharding::shard_novel(&dir, 2).unwrap();
+        let path = chapter_file_path(&dir, "03.txt").unwrap();
+        assert_eq!(path, dir.join("chapters/001/03.txt"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "第3章");
+        assert!(chapter_file_path(&dir, "../secret.txt").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bearer_token_must_match_exactly() {
+        assert!(token_matches(Some("Bearer secret-token"), "secret-token"));
+        assert!(!token_matches(Some("Bearer secret-tokem"), "secret-token"));
+        assert!(!token_matches(Some("secret-token"), "secret-token"));
+        assert!(!token_matches(None, "secret-token"));
+    }
+
+    #[tokio::test]
+    async fn port_conflict_is_reported() {
+        let taken = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+        assert!(bind(port).await.unwrap_err().contains("已被其他程序占用"));
+    }
+}
@@ -0,0 +1,256 @@
+//! 批量移动文件的日志式事务：移动前把全部计划写进 `<base>/.move_journal.json`，逐个移动，全部成功后删除日志。
+//!
+//! 中途失败（磁盘满、权限不足）时立即把已完成的移动逆向移回，目录回到移动前的样子。进程在移动途中退出时
+//! 日志留在磁盘上，启动时或 `recover_incomplete_operations` 经 [`recover`] 按实际完成情况处理：
+//! 剩下的移动都还能完成（源文件在、目标不存在）就补完（roll forward），否则把已完成的移回（roll back）。
+//! 两种情况都会删掉移动时新建、此时已空的目录，最后删除日志。
+//!
+//! 计划中的路径相对 base，用 `/` 分隔。移动前校验每个源文件存在、目标不存在，不会覆盖已有文件。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::storage;
+
+pub const JOURNAL_FILE: &str = ".move_journal.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlannedMove {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Journal {
+    /// 发起移动的操作名，恢复后由调用方据此补做索引等收尾工作
+    pub operation: String,
+    pub started_at: String,
+    pub moves: Vec<PlannedMove>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveState {
+    /// 源文件在、目标不存在
+    Pending,
+    /// 源文件不在、目标存在
+    Done,
+    /// 两边都不在，或两边都在
+    Broken,
+}
+
+fn state(base: &Path, m: &PlannedMove) -> MoveState {
+    match (base.join(&m.from).exists(), base.join(&m.to).exists()) {
+        (true, false) => MoveState::Pending,
+        (false, true) => MoveState::Done,
+        _ => MoveState::Broken,
+    }
+}
+
+pub fn journal_path(base: &Path) -> PathBuf {
+    base.join(JOURNAL_FILE)
+}
+
+fn move_file(base: &Path, from: &str, to: &str) -> std::io::Result<()> {
+    let to = base.join(to);
+    if let Some(parent) = to.parent() {
+        storage::create_dir_all(parent)?;
+    }
+    storage::rename(&base.join(from), &to)
+}
+
+/// 删掉计划中目标（和源）所在、此时已空的目录，向上直到 base
+fn remove_empty_dirs(base: &Path, moves: &[PlannedMove]) {
+    let mut dirs: Vec<PathBuf> = moves
+        .iter()
+        .flat_map(|m| [&m.to, &m.from])
+        .flat_map(|rel| base.join(rel).ancestors().skip(1).take_while(|d| *d != base).map(Path::to_path_buf).collect::<Vec<_>>())
+        .collect();
+    // 深的目录先删
+    dirs.sort_by(|a, b| b.components().count().cmp(&a.components().count()).then_with(|| a.cmp(b)));
+    dirs.dedup();
+    for dir in dirs {
+        let _ = fs::remove_dir(dir); // 非空时失败，正好保留
+    }
+}
+
+/// 逆向移回已完成的移动，返回移回的数量；移不回的继续处理其余的，最后返回第一个错误
+fn roll_back(base: &Path, moves: &[PlannedMove]) -> Result<usize, String> {
+    let mut reverted = 0;
+    let mut first_error = None;
+    for m in moves.iter().rev().filter(|m| state(base, m) == MoveState::Done) {
+        match move_file(base, &m.to, &m.from) {
+            Ok(()) => reverted += 1,
+            Err(e) => {
+                first_error.get_or_insert(format!("移回 {} 失败: {}", m.to, e));
+            }
+        }
+    }
+    remove_empty_dirs(base, moves);
+    first_error.map_or(Ok(reverted), Err)
+}
+
+/// 写好日志、尚未开始移动的事务
+pub struct Transaction {
+    base: PathBuf,
+    journal: Journal,
+}
+
+impl Transaction {
+    /// 校验计划并写入日志。base 下已有未处理的日志时拒绝，需先 [`recover`]
+    pub fn begin(base: &Path, operation: &str, moves: Vec<PlannedMove>) -> Result<Self, String> {
+        let path = journal_path(base);
+        if path.exists() {
+            return Err(format!("{} 有未完成的移动操作，请先恢复", base.display()));
+        }
+        if let Some(m) = moves.iter().find(|m| state(base, m) != MoveState::Pending) {
+            return Err(format!("无法移动 {} → {}：源文件不存在或目标已存在", m.from, m.to));
+        }
+        let journal = Journal {
+            operation: operation.to_string(),
+            started_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            moves,
+        };
+        let content = serde_json::to_vec_pretty(&journal).map_err(|e| format!("序列化移动日志失败: {}", e))?;
+        storage::write_atomic(&path, &content).map_err(|e| format!("写入移动日志失败: {}", e))?;
+        Ok(Transaction { base: base.to_path_buf(), journal })
+    }
+
+    /// 依次移动。全部成功后删除日志；失败时移回已完成的部分、删除日志并返回错误
+    pub fn commit(self) -> Result<usize, String> {
+        for m in &self.journal.moves {
+            if let Err(e) = move_file(&self.base, &m.from, &m.to) {
+                let rollback = roll_back(&self.base, &self.journal.moves);
+                let message = match rollback {
+                    Ok(_) => {
+                        let _ = fs::remove_file(journal_path(&self.base));
+                        format!("移动 {} 失败，已回滚: {}", m.from, e)
+                    }
+                    // 回滚也失败时保留日志，留给 recover 再试
+                    Err(r) => format!("移动 {} 失败: {}；回滚未完成: {}", m.from, e, r),
+                };
+                return Err(message);
+            }
+        }
+        fs::remove_file(journal_path(&self.base)).map_err(|e| format!("删除移动日志失败: {}", e))?;
+        Ok(self.journal.moves.len())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// 日志所在目录
+    pub base: String,
+    pub operation: String,
+    /// true 为补完剩余移动，false 为移回已完成的移动
+    pub rolled_forward: bool,
+    /// 恢复前已完成的移动数
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// 处理 base 下遗留的日志；没有日志时返回 None
+pub fn recover(base: &Path) -> Result<Option<RecoveryReport>, String> {
+    let path = journal_path(base);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let journal: Journal = serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))?;
+    let states: Vec<MoveState> = journal.moves.iter().map(|m| state(base, m)).collect();
+    let completed = states.iter().filter(|s| **s == MoveState::Done).count();
+    let forward = states.iter().all(|s| *s != MoveState::Broken);
+    let rolled_forward = forward
+        && journal
+            .moves
+            .iter()
+            .zip(&states)
+            .filter(|(_, s)| **s == MoveState::Pending)
+            .all(|(m, _)| move_file(base, &m.from, &m.to).is_ok());
+    if !rolled_forward {
+        roll_back(base, &journal.moves)?;
+    }
+    remove_empty_dirs(base, &journal.moves);
+    fs::remove_file(&path).map_err(|e| format!("删除移动日志失败: {}", e))?;
+    Ok(Some(RecoveryReport {
+        base: base.display().to_string(),
+        operation: journal.operation,
+        rolled_forward,
+        completed,
+        total: journal.moves.len(),
+    }))
+}
+
+/// library_dir 下留有日志的目录（书库中每本书一层）
+pub fn pending_dirs(library_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(library_dir) else { return Vec::new() };
+    let mut dirs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| journal_path(p).is_file()).collect();
+    dirs.sort();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(tag: &str) -> (PathBuf, Vec<PlannedMove>) {
+        let base = std::env::temp_dir().join(format!("test_move_journal_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let moves: Vec<PlannedMove> = (1..=3)
+            .map(|i| {
+                fs::write(base.join(format!("{:02}.txt", i)), format!("第{}章", i)).unwrap();
+                PlannedMove { from: format!("{:02}.txt", i), to: format!("chapters/00{}/{:02}.txt", i / 2, i) }
+            })
+            .collect();
+        (base, moves)
+    }
+
+    fn all_at(base: &Path, moves: &[PlannedMove], target: bool) -> bool {
+        moves.iter().all(|m| {
+            let (here, gone) = if target { (&m.to, &m.from) } else { (&m.from, &m.to) };
+            base.join(here).is_file() && !base.join(gone).exists()
+        })
+    }
+
+    #[test]
+    fn interrupted_after_each_step_recovers_to_a_consistent_state() {
+        for done in 0..=3 {
+            let (base, moves) = setup(&format!("step{}", done));
+            let tx = Transaction::begin(&base, "shard_novel", moves.clone()).unwrap();
+            assert!(Transaction::begin(&base, "shard_novel", moves.clone()).is_err(), "已有日志时拒绝");
+            // 模拟进程在第 done 个移动之后退出
+            for m in &moves[..done] {
+                move_file(&base, &m.from, &m.to).unwrap();
+            }
+            drop(tx);
+            let report = recover(&base).unwrap().unwrap();
+            assert_eq!((report.completed, report.total, report.rolled_forward), (done, 3, true));
+            assert!(all_at(&base, &moves, true));
+            assert!(!journal_path(&base).exists());
+            assert_eq!(recover(&base).unwrap(), None);
+            let _ = fs::remove_dir_all(&base);
+        }
+    }
+
+    #[test]
+    fn lost_source_rolls_back_and_failed_commit_reverts() {
+        let (base, moves) = setup("rollback");
+        let tx = Transaction::begin(&base, "shard_novel", moves.clone()).unwrap();
+        move_file(&base, &moves[0].from, &moves[0].to).unwrap();
+        fs::remove_file(base.join(&moves[2].from)).unwrap();
+        drop(tx);
+        let report = recover(&base).unwrap().unwrap();
+        assert!(!report.rolled_forward);
+        assert!(all_at(&base, &moves[..2], false));
+        assert!(!base.join("chapters").exists(), "新建的空目录已删除");
+
+        // 提交时第三个移动失败（源文件被删）：前两个移回，日志删除
+        fs::write(base.join(&moves[2].from), "第3章").unwrap();
+        let tx = Transaction::begin(&base, "shard_novel", moves.clone()).unwrap();
+        fs::remove_file(base.join(&moves[2].from)).unwrap();
+        assert!(tx.commit().unwrap_err().contains("已回滚"));
+        assert!(all_at(&base, &moves[..2], false));
+        assert!(!journal_path(&base).exists());
+        let _ = fs::remove_dir_all(&base);
+    }
+}
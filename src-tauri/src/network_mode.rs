@@ -0,0 +1,191 @@
+//! 全局网络模式（"离线模式"）：按流量计费的网络下只看书、只用本机 AI 时，暂停所有访问站点的请求。
+//!
+//! - `normal`：照常请求
+//! - `paused`：请求在 [`gate`] 处排队等待（调用方上报 waiting），切回 `normal` 后继续，排队的任务不丢
+//! - `blocked`：新请求立即失败，错误信息为 [`BLOCKED_MESSAGE`]
+//!
+//! 下载、扫榜、定时更新、预取、爬虫窗口和远程 AI 接口都经过 [`gate`]；本机地址（localhost / 127.0.0.1 / ::1）
+//! 的 AI 接口不受影响。模式保存在设置的 `network_mode` 中，启动时恢复。
+//!
+//! [`paused_time`] 累计处于 paused 的总时长，按时间计的预算（如单本下载的时限）据此扣除暂停期间。
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+pub const BLOCKED_MESSAGE: &str = "网络访问已暂停";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    #[default]
+    Normal,
+    Paused,
+    Blocked,
+}
+
+impl NetworkMode {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim() {
+            "normal" => Ok(NetworkMode::Normal),
+            "paused" => Ok(NetworkMode::Paused),
+            "blocked" => Ok(NetworkMode::Blocked),
+            other => Err(format!("未知的网络模式: {}（可选 normal / paused / blocked）", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct NetworkStatus {
+    pub mode: NetworkMode,
+    /// 正在等待恢复的请求数
+    pub waiting: usize,
+    /// 其中后台任务（预取、定时扫榜等，见 [`crate::priority`]）发起的请求数
+    pub waiting_background: usize,
+}
+
+fn sender() -> &'static watch::Sender<NetworkMode> {
+    static MODE: OnceLock<watch::Sender<NetworkMode>> = OnceLock::new();
+    MODE.get_or_init(|| watch::Sender::new(NetworkMode::Normal))
+}
+
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+static WAITING_BACKGROUND: AtomicUsize = AtomicUsize::new(0);
+
+/// 等待中的计数，等待的 future 被取消时也能扣回
+struct WaitingGuard {
+    background: bool,
+}
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        WAITING.fetch_sub(1, Ordering::Relaxed);
+        if self.background {
+            WAITING_BACKGROUND.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// (此前暂停的累计时长, 当前这次暂停的开始时刻)
+static PAUSED: Mutex<(Duration, Option<Instant>)> = Mutex::new((Duration::ZERO, None));
+
+pub fn mode() -> NetworkMode {
+    *sender().borrow()
+}
+
+/// 切换模式并唤醒所有等待者：切回 normal 的放行，切到 blocked 的以错误返回
+pub fn set_mode(mode: NetworkMode) {
+    {
+        let mut paused = PAUSED.lock().unwrap_or_else(|e| e.into_inner());
+        match (mode, paused.1) {
+            (NetworkMode::Paused, None) => paused.1 = Some(Instant::now()),
+            (NetworkMode::Paused, Some(_)) => {}
+            (_, Some(since)) => *paused = (paused.0 + since.elapsed(), None),
+            (_, None) => {}
+        }
+    }
+    sender().send_replace(mode);
+}
+
+/// 进程启动以来处于 paused 模式的总时长（含正在进行的这一次）
+pub fn paused_time() -> Duration {
+    let paused = PAUSED.lock().unwrap_or_else(|e| e.into_inner());
+    paused.0 + paused.1.map(|since| since.elapsed()).unwrap_or_default()
+}
+
+pub fn status() -> NetworkStatus {
+    NetworkStatus {
+        mode: mode(),
+        waiting: WAITING.load(Ordering::Relaxed),
+        waiting_background: WAITING_BACKGROUND.load(Ordering::Relaxed),
+    }
+}
+
+/// 目标是否为本机地址（本机 AI 服务在任何模式下都放行）
+pub fn is_local(url: &str) -> bool {
+    let Ok(parsed) = url::Url::parse(url) else { return false };
+    match parsed.host() {
+        Some(url::Host::Domain(host)) => host.eq_ignore_ascii_case("localhost") || host.ends_with(".localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+/// 发起访问 target 的请求前调用。paused 时先调用一次 on_wait（参数为等待中的请求数）再等到模式变化；
+/// blocked 时返回错误。本机地址直接放行。
+pub async fn gate(target: &str, on_wait: impl FnOnce(usize)) -> Result<(), String> {
+    if is_local(target) {
+        return Ok(());
+    }
+    let mut rx = sender().subscribe();
+    let mut on_wait = Some(on_wait);
+    let mut waiting: Option<WaitingGuard> = None;
+    loop {
+        match *rx.borrow_and_update() {
+            NetworkMode::Normal => return Ok(()),
+            NetworkMode::Blocked => return Err(format!("{}: {}", BLOCKED_MESSAGE, target)),
+            NetworkMode::Paused => {}
+        }
+        if waiting.is_none() {
+            let count = WAITING.fetch_add(1, Ordering::Relaxed) + 1;
+            let background = crate::priority::current() == crate::priority::Priority::Background;
+            if background {
+                WAITING_BACKGROUND.fetch_add(1, Ordering::Relaxed);
+            }
+            waiting = Some(WaitingGuard { background });
+            if let Some(f) = on_wait.take() {
+                f(count);
+            }
+        }
+        if rx.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn paused_requests_wait_and_resume_blocked_fail_fast() {
+        assert!(is_local("http://127.0.0.1:11434/v1") && is_local("http://localhost:8080") && is_local("http://[::1]/v1"));
+        assert!(!is_local("https://www.qidian.com/book/1") && !is_local("not a url"));
+        assert_eq!(NetworkMode::parse("paused"), Ok(NetworkMode::Paused));
+        assert!(NetworkMode::parse("offline").is_err());
+
+        set_mode(NetworkMode::Blocked);
+        let err = gate("https://fanqienovel.com/page/1", |_| {}).await.unwrap_err();
+        assert!(err.starts_with(BLOCKED_MESSAGE));
+        assert_eq!(gate("http://localhost:11434/v1", |_| {}).await, Ok(()));
+
+        let before = paused_time();
+        set_mode(NetworkMode::Paused);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let queued = tokio::spawn(gate("https://fanqienovel.com/page/2", move |n| {
+            let _ = tx.send(n);
+        }));
+        assert_eq!(rx.await, Ok(1));
+        assert_eq!(status().waiting, 1);
+        let background = tokio::spawn(crate::priority::background(gate("https://fanqienovel.com/page/3", |_| {})));
+        while status().waiting < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(status().waiting_background, 1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+
+        set_mode(NetworkMode::Normal);
+        let paused = paused_time() - before;
+        assert!(paused >= Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(paused_time() - before, paused, "恢复后不再累计");
+        assert_eq!(queued.await.unwrap(), Ok(()));
+        assert_eq!(background.await.unwrap(), Ok(()));
+        assert_eq!(status(), NetworkStatus { mode: NetworkMode::Normal, waiting: 0, waiting_background: 0 });
+    }
+}
@@ -0,0 +1,466 @@
+//! `info.json` 的读写入口。
+//!
+//! 所有"读-改-写"都在 [`crate::storage::novel_lock`] 内完成，并且在锁内重新读取文件，
+//! 不信任调用方更早读到的旧值，最后通过原子写入落盘。
+//!
+//! `user` 对象是手动维护的字段（个人评分、笔记、修正后的题材等），
+//! 爬虫刷新与 AI 合并都走 [`merge_info`]，该函数永远不会改动 `user`。
+//! 只有 [`set_user_fields`] 能写入它。
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+use crate::source_check::{self, SourceStatus};
+use crate::{library, library_export, storage};
+
+pub const INFO_FILE: &str = "info.json";
+pub const USER_KEY: &str = "user";
+/// `user` 中的归档标记：归档的书留在磁盘上，但不参与自动更新、扫榜抓取和默认的书库列表
+pub const ARCHIVED_KEY: &str = "archived";
+/// 文件格式版本。缺失表示旧文件，首次写入时做一次 user 字段迁移。
+const VERSION_KEY: &str = "info_version";
+const INFO_VERSION: u64 = 2;
+
+/// 旧版程序写入的顶层字段。迁移时其余顶层字段视为手动添加，移入 `user`。
+const SYSTEM_KEYS: &[&str] = &[
+    "title",
+    "url",
+    "platform",
+    "tags",
+    "word_count",
+    "description",
+    "ai_analysis",
+    USER_KEY,
+];
+
+/// 一次性迁移：把手动添加的顶层字段移入 `user`（`user` 里已有的同名字段优先保留），
+/// 并写上版本号。已迁移过的文件不再处理。
+fn migrate_user_fields(info: &mut Map<String, Value>) {
+    if info.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0) >= INFO_VERSION {
+        return;
+    }
+    let unknown: Vec<String> = info
+        .keys()
+        .filter(|k| !SYSTEM_KEYS.contains(&k.as_str()))
+        .cloned()
+        .collect();
+    info.insert(VERSION_KEY.to_string(), Value::from(INFO_VERSION));
+    let has_bad_user = info.get(USER_KEY).is_some_and(|v| !v.is_object());
+    if unknown.is_empty() && !has_bad_user {
+        return;
+    }
+
+    let mut user = match info.remove(USER_KEY) {
+        Some(Value::Object(obj)) => obj,
+        Some(other) => {
+            let mut obj = Map::new();
+            obj.insert("_legacy".to_string(), other);
+            obj
+        }
+        None => Map::new(),
+    };
+    for key in unknown {
+        if let Some(v) = info.remove(&key) {
+            user.entry(key).or_insert(v);
+        }
+    }
+    info.insert(USER_KEY.to_string(), Value::Object(user));
+}
+
+pub fn info_path(novel_dir: &Path) -> PathBuf {
+    novel_dir.join(INFO_FILE)
+}
+
+/// 读取 info.json 为 JSON 对象。文件不存在或不是对象时返回错误。
+pub fn read_info(novel_dir: &Path) -> Result<Map<String, Value>, String> {
+    let path = info_path(novel_dir);
+    let content = storage::read_to_string(&path)
+        .map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(obj)) => Ok(obj),
+        Ok(_) => Err(format!("{} 不是 JSON 对象", path.display())),
+        Err(e) => Err(format!("解析 {} 失败: {}", path.display(), e)),
+    }
+}
+
+fn write_info(novel_dir: &Path, obj: &Map<String, Value>) -> Result<(), String> {
+    let path = info_path(novel_dir);
+    let content = serde_json::to_string_pretty(obj).map_err(|e| format!("序列化 info.json 失败: {}", e))?;
+    storage::write_atomic(&path, content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))
+}
+
+/// 在小说锁内对 info.json 做一次读-改-写。
+///
+/// `create_if_missing` 为 false 时文件不存在直接报错（与旧版 update_novel_metadata 行为一致）。
+pub async fn update_info<F>(novel_dir: &Path, create_if_missing: bool, apply: F) -> Result<Map<String, Value>, String>
+where
+    F: FnOnce(&mut Map<String, Value>),
+{
+    let lock = storage::novel_lock(novel_dir);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut current = if info_path(novel_dir).exists() {
+        read_info(novel_dir)?
+    } else if create_if_missing {
+        storage::create_dir_all(novel_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+        Map::new()
+    } else {
+        return Err("info.json not found".to_string());
+    };
+
+    migrate_user_fields(&mut current);
+    apply(&mut current);
+    write_info(novel_dir, &current)?;
+    Ok(current)
+}
+
+/// 浅合并：把 patch 中的顶层字段逐个覆盖写入 info.json。patch 中的 `user` 会被忽略。
+pub async fn merge_info(novel_dir: &Path, patch: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    update_info(novel_dir, false, |current| apply_patch(current, patch)).await
+}
+
+/// 同 [`merge_info`]，但 info.json 不存在时会创建（下载新书时使用）。
+pub async fn merge_or_create_info(novel_dir: &Path, patch: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    update_info(novel_dir, true, |current| apply_patch(current, patch)).await
+}
+
+fn apply_patch(current: &mut Map<String, Value>, patch: &Map<String, Value>) {
+    for (k, v) in patch {
+        if k == USER_KEY || k == VERSION_KEY {
+            continue;
+        }
+        current.insert(k.clone(), v.clone());
+    }
+}
+
+/// 按目录中实际的章节文件重新统计字数，写入 `downloaded_chars` / `downloaded_chapters`；
+/// 站点显示的 `word_count` 字符串保持不变，能解析时另存为 `reported_chars`。
+pub async fn refresh_download_stats(novel_dir: &Path) -> Result<library::DownloadedStats, String> {
+    let mut stats = library::DownloadedStats::default();
+    update_info(novel_dir, false, |current| {
+        stats = library::downloaded_stats(novel_dir);
+        current.insert("downloaded_chars".to_string(), Value::from(stats.chars));
+        current.insert("downloaded_chapters".to_string(), Value::from(stats.chapters));
+        let reported = current
+            .get("word_count")
+            .and_then(Value::as_str)
+            .and_then(library::parse_reported_chars);
+        current.insert("reported_chars".to_string(), reported.map(Value::from).unwrap_or(Value::Null));
+    })
+    .await?;
+    Ok(stats)
+}
+
+/// 读取 `user` 字段（含尚未迁移的手动顶层字段）。
+pub fn read_user_fields(novel_dir: &Path) -> Result<Map<String, Value>, String> {
+    let mut info = read_info(novel_dir)?;
+    migrate_user_fields(&mut info);
+    match info.remove(USER_KEY) {
+        Some(Value::Object(user)) => Ok(user),
+        _ => Ok(Map::new()),
+    }
+}
+
+/// 是否已归档；没有 info.json 时视为未归档。
+pub fn is_archived(novel_dir: &Path) -> bool {
+    read_user_fields(novel_dir)
+        .ok()
+        .and_then(|user| user.get(ARCHIVED_KEY).and_then(Value::as_bool))
+        .unwrap_or(false)
+}
+
+/// 合并写入 `user` 字段；值为 null 表示删除该字段。
+pub async fn set_user_fields(novel_dir: &Path, fields: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    let info = update_info(novel_dir, false, |current| {
+        let user = current
+            .entry(USER_KEY.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(user) = user {
+            for (k, v) in fields {
+                if v.is_null() {
+                    user.remove(k);
+                } else {
+                    user.insert(k.clone(), v.clone());
+                }
+            }
+        }
+    })
+    .await?;
+    match info.get(USER_KEY) {
+        Some(Value::Object(user)) => Ok(user.clone()),
+        _ => Ok(Map::new()),
+    }
+}
+
+/// 前端读取的书籍信息：info.json 按固定结构校验、补默认值后的结果，不直接返回原始 JSON。
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct NovelInfo {
+    pub title: String,
+    pub url: Option<String>,
+    pub platform: Option<String>,
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    pub honors: Vec<String>,
+    /// 站点显示的字数，原样保留
+    pub word_count: String,
+    pub description: String,
+    pub metadata_truncated: Vec<String>,
+    pub metadata_source: Option<String>,
+    /// 自动分析写入的对象，不是对象时视为没有
+    pub ai_analysis: Option<Map<String, Value>>,
+    pub downloaded_chars: u64,
+    pub downloaded_chapters: usize,
+    pub reported_chars: Option<u64>,
+    pub downloaded_at: Option<String>,
+    pub user: Map<String, Value>,
+    pub archived: bool,
+    pub source_status: Option<SourceStatus>,
+    /// 上面没有列出的字段，原样保留
+    pub extra: Map<String, Value>,
+    /// info.json 缺失或损坏时的原因；此时其余字段为默认值
+    pub load_error: Option<String>,
+}
+
+/// 数字、布尔值转成字符串，空字符串视为没有
+fn coerce_string(value: Option<Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// 数组逐项转字符串；旧文件里逗号分隔的字符串也拆开
+fn coerce_list(value: Option<Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items.into_iter().filter_map(|v| coerce_string(Some(v))).collect(),
+        Some(Value::String(s)) => s
+            .split([',', '，', '、'])
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn coerce_u64(value: Option<Value>) -> Option<u64> {
+    match value? {
+        Value::Number(n) => n.as_u64().or_else(|| n.as_f64().filter(|f| *f >= 0.0).map(|f| f as u64)),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// 读取并校验 info.json。文件缺失或损坏时不报错，书名取目录名、其余字段取默认值，
+/// 并在 `load_error` 中说明原因；统计字段缺失时按目录中的章节文件现算。
+pub fn load_typed(novel_dir: &Path) -> NovelInfo {
+    let dir_title = novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (mut info, load_error) = match read_info(novel_dir) {
+        Ok(info) => (info, None),
+        Err(e) => (Map::new(), Some(e)),
+    };
+    migrate_user_fields(&mut info);
+    info.remove(VERSION_KEY);
+
+    let source_status = source_check::from_info(&info);
+    info.remove(source_check::INFO_KEY);
+    let user = match info.remove(USER_KEY) {
+        Some(Value::Object(user)) => user,
+        _ => Map::new(),
+    };
+    let archived = user.get(ARCHIVED_KEY).and_then(Value::as_bool).unwrap_or(false);
+    let word_count = coerce_string(info.remove("word_count")).unwrap_or_default();
+    let reported_chars = coerce_u64(info.remove("reported_chars")).or_else(|| library::parse_reported_chars(&word_count));
+    let (chars, chapters) = (coerce_u64(info.remove("downloaded_chars")), coerce_u64(info.remove("downloaded_chapters")));
+    let (downloaded_chars, downloaded_chapters) = match (chars, chapters) {
+        (Some(chars), Some(chapters)) => (chars, chapters as usize),
+        _ => {
+            let stats = library::downloaded_stats(novel_dir);
+            (stats.chars, stats.chapters)
+        }
+    };
+
+    NovelInfo {
+        title: coerce_string(info.remove("title")).unwrap_or(dir_title),
+        url: coerce_string(info.remove("url")),
+        platform: coerce_string(info.remove("platform")),
+        author: coerce_string(info.remove("author")),
+        tags: coerce_list(info.remove("tags")),
+        honors: coerce_list(info.remove("honors")),
+        word_count,
+        description: coerce_string(info.remove("description")).unwrap_or_default(),
+        metadata_truncated: coerce_list(info.remove("metadata_truncated")),
+        metadata_source: coerce_string(info.remove("metadata_source")),
+        ai_analysis: match info.remove("ai_analysis") {
+            Some(Value::Object(obj)) => Some(obj),
+            _ => None,
+        },
+        downloaded_chars,
+        downloaded_chapters,
+        reported_chars,
+        downloaded_at: coerce_string(info.remove(library_export::DOWNLOADED_AT_KEY)),
+        user,
+        archived,
+        source_status,
+        extra: info,
+        load_error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    fn temp_novel_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_novel_info_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_merges_do_not_lose_fields() {
+        let dir = temp_novel_dir("concurrent");
+        fs::write(info_path(&dir), r#"{"title":"测试小说"}"#).unwrap();
+
+        let mut handles = Vec::new();
+        for i in 0..32 {
+            let dir = dir.clone();
+            handles.push(tokio::spawn(async move {
+                let mut patch = Map::new();
+                patch.insert(format!("field_{}", i), json!(i));
+                merge_info(&dir, &patch).await
+            }));
+        }
+        for h in handles {
+            h.await.unwrap().unwrap();
+        }
+
+        let info = read_info(&dir).unwrap();
+        assert_eq!(info["title"], "测试小说");
+        for i in 0..32 {
+            assert_eq!(info[&format!("field_{}", i)], json!(i), "field_{} 丢失", i);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn merge_never_touches_user_fields() {
+        let dir = temp_novel_dir("protected");
+        fs::write(info_path(&dir), r#"{"title":"旧书名","user":{"rating":9,"genre":"修正题材"}}"#).unwrap();
+
+        let patch = json!({"title":"新书名","user":{"rating":1}}).as_object().cloned().unwrap();
+        let info = merge_info(&dir, &patch).await.unwrap();
+        assert_eq!(info["title"], "新书名");
+        assert_eq!(info["user"], json!({"rating":9,"genre":"修正题材"}));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn unknown_top_level_keys_migrate_into_user() {
+        let dir = temp_novel_dir("migrate");
+        fs::write(info_path(&dir), r#"{"title":"书","my_note":"好看","user":{"rating":8}}"#).unwrap();
+
+        assert_eq!(read_user_fields(&dir).unwrap(), json!({"rating":8,"my_note":"好看"}).as_object().cloned().unwrap());
+
+        let info = merge_info(&dir, &Map::new()).await.unwrap();
+        assert!(info.get("my_note").is_none());
+        assert_eq!(info["user"]["my_note"], "好看");
+        assert_eq!(info["user"]["rating"], 8);
+
+        // 迁移只做一次：之后程序写入的新顶层字段保持原位
+        let patch = json!({"new_field": 1}).as_object().cloned().unwrap();
+        let info = merge_info(&dir, &patch).await.unwrap();
+        assert_eq!(info["new_field"], 1);
+        assert!(info["user"].get("new_field").is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn set_user_fields_merges_and_deletes() {
+        let dir = temp_novel_dir("set_user");
+        fs::write(info_path(&dir), r#"{"title":"书","user":{"rating":8,"note":"旧"}}"#).unwrap();
+
+        let fields = json!({"rating":10,"note":null}).as_object().cloned().unwrap();
+        let user = set_user_fields(&dir, &fields).await.unwrap();
+        assert_eq!(Value::Object(user), json!({"rating":10}));
+        assert_eq!(read_info(&dir).unwrap()["title"], "书");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn archived_flag_lives_under_user() {
+        let dir = temp_novel_dir("archived");
+        assert!(!is_archived(&dir));
+        fs::write(info_path(&dir), r#"{"title":"书"}"#).unwrap();
+        assert!(!is_archived(&dir));
+
+        let fields = json!({ARCHIVED_KEY: true}).as_object().cloned().unwrap();
+        set_user_fields(&dir, &fields).await.unwrap();
+        assert!(is_archived(&dir));
+        // 爬虫刷新不会清掉归档标记
+        let patch = json!({"title":"新书名","archived":false}).as_object().cloned().unwrap();
+        merge_info(&dir, &patch).await.unwrap();
+        assert!(is_archived(&dir));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn download_stats_keep_site_word_count() {
+        let dir = temp_novel_dir("stats");
+        fs::write(info_path(&dir), r#"{"title":"书","word_count":"123.4万字"}"#).unwrap();
+        let body = "正".repeat(30);
+        fs::write(dir.join("01.txt"), library::render_chapter_file("第一章", "https://example.com/1", &body)).unwrap();
+
+        let stats = refresh_download_stats(&dir).await.unwrap();
+        assert_eq!(stats.chapters, 1);
+        let info = read_info(&dir).unwrap();
+        assert_eq!(info["word_count"], "123.4万字");
+        assert_eq!(info["reported_chars"], 1_234_000);
+        assert_eq!(info["downloaded_chars"], 30);
+        assert_eq!(info["downloaded_chapters"], 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn merge_requires_existing_file() {
+        let dir = temp_novel_dir("missing");
+        let err = merge_info(&dir, &Map::new()).await.unwrap_err();
+        assert!(err.contains("not found"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn typed_info_coerces_fields_and_keeps_extra_keys() {
+        let dir = temp_novel_dir("typed");
+        fs::write(
+            info_path(&dir),
+            r#"{"info_version":2,"title":"书","tags":"玄幻, 系统","word_count":1200,"ai_analysis":"无",
+                "source_status":{"state":"removed","checked_at":"2024-01-01"},
+                "analysis_score":{"pace":7},"user":{"archived":true}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("01.txt"), library::render_chapter_file("第一章", "https://example.com/1", "正文")).unwrap();
+
+        let info = load_typed(&dir);
+        assert_eq!(info.tags, vec!["玄幻", "系统"]);
+        assert_eq!(info.word_count, "1200");
+        assert_eq!(info.ai_analysis, None);
+        assert_eq!(info.downloaded_chapters, 1);
+        assert!(info.archived);
+        assert_eq!(info.source_status.map(|s| s.state), Some(source_check::SourceState::Removed));
+        assert_eq!(Value::Object(info.extra), json!({"analysis_score":{"pace":7}}));
+
+        fs::write(info_path(&dir), "{\"title\":").unwrap();
+        let broken = load_typed(&dir);
+        assert!(broken.load_error.is_some());
+        assert_eq!(broken.title, dir.file_name().unwrap().to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
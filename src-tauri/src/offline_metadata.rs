@@ -0,0 +1,180 @@
+//! 离线重建 info.json：来源网站下线、书是导入的或 info.json 丢失时，只凭本地章节文件生成元数据。
+//!
+//! 标题取已有 info.json 中的值或目录名，章节数和字数按章节文件统计；提供 AI 配置时再用自动分析
+//! 提示词（[`prompts::AUTO_ANALYSIS`]）分析前几章，得到题材 / 风格 / 金手指等，写入 `ai_analysis`，
+//! 原来没有标签的书按题材补上 `tags`。结果经 [`novel_info::merge_or_create_info`] 合并，
+//! 不会改动 `user`，并标记 `metadata_source: "offline-rebuild"`。
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+use crate::errors::AppError;
+use crate::library::{self, ChapterFile};
+use crate::{ai, novel_info, prompts, sharding, storage};
+
+pub const METADATA_SOURCE_KEY: &str = "metadata_source";
+pub const OFFLINE_REBUILD: &str = "offline-rebuild";
+/// 自动分析读取的章节数，与前端自动分析一致
+pub const SAMPLE_CHAPTERS: usize = 5;
+/// 送给 AI 的正文字符上限
+pub const SAMPLE_CHARS: usize = 15000;
+/// 没有来源平台的书登记到书库数据库时使用的平台名
+pub const LOCAL_PLATFORM: &str = "local";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RebuildReport {
+    pub title: String,
+    pub chapters: usize,
+    pub downloaded_chars: u64,
+    /// 本次是否成功写入了 AI 分析
+    pub analyzed: bool,
+    /// 重建后的 info.json
+    pub info: Map<String, Value>,
+}
+
+/// 只凭本地文件得到的字段。已有 info.json 的标题优先，没有时用目录名。
+pub fn local_patch(novel_dir: &Path, existing: &Map<String, Value>) -> Map<String, Value> {
+    let title = existing
+        .get("title")
+        .and_then(Value::as_str)
+        .filter(|t| !t.trim().is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+    let stats = library::downloaded_stats(novel_dir);
+    let mut patch = Map::new();
+    patch.insert("title".into(), title.into());
+    patch.insert("downloaded_chars".into(), stats.chars.into());
+    patch.insert("downloaded_chapters".into(), stats.chapters.into());
+    patch.insert(METADATA_SOURCE_KEY.into(), OFFLINE_REBUILD.into());
+    patch.insert(
+        "metadata_rebuilt_at".into(),
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string().into(),
+    );
+    patch
+}
+
+/// 前 [`SAMPLE_CHAPTERS`] 章正文，截断到 [`SAMPLE_CHARS`] 个字符
+pub fn sample_content(novel_dir: &Path) -> String {
+    let mut content = String::new();
+    for (index, file) in sharding::chapter_files(novel_dir).into_iter().take(SAMPLE_CHAPTERS) {
+        let Ok(text) = storage::read_to_string(&novel_dir.join(&file)) else { continue };
+        let body = ChapterFile::parse(&text).map(|c| c.body).unwrap_or(text);
+        content.push_str(&format!("\n\n--- 第 {} 章 ---\n\n{}", index, body.trim()));
+    }
+    content.chars().take(SAMPLE_CHARS).collect()
+}
+
+/// 从 AI 返回中取出 JSON 对象：去掉 `<think>` 段和 Markdown 代码块，再取最外层的 `{}`
+pub fn parse_analysis(raw: &str) -> Option<Map<String, Value>> {
+    let mut text = raw.to_string();
+    while let Some(start) = text.find("<think>") {
+        let Some(end) = text[start..].find("</think>") else { break };
+        text.replace_range(start..start + end + "</think>".len(), "");
+    }
+    let (start, end) = (text.find('{')?, text.rfind('}')?);
+    match serde_json::from_str::<Value>(text.get(start..=end)?) {
+        Ok(Value::Object(obj)) => Some(obj),
+        _ => None,
+    }
+}
+
+/// 题材字符串拆成标签，如 "玄幻/系统流、都市" → ["玄幻", "系统流", "都市"]
+pub fn tags_from_genre(genre: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in genre.split(['/', '、', ',', '，', '|', ' ']).map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+fn has_tags(info: &Map<String, Value>) -> bool {
+    info.get("tags").and_then(Value::as_array).is_some_and(|t| !t.is_empty())
+}
+
+/// 用自动分析提示词分析前几章，返回分析结果对象
+pub async fn analyze(novel_dir: &Path, config: &ai::AiConfig) -> Result<Map<String, Value>, AppError> {
+    let content = sample_content(novel_dir);
+    if content.trim().is_empty() {
+        return Err(AppError::invalid_input("没有可供分析的章节"));
+    }
+    let prompt = prompts::builtin(prompts::AUTO_ANALYSIS).map(|t| t.content).unwrap_or_default();
+    let (prompt, content) = ai::analysis_messages(&prompt, &content);
+    let raw = ai::call_ai(config.clone(), prompt, content, false).await?;
+    parse_analysis(&raw).ok_or_else(|| {
+        let preview: String = raw.chars().take(200).collect();
+        AppError::invalid_input(format!("AI 返回的不是 JSON 对象: {}", preview))
+    })
+}
+
+/// 重建并合并 info.json。config 为 None 时只写本地统计字段，已有的 ai_analysis 保持不变。
+pub async fn rebuild(novel_dir: &Path, config: Option<&ai::AiConfig>) -> Result<RebuildReport, AppError> {
+    if sharding::chapter_files(novel_dir).is_empty() {
+        return Err(AppError::invalid_input(format!("{} 下没有章节文件", novel_dir.display())));
+    }
+    let existing = novel_info::read_info(novel_dir).unwrap_or_default();
+    let mut patch = local_patch(novel_dir, &existing);
+    let analysis = match config {
+        Some(config) => Some(analyze(novel_dir, config).await?),
+        None => None,
+    };
+    if let Some(analysis) = &analysis {
+        if !has_tags(&existing) {
+            let tags = analysis.get("genre").and_then(Value::as_str).map(tags_from_genre).unwrap_or_default();
+            if !tags.is_empty() {
+                patch.insert("tags".into(), tags.into());
+            }
+        }
+        patch.insert("ai_analysis".into(), Value::Object(analysis.clone()));
+    }
+    let info = novel_info::merge_or_create_info(novel_dir, &patch).await?;
+    Ok(RebuildReport {
+        title: patch["title"].as_str().unwrap_or_default().to_string(),
+        chapters: patch["downloaded_chapters"].as_u64().unwrap_or(0) as usize,
+        downloaded_chars: patch["downloaded_chars"].as_u64().unwrap_or(0),
+        analyzed: analysis.is_some(),
+        info,
+    })
+}
+
+/// 把重建后的书登记到书库数据库，使它出现在 list_novels 和标签筛选中。
+/// 以 平台 + 书名为键，没有来源平台的书记为 [`LOCAL_PLATFORM`]；author 缺失时写空串。
+pub fn register(conn: &rusqlite::Connection, info: &Map<String, Value>) -> rusqlite::Result<i64> {
+    let text = |key: &str| info.get(key).and_then(Value::as_str).unwrap_or_default();
+    let title = text("title");
+    let platform = Some(text("platform")).filter(|p| !p.is_empty()).unwrap_or(LOCAL_PLATFORM);
+    let tags: Vec<&str> = info.get("tags").and_then(Value::as_array).map(|t| t.iter().filter_map(Value::as_str).collect()).unwrap_or_default();
+    let chars = info.get("downloaded_chars").and_then(Value::as_i64).unwrap_or(0);
+    crate::db::upsert_novel(conn, title, platform, title, text("author"), &tags.join(","), chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_local_fields_and_parses_analysis() {
+        let dir = std::env::temp_dir().join(format!("test_offline_metadata_{}", std::process::id())).join("导入的书");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 1..=2 {
+            let text = library::render_chapter_file(&format!("第{}章", i), "", "正文 内容");
+            std::fs::write(dir.join(library::chapter_file_name(i)), text).unwrap();
+        }
+        let patch = local_patch(&dir, &Map::new());
+        assert_eq!(patch["title"], "导入的书");
+        assert_eq!(patch["downloaded_chapters"], 2);
+        assert_eq!(patch["downloaded_chars"], 8);
+        assert_eq!(patch[METADATA_SOURCE_KEY], OFFLINE_REBUILD);
+        assert!(sample_content(&dir).contains("--- 第 2 章 ---"));
+
+        let raw = "<think>先看开篇</think>```json\n{\"genre\": \"玄幻/系统、玄幻\", \"style\": \"热血\"}\n```";
+        let analysis = parse_analysis(raw).unwrap();
+        assert_eq!(analysis["style"], "热血");
+        assert_eq!(tags_from_genre(analysis["genre"].as_str().unwrap()), vec!["玄幻", "系统"]);
+        assert!(parse_analysis("无法分析").is_none());
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
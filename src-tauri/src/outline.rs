@@ -0,0 +1,138 @@
+//! Markdown 细纲（chapter_outline 提示词的输出格式）的结构解析，提示词对比和分析片段提取共用。
+//!
+//! 节点是 `### 1. [标题]` 形式的编号标题，节点下 `> **概括**: …`、`> **目的**: …` 行为字段；
+//! 标题含"本章核心总结"的段落为总结块。其他非编号标题结束当前节点，内容不归入任何节点。
+
+/// 总结块标题中的关键词
+pub const SUMMARY_HEADING: &str = "本章核心总结";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutlineNode {
+    pub number: usize,
+    /// 去掉方括号的标题
+    pub title: String,
+    pub summary: Option<String>,
+    pub purpose: Option<String>,
+    /// 从标题行到下一个标题之前的原文
+    pub markdown: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outline {
+    pub nodes: Vec<OutlineNode>,
+    /// 总结块原文（含标题行）
+    pub summary: Option<String>,
+}
+
+/// `### 1. 标题` 形式的节点序号和标题
+pub fn node_heading(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start_matches('#').trim_start();
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    let number = rest[..digits].parse().ok()?;
+    let title = rest[digits..].strip_prefix(['.', '、'])?;
+    Some((number, title.trim()))
+}
+
+/// `**概括**: 内容` 行的内容；引用符号和冒号前后的空白都可有可无
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let text = line.trim_start().trim_start_matches('>').trim();
+    let value = text.strip_prefix("**")?.strip_prefix(name)?.strip_prefix("**")?;
+    let value = value.trim_start_matches([':', '：', ' ']).trim();
+    (!value.is_empty()).then_some(value)
+}
+
+enum Section {
+    Node,
+    Summary,
+    Other,
+}
+
+/// 解析细纲；没有任何编号节点时为 None（不是细纲格式）
+pub fn parse(body: &str) -> Option<Outline> {
+    let mut outline = Outline::default();
+    let mut summary: Option<Vec<&str>> = None;
+    let mut node_lines: Vec<&str> = Vec::new();
+    let mut section = Section::Other;
+    for line in body.lines() {
+        if line.trim_start().starts_with('#') {
+            if let Some(last) = outline.nodes.last_mut().filter(|_| matches!(section, Section::Node)) {
+                last.markdown = node_lines.join("\n").trim_end().to_string();
+            }
+            node_lines.clear();
+            if let Some((number, title)) = node_heading(line) {
+                outline.nodes.push(OutlineNode { number, title: title.trim_matches(['[', ']']).to_string(), ..Default::default() });
+                node_lines.push(line);
+                section = Section::Node;
+            } else if line.contains(SUMMARY_HEADING) && summary.is_none() {
+                summary = Some(vec![line]);
+                section = Section::Summary;
+            } else {
+                section = Section::Other;
+            }
+            continue;
+        }
+        match section {
+            Section::Node => {
+                node_lines.push(line);
+                let last = outline.nodes.last_mut().expect("节点段落必有节点");
+                if last.summary.is_none() {
+                    last.summary = field(line, "概括").map(str::to_string);
+                }
+                if last.purpose.is_none() {
+                    last.purpose = field(line, "目的").map(str::to_string);
+                }
+            }
+            Section::Summary => summary.as_mut().expect("总结段落必有标题").push(line),
+            Section::Other => {}
+        }
+    }
+    if let Some(last) = outline.nodes.last_mut().filter(|_| matches!(section, Section::Node)) {
+        last.markdown = node_lines.join("\n").trim_end().to_string();
+    }
+    outline.summary = summary.map(|lines| lines.join("\n").trim_end().to_string());
+    (!outline.nodes.is_empty()).then_some(outline)
+}
+
+/// Markdown 转纯文本：去掉标题井号、引用符号、加粗和行内代码标记，节点标题的方括号一并去掉
+pub fn plain_text(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(|line| {
+            let mut text = line.trim_start();
+            if text.starts_with('#') {
+                text = text.trim_start_matches('#').trim_start();
+            }
+            let text = text.trim_start_matches('>').trim();
+            let text = text.replace("**", "").replace('`', "");
+            match node_heading(&format!("# {}", text)) {
+                Some((number, title)) => format!("{}. {}", number, title.trim_matches(['[', ']'])),
+                None => text,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTLINE_MD: &str = "## 细纲\n\n### 1. [开场]\n> **概括**: 齐夏醒来\n> **目的**：制造悬念\n\n### 2. [对峙]\n> **概括**: 众人争吵\n\n#### 备注\n> **概括**: 不属于节点\n\n### 💡 本章核心总结\n一句话";
+
+    #[test]
+    fn nodes_fields_and_summary_are_parsed() {
+        let outline = parse(OUTLINE_MD).unwrap();
+        assert_eq!(outline.nodes.len(), 2);
+        let first = &outline.nodes[0];
+        assert_eq!((first.number, first.title.as_str()), (1, "开场"));
+        assert_eq!((first.summary.as_deref(), first.purpose.as_deref()), (Some("齐夏醒来"), Some("制造悬念")));
+        assert_eq!(first.markdown, "### 1. [开场]\n> **概括**: 齐夏醒来\n> **目的**：制造悬念");
+        assert_eq!(outline.nodes[1].markdown, "### 2. [对峙]\n> **概括**: 众人争吵");
+        assert_eq!(outline.nodes[1].purpose, None);
+        assert_eq!(outline.summary.as_deref(), Some("### 💡 本章核心总结\n一句话"));
+        assert_eq!(plain_text(&first.markdown), "1. 开场\n概括: 齐夏醒来\n目的：制造悬念");
+        assert_eq!(parse("## 分析\n普通段落"), None);
+    }
+}
@@ -0,0 +1,284 @@
+//! 工作区相对路径。
+//!
+//! 命令统一接收 `workspace_root` + 相对路径（如 `downloads`、`downloads/<小说>`），
+//! 记录文件（info.json、chapters.json、analysis_data 等）只保存相对路径，移动工作区后仍然有效。
+//! 旧前端传入的绝对路径仍然接受，但会记录弃用警告；位于工作区内时换算为相对路径处理。
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// 解析命令传入的路径。相对路径按工作区解析，且不允许通过 `..` 跳出工作区。
+pub fn resolve(workspace_root: &Path, path: &str) -> Result<PathBuf, String> {
+    let raw = Path::new(path.trim());
+    if raw.is_absolute() {
+        return Ok(match raw.strip_prefix(workspace_root) {
+            Ok(rel) => {
+                crate::log_to_file_with_root(
+                    &format!("[Paths] 已弃用的绝对路径参数 {}，请改传相对路径 {}", path, to_slash(rel)),
+                    Some(workspace_root),
+                );
+                workspace_root.join(rel)
+            }
+            Err(_) => {
+                crate::log_to_file_with_root(
+                    &format!("[Paths] 已弃用的绝对路径参数 {}（不在工作区内）", path),
+                    Some(workspace_root),
+                );
+                raw.to_path_buf()
+            }
+        });
+    }
+    let mut resolved = workspace_root.to_path_buf();
+    for component in raw.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("路径不能跳出工作区: {}", path)),
+        }
+    }
+    Ok(resolved)
+}
+
+/// 解析 base 下的相对路径：与 [`resolve`] 相同，但不接受绝对路径（供外部请求使用，不走旧前端的兼容分支）。
+pub fn resolve_within(base: &Path, path: &str) -> Result<PathBuf, String> {
+    let raw = Path::new(path.trim());
+    if raw.is_absolute() || raw.has_root() {
+        return Err(format!("不接受绝对路径: {}", path));
+    }
+    resolve(base, path)
+}
+
+/// 解析 `<dir>/<novel_name>`，小说名必须是单层目录名。
+pub fn resolve_novel(workspace_root: &Path, dir: &str, novel_name: &str) -> Result<PathBuf, String> {
+    let mut components = Path::new(novel_name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => Ok(resolve(workspace_root, dir)?.join(name)),
+        _ => Err(format!("无效的小说目录名: {}", novel_name)),
+    }
+}
+
+fn to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 工作区内的路径转为 `/` 分隔的相对路径；不在工作区内返回 None。
+pub fn to_relative(workspace_root: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(workspace_root).ok().map(to_slash)
+}
+
+/// Windows 传统路径上限（MAX_PATH，含结尾的 NUL），按 UTF-16 码元计
+pub const WINDOWS_MAX_PATH: usize = 260;
+/// 创建目录时的上限更低：要给 8.3 短文件名留 12 个码元
+#[cfg_attr(not(windows), allow(dead_code))]
+const WINDOWS_MAX_DIR_PATH: usize = WINDOWS_MAX_PATH - 12;
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// 绝对路径加上 Windows 扩展长度前缀 `\\?\`（UNC 路径为 `\\?\UNC\`）。带前缀的路径不再经过
+/// 系统规范化，所以这里先统一分隔符并消去 `.` / `..`。已带前缀或不是绝对路径时返回 None。
+#[cfg_attr(not(windows), allow(dead_code))]
+fn extended_length(raw: &str) -> Option<String> {
+    if raw.starts_with(r"\\?\") || raw.starts_with(r"\\.\") {
+        return None;
+    }
+    let raw = raw.replace('/', r"\");
+    let (prefix, rest) = if let Some(rest) = raw.strip_prefix(r"\\") {
+        (r"\\?\UNC\".to_string(), rest.to_string())
+    } else {
+        let bytes = raw.as_bytes();
+        if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || bytes[1] != b':' || bytes[2] != b'\\' {
+            return None;
+        }
+        (format!(r"\\?\{}\", &raw[..2]), raw[3..].to_string())
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(format!("{}{}", prefix, parts.join(r"\")))
+}
+
+/// 实际调用文件系统时使用的路径。Windows 上超过传统上限的绝对路径加 `\\?\` 前缀，
+/// 否则深层工作区 + 长书名会让所有文件操作报"系统找不到指定的路径"（os error 3）；其余平台原样返回。
+pub fn long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(raw) = path.to_str() {
+        if utf16_len(raw) >= WINDOWS_MAX_DIR_PATH {
+            if let Some(extended) = extended_length(raw) {
+                return PathBuf::from(extended);
+            }
+        }
+    }
+    path.to_path_buf()
+}
+
+/// `parent` 下单层目录名 / 文件名还能用的长度（UTF-16 码元），`reserved` 为名称之后
+/// 还要再拼上的部分（子文件名等）。按 Windows 传统上限计算，各平台一致，工作区在不同系统间移动时目录名不变。
+pub fn name_budget(parent: &Path, reserved: usize) -> usize {
+    let parent_len = utf16_len(&parent.to_string_lossy());
+    (WINDOWS_MAX_PATH - 1).saturating_sub(parent_len + 1 + reserved)
+}
+
+/// 按 UTF-16 码元截断名称，并去掉末尾 Windows 不允许的空格和 `.`
+pub fn truncate_name(name: &str, budget: usize) -> String {
+    let mut used = 0;
+    let truncated: String = name
+        .chars()
+        .take_while(|c| {
+            used += c.len_utf16();
+            used <= budget
+        })
+        .collect();
+    truncated.trim_end_matches([' ', '.']).to_string()
+}
+
+/// 把 JSON 中指向工作区内的绝对路径字符串改写为相对路径，返回改写次数。
+fn rewrite_value(value: &mut serde_json::Value, workspace_root: &Path) -> usize {
+    match value {
+        serde_json::Value::String(s) => {
+            let path = Path::new(s.as_str());
+            if !path.is_absolute() {
+                return 0;
+            }
+            match to_relative(workspace_root, path) {
+                Some(rel) => {
+                    *s = rel;
+                    1
+                }
+                None => 0,
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().map(|v| rewrite_value(v, workspace_root)).sum(),
+        serde_json::Value::Object(map) => map.values_mut().map(|v| rewrite_value(v, workspace_root)).sum(),
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct NormalizeReport {
+    pub files_scanned: usize,
+    pub files_updated: Vec<String>,
+    pub paths_rewritten: usize,
+}
+
+/// 需要迁移的记录文件：各小说目录下的 json、analysis_data 下的 json、工作区根目录的 json。
+fn record_files(workspace_root: &Path) -> Vec<PathBuf> {
+    let json_in = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut files = json_in(workspace_root);
+    files.extend(json_in(&workspace_root.join("analysis_data")));
+    if let Ok(entries) = fs::read_dir(crate::library::downloads_dir(workspace_root)) {
+        for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+            files.extend(json_in(&entry.path()));
+        }
+    }
+    files.sort();
+    files
+}
+
+/// 把已有记录中的绝对路径改写为工作区相对路径。不是合法 JSON 的文件跳过。
+pub fn normalize_library_paths(workspace_root: &Path) -> Result<NormalizeReport, String> {
+    if !workspace_root.is_dir() {
+        return Err(format!("工作区不存在: {}", workspace_root.display()));
+    }
+    let mut report = NormalizeReport::default();
+    for file in record_files(workspace_root) {
+        report.files_scanned += 1;
+        let Ok(content) = fs::read_to_string(&file) else { continue };
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&content) else { continue };
+        let rewritten = rewrite_value(&mut value, workspace_root);
+        if rewritten == 0 {
+            continue;
+        }
+        let content = serde_json::to_string_pretty(&value).map_err(|e| format!("序列化 {} 失败: {}", file.display(), e))?;
+        crate::storage::write_atomic(&file, content.as_bytes()).map_err(|e| format!("写入 {} 失败: {}", file.display(), e))?;
+        report.paths_rewritten += rewritten;
+        report.files_updated.push(to_relative(workspace_root, &file).unwrap_or_else(|| file.display().to_string()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_and_rejects_escapes() {
+        let root = std::env::temp_dir().join(format!("test_paths_resolve_{}", std::process::id()));
+        assert_eq!(resolve(&root, "downloads").unwrap(), root.join("downloads"));
+        assert_eq!(resolve_novel(&root, "downloads", "书名").unwrap(), root.join("downloads").join("书名"));
+        assert_eq!(resolve(&root, root.join("downloads").to_str().unwrap()).unwrap(), root.join("downloads"));
+        assert!(resolve(&root, "../outside").is_err());
+        assert!(resolve_novel(&root, "downloads", "../x").is_err());
+        assert!(resolve_novel(&root, "downloads", "a/b").is_err());
+        assert_eq!(resolve_within(&root, "downloads/书名").unwrap(), root.join("downloads").join("书名"));
+        assert!(resolve_within(&root, root.join("downloads").to_str().unwrap()).is_err());
+        assert!(resolve_within(&root, "/etc/passwd").is_err());
+        assert!(resolve_within(&root, "a/../../b").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn extended_prefix_and_name_budget() {
+        assert_eq!(extended_length(r"C:\ws\downloads\.\书\..\书名\01.txt").as_deref(), Some(r"\\?\C:\ws\downloads\书名\01.txt"));
+        assert_eq!(extended_length("D:/ws/书名").as_deref(), Some(r"\\?\D:\ws\书名"));
+        assert_eq!(extended_length(r"\\nas\share\ws").as_deref(), Some(r"\\?\UNC\nas\share\ws"));
+        assert_eq!(extended_length(r"\\?\C:\ws"), None);
+        assert_eq!(extended_length("downloads/书名"), None);
+
+        let parent = PathBuf::from("C:/".to_string() + &"a".repeat(200));
+        assert_eq!(name_budget(&parent, 10), 259 - 203 - 1 - 10);
+        assert_eq!(name_budget(&PathBuf::from("x".repeat(300)), 10), 0);
+        assert_eq!(truncate_name("书名很长很长", 4), "书名很长");
+        assert_eq!(truncate_name("书名. 后缀", 4), "书名");
+        assert_eq!(truncate_name("a😀b", 2), "a");
+    }
+
+    #[test]
+    fn normalize_rewrites_absolute_paths_inside_workspace() {
+        let root = std::env::temp_dir().join(format!("test_paths_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let novel = crate::library::downloads_dir(&root).join("书名");
+        fs::create_dir_all(&novel).unwrap();
+        let inside = novel.join("01.txt");
+        let info = serde_json::json!({
+            "title": "书名",
+            "novel_dir": novel.to_str().unwrap(),
+            "files": [inside.to_str().unwrap(), "02.txt"],
+            "other": std::env::temp_dir().join("elsewhere").to_str().unwrap(),
+        });
+        fs::write(novel.join("info.json"), info.to_string()).unwrap();
+        fs::write(novel.join("broken.json"), "{").unwrap();
+
+        let report = normalize_library_paths(&root).unwrap();
+        assert_eq!(report.paths_rewritten, 2);
+        assert_eq!(report.files_updated, vec!["downloads/书名/info.json"]);
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(novel.join("info.json")).unwrap()).unwrap();
+        assert_eq!(value["novel_dir"], "downloads/书名");
+        assert_eq!(value["files"][0], "downloads/书名/01.txt");
+        assert_eq!(value["other"], info["other"]);
+        assert_eq!(normalize_library_paths(&root).unwrap().paths_rewritten, 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,290 @@
+//! 交互任务优先：定时扫榜、阅读预取、AI 重试队列和后台批量分析与用户当场发起的操作共用爬虫窗口预算
+//! 和 AI 请求槽位。排队时交互请求排在所有后台请求之前，已在执行的请求不会被打断。
+//!
+//! 为避免后台任务饿死：有后台请求排队时最多连续放行 [`MAX_INTERACTIVE_STREAK`] 个交互请求，
+//! 之后必须放行一个后台请求。
+//!
+//! 优先级随任务传递：后台入口用 [`background`] 包住整个 future，其中的抓取和 AI 请求经 [`current`]
+//! 取得优先级，未标记的一律视为交互。`tokio::spawn` 出去的子任务不继承，需要用 [`scope`] 再包一次。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// 有后台请求排队时，连续插队的交互请求上限
+pub const MAX_INTERACTIVE_STREAK: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Interactive,
+    Background,
+}
+
+impl Priority {
+    fn lane(self) -> usize {
+        match self {
+            Priority::Interactive => 0,
+            Priority::Background => 1,
+        }
+    }
+}
+
+tokio::task_local! {
+    static PRIORITY: Priority;
+}
+
+/// 当前任务的优先级，未标记时为交互
+pub fn current() -> Priority {
+    PRIORITY.try_with(|p| *p).unwrap_or_default()
+}
+
+/// 以指定优先级执行 future（spawn 子任务时传入 [`current`] 的结果）
+pub async fn scope<F: Future>(priority: Priority, f: F) -> F::Output {
+    PRIORITY.scope(priority, f).await
+}
+
+/// 以后台优先级执行 future
+pub async fn background<F: Future>(f: F) -> F::Output {
+    scope(Priority::Background, f).await
+}
+
+/// 同一优先级的执行中 / 排队数
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct LaneCounts {
+    pub running: usize,
+    pub queued: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct GateMetrics {
+    pub limit: usize,
+    pub interactive: LaneCounts,
+    pub background: LaneCounts,
+}
+
+impl GateMetrics {
+    pub fn running(&self) -> usize {
+        self.interactive.running + self.background.running
+    }
+
+    pub fn queued(&self) -> usize {
+        self.interactive.queued + self.background.queued
+    }
+}
+
+#[derive(Default)]
+struct State {
+    limit: usize,
+    running: [usize; 2],
+    queues: [VecDeque<u64>; 2],
+    next_ticket: u64,
+    /// 有后台请求排队时已连续放行的交互请求数
+    streak: usize,
+}
+
+impl State {
+    /// 下一个应放行的排队者
+    fn head(&self) -> Option<(Priority, u64)> {
+        let interactive = self.queues[0].front().map(|&t| (Priority::Interactive, t));
+        let background = self.queues[1].front().map(|&t| (Priority::Background, t));
+        match (interactive, background) {
+            (Some(i), Some(b)) => Some(if self.streak < MAX_INTERACTIVE_STREAK { i } else { b }),
+            (i, b) => i.or(b),
+        }
+    }
+
+    fn grant(&mut self, priority: Priority) {
+        self.queues[priority.lane()].pop_front();
+        self.running[priority.lane()] += 1;
+        self.streak = match priority {
+            Priority::Interactive if !self.queues[1].is_empty() => self.streak + 1,
+            _ => 0,
+        };
+    }
+}
+
+/// 按优先级放行的并发槽位
+pub struct PriorityGate {
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+/// 槽位许可，drop 时归还
+pub struct GatePermit<'a> {
+    gate: &'a PriorityGate,
+    priority: Priority,
+}
+
+impl GatePermit<'_> {
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+}
+
+impl Drop for GatePermit<'_> {
+    fn drop(&mut self) {
+        let lane = self.priority.lane();
+        self.gate.update(|s| s.running[lane] = s.running[lane].saturating_sub(1));
+        self.gate.changed.notify_waiters();
+    }
+}
+
+/// 排队中的票号，等待的 future 被取消时移出队列
+struct Ticket<'a> {
+    gate: &'a PriorityGate,
+    priority: Priority,
+    id: u64,
+    granted: bool,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        if !self.granted {
+            let (lane, id) = (self.priority.lane(), self.id);
+            self.gate.update(|s| s.queues[lane].retain(|&t| t != id));
+            self.gate.changed.notify_waiters();
+        }
+    }
+}
+
+impl PriorityGate {
+    pub fn new(limit: usize) -> Self {
+        PriorityGate {
+            state: Mutex::new(State { limit: limit.max(1), ..Default::default() }),
+            changed: Notify::new(),
+        }
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn metrics(&self) -> GateMetrics {
+        self.update(|s| GateMetrics {
+            limit: s.limit,
+            interactive: LaneCounts { running: s.running[0], queued: s.queues[0].len() },
+            background: LaneCounts { running: s.running[1], queued: s.queues[1].len() },
+        })
+    }
+
+    /// 调整上限；调大时立即唤醒排队者，调小时执行中的请求不受影响
+    pub fn set_limit(&self, limit: usize) {
+        self.update(|s| s.limit = limit.max(1));
+        self.changed.notify_waiters();
+    }
+
+    /// 取得一个槽位。需要排队时先调用一次 on_queued（参数为排队后的总队列长度）
+    pub async fn acquire(&self, priority: Priority, on_queued: impl FnOnce(usize)) -> GatePermit<'_> {
+        let id = self.update(|s| {
+            let id = s.next_ticket;
+            s.next_ticket += 1;
+            s.queues[priority.lane()].push_back(id);
+            id
+        });
+        let mut ticket = Ticket { gate: self, priority, id, granted: false };
+        let mut on_queued = Some(on_queued);
+        loop {
+            // 先登记唤醒再检查状态，避免检查之后、等待之前的变化被错过
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            let queued = self.update(|s| {
+                if s.running.iter().sum::<usize>() < s.limit && s.head() == Some((priority, id)) {
+                    s.grant(priority);
+                    None
+                } else {
+                    Some(s.queues.iter().map(VecDeque::len).sum::<usize>())
+                }
+            });
+            let Some(length) = queued else {
+                ticket.granted = true;
+                // 队首已变，下一个排队者可能也能拿到空闲槽位
+                self.changed.notify_waiters();
+                return GatePermit { gate: self, priority };
+            };
+            if let Some(f) = on_queued.take() {
+                f(length);
+            }
+            changed.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// 依次排入 order 中的请求，释放占位许可后返回实际放行顺序
+    async fn served_order(order: &[Priority]) -> Vec<(usize, Priority)> {
+        let gate: &'static PriorityGate = Box::leak(Box::new(PriorityGate::new(1)));
+        let held = gate.acquire(Priority::Background, |_| {}).await;
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (i, &priority) in order.iter().enumerate() {
+            let served = served.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = gate.acquire(priority, |_| {}).await;
+                served.lock().unwrap().push((i, priority));
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }));
+            // 保证入队顺序
+            while gate.metrics().queued() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+        }
+        drop(held);
+        for h in handles {
+            h.await.unwrap();
+        }
+        assert_eq!(gate.metrics(), GateMetrics { limit: 1, ..Default::default() });
+        Arc::try_unwrap(served).unwrap().into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn interactive_jumps_queued_background_but_streak_is_bounded() {
+        use Priority::{Background as B, Interactive as I};
+        let order = served_order(&[B, B, I, I, I, I, I, I]).await;
+        let indices: Vec<usize> = order.iter().map(|(i, _)| *i).collect();
+        // 四个交互请求插队后放行一个后台请求，再继续交互
+        assert_eq!(indices, vec![2, 3, 4, 5, 0, 6, 7, 1]);
+
+        let order = served_order(&[B, I, B]).await;
+        assert_eq!(order, vec![(1, I), (0, B), (2, B)]);
+    }
+
+    #[tokio::test]
+    async fn task_priority_and_cancelled_tickets() {
+        assert_eq!(current(), Priority::Interactive);
+        assert_eq!(background(async { current() }).await, Priority::Background);
+        let inherited = background(async {
+            let priority = current();
+            tokio::spawn(scope(priority, async { current() })).await.unwrap()
+        })
+        .await;
+        assert_eq!(inherited, Priority::Background);
+
+        let gate: &'static PriorityGate = Box::leak(Box::new(PriorityGate::new(1)));
+        let held = gate.acquire(Priority::Interactive, |_| {}).await;
+        let queued = tokio::spawn(async move {
+            let _permit = gate.acquire(Priority::Interactive, |_| {}).await;
+        });
+        let waiter = tokio::spawn(async move { gate.acquire(Priority::Background, |_| {}).await.priority() });
+        while gate.metrics().queued() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(gate.metrics().interactive, LaneCounts { running: 1, queued: 1 });
+        // 排队中被取消的票号不会挡住后面的请求
+        queued.abort();
+        let _ = queued.await;
+        drop(held);
+        let served = tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(served, Priority::Background);
+        assert_eq!(gate.metrics().queued(), 0);
+    }
+}
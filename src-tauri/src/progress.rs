@@ -0,0 +1,175 @@
+//! `download-progress` 事件：下载 / 扫榜任务的进度与等待状态。
+//!
+//! 任务主动等待（限速、重试退避、WAF 等待）时通过 [`WaitReporter`] 推送
+//! `status = "waiting"` 事件，前端据此显示倒计时而不是一片沉默。
+
+use serde::Serialize;
+use crate::errors::ErrorCode;
+use crate::events::EventSink;
+use std::time::{Duration, Instant};
+
+pub const DOWNLOAD_PROGRESS_EVENT: &str = "download-progress";
+
+/// 同一任务两次 waiting 事件的最小间隔
+const WAIT_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadProgress {
+    pub message: String,
+    /// "progress" | "skipped" | "warning" | "error" | "completed" | "waiting"
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 后台任务的标签（如预取为 "prefetch"），前端对带标签的事件只做静默提示
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// error / warning 的错误码，与命令返回的 [`crate::errors::AppError`] 一致
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+}
+
+/// 推送一条普通进度，同时写入 app.log。
+pub fn emit_progress<S: EventSink + ?Sized>(app: &S, status: &str, message: String) {
+    emit_tagged_progress(app, status, message, None);
+}
+
+/// 带标签的进度只写 debug 日志，不进 app.log；tag 为 None 时等同 [`emit_progress`]。
+pub fn emit_tagged_progress<S: EventSink + ?Sized>(app: &S, status: &str, message: String, tag: Option<&str>) {
+    emit_coded_progress(app, status, message, None, tag);
+}
+
+/// 带错误码的进度，日志中同样记下错误码
+pub fn emit_coded_progress<S: EventSink + ?Sized>(
+    app: &S,
+    status: &str,
+    message: String,
+    code: Option<ErrorCode>,
+    tag: Option<&str>,
+) {
+    let logged = match code {
+        Some(code) => format!("[{}] {}", code, message),
+        None => message.clone(),
+    };
+    match tag {
+        Some(tag) => log::debug!("[Download:{}] {}", tag, logged),
+        None => crate::log_to_file(&format!("[Download] {}", logged)),
+    }
+    crate::events::emit_and_buffer(
+        app,
+        DOWNLOAD_PROGRESS_EVENT,
+        DownloadProgress { message, status: status.to_string(), tag: tag.map(str::to_string), code, ..Default::default() },
+    );
+}
+
+/// 单个任务的等待上报器：每个任务持有一个，waiting 事件按任务节流到每秒最多一条。
+pub struct WaitReporter {
+    task: String,
+    tag: Option<String>,
+    last_emit: Option<Instant>,
+}
+
+impl WaitReporter {
+    pub fn new(task: impl Into<String>) -> Self {
+        Self { task: task.into(), tag: None, last_emit: None }
+    }
+
+    /// waiting 事件带上后台任务标签，见 [`DownloadProgress::tag`]
+    pub fn tagged(mut self, tag: Option<&str>) -> Self {
+        self.tag = tag.map(str::to_string);
+        self
+    }
+
+    fn should_emit(&mut self, now: Instant) -> bool {
+        match self.last_emit {
+            Some(last) if now.duration_since(last) < WAIT_EVENT_INTERVAL => false,
+            _ => {
+                self.last_emit = Some(now);
+                true
+            }
+        }
+    }
+
+    /// 上报一次等待（不睡眠）。只写 debug 日志，不进 app.log。
+    pub fn report<S: EventSink + ?Sized>(&mut self, app: &S, wait: Duration, reason: &str) {
+        if !self.should_emit(Instant::now()) {
+            return;
+        }
+        let wait_ms = wait.as_millis() as u64;
+        log::debug!("[Wait] {} 等待 {} ms: {}", self.task, wait_ms, reason);
+        crate::events::emit_and_buffer(
+            app,
+            DOWNLOAD_PROGRESS_EVENT,
+            DownloadProgress {
+                message: format!("{}：等待 {:.1} 秒（{}）", self.task, wait_ms as f64 / 1000.0, reason),
+                status: "waiting".to_string(),
+                task: Some(self.task.clone()),
+                wait_ms: Some(wait_ms),
+                reason: Some(reason.to_string()),
+                tag: self.tag.clone(),
+                code: None,
+            },
+        );
+    }
+
+    /// 上报等待后睡眠。所有主动等待点都应通过它，而不是直接 sleep。
+    pub async fn sleep<S: EventSink + Sync + ?Sized>(&mut self, app: &S, wait: Duration, reason: &str) {
+        self.report(app, wait, reason);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_survives_closed_or_failing_window() {
+        // 主窗口已关闭 / 推送 panic 时进度照常写入缓冲，调用方不受影响
+        use crate::events::tests::MockSink;
+        for sink in [MockSink::new(false, false), MockSink::new(true, true)] {
+            emit_progress(&sink, "progress", "已保存: 第1章".to_string());
+            WaitReporter::new("无窗口书").report(&sink, Duration::from_millis(1500), "限速");
+        }
+        let events = crate::events::recent_events(&[DOWNLOAD_PROGRESS_EVENT.to_string()], 0);
+        assert!(events.iter().any(|e| e.payload["task"] == "无窗口书" && e.payload["wait_ms"] == 1500));
+    }
+
+    #[test]
+    fn wait_events_are_throttled_per_task() {
+        let start = Instant::now();
+        let mut a = WaitReporter::new("书A");
+        let mut b = WaitReporter::new("书B");
+
+        assert!(a.should_emit(start));
+        assert!(!a.should_emit(start + Duration::from_millis(400)));
+        assert!(b.should_emit(start + Duration::from_millis(400)), "其他任务不受影响");
+        assert!(a.should_emit(start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn waiting_payload_serializes_structured_fields() {
+        let payload = DownloadProgress {
+            message: "m".into(),
+            status: "waiting".into(),
+            task: Some("书".into()),
+            wait_ms: Some(1500),
+            reason: Some("章节间隔".into()),
+            tag: None,
+            code: None,
+        };
+        let v = serde_json::to_value(&payload).unwrap();
+        assert_eq!(v["wait_ms"], 1500);
+        assert_eq!(v["reason"], "章节间隔");
+
+        let plain = serde_json::to_value(DownloadProgress { message: "m".into(), status: "progress".into(), ..Default::default() }).unwrap();
+        assert!(plain.get("wait_ms").is_none());
+        assert!(plain.get("code").is_none());
+
+        let failed = DownloadProgress { status: "error".into(), code: Some(ErrorCode::SpiderTimeout), ..Default::default() };
+        assert_eq!(serde_json::to_value(failed).unwrap()["code"], "SPIDER_TIMEOUT");
+    }
+}
@@ -0,0 +1,237 @@
+//! 同一章用多个提示词变体分析后的对比文档。
+//!
+//! 变体结果按 `result/<书名>/<章节序号>_<变体名>.md` 保存（结构化细纲也可以是
+//! `<章节序号>_<变体名>.outline.json`）。所有找到的变体都是细纲格式（JSON 节点数组，或 Markdown 中
+//! `### 1. …` 编号节点）时按节点序号逐行对齐，否则每个变体整篇作为一列。结果写入
+//! `result/<书名>/<章节序号>_comparison.md`；找不到的变体列在文档和返回值里，不算错误。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::{analysis_batch, outline, provenance, storage};
+
+const OUTLINE_SUFFIX: &str = ".outline.json";
+const COMPARISON_SUFFIX: &str = "_comparison.md";
+/// 表格单元格里节点概括的最大字数
+const MAX_CELL_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct VariantNodeCount {
+    pub name: String,
+    /// 细纲节点数；整篇对比时为 0
+    pub nodes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct PromptComparison {
+    /// 对比文档，相对工作区
+    pub path: String,
+    /// 是否按节点对齐（否则为整篇对比）
+    pub aligned: bool,
+    pub variants: Vec<VariantNodeCount>,
+    /// 找不到结果文件的变体
+    pub missing: Vec<String>,
+}
+
+/// 一个变体的分析结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VariantOutput {
+    name: String,
+    body: String,
+    /// (节点序号, 概括)；不是细纲格式时为 None
+    nodes: Option<Vec<(usize, String)>>,
+}
+
+fn check_variant_name(name: &str) -> Result<(), String> {
+    let invalid = name.is_empty()
+        || name.contains(['/', '\\'])
+        || name.contains("..")
+        || name.chars().any(|c| c.is_control());
+    if invalid { Err(format!("无效的变体名: {:?}", name)) } else { Ok(()) }
+}
+
+/// JSON 细纲：节点数组，概括取 event 字段
+fn json_nodes(outline: &Value) -> Option<Vec<(usize, String)>> {
+    let nodes = outline.as_array()?;
+    let nodes: Vec<(usize, String)> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (i + 1, n.get("event").and_then(Value::as_str).unwrap_or_default().trim().to_string()))
+        .collect();
+    (!nodes.is_empty()).then_some(nodes)
+}
+
+/// Markdown 细纲：每个编号节点取概括，没有概括时取标题
+fn markdown_nodes(body: &str) -> Option<Vec<(usize, String)>> {
+    let outline = outline::parse(body)?;
+    Some(outline.nodes.into_iter().map(|n| (n.number, n.summary.unwrap_or(n.title))).collect())
+}
+
+/// 读取变体结果：优先 `.outline.json`，其次 `.md`；都不存在时为 None
+fn load_variant(dir: &Path, chapter_index: usize, name: &str) -> Option<VariantOutput> {
+    let stem = format!("{}_{}", chapter_index, name);
+    if let Ok(content) = fs::read_to_string(dir.join(format!("{}{}", stem, OUTLINE_SUFFIX))) {
+        if let Ok(outline) = serde_json::from_str::<Value>(&content) {
+            return Some(VariantOutput { name: name.to_string(), nodes: json_nodes(&outline), body: content });
+        }
+    }
+    let content = fs::read_to_string(dir.join(format!("{}.md", stem))).ok()?;
+    let body = provenance::strip_front_matter(&content).trim().to_string();
+    Some(VariantOutput { name: name.to_string(), nodes: markdown_nodes(&body), body })
+}
+
+/// 表格单元格：换行改为 `<br>`，转义竖线，过长的截断
+fn cell(text: &str) -> String {
+    let text = crate::spiders::truncate_chars(text.trim(), MAX_CELL_CHARS);
+    text.replace('|', "\\|").replace("\r\n", "\n").replace('\n', "<br>")
+}
+
+fn render(novel_title: &str, chapter_index: usize, outputs: &[VariantOutput], missing: &[String]) -> String {
+    let mut doc = format!("# 《{}》第 {} 章提示词变体对比\n\n", novel_title, chapter_index);
+    if !missing.is_empty() {
+        doc.push_str(&format!("> 未找到结果的变体：{}\n\n", missing.join("、")));
+    }
+    if outputs.is_empty() {
+        doc.push_str("没有可对比的结果。\n");
+        return doc;
+    }
+    let header = format!("| 节点 | {} |\n|---|{}\n", outputs.iter().map(|o| cell(&o.name)).collect::<Vec<_>>().join(" | "), "---|".repeat(outputs.len()));
+
+    let all_nodes: Option<Vec<&Vec<(usize, String)>>> = outputs.iter().map(|o| o.nodes.as_ref()).collect();
+    let Some(all_nodes) = all_nodes else {
+        doc.push_str("部分变体不是细纲格式，按整篇对比。\n\n");
+        doc.push_str(&header.replacen("| 节点 |", "| |", 1));
+        let row: Vec<String> = outputs.iter().map(|o| cell(&o.body)).collect();
+        doc.push_str(&format!("| 全文 | {} |\n", row.join(" | ")));
+        return doc;
+    };
+
+    let numbers: BTreeSet<usize> = all_nodes.iter().flat_map(|nodes| nodes.iter().map(|(n, _)| *n)).collect();
+    let mut divergent = Vec::new();
+    doc.push_str("## 节点概括\n\n");
+    doc.push_str(&header);
+    for number in numbers {
+        let row: Vec<Option<&String>> =
+            all_nodes.iter().map(|nodes| nodes.iter().find(|(n, _)| *n == number).map(|(_, s)| s)).collect();
+        let cells: Vec<String> = row.iter().map(|s| s.map_or_else(|| "—".to_string(), |s| cell(s))).collect();
+        doc.push_str(&format!("| {} | {} |\n", number, cells.join(" | ")));
+        if row.iter().any(Option::is_none) {
+            let names = |present: bool| {
+                outputs.iter().zip(&row).filter(|(_, s)| s.is_some() == present).map(|(o, _)| o.name.as_str()).collect::<Vec<_>>().join("、")
+            };
+            divergent.push(format!("- 节点 {}：{} 有，{} 没有", number, names(true), names(false)));
+        }
+    }
+    if !divergent.is_empty() {
+        doc.push_str("\n## 分歧\n\n");
+        doc.push_str(&divergent.join("\n"));
+        doc.push('\n');
+    }
+    doc
+}
+
+/// 生成对比文档并写入结果目录
+pub fn build(
+    workspace_root: &Path,
+    novel_title: &str,
+    chapter_index: usize,
+    variant_names: &[String],
+) -> Result<PromptComparison, String> {
+    if variant_names.is_empty() {
+        return Err("没有指定要对比的变体".to_string());
+    }
+    for name in variant_names {
+        check_variant_name(name)?;
+    }
+    let dir = analysis_batch::result_dir(workspace_root, novel_title);
+    let mut outputs = Vec::new();
+    let mut missing = Vec::new();
+    for name in variant_names {
+        match load_variant(&dir, chapter_index, name) {
+            Some(output) => outputs.push(output),
+            None => missing.push(name.clone()),
+        }
+    }
+
+    let aligned = !outputs.is_empty() && outputs.iter().all(|o| o.nodes.is_some());
+    let doc = render(novel_title, chapter_index, &outputs, &missing);
+    let path = dir.join(format!("{}{}", chapter_index, COMPARISON_SUFFIX));
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    storage::write_text(&path, &doc).map_err(|e| format!("写入对比文档失败: {}", e))?;
+
+    Ok(PromptComparison {
+        path: crate::paths::to_relative(workspace_root, &path).unwrap_or_else(|| path.to_string_lossy().to_string()),
+        aligned,
+        variants: outputs
+            .iter()
+            .map(|o| VariantNodeCount { name: o.name.clone(), nodes: if aligned { o.nodes.as_ref().map_or(0, Vec::len) } else { 0 } })
+            .collect(),
+        missing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(tag: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("test_prompt_comparison_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    const OUTLINE_MD: &str = "### 1. [开场]\n> **概括**: 齐夏醒来|发现圆桌\n> **目的**: 制造悬念\n\n### 2. [对峙]\n> **概括**: 众人争吵\n\n### 💡 本章核心总结\n一句话";
+
+    #[test]
+    fn markdown_outline_nodes_are_parsed() {
+        let nodes = markdown_nodes(OUTLINE_MD).unwrap();
+        assert_eq!(nodes, vec![(1, "齐夏醒来|发现圆桌".to_string()), (2, "众人争吵".to_string())]);
+        assert_eq!(markdown_nodes("## 分析\n普通段落"), None);
+        assert_eq!(outline::node_heading("#### 12、标题"), Some((12, "标题")));
+        assert_eq!(outline::node_heading("### 2024年总结"), None);
+    }
+
+    #[test]
+    fn variants_are_aligned_by_node_and_divergence_noted() {
+        let root = temp_root("aligned");
+        let dir = analysis_batch::result_dir(&root, "书");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("3_简洁.md"), OUTLINE_MD).unwrap();
+        fs::write(dir.join("3_详细.outline.json"), r#"[{"event":"醒来"},{"event":"争吵"},{"event":"投票"}]"#).unwrap();
+
+        let names = vec!["简洁".to_string(), "详细".to_string(), "缺失".to_string()];
+        let result = build(&root, "书", 3, &names).unwrap();
+        assert!(result.aligned);
+        assert_eq!(result.path, "result/书/3_comparison.md");
+        assert_eq!(result.missing, vec!["缺失"]);
+        assert_eq!(result.variants.iter().map(|v| v.nodes).collect::<Vec<_>>(), vec![2, 3]);
+
+        let doc = fs::read_to_string(dir.join("3_comparison.md")).unwrap();
+        assert!(doc.contains("| 1 | 齐夏醒来\\|发现圆桌 | 醒来 |"));
+        assert!(doc.contains("| 3 | — | 投票 |"));
+        assert!(doc.contains("- 节点 3：详细 有，简洁 没有"));
+        assert!(doc.contains("未找到结果的变体：缺失"));
+        assert!(build(&root, "书", 3, &["../x".to_string()]).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unstructured_variants_fall_back_to_whole_documents() {
+        let root = temp_root("whole");
+        let dir = analysis_batch::result_dir(&root, "书");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("1_a.md"), OUTLINE_MD).unwrap();
+        fs::write(dir.join("1_b.md"), "这一章节奏很快。\n结尾留了钩子。").unwrap();
+
+        let result = build(&root, "书", 1, &["a".to_string(), "b".to_string()]).unwrap();
+        assert!(!result.aligned);
+        assert!(result.variants.iter().all(|v| v.nodes == 0));
+        let doc = fs::read_to_string(dir.join("1_comparison.md")).unwrap();
+        assert!(doc.contains("| 全文 |"));
+        assert!(doc.contains("这一章节奏很快。<br>结尾留了钩子。"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
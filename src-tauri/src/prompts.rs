@@ -0,0 +1,253 @@
+//! 提示词模板注册表。
+//!
+//! 内置模板只读；用户模板保存在 settings.json 的 `prompt_templates` 中，不能与内置模板重名。
+//! 未显式指定提示词时按 模板参数 → 题材/平台映射 → 设置中的默认模板 → 内置"细纲还原" 依次解析。
+
+use serde_json::{Map, Value};
+
+use crate::resources;
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PromptTemplate {
+    pub name: &'static str,
+    pub label: String,
+    pub content: String,
+}
+
+/// 单章细纲还原（深度拆解默认）
+pub const CHAPTER_OUTLINE: &str = "chapter_outline";
+/// 开篇商业分析，要求 JSON 输出
+pub const AUTO_ANALYSIS: &str = "auto_analysis";
+/// 选段分析：只针对用户框选的几段文字
+pub const SCENE_ANALYSIS: &str = "scene_analysis";
+
+const BUILTIN_NAMES: &[&str] = &[CHAPTER_OUTLINE, AUTO_ANALYSIS, SCENE_ANALYSIS];
+
+/// 内置模板，正文和显示名取自 [`resources`]（可被工作区覆盖）
+pub fn builtin_templates() -> Vec<PromptTemplate> {
+    BUILTIN_NAMES.iter().filter_map(|name| builtin(name)).collect()
+}
+
+pub fn builtin(name: &str) -> Option<PromptTemplate> {
+    let name = *BUILTIN_NAMES.iter().find(|n| **n == name)?;
+    Some(PromptTemplate { name, label: resources::prompt_label(name), content: resources::prompt(name)? })
+}
+
+/// 可选的输出语言：代码（也用于译文文件名 `NN.<代码>.txt`）和写进提示词的语言名
+#[derive(Debug, Clone, Copy, serde::Serialize, PartialEq, Eq)]
+pub struct OutputLanguage {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+const OUTPUT_LANGUAGES: &[OutputLanguage] = &[
+    OutputLanguage { code: "zh", name: "简体中文" },
+    OutputLanguage { code: "zh-Hant", name: "繁體中文" },
+    OutputLanguage { code: "en", name: "English" },
+    OutputLanguage { code: "ja", name: "日本語" },
+    OutputLanguage { code: "ko", name: "한국어" },
+];
+
+pub fn output_languages() -> &'static [OutputLanguage] {
+    OUTPUT_LANGUAGES
+}
+
+/// 解析命令参数中的输出语言代码（不区分大小写），空值表示不指定
+pub fn output_language(code: Option<&str>) -> Result<Option<OutputLanguage>, String> {
+    let Some(code) = code.map(str::trim).filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    OUTPUT_LANGUAGES
+        .iter()
+        .find(|l| l.code.eq_ignore_ascii_case(code))
+        .copied()
+        .map(Some)
+        .ok_or_else(|| {
+            let codes: Vec<&str> = OUTPUT_LANGUAGES.iter().map(|l| l.code).collect();
+            resources::format("message.unsupported_output_language", &[("code", code), ("choices", &codes.join(" / "))])
+        })
+}
+
+/// 在最终提示词末尾加上输出语言要求。所有模板（含用户直接传入的提示词）都经过这里，
+/// 未指定语言时原样返回。
+pub fn with_output_language(prompt: &str, language: Option<OutputLanguage>) -> String {
+    match language {
+        Some(language) => format!(
+            "{}\n\n{}",
+            prompt.trim_end(),
+            resources::format("prompt.output_language_suffix", &[("language", language.name)])
+        ),
+        None => prompt.to_string(),
+    }
+}
+
+/// 列表项：内置模板 read_only = true。
+#[derive(Debug, Clone, serde::Serialize, PartialEq)]
+pub struct TemplateEntry {
+    pub name: String,
+    pub label: String,
+    pub content: String,
+    pub read_only: bool,
+}
+
+/// 最终生效的提示词及其来源模板名。
+#[derive(Debug, Clone, serde::Serialize, PartialEq)]
+pub struct EffectivePrompt {
+    pub template: String,
+    pub content: String,
+}
+
+pub fn list_templates(settings: &Settings) -> Vec<TemplateEntry> {
+    let builtins = builtin_templates().into_iter().map(|t| TemplateEntry {
+        name: t.name.to_string(),
+        label: t.label,
+        content: t.content,
+        read_only: true,
+    });
+    let custom = settings
+        .prompt_templates
+        .iter()
+        .filter(|(name, _)| builtin(name).is_none())
+        .map(|(name, content)| TemplateEntry {
+            name: name.clone(),
+            label: name.clone(),
+            content: content.clone(),
+            read_only: false,
+        });
+    builtins.chain(custom).collect()
+}
+
+/// 按名称查找模板：内置优先（只读，不可被覆盖），其次用户模板。
+pub fn lookup(settings: &Settings, name: &str) -> Option<String> {
+    builtin(name)
+        .map(|t| t.content)
+        .or_else(|| settings.prompt_templates.get(name).cloned())
+}
+
+/// 从 info.json 取题材：手动修正的 user.genre 优先，其次 AI 分析结果。
+fn novel_genre(info: &Map<String, Value>) -> Option<String> {
+    ["user", "ai_analysis"]
+        .iter()
+        .filter_map(|k| info.get(*k)?.get("genre")?.as_str())
+        .map(|g| g.trim().to_string())
+        .find(|g| !g.is_empty())
+}
+
+/// 题材 / 平台映射：题材完全匹配 > 题材包含的最长键 > 平台完全匹配。
+fn mapped_template(settings: &Settings, info: &Map<String, Value>) -> Option<String> {
+    let map = &settings.genre_prompt_map;
+    if let Some(genre) = novel_genre(info) {
+        if let Some(name) = map.get(&genre) {
+            return Some(name.clone());
+        }
+        if let Some((_, name)) = map
+            .iter()
+            .filter(|(key, _)| !key.is_empty() && genre.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.chars().count())
+        {
+            return Some(name.clone());
+        }
+    }
+    let platform = info.get("platform")?.as_str()?;
+    map.get(platform).cloned()
+}
+
+/// 解析最终使用的提示词。显式指定的模板不存在时报错；映射或默认设置指向不存在的模板时回退到内置。
+pub fn resolve(
+    settings: &Settings,
+    template: Option<&str>,
+    novel_info: Option<&Map<String, Value>>,
+) -> Result<EffectivePrompt, String> {
+    if let Some(name) = template.map(str::trim).filter(|n| !n.is_empty()) {
+        let content = lookup(settings, name).ok_or_else(|| resources::format("message.template_not_found", &[("name", name)]))?;
+        return Ok(EffectivePrompt { template: name.to_string(), content });
+    }
+
+    let candidates = [
+        novel_info.and_then(|info| mapped_template(settings, info)),
+        settings.default_prompt_template.clone(),
+    ];
+    for name in candidates.into_iter().flatten() {
+        match lookup(settings, &name) {
+            Some(content) => return Ok(EffectivePrompt { template: name, content }),
+            None => eprintln!("[Prompts] 设置引用的模板不存在，已忽略: {}", name),
+        }
+    }
+
+    Ok(EffectivePrompt {
+        template: CHAPTER_OUTLINE.to_string(),
+        content: resources::text("prompt.chapter_outline"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings_with_map() -> Settings {
+        let mut s = Settings::default();
+        s.prompt_templates.insert("female".into(), "女频模板".into());
+        s.prompt_templates.insert("fanqie_default".into(), "番茄模板".into());
+        s.genre_prompt_map.insert("女频".into(), "female".into());
+        s.genre_prompt_map.insert("fanqie".into(), "fanqie_default".into());
+        s
+    }
+
+    #[test]
+    fn falls_back_to_builtin_outline() {
+        let p = resolve(&Settings::default(), None, None).unwrap();
+        assert_eq!(p.template, CHAPTER_OUTLINE);
+        assert!(p.content.contains("细纲"));
+    }
+
+    #[test]
+    fn explicit_template_must_exist() {
+        let s = settings_with_map();
+        assert_eq!(resolve(&s, Some("female"), None).unwrap().content, "女频模板");
+        assert!(resolve(&s, Some("nope"), None).unwrap_err().contains("nope"));
+    }
+
+    #[test]
+    fn genre_then_platform_then_default() {
+        let mut s = settings_with_map();
+        s.default_prompt_template = Some(AUTO_ANALYSIS.into());
+
+        let info = json!({"platform":"qidian","ai_analysis":{"genre":"女频言情"}});
+        assert_eq!(resolve(&s, None, info.as_object()).unwrap().template, "female");
+
+        // 手动修正的题材优先于 AI 结果
+        let info = json!({"platform":"fanqie","ai_analysis":{"genre":"女频"},"user":{"genre":"都市"}});
+        assert_eq!(resolve(&s, None, info.as_object()).unwrap().template, "fanqie_default");
+
+        let info = json!({"platform":"qidian"});
+        assert_eq!(resolve(&s, None, info.as_object()).unwrap().template, AUTO_ANALYSIS);
+    }
+
+    #[test]
+    fn output_language_is_appended_to_any_prompt() {
+        assert_eq!(output_language(None).unwrap(), None);
+        assert_eq!(output_language(Some(" ")).unwrap(), None);
+        let en = output_language(Some("EN")).unwrap().unwrap();
+        assert_eq!(en.code, "en");
+        assert!(output_language(Some("xx")).unwrap_err().contains("xx"));
+
+        assert_eq!(with_output_language("原样", None), "原样");
+        let auto_analysis = builtin(AUTO_ANALYSIS).unwrap().content;
+        let prompt = with_output_language(&auto_analysis, Some(en));
+        assert!(prompt.starts_with(&auto_analysis));
+        assert!(prompt.ends_with("JSON 键名保持不变。"));
+        assert!(prompt.contains("English"));
+    }
+
+    #[test]
+    fn builtins_are_read_only() {
+        let mut s = Settings::default();
+        s.prompt_templates.insert(CHAPTER_OUTLINE.into(), "覆盖".into());
+        assert_ne!(lookup(&s, CHAPTER_OUTLINE).unwrap(), "覆盖");
+        let entries = list_templates(&s);
+        assert_eq!(entries.iter().filter(|e| e.name == CHAPTER_OUTLINE).count(), 1);
+        assert!(entries.iter().all(|e| e.read_only));
+    }
+}
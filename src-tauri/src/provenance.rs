@@ -0,0 +1,326 @@
+//! 分析结果的来源记录：每份结果引用了哪些章节文件、当时的内容哈希和正文清洗规则版本。
+//!
+//! 服务端保存的分析结果（`export_chapter`、`analyze_novel` 批次）在 markdown 开头写入
+//! front matter，并在 `result/analysis_index.json` 中记录同样的信息。章节重新下载、清洗或
+//! 手工修改后哈希随之变化，`check_analysis_freshness` 据此列出需要重跑的分析。
+//! 哈希与 chapters.json 相同，使用 [`storage::content_hash`]。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::analysis_batch::{self, IndexEntry};
+use crate::{library, storage};
+
+/// 手动导出的结果在分析索引中统一记在这个批次名下
+pub const MANUAL_BATCH_ID: &str = "manual";
+/// 从外部导入的结果在分析索引中统一记在这个批次名下
+pub const EXTERNAL_BATCH_ID: &str = "external";
+const FRONT_MATTER_FENCE: &str = "---";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceChapter {
+    /// 章节文件名，如 `05.txt`
+    pub file: String,
+    pub content_hash: String,
+    /// 分析时的正文清洗规则版本
+    pub cleaning_rules_version: u32,
+}
+
+impl SourceChapter {
+    pub fn of(file: &str, content: &[u8]) -> Self {
+        SourceChapter {
+            file: file.to_string(),
+            content_hash: storage::content_hash(content),
+            cleaning_rules_version: library::CLEANING_RULES_VERSION,
+        }
+    }
+}
+
+/// 分析索引中一份结果文件的来源
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AnalysisOutput {
+    /// 相对工作区
+    pub output_file: String,
+    pub sources: Vec<SourceChapter>,
+    /// 要求 AI 使用的输出语言代码，未指定时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_language: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub novel_title: String,
+    pub batch_id: Option<String>,
+    pub analyzed_at: String,
+    pub sources: Vec<SourceChapter>,
+    /// 生成结果的模型和提示词哈希，用于判断重跑时是否需要另存新版本
+    pub model: Option<String>,
+    pub prompt_hash: Option<String>,
+    pub output_language: Option<String>,
+    /// 外部导入时的来源说明（如协作者或工具名）
+    pub external_source: Option<String>,
+}
+
+impl Provenance {
+    pub fn new(novel_title: &str, batch_id: Option<&str>, sources: Vec<SourceChapter>) -> Self {
+        Provenance {
+            novel_title: novel_title.to_string(),
+            batch_id: batch_id.map(str::to_string),
+            analyzed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            sources,
+            model: None,
+            prompt_hash: None,
+            output_language: None,
+            external_source: None,
+        }
+    }
+
+    /// 记录生成结果的模型和提示词哈希
+    pub fn generated_by(mut self, model: Option<&str>, prompt_hash: Option<&str>) -> Self {
+        self.model = model.filter(|m| !m.is_empty()).map(str::to_string);
+        self.prompt_hash = prompt_hash.filter(|h| !h.is_empty()).map(str::to_string);
+        self
+    }
+
+    /// 记录要求的输出语言代码
+    pub fn in_language(mut self, language: Option<&str>) -> Self {
+        self.output_language = language.filter(|l| !l.is_empty()).map(str::to_string);
+        self
+    }
+
+    /// 标记为外部导入，analyzed_at 即导入时间
+    pub fn imported_from(mut self, label: &str) -> Self {
+        self.external_source = Some(label.to_string());
+        self
+    }
+
+    fn render(&self) -> String {
+        let mut lines = vec![FRONT_MATTER_FENCE.to_string(), format!("novel: {}", self.novel_title)];
+        if let Some(batch) = &self.batch_id {
+            lines.push(format!("batch: {}", batch));
+        }
+        lines.push(format!("analyzed_at: {}", self.analyzed_at));
+        if let Some(label) = &self.external_source {
+            lines.push(format!("external_source: {}", label));
+            lines.push(format!("imported_at: {}", self.analyzed_at));
+        }
+        if let Some(model) = &self.model {
+            lines.push(format!("model: {}", model));
+        }
+        if let Some(hash) = &self.prompt_hash {
+            lines.push(format!("prompt_hash: {}", hash));
+        }
+        if let Some(language) = &self.output_language {
+            lines.push(format!("output_language: {}", language));
+        }
+        lines.push("sources:".to_string());
+        for source in &self.sources {
+            lines.push(format!("  - file: {}", source.file));
+            lines.push(format!("    content_hash: {}", source.content_hash));
+            lines.push(format!("    cleaning_rules_version: {}", source.cleaning_rules_version));
+        }
+        lines.push(FRONT_MATTER_FENCE.to_string());
+        lines.join("\n")
+    }
+}
+
+/// 在结果正文前加上来源 front matter（已有的 front matter 先去掉）
+pub fn with_front_matter(provenance: &Provenance, body: &str) -> String {
+    format!("{}\n\n{}", provenance.render(), strip_front_matter(body).trim_start())
+}
+
+/// 去掉开头的 front matter，没有时原样返回
+pub fn strip_front_matter(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("---\n") else { return text };
+    match rest.find("\n---") {
+        Some(end) => {
+            let after = &rest[end + 4..];
+            after.strip_prefix('\n').unwrap_or(after)
+        }
+        None => text,
+    }
+}
+
+/// 读取 front matter 中的顶层字段（`key: value`），不含 sources 等嵌套列表；没有 front matter 时为空
+pub fn front_matter_fields(text: &str) -> Vec<(String, String)> {
+    let Some(rest) = text.strip_prefix("---\n") else { return Vec::new() };
+    let Some(end) = rest.find("\n---") else { return Vec::new() };
+    rest[..end]
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// 手动导出的结果记入分析索引（同一结果文件重复导出时替换）
+pub fn record_export(workspace_root: &Path, novel_title: &str, output: AnalysisOutput) -> Result<(), String> {
+    record_output(workspace_root, novel_title, MANUAL_BATCH_ID, output)
+}
+
+/// 外部导入的结果记入分析索引，条目标记为 external
+pub fn record_import(workspace_root: &Path, novel_title: &str, output: AnalysisOutput) -> Result<(), String> {
+    record_output(workspace_root, novel_title, EXTERNAL_BATCH_ID, output)
+}
+
+fn record_output(workspace_root: &Path, novel_title: &str, batch_id: &str, output: AnalysisOutput) -> Result<(), String> {
+    let mut index = analysis_batch::load_index(workspace_root);
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let position = index.iter().position(|e| e.novel_title == novel_title && e.batch_id == batch_id);
+    let entry = match position {
+        Some(i) => &mut index[i],
+        None => {
+            index.push(IndexEntry {
+                novel_title: novel_title.to_string(),
+                batch_id: batch_id.to_string(),
+                model: String::new(),
+                chapters: 0,
+                total_tokens: 0,
+                duration_ms: 0,
+                completed_at: now.clone(),
+                outputs: Vec::new(),
+                external: batch_id == EXTERNAL_BATCH_ID,
+            });
+            index.last_mut().expect("刚插入")
+        }
+    };
+    entry.outputs.retain(|o| o.output_file != output.output_file);
+    entry.outputs.push(output);
+    entry.chapters = entry.outputs.len();
+    entry.completed_at = now;
+    analysis_batch::save_index(workspace_root, &index)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChangedSource {
+    pub file: String,
+    pub recorded_hash: String,
+    /// 章节文件已不存在时为 None
+    pub current_hash: Option<String>,
+    /// 清洗规则版本已更新
+    pub rules_changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct StaleAnalysis {
+    pub output_file: String,
+    pub batch_id: String,
+    pub changed: Vec<ChangedSource>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FreshnessReport {
+    pub checked: usize,
+    /// 没有来源记录（本功能之前生成）的结果
+    pub untracked: usize,
+    pub stale: Vec<StaleAnalysis>,
+}
+
+fn changed_sources(novel_dir: &Path, sources: &[SourceChapter]) -> Vec<ChangedSource> {
+    sources
+        .iter()
+        .filter_map(|source| {
+            let current_hash = fs::read(novel_dir.join(&source.file)).ok().map(|b| storage::content_hash(&b));
+            let rules_changed = source.cleaning_rules_version != library::CLEANING_RULES_VERSION;
+            if current_hash.as_deref() == Some(source.content_hash.as_str()) && !rules_changed {
+                return None;
+            }
+            Some(ChangedSource { file: source.file.clone(), recorded_hash: source.content_hash.clone(), current_hash, rules_changed })
+        })
+        .collect()
+}
+
+/// 某本书的全部分析结果及所属批次：分析索引，加上尚未完成（未进索引）的批次中已完成的分组
+fn collect_outputs(workspace_root: &Path, novel_title: &str) -> Vec<(String, AnalysisOutput)> {
+    let mut outputs: Vec<(String, AnalysisOutput)> = Vec::new();
+    let mut indexed = HashSet::new();
+    for entry in analysis_batch::load_index(workspace_root).into_iter().filter(|e| e.novel_title == novel_title) {
+        indexed.insert(entry.batch_id.clone());
+        outputs.extend(entry.outputs.into_iter().map(|o| (entry.batch_id.clone(), o)));
+    }
+    for batch in analysis_batch::list_batches(workspace_root, novel_title) {
+        if indexed.contains(&batch.id) {
+            continue;
+        }
+        if let Ok(manifest) = analysis_batch::load(workspace_root, novel_title, &batch.id) {
+            outputs.extend(manifest.outputs().into_iter().map(|o| (batch.id.clone(), o)));
+        }
+    }
+    outputs
+}
+
+/// 至少被一份分析结果引用过的章节文件名（没有来源记录的旧结果不计）
+pub fn analyzed_files(workspace_root: &Path, novel_title: &str) -> HashSet<String> {
+    collect_outputs(workspace_root, novel_title)
+        .into_iter()
+        .flat_map(|(_, output)| output.sources)
+        .map(|source| source.file)
+        .collect()
+}
+
+/// 对比记录的哈希与当前章节文件。分析索引之外，还检查尚未完成的批次中已完成的分组。
+pub fn check_freshness(workspace_root: &Path, novel_title: &str) -> FreshnessReport {
+    let novel_dir = library::downloads_dir(workspace_root).join(novel_title);
+    let outputs = collect_outputs(workspace_root, novel_title);
+    let mut report = FreshnessReport { checked: outputs.len(), ..Default::default() };
+    for (batch_id, output) in outputs {
+        if output.sources.is_empty() {
+            report.untracked += 1;
+            continue;
+        }
+        let changed = changed_sources(&novel_dir, &output.sources);
+        if !changed.is_empty() {
+            report.stale.push(StaleAnalysis { output_file: output.output_file, batch_id, changed });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_round_trips_and_is_replaced() {
+        let provenance = Provenance::new("书名", Some("1"), vec![SourceChapter::of("05.txt", b"text")]);
+        let text = with_front_matter(&provenance, "### 细纲\n内容");
+        assert!(text.starts_with("---\nnovel: 书名\nbatch: 1\n"));
+        assert!(text.contains(&format!("content_hash: {}", storage::content_hash(b"text"))));
+        assert_eq!(strip_front_matter(&text).trim_start(), "### 细纲\n内容");
+
+        let again = with_front_matter(&provenance, &text);
+        assert_eq!(again.matches("novel: 书名").count(), 1);
+        assert_eq!(strip_front_matter("没有 front matter"), "没有 front matter");
+    }
+
+    #[test]
+    fn freshness_lists_outputs_whose_chapters_changed() {
+        let root = std::env::temp_dir().join(format!("test_provenance_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let novel_dir = library::downloads_dir(&root).join("书名");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::write(novel_dir.join("01.txt"), "第一章").unwrap();
+        fs::write(novel_dir.join("02.txt"), "第二章").unwrap();
+
+        for (output, file, content) in [("result/书名/1.md", "01.txt", "第一章"), ("result/书名/2.md", "02.txt", "第二章")] {
+            let sources = vec![SourceChapter::of(file, content.as_bytes())];
+            record_export(&root, "书名", AnalysisOutput { output_file: output.to_string(), sources, output_language: None }).unwrap();
+        }
+        assert!(check_freshness(&root, "书名").stale.is_empty());
+
+        fs::write(novel_dir.join("02.txt"), "第二章（重新下载）").unwrap();
+        let report = check_freshness(&root, "书名");
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.stale.len(), 1);
+        assert_eq!(report.stale[0].output_file, "result/书名/2.md");
+        assert_eq!(report.stale[0].batch_id, MANUAL_BATCH_ID);
+        assert!(report.stale[0].changed[0].current_hash.is_some());
+
+        fs::remove_file(novel_dir.join("01.txt")).unwrap();
+        assert_eq!(check_freshness(&root, "书名").stale.len(), 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,270 @@
+//! 写作目的热力图：统计每章细纲中各"写作目的"（purpose）标签出现的次数，供前端画章节 × 标签的热力图。
+//!
+//! 细纲来自流水线 Phase 3 写入数据库的 outline_json，`result/<书名>/N.outline.json` 存在时以文件为准。
+//! AI 给出的目的是自由文本（"打脸爽点"、"制造冲突，埋下伏笔"），先按分隔符拆开，再按关键词映射到
+//! 规范标签；映射表可在设置的 `purpose_tag_map` 中补充或覆盖（关键词 → 规范标签），都不命中的计入"其他"。
+//! 没有细纲的章节在矩阵中为 null，与"有细纲但没有该标签"的 0 区分开。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::analysis_batch;
+use crate::arcs::StoryArc;
+
+/// 未能映射到任何规范标签的目的
+pub const OTHER_TAG: &str = "其他";
+const OUTLINE_FILE_SUFFIX: &str = ".outline.json";
+
+/// 内置映射：关键词 → 规范标签，按顺序匹配第一个包含的关键词。规范标签的顺序即词表顺序。
+const DEFAULT_TAG_KEYWORDS: &[(&str, &str)] = &[
+    ("冲突", "冲突"),
+    ("矛盾", "冲突"),
+    ("对抗", "冲突"),
+    ("爽点", "爽点"),
+    ("打脸", "爽点"),
+    ("装逼", "爽点"),
+    ("爽", "爽点"),
+    ("伏笔", "伏笔"),
+    ("铺垫", "伏笔"),
+    ("埋线", "伏笔"),
+    ("危机", "危机"),
+    ("危险", "危机"),
+    ("绝境", "危机"),
+    ("期待", "期待"),
+    ("悬念", "期待"),
+    ("钩子", "期待"),
+    ("压抑", "压抑"),
+    ("憋屈", "压抑"),
+    ("虐", "压抑"),
+    ("金手指", "金手指"),
+    ("系统", "金手指"),
+    ("地图", "转换地图"),
+    ("新角色", "新角色"),
+    ("引入", "新角色"),
+    ("登场", "新角色"),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct TagPeak {
+    pub chapter: usize,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct PurposeHeatmap {
+    /// 标签词表，矩阵的列顺序
+    pub tags: Vec<String>,
+    /// 章节序号（从 1 开始），矩阵的行顺序
+    pub chapters: Vec<usize>,
+    /// 每章各标签的次数；没有细纲的章节为 null
+    pub matrix: Vec<Option<Vec<usize>>>,
+    /// 各标签的总次数
+    pub totals: Vec<usize>,
+    /// 各标签次数最多的章节（并列取最早的一章），总数为 0 时为 null
+    pub peaks: Vec<Option<TagPeak>>,
+    /// 只统计某个故事弧时的弧标题
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arc: Option<String>,
+}
+
+/// 目的文本 → 规范标签的映射
+pub struct TagMapper {
+    /// 按匹配优先级排列：用户配置在前
+    keywords: Vec<(String, String)>,
+    vocabulary: Vec<String>,
+}
+
+impl TagMapper {
+    pub fn new(custom: &BTreeMap<String, String>) -> Self {
+        let mut keywords: Vec<(String, String)> = custom
+            .iter()
+            .filter(|(k, v)| !k.trim().is_empty() && !v.trim().is_empty())
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        // 长关键词优先，"打脸爽点"不会先被"打脸"截走
+        keywords.sort_by_key(|(k, _)| std::cmp::Reverse(k.chars().count()));
+        keywords.extend(DEFAULT_TAG_KEYWORDS.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+
+        let mut vocabulary: Vec<String> = Vec::new();
+        for tag in DEFAULT_TAG_KEYWORDS.iter().map(|(_, v)| *v).chain(custom.values().map(|v| v.trim())) {
+            if !tag.is_empty() && !vocabulary.iter().any(|t| t == tag) {
+                vocabulary.push(tag.to_string());
+            }
+        }
+        vocabulary.push(OTHER_TAG.to_string());
+        TagMapper { keywords, vocabulary }
+    }
+
+    pub fn vocabulary(&self) -> &[String] {
+        &self.vocabulary
+    }
+
+    /// 单个目的文本映射为规范标签
+    pub fn map(&self, purpose: &str) -> &str {
+        let purpose = purpose.trim();
+        if let Some(tag) = self.vocabulary.iter().find(|t| t.as_str() == purpose) {
+            return tag;
+        }
+        self.keywords.iter().find(|(k, _)| purpose.contains(k.as_str())).map_or(OTHER_TAG, |(_, tag)| tag.as_str())
+    }
+}
+
+/// 一章细纲中的目的文本；AI 常把多个目的写在一个字段里，按中英文分隔符拆开
+fn purposes(outline: &Value) -> Vec<String> {
+    let Value::Array(nodes) = outline else {
+        return Vec::new();
+    };
+    nodes
+        .iter()
+        .filter_map(|n| n.get("purpose").and_then(Value::as_str))
+        .flat_map(|p| p.split(['、', '，', ',', '/', '；', ';', '|']))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 一章的标签计数，列顺序与 mapper 的词表一致
+pub fn count_chapter(outline: &Value, mapper: &TagMapper) -> Vec<usize> {
+    let column: HashMap<&str, usize> = mapper.vocabulary().iter().enumerate().map(|(i, t)| (t.as_str(), i)).collect();
+    let mut counts = vec![0; mapper.vocabulary().len()];
+    for purpose in purposes(outline) {
+        counts[column[mapper.map(&purpose)]] += 1;
+    }
+    counts
+}
+
+/// 由各章细纲构建热力图；total_chapters 之内没有细纲的章节为 null
+pub fn build(outlines: &BTreeMap<usize, Value>, total_chapters: usize, mapper: &TagMapper) -> PurposeHeatmap {
+    let last = outlines.keys().next_back().copied().unwrap_or(0).max(total_chapters);
+    build_rows(outlines, (1..=last).collect(), mapper)
+}
+
+/// 只取故事弧内的章节，合计与峰值也只按弧内计算
+pub fn build_arc(outlines: &BTreeMap<usize, Value>, arc: &StoryArc, mapper: &TagMapper) -> PurposeHeatmap {
+    let map = build_rows(outlines, (arc.start_index..=arc.end_index).collect(), mapper);
+    PurposeHeatmap { arc: Some(arc.heading()), ..map }
+}
+
+fn build_rows(outlines: &BTreeMap<usize, Value>, chapters: Vec<usize>, mapper: &TagMapper) -> PurposeHeatmap {
+    let tags = mapper.vocabulary().to_vec();
+    let matrix: Vec<Option<Vec<usize>>> =
+        chapters.iter().map(|n| outlines.get(n).map(|outline| count_chapter(outline, mapper))).collect();
+
+    let mut totals = vec![0; tags.len()];
+    let mut peaks: Vec<Option<TagPeak>> = vec![None; tags.len()];
+    for (chapter, row) in chapters.iter().zip(&matrix) {
+        let Some(row) = row else { continue };
+        for (i, &count) in row.iter().enumerate() {
+            totals[i] += count;
+            if count > 0 && peaks[i].is_none_or(|p| count > p.count) {
+                peaks[i] = Some(TagPeak { chapter: *chapter, count });
+            }
+        }
+    }
+    PurposeHeatmap { tags, chapters, matrix, totals, peaks, arc: None }
+}
+
+/// `result/<书名>/N.outline.json`
+fn outline_files(workspace_root: &Path, novel_title: &str) -> BTreeMap<usize, Value> {
+    let dir = workspace_root.join(analysis_batch::RESULT_DIR).join(novel_title);
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let index = name.strip_suffix(OUTLINE_FILE_SUFFIX)?.parse::<usize>().ok()?;
+            let content = fs::read_to_string(e.path()).ok()?;
+            match serde_json::from_str(&content) {
+                Ok(outline) => Some((index, outline)),
+                Err(e) => {
+                    eprintln!("[Heatmap] 解析 {} 失败，按未分析处理: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// 汇总数据库和 result 目录中的细纲。章节总数取数据库中的章节数与已下载章节数的较大者；
+/// 传 arc 时只统计该弧的章节范围
+pub fn load(
+    workspace_root: &Path,
+    novel_title: &str,
+    custom_map: &BTreeMap<String, String>,
+    arc: Option<&StoryArc>,
+) -> Result<PurposeHeatmap, String> {
+    let mut outlines = BTreeMap::new();
+    let mut total = 0;
+    if let Ok(conn) = crate::db::get_conn() {
+        let rows = crate::db::load_chapter_outlines(&conn, novel_title).map_err(|e| format!("读取细纲失败: {}", e))?;
+        for (index, outline) in rows {
+            total = total.max(index);
+            if let Some(outline) = outline.and_then(|o| serde_json::from_str::<Value>(&o).ok()) {
+                outlines.insert(index, outline);
+            }
+        }
+    }
+    outlines.extend(outline_files(workspace_root, novel_title));
+    let novel_dir = crate::library::novel_dir_in(&crate::library::downloads_dir(workspace_root), novel_title);
+    total = total.max(analysis_batch::chapter_files(&novel_dir).len());
+    let mapper = TagMapper::new(custom_map);
+    Ok(match arc {
+        Some(arc) => build_arc(&outlines, arc, &mapper),
+        None => build(&outlines, total, &mapper),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn purposes_are_mapped_to_canonical_tags() {
+        let mut custom = BTreeMap::new();
+        custom.insert("扮猪吃虎".to_string(), "爽点".to_string());
+        custom.insert("感情线".to_string(), "感情".to_string());
+        let mapper = TagMapper::new(&custom);
+        assert_eq!(mapper.map("打脸爽点"), "爽点");
+        assert_eq!(mapper.map("打脸"), "爽点");
+        assert_eq!(mapper.map("扮猪吃虎"), "爽点");
+        assert_eq!(mapper.map("推进感情线"), "感情");
+        assert_eq!(mapper.map("制造冲突"), "冲突");
+        assert_eq!(mapper.map("日常过渡"), OTHER_TAG);
+        assert!(mapper.vocabulary().ends_with(&["感情".to_string(), OTHER_TAG.to_string()]));
+    }
+
+    #[test]
+    fn missing_outlines_are_null_and_peaks_pick_first_max() {
+        let mapper = TagMapper::new(&BTreeMap::new());
+        let mut outlines = BTreeMap::new();
+        outlines.insert(1, json!([{ "purpose": "制造冲突、埋下伏笔" }, { "purpose": "打脸爽点" }]));
+        outlines.insert(3, json!([{ "purpose": "冲突升级" }, { "purpose": "矛盾激化" }, { "event": "无目的" }]));
+        outlines.insert(4, json!([]));
+        let map = build(&outlines, 5, &mapper);
+
+        let col = |tag: &str| map.tags.iter().position(|t| t == tag).unwrap();
+        assert_eq!(map.chapters, vec![1, 2, 3, 4, 5]);
+        assert!(map.matrix[1].is_none() && map.matrix[4].is_none());
+        assert_eq!(map.matrix[3], Some(vec![0; map.tags.len()]));
+        assert_eq!(map.matrix[0].as_ref().unwrap()[col("伏笔")], 1);
+        assert_eq!(map.totals[col("冲突")], 3);
+        assert_eq!(map.peaks[col("冲突")], Some(TagPeak { chapter: 3, count: 2 }));
+        assert_eq!(map.peaks[col("爽点")], Some(TagPeak { chapter: 1, count: 1 }));
+        assert_eq!(map.peaks[col("危机")], None);
+
+        let arc = StoryArc { name: "中段".to_string(), start_index: 2, end_index: 4 };
+        let map = build_arc(&outlines, &arc, &mapper);
+        assert_eq!(map.chapters, vec![2, 3, 4]);
+        assert_eq!(map.arc.as_deref(), Some("中段（第 2-4 章）"));
+        assert_eq!(map.totals[col("冲突")], 2);
+        assert_eq!(map.totals[col("爽点")], 0);
+        assert_eq!(map.peaks[col("冲突")], Some(TagPeak { chapter: 3, count: 2 }));
+    }
+}
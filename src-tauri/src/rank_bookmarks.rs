@@ -0,0 +1,261 @@
+//! 榜单书签：常扫的榜单存进设置的 `rank_bookmarks`，一键重扫。
+//!
+//! 重扫按书签的默认参数走完整流水线（下载 + 分析）或仅元数据扫榜，两者都会写当天的榜单快照
+//! （见 [`rank_snapshots`]）。扫完后与上一份快照比对，把新上榜的本数等记入书签的 `last_result`，
+//! 列表据此显示"上次扫描：3 天前，新增 4 本"。删除书签不会删除 `ranks/` 下的历史快照。
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::rank_snapshots;
+use crate::settings;
+
+/// 重扫完成后推送的事件，payload 为 [`RankBookmarkView`]
+pub const COMPLETED_EVENT: &str = "rank-bookmark-completed";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanMode {
+    /// 完整流水线：下载榜单上的书并分析，生成报告
+    #[default]
+    Full,
+    /// 只抓元数据，见 [`crate::rank_metadata`]
+    MetadataOnly,
+}
+
+/// 重扫时使用的默认参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScanParams {
+    pub mode: ScanMode,
+    /// 仅元数据模式：汇总到 rank_metadata.json
+    pub consolidate: bool,
+    /// 仅元数据模式：另导出 CSV
+    pub csv: bool,
+}
+
+/// 一次重扫的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RunSummary {
+    pub success: bool,
+    /// 本次快照的书数
+    pub total: usize,
+    /// 与上一份快照相比新上榜 / 落榜的书数；首次扫描时为 None
+    pub entered: Option<usize>,
+    pub exited: Option<usize>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RankBookmark {
+    pub url: String,
+    pub platform: String,
+    pub params: ScanParams,
+    /// 上次重扫完成的时间（RFC 3339）
+    pub last_run: Option<String>,
+    pub last_result: Option<RunSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RankBookmarkView {
+    pub name: String,
+    #[serde(flatten)]
+    pub bookmark: RankBookmark,
+    /// 如"上次扫描：3 天前，新增 4 本"，从未扫描时为 None
+    pub last_run_label: Option<String>,
+}
+
+fn ago(then: DateTime<Local>, now: DateTime<Local>) -> String {
+    let elapsed = now.signed_duration_since(then);
+    match (elapsed.num_days(), elapsed.num_hours(), elapsed.num_minutes()) {
+        (d, _, _) if d > 0 => format!("{} 天前", d),
+        (_, h, _) if h > 0 => format!("{} 小时前", h),
+        (_, _, m) if m > 0 => format!("{} 分钟前", m),
+        _ => "刚刚".to_string(),
+    }
+}
+
+/// 列表中显示的上次扫描说明
+pub fn describe(bookmark: &RankBookmark, now: DateTime<Local>) -> Option<String> {
+    let then = DateTime::parse_from_rfc3339(bookmark.last_run.as_deref()?).ok()?.with_timezone(&Local);
+    let result = match &bookmark.last_result {
+        Some(RunSummary { success: false, .. }) => "失败".to_string(),
+        Some(RunSummary { entered: Some(n), .. }) => format!("新增 {} 本", n),
+        Some(RunSummary { total, .. }) => format!("首次扫描，共 {} 本", total),
+        None => return Some(format!("上次扫描：{}", ago(then, now))),
+    };
+    Some(format!("上次扫描：{}，{}", ago(then, now), result))
+}
+
+pub fn view(name: &str, bookmark: &RankBookmark) -> RankBookmarkView {
+    RankBookmarkView { name: name.to_string(), bookmark: bookmark.clone(), last_run_label: describe(bookmark, Local::now()) }
+}
+
+/// 当天快照与上一份快照比对，得出本次的结果摘要。当天没有快照（扫榜失败）时为失败
+pub fn summarize(workspace_root: &Path, rank_url: &str) -> RunSummary {
+    let rank_id = rank_snapshots::rank_id(rank_url);
+    let dates = rank_snapshots::list(workspace_root, &rank_id);
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let Some(latest) = dates.last().filter(|d| **d == today) else {
+        return RunSummary { error: Some("今天没有生成榜单快照".to_string()), ..Default::default() };
+    };
+    let current = match rank_snapshots::load(workspace_root, &rank_id, latest) {
+        Ok(snapshot) => snapshot,
+        Err(e) => return RunSummary { error: Some(e), ..Default::default() },
+    };
+    let previous = dates
+        .iter()
+        .rev()
+        .nth(1)
+        .and_then(|date| rank_snapshots::load(workspace_root, &rank_id, date).ok().map(|s| (date, s)));
+    let (entered, exited) = match &previous {
+        Some((date, prev)) => {
+            let diff = rank_snapshots::diff(&rank_id, date, &prev.entries, latest, &current.entries);
+            (Some(diff.entered.len()), Some(diff.exited.len()))
+        }
+        None => (None, None),
+    };
+    RunSummary { success: true, total: current.entries.len(), entered, exited, error: None }
+}
+
+pub fn list(workspace_root: &Path) -> Vec<RankBookmarkView> {
+    settings::load(workspace_root).rank_bookmarks.iter().map(|(name, b)| view(name, b)).collect()
+}
+
+pub fn save(workspace_root: &Path, name: &str, bookmark: RankBookmark) -> Result<RankBookmarkView, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("书签名不能为空".to_string());
+    }
+    if bookmark.url.contains("/book/") || bookmark.url.contains("/info/") {
+        return Err("书签只支持榜单地址".to_string());
+    }
+    settings::update(workspace_root, |settings| {
+        // 修改已有书签时保留上次扫描记录
+        let bookmark = match settings.rank_bookmarks.remove(name) {
+            Some(prev) if prev.url == bookmark.url => RankBookmark { last_run: prev.last_run, last_result: prev.last_result, ..bookmark },
+            _ => bookmark,
+        };
+        settings.rank_bookmarks.insert(name.to_string(), bookmark.clone());
+        Ok(view(name, &bookmark))
+    })
+}
+
+/// 只删除书签，历史快照保留
+pub fn delete(workspace_root: &Path, name: &str) -> Result<(), String> {
+    settings::update(workspace_root, |settings| match settings.rank_bookmarks.remove(name) {
+        Some(_) => Ok(()),
+        None => Err(format!("没有名为 {} 的榜单书签", name)),
+    })
+}
+
+pub fn get(workspace_root: &Path, name: &str) -> Result<RankBookmark, String> {
+    settings::load(workspace_root).rank_bookmarks.remove(name).ok_or_else(|| format!("没有名为 {} 的榜单书签", name))
+}
+
+/// 记录一次重扫的结果：在设置锁内重新读取后只改这个书签，扫描期间对设置的其他修改不受影响
+pub fn record_run(workspace_root: &Path, name: &str, summary: RunSummary) -> Result<RankBookmarkView, String> {
+    settings::update(workspace_root, |settings| {
+        let bookmark = settings.rank_bookmarks.get_mut(name).ok_or_else(|| format!("书签 {} 已被删除", name))?;
+        bookmark.last_run = Some(Local::now().to_rfc3339());
+        bookmark.last_result = Some(summary);
+        Ok(view(name, bookmark))
+    })
+}
+
+/// 完整流水线的报告写入 reports/，与手动扫榜的报告并列
+fn save_report(app: &tauri::AppHandle, workspace_root: &Path, name: &str, report: &str) {
+    let now = Local::now();
+    let dir = workspace_root.join("reports");
+    let path = dir.join(format!("bookmark_report_{}.md", now.format("%Y%m%d_%H%M%S")));
+    let content = format!("# 榜单书签「{}」扫榜报告 ({})\n\n{}", name, now.format("%Y-%m-%d %H:%M:%S"), report);
+    match crate::storage::create_dir_all(&dir).and_then(|()| crate::storage::write_text(&path, &content)) {
+        Ok(()) => crate::events::emit_and_buffer(app, "report-generated", ()),
+        Err(e) => crate::log_to_file_with_root(&format!("[RankBookmark] 写入报告失败: {}", e), Some(workspace_root)),
+    }
+}
+
+/// 按书签的默认参数重扫一次，返回记录后的书签
+pub async fn rescan(app: &tauri::AppHandle, workspace_root: &Path, name: &str) -> Result<RankBookmarkView, String> {
+    let bookmark = get(workspace_root, name)?;
+    let result = match bookmark.params.mode {
+        ScanMode::Full => crate::analysis_engine::run_full_analysis_pipeline(
+            app,
+            &bookmark.url,
+            &bookmark.platform,
+            workspace_root,
+            crate::analysis_engine::PipelineMode::Rank,
+        )
+        .await
+        .map(|report| save_report(app, workspace_root, name, &report)),
+        ScanMode::MetadataOnly => {
+            let targets = [(bookmark.url.clone(), bookmark.platform.clone())];
+            crate::rank_metadata::run(app, workspace_root, &targets, bookmark.params.consolidate, bookmark.params.csv).await.map(|_| ())
+        }
+    };
+    let summary = match result {
+        // 流水线成功但当天没有快照时，summarize 会记为失败
+        Ok(()) => summarize(workspace_root, &bookmark.url),
+        Err(e) => RunSummary { error: Some(e), ..Default::default() },
+    };
+    crate::log_to_file_with_root(&format!("[RankBookmark] {} 重扫完成: {:?}", name, summary), Some(workspace_root));
+    let view = record_run(workspace_root, name, summary)?;
+    crate::events::emit_and_buffer(app, COMPLETED_EVENT, &view);
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn describes_last_run_relative_to_now() {
+        let now = Local::now();
+        let mut bookmark = RankBookmark {
+            last_run: Some((now - Duration::days(3)).to_rfc3339()),
+            last_result: Some(RunSummary { success: true, total: 50, entered: Some(4), exited: Some(4), error: None }),
+            ..Default::default()
+        };
+        assert_eq!(describe(&bookmark, now).as_deref(), Some("上次扫描：3 天前，新增 4 本"));
+        bookmark.last_result = Some(RunSummary { success: true, total: 50, ..Default::default() });
+        assert_eq!(describe(&bookmark, now).as_deref(), Some("上次扫描：3 天前，首次扫描，共 50 本"));
+        bookmark.last_run = Some((now - Duration::hours(2)).to_rfc3339());
+        bookmark.last_result = Some(RunSummary { error: Some("超时".into()), ..Default::default() });
+        assert_eq!(describe(&bookmark, now).as_deref(), Some("上次扫描：2 小时前，失败"));
+        assert_eq!(describe(&RankBookmark::default(), now), None);
+    }
+
+    #[test]
+    fn deleting_bookmark_keeps_snapshots() {
+        let root = std::env::temp_dir().join(format!("test_rank_bookmarks_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let url = "https://www.qidian.com/rank/yuepiao/";
+        let bookmark = RankBookmark { url: url.into(), platform: "qidian".into(), ..Default::default() };
+        save(&root, "月票榜", bookmark.clone()).unwrap();
+        assert!(save(&root, " ", bookmark).is_err());
+
+        let snapshot_dir = root.join(rank_snapshots::RANKS_DIR).join(rank_snapshots::rank_id(url));
+        std::fs::create_dir_all(&snapshot_dir).unwrap();
+        std::fs::write(snapshot_dir.join("2026-01-01.json"), "{}").unwrap();
+        // 扫描期间的其他修改不会被记录结果覆盖
+        settings::update(&root, |s| {
+            s.keep_raw_ai_output = true;
+            Ok(())
+        })
+        .unwrap();
+        record_run(&root, "月票榜", RunSummary { success: true, total: 3, ..Default::default() }).unwrap();
+        assert!(list(&root)[0].last_run_label.as_deref().unwrap().ends_with("首次扫描，共 3 本"));
+        assert!(settings::load(&root).keep_raw_ai_output);
+
+        delete(&root, "月票榜").unwrap();
+        assert!(list(&root).is_empty());
+        assert!(snapshot_dir.join("2026-01-01.json").exists());
+        assert!(delete(&root, "月票榜").is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
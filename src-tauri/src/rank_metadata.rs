@@ -0,0 +1,277 @@
+//! 仅元数据扫榜：只抓榜单上每本书的书名、标签、字数、简介，不取目录、不下载章节。
+//!
+//! 结果逐本写入小说目录的 info.json，或（`consolidate`）汇总到
+//! `analysis_data/rank_metadata.json`，可同时导出 `rank_metadata.csv` 供表格软件打开。
+//! 书与书之间按资源档位的流水线并发数（[`crate::limits`]）并发。
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::analysis_engine;
+use crate::spiders::{LiveSource, NovelSource, RankEntry};
+use crate::{download, library, novel_info};
+
+pub const CONSOLIDATED_JSON: &str = "rank_metadata.json";
+pub const CONSOLIDATED_CSV: &str = "rank_metadata.csv";
+/// 扫描结束时发出的事件
+pub const COMPLETED_EVENT: &str = "rank-metadata-completed";
+const CSV_HEADER: [&str; 10] = ["榜单", "排名", "书名", "作者", "标签", "字数", "月票/热度", "简介", "链接", "错误"];
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct RankMetadataRow {
+    pub rank_url: String,
+    pub rank: usize,
+    pub title: String,
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    /// 榜单名次、获奖等荣誉，不计入标签
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub honors: Vec<String>,
+    pub word_count: String,
+    pub score: Option<String>,
+    pub description: String,
+    pub url: String,
+    /// 元数据抓取失败的原因，此时书名 / 作者取自榜单
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetadataScanCompleted {
+    /// 汇总 JSON，相对工作区；未汇总时为 None
+    pub output_file: Option<String>,
+    pub csv_file: Option<String>,
+    /// 成功抓到元数据的书数
+    pub profiled: usize,
+    pub failed: usize,
+}
+
+async fn profile_one(app: &tauri::AppHandle, platform: &str, rank_url: &str, entry: &RankEntry) -> RankMetadataRow {
+    if let Some(wait) = crate::spiders::circuit::cooldown_remaining(platform) {
+        tokio::time::sleep(wait).await;
+    }
+    let metadata = LiveSource::new(app).fetch_metadata(platform, &entry.url, false).await;
+    let mut row = RankMetadataRow {
+        rank_url: rank_url.to_string(),
+        rank: entry.position,
+        author: entry.author.clone(),
+        score: entry.score.clone(),
+        url: entry.url.clone(),
+        ..Default::default()
+    };
+    match metadata {
+        Ok(meta) if !meta.title.trim().is_empty() => {
+            row.title = meta.title.trim().to_string();
+            row.tags = meta.tags;
+            row.honors = meta.honors;
+            row.word_count = meta.word_count;
+            row.description = meta.description;
+        }
+        Ok(_) => {
+            row.title = analysis_engine::fallback_title(entry);
+            row.error = Some("未解析到书名".to_string());
+        }
+        Err(e) => {
+            eprintln!("[RankMetadata] 获取元数据失败 [{}]: {}", entry.url, e);
+            row.title = analysis_engine::fallback_title(entry);
+            row.error = Some(e);
+        }
+    }
+    row
+}
+
+/// 抓取一个榜单上所有书的元数据，按名次排序返回。`write_info` 为 true 时写入各书的 info.json。
+pub async fn profile_rank(
+    app: &tauri::AppHandle,
+    workspace_root: &Path,
+    rank_url: &str,
+    platform: &str,
+    write_info: bool,
+) -> Result<Vec<RankMetadataRow>, String> {
+    let scan = analysis_engine::fetch_rank(&LiveSource::new(app), rank_url, platform).await?;
+    if let Err(e) = crate::rank_snapshots::save(workspace_root, rank_url, &scan, None) {
+        eprintln!("[RankMetadata] 保存榜单快照失败: {}", e);
+    }
+    let total = scan.entries.len();
+    let semaphore = Arc::new(Semaphore::new(crate::limits::current().pipeline_workers));
+    let mut handles = Vec::new();
+    for entry in scan.entries {
+        let permit = semaphore.clone().acquire_owned().await.map_err(|e| e.to_string())?;
+        let app = app.clone();
+        let platform = platform.to_string();
+        let rank_url = rank_url.to_string();
+        handles.push(tokio::spawn(crate::priority::scope(crate::priority::current(), async move {
+            let _permit = permit;
+            profile_one(&app, &platform, &rank_url, &entry).await
+        })));
+    }
+
+    let mut rows = Vec::new();
+    for handle in handles {
+        match handle.await {
+            Ok(row) => {
+                crate::progress::emit_progress(
+                    app,
+                    "progress",
+                    format!("元数据 {}/{}: #{} {}", rows.len() + 1, total, row.rank, row.title),
+                );
+                rows.push(row);
+            }
+            Err(e) => eprintln!("[RankMetadata] 任务异常: {}", e),
+        }
+    }
+    rows.sort_by_key(|r| r.rank);
+
+    if write_info {
+        for row in rows.iter().filter(|r| r.error.is_none()) {
+            let novel_dir = library::novel_dir_in(&library::downloads_dir(workspace_root), &row.title);
+            if library::is_unrecognized(&novel_dir) || novel_info::is_archived(&novel_dir) {
+                continue;
+            }
+            if let Err(e) = crate::storage::create_dir_all(&novel_dir) {
+                eprintln!("[RankMetadata] 创建目录失败 {}: {}", novel_dir.display(), e);
+                continue;
+            }
+            let metadata = crate::spiders::fanqie::NovelMetadata {
+                url: row.url.clone(),
+                title: row.title.clone(),
+                tags: row.tags.clone(),
+                word_count: row.word_count.clone(),
+                description: row.description.clone(),
+                honors: row.honors.clone(),
+                ..Default::default()
+            };
+            let mut patch = download::info_patch(&row.title, &row.url, platform, Some(&metadata));
+            if let Some(author) = &row.author {
+                patch.insert("author".into(), author.clone().into());
+            }
+            if let Err(e) = novel_info::merge_or_create_info(&novel_dir, &patch).await {
+                eprintln!("[RankMetadata] 写入 info.json 失败《{}》: {}", row.title, e);
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// 汇总结果的 CSV，每本书一行
+pub fn to_csv(rows: &[RankMetadataRow]) -> String {
+    let records: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.rank_url.clone(),
+                row.rank.to_string(),
+                row.title.clone(),
+                row.author.clone().unwrap_or_default(),
+                row.tags.join("、"),
+                row.word_count.clone(),
+                row.score.clone().unwrap_or_default(),
+                row.description.clone(),
+                row.url.clone(),
+                row.error.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    crate::csv_export::render(&CSV_HEADER, &records)
+}
+
+/// 写入汇总文件，返回 (json, csv) 路径
+pub fn write_consolidated(
+    workspace_root: &Path,
+    rows: &[RankMetadataRow],
+    csv: bool,
+) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let dir = workspace_root.join("analysis_data");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let json_path = dir.join(CONSOLIDATED_JSON);
+    let report = serde_json::json!({
+        "generated_at": chrono::Local::now().to_rfc3339(),
+        "books": rows,
+    });
+    let content = serde_json::to_string_pretty(&report).map_err(|e| format!("序列化失败: {}", e))?;
+    crate::storage::write_atomic(&json_path, content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", json_path.display(), e))?;
+    let csv_path = if csv {
+        let path = dir.join(CONSOLIDATED_CSV);
+        crate::storage::write_atomic(&path, to_csv(rows).as_bytes())
+            .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+        Some(path)
+    } else {
+        None
+    };
+    Ok((json_path, csv_path))
+}
+
+/// 依次扫描各榜单的元数据。CSV 导出基于汇总结果，`csv` 为 true 时也会写出汇总 JSON。
+pub async fn run(
+    app: &tauri::AppHandle,
+    workspace_root: &Path,
+    targets: &[(String, String)],
+    consolidate: bool,
+    csv: bool,
+) -> Result<MetadataScanCompleted, String> {
+    let consolidate = consolidate || csv;
+    let mut rows = Vec::new();
+    for (rank_url, platform) in targets {
+        match profile_rank(app, workspace_root, rank_url, platform, !consolidate).await {
+            Ok(mut ranked) => rows.append(&mut ranked),
+            Err(e) => {
+                eprintln!("[RankMetadata] 扫榜失败 {}: {}", rank_url, e);
+                crate::progress::emit_progress(app, "error", format!("扫榜失败 {}: {}", rank_url, e));
+            }
+        }
+    }
+
+    let failed = rows.iter().filter(|r| r.error.is_some()).count();
+    let mut completed = MetadataScanCompleted { profiled: rows.len() - failed, failed, ..Default::default() };
+    if consolidate && !rows.is_empty() {
+        let (json_path, csv_path) = write_consolidated(workspace_root, &rows, csv)?;
+        let relative = |p: &Path| crate::paths::to_relative(workspace_root, p).unwrap_or_else(|| p.display().to_string());
+        completed.output_file = Some(relative(&json_path));
+        completed.csv_file = csv_path.as_deref().map(relative);
+    }
+    crate::log_to_file_with_root(
+        &format!("[RankMetadata] 完成: {} 本成功 / {} 本失败", completed.profiled, completed.failed),
+        Some(workspace_root),
+    );
+    crate::events::emit_and_buffer(app, COMPLETED_EVENT, &completed);
+    Ok(completed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escapes_fields_and_starts_with_bom() {
+        let rows = vec![RankMetadataRow {
+            rank_url: "https://www.qidian.com/rank/yuepiao/".to_string(),
+            rank: 1,
+            title: "书名".to_string(),
+            tags: vec!["玄幻".to_string(), "系统".to_string()],
+            description: "第一行\n他说\"你好\"，然后走了".to_string(),
+            score: Some("1,234".to_string()),
+            ..Default::default()
+        }];
+        let csv = to_csv(&rows);
+        assert!(csv.starts_with('\u{feff}'));
+        let mut lines = csv.trim_start_matches('\u{feff}').split("\r\n");
+        assert_eq!(lines.next().unwrap().split(',').count(), CSV_HEADER.len());
+        let record = lines.next().unwrap();
+        assert!(record.starts_with("https://www.qidian.com/rank/yuepiao/,1,书名,,玄幻、系统,,\"1,234\",\"第一行\n他说\"\"你好\"\"，然后走了\""));
+    }
+
+    #[test]
+    fn consolidated_files_are_written_under_analysis_data() {
+        let root = std::env::temp_dir().join(format!("test_rank_metadata_{}", std::process::id()));
+        let rows = vec![RankMetadataRow { rank: 2, title: "书名".to_string(), ..Default::default() }];
+        let (json, csv) = write_consolidated(&root, &rows, true).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(report["books"][0]["title"], "书名");
+        assert!(report["books"][0].get("error").is_none());
+        assert!(std::fs::read_to_string(csv.unwrap()).unwrap().contains(",2,书名,"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
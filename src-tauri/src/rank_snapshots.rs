@@ -0,0 +1,251 @@
+//! 榜单快照：每次扫榜（完整流水线和仅元数据扫榜）把按名次排列的 [`RankEntry`] 列表保存到
+//! `<workspace>/ranks/<rank-id>/<日期>.json`，同一天重复扫榜覆盖当天的快照。
+//!
+//! rank-id 由规范化后的榜单 URL 生成（见 [`download::canonical_url`]），同一榜单的移动站 / PC 站
+//! 链接落在同一目录。两次快照按书籍链接比对，得出新上榜、落榜和名次变化，并可渲染为 markdown 写入 `result/`。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::access_probe::AccessProbe;
+use crate::analysis_batch::RESULT_DIR;
+use crate::download;
+use crate::spiders::{RankEntry, RankScan, RankSource};
+
+pub const RANKS_DIR: &str = "ranks";
+const MAX_RANK_ID_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RankSnapshot {
+    pub rank_url: String,
+    pub taken_at: String,
+    pub source: RankSource,
+    pub entries: Vec<RankEntry>,
+    /// 完整流水线开始抓章节前对第一本书的访问探测，见 [`crate::access_probe`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<AccessProbe>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RankMove {
+    pub title: String,
+    pub url: String,
+    pub from: usize,
+    pub to: usize,
+    /// 上升为正，下降为负
+    pub delta: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RankDiff {
+    pub rank_id: String,
+    pub date_a: String,
+    pub date_b: String,
+    /// b 中有、a 中没有（按 b 中名次）
+    pub entered: Vec<RankEntry>,
+    /// a 中有、b 中没有（按 a 中名次）
+    pub exited: Vec<RankEntry>,
+    /// 两次都在榜且名次变化的书（按 b 中名次）
+    pub moved: Vec<RankMove>,
+    pub unchanged: usize,
+}
+
+/// 规范化榜单 URL 生成的目录名：字母数字以外的字符替换为 `_`
+pub fn rank_id(rank_url: &str) -> String {
+    let canonical = download::canonical_url(rank_url);
+    let id: String = canonical
+        .trim_start_matches("https://")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .take(MAX_RANK_ID_CHARS)
+        .collect();
+    id.trim_matches('_').to_string()
+}
+
+/// 接受 rank-id 或榜单 URL；拒绝可能跳出 ranks 目录的值
+pub fn resolve_rank_id(id_or_url: &str) -> Result<String, String> {
+    let value = id_or_url.trim();
+    if value.contains("://") {
+        return Ok(rank_id(value));
+    }
+    let valid = !value.is_empty()
+        && value != "."
+        && value != ".."
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if valid { Ok(value.to_string()) } else { Err(format!("无效的榜单 id: {}", value)) }
+}
+
+fn validate_date(date: &str) -> Result<(), String> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|_| ())
+        .map_err(|_| format!("无效的日期: {}（格式为 YYYY-MM-DD）", date))
+}
+
+fn rank_dir(workspace_root: &Path, rank_id: &str) -> PathBuf {
+    workspace_root.join(RANKS_DIR).join(rank_id)
+}
+
+/// 保存当天的快照，返回文件路径
+pub fn save(workspace_root: &Path, rank_url: &str, scan: &RankScan, access: Option<&AccessProbe>) -> Result<PathBuf, String> {
+    let now = chrono::Local::now();
+    let snapshot = RankSnapshot {
+        rank_url: rank_url.to_string(),
+        taken_at: now.to_rfc3339(),
+        source: scan.source,
+        entries: scan.entries.clone(),
+        access: access.cloned(),
+    };
+    let dir = rank_dir(workspace_root, &rank_id(rank_url));
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let path = dir.join(format!("{}.json", now.format("%Y-%m-%d")));
+    let content = serde_json::to_string_pretty(&snapshot).map_err(|e| format!("序列化失败: {}", e))?;
+    crate::storage::write_atomic(&path, content.as_bytes()).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// 某榜单已有快照的日期，升序
+pub fn list(workspace_root: &Path, rank_id: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(rank_dir(workspace_root, rank_id)) else {
+        return Vec::new();
+    };
+    let mut dates: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_string_lossy().strip_suffix(".json").map(str::to_string))
+        .filter(|date| validate_date(date).is_ok())
+        .collect();
+    dates.sort();
+    dates
+}
+
+pub fn load(workspace_root: &Path, rank_id: &str, date: &str) -> Result<RankSnapshot, String> {
+    validate_date(date)?;
+    let path = rank_dir(workspace_root, rank_id).join(format!("{}.json", date));
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取快照 {} 失败: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析快照 {} 失败: {}", path.display(), e))
+}
+
+/// 书籍在榜单中的标识：链接规范化后比对，没有链接时用书名
+fn book_key(entry: &RankEntry) -> String {
+    if entry.url.trim().is_empty() { entry.title.clone() } else { download::canonical_url(&entry.url) }
+}
+
+pub fn diff(rank_id: &str, date_a: &str, a: &[RankEntry], date_b: &str, b: &[RankEntry]) -> RankDiff {
+    let before: HashMap<String, &RankEntry> = a.iter().map(|e| (book_key(e), e)).collect();
+    let after: HashMap<String, &RankEntry> = b.iter().map(|e| (book_key(e), e)).collect();
+    let mut result = RankDiff {
+        rank_id: rank_id.to_string(),
+        date_a: date_a.to_string(),
+        date_b: date_b.to_string(),
+        entered: Vec::new(),
+        exited: a.iter().filter(|e| !after.contains_key(&book_key(e))).cloned().collect(),
+        moved: Vec::new(),
+        unchanged: 0,
+    };
+    for entry in b {
+        match before.get(&book_key(entry)) {
+            None => result.entered.push(entry.clone()),
+            Some(old) if old.position == entry.position => result.unchanged += 1,
+            Some(old) => result.moved.push(RankMove {
+                title: entry.title.clone(),
+                url: entry.url.clone(),
+                from: old.position,
+                to: entry.position,
+                delta: old.position as i64 - entry.position as i64,
+            }),
+        }
+    }
+    result
+}
+
+pub fn render_markdown(diff: &RankDiff) -> String {
+    let mut out = format!("# 榜单变化 {}\n\n{} → {}\n\n", diff.rank_id, diff.date_a, diff.date_b);
+    out.push_str(&format!("## 新上榜（{}）\n\n", diff.entered.len()));
+    for e in &diff.entered {
+        out.push_str(&format!("- #{} {}\n", e.position, e.title));
+    }
+    out.push_str(&format!("\n## 落榜（{}）\n\n", diff.exited.len()));
+    for e in &diff.exited {
+        out.push_str(&format!("- 原 #{} {}\n", e.position, e.title));
+    }
+    out.push_str(&format!("\n## 名次变化（{}，另有 {} 本不变）\n\n", diff.moved.len(), diff.unchanged));
+    if !diff.moved.is_empty() {
+        out.push_str("| 书名 | 原名次 | 现名次 | 变化 |\n| --- | --- | --- | --- |\n");
+        for m in &diff.moved {
+            let delta = if m.delta > 0 { format!("↑{}", m.delta) } else { format!("↓{}", -m.delta) };
+            out.push_str(&format!("| {} | {} | {} | {} |\n", m.title.replace('|', "\\|"), m.from, m.to, delta));
+        }
+    }
+    out
+}
+
+/// 比对两天的快照，并把 markdown 写入 `result/rank_diff_<rank-id>_<a>_<b>.md`
+pub fn diff_and_render(workspace_root: &Path, rank_id: &str, date_a: &str, date_b: &str) -> Result<(RankDiff, PathBuf), String> {
+    let a = load(workspace_root, rank_id, date_a)?;
+    let b = load(workspace_root, rank_id, date_b)?;
+    let result = diff(rank_id, date_a, &a.entries, date_b, &b.entries);
+    let dir = workspace_root.join(RESULT_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let path = dir.join(format!("rank_diff_{}_{}_{}.md", rank_id, date_a, date_b));
+    crate::storage::write_text(&path, &render_markdown(&result))
+        .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok((result, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(position: usize, title: &str) -> RankEntry {
+        RankEntry {
+            position,
+            title: title.to_string(),
+            url: format!("https://www.qidian.com/book/{}/", title),
+            score: None,
+            author: None,
+        }
+    }
+
+    #[test]
+    fn rank_id_is_stable_across_url_variants() {
+        let id = rank_id("https://m.qidian.com/rank/yuepiao/?source=m");
+        assert_eq!(id, "www.qidian.com_rank_yuepiao");
+        assert_eq!(rank_id("https://www.qidian.com/rank/yuepiao"), id);
+        assert_eq!(resolve_rank_id(&id).unwrap(), id);
+        assert_eq!(resolve_rank_id("https://www.qidian.com/rank/yuepiao/").unwrap(), id);
+        assert!(resolve_rank_id("../secret").is_err());
+        assert!(resolve_rank_id("..").is_err());
+    }
+
+    #[test]
+    fn diff_reports_entered_exited_and_moves() {
+        let a = vec![entry(1, "a"), entry(2, "b"), entry(3, "c"), entry(4, "d")];
+        let b = vec![entry(1, "c"), entry(2, "b"), entry(3, "e"), entry(4, "a")];
+        let d = diff("r", "2026-01-01", &a, "2026-01-08", &b);
+        assert_eq!(d.entered.iter().map(|e| e.title.as_str()).collect::<Vec<_>>(), vec!["e"]);
+        assert_eq!(d.exited.iter().map(|e| e.title.as_str()).collect::<Vec<_>>(), vec!["d"]);
+        assert_eq!(d.moved.iter().map(|m| (m.title.as_str(), m.delta)).collect::<Vec<_>>(), vec![("c", 2), ("a", -3)]);
+        assert_eq!(d.unchanged, 1);
+
+        let md = render_markdown(&d);
+        assert!(md.contains("- #3 e"));
+        assert!(md.contains("| c | 3 | 1 | ↑2 |"));
+        assert!(md.contains("| a | 1 | 4 | ↓3 |"));
+    }
+
+    #[test]
+    fn snapshots_are_listed_by_date() {
+        let root = std::env::temp_dir().join(format!("test_rank_snapshots_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let url = "https://www.qidian.com/rank/yuepiao/";
+        let path = save(&root, url, &RankScan { entries: vec![entry(1, "a")], source: RankSource::Html }, None).unwrap();
+        let id = rank_id(url);
+        let today = path.file_stem().unwrap().to_string_lossy().to_string();
+        fs::write(rank_dir(&root, &id).join("notes.json"), "{}").unwrap();
+        assert_eq!(list(&root, &id), vec![today.clone()]);
+        assert_eq!(load(&root, &id, &today).unwrap().entries, vec![entry(1, "a")]);
+        assert!(load(&root, &id, "../x").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
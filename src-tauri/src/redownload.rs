@@ -0,0 +1,455 @@
+//! 批量重新下载指定章节：改了清洗规则、或发现被 WAF 页面污染的章节后，跨多本书一次补齐。
+//!
+//! 每个任务的清单写在 `redownloads/<id>.json`，记录各书要重下的章节和逐章状态。章节链接取自
+//! chapters.json，没有记录链接的章节直接记为失败。书按站点分组：不同站点并行，同一站点内逐本串行，
+//! 沿用下载的章节间隔与熔断器。被覆盖的旧文件始终记入 [`crate::edit_journal`]，可整本撤销。
+//! 取消后未完成的章节留在清单中，用 `resume_id` 续跑（失败的章节也会重试）。
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::chapter_index::ChapterIndex;
+use crate::download::{self, ChapterReady, DownloadRequest};
+use crate::events::EventSink;
+use crate::spiders::NovelSource;
+use crate::{library, novel_info, paths, storage};
+
+pub const REDOWNLOAD_DIR: &str = "redownloads";
+pub const PROGRESS_EVENT: &str = "redownload-progress";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedownloadItem {
+    pub dir_name: String,
+    pub novel_name: String,
+    /// 章节文件名，如 `01.txt`
+    pub chapter_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RedownloadOptions {
+    pub debug_visible: bool,
+    /// 正文最少字数，缺省按设置中各平台的阈值
+    pub min_chapter_chars: Option<usize>,
+    /// 要续跑的任务 ID，提供时忽略 items
+    pub resume_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobChapter {
+    pub file: String,
+    pub status: ChapterStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobItem {
+    pub dir_name: String,
+    pub novel_name: String,
+    pub platform: String,
+    /// 书籍链接，按它的站点分组
+    pub url: String,
+    pub chapters: Vec<JobChapter>,
+}
+
+impl JobItem {
+    fn count(&self, status: ChapterStatus) -> usize {
+        self.chapters.iter().filter(|c| c.status == status).count()
+    }
+
+    fn pending_indices(&self) -> Vec<usize> {
+        self.chapters
+            .iter()
+            .filter(|c| c.status == ChapterStatus::Pending)
+            .filter_map(|c| chapter_number(&c.file))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedownloadJob {
+    pub id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    pub items: Vec<JobItem>,
+}
+
+impl RedownloadJob {
+    fn count(&self, status: ChapterStatus) -> usize {
+        self.items.iter().map(|i| i.count(status)).sum()
+    }
+
+    fn total(&self) -> usize {
+        self.items.iter().map(|i| i.chapters.len()).sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedownloadProgress {
+    pub job_id: String,
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    /// "progress" | "cancelled" | "completed"
+    pub status: String,
+    pub message: String,
+}
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn chapter_number(file: &str) -> Option<usize> {
+    library::chapter_index_of(file)
+}
+
+fn check_job_id(id: &str) -> Result<(), String> {
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("无效的任务 ID: {}", id))
+    }
+}
+
+fn job_path(workspace_root: &Path, id: &str) -> PathBuf {
+    workspace_root.join(REDOWNLOAD_DIR).join(format!("{}.json", id))
+}
+
+pub fn load(workspace_root: &Path, id: &str) -> Result<RedownloadJob, String> {
+    check_job_id(id)?;
+    let content = fs::read_to_string(job_path(workspace_root, id)).map_err(|_| format!("重新下载任务 {} 不存在", id))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析重新下载任务 {} 失败: {}", id, e))
+}
+
+pub fn save(workspace_root: &Path, job: &mut RedownloadJob) -> Result<(), String> {
+    job.updated_at = now();
+    let path = job_path(workspace_root, &job.id);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(job).map_err(|e| format!("序列化重新下载任务失败: {}", e))?;
+    storage::write_atomic(&path, content.as_bytes()).map_err(|e| format!("写入重新下载任务失败: {}", e))
+}
+
+/// 章节在 chapters.json 中有链接时返回目录序号
+fn check_chapter(index: &ChapterIndex, file: &str) -> Result<usize, String> {
+    if !library::is_chapter_path(file) {
+        return Err(format!("不是章节文件: {}", file));
+    }
+    let n = chapter_number(file).ok_or_else(|| format!("不是章节文件: {}", file))?;
+    match index.get(n) {
+        Some(record) if !record.url.trim().is_empty() => Ok(n),
+        _ => Err("chapters.json 中没有该章的链接，请先更新目录".to_string()),
+    }
+}
+
+/// 书籍链接和平台；没有链接时无法下载
+fn book_source(info: &serde_json::Map<String, serde_json::Value>) -> Result<(String, String), String> {
+    let url = info
+        .get("url")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .ok_or("info.json 中没有书籍链接")?;
+    let platform = info
+        .get("platform")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| crate::guess_platform(url));
+    Ok((url.to_string(), platform))
+}
+
+/// 逐章核对链接：有链接的置为 pending，没有的记为失败
+fn plan_item(workspace_root: &Path, item: &mut JobItem) {
+    let checked = paths::resolve_novel(workspace_root, &item.dir_name, &item.novel_name).and_then(|novel_dir| {
+        let info = novel_info::read_info(&novel_dir)?;
+        Ok((ChapterIndex::load(&novel_dir), book_source(&info)))
+    });
+    for chapter in item.chapters.iter_mut().filter(|c| c.status != ChapterStatus::Completed) {
+        let result = match &checked {
+            Ok((index, Ok(_))) => check_chapter(index, &chapter.file).map(|_| ()),
+            Ok((_, Err(e))) | Err(e) => Err(e.clone()),
+        };
+        (chapter.status, chapter.error) = match result {
+            Ok(()) => (ChapterStatus::Pending, None),
+            Err(e) => (ChapterStatus::Failed, Some(e)),
+        };
+    }
+    if let Ok((_, Ok((url, platform)))) = checked {
+        (item.url, item.platform) = (url, platform);
+    }
+}
+
+/// 新建任务，或载入要续跑的任务（未完成的章节重新核对链接后置为 pending）
+pub fn prepare(workspace_root: &Path, items: Vec<RedownloadItem>, resume_id: Option<&str>) -> Result<RedownloadJob, String> {
+    let mut job = match resume_id.map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => {
+            let job = load(workspace_root, id)?;
+            if job.count(ChapterStatus::Completed) == job.total() {
+                return Err(format!("重新下载任务 {} 已全部完成", id));
+            }
+            job
+        }
+        None => {
+            let items: Vec<JobItem> = items
+                .into_iter()
+                .filter(|item| !item.chapter_files.is_empty())
+                .map(|item| {
+                    let mut seen = HashSet::new();
+                    let chapters = item
+                        .chapter_files
+                        .into_iter()
+                        .map(|f| f.trim().to_string())
+                        .filter(|f| seen.insert(f.clone()))
+                        .map(|file| JobChapter { file, status: ChapterStatus::Pending, error: None })
+                        .collect();
+                    JobItem {
+                        dir_name: item.dir_name,
+                        novel_name: item.novel_name,
+                        platform: String::new(),
+                        url: String::new(),
+                        chapters,
+                    }
+                })
+                .collect();
+            if items.is_empty() {
+                return Err("没有要重新下载的章节".to_string());
+            }
+            let created_at = now();
+            RedownloadJob {
+                id: Local::now().format("%Y%m%d%H%M%S%3f").to_string(),
+                updated_at: created_at.clone(),
+                created_at,
+                completed_at: None,
+                items,
+            }
+        }
+    };
+    for item in &mut job.items {
+        plan_item(workspace_root, item);
+    }
+    save(workspace_root, &mut job)?;
+    Ok(job)
+}
+
+/// 分组用的站点：书籍链接的主机名（去掉 `m.` / `www.`），解析不出时用平台名
+fn site_key(item: &JobItem) -> String {
+    url::Url::parse(&item.url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase()))
+        .map(|h| h.trim_start_matches("m.").trim_start_matches("www.").to_string())
+        .unwrap_or_else(|| item.platform.clone())
+}
+
+struct Runner<'a, E: ?Sized, S> {
+    events: &'a E,
+    source: &'a S,
+    workspace_root: &'a Path,
+    job: Mutex<RedownloadJob>,
+    cancel: &'a Arc<AtomicBool>,
+    options: &'a RedownloadOptions,
+}
+
+impl<E: EventSink + Sync + ?Sized, S: NovelSource> Runner<'_, E, S> {
+    fn emit(&self, status: &str, message: String) {
+        let progress = {
+            let job = self.job.lock().unwrap_or_else(|e| e.into_inner());
+            RedownloadProgress {
+                job_id: job.id.clone(),
+                total: job.total(),
+                completed: job.count(ChapterStatus::Completed),
+                failed: job.count(ChapterStatus::Failed),
+                status: status.to_string(),
+                message,
+            }
+        };
+        crate::events::emit_and_buffer(self.events, PROGRESS_EVENT, progress);
+    }
+
+    /// 重新下载一本书中 pending 的章节，结果写回清单
+    async fn run_item(&self, i: usize) {
+        let item = self.job.lock().unwrap_or_else(|e| e.into_inner()).items[i].clone();
+        let pending = item.pending_indices();
+        if pending.is_empty() {
+            return;
+        }
+        let prepared = paths::resolve_novel(self.workspace_root, &item.dir_name, &item.novel_name).and_then(|novel_dir| {
+            let title = novel_info::read_info(&novel_dir)?
+                .get("title")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| item.novel_name.clone());
+            let catalog = download::catalog_from_index(&title, &ChapterIndex::load(&novel_dir));
+            Ok((novel_dir, catalog))
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<ChapterReady>();
+        let result = match prepared {
+            Ok((novel_dir, catalog)) => {
+                let req = DownloadRequest {
+                    url: item.url.clone(),
+                    platform: item.platform.clone(),
+                    debug_visible: self.options.debug_visible,
+                    selected_indices: Some(pending),
+                    novel_dir: Some(novel_dir),
+                    force: true,
+                    chapter_tx: Some(tx),
+                    cancel: Some(self.cancel.clone()),
+                    catalog: Some(catalog),
+                    min_chapter_chars: self.options.min_chapter_chars,
+                    record_edits: Some(true),
+                    ..Default::default()
+                };
+                download::process_novel_download(self.events, self.source, self.workspace_root, req).await
+            }
+            Err(e) => Err(e),
+        };
+        let mut done = HashSet::new();
+        while let Ok(ready) = rx.try_recv() {
+            done.insert(ready.index);
+        }
+        let failures: HashMap<usize, String> = match &result {
+            Ok(summary) => summary.failures.iter().map(|f| (f.index, f.error.clone())).collect(),
+            Err(_) => HashMap::new(),
+        };
+
+        let (completed, failed) = {
+            let mut job = self.job.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = &mut job.items[i];
+            for chapter in entry.chapters.iter_mut().filter(|c| c.status == ChapterStatus::Pending) {
+                let Some(n) = chapter_number(&chapter.file) else { continue };
+                if done.contains(&n) {
+                    (chapter.status, chapter.error) = (ChapterStatus::Completed, None);
+                } else if let Some(e) = failures.get(&n) {
+                    (chapter.status, chapter.error) = (ChapterStatus::Failed, Some(e.clone()));
+                } else if let Err(e) = &result {
+                    (chapter.status, chapter.error) = (ChapterStatus::Failed, Some(e.clone()));
+                }
+                // 其余是取消时尚未开始的章节，保持 pending 待续跑
+            }
+            let counts = (entry.count(ChapterStatus::Completed), entry.count(ChapterStatus::Failed));
+            if let Err(e) = save(self.workspace_root, &mut job) {
+                eprintln!("[Redownload] {}", e);
+            }
+            counts
+        };
+        self.emit("progress", format!("《{}》: 成功 {} / 失败 {}", item.novel_name, completed, failed));
+    }
+}
+
+/// 执行任务直到完成或取消，返回最终清单（逐书逐章的结果）。cancel 为 [`crate::tasks::register`] 返回的取消标记。
+pub async fn run<E: EventSink + Sync + ?Sized, S: NovelSource>(
+    events: &E,
+    source: &S,
+    workspace_root: &Path,
+    job: RedownloadJob,
+    cancel: Arc<AtomicBool>,
+    options: &RedownloadOptions,
+) -> RedownloadJob {
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, item) in job.items.iter().enumerate().filter(|(_, item)| item.count(ChapterStatus::Pending) > 0) {
+        groups.entry(site_key(item)).or_default().push(i);
+    }
+    let runner = Runner {
+        events,
+        source,
+        workspace_root,
+        job: Mutex::new(job),
+        cancel: &cancel,
+        options,
+    };
+    runner.emit("progress", format!("开始重新下载，共 {} 个站点", groups.len()));
+
+    let runner = &runner;
+    futures::future::join_all(groups.into_values().map(|items| async move {
+        for i in items {
+            if runner.cancel.load(Ordering::Relaxed) {
+                break;
+            }
+            runner.run_item(i).await;
+        }
+    }))
+    .await;
+
+    let cancelled = cancel.load(Ordering::Relaxed);
+    let mut job = runner.job.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if job.count(ChapterStatus::Pending) == 0 {
+        job.completed_at = Some(now());
+    }
+    if let Err(e) = save(workspace_root, &mut job) {
+        eprintln!("[Redownload] {}", e);
+    }
+    let message = format!(
+        "{}: 成功 {} / 失败 {} / 未完成 {}（任务 {}）",
+        if cancelled { "重新下载已取消" } else { "重新下载完成" },
+        job.count(ChapterStatus::Completed),
+        job.count(ChapterStatus::Failed),
+        job.count(ChapterStatus::Pending),
+        job.id
+    );
+    *runner.job.lock().unwrap_or_else(|e| e.into_inner()) = job.clone();
+    runner.emit(if cancelled { "cancelled" } else { "completed" }, message);
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chapter_index::ChapterRecord;
+
+    #[test]
+    fn chapters_without_recorded_url_fail_during_planning() {
+        let root = std::env::temp_dir().join(format!("test_redownload_plan_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let novel_dir = library::downloads_dir(&root).join("书");
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::write(novel_info::info_path(&novel_dir), r#"{"title":"书","url":"https://m.qidian.com/book/1/"}"#).unwrap();
+        let mut index = ChapterIndex::load(&novel_dir);
+        index.update_catalog([
+            ChapterRecord { index: 1, url: "https://www.qidian.com/chapter/1/1/".into(), ..Default::default() },
+            ChapterRecord { index: 2, ..Default::default() },
+        ]);
+        index.save().unwrap();
+
+        let item = |files: &[&str]| RedownloadItem {
+            dir_name: library::DOWNLOADS_DIR.to_string(),
+            novel_name: "书".to_string(),
+            chapter_files: files.iter().map(|f| f.to_string()).collect(),
+        };
+        let job = prepare(&root, vec![item(&["01.txt", "02.txt", "01.txt", "info.json"]), item(&[])], None).unwrap();
+        assert_eq!(job.items.len(), 1);
+        let statuses: Vec<_> = job.items[0].chapters.iter().map(|c| (c.file.as_str(), c.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![("01.txt", ChapterStatus::Pending), ("02.txt", ChapterStatus::Failed), ("info.json", ChapterStatus::Failed)]
+        );
+        assert!(job.items[0].chapters[1].error.as_deref().is_some_and(|e| e.contains("没有该章的链接")));
+        assert_eq!((job.items[0].platform.as_str(), site_key(&job.items[0]).as_str()), ("qidian", "qidian.com"));
+
+        // 续跑时失败的章节重新核对，补上链接后转为 pending
+        let mut index = ChapterIndex::load(&novel_dir);
+        index.update_catalog([ChapterRecord { index: 2, url: "https://www.qidian.com/chapter/1/2/".into(), ..Default::default() }]);
+        index.save().unwrap();
+        let resumed = prepare(&root, Vec::new(), Some(&job.id)).unwrap();
+        assert_eq!(resumed.items[0].pending_indices(), vec![1, 2]);
+        assert!(prepare(&root, Vec::new(), None).is_err());
+        assert!(load(&root, "../x").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
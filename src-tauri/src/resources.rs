@@ -0,0 +1,224 @@
+//! 内置文案资源包：默认提示词、错误说明和面向用户的提示语，措辞只在这里维护一份。
+//!
+//! 文案编译进二进制：短文案在 `resources/strings.json`（扁平的 `键 → 文本`），长提示词是 `resources/prompts/`
+//! 下的 Markdown，登记为 `prompt.<名称>` 键。`<workspace>/overrides/strings.json` 可以按键覆盖其中任意一项，
+//! 只需写要改的键；启动和切换工作区时由 [`reload`] 读入并缓存。未登记的键和非字符串值记录日志后忽略。
+//!
+//! 文本中的 `{name}` 占位符由 [`format`] 替换。提示词、错误信息、进度事件等面向用户的文案只通过本模块的函数取，
+//! 测试会检查代码引用的键都已登记；`[模块]` 开头、只写进 app.log 的诊断日志不在此列。
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+pub const OVERRIDES_DIR: &str = "overrides";
+pub const STRINGS_FILE: &str = "strings.json";
+
+const STRINGS_JSON: &str = include_str!("resources/strings.json");
+
+/// 长提示词：(键, Markdown 原文)
+const PROMPT_FILES: &[(&str, &str)] = &[
+    ("prompt.chapter_outline", include_str!("resources/prompts/chapter_outline.md")),
+    ("prompt.auto_analysis", include_str!("resources/prompts/auto_analysis.md")),
+    ("prompt.scene_analysis", include_str!("resources/prompts/scene_analysis.md")),
+    ("prompt.pipeline_outline", include_str!("resources/prompts/pipeline_outline.md")),
+    ("prompt.review_reader", include_str!("resources/prompts/review_reader.md")),
+    ("prompt.review_editor", include_str!("resources/prompts/review_editor.md")),
+    ("prompt.review_author", include_str!("resources/prompts/review_author.md")),
+];
+
+fn bundle() -> &'static HashMap<String, String> {
+    static BUNDLE: OnceLock<HashMap<String, String>> = OnceLock::new();
+    BUNDLE.get_or_init(|| {
+        let mut strings: HashMap<String, String> = serde_json::from_str(STRINGS_JSON).expect("内置 strings.json 格式错误");
+        for (key, content) in PROMPT_FILES {
+            // 文件末尾的换行不属于提示词
+            strings.insert(key.to_string(), content.strip_suffix('\n').unwrap_or(content).to_string());
+        }
+        strings
+    })
+}
+
+fn overrides() -> &'static RwLock<HashMap<String, String>> {
+    static OVERRIDES: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+pub fn overrides_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(OVERRIDES_DIR).join(STRINGS_FILE)
+}
+
+fn read_file(workspace_root: &Path) -> HashMap<String, String> {
+    let log = |msg: String| crate::log_to_file_with_root(&msg, Some(workspace_root));
+    let path = overrides_path(workspace_root);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    let entries: Map<String, Value> = match serde_json::from_str(content.trim_start_matches('\u{feff}')) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log(format!("[Resources] 解析 {:?} 失败，使用内置文案: {}", path, e));
+            return HashMap::new();
+        }
+    };
+    let mut loaded = HashMap::new();
+    for (key, value) in entries {
+        match value {
+            Value::String(text) if bundle().contains_key(&key) => {
+                loaded.insert(key, text);
+            }
+            Value::String(_) => log(format!("[Resources] 未知的文案键，已忽略: {}", key)),
+            _ => log(format!("[Resources] {} 的覆盖值不是字符串，已忽略", key)),
+        }
+    }
+    loaded
+}
+
+/// 从工作区重新加载文案覆盖（启动、切换工作区时调用）。
+pub fn reload(workspace_root: &Path) {
+    let loaded = read_file(workspace_root);
+    if let Ok(mut guard) = overrides().write() {
+        *guard = loaded;
+    }
+}
+
+/// 键是否已在资源包中登记
+pub fn contains(key: &str) -> bool {
+    bundle().contains_key(key)
+}
+
+/// 当前生效的文案：工作区覆盖优先，其次内置文本。未登记的键原样返回，便于在界面上发现漏登记。
+pub fn text(key: &str) -> String {
+    lookup(&overrides().read().unwrap_or_else(|e| e.into_inner()), key)
+}
+
+fn lookup(overrides: &HashMap<String, String>, key: &str) -> String {
+    if let Some(custom) = overrides.get(key) {
+        return custom.clone();
+    }
+    match bundle().get(key) {
+        Some(text) => text.clone(),
+        None => {
+            eprintln!("[Resources] 未登记的文案键: {}", key);
+            key.to_string()
+        }
+    }
+}
+
+/// 取文案并把 `{name}` 占位符替换为对应参数
+pub fn format(key: &str, args: &[(&str, &str)]) -> String {
+    fill(&text(key), args)
+}
+
+/// 从左到右只扫一遍：参数值（书名、正文选段）里的 `{name}` 不会再被替换；没有对应参数的占位符原样保留
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = tail[1..]
+            .find('}')
+            .and_then(|end| args.iter().find(|(name, _)| *name == &tail[1..=end]).map(|(_, value)| (value, end + 2)));
+        match value {
+            Some((value, len)) => {
+                out.push_str(value);
+                rest = &tail[len..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 资源包中 `prompt.<名称>` 的提示词正文；未登记时为 None
+pub fn prompt(name: &str) -> Option<String> {
+    let key = format!("prompt.{}", name);
+    contains(&key).then(|| text(&key))
+}
+
+/// 内置提示词模板的显示名
+pub fn prompt_label(name: &str) -> String {
+    text(&format!("prompt_label.{}", name))
+}
+
+/// 错误码的通用说明；english 为 false 时取中文
+pub fn error_message(code: &str, english: bool) -> String {
+    text(&format!("error.{}.{}", code, if english { "en" } else { "zh" }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn source_files(dir: &Path, out: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                source_files(&path, out);
+            } else if path.extension().is_some_and(|e| e == "rs") {
+                out.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_referenced_in_code_is_in_the_bundle() {
+        let call = Regex::new(r#"resources::(?:text|format)\(\s*"([^"]+)""#).unwrap();
+        let mut files = Vec::new();
+        source_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut files);
+        let mut referenced = 0;
+        for file in files {
+            let source = fs::read_to_string(&file).unwrap();
+            for key in call.captures_iter(&source).map(|c| c[1].to_string()) {
+                assert!(contains(&key), "{} 引用了未登记的文案键 {}", file.display(), key);
+                referenced += 1;
+            }
+        }
+        assert!(referenced > 0);
+        for code in crate::errors::ErrorCode::ALL {
+            assert!(contains(&format!("error.{}.zh", code)) && contains(&format!("error.{}.en", code)), "{} 缺少说明", code);
+        }
+        for template in crate::prompts::builtin_templates() {
+            assert!(contains(&format!("prompt_label.{}", template.name)), "{} 缺少显示名", template.name);
+        }
+        assert!(bundle().values().all(|text| !text.trim().is_empty()));
+    }
+
+    // 只读 read_file 的结果，不改进程内共享的覆盖表，其他测试取到的仍是内置文案
+    #[test]
+    fn overrides_replace_single_keys_and_ignore_unknown_ones() {
+        let root = std::env::temp_dir().join(format!("test_resources_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(OVERRIDES_DIR)).unwrap();
+        fs::write(overrides_path(&root), r#"{"tray.quit": "关闭", "no.such.key": "x", "tray.show": 1}"#).unwrap();
+
+        let loaded = read_file(&root);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(lookup(&loaded, "tray.quit"), "关闭");
+        assert_eq!(lookup(&loaded, "tray.show"), "显示主界面");
+        assert_eq!(lookup(&loaded, "no.such.key"), "no.such.key");
+        let log = fs::read_to_string(root.join("logs").join("app.log")).unwrap();
+        assert!(log.contains("no.such.key") && log.contains("tray.show"), "日志写进被加载的工作区");
+
+        assert!(read_file(&root.join("missing")).is_empty());
+        assert_eq!(text("tray.quit"), "退出应用");
+        assert!(prompt("chapter_outline").unwrap().ends_with("(一句话概括本章主旨)"));
+        assert_eq!(prompt("female"), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_one_pass() {
+        assert_eq!(format("spider.timeout", &[("detail", "u")]), "请求超时: u");
+        assert_eq!(fill("《{title}》{count} 章", &[("title", "{count}"), ("count", "3")]), "《{count}》3 章");
+        assert_eq!(fill("{a} {missing} {", &[("a", "1")]), "1 {missing} {");
+    }
+}
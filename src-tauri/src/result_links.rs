@@ -0,0 +1,171 @@
+//! `result/<小说>` 分析目录与下载目录中小说的对应关系。
+//!
+//! 小说改名或删除后，分析目录会留下来对不上号。分析索引中有记录的目录按记录的 `novel_title`
+//! 判断对应的小说是否还在；索引里没有记录的目录退回按目录名匹配。`relink_result` 在手动移动后
+//! 把目录、索引和批次清单统一改到新的小说名下。
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::analysis_batch::{self, RESULT_DIR};
+use crate::library;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct OrphanResult {
+    /// `result` 下的目录名
+    pub folder: String,
+    /// 分析索引中记录的小说名，索引没有记录时为空（按目录名匹配）
+    pub indexed_titles: Vec<String>,
+    /// 目录下的文件数
+    pub files: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RelinkReport {
+    pub folder: String,
+    pub index_entries: usize,
+    pub manifests: usize,
+}
+
+fn output_prefix(folder: &str) -> String {
+    format!("{}/{}/", RESULT_DIR, folder)
+}
+
+/// 分析索引中指向该目录的小说名（按结果文件路径或小说名匹配）
+fn indexed_titles(index: &[analysis_batch::IndexEntry], folder: &str) -> Vec<String> {
+    let prefix = output_prefix(folder);
+    let mut titles: Vec<String> = index
+        .iter()
+        .filter(|e| e.novel_title == folder || e.outputs.iter().any(|o| o.output_file.starts_with(&prefix)))
+        .map(|e| e.novel_title.clone())
+        .collect();
+    titles.sort();
+    titles.dedup();
+    titles
+}
+
+/// 找不到对应小说的分析目录
+pub fn find_orphans(workspace_root: &Path) -> Vec<OrphanResult> {
+    let Ok(entries) = fs::read_dir(workspace_root.join(RESULT_DIR)) else {
+        return Vec::new();
+    };
+    let downloads = library::downloads_dir(workspace_root);
+    let index = analysis_batch::load_index(workspace_root);
+    let mut orphans: Vec<OrphanResult> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let folder = e.file_name().to_string_lossy().to_string();
+            if folder.starts_with('.') {
+                return None;
+            }
+            let titles = indexed_titles(&index, &folder);
+            let candidates = if titles.is_empty() { std::slice::from_ref(&folder) } else { titles.as_slice() };
+            if candidates.iter().any(|t| library::is_novel_dir(&downloads.join(t))) {
+                return None;
+            }
+            let files = fs::read_dir(e.path()).map(|d| d.flatten().count()).unwrap_or(0);
+            Some(OrphanResult { folder, indexed_titles: titles, files })
+        })
+        .collect();
+    orphans.sort_by(|a, b| a.folder.cmp(&b.folder));
+    orphans
+}
+
+/// 把 `result/<folder>` 关联到下载目录中的 `novel_name`：目录改名，索引与批次清单中的小说名和结果路径同步更新
+pub fn relink(workspace_root: &Path, folder: &str, novel_name: &str) -> Result<RelinkReport, String> {
+    let result_root = workspace_root.join(RESULT_DIR);
+    let source = crate::paths::resolve_novel(workspace_root, RESULT_DIR, folder)?;
+    if !source.is_dir() {
+        return Err(format!("分析目录不存在: {}", folder));
+    }
+    let novel_dir = crate::paths::resolve_novel(workspace_root, library::DOWNLOADS_DIR, novel_name)?;
+    if !library::is_novel_dir(&novel_dir) {
+        return Err(format!("小说不存在: {}", novel_name));
+    }
+    if folder != novel_name {
+        let target = result_root.join(novel_name);
+        if target.exists() {
+            return Err(format!("分析目录已存在: {}，请先合并或删除", novel_name));
+        }
+        crate::storage::rename(&source, &target).map_err(|e| format!("重命名分析目录失败: {}", e))?;
+    }
+
+    let old_prefix = output_prefix(folder);
+    let new_prefix = output_prefix(novel_name);
+    let repath = |file: &mut String| {
+        if let Some(rest) = file.strip_prefix(&old_prefix) {
+            *file = format!("{}{}", new_prefix, rest);
+        }
+    };
+
+    let mut report = RelinkReport { folder: novel_name.to_string(), ..Default::default() };
+    let mut index = analysis_batch::load_index(workspace_root);
+    for entry in index.iter_mut() {
+        let linked = entry.novel_title == folder || entry.outputs.iter().any(|o| o.output_file.starts_with(&old_prefix));
+        if !linked {
+            continue;
+        }
+        entry.novel_title = novel_name.to_string();
+        entry.outputs.iter_mut().for_each(|o| repath(&mut o.output_file));
+        report.index_entries += 1;
+    }
+    if report.index_entries > 0 {
+        analysis_batch::save_index(workspace_root, &index)?;
+    }
+
+    for batch in analysis_batch::list_batches(workspace_root, novel_name) {
+        let Ok(mut manifest) = analysis_batch::load(workspace_root, novel_name, &batch.id) else { continue };
+        manifest.novel_title = novel_name.to_string();
+        manifest.entries.iter_mut().filter_map(|e| e.output_file.as_mut()).for_each(repath);
+        analysis_batch::save(workspace_root, &mut manifest)?;
+        report.manifests += 1;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{self, AnalysisOutput, SourceChapter};
+
+    #[test]
+    fn orphans_use_index_then_names_and_relink_fixes_them() {
+        let root = std::env::temp_dir().join(format!("test_result_links_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let downloads = library::downloads_dir(&root);
+        for novel in ["新书名", "在库"] {
+            fs::create_dir_all(downloads.join(novel)).unwrap();
+            fs::write(downloads.join(novel).join("01.txt"), "第一章").unwrap();
+        }
+        for folder in ["旧书名", "在库", "已删除"] {
+            fs::create_dir_all(analysis_batch::result_dir(&root, folder)).unwrap();
+        }
+        fs::write(analysis_batch::result_dir(&root, "旧书名").join("01.md"), "细纲").unwrap();
+        let output = AnalysisOutput {
+            output_file: "result/旧书名/01.md".to_string(),
+            sources: vec![SourceChapter::of("01.txt", "第一章".as_bytes())],
+            output_language: None,
+        };
+        provenance::record_export(&root, "旧书名", output).unwrap();
+
+        let orphans = find_orphans(&root);
+        let folders: Vec<&str> = orphans.iter().map(|o| o.folder.as_str()).collect();
+        assert_eq!(folders, vec!["已删除", "旧书名"]);
+        assert_eq!(orphans[1].indexed_titles, vec!["旧书名"]);
+        assert_eq!(orphans[1].files, 1);
+
+        let report = relink(&root, "旧书名", "新书名").unwrap();
+        assert_eq!(report.index_entries, 1);
+        assert!(analysis_batch::result_dir(&root, "新书名").join("01.md").is_file());
+        let index = analysis_batch::load_index(&root);
+        assert_eq!(index[0].novel_title, "新书名");
+        assert_eq!(index[0].outputs[0].output_file, "result/新书名/01.md");
+        assert!(provenance::check_freshness(&root, "新书名").stale.is_empty());
+        assert_eq!(find_orphans(&root).len(), 1);
+
+        assert!(relink(&root, "已删除", "不存在").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,11 +1,12 @@
-use tauri::AppHandle;
-use tokio::time::{interval, Duration};
+use tauri::{AppHandle, Manager};
+use tokio::time::{interval, Duration, Instant};
 use chrono::Local;
 
 pub fn init(app_handle: AppHandle) {
-    // 启动一个后台任务
-    tauri::async_runtime::spawn(async move {
+    // 启动一个后台任务；其中的抓取和 AI 请求排在用户发起的操作之后
+    tauri::async_runtime::spawn(crate::priority::background(async move {
         let mut check_interval = interval(Duration::from_secs(60));
+        let mut last_retry: Option<Instant> = None;
         println!("Scheduler: Loop started.");
         
         loop {
@@ -15,6 +16,39 @@ pub fn init(app_handle: AppHandle) {
             
             // 1. 尝试加载配置 (通常放在工作区根目录)
             let project_root = crate::get_project_root();
+
+            // AI 分析重试队列：设置中开启自动重试时按间隔处理
+            let settings = crate::settings::load(&project_root);
+            let retry = &settings.ai_retry;
+            let due = last_retry.is_none_or(|t| t.elapsed() >= Duration::from_secs(retry.interval_minutes.max(1) * 60));
+            if retry.auto && due {
+                last_retry = Some(Instant::now());
+                let global = app_handle.state::<crate::ai::GlobalAiConfig>().0.lock().ok().and_then(|c| c.clone());
+                let queued = !crate::ai_retry::list(&project_root).is_empty();
+                if let (Some(global), true) = (global, queued) {
+                    if crate::workspace_lock::ensure_writable(&project_root).is_ok() {
+                        match crate::ai_retry::process(&project_root, &global, &settings, retry.batch_limit, retry.max_attempts).await {
+                            Ok(report) => println!("Scheduler: AI 重试队列 {:?}", report),
+                            Err(e) => eprintln!("Scheduler: AI 重试队列处理失败: {}", e),
+                        }
+                    }
+                }
+            }
+            // 活动周报：到了设置的星期几且当天还没生成过时，汇总前 7 天
+            let today = now.date_naive();
+            if settings.activity_digest.is_due(today) {
+                let window = crate::activity_digest::DigestWindow::last_week(today);
+                let exists = crate::activity_digest::digest_path(&project_root, window.until).exists();
+                if !exists && crate::workspace_lock::ensure_writable(&project_root).is_ok() {
+                    match crate::activity_digest::generate(&project_root, window) {
+                        Ok(digest) => {
+                            println!("Scheduler: 活动周报已生成 {}", digest.path);
+                            crate::events::emit_and_buffer(&app_handle, "report-generated", ());
+                        }
+                        Err(e) => eprintln!("Scheduler: 生成活动周报失败: {}", e),
+                    }
+                }
+            }
             let config_path = project_root.join("workflow_config.json");
             
             if let Ok(content) = std::fs::read_to_string(&config_path) {
@@ -23,6 +57,10 @@ pub fn init(app_handle: AppHandle) {
                     let enabled = config["enabled"].as_bool().unwrap_or(false);
 
                     if enabled && current_time == target_time {
+                        if let Err(e) = crate::workspace_lock::ensure_writable(&project_root) {
+                            println!("Scheduler: 跳过本次扫榜: {}", e);
+                            continue;
+                        }
                         println!("Scheduler: Time to scan! [{}]", current_time);
                         
                         let workspace_root_buf = project_root.clone();
@@ -61,7 +99,7 @@ pub fn init(app_handle: AppHandle) {
                             let reports_dir = workspace_root.join("reports");
                             let _ = std::fs::create_dir_all(&reports_dir);
                             let report_path = reports_dir.join(format!("report_{}.md", Local::now().format("%Y-%m-%d")));
-                            let _ = std::fs::write(&report_path, full_report);
+                            let _ = crate::storage::write_text(&report_path, &full_report);
                             println!("Scheduler: Final report generated at {:?}", report_path);
                         } else {
                             println!("Scheduler: All ranks failed, no report saved.");
@@ -70,5 +108,5 @@ pub fn init(app_handle: AppHandle) {
                 }
             }
         }
-    });
+    }));
 }
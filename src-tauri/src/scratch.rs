@@ -0,0 +1,171 @@
+//! 临时文档：没有对应小说目录的粘贴文本（如朋友发来的一章），也能走分析 / 保存 / 批次历史。
+//!
+//! 文档存放在 `<workspace>/scratch/<id>/`：正文按章节文件格式写入 `01.txt`，标题和创建时间写在
+//! `scratch.json`。文档目录可以直接当作小说目录交给 [`analysis_batch`]，结果以 `scratch/<id>`
+//! 为"书名"保存到 `result/scratch/<id>/`，批次清单和分析索引照常记录。
+//!
+//! [`ContentSource`] 统一分析命令的正文来源：下载目录中的章节、临时文档或直接传入的文本。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{analysis_batch, library, paths, storage};
+
+pub const SCRATCH_DIR: &str = "scratch";
+const META_FILE: &str = "scratch.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScratchDocument {
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    /// 正文字数
+    pub chars: usize,
+}
+
+/// 分析命令的正文来源
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ContentSource {
+    /// 下载目录中某本小说的章节文件（如 `05.txt`，分目录存放时为 `chapters/000/05.txt`）
+    Chapter { dir_name: String, novel_name: String, chapter_file: String },
+    Scratch { id: String },
+    Inline { content: String },
+}
+
+impl ContentSource {
+    pub fn read(&self, workspace_root: &Path) -> Result<String, String> {
+        match self {
+            ContentSource::Chapter { dir_name, novel_name, chapter_file } => {
+                if !library::is_chapter_path(chapter_file) {
+                    return Err(format!("无效的章节文件名: {}", chapter_file));
+                }
+                let path = paths::resolve_novel(workspace_root, dir_name, novel_name)?.join(chapter_file);
+                fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", chapter_file, e))
+            }
+            ContentSource::Scratch { id } => read(workspace_root, id),
+            ContentSource::Inline { content } => Ok(content.clone()),
+        }
+    }
+}
+
+fn check_id(id: &str) -> Result<(), String> {
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("无效的临时文档 ID: {}", id))
+    }
+}
+
+/// 文档目录，可直接作为小说目录使用；文档不存在时报错
+pub fn dir(workspace_root: &Path, id: &str) -> Result<PathBuf, String> {
+    check_id(id)?;
+    let dir = workspace_root.join(SCRATCH_DIR).join(id);
+    if dir.join(META_FILE).is_file() { Ok(dir) } else { Err(format!("临时文档 {} 不存在", id)) }
+}
+
+/// 分析结果和批次使用的"书名"，结果目录为 `result/scratch/<id>/`
+pub fn result_key(id: &str) -> String {
+    format!("{}/{}", SCRATCH_DIR, id)
+}
+
+pub fn create(workspace_root: &Path, title: &str, content: &str) -> Result<ScratchDocument, String> {
+    if content.trim().is_empty() {
+        return Err("正文为空".to_string());
+    }
+    let title = match crate::spiders::clean_text(title) {
+        t if t.is_empty() => "未命名文档".to_string(),
+        t => t,
+    };
+    let now = chrono::Local::now();
+    let doc = ScratchDocument {
+        id: now.format("%Y%m%d%H%M%S%3f").to_string(),
+        title,
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        chars: content.chars().filter(|c| !c.is_whitespace()).count(),
+    };
+    let dir = workspace_root.join(SCRATCH_DIR).join(&doc.id);
+    if dir.exists() {
+        return Err(format!("临时文档 {} 已存在，请稍后重试", doc.id));
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let chapter = library::render_chapter_file(&doc.title, "", content);
+    storage::write_text(&dir.join(library::chapter_file_name(1)), &chapter)
+        .map_err(|e| format!("写入正文失败: {}", e))?;
+    let meta = serde_json::to_string_pretty(&doc).map_err(|e| format!("序列化失败: {}", e))?;
+    storage::write_atomic(&dir.join(META_FILE), meta.as_bytes()).map_err(|e| format!("写入 {} 失败: {}", META_FILE, e))?;
+    Ok(doc)
+}
+
+/// 全部临时文档，从新到旧
+pub fn list(workspace_root: &Path) -> Vec<ScratchDocument> {
+    let Ok(entries) = fs::read_dir(workspace_root.join(SCRATCH_DIR)) else {
+        return Vec::new();
+    };
+    let mut docs: Vec<ScratchDocument> = entries
+        .flatten()
+        .filter_map(|e| fs::read_to_string(e.path().join(META_FILE)).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    docs.sort_by(|a, b| b.id.cmp(&a.id));
+    docs
+}
+
+/// 文档的章节文件内容（含标题头部，与下载的章节一致）
+pub fn read(workspace_root: &Path, id: &str) -> Result<String, String> {
+    let path = dir(workspace_root, id)?.join(library::chapter_file_name(1));
+    fs::read_to_string(&path).map_err(|e| format!("读取临时文档 {} 失败: {}", id, e))
+}
+
+/// 删除文档；delete_results 为 true 时一并删除 `result/scratch/<id>/`。分析索引中的记录保留
+pub fn delete(workspace_root: &Path, id: &str, delete_results: bool) -> Result<(), String> {
+    let dir = dir(workspace_root, id)?;
+    crate::storage::remove(&dir).map_err(|e| format!("删除临时文档 {} 失败: {}", id, e))?;
+    let results = analysis_batch::result_dir(workspace_root, &result_key(id));
+    if delete_results && results.exists() {
+        crate::storage::remove(&results).map_err(|e| format!("删除分析结果失败: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_can_be_created_listed_and_deleted() {
+        let root = std::env::temp_dir().join(format!("test_scratch_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let doc = create(&root, "  朋友发来的一章 ", "他推开门。\n\n外面下着雨。").unwrap();
+        assert_eq!(doc.title, "朋友发来的一章");
+        assert_eq!(doc.chars, 11);
+        assert!(create(&root, "空", " \n ").is_err());
+
+        let doc_dir = dir(&root, &doc.id).unwrap();
+        assert_eq!(analysis_batch::chapter_files(&doc_dir), vec!["01.txt".to_string()]);
+        let source = ContentSource::Scratch { id: doc.id.clone() };
+        assert!(source.read(&root).unwrap().ends_with("外面下着雨。"));
+        assert_eq!(list(&root), vec![doc.clone()]);
+
+        let results = analysis_batch::result_dir(&root, &result_key(&doc.id));
+        assert_eq!(results, root.join("result").join("scratch").join(&doc.id));
+        fs::create_dir_all(&results).unwrap();
+        delete(&root, &doc.id, true).unwrap();
+        assert!(list(&root).is_empty() && !results.exists());
+        assert!(dir(&root, &doc.id).is_err());
+        assert!(dir(&root, "../downloads").is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn content_sources_deserialize_by_kind() {
+        let inline: ContentSource = serde_json::from_str(r#"{"kind":"inline","content":"正文"}"#).unwrap();
+        assert_eq!(inline.read(Path::new("/nonexistent")).unwrap(), "正文");
+        let chapter: ContentSource = serde_json::from_str(
+            r#"{"kind":"chapter","dir_name":"downloads","novel_name":"书","chapter_file":"../x.txt"}"#,
+        )
+        .unwrap();
+        assert!(chapter.read(Path::new("/nonexistent")).unwrap_err().contains("无效的章节文件名"));
+    }
+}
@@ -0,0 +1,170 @@
+//! 多章合页的拆分：部分镜像站一页放 3～5 章，按"一个链接一个文件"保存会得到一个标题错误的超长章节。
+//!
+//! 开启拆分的平台（见 [`crate::spiders::default_segment_pages`]，可在设置中按平台覆盖）下载时，
+//! 原始页面按目录序号存入小说目录下的 [`PAGES_DIR`]，下载状态记在该目录自己的 `chapters.json`；
+//! 下载结束后 [`rebuild`] 按目录顺序扫描每页正文中的章节标题行，拆成连续编号的 `NN.txt`，
+//! 小说目录的 `chapters.json` 中拆出的章节记录共同的来源链接和 `segment`（第几页的第几段）。
+//! 拆分规则 [`split_chapters`] 不依赖下载流程，整本 txt 的分章也用同一套。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::catalog_order;
+use crate::chapter_index::{ChapterIndex, ChapterRecord};
+use crate::library::{self, ChapterFile};
+use crate::storage;
+
+/// 原始页面的存放目录（相对小说目录）
+pub const PAGES_DIR: &str = ".pages";
+/// 超过该字数的行不当作章节标题（正文里提到"第三章"的长句）
+const MAX_HEADING_CHARS: usize = 40;
+/// 以这些标点结尾的行是正文句子，不当作标题（"第一章的内容……。"）
+const SENTENCE_ENDS: &[char] = &['。', '！', '？', '…', '；', '，', '.', '!', '?'];
+
+/// 拆出的章节来自目录第 `page` 条链接的第 `part` 段（共 `parts` 段）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChapterSegment {
+    pub page: usize,
+    pub part: usize,
+    pub parts: usize,
+}
+
+/// 拆分后的统计：目录中已下载的页面数 → 实际得到的章节数
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct SegmentStats {
+    pub catalog_entries: usize,
+    pub chapters: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// 段首的章节标题行；第一个标题之前的文字并入第一段
+    pub title: Option<String>,
+    pub body: String,
+}
+
+fn heading_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^第\s*[0-9０-９零〇一二两三四五六七八九十百千万]+\s*[章回节]").unwrap())
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let is_heading = line.chars().count() <= MAX_HEADING_CHARS
+        && heading_re().is_match(line)
+        && !line.ends_with(SENTENCE_ENDS);
+    is_heading.then_some(line)
+}
+
+/// 按"第N章"标题行拆分正文。找到的标题不超过一个时原样返回一段。
+pub fn split_chapters(text: &str) -> Vec<Segment> {
+    let headings = text.lines().filter(|line| heading(line).is_some()).count();
+    if headings <= 1 {
+        return vec![Segment { title: None, body: text.to_string() }];
+    }
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut preface = String::new();
+    for line in text.lines() {
+        match heading(line) {
+            Some(title) => segments.push(Segment { title: Some(title.to_string()), body: String::new() }),
+            None => {
+                let body = match segments.last_mut() {
+                    Some(segment) => &mut segment.body,
+                    None => &mut preface,
+                };
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+    }
+    for segment in &mut segments {
+        segment.body = segment.body.trim_matches('\n').to_string();
+    }
+    if !preface.trim().is_empty() {
+        let first = &mut segments[0];
+        first.body = format!("{}\n{}", preface.trim_end(), first.body);
+    }
+    segments
+}
+
+/// 按目录顺序把已下载的页面拆成连续编号的章节文件，重写小说目录的 `chapters.json`。
+/// 内容没变的章节文件不重写；编号超出新章节数的旧文件删除。
+pub fn rebuild(novel_dir: &Path, pages: &ChapterIndex) -> Result<SegmentStats, String> {
+    let pages_dir = novel_dir.join(PAGES_DIR);
+    let mut stats = SegmentStats::default();
+    let mut records = Vec::new();
+    for page in pages.records().filter(|r| r.downloaded) {
+        let path = pages_dir.join(library::chapter_file_name(page.index));
+        let Some(file) = storage::read_to_string(&path).ok().and_then(|text| ChapterFile::parse(&text)) else {
+            continue;
+        };
+        stats.catalog_entries += 1;
+        let segments = split_chapters(&file.body);
+        let parts = segments.len();
+        for (i, segment) in segments.into_iter().enumerate() {
+            let index = records.len() + 1;
+            let title = segment.title.unwrap_or_else(|| file.title.clone());
+            let content = library::render_chapter_file(&title, &file.url, &segment.body);
+            let target = novel_dir.join(library::chapter_file_name(index));
+            let bytes = storage::encode_text(&target, &content);
+            if fs::read(&target).ok().as_deref() != Some(bytes.as_slice()) {
+                storage::write_atomic(&target, &bytes)
+                    .map_err(|e| format!("写入 {} 失败: {}", target.display(), e))?;
+            }
+            records.push(ChapterRecord {
+                index,
+                number: catalog_order::chapter_number(&title),
+                title,
+                url: file.url.clone(),
+                downloaded: true,
+                content_hash: Some(storage::content_hash(&bytes)),
+                source: page.source,
+                segment: (parts > 1).then_some(ChapterSegment { page: page.index, part: i + 1, parts }),
+                ..Default::default()
+            });
+        }
+    }
+    stats.chapters = records.len();
+
+    if let Ok(entries) = fs::read_dir(novel_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let stale = library::is_chapter_file_name(&name)
+                && name.trim_end_matches(".txt").parse::<usize>().is_ok_and(|n| n > stats.chapters);
+            if stale {
+                storage::remove(&entry.path()).map_err(|e| format!("删除多余的 {} 失败: {}", name, e))?;
+            }
+        }
+    }
+    ChapterIndex::update(novel_dir, |index| {
+        index.replace_records(records);
+        Ok(())
+    })?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_heading_lines_only() {
+        let text = "前言一句\n第一章 初见\n正文一\n他想起第三章里提到的那件事，久久不能平静，于是又翻开了那本旧书继续往下读\n\n第二章 再会\n正文二\n第 3 章\n正文三";
+        let segments = split_chapters(text);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].title.as_deref(), Some("第一章 初见"));
+        assert!(segments[0].body.starts_with("前言一句\n正文一"));
+        assert_eq!(segments[1].body, "正文二");
+        assert_eq!(segments[2].title.as_deref(), Some("第 3 章"));
+
+        let sentences = split_chapters("第一章 开端\n第一章的正文到此为止。\n第二章 转折\n第二章里他终于醒了！");
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].body, "第一章的正文到此为止。");
+
+        let single = split_chapters("第一章 只有一章\n正文");
+        assert_eq!(single, vec![Segment { title: None, body: "第一章 只有一章\n正文".to_string() }]);
+    }
+}
@@ -0,0 +1,234 @@
+//! 工作区设置：`<workspace>/settings.json`。
+//!
+//! 所有字段都有默认值，文件缺失或字段缺失时按默认处理；读取时解析失败只记日志，不阻断功能。
+//!
+//! 修改一律经 [`update`]：在工作区的设置锁内重新读取、修改、写回。文件无法解析时报错而不是按默认值
+//! 写回，手动改错一处不会清掉模板、书签和 AI 配置。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+/// 公告类非正文章节的标题关键词（未配置时使用）
+pub const DEFAULT_EXTRA_CHAPTER_PATTERNS: &[&str] = &["上架感言", "请假", "通知", "感言", "完本感言"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// 未指定提示词时使用的模板名，缺省为内置"细纲还原"
+    pub default_prompt_template: Option<String>,
+    /// 题材 / 平台 → 模板名。按 info.json 中的题材（或平台）匹配
+    pub genre_prompt_map: BTreeMap<String, String>,
+    /// 用户自定义模板：名称 → 内容。不能与内置模板重名
+    pub prompt_templates: BTreeMap<String, String>,
+    /// 大于 0 时普通下载覆盖内容有变化的章节也记入修改日志（可撤销、可对比），0 表示直接覆盖。
+    /// 批量重新下载始终记录，见 [`crate::edit_journal`]
+    pub keep_chapter_versions: usize,
+    /// 每本书修改历史（`.history/`）备份的总大小上限（MB），超过时删除最旧的备份；缺省为
+    /// [`crate::edit_journal::DEFAULT_MAX_HISTORY_MB`]
+    pub edit_history_max_mb: Option<u64>,
+    /// 下载完成后的通知（webhook / completed.flag），仅对 notify 为 true 的任务执行
+    pub post_download: crate::hooks::PostDownloadHooks,
+    /// 目录中公告类非正文章节的标题关键词，缺省为 [`DEFAULT_EXTRA_CHAPTER_PATTERNS`]。
+    /// 标题带"第N章"的条目不受影响
+    pub extra_chapter_patterns: Option<Vec<String>>,
+    /// 同时存在的爬虫窗口上限，缺省取资源档位的值（`normal` 为 [`crate::browser_spider::DEFAULT_WINDOW_BUDGET`]）
+    pub spider_window_budget: Option<usize>,
+    /// 平台 → 正文最少字数，低于它的章节视为异常内容。未配置的平台用爬虫登记的缺省值，
+    /// 见 [`crate::spiders::default_min_chapter_chars`]
+    pub min_chapter_chars: BTreeMap<String, usize>,
+    /// 写作目的热力图的补充映射：关键词 → 规范标签，优先于内置映射，见 [`crate::purpose_heatmap`]
+    pub purpose_tag_map: BTreeMap<String, String>,
+    /// 调试用：保存 AI 分析结果时，把清理前的原始输出另存为同名 `.raw.md`，见 [`crate::ai::sanitize_markdown`]
+    pub keep_raw_ai_output: bool,
+    /// 重跑分析时已有结果的处理方式，缺省按模型和提示词是否变化决定；设为 `overwrite` 恢复直接覆盖，
+    /// 见 [`crate::analysis_versions`]
+    pub analysis_version_policy: Option<crate::analysis_versions::VersionPolicy>,
+    /// 平台 → 是否拆分一页多章的页面，未配置的平台见 [`crate::spiders::default_segment_pages`]
+    pub segment_pages: BTreeMap<String, bool>,
+    /// 每个章节子目录最多放的章数，目录超过它的新书分目录存放；缺省为
+    /// [`crate::sharding::DEFAULT_CHAPTERS_PER_DIR`]，0 表示始终平铺
+    pub chapters_per_dir: Option<usize>,
+    /// 命名的 AI 配置：名称 → 覆盖的接口地址 / 密钥 / 模型，供单本书的 `user.ai_overrides.profile` 引用，
+    /// 见 [`crate::ai_overrides`]
+    pub ai_profiles: BTreeMap<String, crate::ai_overrides::AiProfile>,
+    /// 网络模式（normal / paused / blocked），启动时恢复，见 [`crate::network_mode`]
+    pub network_mode: crate::network_mode::NetworkMode,
+    /// 资源档位（low / normal / high），决定爬虫窗口、AI 请求和扫榜流水线的并发上限，见 [`crate::limits`]
+    pub resource_profile: crate::limits::ResourceProfile,
+    /// 平台 → 正文检查的阈值和界面词汇，未配置的平台见 [`crate::content_check::ContentCheckConfig::for_platform`]
+    pub content_check: BTreeMap<String, crate::content_check::ContentCheckConfig>,
+    /// 榜单书签：名称 → 榜单地址、平台、重扫参数和上次结果，见 [`crate::rank_bookmarks`]
+    pub rank_bookmarks: BTreeMap<String, crate::rank_bookmarks::RankBookmark>,
+    /// AI 分析重试队列：是否由调度器自动处理、间隔、次数上限，见 [`crate::ai_retry`]
+    pub ai_retry: crate::ai_retry::RetrySettings,
+    /// 写出章节、分析结果和导出时的换行符（lf / crlf）和是否给 `.txt` / `.csv` 加 BOM，见 [`crate::storage::write_text`]
+    pub text_files: crate::storage::TextFormat,
+    /// 活动周报：是否由调度器在指定的星期几自动生成，见 [`crate::activity_digest`]
+    pub activity_digest: crate::activity_digest::DigestSettings,
+    /// AI 接口主机名 → 是否 gzip 压缩请求体、请求体上限，未配置的接口不压缩，见 [`crate::ai::AiRequestOptions`]
+    pub ai_requests: BTreeMap<String, crate::ai::AiRequestOptions>,
+}
+
+impl Settings {
+    /// 分目录存放的章数阈值，关闭时为 None
+    pub fn chapters_per_dir(&self) -> Option<usize> {
+        match self.chapters_per_dir {
+            Some(0) => None,
+            Some(n) => Some(n),
+            None => Some(crate::sharding::DEFAULT_CHAPTERS_PER_DIR),
+        }
+    }
+
+    pub fn edit_history_max_bytes(&self) -> u64 {
+        self.edit_history_max_mb.unwrap_or(crate::edit_journal::DEFAULT_MAX_HISTORY_MB) * 1024 * 1024
+    }
+
+    pub fn spider_window_budget(&self) -> usize {
+        self.spider_window_budget.filter(|&n| n > 0).unwrap_or(self.resource_profile.limits().spider_windows)
+    }
+
+    pub fn min_chapter_chars(&self, platform: &str) -> usize {
+        self.min_chapter_chars
+            .get(platform)
+            .copied()
+            .unwrap_or_else(|| crate::spiders::default_min_chapter_chars(platform))
+    }
+
+    pub fn content_check(&self, platform: &str) -> crate::content_check::ContentCheckConfig {
+        match self.content_check.get(platform) {
+            Some(config) => config.clone().with_platform_vocabulary(platform),
+            None => crate::content_check::ContentCheckConfig::for_platform(platform),
+        }
+    }
+
+    pub fn segment_pages(&self, platform: &str) -> bool {
+        self.segment_pages
+            .get(platform)
+            .copied()
+            .unwrap_or_else(|| crate::spiders::default_segment_pages(platform))
+    }
+
+    pub fn extra_chapter_patterns(&self) -> Vec<String> {
+        match &self.extra_chapter_patterns {
+            Some(patterns) => patterns.clone(),
+            None => DEFAULT_EXTRA_CHAPTER_PATTERNS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+pub fn settings_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(SETTINGS_FILE)
+}
+
+pub fn load(workspace_root: &Path) -> Settings {
+    match try_load(workspace_root) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("[Settings] {}，使用默认设置", e);
+            Settings::default()
+        }
+    }
+}
+
+/// 读取设置：文件不存在时为默认值，读取或解析失败时报错
+pub fn try_load(workspace_root: &Path) -> Result<Settings, String> {
+    let path = settings_path(workspace_root);
+    let content = match crate::storage::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(format!("读取 {} 失败: {}", path.display(), e)),
+    };
+    serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))
+}
+
+/// 同一工作区的设置写入锁（同一目录的不同写法映射到同一把锁）
+fn write_lock(workspace_root: &Path) -> Arc<Mutex<()>> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+    let key = fs::canonicalize(workspace_root).unwrap_or_else(|_| workspace_root.to_path_buf());
+    let mut locks = LOCKS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(key).or_default().clone()
+}
+
+/// 在设置锁内读-改-写 settings.json。文件无法解析时报错，不写回；apply 返回错误时也不写回
+pub fn update<T>(workspace_root: &Path, apply: impl FnOnce(&mut Settings) -> Result<T, String>) -> Result<T, String> {
+    let lock = write_lock(workspace_root);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let mut settings = try_load(workspace_root)?;
+    let output = apply(&mut settings)?;
+    let path = settings_path(workspace_root);
+    let content = serde_json::to_string_pretty(&settings).map_err(|e| format!("序列化设置失败: {}", e))?;
+    crate::storage::write_atomic(&path, content.as_bytes()).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_or_partial_file_uses_defaults() {
+        let dir = std::env::temp_dir().join(format!("test_settings_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(load(&dir), Settings::default());
+
+        fs::write(settings_path(&dir), r#"{"genre_prompt_map":{"女频":"my_female"}}"#).unwrap();
+        let s = load(&dir);
+        assert_eq!(s.genre_prompt_map["女频"], "my_female");
+        assert!(s.default_prompt_template.is_none());
+
+        update(&dir, |current| {
+            current.keep_raw_ai_output = true;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(load(&dir), Settings { keep_raw_ai_output: true, ..s });
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn update_refuses_to_overwrite_an_unparsable_file() {
+        let dir = std::env::temp_dir().join(format!("test_settings_broken_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let broken = r#"{"prompt_templates":{"mine":"..."},}"#;
+        fs::write(settings_path(&dir), broken).unwrap();
+
+        assert_eq!(load(&dir), Settings::default());
+        let err = update(&dir, |s| {
+            s.keep_raw_ai_output = true;
+            Ok(())
+        })
+        .unwrap_err();
+        assert!(err.contains("解析"), "{}", err);
+        assert_eq!(fs::read_to_string(settings_path(&dir)).unwrap(), broken);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let dir = std::env::temp_dir().join(format!("test_settings_concurrent_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    update(&dir, |s| {
+                        s.purpose_tag_map.insert(format!("k{}", i), "v".to_string());
+                        Ok(())
+                    })
+                    .unwrap()
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        assert_eq!(load(&dir).purpose_tag_map.len(), 8);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
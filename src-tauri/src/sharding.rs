@@ -0,0 +1,167 @@
+//! 章节很多的书分目录存放：单个目录里放几千个文件时，文件系统、文件树和部分云同步客户端都会变慢甚至卡死。
+//!
+//! 超过设置中 `chapters_per_dir`（缺省 [`DEFAULT_CHAPTERS_PER_DIR`]）章的书，章节存到小说目录下的
+//! `chapters/000/`、`chapters/001/`……，每个子目录最多放这么多章，文件名仍是 `NN.txt`；
+//! `chapters.json` 中每章记录相对小说目录的路径（[`ChapterRecord::path`]）。按序号找章节文件一律经
+//! [`chapter_path`]，列出章节文件经 [`chapter_files`]，不要再假定章节平铺在小说目录下。
+//! 普通篇幅的书仍然平铺存放；已有的平铺大书用 [`shard_novel`] 迁移。多章合页拆分的书保持平铺。
+//! 迁移经 [`crate::move_journal`] 的事务完成，中途失败或进程退出都不会留下一半平铺、一半分目录的书。
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chapter_index::{ChapterIndex, ChapterRecord};
+use crate::library;
+use crate::move_journal::{PlannedMove, Transaction};
+
+/// 移动日志中的操作名，见 [`sync_index_paths`]
+pub const SHARD_OPERATION: &str = "shard_novel";
+/// 分目录存放时章节子目录的上级（相对小说目录）
+pub const SHARDS_DIR: &str = "chapters";
+/// 每个子目录的章数上限，也是自动分目录的阈值
+pub const DEFAULT_CHAPTERS_PER_DIR: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct ShardReport {
+    /// 移入子目录的章节文件数
+    pub moved: usize,
+    /// 用到的子目录数
+    pub shards: usize,
+    pub chapters_per_dir: usize,
+}
+
+/// 第 index 章分目录存放时的相对路径，如 `chapters/001/2001.txt`
+pub fn shard_relative_path(index: usize, per_dir: usize) -> String {
+    format!("{}/{:03}/{}", SHARDS_DIR, index.saturating_sub(1) / per_dir.max(1), library::chapter_file_name(index))
+}
+
+/// 小说目录是否已分目录存放
+pub fn is_sharded(novel_dir: &Path) -> bool {
+    novel_dir.join(SHARDS_DIR).is_dir()
+}
+
+/// 第 index 章的文件路径：分目录存放的书按 chapters.json 中记录的路径，否则为 `NN.txt`
+pub fn chapter_path(novel_dir: &Path, index: usize) -> PathBuf {
+    if !is_sharded(novel_dir) {
+        return novel_dir.join(library::chapter_file_name(index));
+    }
+    ChapterIndex::load(novel_dir).chapter_path(index)
+}
+
+/// 第 index 章相对小说目录的路径（用 `/` 分隔），与 [`chapter_files`] 返回的写法一致
+pub fn relative_chapter_path(novel_dir: &Path, index: usize) -> String {
+    if !is_sharded(novel_dir) {
+        return library::chapter_file_name(index);
+    }
+    ChapterIndex::load(novel_dir).get(index).map(ChapterRecord::relative_path).unwrap_or_else(|| library::chapter_file_name(index))
+}
+
+fn scan(dir: &Path, prefix: &str, files: &mut Vec<(usize, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_file() || !library::is_chapter_file_name(&name) {
+            continue;
+        }
+        if let Some(index) = library::chapter_index_of(&name) {
+            files.push((index, format!("{}{}", prefix, name)));
+        }
+    }
+}
+
+/// 小说目录下全部章节文件：(序号, 相对路径)，按序号排序。平铺的 `NN.txt` 和 `chapters/*/NN.txt` 都列出
+pub fn chapter_files(novel_dir: &Path) -> Vec<(usize, String)> {
+    let mut files = Vec::new();
+    scan(novel_dir, "", &mut files);
+    if let Ok(shards) = fs::read_dir(novel_dir.join(SHARDS_DIR)) {
+        for shard in shards.flatten().filter(|e| e.path().is_dir()) {
+            let name = shard.file_name().to_string_lossy().to_string();
+            scan(&shard.path(), &format!("{}/{}/", SHARDS_DIR, name), &mut files);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// 新下载是否分目录存放：已分目录的书继续分；目录超过阈值且还没有平铺章节的新书开始分
+pub fn should_shard(novel_dir: &Path, catalog_len: usize, per_dir: usize) -> bool {
+    is_sharded(novel_dir) || (catalog_len > per_dir && chapter_files(novel_dir).is_empty())
+}
+
+/// 给还没有路径的章节记录分配分目录路径（下载前调用，章节随后直接写到子目录）
+pub fn assign_paths(index: &mut ChapterIndex, per_dir: usize) {
+    let pending: Vec<usize> = index.records().filter(|r| r.path.is_none()).map(|r| r.index).collect();
+    for i in pending {
+        index.set_path(i, Some(shard_relative_path(i, per_dir)));
+    }
+}
+
+/// 把平铺的章节移入 `chapters/NNN/`，并在 chapters.json 中记录每章的路径。已在正确子目录中的章节不动；
+/// 中途失败时已移动的章节全部移回，索引不变。
+pub fn shard_novel(novel_dir: &Path, per_dir: usize) -> Result<ShardReport, String> {
+    let per_dir = per_dir.max(1);
+    let mut report = ShardReport { chapters_per_dir: per_dir, ..Default::default() };
+    ChapterIndex::update(novel_dir, |index| {
+        let mut moves = Vec::new();
+        for (i, current) in chapter_files(novel_dir) {
+            let target = shard_relative_path(i, per_dir);
+            if current != target {
+                moves.push(PlannedMove { from: current, to: target.clone() });
+            }
+            index.set_path(i, Some(target));
+        }
+        if !moves.is_empty() {
+            report.moved = Transaction::begin(novel_dir, SHARD_OPERATION, moves)?.commit()?;
+        }
+        assign_paths(index, per_dir);
+        Ok(())
+    })?;
+    report.shards = fs::read_dir(novel_dir.join(SHARDS_DIR)).map(|d| d.flatten().filter(|e| e.path().is_dir()).count()).unwrap_or(0);
+    Ok(report)
+}
+
+/// 按磁盘上实际的章节文件改写 chapters.json 中的路径（移动日志恢复之后调用）：分目录中的章节记录其路径，
+/// 平铺的章节清除路径
+pub fn sync_index_paths(novel_dir: &Path) -> Result<(), String> {
+    ChapterIndex::update(novel_dir, |index| {
+        for (i, file) in chapter_files(novel_dir) {
+            index.set_path(i, file.contains('/').then_some(file));
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_flat_chapters_and_resolves_through_index() {
+        let dir = std::env::temp_dir().join(format!("test_sharding_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut index = ChapterIndex::load(&dir);
+        index.update_catalog((1..=5).map(|i| ChapterRecord { index: i, url: format!("u{}", i), ..Default::default() }));
+        index.save().unwrap();
+        for i in 1..=4 {
+            fs::write(dir.join(library::chapter_file_name(i)), format!("第{}章", i)).unwrap();
+        }
+        assert!(!is_sharded(&dir));
+        assert_eq!(chapter_path(&dir, 3), dir.join("03.txt"));
+        assert!(should_shard(&dir.join("新书"), 3, 2));
+        assert!(!should_shard(&dir, 5, 2));
+
+        let report = shard_novel(&dir, 2).unwrap();
+        assert_eq!((report.moved, report.shards), (4, 2));
+        assert_eq!(shard_relative_path(3, 2), "chapters/001/03.txt");
+        let files: Vec<String> = chapter_files(&dir).into_iter().map(|(_, f)| f).collect();
+        assert_eq!(files, vec!["chapters/000/01.txt", "chapters/000/02.txt", "chapters/001/03.txt", "chapters/001/04.txt"]);
+        assert_eq!(fs::read_to_string(chapter_path(&dir, 3)).unwrap(), "第3章");
+        // 还没下载的章节也已分配好路径
+        assert_eq!(relative_chapter_path(&dir, 5), "chapters/002/05.txt");
+        assert!(should_shard(&dir, 5, 2));
+        assert!(library::is_novel_dir(&dir));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
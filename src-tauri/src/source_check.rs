@@ -0,0 +1,266 @@
+//! 来源链接检查：用 reqwest 直接请求 info.json 中的书籍链接（不走爬虫窗口），
+//! 判断来源页是否仍可访问，结果连同检查时间写入 info.json 的 `source_status`。
+//!
+//! 先发 HEAD，站点不支持 HEAD 时改用 GET。404 / 410 视为已下架；跳转到其他页面
+//! （移动站 `m.` 与 `www.` 之间的跳转不算）记为 redirected。批量检查跳过已归档的书，
+//! 并发数取资源档位的 `source_checks`，见 [`crate::limits`]。
+//!
+//! 更新 / 下载时目录抓取报"作品已下架"（状态码或平台提示页，见 [`crate::spiders::removed_reason`]）
+//! 也经 [`record_removed`] 记为 removed 并发出 [`REMOVED_EVENT`]；定时抓取和自动预取跳过
+//! [`is_removed`] 的书，手动更新或重新检查来源链接不受影响。
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::events::{self, EventSink};
+use crate::{download, library, novel_info};
+
+/// info.json 中的检查结果
+pub const INFO_KEY: &str = "source_status";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 抓取时发现作品已下架后发出的事件，payload 为 [`SourceCheckRow`]
+pub const REMOVED_EVENT: &str = "novel-removed";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceState {
+    Reachable,
+    Redirected,
+    Removed,
+    /// 链接无效、超时或其他 HTTP 错误（如 403 反爬），无法判断
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceStatus {
+    pub state: SourceState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// 跳转后的地址，仅 redirected 时有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceCheckRow {
+    pub title: String,
+    /// 小说目录，相对工作区
+    pub path: String,
+    pub url: Option<String>,
+    pub status: SourceStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SourceCheckSummary {
+    pub checked: usize,
+    /// 来源页已下架（404 / 410）的书
+    pub removed: Vec<String>,
+    pub rows: Vec<SourceCheckRow>,
+}
+
+/// 按最终响应归类
+pub fn classify(requested: &str, status: u16, final_url: &str) -> SourceState {
+    match status {
+        404 | 410 => SourceState::Removed,
+        200..=299 if download::canonical_url(final_url) != download::canonical_url(requested) => SourceState::Redirected,
+        200..=299 => SourceState::Reachable,
+        _ => SourceState::Error,
+    }
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn failed(error: String) -> SourceStatus {
+    SourceStatus { state: SourceState::Error, http_status: None, final_url: None, error: Some(error), checked_at: now() }
+}
+
+/// 请求来源页并归类，不写文件
+pub async fn check_url(client: &Client, url: &str) -> SourceStatus {
+    let url = url.trim();
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => return failed(format!("无效的链接: {}", url)),
+    }
+    let head = client.head(url).timeout(CHECK_TIMEOUT).send().await;
+    let response = match head {
+        Ok(r) if r.status().is_success() || matches!(r.status().as_u16(), 404 | 410) => Ok(r),
+        _ => client.get(url).timeout(CHECK_TIMEOUT).send().await,
+    };
+    match response {
+        Ok(r) => {
+            let status = r.status().as_u16();
+            let final_url = r.url().to_string();
+            let state = classify(url, status, &final_url);
+            SourceStatus {
+                state,
+                http_status: Some(status),
+                final_url: (state == SourceState::Redirected).then_some(final_url),
+                error: (state == SourceState::Error).then(|| format!("HTTP {}", status)),
+                checked_at: now(),
+            }
+        }
+        Err(e) => failed(format!("请求失败: {}", e)),
+    }
+}
+
+/// info.json 中记录的检查结果
+pub fn from_info(info: &serde_json::Map<String, Value>) -> Option<SourceStatus> {
+    info.get(INFO_KEY).and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+/// 检查一本书的来源链接并写入 info.json
+pub async fn check_novel(client: &Client, novel_dir: &Path) -> Result<(Option<String>, SourceStatus), String> {
+    let info = novel_info::read_info(novel_dir)?;
+    let url = info.get("url").and_then(Value::as_str).map(str::trim).filter(|u| !u.is_empty()).map(str::to_string);
+    let status = match &url {
+        Some(url) => {
+            // 网络暂停 / 阻断时不检查，也不把"请求失败"写进 info.json
+            crate::network_mode::gate(url, |_| {}).await?;
+            check_url(client, url).await
+        }
+        None => failed("info.json 中没有书籍链接".to_string()),
+    };
+    write_status(novel_dir, &status).await?;
+    Ok((url, status))
+}
+
+async fn write_status(novel_dir: &Path, status: &SourceStatus) -> Result<(), String> {
+    let value = serde_json::to_value(status).map_err(|e| format!("序列化失败: {}", e))?;
+    novel_info::update_info(novel_dir, false, |info| {
+        info.insert(INFO_KEY.to_string(), value);
+    })
+    .await?;
+    Ok(())
+}
+
+/// info.json 中记录的来源状态是否为已下架
+pub fn is_removed(novel_dir: &Path) -> bool {
+    novel_info::read_info(novel_dir).ok().and_then(|info| from_info(&info)).is_some_and(|s| s.state == SourceState::Removed)
+}
+
+fn check_row(workspace_root: &Path, novel_dir: &Path, url: Option<String>, status: SourceStatus) -> SourceCheckRow {
+    SourceCheckRow {
+        title: novel_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        path: crate::paths::to_relative(workspace_root, novel_dir).unwrap_or_else(|| novel_dir.display().to_string()),
+        url,
+        status,
+    }
+}
+
+/// 抓取时确认作品已下架：source_status 记为 removed（reason 记入 error），发出 [`REMOVED_EVENT`]
+pub async fn record_removed<E: EventSink + ?Sized>(
+    events: &E,
+    workspace_root: &Path,
+    novel_dir: &Path,
+    reason: &str,
+) -> Result<SourceStatus, String> {
+    let status = SourceStatus {
+        state: SourceState::Removed,
+        http_status: None,
+        final_url: None,
+        error: Some(reason.to_string()),
+        checked_at: now(),
+    };
+    write_status(novel_dir, &status).await?;
+    let url = novel_info::read_info(novel_dir).ok().and_then(|info| info.get("url").and_then(Value::as_str).map(str::to_string));
+    events::emit_and_buffer(events, REMOVED_EVENT, check_row(workspace_root, novel_dir, url, status.clone()));
+    Ok(status)
+}
+
+/// 书库中来源状态为已下架的书（含已归档的），按目录名排序
+pub fn list_removed(workspace_root: &Path, library_dir: &Path) -> Vec<SourceCheckRow> {
+    library::scan_library(library_dir)
+        .novels
+        .into_iter()
+        .filter_map(|novel_dir| {
+            let info = novel_info::read_info(&novel_dir).ok()?;
+            let status = from_info(&info).filter(|s| s.state == SourceState::Removed)?;
+            let url = info.get("url").and_then(Value::as_str).map(str::to_string);
+            Some(check_row(workspace_root, &novel_dir, url, status))
+        })
+        .collect()
+}
+
+/// 检查书库中所有未归档的书，结果按目录名排序
+pub async fn check_all(workspace_root: &Path, library_dir: &Path) -> SourceCheckSummary {
+    let novels: Vec<PathBuf> =
+        library::scan_library(library_dir).novels.into_iter().filter(|dir| !novel_info::is_archived(dir)).collect();
+    let client = Client::new();
+    let semaphore = Arc::new(Semaphore::new(crate::limits::current().source_checks));
+    let mut handles = Vec::new();
+    for novel_dir in novels {
+        let Ok(permit) = semaphore.clone().acquire_owned().await else {
+            break;
+        };
+        let client = client.clone();
+        handles.push(tokio::spawn(async move {
+            let _permit = permit;
+            let result = check_novel(&client, &novel_dir).await;
+            (novel_dir, result)
+        }));
+    }
+
+    let mut summary = SourceCheckSummary::default();
+    for handle in handles {
+        let (novel_dir, result) = match handle.await {
+            Ok(done) => done,
+            Err(e) => {
+                eprintln!("[SourceCheck] 任务异常: {}", e);
+                continue;
+            }
+        };
+        let (url, status) = match result {
+            Ok(checked) => checked,
+            Err(e) => (None, failed(e)),
+        };
+        let row = check_row(workspace_root, &novel_dir, url, status);
+        if row.status.state == SourceState::Removed {
+            summary.removed.push(row.title.clone());
+        }
+        summary.rows.push(row);
+    }
+    summary.checked = summary.rows.len();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_are_classified() {
+        let url = "https://book.qidian.com/info/1";
+        assert_eq!(classify(url, 200, url), SourceState::Reachable);
+        assert_eq!(classify("https://m.qidian.com/book/1", 200, "https://www.qidian.com/book/1/"), SourceState::Reachable);
+        assert_eq!(classify(url, 200, "https://www.qidian.com/"), SourceState::Redirected);
+        assert_eq!(classify(url, 404, url), SourceState::Removed);
+        assert_eq!(classify(url, 410, url), SourceState::Removed);
+        assert_eq!(classify(url, 403, url), SourceState::Error);
+    }
+
+    #[test]
+    fn status_round_trips_through_info() {
+        let status = SourceStatus {
+            state: SourceState::Removed,
+            http_status: Some(404),
+            final_url: None,
+            error: None,
+            checked_at: "2026-01-02 03:04:05".to_string(),
+        };
+        let mut info = serde_json::Map::new();
+        info.insert(INFO_KEY.to_string(), serde_json::to_value(&status).unwrap());
+        assert_eq!(info[INFO_KEY]["state"], "removed");
+        assert_eq!(from_info(&info), Some(status));
+        assert_eq!(from_info(&serde_json::Map::new()), None);
+    }
+}
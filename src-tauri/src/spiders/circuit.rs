@@ -0,0 +1,293 @@
+//! 按平台的熔断器。
+//!
+//! 连续 [`failure_threshold`] 次硬失败（见 [`SpiderError::is_hard`]）后熔断，冷却期内
+//! 该平台的请求直接返回 [`SpiderError::CircuitOpen`]；冷却结束后只放行一个探测请求，
+//! 成功则恢复，失败则重新冷却。探测超过 [`probe_timeout`] 仍没有结果（请求被取消、调用方没有 [`record`]）时
+//! 视为丢失，再放行一个新的探测。所有抓取入口都应先 [`before_request`]，结束后 [`record`]。
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::SpiderError;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+/// 熔断阈值，`SPIDER_CIRCUIT_THRESHOLD` 可覆盖
+fn failure_threshold() -> u32 {
+    std::env::var("SPIDER_CIRCUIT_THRESHOLD")
+        .ok()
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_FAILURE_THRESHOLD)
+}
+
+/// 冷却时长，`SPIDER_CIRCUIT_COOLDOWN_SECS` 可覆盖
+fn cooldown() -> Duration {
+    std::env::var("SPIDER_CIRCUIT_COOLDOWN_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_COOLDOWN)
+}
+
+/// 等待探测结果的最长时间，`SPIDER_CIRCUIT_PROBE_TIMEOUT_SECS` 可覆盖
+fn probe_timeout() -> Duration {
+    std::env::var("SPIDER_CIRCUIT_PROBE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&n| n > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_PROBE_TIMEOUT)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed,
+    Open { until: Instant },
+    /// 冷却结束，探测请求进行中；超过 deadline 仍无结果时放行新的探测
+    HalfOpen { deadline: Instant },
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: State,
+    consecutive_failures: u32,
+    total_requests: u64,
+    total_failures: u64,
+    last_error: Option<String>,
+    /// 正文检查判为可疑的章节数（累计 / 连续），见 [`crate::content_check`]
+    total_suspect: u64,
+    consecutive_suspect: u32,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: State::Closed,
+            consecutive_failures: 0,
+            total_requests: 0,
+            total_failures: 0,
+            last_error: None,
+            total_suspect: 0,
+            consecutive_suspect: 0,
+        }
+    }
+
+    /// 放行返回 Ok，熔断中返回需要等待的时间。冷却结束后的第一个请求作为探测放行，
+    /// 探测超时未返回时下一个请求接替探测。
+    fn try_acquire(&mut self, now: Instant, probe_timeout: Duration) -> Result<(), Duration> {
+        match self.state {
+            State::Closed => {}
+            State::Open { until } | State::HalfOpen { deadline: until } if now < until => return Err(until - now),
+            State::Open { .. } | State::HalfOpen { .. } => self.state = State::HalfOpen { deadline: now + probe_timeout },
+        }
+        self.total_requests += 1;
+        Ok(())
+    }
+
+    fn on_result(&mut self, now: Instant, error: Option<&SpiderError>, threshold: u32, cooldown: Duration) {
+        match error {
+            Some(e) if e.is_hard() => {
+                self.total_failures += 1;
+                self.consecutive_failures += 1;
+                self.last_error = Some(e.to_string());
+                if matches!(self.state, State::HalfOpen { .. }) || self.consecutive_failures >= threshold {
+                    self.state = State::Open { until: now + cooldown };
+                }
+            }
+            // 熔断提示本身不是一次请求
+            Some(SpiderError::CircuitOpen { .. }) => {}
+            // 解析失败说明平台有响应，同成功一样重置
+            _ => {
+                self.consecutive_failures = 0;
+                self.state = State::Closed;
+            }
+        }
+    }
+
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        match self.state {
+            State::Open { until } if now < until => Some(until - now),
+            _ => None,
+        }
+    }
+}
+
+fn breakers() -> &'static Mutex<BTreeMap<String, Breaker>> {
+    static BREAKERS: OnceLock<Mutex<BTreeMap<String, Breaker>>> = OnceLock::new();
+    BREAKERS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn with_breaker<T>(platform: &str, f: impl FnOnce(&mut Breaker) -> T) -> T {
+    let mut map = breakers().lock().unwrap_or_else(|e| e.into_inner());
+    f(map.entry(platform.to_string()).or_insert_with(Breaker::new))
+}
+
+/// 请求前检查。熔断中直接返回 [`SpiderError::CircuitOpen`]。
+pub fn before_request(platform: &str) -> Result<(), SpiderError> {
+    let probe_timeout = probe_timeout();
+    with_breaker(platform, |b| b.try_acquire(Instant::now(), probe_timeout)).map_err(|wait| SpiderError::CircuitOpen {
+        platform: platform.to_string(),
+        retry_after_secs: wait.as_secs(),
+    })
+}
+
+/// 记录一次请求结果。
+pub fn record<T>(platform: &str, result: &Result<T, SpiderError>) {
+    let (threshold, cooldown) = (failure_threshold(), cooldown());
+    let opened = with_breaker(platform, |b| {
+        let was_open = matches!(b.state, State::Open { .. });
+        b.on_result(Instant::now(), result.as_ref().err(), threshold, cooldown);
+        !was_open && matches!(b.state, State::Open { .. })
+    });
+    if opened {
+        eprintln!("[Circuit] {} 连续硬失败，熔断 {} 秒", platform, cooldown.as_secs());
+        crate::log_to_file(&format!("[Circuit] {} opened for {}s", platform, cooldown.as_secs()));
+    }
+}
+
+/// 记录一章正文检查的结果，返回该平台当前连续可疑的章数。可疑内容说明请求"成功"了，不影响熔断。
+pub fn record_suspect(platform: &str, suspect: bool) -> u32 {
+    with_breaker(platform, |b| {
+        if suspect {
+            b.total_suspect += 1;
+            b.consecutive_suspect += 1;
+        } else {
+            b.consecutive_suspect = 0;
+        }
+        b.consecutive_suspect
+    })
+}
+
+/// 平台处于熔断冷却期时返回剩余时间。
+pub fn cooldown_remaining(platform: &str) -> Option<Duration> {
+    with_breaker(platform, |b| b.remaining(Instant::now()))
+}
+
+/// 手动恢复某个平台（清空连续失败计数，保留累计统计）。
+pub fn reset(platform: &str) {
+    with_breaker(platform, |b| {
+        b.state = State::Closed;
+        b.consecutive_failures = 0;
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformMetrics {
+    pub platform: String,
+    /// "closed" | "open" | "half_open"
+    pub state: String,
+    pub consecutive_failures: u32,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    /// 熔断剩余秒数
+    pub retry_after_secs: Option<u64>,
+    pub last_error: Option<String>,
+    /// 正文检查判为可疑的章节数
+    pub total_suspect: u64,
+    pub consecutive_suspect: u32,
+}
+
+/// 各平台熔断器状态快照。
+pub fn metrics() -> Vec<PlatformMetrics> {
+    let now = Instant::now();
+    let map = breakers().lock().unwrap_or_else(|e| e.into_inner());
+    map.iter()
+        .map(|(platform, b)| PlatformMetrics {
+            platform: platform.clone(),
+            state: match b.state {
+                State::Closed => "closed",
+                State::Open { until } if now < until => "open",
+                State::Open { .. } | State::HalfOpen { .. } => "half_open",
+            }
+            .to_string(),
+            consecutive_failures: b.consecutive_failures,
+            total_requests: b.total_requests,
+            total_failures: b.total_failures,
+            retry_after_secs: b.remaining(now).map(|d| d.as_secs()),
+            last_error: b.last_error.clone(),
+            total_suspect: b.total_suspect,
+            consecutive_suspect: b.consecutive_suspect,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(600);
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(180);
+
+    fn waf() -> SpiderError {
+        SpiderError::WafBlocked("Just a moment".into())
+    }
+
+    #[test]
+    fn opens_after_consecutive_hard_failures_and_probes_once() {
+        let start = Instant::now();
+        let mut b = Breaker::new();
+        for _ in 0..3 {
+            assert!(b.try_acquire(start, PROBE_TIMEOUT).is_ok());
+            b.on_result(start, Some(&waf()), 3, COOLDOWN);
+        }
+        assert_eq!(b.try_acquire(start + Duration::from_secs(60), PROBE_TIMEOUT), Err(Duration::from_secs(540)));
+
+        // 冷却结束：只放行一个探测请求
+        let later = start + COOLDOWN;
+        assert!(b.try_acquire(later, PROBE_TIMEOUT).is_ok());
+        assert!(b.try_acquire(later, PROBE_TIMEOUT).is_err());
+
+        // 探测失败立即重新熔断
+        b.on_result(later, Some(&SpiderError::Timeout("60s".into())), 3, COOLDOWN);
+        assert_eq!(b.remaining(later), Some(COOLDOWN));
+
+        // 再次探测成功后恢复
+        let probe = later + COOLDOWN;
+        assert!(b.try_acquire(probe, PROBE_TIMEOUT).is_ok());
+        b.on_result(probe, None, 3, COOLDOWN);
+        assert_eq!(b.state, State::Closed);
+        assert_eq!(b.consecutive_failures, 0);
+    }
+
+    #[test]
+    fn lost_probe_is_replaced_after_the_timeout() {
+        let start = Instant::now();
+        let mut b = Breaker::new();
+        for _ in 0..3 {
+            b.on_result(start, Some(&waf()), 3, COOLDOWN);
+        }
+        let later = start + COOLDOWN;
+        assert!(b.try_acquire(later, PROBE_TIMEOUT).is_ok());
+        // 探测一直没有 record：超时前继续拒绝，并给出剩余等待时间
+        let waiting = later + Duration::from_secs(60);
+        assert_eq!(b.try_acquire(waiting, PROBE_TIMEOUT), Err(Duration::from_secs(120)));
+        let expired = later + PROBE_TIMEOUT;
+        assert!(b.try_acquire(expired, PROBE_TIMEOUT).is_ok());
+        assert!(b.try_acquire(expired, PROBE_TIMEOUT).is_err());
+        b.on_result(expired, None, 3, COOLDOWN);
+        assert_eq!(b.state, State::Closed);
+    }
+
+    #[test]
+    fn soft_failures_reset_the_streak() {
+        let now = Instant::now();
+        let mut b = Breaker::new();
+        b.on_result(now, Some(&waf()), 3, COOLDOWN);
+        b.on_result(now, Some(&waf()), 3, COOLDOWN);
+        b.on_result(now, Some(&SpiderError::Other("No chapters found".into())), 3, COOLDOWN);
+        b.on_result(now, Some(&waf()), 3, COOLDOWN);
+        assert_eq!(b.state, State::Closed);
+        assert_eq!(b.total_failures, 3);
+    }
+
+    #[test]
+    fn open_circuit_error_message() {
+        let e = SpiderError::CircuitOpen { platform: "qidian".into(), retry_after_secs: 599 };
+        assert_eq!(e.to_string(), "qidian 平台暂时不可用，已暂停 10 分钟");
+    }
+}
@@ -0,0 +1,245 @@
+//! 解析器回归夹具：录制的真实页面 + 期望的解析结果（快照）。
+//!
+//! 夹具在 `src/spiders/fixtures/corpus/` 下，`index.json` 登记每个页面的平台、类型和原始地址；
+//! 同名的 `.snap.json` 是该页面的解析结果。改选择器后跑测试即可看出哪一代页面结构被改坏了。
+//! 快照缺失或设置 `UPDATE_SNAPSHOTS=1` 时测试会写出当前结果并失败，检查 diff 后提交。
+//! 新页面用 `record_fixture` 命令录制：抓取、去除脚本和个人信息后存入目录并登记。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::{fanqie, qidian, SpiderError};
+use crate::browser_spider::PageFetcher;
+
+/// 夹具目录，相对 src-tauri
+pub const CORPUS_DIR: &str = "src/spiders/fixtures/corpus";
+pub const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureKind {
+    /// 书籍详情页
+    Metadata,
+    /// 目录页
+    Catalog,
+    /// 章节阅读页
+    Chapter,
+    /// 榜单页
+    Rank,
+}
+
+impl FixtureKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            FixtureKind::Metadata => "metadata",
+            FixtureKind::Catalog => "catalog",
+            FixtureKind::Chapter => "chapter",
+            FixtureKind::Rank => "rank",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureEntry {
+    /// 页面文件名（相对夹具目录）
+    pub file: String,
+    pub platform: String,
+    pub kind: FixtureKind,
+    /// 录制时的地址；解析器用它拼接绝对链接、区分移动站
+    pub url: String,
+    /// 页面结构的说明（哪一代改版等）
+    #[serde(default)]
+    pub note: String,
+}
+
+/// 用与线上相同的纯解析函数解析一个夹具页面，结果转为 JSON 以便和快照比较
+pub fn parse(entry: &FixtureEntry, html: &str) -> Result<serde_json::Value, String> {
+    let value = match (entry.platform.as_str(), entry.kind) {
+        (qidian::PLATFORM, FixtureKind::Metadata) if entry.url.contains("m.qidian.com") => {
+            serde_json::to_value(qidian::parse_mobile_metadata(html, &entry.url))
+        }
+        (qidian::PLATFORM, FixtureKind::Metadata) => serde_json::to_value(qidian::parse_metadata(html, &entry.url)),
+        (qidian::PLATFORM, FixtureKind::Catalog) => {
+            serde_json::to_value(qidian::parse_catalog(html, super::max_catalog_chapters()))
+        }
+        (qidian::PLATFORM, FixtureKind::Chapter) => {
+            let (title, content) = qidian::parse_chapter(html).ok_or("找不到正文容器")?;
+            Ok(serde_json::json!({ "title": title, "content": content }))
+        }
+        (qidian::PLATFORM, FixtureKind::Rank) => serde_json::to_value(qidian::parse_rank_entries(html, 0)),
+        (fanqie::PLATFORM, FixtureKind::Metadata) => serde_json::to_value(fanqie::parse_metadata(html, &entry.url)),
+        (fanqie::PLATFORM, FixtureKind::Catalog) => {
+            serde_json::to_value(fanqie::parse_catalog(html, super::max_catalog_chapters()))
+        }
+        (fanqie::PLATFORM, FixtureKind::Chapter) => {
+            let (title, content) = fanqie::parse_chapter_html(html, &entry.url);
+            Ok(serde_json::json!({ "title": title, "content": content }))
+        }
+        (fanqie::PLATFORM, FixtureKind::Rank) => serde_json::to_value(fanqie::parse_rank_entries(html)),
+        (other, _) => return Err(format!("不支持的平台: {}", other)),
+    };
+    value.map_err(|e| e.to_string())
+}
+
+fn scrub_rules() -> &'static [(Regex, &'static str)] {
+    static RULES: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            // 脚本里是登录态、埋点和用户 ID，解析器用不到
+            (r"(?is)<script\b.*?</script\s*>", ""),
+            (r"(?is)<noscript\b.*?</noscript\s*>", ""),
+            (r"(?i)<meta\b[^>]*(csrf|token)[^>]*>", ""),
+            (r#"(?i)\b((?:data-)?(?:uid|user-?id|nick-?name|token|phone|email))="[^"]*""#, r#"$1="""#),
+            (r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "user@example.com"),
+            (r"\b1[3-9]\d{9}\b", "13800000000"),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("内置去敏规则无效"), replacement))
+        .collect()
+    })
+}
+
+/// 去掉脚本和页面中的个人信息（用户 ID、昵称、邮箱、手机号、令牌），保留解析器需要的结构
+pub fn scrub(html: &str) -> String {
+    scrub_rules()
+        .iter()
+        .fold(html.to_string(), |text, (re, replacement)| re.replace_all(&text, *replacement).into_owned())
+}
+
+fn read_index(dir: &Path) -> Result<Vec<FixtureEntry>, String> {
+    let path = dir.join(INDEX_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("解析 {} 失败: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("读取 {} 失败: {}", path.display(), e)),
+    }
+}
+
+/// 夹具文件名 `<平台>_<类型>_<日期>.html`，同一天重复录制时追加序号
+fn fixture_name(dir: &Path, platform: &str, kind: FixtureKind) -> String {
+    let stem = format!("{}_{}_{}", platform, kind.as_str(), chrono::Local::now().format("%Y%m%d"));
+    (1..)
+        .map(|n| if n == 1 { format!("{}.html", stem) } else { format!("{}_{}.html", stem, n) })
+        .find(|name| !dir.join(name).exists())
+        .unwrap_or_default()
+}
+
+/// 抓取页面（起点经 pages，番茄走 HTTP），去敏后存入夹具目录并登记到 index.json，返回文件名。
+/// 快照由下一次测试运行生成。
+pub async fn record<P: PageFetcher + ?Sized>(
+    pages: &P,
+    dir: &Path,
+    platform: &str,
+    kind: FixtureKind,
+    url: &str,
+) -> Result<String, SpiderError> {
+    let html = match platform {
+        qidian::PLATFORM => qidian::fetch_page(pages, url, false).await?,
+        fanqie::PLATFORM => {
+            crate::network_mode::gate(url, |_| {}).await.map_err(SpiderError::Other)?;
+            let resp = reqwest::Client::new()
+                .get(url)
+                .header("User-Agent", "Mozilla/5.0")
+                .send()
+                .await
+                .map_err(|e| SpiderError::Other(e.to_string()))?;
+            resp.text().await.map_err(|e| SpiderError::Other(e.to_string()))?
+        }
+        other => return Err(SpiderError::Other(format!("不支持的平台: {}", other))),
+    };
+
+    let mut index = read_index(dir)?;
+    let file = fixture_name(dir, platform, kind);
+    let write = |path: PathBuf, bytes: &[u8]| {
+        crate::storage::write_atomic(&path, bytes)
+            .map_err(|e| SpiderError::Other(format!("写入 {} 失败: {}", path.display(), e)))
+    };
+    write(dir.join(&file), scrub(&html).as_bytes())?;
+    index.push(FixtureEntry {
+        file: file.clone(),
+        platform: platform.to_string(),
+        kind,
+        url: url.to_string(),
+        note: String::new(),
+    });
+    let json = serde_json::to_string_pretty(&index).map_err(|e| SpiderError::Other(e.to_string()))?;
+    write(dir.join(INDEX_FILE), format!("{}\n", json).as_bytes())?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(CORPUS_DIR)
+    }
+
+    fn snapshot_path(dir: &Path, file: &str) -> PathBuf {
+        dir.join(format!("{}.snap.json", file.trim_end_matches(".html")))
+    }
+
+    #[test]
+    fn corpus_matches_snapshots() {
+        let dir = corpus_dir();
+        let index = read_index(&dir).unwrap();
+        assert!(!index.is_empty(), "夹具目录为空: {}", dir.display());
+        let update = std::env::var("UPDATE_SNAPSHOTS").is_ok_and(|v| v == "1");
+
+        let mut failures = Vec::new();
+        for entry in &index {
+            let html = fs::read_to_string(dir.join(&entry.file)).unwrap_or_else(|e| panic!("{}: {}", entry.file, e));
+            let actual = parse(entry, &html).unwrap_or_else(|e| panic!("{}: {}", entry.file, e));
+            let snap = snapshot_path(&dir, &entry.file);
+            let expected: Option<serde_json::Value> =
+                fs::read_to_string(&snap).ok().map(|text| serde_json::from_str(&text).unwrap());
+            if expected.as_ref() == Some(&actual) {
+                continue;
+            }
+            if update || expected.is_none() {
+                fs::write(&snap, format!("{}\n", serde_json::to_string_pretty(&actual).unwrap())).unwrap();
+            }
+            failures.push(match expected {
+                Some(_) if update => format!("{}: 快照已更新", entry.file),
+                Some(expected) => format!(
+                    "{} ({}): 解析结果与快照不一致\n期望: {}\n实际: {}",
+                    entry.file,
+                    entry.note,
+                    serde_json::to_string_pretty(&expected).unwrap(),
+                    serde_json::to_string_pretty(&actual).unwrap()
+                ),
+                None => format!("{}: 新快照已写入，检查后提交", entry.file),
+            });
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    }
+
+    #[test]
+    fn every_layout_generation_yields_content() {
+        // 快照之外的底线：每个夹具都必须解析出东西，防止把"什么都没解析到"录成快照
+        let dir = corpus_dir();
+        for entry in read_index(&dir).unwrap() {
+            let html = fs::read_to_string(dir.join(&entry.file)).unwrap();
+            let value = parse(&entry, &html).unwrap();
+            let empty = match entry.kind {
+                FixtureKind::Metadata => value["title"] == "Unknown Title",
+                FixtureKind::Catalog | FixtureKind::Rank => value.as_array().is_none_or(|items| items.is_empty()),
+                FixtureKind::Chapter => value["content"].as_str().is_none_or(|text| text.trim().is_empty()),
+            };
+            assert!(!empty, "{} ({}) 没有解析出内容", entry.file, entry.note);
+        }
+    }
+
+    #[test]
+    fn scrub_removes_scripts_and_personal_data() {
+        let html = r#"<meta name="csrf-token" content="abc"><div data-uid="889900" class="user">联系 reader@mail.test 或 13912345678</div>
+<script>window.g_data = { userId: 42 };</script><a href="/book/1035420986/">书</a>"#;
+        assert_eq!(
+            scrub(html),
+            "<div data-uid=\"\" class=\"user\">联系 user@example.com 或 13800000000</div>\n<a href=\"/book/1035420986/\">书</a>"
+        );
+    }
+}
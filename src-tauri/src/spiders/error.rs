@@ -0,0 +1,72 @@
+//! 爬虫层错误类型。熔断器只统计"硬失败"（WAF 拦截、超时），选择器不匹配等解析问题不计入。
+
+use std::fmt;
+
+use crate::resources;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpiderError {
+    /// 被 WAF / 人机验证页拦截
+    WafBlocked(String),
+    /// 浏览器蜘蛛或请求超时
+    Timeout(String),
+    /// 熔断器打开，请求未发出
+    CircuitOpen { platform: String, retry_after_secs: u64 },
+    /// 蜘蛛页面中没有 Tauri 事件 API，document.title 备用通道也未返回页面（环境问题，不计入熔断）
+    EventBridgeUnavailable,
+    /// 回传页面超过 [`super::max_html_bytes`] 上限
+    PageTooLarge { bytes: usize, limit: usize },
+    /// 超大页面分片回传时，收到结束标记但缺少部分分片（IPC 消息被静默丢弃）
+    PayloadIncomplete { missing: Vec<usize>, total: usize },
+    /// 章节链接跳转到了书籍页等非章节页面（章节已下架或目录重排），重试也不会好转
+    ChapterUnavailable { url: String, final_url: String },
+    /// 书籍页 / 目录页返回 404、410 或平台的"作品不存在"提示页（见 [`super::removed_reason`]），作品已下架
+    NovelRemoved { url: String, reason: String },
+    Other(String),
+}
+
+impl SpiderError {
+    /// 是否计入熔断器的连续失败次数
+    pub fn is_hard(&self) -> bool {
+        matches!(self, SpiderError::WafBlocked(_) | SpiderError::Timeout(_))
+    }
+}
+
+impl fmt::Display for SpiderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            SpiderError::WafBlocked(msg) => resources::format("spider.waf_blocked", &[("detail", msg)]),
+            SpiderError::Timeout(msg) => resources::format("spider.timeout", &[("detail", msg)]),
+            SpiderError::CircuitOpen { platform, retry_after_secs } => resources::format(
+                "spider.circuit_open",
+                &[("platform", platform), ("minutes", &retry_after_secs.div_ceil(60).max(1).to_string())],
+            ),
+            SpiderError::EventBridgeUnavailable => resources::text("spider.bridge_unavailable"),
+            SpiderError::PageTooLarge { bytes, limit } => {
+                resources::format("spider.page_too_large", &[("bytes", &bytes.to_string()), ("limit", &limit.to_string())])
+            }
+            SpiderError::PayloadIncomplete { missing, total } => resources::format(
+                "spider.payload_incomplete",
+                &[("total", &total.to_string()), ("missing", &format!("{:?}", missing))],
+            ),
+            SpiderError::ChapterUnavailable { final_url, .. } => {
+                resources::format("spider.chapter_unavailable", &[("final_url", final_url)])
+            }
+            SpiderError::NovelRemoved { url, reason } => resources::format("spider.novel_removed", &[("reason", reason), ("url", url)]),
+            SpiderError::Other(msg) => msg.clone(),
+        };
+        f.write_str(&text)
+    }
+}
+
+impl From<SpiderError> for String {
+    fn from(e: SpiderError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<String> for SpiderError {
+    fn from(msg: String) -> Self {
+        SpiderError::Other(msg)
+    }
+}
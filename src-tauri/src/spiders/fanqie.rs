@@ -1,14 +1,38 @@
 use std::collections::HashMap;
 use reqwest::Client; // Async Client
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 
-#[derive(Debug, Clone, serde::Serialize)]
+use super::{selectors, ChapterSource, RankEntry, RankScan, RankSource, SpiderError};
+
+pub(crate) const PLATFORM: &str = "fanqie";
+/// 微短篇单章只有几百字，阈值需远低于起点
+pub const MIN_CHAPTER_CHARS: usize = 300;
+/// 作品下架后书籍主页显示的提示文字
+pub const REMOVED_MARKERS: &[&str] = &["作品不存在", "该书已下架", "书籍不存在", "作品已下架"];
+/// 未登录时章节页显示的提示文字，见 [`crate::access_probe`]
+pub const LOGIN_MARKERS: &[&str] = &["登录后继续阅读", "请先登录", "登录后可免费阅读"];
+/// 付费章节未订阅时的锁页提示文字
+pub const VIP_MARKERS: &[&str] = &["开通会员", "解锁本章", "付费章节"];
+/// 页面导航 / 推广文字，正文检查用
+pub const UI_VOCABULARY: &[&str] = &["番茄小说", "番茄免费小说", "打开APP", "书评", "催更"];
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct NovelMetadata {
     pub url: String,
     pub title: String,
     pub tags: Vec<String>,
     pub word_count: String,
     pub description: String,
+    /// 榜单名次、获奖等荣誉（如"男生月票榜No.1"），与标签分开保存，不参与标签索引和筛选
+    #[serde(default)]
+    pub honors: Vec<String>,
+    /// 因超出上限被截断的字段（description / tags / honors），见 [`super::tidy_metadata`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub truncated: Vec<String>,
+    /// 非完整详情页得到的元数据来源，如起点移动站兜底的 [`super::qidian::MOBILE_FALLBACK_SOURCE`]；
+    /// 有值时下次更新会重新尝试完整抓取
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
 pub fn get_full_decrypt_map() -> HashMap<String, String> {
@@ -125,35 +149,142 @@ pub async fn fetch_novel_metadata(client: &Client, url: &str) -> Result<NovelMet
         .await
         .map_err(|e| e.to_string())?;
 
+    let status = resp.status().as_u16();
     let html_text = resp.text().await.map_err(|e| e.to_string())?;
-    let document = Html::parse_document(&html_text);
+    let metadata = parse_metadata(&html_text, url);
+    if let Some(reason) = super::removed_reason(PLATFORM, Some(status), &metadata.title) {
+        return Err(SpiderError::NovelRemoved { url: url.to_string(), reason }.into());
+    }
+    Ok(metadata)
+}
+
+/// 解析书籍主页（/page/<id>），字体加密的字符在这里解密
+pub(crate) fn parse_metadata(html: &str, url: &str) -> NovelMetadata {
+    let document = Html::parse_document(html);
 
     // Selectors (Best Guess + decryption)
     // Title usually in H1
-    let title = extract_and_decrypt(&document, "h1");
+    let title = extract_and_decrypt(&document, &selectors::get(PLATFORM, selectors::METADATA_TITLE));
     // Word count often has a specific class or check meta
     // For general robustness, we might just look for commonly used classes
     let word_count = extract_and_decrypt(&document, ".info-count-word"); 
-    let description = extract_and_decrypt(&document, ".page-abstract-content");
+    let description = extract_and_decrypt(&document, &selectors::get(PLATFORM, selectors::METADATA_DESC));
     
     // Tags
-    let tag_selector = Selector::parse(".info-label").unwrap();
+    let tag_selector = selectors::selector(PLATFORM, selectors::METADATA_TAGS);
     let mut tags = Vec::new();
     for element in document.select(&tag_selector) {
         let raw_tag = element.text().collect::<String>();
         tags.push(decrypt_content(&raw_tag));
     }
 
-    Ok(NovelMetadata {
+    let mut metadata = NovelMetadata {
         url: url.to_string(),
         title: if title.is_empty() { "Unknown Title".to_string() } else { title },
         tags,
         word_count: if word_count.is_empty() { "Unknown".to_string() } else { word_count },
         description: if description.is_empty() { "No description".to_string() } else { description },
-    })
+        ..Default::default()
+    };
+    super::tidy_metadata(&mut metadata);
+    metadata
+}
+
+/// 榜单页 `/rank/{gender}_{mold}_{category}` 对应的 JSON 接口参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RankId {
+    gender: String,
+    mold: String,
+    category: String,
+}
+
+fn parse_rank_id(url: &str) -> Option<RankId> {
+    let parsed = url::Url::parse(url.trim()).ok()?;
+    let mut segments = parsed.path_segments()?.filter(|s| !s.is_empty());
+    if segments.next()? != "rank" {
+        return None;
+    }
+    let mut parts = segments.next()?.split('_');
+    let (gender, mold, category) = (parts.next()?, parts.next()?, parts.next()?);
+    let numeric = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if parts.next().is_some() || !numeric(gender) || !numeric(mold) || !numeric(category) {
+        return None;
+    }
+    Some(RankId { gender: gender.to_string(), mold: mold.to_string(), category: category.to_string() })
+}
+
+/// 榜单页实际加载数据用的接口
+const RANK_API: &str = "https://fanqienovel.com/api/rank/category/list";
+const RANK_API_LIMIT: usize = 30;
+
+fn rank_api_url(id: &RankId) -> String {
+    format!(
+        "{}?app_id=2503&rank_list_type=3&offset=0&limit={}&category_id={}&rank_version=&gender={}&rankMold={}",
+        RANK_API, RANK_API_LIMIT, id.category, id.gender, id.mold
+    )
+}
+
+/// 解析榜单接口 JSON。结构不认识（缺 data.book_list 或没有一本可用的书）时返回 None，由调用方回退到 HTML。
+fn parse_rank_api(json: &serde_json::Value) -> Option<Vec<RankEntry>> {
+    if json.get("code").and_then(|c| c.as_i64()).is_some_and(|c| c != 0) {
+        return None;
+    }
+    let books = json.pointer("/data/book_list")?.as_array()?;
+    let field = |book: &serde_json::Value, keys: &[&str]| -> Option<String> {
+        keys.iter().find_map(|k| match book.get(*k)? {
+            serde_json::Value::String(s) if !s.trim().is_empty() => Some(decrypt_content(s.trim())),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    };
+
+    let mut entries: Vec<RankEntry> = Vec::new();
+    for book in books {
+        let Some(book_id) = field(book, &["bookId", "book_id"]).filter(|id| id.chars().all(|c| c.is_ascii_digit())) else {
+            continue;
+        };
+        let url = format!("https://fanqienovel.com/page/{}", book_id);
+        if entries.iter().any(|e| e.url == url) {
+            continue;
+        }
+        entries.push(RankEntry {
+            position: entries.len() + 1,
+            title: super::truncate_chars(&super::clean_text(&field(book, &["bookName", "book_name"]).unwrap_or_default()), super::MAX_TITLE_CHARS),
+            url,
+            score: field(book, &["read_count", "readCount", "score"]),
+            author: field(book, &["author", "authorName"]),
+        });
+    }
+    (!entries.is_empty()).then_some(entries)
+}
+
+async fn fetch_rank_api(client: &Client, url: &str) -> Result<Vec<RankEntry>, String> {
+    let id = parse_rank_id(url).ok_or_else(|| format!("无法从链接解析榜单 ID: {}", url))?;
+    let resp = client
+        .get(rank_api_url(&id))
+        .header("User-Agent", "Mozilla/5.0")
+        .header("Accept", "application/json, text/plain, */*")
+        .header("Referer", url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("榜单接口返回 {}", resp.status()));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| format!("榜单接口不是 JSON: {}", e))?;
+    parse_rank_api(&json).ok_or_else(|| "榜单接口返回结构无法识别".to_string())
 }
 
-pub async fn fetch_rank_list(client: &Client, url: &str) -> Result<Vec<String>, String> {
+/// 先走榜单 JSON 接口，失败或结构无法识别时回退到解析榜单页 HTML。
+pub async fn fetch_rank_list(client: &Client, url: &str) -> Result<RankScan, String> {
+    match fetch_rank_api(client, url).await {
+        Ok(entries) => return Ok(RankScan { entries, source: RankSource::Api }),
+        Err(e) => {
+            eprintln!("[Fanqie] 榜单接口不可用，改为解析页面: {}", e);
+            crate::log_to_file(&format!("[Fanqie] rank api failed, falling back to html: {}", e));
+        }
+    }
+
     let resp = client.get(url)
         .header("User-Agent", "Mozilla/5.0")
         .send()
@@ -161,27 +292,125 @@ pub async fn fetch_rank_list(client: &Client, url: &str) -> Result<Vec<String>,
         .map_err(|e| e.to_string())?;
 
     let html_text = resp.text().await.map_err(|e| e.to_string())?;
-    let document = Html::parse_document(&html_text);
+    Ok(RankScan { entries: parse_rank_entries(&html_text), source: RankSource::Html })
+}
+
+/// 解析番茄榜单页，按页面顺序返回（不排序，排序会打乱名次）。
+pub(crate) fn parse_rank_entries(html: &str) -> Vec<RankEntry> {
+    let document = Html::parse_document(html);
 
     // The links to novels usually contain "/page/"
-    let link_selector = Selector::parse("a[href^='/page/']").unwrap();
-    let mut links = Vec::new();
-    
+    let link_selector = selectors::selector(PLATFORM, selectors::RANK);
+    let mut entries: Vec<RankEntry> = Vec::new();
+
     for element in document.select(&link_selector) {
-        if let Some(href) = element.value().attr("href") {
-            links.push(format!("https://fanqienovel.com{}", href));
+        let Some(href) = element.value().attr("href") else { continue };
+        let url = format!("https://fanqienovel.com{}", href.split(['?', '#']).next().unwrap_or(href));
+        // 封面和书名通常是指向同一本书的两个链接，取第一个有文字的作为书名
+        let title = decrypt_content(element.text().collect::<String>().trim());
+        match entries.iter_mut().find(|e| e.url == url) {
+            Some(existing) if existing.title.is_empty() => existing.title = title,
+            Some(_) => {}
+            None => {
+                // 作者 / 在读人数在书名链接所在的榜单条目里
+                let item = element
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|el| el.value().attr("class").is_some_and(|c| c.contains("item")));
+                let (author, score) = match &item {
+                    Some(item) => (
+                        super::select_text(item, "[class*='author']").map(|a| decrypt_content(&a)),
+                        super::select_text(item, "[class*='read'], [class*='count'], [class*='score']")
+                            .map(|s| decrypt_content(&s)),
+                    ),
+                    None => (None, None),
+                };
+                entries.push(RankEntry { position: entries.len() + 1, title, url, score, author });
+            }
         }
     }
-    
-    // Dedup
-    links.sort();
-    links.dedup();
-    
-    Ok(links)
+
+    entries
+}
+
+/// 网页阅读器加载章节正文用的接口
+const CHAPTER_API: &str = "https://fanqienovel.com/api/reader/full";
+
+/// 章节链接 `/reader/<item_id>` 中的 item_id
+fn parse_item_id(url: &str) -> Option<String> {
+    let parsed = url::Url::parse("https://fanqienovel.com/").ok()?.join(url.trim()).ok()?;
+    let mut segments = parsed.path_segments()?.filter(|s| !s.is_empty());
+    if segments.next()? != "reader" {
+        return None;
+    }
+    let id = segments.next()?;
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then(|| id.to_string())
+}
+
+/// 接口正文是带 `<p>` 的 HTML 片段，按段落转为纯文本；没有段落标签时取全部文本
+fn paragraphs_to_text(markup: &str) -> String {
+    let fragment = Html::parse_fragment(markup);
+    let p = Selector::parse("p").unwrap();
+    let paragraphs: Vec<String> = fragment
+        .select(&p)
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let text = if paragraphs.is_empty() {
+        fragment.root_element().text().collect::<String>().trim().to_string()
+    } else {
+        paragraphs.join("\n")
+    };
+    decrypt_content(&text)
+}
+
+/// 解析章节接口 JSON，返回 (标题, 正文)。code 非 0（含签名校验失败）、缺 data.chapterData.content
+/// 或正文为空时返回 None，由调用方回退到 HTML。
+fn parse_chapter_api(json: &serde_json::Value) -> Option<(String, String)> {
+    if json.get("code").and_then(|c| c.as_i64()).is_some_and(|c| c != 0) {
+        return None;
+    }
+    let data = json.pointer("/data/chapterData")?;
+    let content = paragraphs_to_text(data.get("content")?.as_str()?);
+    if content.is_empty() {
+        return None;
+    }
+    let title = data.get("title").and_then(|t| t.as_str()).map(|t| decrypt_content(t.trim())).unwrap_or_default();
+    Some((title, content))
+}
+
+async fn fetch_chapter_api(client: &Client, url: &str) -> Result<(String, String), String> {
+    let item_id = parse_item_id(url).ok_or_else(|| format!("无法从链接解析章节 ID: {}", url))?;
+    let resp = client
+        .get(CHAPTER_API)
+        .query(&[("itemId", item_id.as_str())])
+        .header("User-Agent", "Mozilla/5.0")
+        .header("Accept", "application/json, text/plain, */*")
+        .header("Referer", url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("章节接口返回 {}", resp.status()));
+    }
+    let json: serde_json::Value = resp.json().await.map_err(|e| format!("章节接口不是 JSON: {}", e))?;
+    parse_chapter_api(&json).ok_or_else(|| "章节接口返回结构无法识别".to_string())
 }
 
-pub async fn download_chapter(client: &Client, url: &str) -> Result<(String, String), String> {
-    // ... (Existing logic but ensuring uses decrypt_content)
+/// 下载一章，返回 (标题, 正文, 来源)。新书的阅读页在浏览器端渲染正文，页面里抓不到内容，
+/// 所以先走阅读器的章节接口，失败或结构无法识别时回退到解析阅读页 HTML。
+pub async fn download_chapter(client: &Client, url: &str) -> Result<(String, String, ChapterSource), String> {
+    match fetch_chapter_api(client, url).await {
+        Ok((title, content)) => {
+            let title = if title.is_empty() { url.to_string() } else { title };
+            return Ok((title, content, ChapterSource::Api));
+        }
+        Err(e) => {
+            eprintln!("[Fanqie] 章节接口不可用，改为解析页面: {}", e);
+            crate::log_to_file(&format!("[Fanqie] chapter api failed, falling back to html: {}", e));
+        }
+    }
+
     let resp = client.get(url)
         .header("User-Agent", "Mozilla/5.0")
         .send()
@@ -189,9 +418,15 @@ pub async fn download_chapter(client: &Client, url: &str) -> Result<(String, Str
         .map_err(|e| e.to_string())?;
     
     let html_text = resp.text().await.map_err(|e| e.to_string())?;
-    let document = Html::parse_document(&html_text);
+    let (title, content) = parse_chapter_html(&html_text, url);
+    Ok((title, content, ChapterSource::Html))
+}
+
+/// 解析阅读页 HTML，返回 (标题, 正文)；页面上没有标题时标题为 url
+pub(crate) fn parse_chapter_html(html: &str, url: &str) -> (String, String) {
+    let document = Html::parse_document(html);
 
-    let content_selector = Selector::parse(".muye-reader-content-16 p").unwrap();
+    let content_selector = selectors::selector(PLATFORM, selectors::CHAPTER_CONTENT);
     let mut content_lines = Vec::new();
 
     for element in document.select(&content_selector) {
@@ -201,6 +436,175 @@ pub async fn download_chapter(client: &Client, url: &str) -> Result<(String, Str
     
     let raw_content = content_lines.join("\n");
     let decrypted = decrypt_content(&raw_content);
-    
-    Ok((url.to_string(), decrypted))
+    let title = super::select_text(&document.root_element(), &selectors::get(PLATFORM, selectors::CHAPTER_TITLE))
+        .map(|t| decrypt_content(&t))
+        .unwrap_or_else(|| url.to_string());
+    (title, decrypted)
+}
+
+/// 书籍主页上的完整目录。番茄目录直接渲染在 /page/ 页面中，无需浏览器蜘蛛。
+/// 目录为空时按状态码和下架提示判断是否已下架。
+pub async fn fetch_catalog(client: &Client, url: &str) -> Result<Vec<super::CatalogChapter>, SpiderError> {
+    let resp = client.get(url)
+        .header("User-Agent", "Mozilla/5.0")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = resp.status().as_u16();
+    let html_text = resp.text().await.map_err(|e| e.to_string())?;
+    // 多取一章，供下载流程判断目录是否真的超出上限
+    let chapters = parse_catalog(&html_text, super::max_catalog_chapters() + 1);
+    if chapters.is_empty() {
+        if let Some(reason) = super::removed_reason(PLATFORM, Some(status), &html_text) {
+            return Err(SpiderError::NovelRemoved { url: url.to_string(), reason });
+        }
+        return Err("目录为空，页面结构可能已变化".to_string().into());
+    }
+    Ok(chapters)
+}
+
+/// 解析目录页，最多取 max 章；非 http(s) 链接丢弃。
+pub(crate) fn parse_catalog(html: &str, max: usize) -> Vec<super::CatalogChapter> {
+    let document = Html::parse_document(html);
+    let item_selector = selectors::selector(PLATFORM, selectors::CATALOG);
+    let link_selector = Selector::parse("a[href*='/reader/']").unwrap();
+
+    let mut chapters = Vec::new();
+    for item in document.select(&item_selector) {
+        let Some(link) = item.select(&link_selector).next() else { continue };
+        let href = link.value().attr("href").unwrap_or_default();
+        let title = decrypt_content(link.text().collect::<String>().trim());
+        if super::clean_text(&title).is_empty() {
+            continue;
+        }
+        let Some(url) = super::normalize_href("https://fanqienovel.com/", href) else { continue };
+        if chapters.len() >= max {
+            crate::log_to_file(&format!("[Fanqie] 目录在第 {} 章处截断，其余已忽略", max));
+            break;
+        }
+        chapters.push(super::CatalogChapter::new(&title, url, super::element_looks_vip(&item)));
+    }
+    chapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANK_API_JSON: &str = include_str!("fixtures/fanqie_rank_api.json");
+    const RANK_HTML: &str = include_str!("fixtures/fanqie_rank.html");
+    const CHAPTER_API_JSON: &str = include_str!("fixtures/fanqie_chapter_api.json");
+
+    #[test]
+    fn rank_id_comes_from_rank_url() {
+        let id = parse_rank_id("https://fanqienovel.com/rank/1_2_1141?enter_from=menu").unwrap();
+        assert_eq!(id, RankId { gender: "1".into(), mold: "2".into(), category: "1141".into() });
+        assert!(rank_api_url(&id).contains("category_id=1141&rank_version=&gender=1&rankMold=2"));
+        assert_eq!(parse_rank_id("https://fanqienovel.com/page/7143038691944959011"), None);
+        assert_eq!(parse_rank_id("https://fanqienovel.com/rank/1_x_1141"), None);
+    }
+
+    #[test]
+    fn rank_api_json_is_parsed() {
+        let json: serde_json::Value = serde_json::from_str(RANK_API_JSON).unwrap();
+        let entries = parse_rank_api(&json).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://fanqienovel.com/page/7143038691944959011");
+        assert_eq!((entries[0].position, entries[0].title.as_str()), (1, "十日终焉"));
+        assert_eq!(entries[0].score.as_deref(), Some("1285.6万"));
+        assert_eq!(entries[1].title, "我在精神病院学斩神");
+        assert_eq!(entries[1].author.as_deref(), Some("三九音域"));
+        assert_eq!(entries[1].score.as_deref(), Some("986000"));
+    }
+
+    #[test]
+    fn unrecognized_api_shape_falls_back_to_html() {
+        for body in [r#"{"code":0,"data":{"list":[]}}"#, r#"{"code":-1,"data":{"book_list":[{"bookId":"1"}]}}"#, r#"{"data":{"book_list":[]}}"#] {
+            assert_eq!(parse_rank_api(&serde_json::from_str(body).unwrap()), None, "{}", body);
+        }
+        let entries = parse_rank_entries(RANK_HTML);
+        let json: serde_json::Value = serde_json::from_str(RANK_API_JSON).unwrap();
+        let api = parse_rank_api(&json).unwrap();
+        // 两条路径得到同样的名次、链接和书名
+        let key = |e: &RankEntry| (e.position, e.url.clone(), e.title.clone());
+        assert_eq!(entries.iter().map(key).collect::<Vec<_>>(), api.iter().map(key).collect::<Vec<_>>());
+        assert_eq!(entries[0].author.as_deref(), Some("杀虫队队员"));
+    }
+
+    #[test]
+    fn item_id_comes_from_reader_href() {
+        assert_eq!(parse_item_id("/reader/7143039137061864974?enter_from=page").as_deref(), Some("7143039137061864974"));
+        assert_eq!(parse_item_id("https://fanqienovel.com/reader/7143039137061864974/").as_deref(), Some("7143039137061864974"));
+        assert_eq!(parse_item_id("//fanqienovel.com/reader/123#top").as_deref(), Some("123"));
+        assert_eq!(parse_item_id("https://fanqienovel.com/page/7143038691944959011"), None);
+        assert_eq!(parse_item_id("/reader/abc"), None);
+        assert_eq!(parse_item_id("/reader/"), None);
+    }
+
+    #[test]
+    fn chapter_api_json_is_converted_to_text() {
+        let json: serde_json::Value = serde_json::from_str(CHAPTER_API_JSON).unwrap();
+        let (title, content) = parse_chapter_api(&json).unwrap();
+        assert_eq!(title, "第1章 终焉之地");
+        assert_eq!(content, "齐夏睁开眼，发现自己坐在一张圆桌前。\n房间里一共有十个人。\n“我们这是在哪？”");
+        for body in [
+            r#"{"code":0,"data":{"chapterData":{"content":"  "}}}"#,
+            r#"{"code":-1,"data":{"chapterData":{"content":"<p>正文</p>"}}}"#,
+            r#"{"code":0,"data":{}}"#,
+        ] {
+            assert_eq!(parse_chapter_api(&serde_json::from_str(body).unwrap()), None, "{}", body);
+        }
+        let plain: serde_json::Value = serde_json::from_str(r#"{"data":{"chapterData":{"content":"没有段落标签"}}}"#).unwrap();
+        assert_eq!(parse_chapter_api(&plain), Some((String::new(), "没有段落标签".to_string())));
+    }
+
+    #[test]
+    fn rank_entries_keep_page_order() {
+        let html = r#"<div class="rank-book-item">
+              <a href="/page/7373"><img></a><a href="/page/7373?from=rank">第二本书</a>
+              <span class="author">作者乙</span><span class="book-read-count">在读：12万</span>
+            </div>
+            <div class="rank-book-item"><a href="/page/1111">第一本书</a></div>"#;
+        let entries = parse_rank_entries(html);
+        let titles: Vec<_> = entries.iter().map(|e| (e.position, e.title.as_str())).collect();
+        assert_eq!(titles, vec![(1, "第二本书"), (2, "第一本书")]);
+        assert_eq!(entries[0].url, "https://fanqienovel.com/page/7373");
+        assert_eq!(entries[0].author.as_deref(), Some("作者乙"));
+        assert_eq!(entries[0].score.as_deref(), Some("在读：12万"));
+        assert_eq!(entries[1].score, None);
+    }
+
+    #[test]
+    fn pathological_catalog_is_bounded() {
+        let mut html = String::new();
+        html.push_str(&format!(
+            "<div class='chapter-item'><a href='/reader/1'>{}</a></div>",
+            "长".repeat(20_000)
+        ));
+        html.push_str("<div class='chapter-item'><a href='javascript:/reader/2'>坏链接</a></div>");
+        html.push_str("<div class='chapter-item'><a href='/reader/3'>\u{0}\n\t</a></div>");
+        for i in 4..600 {
+            html.push_str(&format!("<div class='chapter-item'><a href='/reader/{}'>第{}章</a></div>", i, i));
+        }
+        let chapters = parse_catalog(&html, 100);
+        assert_eq!(chapters.len(), 100);
+        assert!(chapters.iter().all(|c| c.title.chars().count() <= super::super::MAX_TITLE_CHARS));
+        assert!(chapters.iter().all(|c| c.url.starts_with("https://fanqienovel.com/reader/")));
+        assert_eq!(chapters[0].full_title.as_ref().map(|t| t.chars().count()), Some(20_000));
+        assert_eq!(chapters[1].title, "第4章");
+    }
+
+    #[test]
+    fn catalog_overflow_is_visible_only_past_the_cap() {
+        let catalog = |n: usize| {
+            let items: String = (1..=n)
+                .map(|i| format!("<div class='chapter-item'><a href='/reader/{}'>第{}章</a></div>", i, i))
+                .collect();
+            format!("<html><body>{}</body></html>", items)
+        };
+        // 抓取时多取一章：恰好 5 章的目录不会被当成超限
+        assert_eq!(parse_catalog(&catalog(5), 5 + 1).len(), 5);
+        assert_eq!(parse_catalog(&catalog(9), 5 + 1).len(), 6);
+    }
 }
@@ -1,2 +1,399 @@
+pub mod circuit;
+pub mod corpus;
+mod error;
 pub mod fanqie;
 pub mod qidian;
+pub mod selectors;
+pub mod site_options;
+pub mod source;
+
+pub use error::SpiderError;
+pub use source::{LiveSource, NovelSource};
+
+use scraper::{ElementRef, Selector};
+
+/// 目录中的一章（各平台共用）
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CatalogChapter {
+    /// 用于显示和日志的标题，已截断到 [`MAX_TITLE_CHARS`]
+    pub title: String,
+    /// 被截断时保留的完整标题
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_title: Option<String>,
+    pub url: String,
+    pub is_vip: bool,
+    /// 作品相关 / 公告等非正文条目（上架感言、请假条等）
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_extra: bool,
+    /// 目录页上所属的卷名（作品相关卷除外），分卷各自编号的书靠它区分重复的章节号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+}
+
+impl CatalogChapter {
+    pub fn new(raw_title: &str, url: String, is_vip: bool) -> Self {
+        let full = clean_text(raw_title);
+        let title = truncate_chars(&full, MAX_TITLE_CHARS);
+        let full_title = (title != full).then_some(full);
+        Self { title, full_title, url, is_vip, is_extra: false, volume: None }
+    }
+}
+
+/// 各平台正文最少字数的缺省值，可在设置中按平台覆盖。未登记的平台用 [`crate::library::MIN_CHAPTER_BODY_CHARS`]
+pub fn default_min_chapter_chars(platform: &str) -> usize {
+    match platform {
+        qidian::PLATFORM => qidian::MIN_CHAPTER_CHARS,
+        fanqie::PLATFORM => fanqie::MIN_CHAPTER_CHARS,
+        _ => crate::library::MIN_CHAPTER_BODY_CHARS,
+    }
+}
+
+/// 平台是否默认拆分多章合页，可在设置中按平台覆盖。已登记的起点、番茄都是一页一章，缺省都不拆分
+pub fn default_segment_pages(_platform: &str) -> bool {
+    false
+}
+
+/// 平台页面上的界面文字（导航、推广等），正文检查用，见 [`crate::content_check`]
+pub fn ui_vocabulary(platform: &str) -> &'static [&'static str] {
+    match platform {
+        qidian::PLATFORM => qidian::UI_VOCABULARY,
+        fanqie::PLATFORM => fanqie::UI_VOCABULARY,
+        _ => &[],
+    }
+}
+
+/// 平台"作品不存在 / 已下架"提示页的特征文字。这类页面常以 200 返回，只能按文字判断
+pub fn removed_markers(platform: &str) -> &'static [&'static str] {
+    match platform {
+        qidian::PLATFORM => qidian::REMOVED_MARKERS,
+        fanqie::PLATFORM => fanqie::REMOVED_MARKERS,
+        _ => &[],
+    }
+}
+
+/// 平台章节页的登录墙提示文字，见 [`crate::access_probe`]
+pub fn login_markers(platform: &str) -> &'static [&'static str] {
+    match platform {
+        qidian::PLATFORM => qidian::LOGIN_MARKERS,
+        fanqie::PLATFORM => fanqie::LOGIN_MARKERS,
+        _ => &[],
+    }
+}
+
+/// 平台付费章节锁页的提示文字
+pub fn vip_markers(platform: &str) -> &'static [&'static str] {
+    match platform {
+        qidian::PLATFORM => qidian::VIP_MARKERS,
+        fanqie::PLATFORM => fanqie::VIP_MARKERS,
+        _ => &[],
+    }
+}
+
+/// 抓到的页面是否表明作品已下架，返回判断依据。404 / 410 直接判定（HTTP 抓取才有状态码），
+/// 否则在 text 中找 [`removed_markers`]。正常页面也可能提到"已下架"，调用方只应在
+/// 目录为空或标题本身时传入页面文字
+pub fn removed_reason(platform: &str, http_status: Option<u16>, text: &str) -> Option<String> {
+    if let Some(status @ (404 | 410)) = http_status {
+        return Some(format!("HTTP {}", status));
+    }
+    removed_markers(platform).iter().find(|m| text.contains(**m)).map(|m| format!("页面提示“{}”", m))
+}
+
+/// 作品相关卷的卷名特征
+pub const EXTRA_VOLUME_MARKER: &str = "作品相关";
+
+// ========================================================================
+//  目录页输入校验：目录页可能被篡改或结构异常，标题和链接都不可信
+// ========================================================================
+
+/// 显示 / 日志用标题的最大字数
+pub const MAX_TITLE_CHARS: usize = 80;
+const DEFAULT_MAX_CATALOG_CHAPTERS: usize = 10_000;
+const DEFAULT_MAX_HTML_BYTES: usize = 20 * 1024 * 1024;
+
+/// 单个目录最多接受的章节数，`CATALOG_MAX_CHAPTERS` 可覆盖
+pub fn max_catalog_chapters() -> usize {
+    std::env::var("CATALOG_MAX_CHAPTERS")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_CATALOG_CHAPTERS)
+}
+
+/// 蜘蛛回传页面的最大字节数，`SPIDER_MAX_HTML_BYTES` 可覆盖。超出的页面直接拒绝，不进入解析。
+pub fn max_html_bytes() -> usize {
+    std::env::var("SPIDER_MAX_HTML_BYTES")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_HTML_BYTES)
+}
+
+// ========================================================================
+//  书籍元数据的长度限制：简介里可能混入大段获奖横幅，标签里混有"男生月票榜No.1"之类的荣誉，
+//  会撑大 info.json 和送给 AI 的背景块
+// ========================================================================
+
+const DEFAULT_MAX_DESCRIPTION_CHARS: usize = 800;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_HONORS: usize = 20;
+/// 超过该字数的"标签"视为荣誉横幅
+const MAX_TAG_CHARS: usize = 8;
+/// 荣誉类标签的特征文本
+const HONOR_MARKERS: &[&str] = &["榜", "No.", "NO.", "奖", "年度", "冠军", "入选", "荣获", "十佳", "天王"];
+/// 简介截断时优先停在这些字符之后
+const SENTENCE_ENDS: &[char] = &['。', '！', '？', '!', '?', '…', '\n'];
+
+/// 简介的最大字数，`METADATA_MAX_DESCRIPTION_CHARS` 可覆盖
+pub fn max_description_chars() -> usize {
+    std::env::var("METADATA_MAX_DESCRIPTION_CHARS")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_DESCRIPTION_CHARS)
+}
+
+pub(crate) fn is_honor(tag: &str) -> bool {
+    tag.chars().count() > MAX_TAG_CHARS || HONOR_MARKERS.iter().any(|m| tag.contains(m))
+}
+
+/// 截断到 max 个字符以内：后半段有句末标点时停在标点之后，否则硬截断并加省略号。返回是否截断
+pub(crate) fn truncate_description(text: &str, max: usize) -> (String, bool) {
+    let text = text.trim();
+    if text.chars().count() <= max {
+        return (text.to_string(), false);
+    }
+    let head = char_prefix(text, max);
+    let boundary = head
+        .char_indices()
+        .rfind(|(i, c)| *i >= head.len() / 2 && SENTENCE_ENDS.contains(c))
+        .map(|(i, c)| i + c.len_utf8());
+    match boundary {
+        Some(end) => (head[..end].trim_end().to_string(), true),
+        None => (truncate_chars(text, max), true),
+    }
+}
+
+/// 清理、去重（保持顺序）并限量，返回是否超量
+fn dedup_capped(items: Vec<String>, exclude: &[String], max: usize) -> (Vec<String>, bool) {
+    let mut seen = std::collections::HashSet::new();
+    let mut out: Vec<String> =
+        items.into_iter().filter(|t| !exclude.contains(t) && seen.insert(t.clone())).collect();
+    let over = out.len() > max;
+    out.truncate(max);
+    (out, over)
+}
+
+/// 整理抓到的元数据（两个平台的 fetch_novel_metadata 返回前都会调用）：简介截断到
+/// [`max_description_chars`]；标签清理去重，纯数字的丢弃，荣誉类（过长或带"榜""No."等字样）移入 honors；
+/// 标签和荣誉各自限量。发生截断的字段记入 truncated
+pub fn tidy_metadata(meta: &mut fanqie::NovelMetadata) {
+    tidy_metadata_with(meta, max_description_chars());
+}
+
+pub(crate) fn tidy_metadata_with(meta: &mut fanqie::NovelMetadata, max_description_chars: usize) {
+    let mut truncated = Vec::new();
+    let (description, cut) = truncate_description(&meta.description, max_description_chars);
+    meta.description = description;
+    if cut {
+        truncated.push("description");
+    }
+
+    let mut tags = Vec::new();
+    let mut honors: Vec<String> = meta.honors.drain(..).map(|h| clean_text(&h)).filter(|h| !h.is_empty()).collect();
+    for raw in meta.tags.drain(..) {
+        let tag = clean_text(&raw);
+        if tag.is_empty() || tag.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if is_honor(&tag) { honors.push(tag) } else { tags.push(tag) }
+    }
+    let (honors, honors_cut) = dedup_capped(honors, &[], MAX_HONORS);
+    let (tags, tags_cut) = dedup_capped(tags, &honors, MAX_TAGS);
+    if tags_cut {
+        truncated.push("tags");
+    }
+    if honors_cut {
+        truncated.push("honors");
+    }
+    meta.tags = tags;
+    meta.honors = honors;
+    meta.truncated = truncated.into_iter().map(str::to_string).collect();
+}
+
+/// 前 max 个字符的切片，不分配新字符串（日志预览、特征检测用）。
+pub(crate) fn char_prefix(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+/// 去掉控制字符和双向文本控制符，连续空白合并为一个空格。
+pub(crate) fn clean_text(raw: &str) -> String {
+    raw.split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.chars().filter(|c| !is_bidi_control(*c)).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// 按字符截断，超长时末尾加省略号（总长不超过 max）。
+pub(crate) fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+/// 把目录页里的 href 解析为绝对 URL。只接受 http / https，其余（javascript:、data: 等）返回 None。
+pub(crate) fn normalize_href(base: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() {
+        return None;
+    }
+    let url = url::Url::parse(base).ok()?.join(href).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// 榜单中的一本书。position 为跨分页的榜单名次（从 1 开始）。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RankEntry {
+    pub position: usize,
+    pub title: String,
+    pub url: String,
+    /// 月票 / 热度等数值，原样保留页面文本；榜单没有该列时为 None
+    pub score: Option<String>,
+    pub author: Option<String>,
+}
+
+/// 榜单数据来源，写入扫榜历史以便定位是哪条路径坏了
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankSource {
+    Api,
+    Html,
+}
+
+/// 章节正文来源，写入 chapters.json，成功率下降时据此判断是接口还是页面解析出了问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterSource {
+    Api,
+    Html,
+}
+
+/// 一次扫榜的结果
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RankScan {
+    pub entries: Vec<RankEntry>,
+    pub source: RankSource,
+}
+
+/// 作者页上的一部作品
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuthorWork {
+    pub title: String,
+    pub url: String,
+    /// "连载" / "完本" / "暂停"，页面上没有时为 None
+    pub status: Option<String>,
+}
+
+/// 作者页的作品列表（已合并各分页）
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuthorWorks {
+    pub author: Option<String>,
+    pub works: Vec<AuthorWork>,
+}
+
+/// 元素内第一个匹配选择器且文本非空的节点文本（已 trim）
+pub(crate) fn select_text(element: &ElementRef, css: &str) -> Option<String> {
+    if css.is_empty() {
+        return None;
+    }
+    let selector = Selector::parse(css).ok()?;
+    element
+        .select(&selector)
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .find(|t| !t.is_empty())
+}
+
+/// 章节链接元素或其子元素的 class 带 vip / lock 字样即视为付费章节
+pub(crate) fn element_looks_vip(element: &ElementRef) -> bool {
+    let marked = |class: &str| {
+        let class = class.to_ascii_lowercase();
+        class.contains("vip") || class.contains("lock")
+    };
+    if element.value().attr("class").is_some_and(marked) {
+        return true;
+    }
+    element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .any(|el| el.value().attr("class").is_some_and(marked))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_http_links_survive_normalization() {
+        let base = "https://m.qidian.com/book/1/catalog";
+        assert_eq!(normalize_href(base, "/chapter/1/2/").as_deref(), Some("https://m.qidian.com/chapter/1/2/"));
+        assert_eq!(normalize_href(base, "//m.qidian.com/chapter/1/3/").as_deref(), Some("https://m.qidian.com/chapter/1/3/"));
+        for hostile in ["javascript:alert(1)", " JaVaScRiPt:void(0)", "data:text/html,x", "file:///etc/passwd", "mailto:a@b", ""] {
+            assert_eq!(normalize_href(base, hostile), None, "{:?}", hostile);
+        }
+    }
+
+    #[test]
+    fn descriptions_are_cut_at_sentence_ends() {
+        assert_eq!(truncate_description("  短简介。 ", 10), ("短简介。".to_string(), false));
+        let (cut, truncated) = truncate_description("第一句话。第二句话！第三句话", 12);
+        assert!(truncated);
+        assert_eq!(cut, "第一句话。第二句话！");
+        let (hard, _) = truncate_description(&"长".repeat(20), 10);
+        assert_eq!(hard.chars().count(), 10);
+        assert!(hard.ends_with('…'));
+    }
+
+    #[test]
+    fn honors_are_split_from_tags_and_capped() {
+        let mut meta = fanqie::NovelMetadata {
+            tags: vec!["系统流".into(), " 系统流 ".into(), "2024".into(), "月票榜No.3".into(), "".into()]
+                .into_iter()
+                .chain((0..30).map(|i| format!("标签{}", i)))
+                .collect(),
+            honors: vec!["月票榜No.3".into()],
+            description: "简介".into(),
+            ..Default::default()
+        };
+        tidy_metadata_with(&mut meta, 800);
+        assert_eq!(meta.honors, vec!["月票榜No.3"]);
+        assert_eq!(meta.tags.len(), MAX_TAGS);
+        assert_eq!(meta.tags[..2], ["系统流", "标签0"]);
+        assert_eq!(meta.truncated, vec!["tags"]);
+    }
+
+    #[test]
+    fn hostile_titles_are_cleaned_and_truncated() {
+        let raw = format!("第一章\u{202E}\n\r\t{}\u{0}", "长".repeat(5000));
+        let ch = CatalogChapter::new(&raw, "https://example.com/1".into(), false);
+        assert_eq!(ch.title.chars().count(), MAX_TITLE_CHARS);
+        assert!(ch.title.ends_with('…'));
+        assert!(!ch.title.chars().any(|c| c.is_control() || is_bidi_control(c)));
+        let full = ch.full_title.unwrap();
+        assert!(full.starts_with("第一章 长") && full.chars().count() == 5004);
+
+        let short = CatalogChapter::new("  第二章   重逢 ", "https://example.com/2".into(), false);
+        assert_eq!(short.title, "第二章 重逢");
+        assert!(short.full_title.is_none());
+    }
+}
@@ -1,7 +1,67 @@
 use reqwest::Client;
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use regex::Regex;
-use crate::log_to_file;
+use crate::{log_to_file, resources};
+use crate::browser_spider::{FetchedPage, PageFetcher};
+
+use super::{circuit, selectors, SpiderError};
+
+pub(crate) const PLATFORM: &str = "qidian";
+/// 正文少于该字数的章节几乎都是 WAF 验证页等残缺内容
+pub const MIN_CHAPTER_CHARS: usize = 1500;
+/// 作品下架后书籍页 / 目录页显示的提示文字
+pub const REMOVED_MARKERS: &[&str] = &["作品不存在", "该作品已下架", "书籍已下架", "作品已被下架"];
+/// 未登录时章节页显示的提示文字，见 [`crate::access_probe`]
+pub const LOGIN_MARKERS: &[&str] = &["登录后继续阅读", "请先登录", "登录后查看"];
+/// 付费章节未订阅时的锁页提示文字
+pub const VIP_MARKERS: &[&str] = &["订阅本章", "本章为VIP章节", "订阅后才能阅读", "自动订阅下一章"];
+/// 页面导航 / 推广文字，正文检查用
+pub const UI_VOCABULARY: &[&str] = &["起点中文网", "本章说", "月票", "打赏", "推荐票", "自动订阅"];
+/// 章节页路径前缀：`/chapter/<书籍 id>/<章节 id>/`
+const CHAPTER_PATH_PREFIX: &str = "/chapter/";
+
+/// WAF / 人机验证页的特征文本
+const WAF_MARKERS: &[&str] = &["Just a moment", "Security checking", "安全验证", "访问验证"];
+/// 移动站兜底得到的元数据的 `source`：只有标题、简介、分类和字数，之后应重新尝试完整抓取
+pub const MOBILE_FALLBACK_SOURCE: &str = "mobile-fallback";
+
+fn looks_like_waf(html: &str) -> bool {
+    let head = super::char_prefix(html, 4096);
+    WAF_MARKERS.iter().any(|m| head.contains(m))
+}
+
+/// 经过熔断器的浏览器抓取。起点所有页面请求都走这里。
+pub(crate) async fn fetch_page<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<String, SpiderError> {
+    fetch_document(pages, url, debug_visible).await.map(|page| page.html)
+}
+
+/// 同 [`fetch_page`]，另带跳转后的最终地址
+async fn fetch_document<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<FetchedPage, SpiderError> {
+    circuit::before_request(PLATFORM)?;
+    let result = match pages.fetch_page(url, debug_visible).await {
+        Ok(page) if looks_like_waf(&page.html) => {
+            Err(crate::browser_spider::attach_page_logs(SpiderError::WafBlocked(url.to_string()), url, &page.logs))
+        }
+        other => other,
+    };
+    circuit::record(PLATFORM, &result);
+    result
+}
+
+/// 章节页是否被跳转到了非章节页面（书籍详情页、首页等）。最终地址未知时按未跳转处理；
+/// 移动站与 PC 站之间的跳转、以及跳到另一个章节页（分卷重排）都不算。
+pub fn redirected_off_chapter(requested: &str, final_url: Option<&str>) -> bool {
+    let Some(final_url) = final_url.map(str::trim).filter(|u| !u.is_empty()) else {
+        return false;
+    };
+    if crate::download::canonical_url(final_url) == crate::download::canonical_url(requested) {
+        return false;
+    }
+    match url::Url::parse(final_url) {
+        Ok(parsed) => !parsed.path().starts_with(CHAPTER_PATH_PREFIX),
+        Err(_) => false,
+    }
+}
 
 // Helper to get debug directory path
 fn get_debug_dir() -> std::path::PathBuf {
@@ -37,12 +97,185 @@ fn get_debug_dir() -> std::path::PathBuf {
 
 // Using the same struct as Fanqie for consistency
 pub use super::fanqie::NovelMetadata;
+use super::{AuthorWork, AuthorWorks, CatalogChapter, RankEntry};
+
+/// 一类榜单页面的结构：条目选择器 + 条目内各字段的选择器。
+struct RankFamily<'a> {
+    name: &'a str,
+    item: &'a str,
+    /// 条目内的书籍链接；为空表示条目本身就是链接
+    link: &'a str,
+    title: &'a str,
+    author: &'a str,
+    score: &'a str,
+}
+
+/// 榜单页面族；抓不到时错误信息会列出尝试过的族。
+const RANK_FAMILIES: &[RankFamily<'static>] = &[
+    // 桌面榜单：#rank-view-list（旧版）/ .book-img-text（新版）
+    // 作者取 .author a.name，不能直接匹配 .rank-body a.name
+    RankFamily {
+        name: "desktop",
+        item: "#rank-view-list li, .book-img-text li",
+        link: ".book-mid-info h2 a",
+        title: ".book-mid-info h2 a",
+        author: ".author a.name, .author .name",
+        score: ".book-right-info .total p span, .book-right-info .total span",
+    },
+    // 桌面通用列表
+    RankFamily {
+        name: "desktop-generic",
+        item: ".rank-list a.book-layout",
+        link: "",
+        title: "h2, h4, .book-title",
+        author: ".author, .book-author",
+        score: "",
+    },
+    // 移动端榜单卡片：.y-list__item 卡片 / CSS module 生成的 bookItem 类名
+    RankFamily {
+        name: "mobile",
+        item: ".y-list__item, a[class*='bookItem'][href*='/book/'], a[class*='rank-item'][href*='/book/']",
+        link: "a[href*='/book/']",
+        title: ".book-title, h4, [class*='title']",
+        author: ".book-author, [class*='author']",
+        score: ".book-score, [class*='score'], [class*='count']",
+    },
+];
+
+/// 解析一页榜单。offset 为之前各页已有的条数，名次从 offset + 1 开始连续编号。
+pub(crate) fn parse_rank_entries(html: &str, offset: usize) -> Vec<RankEntry> {
+    let document = Html::parse_document(html);
+    let mut entries = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+
+    // 用户覆盖的榜单选择器直接匹配书籍链接，优先于内置页面族
+    let custom = selectors::custom(PLATFORM, selectors::RANK);
+    let custom_family = custom.as_deref().map(|item| RankFamily {
+        name: "custom",
+        item,
+        link: "",
+        title: "",
+        author: "",
+        score: "",
+    });
+
+    for family in custom_family.iter().chain(RANK_FAMILIES) {
+        let Ok(item_sel) = Selector::parse(family.item) else {
+            log::error!("Selector parse error in rank family {}", family.name);
+            continue;
+        };
+        let link_sel = Selector::parse(family.link).ok().filter(|_| !family.link.is_empty());
+        for item in document.select(&item_sel) {
+            // select 只找后代：卡片本身就是书籍链接时直接用卡片
+            let link = match &link_sel {
+                Some(sel) if !sel.matches(&item) => item.select(sel).next(),
+                _ => Some(item),
+            };
+            let Some(link) = link else { continue };
+            let href = link.value().attr("href").unwrap_or_default();
+            let Some(book_id) = extract_book_id(href) else { continue };
+            if !seen.insert(book_id.clone()) {
+                continue;
+            }
+            let title = super::select_text(&item, family.title)
+                .unwrap_or_else(|| link.text().collect::<String>().trim().to_string());
+            // 移动端作者栏形如 "作者 · 类型"
+            let author = super::select_text(&item, family.author)
+                .map(|a| a.split('·').next().unwrap_or_default().trim().to_string())
+                .filter(|a| !a.is_empty());
+            entries.push(RankEntry {
+                position: offset + entries.len() + 1,
+                title,
+                url: canonical_book_url(&book_id),
+                score: super::select_text(&item, family.score),
+                author,
+            });
+        }
+        // 某个页面族已命中就不再用后面的选择器，避免打乱榜单顺序
+        if !entries.is_empty() {
+            break;
+        }
+    }
 
-pub async fn fetch_rank_list(app: &AppHandle, url: &str, debug_visible: bool) -> Result<Vec<String>, String> {
+    // Do NOT sort, as it destroys the rank order!
+    entries
+}
+
+/// 桌面榜单第 page 页的 URL（`.../rank/yuepiao/page2/`）；移动端榜单不分页，返回 None。
+fn rank_page_url(url: &str, page: usize) -> Option<String> {
+    if url.contains("m.qidian.com") {
+        return None;
+    }
+    let re = Regex::new(r"page\d+/?$").ok()?;
+    let base = re.replace(url.trim_end_matches('/'), "").trim_end_matches('/').to_string();
+    Some(format!("{}/page{}/", base, page))
+}
+
+/// 榜单 URL 本身所在的页码（`.../page3/` 为 3），没有页码时为第 1 页
+fn rank_start_page(url: &str) -> usize {
+    Regex::new(r"page(\d+)/?$")
+        .ok()
+        .and_then(|re| re.captures(url).and_then(|cap| cap[1].parse().ok()))
+        .unwrap_or(1)
+}
+
+/// 最多翻页数
+const MAX_RANK_PAGES: usize = 5;
+
+/// 从起点书籍 URL（桌面 / 移动 / book.qidian.com/info）中提取 bookId。
+pub fn extract_book_id(url: &str) -> Option<String> {
+    let re = Regex::new(r"/(?:book|info)/([0-9]+)").ok()?;
+    re.captures(url).and_then(|cap| cap.get(1)).map(|m| m.as_str().to_string())
+}
+
+/// 统一的桌面书籍主页 URL。
+pub fn canonical_book_url(book_id: &str) -> String {
+    format!("https://www.qidian.com/book/{}/", book_id)
+}
+
+/// 抓取榜单，桌面榜单从 URL 所在页起按需翻页直到凑够 max_entries 条。
+/// 只有第一页失败时返回错误；之后的页失败时停止翻页，返回已取得的条目。
+pub async fn fetch_rank_list<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool, max_entries: usize) -> Result<Vec<RankEntry>, String> {
+    let mut entries: Vec<RankEntry> = Vec::new();
+    let start = rank_start_page(url);
+    let mut page = start;
+    let mut page_url = url.to_string();
+
+    loop {
+        let page_entries = match fetch_rank_page(pages, &page_url, debug_visible, entries.len()).await {
+            Ok(page_entries) => page_entries,
+            Err(e) if page > start => {
+                log_to_file(&format!("Rank page {} failed, keeping {} entries: {}", page_url, entries.len(), e));
+                page -= 1;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let before = entries.len();
+        for entry in page_entries {
+            if !entries.iter().any(|e| e.url == entry.url) {
+                entries.push(RankEntry { position: entries.len() + 1, ..entry });
+            }
+        }
+        if entries.len() >= max_entries || entries.len() == before || page + 1 - start >= MAX_RANK_PAGES {
+            break;
+        }
+        page += 1;
+        match rank_page_url(url, page) {
+            Some(next) => page_url = next,
+            None => break,
+        }
+    }
+
+    log_to_file(&format!("Found {} novels in rank list ({} page(s)).", entries.len(), page + 1 - start));
+    Ok(entries)
+}
+
+async fn fetch_rank_page<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool, offset: usize) -> Result<Vec<RankEntry>, String> {
     log_to_file(&format!("Starting browser spider for rank list: {}", url));
     
     // 1. Fetch via Browser Spider
-    let html = crate::browser_spider::fetch_via_window(app, url, debug_visible).await
+    let html = fetch_page(pages, url, debug_visible).await
         .map_err(|e| format!("Browser spider failed: {}", e))?;
 
     // Debug: Save rank page HTML
@@ -56,64 +289,165 @@ pub async fn fetch_rank_list(app: &AppHandle, url: &str, debug_visible: bool) ->
         log_to_file(&format!("Saved rank HTML to {:?}", debug_path));
     }
     
-    // 2. Parse
-    let document = Html::parse_document(&html);
-    
-    // Selectors:
-    // 1. #rank-view-list .book-mid-info h2 a (Standard Desktop Rank)
-    // 2. .book-img-text .book-mid-info h2 a (New Desktop Rank)
-    // 3. .rank-list a.book-layout (Generic)
-    // Removed .rank-body a.name as it matches authors.
-    let selector = Selector::parse("#rank-view-list .book-mid-info h2 a, .book-img-text .book-mid-info h2 a, .rank-list a.book-layout")
-        .map_err(|e| format!("Selector parse error: {:?}", e))?;
-
-    let mut links = Vec::new();
-    let mut seen = std::collections::HashSet::new();
+    // 2. Parse（桌面 / 移动端链接统一为桌面书籍 URL）
+    let entries = parse_rank_entries(&html, offset);
 
-    for element in document.select(&selector) {
-        let href = element.value().attr("href").unwrap_or_default();
-        if !href.is_empty() {
-             // Qidian links can be "//book.qidian.com/..." or "/book/..."
-             let full_url = if href.starts_with("//") {
-                 format!("https:{}", href)
-             } else if href.starts_with("/") {
-                 // Check if it is mobile or desktop
-                 if url.contains("m.qidian.com") {
-                     format!("https://m.qidian.com{}", href)
-                 } else {
-                     format!("https://www.qidian.com{}", href)
-                 }
-             } else {
-                 href.to_string()
-             };
-             
-             if full_url.contains("/book/") && !seen.contains(&full_url) {
-                 seen.insert(full_url.clone());
-                 links.push(full_url);
-             }
+    if entries.is_empty() && offset == 0 {
+        let mut error_debug_path = get_debug_dir();
+        error_debug_path.push("qidian_rank_debug.html");
+        let _ = fs::write(&error_debug_path, &html);
+        let tried: Vec<&str> = RANK_FAMILIES.iter().map(|f| f.name).collect();
+        log_to_file(&format!("Qidian Spider: No books found in rank page {}. Saved HTML to {:?}", url, error_debug_path));
+        return Err(resources::format(
+            "spider.rank_no_books",
+            &[("selectors", &tried.join(" / ")), ("path", &error_debug_path.display().to_string())],
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// 作者页作品条目：桌面作者页 .author-work / 移动端作者页书单
+const AUTHOR_WORK_ITEMS: &str =
+    ".author-work .author-item, .author-books li, .author-book-list li, .book-list li, a[class*='bookItem'][href*='/book/']";
+const AUTHOR_WORK_LINK: &str = "a[href*='/book/'], a[href*='/info/']";
+const AUTHOR_WORK_TITLE: &str = ".author-item-title a, h3 a, h4, .book-title, [class*='title']";
+const AUTHOR_NAME: &str = ".header-msg h1, .header-msg-name, .author-name, .book-author-name";
+/// 桌面作者页的翻页链接；纯脚本翻页时改用 `data-pagemax` 拼 `?page=N`
+const AUTHOR_NEXT_PAGE: &str = "a.lbf-pagination-next, a[rel='next'], .pagination a.next";
+/// 作品列表最多翻页数
+const MAX_AUTHOR_PAGES: usize = 10;
+
+/// 作者页的一页
+#[derive(Debug, Default)]
+pub(crate) struct AuthorPage {
+    pub author: Option<String>,
+    pub works: Vec<AuthorWork>,
+    pub next: Option<String>,
+}
+
+fn work_status(text: &str) -> Option<String> {
+    let status = if text.contains("完本") || text.contains("完结") {
+        "完本"
+    } else if text.contains("连载") {
+        "连载"
+    } else if text.contains("暂停") || text.contains("断更") {
+        "暂停"
+    } else {
+        return None;
+    };
+    Some(status.to_string())
+}
+
+/// 作者作品列表第 page 页的 URL（`?page=N`）
+fn author_page_url(url: &str, page: usize) -> Option<String> {
+    let mut parsed = url::Url::parse(url).ok()?;
+    let query: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(k, _)| k != "page")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(query).append_pair("page", &page.to_string());
+    Some(parsed.to_string())
+}
+
+/// 解析作者页的一页：作者名、作品（统一为桌面书籍 URL）和下一页地址。
+pub(crate) fn parse_author_page(html: &str, page_url: &str) -> AuthorPage {
+    let document = Html::parse_document(html);
+    let root = document.root_element();
+    let mut page = AuthorPage {
+        author: super::select_text(&root, AUTHOR_NAME).map(|name| name.trim_end_matches("的作品").trim().to_string()),
+        ..Default::default()
+    };
+    let (Ok(item_sel), Ok(link_sel)) = (Selector::parse(AUTHOR_WORK_ITEMS), Selector::parse(AUTHOR_WORK_LINK)) else {
+        return page;
+    };
+    let mut seen = std::collections::HashSet::new();
+    for item in document.select(&item_sel) {
+        let link = match item.value().attr("href") {
+            Some(_) => Some(item),
+            None => item.select(&link_sel).next(),
+        };
+        let Some(book_id) = link.and_then(|l| l.value().attr("href")).and_then(extract_book_id) else { continue };
+        if !seen.insert(book_id.clone()) {
+            continue;
         }
+        let title = super::select_text(&item, AUTHOR_WORK_TITLE)
+            .or_else(|| link.map(|l| l.text().collect::<String>().trim().to_string()))
+            .unwrap_or_default();
+        page.works.push(AuthorWork {
+            title,
+            url: canonical_book_url(&book_id),
+            status: work_status(&item.text().collect::<String>()),
+        });
     }
-    
-    // Do NOT sort, as it destroys the rank order!
-    
-    log_to_file(&format!("Found {} novels in rank list.", links.len()));
 
-    Ok(links)
+    let next_link = Selector::parse(AUTHOR_NEXT_PAGE).ok().and_then(|sel| {
+        document
+            .select(&sel)
+            .filter(|a| !a.value().classes().any(|c| c.contains("disabled")))
+            .find_map(|a| a.value().attr("href").and_then(|href| super::normalize_href(page_url, href)))
+    });
+    page.next = next_link.or_else(|| {
+        let sel = Selector::parse("[data-pagemax]").ok()?;
+        let pager = document.select(&sel).next()?;
+        let max: usize = pager.value().attr("data-pagemax")?.parse().ok()?;
+        let current: usize = pager.value().attr("data-page").and_then(|p| p.parse().ok()).unwrap_or(1);
+        (current < max).then(|| author_page_url(page_url, current + 1)).flatten()
+    });
+    page
 }
 
-use tauri::AppHandle;
+/// 抓取作者页上的全部作品，按下一页链接翻页，直到没有新作品或达到 [`MAX_AUTHOR_PAGES`]。
+pub async fn fetch_author_works<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<AuthorWorks, String> {
+    let mut result = AuthorWorks::default();
+    let mut page_url = url.to_string();
+    for page in 1..=MAX_AUTHOR_PAGES {
+        let html = match fetch_page(pages, &page_url, debug_visible).await {
+            Ok(html) => html,
+            Err(e) if page == 1 => return Err(resources::format("spider.author_fetch_failed", &[("error", &e.to_string())])),
+            Err(e) => {
+                log_to_file(&format!("Author page {} failed, keeping {} works: {}", page_url, result.works.len(), e));
+                break;
+            }
+        };
+        let parsed = parse_author_page(&html, &page_url);
+        if result.author.is_none() {
+            result.author = parsed.author;
+        }
+        let before = result.works.len();
+        for work in parsed.works {
+            if !result.works.iter().any(|w| w.url == work.url) {
+                result.works.push(work);
+            }
+        }
+        if result.works.is_empty() {
+            let mut debug_path = get_debug_dir();
+            debug_path.push("qidian_author_debug.html");
+            let _ = std::fs::write(&debug_path, &html);
+            return Err(resources::format("spider.author_no_works", &[("path", &debug_path.display().to_string())]));
+        }
+        match parsed.next {
+            Some(next) if result.works.len() > before && next != page_url => page_url = next,
+            _ => break,
+        }
+    }
+    log_to_file(&format!("Found {} works on author page {}.", result.works.len(), url));
+    Ok(result)
+}
 
 // Use browser spider for metadata to bypass WAF
-pub async fn fetch_novel_metadata(client: &Client, url: &str, app: &AppHandle, debug_visible: bool) -> Result<NovelMetadata, String> {
+pub async fn fetch_novel_metadata<P: PageFetcher + ?Sized>(client: &Client, url: &str, pages: &P, debug_visible: bool) -> Result<NovelMetadata, String> {
     let start_time = std::time::Instant::now();
     log_to_file(&format!("[START] fetch_novel_metadata: {}", url));
     
     // 1) 先尝试浏览器蜘蛛（可过大部分 WAF）
-    let html = match crate::browser_spider::fetch_via_window(app, url, debug_visible).await {
+    let html = match fetch_page(pages, url, debug_visible).await {
         Ok(h) => {
             log_to_file(&format!("Browser spider succeeded, got {} bytes", h.len()));
             h
         },
+        Err(e @ SpiderError::CircuitOpen { .. }) => return Err(e.into()),
         Err(e) => {
             // 浏览器蜘蛛失败，尝试移动端纯 HTTP 兜底
             log::warn!("Browser spider failed: {}. Trying mobile fallback...", e);
@@ -131,10 +465,38 @@ pub async fn fetch_novel_metadata(client: &Client, url: &str, app: &AppHandle, d
         Err(e) => log::error!("Failed to save metadata HTML to {:?}: {}", debug_path, e),
     }
     
-    let document = Html::parse_document(&html);
+    let metadata = parse_metadata(&html, url);
+    // 下架的书标题取自提示页的 <title>
+    if let Some(reason) = super::removed_reason(PLATFORM, None, &metadata.title) {
+        log_to_file(&format!("[FAILED] fetch_novel_metadata: novel removed ({})", reason));
+        return Err(SpiderError::NovelRemoved { url: url.to_string(), reason }.into());
+    }
+
+    // 浏览器拿到的仍是验证页：和蜘蛛失败一样改走移动端，两条路都失败才报错
+    if WAF_MARKERS.iter().any(|m| metadata.title.contains(m)) {
+        log_to_file(&format!("WAF detected after {} ms, trying mobile fallback", start_time.elapsed().as_millis()));
+        return match fetch_mobile_metadata(client, url).await {
+            Ok(metadata) => {
+                log_to_file(&format!("[SUCCESS] fetch_novel_metadata: {} via mobile fallback", metadata.title));
+                Ok(metadata)
+            }
+            Err(e) => {
+                log_to_file(&format!("[FAILED] fetch_novel_metadata: WAF detected, mobile fallback failed: {}", e));
+                Err(format!("Browser Spider still caught by WAF; mobile fallback failed: {}", e))
+            }
+        };
+    }
+    
+    log_to_file(&format!("[SUCCESS] fetch_novel_metadata: {} in {} ms", metadata.title, start_time.elapsed().as_millis()));
+    Ok(metadata)
+}
+
+/// 解析 PC 站书籍详情页
+pub(crate) fn parse_metadata(html: &str, url: &str) -> NovelMetadata {
+    let document = Html::parse_document(html);
     
     // Title: h1 or head > title
-    let title_sel = Selector::parse("h1, #bookName").unwrap();
+    let title_sel = selectors::selector(PLATFORM, selectors::METADATA_TITLE);
     let title = document.select(&title_sel).next()
         .map(|el| el.text().collect::<String>())
         .or_else(|| {
@@ -149,15 +511,9 @@ pub async fn fetch_novel_metadata(client: &Client, url: &str, app: &AppHandle, d
         })
         .unwrap_or_else(|| "Unknown Title".to_string()); // Soft fail if browser loaded something else
 
-    // Check if we are still on WAF page?
-    if title.contains("Just a moment") || title.contains("Security checking") {
-        log_to_file(&format!("[FAILED] fetch_novel_metadata: WAF detected after {} ms", start_time.elapsed().as_millis()));
-        return Err("Browser Spider still caught by WAF".to_string());
-    }
-
     // Description: Prioritize #book-intro-detail (User Request: 作品简介)
     // Then try .intro (short summary) or meta
-    let desc_sel_main = Selector::parse("#book-intro-detail").unwrap();
+    let desc_sel_main = selectors::selector(PLATFORM, selectors::METADATA_DESC);
     let desc_sel_fallback = Selector::parse(".book-intro, .intro").unwrap();
     
     let description = document.select(&desc_sel_main).next()
@@ -177,23 +533,14 @@ pub async fn fetch_novel_metadata(client: &Client, url: &str, app: &AppHandle, d
     let mut tags = Vec::new();
     
     // 1. Standard tags from book attribute (e.g. 连载, 签约, VIP, 都市, 异术超能)
-    let attr_tags_sel = Selector::parse(".book-attribute a").unwrap();
-    for el in document.select(&attr_tags_sel) {
+    // 2. Extra tags below description (e.g. 男生月票榜No.1, 系统流, 腹黑, 轻松)
+    //    Matches structure: <p class="all-label"> ... <a ...>Tag</a> ... </p>
+    let tags_sel = selectors::selector(PLATFORM, selectors::METADATA_TAGS);
+    for el in document.select(&tags_sel) {
         let t = el.text().collect::<String>().trim().to_string();
         if !t.is_empty() { tags.push(t); }
     }
-
-    // 2. Extra tags below description (e.g. 男生月票榜No.1, 系统流, 腹黑, 轻松)
-    // Matches structure: <p class="all-label"> ... <a ...>Tag</a> ... </p>
-    let extra_tags_sel = Selector::parse(".intro-honor-label .all-label a, .all-label a").unwrap();
-    for el in document.select(&extra_tags_sel) {
-         let t = el.text().collect::<String>().trim().to_string();
-         if !t.is_empty() { tags.push(t); }
-    }
-    
-    // Deduplicate
-    tags.sort();
-    tags.dedup();
+    // 去重和荣誉类标签的拆分在 tidy_metadata 中完成
 
     // Word count: .count em (first one)
     let count_sel = Selector::parse(".count em").unwrap();
@@ -201,26 +548,22 @@ pub async fn fetch_novel_metadata(client: &Client, url: &str, app: &AppHandle, d
         .map(|el| el.text().collect::<String>())
         .unwrap_or_else(|| "未知".to_string());
     
-    let metadata = NovelMetadata {
+    let mut metadata = NovelMetadata {
         title,
         url: url.to_string(),
         tags,
         word_count,
         description,
+        ..Default::default()
     };
-    
-    log_to_file(&format!("[SUCCESS] fetch_novel_metadata: {} in {} ms", metadata.title, start_time.elapsed().as_millis()));
-    Ok(metadata)
+    super::tidy_metadata(&mut metadata);
+    metadata
 }
 
 // 兜底：请求移动端页面（通常 WAF 较宽松）
 async fn fetch_mobile_metadata(client: &Client, url: &str) -> Result<NovelMetadata, String> {
     // 从 URL 中提取 bookId
-    let re = Regex::new(r"book/([0-9]+)/?").map_err(|e| e.to_string())?;
-    let book_id = re
-        .captures(url)
-        .and_then(|cap| cap.get(1).map(|m| m.as_str()))
-        .ok_or_else(|| "无法从 URL 提取 bookId".to_string())?;
+    let book_id = extract_book_id(url).ok_or_else(|| "无法从 URL 提取 bookId".to_string())?;
 
     let mobile_url = format!("https://m.qidian.com/book/{}", book_id);
     let resp = client
@@ -232,7 +575,27 @@ async fn fetch_mobile_metadata(client: &Client, url: &str) -> Result<NovelMetada
         .map_err(|e| format!("移动端请求失败: {}", e))?;
 
     let html = resp.text().await.map_err(|e| e.to_string())?;
-    let document = Html::parse_document(&html);
+    if looks_like_waf(&html) {
+        return Err("移动端页面同样被 WAF 拦截".to_string());
+    }
+    let mut metadata = parse_mobile_metadata(&html, &mobile_url);
+    if metadata.title == "Unknown Title" {
+        return Err("移动端页面中找不到书名".to_string());
+    }
+    metadata.source = Some(MOBILE_FALLBACK_SOURCE.to_string());
+    Ok(metadata)
+}
+
+/// 移动站字数，如 `446.52万字` 中的 `446.52万`，与 PC 站 `.count em` 的写法一致
+fn mobile_word_count(text: &str) -> Option<String> {
+    static RE: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(\d+(?:\.\d+)?\s*[万亿]?)\s*字").unwrap());
+    re.captures(text).map(|c| c[1].split_whitespace().collect())
+}
+
+/// 解析移动站书籍页，url 为移动站地址
+pub(crate) fn parse_mobile_metadata(html: &str, url: &str) -> NovelMetadata {
+    let document = Html::parse_document(html);
 
     // 移动端标题选择器尝试
     let title_sel = Selector::parse("h1, .book-title, .detail h2").unwrap();
@@ -243,50 +606,71 @@ async fn fetch_mobile_metadata(client: &Client, url: &str) -> Result<NovelMetada
         .filter(|s| !s.is_empty())
         .unwrap_or_else(|| "Unknown Title".to_string());
 
-    // 描述选择器
-    let desc_sel = Selector::parse(".book-intro, .intro, meta[name='description']").unwrap();
+    // 描述选择器：页面上的简介优先，head 里的 meta 描述常是"最新章节由网友提供"之类的套话，只作兜底
+    let desc_sel = Selector::parse(".book-intro, .intro").unwrap();
+    let meta_desc_sel = Selector::parse("meta[name='description']").unwrap();
     let description = document
         .select(&desc_sel)
         .next()
-        .map(|el| {
-            if el.value().name() == "meta" {
-                el.value()
-                    .attr("content")
-                    .unwrap_or_default()
-                    .to_string()
-            } else {
-                el.text().collect::<String>().trim().to_string()
-            }
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            document
+                .select(&meta_desc_sel)
+                .next()
+                .and_then(|el| el.value().attr("content"))
+                .map(|s| s.to_string())
         })
         .unwrap_or_default();
 
-    Ok(NovelMetadata {
+    // 分类：作者行 `作者 · 分类` 中点号之后的部分，加上标签区的标签（去重在 tidy_metadata 中完成）
+    let author_sel = Selector::parse(".book-author").unwrap();
+    let mut tags: Vec<String> = document
+        .select(&author_sel)
+        .next()
+        .map(|el| el.text().collect::<String>())
+        .map(|line| line.split('·').skip(1).map(|s| s.trim().to_string()).collect())
+        .unwrap_or_default();
+    let tag_sel = Selector::parse(".book-tags a, .book-tag, .book-label a").unwrap();
+    tags.extend(document.select(&tag_sel).map(|el| el.text().collect::<String>()));
+
+    // 字数：写在 `.book-meta` 等信息行里，如 `446.52万字`
+    let meta_sel = Selector::parse(".book-meta, .book-info, [class*='count']").unwrap();
+    let word_count = document
+        .select(&meta_sel)
+        .find_map(|el| mobile_word_count(&el.text().collect::<String>()))
+        .unwrap_or_else(|| "未知".to_string());
+
+    let mut metadata = NovelMetadata {
         title,
-        url: mobile_url,
-        tags: vec![],
-        word_count: "未知".to_string(),
+        url: url.to_string(),
+        tags,
+        word_count,
         description,
-    })
+        ..Default::default()
+    };
+    super::tidy_metadata(&mut metadata);
+    metadata
 }
 
 // Fetch chapter list using browser spider (to bypass WAF/JS render)
-pub async fn fetch_chapter_list(app: &AppHandle, url: &str, debug_visible: bool) -> Result<Vec<(String, String)>, String> {
+pub async fn fetch_chapter_list<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<Vec<(String, String)>, String> {
+    let catalog = fetch_catalog(pages, url, debug_visible).await?;
+    Ok(catalog.into_iter().take(super::max_catalog_chapters()).map(|c| (c.title, c.url)).collect())
+}
+
+// 完整目录（含 VIP 标记），顺序与目录页一致
+pub async fn fetch_catalog<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<Vec<CatalogChapter>, SpiderError> {
     let start_time = std::time::Instant::now();
     log_to_file(&format!("[START] fetch_chapter_list: {}", url));
     log_to_file(&format!("Debug visible: {}", debug_visible));
     
     // 1. Extract Book ID
-    let re = Regex::new(r"book/([0-9]+)").map_err(|e| {
-        log_to_file(&format!("[FAILED] fetch_chapter_list: Regex error: {}", e));
-        e.to_string()
-    })?;
-    let book_id = re.captures(url)
-        .and_then(|cap| cap.get(1))
-        .map(|m| m.as_str())
+    let book_id = extract_book_id(url)
         .ok_or_else(|| {
             let err = "Failed to extract book ID for catalog";
             log_to_file(&format!("[FAILED] fetch_chapter_list: {}", err));
-            err.to_string()
+            SpiderError::Other(err.to_string())
         })?;
     
     log_to_file(&format!("Extracted book ID: {}", book_id));
@@ -297,21 +681,20 @@ pub async fn fetch_chapter_list(app: &AppHandle, url: &str, debug_visible: bool)
     log_to_file("Calling browser spider...");
 
     // 3. Fetch via Browser Spider
-    let html = crate::browser_spider::fetch_via_window(app, &catalog_url, debug_visible).await
+    let html = fetch_page(pages, &catalog_url, debug_visible).await
         .map_err(|e| {
             log_to_file(&format!("[FAILED] fetch_chapter_list: Browser spider error: {}", e));
             e
         })?;
     
     log_to_file(&format!("Browser spider returned HTML: {} bytes", html.len()));
-    log_to_file(&format!("HTML preview (first 200 chars): {}", html.chars().take(200).collect::<String>()));
+    log_to_file(&format!("HTML preview (first 200 chars): {}", super::char_prefix(&html, 200)));
     
     // Debug: Save catalog page HTML
     use std::fs;
     let debug_dir = get_debug_dir();
     log_to_file(&format!("Debug directory: {:?}", debug_dir));
-    let mut debug_path = debug_dir.clone();
-    debug_path.push("debug_2_catalog.html");
+    let debug_path = debug_dir.join("debug_2_catalog.html");
     log_to_file(&format!("Attempting to save HTML to: {:?}", debug_path));
     
     match fs::write(&debug_path, &html) {
@@ -319,39 +702,12 @@ pub async fn fetch_chapter_list(app: &AppHandle, url: &str, debug_visible: bool)
         Err(e) => log_to_file(&format!("✗ Failed to save catalog HTML to {:?}: {}", debug_path, e)),
     }
     
-    // 4. Parse
-    let document = Html::parse_document(&html);
-    
-    // Selectors for mobile catalog
-    // Updated: matches .y-list__item a (standard list) or class contianing chapterItem (robustness)
-    let selector = Selector::parse(".y-list__item a, a[class*='chapterItem']").map_err(|_| "Selector error".to_string())?;
-    
-    let mut chapters = Vec::new();
-    for element in document.select(&selector) {
-        let title = element.text().collect::<String>().trim().to_string();
-        let href = element.value().attr("href").unwrap_or_default().to_string();
-
-        if !title.is_empty() && !href.is_empty() && !href.contains("javascript") {
-             let full_url = if href.starts_with("//") {
-                 format!("https:{}", href)
-             } else if href.starts_with("/") {
-                 format!("https://m.qidian.com{}", href)
-             } else {
-                 href
-             };
-             
-             // Simple dedup check or validation?
-             // Only add if it looks like a chapter link
-             if full_url.contains("/chapter/") || full_url.contains("/read/") {
-                  chapters.push((title, full_url));
-             }
-        }
-    }
+    // 4. Parse（多取一章，供下载流程判断目录是否真的超出上限）
+    let chapters = parse_catalog(&html, super::max_catalog_chapters() + 1);
     
     if chapters.is_empty() {
         // Debug: Log HTML snippet to see what happened
-        let snippet: String = html.chars().take(1000).collect();
-        log_to_file(&format!("Qidian Spider: No chapters found! HTML Snippet: {}", snippet));
+        log_to_file(&format!("Qidian Spider: No chapters found! HTML Snippet: {}", super::char_prefix(&html, 1000)));
         
         // Also try to write full HTML to a file for debugging
         let mut error_debug_path = get_debug_dir();
@@ -359,15 +715,66 @@ pub async fn fetch_chapter_list(app: &AppHandle, url: &str, debug_visible: bool)
         let _ = fs::write(&error_debug_path, &html);
         log_to_file(&format!("Saved error catalog HTML to {:?}", error_debug_path));
 
-        return Err(format!("No chapters found in catalog. Check {:?}", error_debug_path));
+        if let Some(reason) = super::removed_reason(PLATFORM, None, &html) {
+            log_to_file(&format!("[FAILED] fetch_chapter_list: novel removed ({})", reason));
+            return Err(SpiderError::NovelRemoved { url: url.to_string(), reason });
+        }
+        return Err(SpiderError::Other(format!("No chapters found in catalog. Check {:?}", error_debug_path)));
     }
 
     log_to_file(&format!("[SUCCESS] fetch_chapter_list: Found {} chapters in {} ms", chapters.len(), start_time.elapsed().as_millis()));
     Ok(chapters)
 }
 
+/// 解析移动端目录页，最多取 max 章。链接统一解析为绝对 URL，非 http(s) 链接丢弃。
+pub(crate) fn parse_catalog(html: &str, max: usize) -> Vec<CatalogChapter> {
+    let document = Html::parse_document(html);
+
+    // Selectors for mobile catalog
+    // Updated: matches .y-list__item a (standard list) or class contianing chapterItem (robustness)
+    let selector = selectors::selector(PLATFORM, selectors::CATALOG);
+
+    // 按文档顺序遍历，记录当前所在的卷，作品相关卷下的条目标记为 is_extra，其余条目记下卷名
+    let volume_selector = Selector::parse("h2, h3, h4, [class*='volume'], [class*='y-list__header']").unwrap();
+    let mut in_extra_volume = false;
+    let mut volume: Option<String> = None;
+    let mut chapters = Vec::new();
+    for element in document.root_element().descendants().filter_map(ElementRef::wrap) {
+        // 只认不含章节链接的卷标题，避免把整个目录容器当作卷
+        if volume_selector.matches(&element) && element.select(&selector).next().is_none() {
+            let name = super::clean_text(&element.text().collect::<String>());
+            in_extra_volume = name.contains(super::EXTRA_VOLUME_MARKER);
+            volume = Some(name).filter(|n| !in_extra_volume && !n.is_empty());
+            continue;
+        }
+        if !selector.matches(&element) {
+            continue;
+        }
+        let title = element.text().collect::<String>();
+        if super::clean_text(&title).is_empty() {
+            continue;
+        }
+        let href = element.value().attr("href").unwrap_or_default();
+        let Some(full_url) = super::normalize_href("https://m.qidian.com/", href) else { continue };
+
+        // Only add if it looks like a chapter link
+        if full_url.contains("/chapter/") || full_url.contains("/read/") {
+            if chapters.len() >= max {
+                log_to_file(&format!("Qidian Spider: catalog truncated at {} chapters, rest ignored", max));
+                break;
+            }
+            let is_vip = full_url.contains("vipreader") || super::element_looks_vip(&element);
+            let mut chapter = CatalogChapter::new(&title, full_url, is_vip);
+            chapter.is_extra = in_extra_volume;
+            chapter.volume = volume.clone();
+            chapters.push(chapter);
+        }
+    }
+    chapters
+}
+
 // Qidian chapter pages. We use browser spider to bypass WAF.
-pub async fn download_chapter(app: &AppHandle, url: &str, debug_visible: bool) -> Result<(String, String), String> {
+pub async fn download_chapter<P: PageFetcher + ?Sized>(pages: &P, url: &str, debug_visible: bool) -> Result<(String, String), SpiderError> {
     let start_time = std::time::Instant::now();
     log_to_file(&format!("[START] download_chapter: {}", url));
     
@@ -375,11 +782,18 @@ pub async fn download_chapter(app: &AppHandle, url: &str, debug_visible: bool) -
     let target_url = url.replace("m.qidian.com", "www.qidian.com");
     
     // Use browser spider
-    let html = crate::browser_spider::fetch_via_window(app, &target_url, debug_visible).await
+    let page = fetch_document(pages, &target_url, debug_visible).await
         .map_err(|e| {
             log_to_file(&format!("[FAILED] download_chapter: Browser spider error: {}", e));
             e
         })?;
+    // 下架 / 重排的章节会 302 回书籍页，书籍简介也能被正文选择器宽松匹配到，不能继续解析
+    if redirected_off_chapter(&target_url, page.final_url.as_deref()) {
+        let final_url = page.final_url.unwrap_or_default();
+        log_to_file(&format!("[FAILED] download_chapter: {} redirected to {}", url, final_url));
+        return Err(SpiderError::ChapterUnavailable { url: url.to_string(), final_url });
+    }
+    let html = page.html;
     
     // Debug: Save chapter page HTML
     use std::fs;
@@ -391,45 +805,279 @@ pub async fn download_chapter(app: &AppHandle, url: &str, debug_visible: bool) -
         Err(e) => log_to_file(&format!("Failed to save chapter HTML to {:?}: {}", debug_path, e)),
     }
     
-    let document = Html::parse_document(&html);
+    let Some((title, content)) = parse_chapter(&html) else {
+        // Enhanced Debugging
+        log_to_file(&format!("Failed to find content for url: {}\nSelectors tried: {}\nHTML Snippet: {}", url, selectors::get(PLATFORM, selectors::CHAPTER_CONTENT), super::char_prefix(&html, 500)));
+        log_to_file(&format!("[FAILED] download_chapter: Content not found after {} ms", start_time.elapsed().as_millis()));
+        return Err(SpiderError::Other("Failed to find content (WAF or Selector Mismatch). See logs.".to_string()));
+    };
+    
+    // Extra cleaner? Qidian sometimes has hidden elements or anti-copy. 
+    // For now, let's trust simple text extraction.
+    
+    log_to_file(&format!("[SUCCESS] download_chapter: {} ({} chars) in {} ms", title, content.len(), start_time.elapsed().as_millis()));
+    Ok((title, content))
+}
+
+/// 解析 PC 站章节页，返回 (章节页标题, 正文)；找不到正文容器时返回 None
+pub(crate) fn parse_chapter(html: &str) -> Option<(String, String)> {
+    let document = Html::parse_document(html);
 
     // Selectors for WWW site
     // Title: .j_chapterName, .text-head h3, or h1
-    let title_sel = Selector::parse(".j_chapterName, .text-head h3, h1, .chapter-name").unwrap();
+    let title_sel = selectors::selector(PLATFORM, selectors::CHAPTER_TITLE);
     let title = document.select(&title_sel).next()
         .map(|el| el.text().collect::<String>().trim().to_string())
-        .unwrap_or_else(|| "".to_string()); // Title is optional here as we have it from list, but good for verify
+        .unwrap_or_default(); // Title is optional here as we have it from list, but good for verify
         
     // Content: .read-content or .main-text-wrap
     // NOTE: Qidian sometimes splits content into multiple paragraphs/elements.
     // Updated: matches new desktop structure (main.content)
-    let content_sel = Selector::parse("main.content, .read-content, .main-text-wrap, .j_readContent, #reader-content").unwrap();
+    let content_sel = selectors::selector(PLATFORM, selectors::CHAPTER_CONTENT);
     
-    let content = if let Some(container) = document.select(&content_sel).next() {
-        // We prefer to iterate over paragraphs <p> if they exist to keep formatting
-        let p_sel = Selector::parse("p").unwrap();
-        let mut lines = Vec::new();
-        for p in container.select(&p_sel) {
-            lines.push(p.text().collect::<String>());
-        }
-        
-        if !lines.is_empty() {
-             lines.join("\n\n")
-        } else {
-             // Fallback: just raw text
-             container.text().collect::<String>()
-        }
+    let container = document.select(&content_sel).next()?;
+    // We prefer to iterate over paragraphs <p> if they exist to keep formatting
+    let p_sel = Selector::parse("p").unwrap();
+    let mut lines = Vec::new();
+    for p in container.select(&p_sel) {
+        lines.push(p.text().collect::<String>());
+    }
+    let content = if !lines.is_empty() {
+        lines.join("\n\n")
     } else {
-        // Enhanced Debugging
-        let snippet: String = html.chars().take(500).collect();
-        log_to_file(&format!("Failed to find content for url: {}\nSelectors tried: main.content, .read-content, .main-text-wrap, .j_readContent, #reader-content\nHTML Snippet: {}", url, snippet));
-        log_to_file(&format!("[FAILED] download_chapter: Content not found after {} ms", start_time.elapsed().as_millis()));
-        return Err("Failed to find content (WAF or Selector Mismatch). See logs.".to_string());
+        // Fallback: just raw text
+        container.text().collect::<String>()
     };
-    
-    // Extra cleaner? Qidian sometimes has hidden elements or anti-copy. 
-    // For now, let's trust simple text extraction.
-    
-    log_to_file(&format!("[SUCCESS] download_chapter: {} ({} chars) in {} ms", title, content.len(), start_time.elapsed().as_millis()));
-    Ok((title, content))
+    Some((title, content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESKTOP_RANK: &str = include_str!("fixtures/qidian_rank_desktop.html");
+    const DESKTOP_RANK_PAGE2: &str = include_str!("fixtures/qidian_rank_desktop_page2.html");
+    const MOBILE_RANK: &str = include_str!("fixtures/qidian_rank_mobile.html");
+    const MOBILE_RANK_CARDS: &str = include_str!("fixtures/qidian_rank_mobile_cards.html");
+    const DECORATED_BOOK: &str = include_str!("fixtures/qidian_book_decorated.html");
+    const MOBILE_BOOK: &str = include_str!("fixtures/corpus/qidian_metadata_mobile_20241012.html");
+
+    fn urls(entries: &[RankEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    #[test]
+    fn book_id_from_various_urls() {
+        assert_eq!(extract_book_id("https://www.qidian.com/book/1035420986/").as_deref(), Some("1035420986"));
+        assert_eq!(extract_book_id("//m.qidian.com/book/1035420986"), Some("1035420986".to_string()));
+        assert_eq!(extract_book_id("https://book.qidian.com/info/1035420986"), Some("1035420986".to_string()));
+        assert_eq!(extract_book_id("https://my.qidian.com/author/4363355/"), None);
+    }
+
+    #[test]
+    fn decorated_book_page_is_tidied() {
+        let meta = parse_metadata(DECORATED_BOOK, "https://www.qidian.com/book/1/");
+        assert_eq!(meta.title, "深海余烬");
+        assert_eq!(meta.word_count, "325.6万");
+        assert_eq!(meta.tags, vec!["连载", "签约", "VIP", "玄幻", "东方玄幻", "系统流", "克苏鲁", "轻松", "腹黑"]);
+        assert_eq!(
+            meta.honors,
+            vec!["男生月票榜No.1", "2023年度十佳作品", "起点十二天王", "这是一个特别特别长的伪标签文本"]
+        );
+        assert!(meta.description.starts_with("开篇是一场海难。"));
+        assert!(meta.description.chars().count() <= crate::spiders::max_description_chars());
+        assert!(meta.description.ends_with('。'));
+        assert_eq!(meta.truncated, vec!["description"]);
+    }
+
+    #[test]
+    fn mobile_book_page_yields_tags_and_word_count() {
+        let meta = parse_mobile_metadata(MOBILE_BOOK, "https://m.qidian.com/book/1035420986");
+        assert_eq!(meta.title, "深海余烬");
+        assert_eq!(meta.word_count, "325.6万");
+        // 作者行里的分类在前，标签区重复的"时空穿梭"去重
+        assert_eq!(meta.tags, vec!["科幻", "时空穿梭", "克苏鲁", "轻松"]);
+        assert!(meta.description.starts_with("开篇是一场海难。"));
+        // 来源只在兜底抓取时标记，解析本身不带
+        assert_eq!(meta.source, None);
+
+        assert_eq!(mobile_word_count("连载中 | 446.52万字").as_deref(), Some("446.52万"));
+        assert_eq!(mobile_word_count("共 12 万字").as_deref(), Some("12万"));
+        assert_eq!(mobile_word_count("月票 2356 · 推荐 12.8万"), None);
+    }
+
+    #[test]
+    fn desktop_and_mobile_rank_pages_yield_same_canonical_links() {
+        let desktop = parse_rank_entries(DESKTOP_RANK, 0);
+        let mobile = parse_rank_entries(MOBILE_RANK, 0);
+        assert_eq!(
+            urls(&desktop),
+            vec![
+                "https://www.qidian.com/book/1035420986/",
+                "https://www.qidian.com/book/1036370336/",
+                "https://www.qidian.com/book/1031940621/",
+            ]
+        );
+        assert_eq!(urls(&mobile), urls(&desktop));
+        assert_eq!(mobile[0].title, "宿命之环");
+        assert_eq!(mobile[0].author.as_deref(), Some("爱潜水的乌贼"));
+
+        // CSS module 卡片：<a class="…bookItem…"> 本身就是书籍链接
+        let cards = parse_rank_entries(MOBILE_RANK_CARDS, 0);
+        assert_eq!(urls(&cards), urls(&desktop));
+        assert_eq!(cards[1].title, "我的属性修行人生");
+        assert_eq!(cards[1].author.as_deref(), Some("滚开"));
+        assert_eq!(cards[0].score.as_deref(), Some("12.3万月票"));
+    }
+
+    #[test]
+    fn rank_entries_keep_metadata_and_tolerate_missing_score() {
+        let entries = parse_rank_entries(DESKTOP_RANK, 0);
+        assert_eq!(entries[0].title, "宿命之环");
+        assert_eq!(entries[0].author.as_deref(), Some("爱潜水的乌贼"));
+        assert_eq!(entries[0].score.as_deref(), Some("25478"));
+        assert_eq!(entries[2].score, None);
+    }
+
+    #[test]
+    fn positions_continue_across_pages() {
+        let page1 = parse_rank_entries(DESKTOP_RANK, 0);
+        let page2 = parse_rank_entries(DESKTOP_RANK_PAGE2, page1.len());
+        let positions: Vec<usize> = page1.iter().chain(page2.iter()).map(|e| e.position).collect();
+        assert_eq!(positions, vec![1, 2, 3, 4, 5]);
+        assert_eq!(page2[0].title, "玄鉴仙族");
+    }
+
+    #[test]
+    fn rank_page_urls() {
+        assert_eq!(
+            rank_page_url("https://www.qidian.com/rank/yuepiao/", 2).as_deref(),
+            Some("https://www.qidian.com/rank/yuepiao/page2/")
+        );
+        assert_eq!(
+            rank_page_url("https://www.qidian.com/rank/yuepiao/page3/", 4).as_deref(),
+            Some("https://www.qidian.com/rank/yuepiao/page4/")
+        );
+        assert_eq!(rank_page_url("https://m.qidian.com/rank/yuepiao/", 2), None);
+        assert_eq!(rank_start_page("https://www.qidian.com/rank/yuepiao/"), 1);
+        assert_eq!(rank_start_page("https://www.qidian.com/rank/yuepiao/page3/"), 3);
+        assert_eq!(rank_start_page("https://www.qidian.com/rank/yuepiao/page12"), 12);
+    }
+
+    #[test]
+    fn pathological_catalog_is_bounded() {
+        let mut html = String::from("<ul>");
+        html.push_str(&format!("<li class='y-list__item'><a href='/chapter/1/1/'>{}</a></li>", "\u{202E}章".repeat(4000)));
+        html.push_str("<li class='y-list__item'><a href='javascript:location=\"/chapter/1/2/\"'>第二章</a></li>");
+        html.push_str("<li class='y-list__item'><a href='data:text/html,/chapter/'>第三章</a></li>");
+        for i in 4..300 {
+            html.push_str(&format!("<li class='y-list__item'><a href='//m.qidian.com/chapter/1/{}/'>第{}章</a></li>", i, i));
+        }
+        html.push_str("</ul>");
+
+        let chapters = parse_catalog(&html, 50);
+        assert_eq!(chapters.len(), 50);
+        assert!(chapters.iter().all(|c| c.url.starts_with("https://m.qidian.com/chapter/")));
+        assert!(chapters.iter().all(|c| c.title.chars().count() <= super::super::MAX_TITLE_CHARS));
+        assert_eq!(chapters[0].full_title.as_ref().map(|t| t.chars().count()), Some(4000));
+        assert_eq!(chapters[1].title, "第4章");
+    }
+
+    #[test]
+    fn chapters_in_extra_volume_are_flagged() {
+        let html = "<div class='catalog'>\
+            <h3 class='y-list__header'>作品相关</h3>\
+            <ul><li class='y-list__item'><a href='/chapter/1/1/'>上架感言</a></li>\
+            <li class='y-list__item'><a href='/chapter/1/2/'>人物设定</a></li></ul>\
+            <h3 class='y-list__header'>第一卷 初入江湖</h3>\
+            <ul><li class='y-list__item'><a href='/chapter/1/3/'>第一章 下山</a></li></ul>\
+            </div>";
+        let chapters = parse_catalog(html, 50);
+        assert_eq!(chapters.iter().map(|c| c.is_extra).collect::<Vec<_>>(), vec![true, true, false]);
+    }
+
+    #[test]
+    fn large_catalog_parse_memory_is_bounded() {
+        use crate::test_alloc::measure;
+
+        let mut html = String::from("<html><body><ul>");
+        for i in 1..=8000 {
+            html.push_str(&format!(
+                "<li class='y-list__item'><a href='//m.qidian.com/chapter/1/{}/'>第{}章 {}</a></li>",
+                i,
+                i,
+                "长标题".repeat(8)
+            ));
+        }
+        html.push_str("</ul></body></html>");
+
+        // 预览和 WAF 检测只切片，不分配
+        let (_, stats) = measure(|| {
+            assert!(!looks_like_waf(&html));
+            assert_eq!(super::super::char_prefix(&html, 200).chars().count(), 200);
+        });
+        assert_eq!(stats.total_bytes, 0);
+
+        let start = std::time::Instant::now();
+        let (chapters, stats) = measure(|| parse_catalog(&html, super::super::max_catalog_chapters()));
+        eprintln!(
+            "parse_catalog: {} 字节 HTML，峰值 {} 字节，累计 {} 字节，{} ms",
+            html.len(),
+            stats.peak_bytes,
+            stats.total_bytes,
+            start.elapsed().as_millis()
+        );
+        assert_eq!(chapters.len(), 8000);
+        assert!(stats.peak_bytes < html.len() * 16, "峰值 {} 字节超出预期", stats.peak_bytes);
+    }
+
+    #[test]
+    fn waf_page_is_detected() {
+        assert!(looks_like_waf("<html><head><title>Just a moment...</title></head></html>"));
+        assert!(!looks_like_waf(DESKTOP_RANK));
+    }
+
+    #[test]
+    fn chapter_redirect_to_book_page_is_detected() {
+        let chapter = "https://www.qidian.com/chapter/1035420986/742187321/";
+        assert!(redirected_off_chapter(chapter, Some("https://www.qidian.com/book/1035420986/")));
+        assert!(redirected_off_chapter(chapter, Some("https://book.qidian.com/info/1035420986")));
+        assert!(redirected_off_chapter(chapter, Some("https://www.qidian.com/")));
+        // 未跳转、移动站跳转、跳到另一章节页、最终地址未知
+        assert!(!redirected_off_chapter(chapter, Some(chapter)));
+        assert!(!redirected_off_chapter(chapter, Some("https://m.qidian.com/chapter/1035420986/742187321/?from=pc")));
+        assert!(!redirected_off_chapter(chapter, Some("https://www.qidian.com/chapter/1035420986/800000001/")));
+        assert!(!redirected_off_chapter(chapter, None));
+        assert!(!redirected_off_chapter(chapter, Some("")));
+    }
+
+    #[test]
+    fn author_page_lists_works_and_next_page() {
+        let url = "https://my.qidian.com/author/2536/";
+        let page = parse_author_page(include_str!("fixtures/qidian_author_desktop.html"), url);
+        assert_eq!(page.author.as_deref(), Some("忘语"));
+        let works: Vec<_> = page.works.iter().map(|w| (w.title.as_str(), w.url.as_str(), w.status.as_deref())).collect();
+        assert_eq!(
+            works,
+            vec![
+                ("凡人修仙传", "https://www.qidian.com/book/107580/", Some("完本")),
+                ("玄界之门", "https://www.qidian.com/book/1010734492/", Some("完本")),
+                ("大夏文圣", "https://www.qidian.com/book/1036370336/", Some("连载")),
+            ]
+        );
+        assert_eq!(page.next.as_deref(), Some("https://my.qidian.com/author/2536/?page=2"));
+
+        // 只有 data-pagemax 的脚本翻页
+        let scripted = r#"<div class="author-work"><div class="author-item"><a href="//book.qidian.com/info/1/">书</a></div></div>
+            <div id="page-container" data-page="2" data-pagemax="3"></div>"#;
+        let page = parse_author_page(scripted, "https://my.qidian.com/author/2536/?page=2");
+        assert_eq!(page.next.as_deref(), Some("https://my.qidian.com/author/2536/?page=3"));
+        let last = scripted.replace("data-page=\"2\"", "data-page=\"3\"");
+        assert!(parse_author_page(&last, "https://my.qidian.com/author/2536/?page=3").next.is_none());
+    }
+
+    #[test]
+    fn rank_page_without_books_is_empty() {
+        assert!(parse_rank_entries("<html><body><a href='/author/1/'>作者</a></body></html>", 0).is_empty());
+    }
 }
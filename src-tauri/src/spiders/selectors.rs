@@ -0,0 +1,270 @@
+//! 可覆盖的 CSS 选择器：`<workspace>/selectors.json`。
+//!
+//! 文件格式为 `{ "qidian": { "catalog": "..." }, "fanqie": { ... } }`，只需写要覆盖的项。
+//! 站点改版时用户改这个文件（或通过 `set_selector_override`）即可恢复抓取，不必等新版本。
+//! 无法解析的选择器逐条记录错误并回退到内置默认值。
+
+use scraper::Selector;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+pub const SELECTORS_FILE: &str = "selectors.json";
+
+pub const PLATFORMS: &[&str] = &["qidian", "fanqie"];
+
+/// 用途名称，与 selectors.json 的键一致
+pub const RANK: &str = "rank";
+pub const CATALOG: &str = "catalog";
+pub const METADATA_TITLE: &str = "metadata_title";
+pub const METADATA_DESC: &str = "metadata_desc";
+pub const METADATA_TAGS: &str = "metadata_tags";
+pub const CHAPTER_TITLE: &str = "chapter_title";
+pub const CHAPTER_CONTENT: &str = "chapter_content";
+
+pub const PURPOSES: &[&str] = &[RANK, CATALOG, METADATA_TITLE, METADATA_DESC, METADATA_TAGS, CHAPTER_TITLE, CHAPTER_CONTENT];
+
+/// 内置默认值
+fn default_selector(platform: &str, purpose: &str) -> Option<&'static str> {
+    let selector = match (platform, purpose) {
+        // 起点榜单按页面族解析（见 qidian::RANK_FAMILIES），覆盖值需直接匹配书籍链接
+        ("qidian", RANK) => ".book-mid-info h2 a, .rank-list a.book-layout, .y-list__item a[href*='/book/']",
+        ("qidian", CATALOG) => ".y-list__item a, a[class*='chapterItem']",
+        ("qidian", METADATA_TITLE) => "h1, #bookName",
+        ("qidian", METADATA_DESC) => "#book-intro-detail",
+        ("qidian", METADATA_TAGS) => ".book-attribute a, .intro-honor-label .all-label a, .all-label a",
+        ("qidian", CHAPTER_TITLE) => ".j_chapterName, .text-head h3, h1, .chapter-name",
+        ("qidian", CHAPTER_CONTENT) => "main.content, .read-content, .main-text-wrap, .j_readContent, #reader-content",
+        ("fanqie", RANK) => "a[href^='/page/']",
+        ("fanqie", CATALOG) => ".chapter-item",
+        ("fanqie", METADATA_TITLE) => "h1",
+        ("fanqie", METADATA_DESC) => ".page-abstract-content",
+        ("fanqie", METADATA_TAGS) => ".info-label",
+        ("fanqie", CHAPTER_TITLE) => ".muye-reader-title, h1",
+        ("fanqie", CHAPTER_CONTENT) => ".muye-reader-content-16 p",
+        _ => return None,
+    };
+    Some(selector)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ActiveSelector {
+    pub purpose: String,
+    /// 实际生效的选择器
+    pub selector: String,
+    pub default: String,
+    pub overridden: bool,
+    /// 覆盖值无法解析时的错误（此时 selector 为默认值）
+    pub error: Option<String>,
+}
+
+type Overrides = BTreeMap<String, BTreeMap<String, String>>;
+
+fn overrides() -> &'static RwLock<Overrides> {
+    static OVERRIDES: OnceLock<RwLock<Overrides>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| RwLock::new(Overrides::new()))
+}
+
+pub fn selectors_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(SELECTORS_FILE)
+}
+
+fn read_file(workspace_root: &Path) -> Overrides {
+    let path = selectors_path(workspace_root);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Overrides::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("[Selectors] 解析 {:?} 失败，使用内置选择器: {}", path, e);
+        Overrides::new()
+    })
+}
+
+/// 校验选择器，返回错误描述。
+pub fn validate(selector: &str) -> Result<(), String> {
+    if selector.trim().is_empty() {
+        return Err("选择器为空".to_string());
+    }
+    Selector::parse(selector).map(|_| ()).map_err(|e| format!("无效的选择器: {}", e))
+}
+
+/// 从工作区重新加载覆盖配置（启动、切换工作区、修改覆盖后调用）。
+pub fn reload(workspace_root: &Path) {
+    let loaded = read_file(workspace_root);
+    for (platform, purposes) in &loaded {
+        for (purpose, selector) in purposes {
+            if let Err(e) = validate(selector) {
+                crate::log_to_file(&format!("[Selectors] {}.{} 覆盖无效，使用默认值: {}", platform, purpose, e));
+            }
+        }
+    }
+    if let Ok(mut guard) = overrides().write() {
+        *guard = loaded;
+    }
+}
+
+fn resolve(current: &Overrides, platform: &str, purpose: &str) -> Option<ActiveSelector> {
+    let default = default_selector(platform, purpose)?;
+    let custom = current.get(platform).and_then(|p| p.get(purpose));
+    let (selector, overridden, error) = match custom.map(|s| (s, validate(s))) {
+        Some((s, Ok(()))) => (s.clone(), true, None),
+        Some((_, Err(e))) => (default.to_string(), false, Some(e)),
+        None => (default.to_string(), false, None),
+    };
+    Some(ActiveSelector { purpose: purpose.to_string(), selector, default: default.to_string(), overridden, error })
+}
+
+/// 当前生效的选择器字符串。未知的平台 / 用途返回空串。
+pub fn get(platform: &str, purpose: &str) -> String {
+    let guard = overrides().read().unwrap_or_else(|e| e.into_inner());
+    resolve(&guard, platform, purpose).map(|a| a.selector).unwrap_or_default()
+}
+
+/// 有效的用户覆盖值；未覆盖或覆盖无效时返回 None。
+pub fn custom(platform: &str, purpose: &str) -> Option<String> {
+    let guard = overrides().read().unwrap_or_else(|e| e.into_inner());
+    resolve(&guard, platform, purpose).filter(|a| a.overridden).map(|a| a.selector)
+}
+
+/// 当前生效的已解析选择器（覆盖值无效时已回退为默认值，必定可解析）。
+pub fn selector(platform: &str, purpose: &str) -> Selector {
+    Selector::parse(&get(platform, purpose)).unwrap_or_else(|_| Selector::parse("*").unwrap())
+}
+
+/// 某平台（覆盖项是否生效）的全部选择器
+pub fn active(platform: &str) -> Result<Vec<ActiveSelector>, String> {
+    check_platform(platform)?;
+    let guard = overrides().read().unwrap_or_else(|e| e.into_inner());
+    Ok(PURPOSES.iter().filter_map(|p| resolve(&guard, platform, p)).collect())
+}
+
+fn check_platform(platform: &str) -> Result<(), String> {
+    if PLATFORMS.contains(&platform) {
+        Ok(())
+    } else {
+        Err(format!("不支持的平台: {}", platform))
+    }
+}
+
+/// 写入一条覆盖；selector 为 None 或空串表示恢复默认。无效选择器直接拒绝。
+pub fn set_override(workspace_root: &Path, platform: &str, purpose: &str, selector: Option<&str>) -> Result<(), String> {
+    check_platform(platform)?;
+    if !PURPOSES.contains(&purpose) {
+        return Err(format!("未知的选择器用途: {}", purpose));
+    }
+    let selector = selector.map(str::trim).filter(|s| !s.is_empty());
+    if let Some(s) = selector {
+        validate(s)?;
+    }
+
+    let mut current = read_file(workspace_root);
+    match selector {
+        Some(s) => {
+            current.entry(platform.to_string()).or_default().insert(purpose.to_string(), s.to_string());
+        }
+        None => {
+            if let Some(p) = current.get_mut(platform) {
+                p.remove(purpose);
+            }
+            current.retain(|_, p| !p.is_empty());
+        }
+    }
+    let content = serde_json::to_string_pretty(&current).map_err(|e| format!("序列化选择器失败: {}", e))?;
+    crate::storage::write_atomic(&selectors_path(workspace_root), content.as_bytes())
+        .map_err(|e| format!("写入 {} 失败: {}", SELECTORS_FILE, e))?;
+    reload(workspace_root);
+    Ok(())
+}
+
+/// 选择器试运行结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectorTestResult {
+    pub selector: String,
+    pub matched: usize,
+    /// 前几个匹配元素的文本（已截断）
+    pub samples: Vec<String>,
+}
+
+const TEST_SAMPLE_COUNT: usize = 5;
+const TEST_SAMPLE_CHARS: usize = 100;
+
+/// 在页面 HTML 上试运行选择器。
+pub fn test_on_html(html: &str, selector_str: &str) -> Result<SelectorTestResult, String> {
+    validate(selector_str)?;
+    let selector = Selector::parse(selector_str).map_err(|e| format!("无效的选择器: {}", e))?;
+    let document = scraper::Html::parse_document(html);
+    let mut matched = 0;
+    let mut samples = Vec::new();
+    for element in document.select(&selector) {
+        matched += 1;
+        if samples.len() < TEST_SAMPLE_COUNT {
+            let text = super::clean_text(&element.text().collect::<String>());
+            let text = if text.is_empty() {
+                element.value().attr("href").unwrap_or_default().to_string()
+            } else {
+                text
+            };
+            samples.push(super::truncate_chars(&text, TEST_SAMPLE_CHARS));
+        }
+    }
+    Ok(SelectorTestResult { selector: selector_str.to_string(), matched, samples })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_default_selector_parses() {
+        for platform in PLATFORMS {
+            for purpose in PURPOSES {
+                let s = default_selector(platform, purpose).unwrap();
+                assert!(validate(s).is_ok(), "{}.{}", platform, purpose);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_override_falls_back_per_entry() {
+        let mut current = Overrides::new();
+        let qidian = current.entry("qidian".to_string()).or_default();
+        qidian.insert(CATALOG.to_string(), ".new-list a".to_string());
+        qidian.insert(CHAPTER_CONTENT.to_string(), "main[[".to_string());
+
+        let catalog = resolve(&current, "qidian", CATALOG).unwrap();
+        assert_eq!(catalog.selector, ".new-list a");
+        assert!(catalog.overridden && catalog.error.is_none());
+
+        let content = resolve(&current, "qidian", CHAPTER_CONTENT).unwrap();
+        assert_eq!(content.selector, content.default);
+        assert!(!content.overridden && content.error.is_some());
+
+        assert_eq!(resolve(&current, "fanqie", CATALOG).unwrap().selector, ".chapter-item");
+    }
+
+    #[test]
+    fn test_on_html_counts_and_samples() {
+        let html = "<ul><li class='c'><a href='/a'>第一章  开端</a></li><li class='c'><a href='/b'></a></li></ul>";
+        let result = test_on_html(html, ".c a").unwrap();
+        assert_eq!(result.matched, 2);
+        assert_eq!(result.samples, vec!["第一章 开端", "/b"]);
+        assert!(test_on_html(html, "li[[").is_err());
+    }
+
+    #[test]
+    fn set_override_rejects_invalid_and_resets() {
+        let dir = std::env::temp_dir().join(format!("test_selectors_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(set_override(&dir, "qidian", CATALOG, Some("a[[")).is_err());
+        assert!(set_override(&dir, "qidian", "unknown", Some("a")).is_err());
+        set_override(&dir, "fanqie", METADATA_TITLE, Some("h1.title")).unwrap();
+        assert_eq!(read_file(&dir)["fanqie"][METADATA_TITLE], "h1.title");
+        set_override(&dir, "fanqie", METADATA_TITLE, None).unwrap();
+        assert!(read_file(&dir).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
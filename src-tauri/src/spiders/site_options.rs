@@ -0,0 +1,162 @@
+//! 按站点的爬虫窗口选项：`<workspace>/spider_options.json`。
+//!
+//! 部分站点在正文前弹出年龄确认 / 同意条款的遮罩，内容选择器被挡住，爬虫窗口要么等到超时，要么抓回遮罩页面。
+//! 这类站点不必改代码，在文件中按域名配置 `pre_actions` 即可：爬虫窗口的注入脚本在等待内容元素之前
+//! 依次找到元素、点击、等待 `wait_ms`，再进入正常的等待逻辑。找不到的元素只记入页面日志，不算失败。
+//!
+//! ```json
+//! { "example.com": { "pre_actions": [ { "selector": "#age-gate .confirm", "action": "click", "wait_ms": 800 } ] } }
+//! ```
+//!
+//! 域名同时匹配其子域名，多个键都匹配时取最长的一个。起点和番茄不需要配置。
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+pub const OPTIONS_FILE: &str = "spider_options.json";
+/// 单个前置动作之后的等待上限，避免一条配置吃掉整个抓取超时
+pub const MAX_PRE_ACTION_WAIT_MS: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreActionKind {
+    #[default]
+    Click,
+}
+
+/// 等待内容之前在页面上执行的动作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PreAction {
+    pub selector: String,
+    #[serde(default)]
+    pub action: PreActionKind,
+    /// 动作之后等待的毫秒数
+    #[serde(default)]
+    pub wait_ms: u64,
+}
+
+/// 一个站点的爬虫窗口选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SpiderOptions {
+    pub pre_actions: Vec<PreAction>,
+}
+
+type SiteOptions = BTreeMap<String, SpiderOptions>;
+
+fn current() -> &'static RwLock<SiteOptions> {
+    static OPTIONS: OnceLock<RwLock<SiteOptions>> = OnceLock::new();
+    OPTIONS.get_or_init(|| RwLock::new(SiteOptions::new()))
+}
+
+pub fn options_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(OPTIONS_FILE)
+}
+
+/// 解析配置：域名统一小写，选择器无效的动作逐条丢弃并返回说明，等待时间截到 [`MAX_PRE_ACTION_WAIT_MS`]
+fn parse(content: &str) -> Result<(SiteOptions, Vec<String>), String> {
+    let raw: SiteOptions = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let mut problems = Vec::new();
+    let mut sites = SiteOptions::new();
+    for (host, mut options) in raw {
+        let host = host.trim().trim_start_matches('.').to_lowercase();
+        options.pre_actions.retain(|a| match super::selectors::validate(&a.selector) {
+            Ok(()) => true,
+            Err(e) => {
+                problems.push(format!("{} 的前置动作 {:?} 已忽略: {}", host, a.selector, e));
+                false
+            }
+        });
+        for action in &mut options.pre_actions {
+            action.wait_ms = action.wait_ms.min(MAX_PRE_ACTION_WAIT_MS);
+        }
+        sites.insert(host, options);
+    }
+    Ok((sites, problems))
+}
+
+/// 从工作区重新加载配置（启动、切换工作区时调用）。文件缺失或无法解析时视为没有配置
+pub fn reload(workspace_root: &Path) {
+    let path = options_path(workspace_root);
+    let loaded = match fs::read_to_string(&path) {
+        Ok(content) => match parse(&content) {
+            Ok((sites, problems)) => {
+                for problem in problems {
+                    crate::log_to_file(&format!("[SpiderOptions] {}", problem));
+                }
+                sites
+            }
+            Err(e) => {
+                crate::log_to_file(&format!("[SpiderOptions] 解析 {:?} 失败，忽略站点选项: {}", path, e));
+                SiteOptions::new()
+            }
+        },
+        Err(_) => SiteOptions::new(),
+    };
+    if let Ok(mut guard) = current().write() {
+        *guard = loaded;
+    }
+}
+
+fn lookup(sites: &SiteOptions, url: &str) -> SpiderOptions {
+    let Some(host) = url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_lowercase)) else {
+        return SpiderOptions::default();
+    };
+    sites
+        .iter()
+        .filter(|(key, _)| host == **key || host.ends_with(&format!(".{}", key)))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, options)| options.clone())
+        .unwrap_or_default()
+}
+
+/// 抓取 url 时使用的选项
+pub fn for_url(url: &str) -> SpiderOptions {
+    let guard = current().read().unwrap_or_else(|e| e.into_inner());
+    lookup(&guard, url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::{Html, Selector};
+
+    const CONSENT_GATE: &str = include_str!("fixtures/consent_gate.html");
+
+    #[test]
+    fn options_match_subdomains_and_drop_invalid_actions() {
+        let (sites, problems) = parse(
+            r##"{
+                "Example.com": { "pre_actions": [
+                    { "selector": "#age-gate .confirm", "wait_ms": 60000 },
+                    { "selector": "button[[", "action": "click" }
+                ] },
+                "read.example.com": { "pre_actions": [ { "selector": ".agree", "action": "click", "wait_ms": 300 } ] }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(problems.len(), 1);
+
+        let root = lookup(&sites, "https://www.example.com/book/1");
+        assert_eq!(root.pre_actions, vec![PreAction { selector: "#age-gate .confirm".into(), action: PreActionKind::Click, wait_ms: MAX_PRE_ACTION_WAIT_MS }]);
+        assert_eq!(lookup(&sites, "https://m.read.example.com/c/2").pre_actions[0].selector, ".agree");
+        assert!(lookup(&sites, "https://notexample.com/").pre_actions.is_empty());
+        assert!(lookup(&sites, "not a url").pre_actions.is_empty());
+        assert!(parse(r#"{ "example.com": { "pre_actions": [ { "selector": "a", "action": "hover" } ] } }"#).is_err());
+    }
+
+    #[test]
+    fn bundled_consent_gate_is_reachable_by_pre_action() {
+        // 夹具页面：正文在点击遮罩上的确认按钮后才由脚本写入
+        let page = Html::parse_document(CONSENT_GATE);
+        let count = |s: &str| page.select(&Selector::parse(s).unwrap()).count();
+        let (sites, _) = parse(r##"{ "localhost": { "pre_actions": [ { "selector": "#age-gate .confirm", "wait_ms": 500 } ] } }"##).unwrap();
+        let options = lookup(&sites, "http://localhost:8080/consent_gate.html");
+        assert_eq!(options.pre_actions.len(), 1);
+        assert_eq!(count(&options.pre_actions[0].selector), 1);
+        assert_eq!(count(".chapter-content p"), 0);
+    }
+}
@@ -0,0 +1,130 @@
+//! 下载 / 扫榜流程依赖的抓取接口。
+//!
+//! `process_novel_download`、扫榜等流程只通过 [`NovelSource`] 取榜单、书籍信息、目录和正文，
+//! 真实实现 [`LiveSource`] 按平台分派到各爬虫；测试中可换成读取夹具文件的实现，
+//! 不必访问真实站点或打开 webview。
+
+use reqwest::Client;
+use std::future::Future;
+
+use super::fanqie::{self, NovelMetadata};
+use super::{qidian, AuthorWorks, CatalogChapter, ChapterSource, RankScan, RankSource, SpiderError};
+use crate::browser_spider::PageFetcher;
+
+pub trait NovelSource: Sync {
+    /// 榜单，最多取 max_entries 条（番茄榜单接口一次返回全部，由调用方截断）
+    fn fetch_rank_list(
+        &self,
+        platform: &str,
+        url: &str,
+        max_entries: usize,
+    ) -> impl Future<Output = Result<RankScan, String>> + Send;
+
+    fn fetch_metadata(
+        &self,
+        platform: &str,
+        url: &str,
+        debug_visible: bool,
+    ) -> impl Future<Output = Result<NovelMetadata, String>> + Send;
+
+    /// 作者页上的全部作品（已翻页合并）
+    fn fetch_author_works(
+        &self,
+        platform: &str,
+        url: &str,
+        debug_visible: bool,
+    ) -> impl Future<Output = Result<AuthorWorks, String>> + Send;
+
+    fn fetch_catalog(
+        &self,
+        platform: &str,
+        url: &str,
+        debug_visible: bool,
+    ) -> impl Future<Output = Result<Vec<CatalogChapter>, SpiderError>> + Send;
+
+    /// 返回 (章节页标题, 正文, 正文来源)
+    fn download_chapter(
+        &self,
+        platform: &str,
+        url: &str,
+        debug_visible: bool,
+    ) -> impl Future<Output = Result<(String, String, ChapterSource), SpiderError>> + Send;
+}
+
+fn unsupported(platform: &str) -> String {
+    format!("不支持的平台: {}", platform)
+}
+
+/// 真实站点：起点走浏览器抓取（`pages`，通常是 AppHandle），番茄走 HTTP
+pub struct LiveSource<'a, P: PageFetcher + ?Sized> {
+    pages: &'a P,
+    client: Client,
+}
+
+impl<'a, P: PageFetcher + ?Sized> LiveSource<'a, P> {
+    pub fn new(pages: &'a P) -> Self {
+        Self { pages, client: Client::new() }
+    }
+
+    /// 网络暂停时排队、阻断时直接失败，见 [`crate::network_mode`]
+    async fn gate(&self, url: &str) -> Result<(), String> {
+        crate::network_mode::gate(url, |waiting| self.pages.network_waiting(url, waiting)).await
+    }
+}
+
+impl<P: PageFetcher + ?Sized> NovelSource for LiveSource<'_, P> {
+    async fn fetch_rank_list(&self, platform: &str, url: &str, max_entries: usize) -> Result<RankScan, String> {
+        self.gate(url).await?;
+        match platform {
+            qidian::PLATFORM => Ok(RankScan {
+                entries: qidian::fetch_rank_list(self.pages, url, false, max_entries).await?,
+                source: RankSource::Html,
+            }),
+            fanqie::PLATFORM => fanqie::fetch_rank_list(&self.client, url).await,
+            other => Err(unsupported(other)),
+        }
+    }
+
+    async fn fetch_metadata(&self, platform: &str, url: &str, debug_visible: bool) -> Result<NovelMetadata, String> {
+        self.gate(url).await?;
+        match platform {
+            qidian::PLATFORM => qidian::fetch_novel_metadata(&self.client, url, self.pages, debug_visible).await,
+            fanqie::PLATFORM => fanqie::fetch_novel_metadata(&self.client, url).await,
+            other => Err(unsupported(other)),
+        }
+    }
+
+    async fn fetch_author_works(&self, platform: &str, url: &str, debug_visible: bool) -> Result<AuthorWorks, String> {
+        self.gate(url).await?;
+        match platform {
+            qidian::PLATFORM => qidian::fetch_author_works(self.pages, url, debug_visible).await,
+            other => Err(unsupported(other)),
+        }
+    }
+
+    async fn fetch_catalog(&self, platform: &str, url: &str, debug_visible: bool) -> Result<Vec<CatalogChapter>, SpiderError> {
+        self.gate(url).await.map_err(SpiderError::Other)?;
+        match platform {
+            qidian::PLATFORM => qidian::fetch_catalog(self.pages, url, debug_visible).await,
+            fanqie::PLATFORM => fanqie::fetch_catalog(&self.client, url).await,
+            other => Err(SpiderError::Other(unsupported(other))),
+        }
+    }
+
+    async fn download_chapter(
+        &self,
+        platform: &str,
+        url: &str,
+        debug_visible: bool,
+    ) -> Result<(String, String, ChapterSource), SpiderError> {
+        self.gate(url).await.map_err(SpiderError::Other)?;
+        match platform {
+            qidian::PLATFORM => {
+                let (title, content) = qidian::download_chapter(self.pages, url, debug_visible).await?;
+                Ok((title, content, ChapterSource::Html))
+            }
+            fanqie::PLATFORM => Ok(fanqie::download_chapter(&self.client, url).await?),
+            other => Err(SpiderError::Other(unsupported(other))),
+        }
+    }
+}
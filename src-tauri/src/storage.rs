@@ -0,0 +1,581 @@
+//! 文件存储底层工具：原子写入 + 按小说目录粒度的锁。
+//!
+//! 所有对 `info.json`、`chapters.json` 这类"读-改-写"文件的修改都应先通过 [`novel_lock`] 拿到
+//! 该小说目录的锁，在锁内重新读取、修改，再用 [`write_atomic`] 落盘，避免并发写入互相覆盖或留下半截文件。
+//!
+//! 工作区放在 OneDrive / iCloud 等同步目录时，同步客户端会短暂独占文件，写入偶尔报
+//! "拒绝访问" / 共享冲突：[`write_atomic`]、[`append`] 和 [`read_to_string`] 遇到这类错误会退避重试几次。
+//! 未下载到本地的云端占位文件读取时直接报"文件尚未从云端下载"，不把空内容当成文件内容解析。
+//!
+//! 这里的函数都经过 [`paths::long_path`]：Windows 上超过 260 字符的路径自动加扩展长度前缀。
+//!
+//! 章节、分析结果和导出这类文本文件经 [`write_text`]（流式输出经 [`TextWriter`]）写出：换行统一为
+//! 设置中的 `text_files.line_ending`（缺省 `\n`），开启 `text_files.bom` 时 `.txt` / `.csv` 开头加
+//! UTF-8 BOM。[`read_to_string`] 读取时去掉 BOM，读写往返内容不变。
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 原子写入：先写同目录下的临时文件并 fsync，再 rename 覆盖目标文件。
+/// 进程中途崩溃时目标文件要么是旧内容，要么是完整的新内容。文件被同步客户端占用时重试。
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let path = &paths::long_path(path);
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+    let tmp_path = dir.join(format!(
+        ".{}.tmp-{}-{}",
+        file_name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let result = with_retry(|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// 文件被占用时的重试次数（不含第一次）和首次退避时间，之后每次翻倍
+const CONTENTION_RETRIES: u32 = 4;
+const CONTENTION_BACKOFF: Duration = Duration::from_millis(50);
+
+/// 是否为文件被其他进程（同步客户端、杀毒软件）占用导致的错误
+pub fn is_contention(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::PermissionDenied {
+        return true;
+    }
+    // Windows: ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION；Unix: EBUSY
+    #[cfg(windows)]
+    const BUSY_CODES: &[i32] = &[32, 33];
+    #[cfg(not(windows))]
+    const BUSY_CODES: &[i32] = &[16];
+    err.raw_os_error().is_some_and(|code| BUSY_CODES.contains(&code))
+}
+
+/// 退避等待。异步命令里调用时先把当前 tokio 工作线程上的其他任务交给别的线程，不阻塞它们
+fn backoff(delay: Duration) {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| std::thread::sleep(delay))
+        }
+        _ => std::thread::sleep(delay),
+    }
+}
+
+/// 执行文件操作，遇到占用类错误时退避重试，其余错误直接返回
+pub fn with_retry<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = CONTENTION_BACKOFF;
+    for _ in 0..CONTENTION_RETRIES {
+        match op() {
+            Err(e) if is_contention(&e) => {
+                backoff(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+/// 追加写入（日志），文件被占用时重试
+pub fn append(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let path = paths::long_path(path);
+    with_retry(|| fs::OpenOptions::new().create(true).append(true).open(&path)?.write_all(bytes))
+}
+
+/// 创建目录（含上级目录）
+pub fn create_dir_all(path: &Path) -> io::Result<()> {
+    fs::create_dir_all(paths::long_path(path))
+}
+
+/// 重命名文件或目录，被占用时重试
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    let (from, to) = (paths::long_path(from), paths::long_path(to));
+    with_retry(|| fs::rename(&from, &to))
+}
+
+/// 删除文件或整个目录，被占用时重试
+pub fn remove(path: &Path) -> io::Result<()> {
+    let path = paths::long_path(path);
+    with_retry(|| if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) })
+}
+
+/// 云端占位文件：Windows 上带"脱机 / 访问时回调"属性且尚无本地内容的文件，或 macOS iCloud
+/// 以 `.<文件名>.icloud` 代替原文件的情况
+pub fn is_cloud_placeholder(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+        const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+        const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+        if let Ok(meta) = fs::metadata(paths::long_path(path)) {
+            let attrs = meta.file_attributes();
+            return attrs & (FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+                || (attrs & FILE_ATTRIBUTE_OFFLINE != 0 && meta.len() == 0);
+        }
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if !path.exists() => {
+            dir.join(format!(".{}.icloud", name.to_string_lossy())).is_file()
+        }
+        _ => false,
+    }
+}
+
+/// 读取文本文件：云端占位文件报"文件尚未从云端下载"，文件被占用时重试
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    if is_cloud_placeholder(path) {
+        return Err(io::Error::other(format!("文件尚未从云端下载: {}", path.display())));
+    }
+    let path = paths::long_path(path);
+    let mut text = with_retry(|| fs::read_to_string(&path))?;
+    if text.starts_with(BOM) {
+        text.drain(..BOM.len());
+    }
+    Ok(text)
+}
+
+/// UTF-8 BOM
+pub const BOM: &str = "\u{feff}";
+
+/// 写出文本文件时使用的换行符
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// 文本文件的写出格式，保存在设置的 `text_files` 中，启动和保存设置时经 [`set_text_format`] 生效
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TextFormat {
+    pub line_ending: LineEnding,
+    /// `.txt` / `.csv` 开头写 UTF-8 BOM，部分 Windows 编辑器靠它识别短文件的编码
+    pub bom: bool,
+}
+
+static TEXT_FORMAT: Mutex<TextFormat> = Mutex::new(TextFormat { line_ending: LineEnding::Lf, bom: false });
+
+pub fn set_text_format(format: TextFormat) {
+    *TEXT_FORMAT.lock().unwrap_or_else(|e| e.into_inner()) = format;
+}
+
+pub fn text_format() -> TextFormat {
+    *TEXT_FORMAT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 去掉开头的 BOM
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+/// 把 `\r\n`、单独的 `\r` 和 `\n` 统一为 ending
+pub fn normalize_newlines(text: &str, ending: LineEnding) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                out.push_str(ending.as_str());
+            }
+            '\n' => out.push_str(ending.as_str()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 开启 BOM 时是否给该文件加 BOM：`.txt` / `.csv`，`.part` 临时文件看去掉后缀后的扩展名
+fn bom_applies(path: &Path) -> bool {
+    let path = match path.extension() {
+        Some(ext) if ext == "part" => Path::new(path.file_stem().unwrap_or_default()),
+        _ => path,
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("csv"))
+}
+
+/// 按 format 编码要写出的文本：去掉原有的 BOM，统一换行，再按扩展名决定是否加 BOM
+pub fn encode_text_as(path: &Path, text: &str, format: TextFormat) -> Vec<u8> {
+    let body = normalize_newlines(strip_bom(text), format.line_ending);
+    let mut bytes = Vec::with_capacity(BOM.len() + body.len());
+    if format.bom && bom_applies(path) {
+        bytes.extend_from_slice(BOM.as_bytes());
+    }
+    bytes.extend_from_slice(body.as_bytes());
+    bytes
+}
+
+/// 按当前设置编码要写出的文本。需要记录内容哈希的调用方（章节索引）对编码后的字节计算哈希
+pub fn encode_text(path: &Path, text: &str) -> Vec<u8> {
+    encode_text_as(path, text, text_format())
+}
+
+/// 写文本文件（章节、分析结果、导出）：按设置统一换行和 BOM 后 [`write_atomic`]
+pub fn write_text(path: &Path, text: &str) -> io::Result<()> {
+    write_atomic(path, &encode_text(path, text))
+}
+
+/// 流式写文本：边写边统一换行，创建时按需写 BOM。`\r\n` 被拆在两次写入之间时也只输出一个换行
+pub struct TextWriter<W: Write> {
+    inner: W,
+    ending: LineEnding,
+    pending_cr: bool,
+}
+
+impl<W: Write> TextWriter<W> {
+    pub fn new(mut inner: W, ending: LineEnding, bom: bool) -> io::Result<Self> {
+        if bom {
+            inner.write_all(BOM.as_bytes())?;
+        }
+        Ok(Self { inner, ending, pending_cr: false })
+    }
+
+    /// 按当前设置为 path 创建
+    pub fn for_path(inner: W, path: &Path) -> io::Result<Self> {
+        let format = text_format();
+        Self::new(inner, format.line_ending, format.bom && bom_applies(path))
+    }
+
+    /// 写出挂起的 `\r` 并交还内层 writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        if self.pending_cr {
+            self.inner.write_all(self.ending.as_str().as_bytes())?;
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for TextWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let newline = self.ending.as_str().as_bytes();
+        let mut rest = buf;
+        if self.pending_cr && !rest.is_empty() {
+            self.pending_cr = false;
+            self.inner.write_all(newline)?;
+            if rest[0] == b'\n' {
+                rest = &rest[1..];
+            }
+        }
+        while let Some(pos) = rest.iter().position(|&b| b == b'\r' || b == b'\n') {
+            self.inner.write_all(&rest[..pos])?;
+            if rest[pos] == b'\r' && pos + 1 == rest.len() {
+                self.pending_cr = true;
+                return Ok(buf.len());
+            }
+            self.inner.write_all(newline)?;
+            let crlf = rest[pos] == b'\r' && rest[pos + 1] == b'\n';
+            rest = &rest[pos + if crlf { 2 } else { 1 }..];
+        }
+        self.inner.write_all(rest)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 常见同步盘在路径中的特征目录名
+const CLOUD_SYNC_MARKERS: &[(&str, &str)] = &[
+    ("onedrive", "OneDrive"),
+    ("icloud drive", "iCloud"),
+    ("mobile documents", "iCloud"),
+    ("com~apple~clouddocs", "iCloud"),
+    ("dropbox", "Dropbox"),
+    ("google drive", "Google Drive"),
+    ("googledrive", "Google Drive"),
+    ("nutstore", "坚果云"),
+    ("坚果云", "坚果云"),
+    ("百度网盘", "百度网盘"),
+    ("baidunetdisk", "百度网盘"),
+];
+
+/// 路径看起来位于同步盘目录下时返回同步服务名（按目录名判断，只作提示）
+pub fn cloud_sync_provider(path: &Path) -> Option<&'static str> {
+    path.components().find_map(|c| {
+        let name = c.as_os_str().to_string_lossy().to_lowercase();
+        CLOUD_SYNC_MARKERS.iter().find(|(marker, _)| name.starts_with(marker)).map(|(_, provider)| *provider)
+    })
+}
+
+/// 内容哈希（FNV-1a 64，16 位十六进制）。只用于判断内容是否变化，不用于安全场景。
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hasher = ContentHasher::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// 分块计算的 [`content_hash`]，用于不便整块读入内存的大文件
+#[derive(Debug, Clone, Copy)]
+pub struct ContentHasher(u64);
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        ContentHasher(0xcbf29ce484222325)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type LockRegistry = Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>;
+
+fn lock_registry() -> &'static LockRegistry {
+    static REGISTRY: OnceLock<LockRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 规范化路径作为锁的 key：目录还不存在时规范化最近一层已存在的上级，再接上其余部分，
+/// 目录创建前后、不同写法都得到同一个键，见 [`novel_lock`]
+pub(crate) fn canonical_key(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(paths::long_path(path)) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => canonical_key(parent).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// 获取某本小说目录对应的锁（同一目录的不同写法会映射到同一把锁）。
+///
+/// 同步代码和异步代码共用：锁内只做文件读写，不能跨 `.await` 持有，也不能在持有时再次获取同一把锁。
+pub fn novel_lock(novel_dir: &Path) -> Arc<Mutex<()>> {
+    let key = canonical_key(novel_dir);
+    let mut registry = match lock_registry().lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    registry.entry(key).or_default().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_content_and_leaves_no_temp_files() {
+        let dir = std::env::temp_dir().join(format!("test_write_atomic_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("info.json");
+
+        write_atomic(&target, b"old").unwrap();
+        write_atomic(&target, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+
+        let leftovers = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(leftovers, 1, "临时文件应已被 rename 掉");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn text_is_normalized_on_write_and_bom_is_stripped_on_read() {
+        let crlf = TextFormat { line_ending: LineEnding::Crlf, bom: true };
+        let bytes = encode_text_as(Path::new("01.txt"), "\u{feff}a\r\nb\rc\n", crlf);
+        assert_eq!(bytes, "\u{feff}a\r\nb\r\nc\r\n".as_bytes());
+        assert_eq!(encode_text_as(Path::new("01.md"), "a\r\n", crlf), b"a\r\n");
+        assert_eq!(encode_text_as(Path::new("out.csv.part"), "a", crlf), "\u{feff}a".as_bytes());
+        assert_eq!(encode_text_as(Path::new("01.txt"), "\u{feff}a\r\nb\r", TextFormat::default()), b"a\nb\n");
+
+        let dir = std::env::temp_dir().join(format!("test_write_text_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("01.txt");
+        write_atomic(&target, &bytes).unwrap();
+        assert_eq!(read_to_string(&target).unwrap(), "a\r\nb\r\nc\r\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn text_writer_joins_crlf_split_across_writes() {
+        let mut out = TextWriter::new(Vec::new(), LineEnding::Lf, false).unwrap();
+        for chunk in ["a\r", "\nb\r", "c\r\n\r", ""] {
+            out.write_all(chunk.as_bytes()).unwrap();
+        }
+        assert_eq!(out.into_inner().unwrap(), b"a\nb\nc\n\n");
+
+        let mut out = TextWriter::new(Vec::new(), LineEnding::Crlf, true).unwrap();
+        out.write_all(b"x\ny\r").unwrap();
+        assert_eq!(out.into_inner().unwrap(), "\u{feff}x\r\ny\r\n".as_bytes());
+    }
+
+    #[test]
+    fn contention_errors_are_retried_others_are_not() {
+        let mut attempts = 0;
+        let result = with_retry(|| {
+            attempts += 1;
+            if attempts < 3 { Err(io::Error::from(io::ErrorKind::PermissionDenied)) } else { Ok(attempts) }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result: io::Result<()> = with_retry(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn icloud_stub_is_reported_as_placeholder() {
+        let dir = std::env::temp_dir().join(format!("test_cloud_placeholder_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".info.json.icloud"), b"stub").unwrap();
+        let err = read_to_string(&dir.join("info.json")).unwrap_err();
+        assert!(err.to_string().contains("文件尚未从云端下载"));
+        assert_eq!(read_to_string(&dir.join("other.json")).unwrap_err().kind(), io::ErrorKind::NotFound);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sync_folders_are_recognized_by_path() {
+        assert_eq!(cloud_sync_provider(Path::new("/Users/a/Library/Mobile Documents/com~apple~CloudDocs/ws")), Some("iCloud"));
+        assert_eq!(cloud_sync_provider(Path::new("C:/Users/a/OneDrive - Contoso/小说")), Some("OneDrive"));
+        assert_eq!(cloud_sync_provider(Path::new("/home/a/novels")), None);
+    }
+
+    /// 另一个线程独占打开目标文件一段时间，写入应在对方释放后重试成功
+    #[cfg(windows)]
+    #[test]
+    fn write_succeeds_after_exclusive_handle_is_released() {
+        use std::os::windows::fs::OpenOptionsExt;
+        let dir = std::env::temp_dir().join(format!("test_contention_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("info.json");
+        fs::write(&target, b"old").unwrap();
+
+        let held = fs::OpenOptions::new().read(true).share_mode(0).open(&target).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let holder = std::thread::spawn(move || {
+            tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(120));
+            drop(held);
+        });
+        rx.recv().unwrap();
+        write_atomic(&target, b"new").unwrap();
+        append(&target, b"!").unwrap();
+        holder.join().unwrap();
+        assert_eq!(read_to_string(&target).unwrap(), "new!");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// 工作区 + 书名 + 文件名接近 / 超过 260 字符时，读写删除都走 long_path
+    #[test]
+    fn near_limit_paths_round_trip_through_helpers() {
+        let root = std::env::temp_dir().join(format!("test_long_path_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut dir = root.clone();
+        while dir.to_string_lossy().chars().count() < paths::WINDOWS_MAX_PATH + 20 {
+            dir.push("很长的小说标题".repeat(6));
+        }
+        create_dir_all(&dir).unwrap();
+        let target = dir.join("01.txt");
+        write_atomic(&target, "正文".as_bytes()).unwrap();
+        append(&target, "追加".as_bytes()).unwrap();
+        assert_eq!(read_to_string(&target).unwrap(), "正文追加");
+
+        let renamed = dir.join("02.txt");
+        rename(&target, &renamed).unwrap();
+        assert_eq!(read_to_string(&target).unwrap_err().kind(), io::ErrorKind::NotFound);
+        remove(&renamed).unwrap();
+        assert!(!paths::long_path(&renamed).exists());
+        remove(&root).unwrap();
+        assert!(!root.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn contention_backoff_does_not_block_other_tasks() {
+        let ticker = tokio::spawn(async {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        // 唯一的工作线程上重试：退避期间计时任务仍应跑完
+        let attempts = tokio::spawn(async {
+            let mut attempts = 0;
+            let result: io::Result<()> = with_retry(|| {
+                attempts += 1;
+                Err(io::Error::from(io::ErrorKind::PermissionDenied))
+            });
+            assert!(result.is_err());
+            attempts
+        })
+        .await
+        .unwrap();
+        assert_eq!(attempts, CONTENTION_RETRIES + 1);
+        assert!(ticker.is_finished(), "退避期间其他任务应能继续运行");
+    }
+
+    #[test]
+    fn novel_lock_is_shared_for_equivalent_paths() {
+        let dir = std::env::temp_dir().join(format!("test_novel_lock_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = novel_lock(&dir);
+        let b = novel_lock(&dir.join("."));
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // 小说目录还没创建：上级的不同写法、创建前后都是同一把锁
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let new_dir = dir.join("新书");
+        let before = novel_lock(&dir.join("sub").join("..").join("新书"));
+        assert!(Arc::ptr_eq(&before, &novel_lock(&new_dir)));
+        fs::create_dir_all(&new_dir).unwrap();
+        assert!(Arc::ptr_eq(&before, &novel_lock(&new_dir)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
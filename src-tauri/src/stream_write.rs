@@ -0,0 +1,179 @@
+//! 导出类功能共用的流式读写：输出文件边生成边写，输入文件经固定大小的缓冲区逐块拷贝，
+//! 不把整本书（或整套分析结果）读进内存。整书导出、合并分析报告和书库目录导出都走这里，
+//! 峰值内存只取决于 [`BUFFER_BYTES`]，与书的大小无关。
+//!
+//! 文本输出（整书 txt、合并报告）经 [`create_text`] 按设置统一换行和 BOM，输入文件开头的 BOM 在 [`open`] 时跳过。
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{paths, storage};
+
+/// 读写缓冲区大小
+pub const BUFFER_BYTES: usize = 64 * 1024;
+const PART_SUFFIX: &str = ".part";
+
+/// 创建输出文件（含父目录）
+pub fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    if let Some(dir) = path.parent() {
+        storage::create_dir_all(dir)?;
+    }
+    Ok(BufWriter::with_capacity(BUFFER_BYTES, File::create(paths::long_path(path))?))
+}
+
+/// 文本输出：写入时统一换行，见 [`storage::TextWriter`]
+pub type TextOutput = storage::TextWriter<BufWriter<File>>;
+
+/// 创建文本输出文件，按设置写 BOM
+pub fn create_text(path: &Path) -> io::Result<TextOutput> {
+    storage::TextWriter::for_path(create(path)?, path)
+}
+
+/// 打开输入文件：云端占位文件报错，文件被占用时重试，开头的 BOM 不返回给调用方
+pub fn open(path: &Path) -> io::Result<BufReader<File>> {
+    if storage::is_cloud_placeholder(path) {
+        return Err(io::Error::other(format!("文件尚未从云端下载: {}", path.display())));
+    }
+    let path = paths::long_path(path);
+    let file = storage::with_retry(|| File::open(&path))?;
+    let mut reader = BufReader::with_capacity(BUFFER_BYTES, file);
+    if reader.fill_buf()?.starts_with(storage::BOM.as_bytes()) {
+        reader.consume(storage::BOM.len());
+    }
+    Ok(reader)
+}
+
+/// 写盘并落盘
+pub fn finish(mut out: BufWriter<File>) -> io::Result<()> {
+    out.flush()?;
+    out.get_ref().sync_all()
+}
+
+/// 写出挂起的换行后写盘并落盘
+pub fn finish_text(out: TextOutput) -> io::Result<()> {
+    finish(out.into_inner()?)
+}
+
+/// 先写 `<dest>.part`，write 成功后改名为 dest；失败时删除半成品
+pub fn write_file(dest: &Path, write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+    let mut part = dest.as_os_str().to_owned();
+    part.push(PART_SUFFIX);
+    let part = PathBuf::from(part);
+    let result = create(&part).and_then(|mut out| {
+        write(&mut out)?;
+        finish(out)
+    });
+    match result.and_then(|()| storage::rename(&part, dest)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
+fn read_short_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.by_ref().take(BUFFER_BYTES as u64).read_line(&mut line)?;
+    Ok(line)
+}
+
+/// 读掉章节文件的 标题 / 链接 / 分隔线 头部，返回标题；不是章节文件格式时不消耗任何内容并返回 None
+pub fn read_chapter_header(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    if !reader.fill_buf()?.starts_with("标题:".as_bytes()) {
+        return Ok(None);
+    }
+    let title = read_short_line(reader)?;
+    let title = title.trim_end().strip_prefix("标题:").unwrap_or_default().trim().to_string();
+    read_short_line(reader)?; // 链接
+    read_short_line(reader)?; // 分隔线
+    Ok(Some(title))
+}
+
+/// 读掉开头的 front matter（`---` 包围的块），没有时不消耗任何内容
+pub fn skip_front_matter(reader: &mut impl BufRead) -> io::Result<()> {
+    let head = reader.fill_buf()?;
+    if !head.starts_with(b"---\n") && !head.starts_with(b"---\r\n") {
+        return Ok(());
+    }
+    read_short_line(reader)?;
+    loop {
+        let line = read_short_line(reader)?;
+        if line.is_empty() || line.trim_end() == "---" {
+            return Ok(());
+        }
+    }
+}
+
+/// 把 reader 的剩余内容拷贝到 out，去掉开头和结尾的空白（ASCII 空白；行首的全角缩进保留）。
+/// 每次只处理一个缓冲区，结尾的空白暂存到看见下一个非空白字节为止，暂存超过一个缓冲区时直接写出。
+pub fn copy_trimmed(reader: &mut impl BufRead, out: &mut impl Write) -> io::Result<u64> {
+    let mut written = 0u64;
+    let mut started = false;
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(written);
+        }
+        let len = chunk.len();
+        let mut data = chunk;
+        if !started {
+            match data.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(start) => {
+                    data = &data[start..];
+                    started = true;
+                }
+                None => {
+                    reader.consume(len);
+                    continue;
+                }
+            }
+        }
+        match data.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(end) => {
+                out.write_all(&pending)?;
+                out.write_all(&data[..=end])?;
+                written += (pending.len() + end + 1) as u64;
+                pending.clear();
+                pending.extend_from_slice(&data[end + 1..]);
+            }
+            None => pending.extend_from_slice(data),
+        }
+        if pending.len() > BUFFER_BYTES {
+            out.write_all(&pending)?;
+            written += pending.len() as u64;
+            pending.clear();
+        }
+        reader.consume(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn headers_are_consumed_and_body_is_trimmed_across_chunks() {
+        let text = crate::library::render_chapter_file("第1章 下山", "https://example.com/1", "\n　　正文第一段\n\n　　第二段\n\n\n");
+        let mut reader = BufReader::with_capacity(8, Cursor::new(text.into_bytes()));
+        assert_eq!(read_chapter_header(&mut reader).unwrap().as_deref(), Some("第1章 下山"));
+        let mut out = Vec::new();
+        copy_trimmed(&mut reader, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "　　正文第一段\n\n　　第二段");
+
+        let mut plain = Cursor::new(b"no header".to_vec());
+        assert_eq!(read_chapter_header(&mut plain).unwrap(), None);
+        let mut out = Vec::new();
+        copy_trimmed(&mut plain, &mut out).unwrap();
+        assert_eq!(out, b"no header");
+
+        let mut md = Cursor::new(b"---\nmodel: m\nsources:\n  - file: 01.txt\n---\n\n# \xe7\xbb\x86\xe7\xba\xb2\n".to_vec());
+        skip_front_matter(&mut md).unwrap();
+        let mut out = Vec::new();
+        copy_trimmed(&mut md, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "# 细纲");
+    }
+}
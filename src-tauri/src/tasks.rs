@@ -0,0 +1,106 @@
+//! 后台任务登记：边下载边分析、整书导出等长任务在这里登记取消标记，
+//! `list_active_tasks` 列出进行中的任务，`abort_all_tasks` 一并取消。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    DownloadAnalysis,
+    Export,
+    Redownload,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ActiveTask {
+    pub task_id: String,
+    pub kind: TaskKind,
+    /// 显示用的说明（书名等）
+    pub label: String,
+    pub started_at: String,
+    pub cancelling: bool,
+}
+
+struct Entry {
+    task: ActiveTask,
+    cancel: Arc<AtomicBool>,
+}
+
+fn tasks() -> &'static Mutex<HashMap<String, Entry>> {
+    static TASKS: OnceLock<Mutex<HashMap<String, Entry>>> = OnceLock::new();
+    TASKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 登记任务并返回其取消标记
+pub fn register(task_id: &str, kind: TaskKind, label: &str) -> Arc<AtomicBool> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let task = ActiveTask {
+        task_id: task_id.to_string(),
+        kind,
+        label: label.to_string(),
+        started_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        cancelling: false,
+    };
+    let mut map = tasks().lock().unwrap_or_else(|e| e.into_inner());
+    map.insert(task_id.to_string(), Entry { task, cancel: cancel.clone() });
+    cancel
+}
+
+/// 任务结束（完成、失败或取消）后移除
+pub fn unregister(task_id: &str) {
+    let mut map = tasks().lock().unwrap_or_else(|e| e.into_inner());
+    map.remove(task_id);
+}
+
+/// 取消任务，任务不存在（已结束）时返回 false
+pub fn cancel(task_id: &str) -> bool {
+    let map = tasks().lock().unwrap_or_else(|e| e.into_inner());
+    match map.get(task_id) {
+        Some(entry) => {
+            entry.cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 取消所有登记的任务，返回数量
+pub fn cancel_all() -> usize {
+    let map = tasks().lock().unwrap_or_else(|e| e.into_inner());
+    for entry in map.values() {
+        entry.cancel.store(true, Ordering::Relaxed);
+    }
+    map.len()
+}
+
+/// 进行中的任务，按开始时间排序
+pub fn list() -> Vec<ActiveTask> {
+    let map = tasks().lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<ActiveTask> = map
+        .values()
+        .map(|e| ActiveTask { cancelling: e.cancel.load(Ordering::Relaxed), ..e.task.clone() })
+        .collect();
+    list.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.task_id.cmp(&b.task_id)));
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_sets_flag_until_task_unregisters() {
+        let flag = register("test_task", TaskKind::Export, "书");
+        let listed = list().into_iter().find(|t| t.task_id == "test_task").unwrap();
+        assert_eq!((listed.kind, listed.cancelling), (TaskKind::Export, false));
+        assert!(cancel("test_task"));
+        assert!(flag.load(Ordering::Relaxed));
+        assert!(list().iter().any(|t| t.task_id == "test_task" && t.cancelling));
+        unregister("test_task");
+        assert!(!cancel("test_task"));
+        assert!(!list().iter().any(|t| t.task_id == "test_task"));
+    }
+}
@@ -0,0 +1,77 @@
+//! 测试用计数分配器：只统计调用 [`measure`] 的线程在闭包执行期间的分配，其他并行测试不受影响。
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAlloc;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+thread_local! {
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+    static TOTAL: Cell<usize> = const { Cell::new(0) };
+}
+
+fn track(delta: isize, allocated: usize) {
+    // 线程退出阶段 thread_local 可能已销毁，此时不计数
+    let _ = ACTIVE.try_with(|active| {
+        if !active.get() {
+            return;
+        }
+        CURRENT.with(|c| {
+            let now = c.get() + delta;
+            c.set(now);
+            PEAK.with(|p| p.set(p.get().max(now)));
+        });
+        TOTAL.with(|t| t.set(t.get() + allocated));
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            track(layout.size() as isize, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        track(-(layout.size() as isize), 0);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            track(new_size as isize - layout.size() as isize, new_size.saturating_sub(layout.size()));
+        }
+        new_ptr
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AllocStats {
+    /// 闭包执行期间相对起点的最高占用
+    pub peak_bytes: usize,
+    /// 闭包执行期间累计分配的字节数
+    pub total_bytes: usize,
+}
+
+/// 运行 f 并统计当前线程的内存分配
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    CURRENT.with(|c| c.set(0));
+    PEAK.with(|p| p.set(0));
+    TOTAL.with(|t| t.set(0));
+    ACTIVE.with(|a| a.set(true));
+    let result = f();
+    ACTIVE.with(|a| a.set(false));
+    let stats = AllocStats {
+        peak_bytes: PEAK.with(Cell::get).max(0) as usize,
+        total_bytes: TOTAL.with(Cell::get),
+    };
+    (result, stats)
+}
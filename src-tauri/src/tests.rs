@@ -22,6 +22,7 @@ fn test_pipeline_e2e() {
         api_base: std::env::var("AI_API_BASE").unwrap_or_else(|_| "http://127.0.0.1:8317/v1".into()),
         api_key: std::env::var("AI_API_KEY").unwrap_or_else(|_| "sk-test".into()),
         model: std::env::var("AI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".into()),
+        ..Default::default()
     }))));
 
     // 3. 初始化数据库（测试用独立文件）
@@ -37,7 +38,7 @@ fn test_pipeline_e2e() {
     let rt = tokio::runtime::Runtime::new().expect("创建 tokio runtime 失败");
     let result = rt.block_on(async {
         crate::analysis_engine::run_full_analysis_pipeline(
-            &handle, rank_url, platform, &project_root,
+            handle, rank_url, platform, &project_root,
             crate::analysis_engine::PipelineMode::Rank,
         ).await
     });
@@ -111,3 +112,503 @@ fn test_pipeline_e2e() {
 
     println!("\n🎉 E2E 管线测试全部通过!");
 }
+
+#[test]
+fn log_text_escapes_control_characters() {
+    assert_eq!(crate::escape_log_text("第一章\n[ERROR] 伪造\u{1b}[31m"), "第一章\\n[ERROR] 伪造\\u{1b}[31m");
+    assert_eq!(crate::escape_log_text("普通日志"), "普通日志");
+}
+
+#[test]
+fn log_text_escapes_bidi_and_invisible_format_characters() {
+    assert_eq!(crate::escape_log_text("第一章\u{202e}txt.exe"), "第一章\\u{202e}txt.exe");
+    assert_eq!(crate::escape_log_text("\u{feff}标\u{200b}题\u{2066}"), "\\u{feff}标\\u{200b}题\\u{2066}");
+}
+
+// ========================================================================
+//  离线流程测试：下载 / 扫榜经 NovelSource、PageFetcher 的夹具实现运行，不访问真实站点、不开 webview
+// ========================================================================
+
+use crate::browser_spider::{FetchedPage, PageFetcher};
+use crate::events::tests::MockSink;
+use crate::spiders::fanqie::NovelMetadata;
+use crate::spiders::{AuthorWork, AuthorWorks, CatalogChapter, ChapterSource, LiveSource, NovelSource, RankEntry, RankScan, RankSource, SpiderError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FAKE_NOVEL: &str = include_str!("spiders/fixtures/fake_novel.json");
+
+#[derive(serde::Deserialize)]
+struct FakeNovel {
+    metadata: NovelMetadata,
+    chapters: Vec<FakeChapter>,
+}
+
+#[derive(serde::Deserialize)]
+struct FakeChapter {
+    title: String,
+    url: String,
+    #[serde(default)]
+    is_vip: bool,
+    /// 第 n 次请求返回第 n 条，之后一直返回最后一条
+    responses: Vec<FakeResponse>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+struct FakeResponse {
+    title: Option<String>,
+    content: Option<String>,
+    /// 超时类错误
+    error: Option<String>,
+    /// 跳转到的非章节页面
+    unavailable: Option<String>,
+}
+
+/// 按 fake_novel.json 回答的抓取源，记录每章的请求次数
+struct FixtureSource {
+    novel: FakeNovel,
+    attempts: Mutex<HashMap<String, usize>>,
+    catalog_fetches: AtomicUsize,
+}
+
+impl FixtureSource {
+    fn load() -> Self {
+        FixtureSource {
+            novel: serde_json::from_str(FAKE_NOVEL).unwrap(),
+            attempts: Mutex::new(HashMap::new()),
+            catalog_fetches: AtomicUsize::new(0),
+        }
+    }
+
+    fn attempts(&self, url: &str) -> usize {
+        self.attempts.lock().unwrap().get(url).copied().unwrap_or(0)
+    }
+}
+
+impl NovelSource for FixtureSource {
+    async fn fetch_rank_list(&self, _platform: &str, _url: &str, _max_entries: usize) -> Result<RankScan, String> {
+        let meta = &self.novel.metadata;
+        let entry = RankEntry { position: 1, title: meta.title.clone(), url: meta.url.clone(), score: None, author: None };
+        Ok(RankScan { entries: vec![entry], source: RankSource::Api })
+    }
+
+    async fn fetch_metadata(&self, _platform: &str, _url: &str, _debug_visible: bool) -> Result<NovelMetadata, String> {
+        Ok(self.novel.metadata.clone())
+    }
+
+    /// 夹具之书之外另有一部目录取不到的作品
+    async fn fetch_author_works(&self, _platform: &str, _url: &str, _debug_visible: bool) -> Result<AuthorWorks, String> {
+        let meta = &self.novel.metadata;
+        let works = vec![
+            AuthorWork { title: meta.title.clone(), url: meta.url.clone(), status: Some("连载".to_string()) },
+            AuthorWork { title: "下架之书".to_string(), url: "https://fake.test/book/missing/".to_string(), status: None },
+        ];
+        Ok(AuthorWorks { author: Some("夹具作者".to_string()), works })
+    }
+
+    async fn fetch_catalog(&self, _platform: &str, url: &str, _debug_visible: bool) -> Result<Vec<CatalogChapter>, SpiderError> {
+        if url.contains("/missing/") {
+            return Err(SpiderError::Other(format!("目录为空: {}", url)));
+        }
+        if url.contains("/removed/") {
+            return Err(SpiderError::NovelRemoved { url: url.to_string(), reason: "页面提示“作品不存在”".to_string() });
+        }
+        self.catalog_fetches.fetch_add(1, Ordering::SeqCst);
+        // 模拟较慢的目录页，让并发的调用方有机会同时未命中
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        Ok(self.novel.chapters.iter().map(|c| CatalogChapter::new(&c.title, c.url.clone(), c.is_vip)).collect())
+    }
+
+    async fn download_chapter(
+        &self,
+        _platform: &str,
+        url: &str,
+        _debug_visible: bool,
+    ) -> Result<(String, String, ChapterSource), SpiderError> {
+        let chapter = self.novel.chapters.iter().find(|c| c.url == url).ok_or_else(|| SpiderError::Other(url.to_string()))?;
+        let attempt = {
+            let mut attempts = self.attempts.lock().unwrap();
+            let n = attempts.entry(url.to_string()).or_insert(0);
+            *n += 1;
+            *n - 1
+        };
+        let response = chapter.responses[attempt.min(chapter.responses.len() - 1)].clone();
+        if let Some(e) = response.error {
+            return Err(SpiderError::Timeout(e));
+        }
+        if let Some(final_url) = response.unavailable {
+            return Err(SpiderError::ChapterUnavailable { url: url.to_string(), final_url });
+        }
+        Ok((response.title.unwrap_or_default(), response.content.unwrap_or_default(), ChapterSource::Api))
+    }
+}
+
+/// 按 URL 返回夹具页面的浏览器抓取，记录请求过的 URL
+struct FixturePages {
+    pages: HashMap<&'static str, &'static str>,
+    requested: Mutex<Vec<String>>,
+}
+
+impl PageFetcher for FixturePages {
+    async fn fetch_page(&self, url: &str, _debug_visible: bool) -> Result<FetchedPage, SpiderError> {
+        self.requested.lock().unwrap().push(url.to_string());
+        match self.pages.get(url) {
+            Some(html) => Ok(FetchedPage { html: html.to_string(), final_url: Some(url.to_string()), ..Default::default() }),
+            None => Err(SpiderError::Timeout(url.to_string())),
+        }
+    }
+}
+
+fn fixture_request(url: &str) -> crate::download::DownloadRequest {
+    crate::download::DownloadRequest {
+        url: url.to_string(),
+        platform: "fixture".to_string(),
+        start_chapter: Some(1),
+        chapter_count: Some(usize::MAX),
+        min_chapter_chars: Some(crate::library::MIN_CHAPTER_BODY_CHARS),
+        ..Default::default()
+    }
+}
+
+fn completed_message(title: &str) -> Option<String> {
+    crate::events::recent_events(&[crate::progress::DOWNLOAD_PROGRESS_EVENT.to_string()], 0)
+        .into_iter()
+        .rev()
+        .find(|e| e.payload["status"] == "completed" && e.payload["message"].as_str().is_some_and(|m| m.contains(title)))
+        .and_then(|e| e.payload["message"].as_str().map(str::to_string))
+}
+
+#[tokio::test]
+async fn fixture_download_retries_skips_and_summarizes_failures() {
+    let root = std::env::temp_dir().join(format!("test_fixture_download_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let source = FixtureSource::load();
+    // 没有窗口：进度只进缓冲，下载照常进行
+    let events = MockSink::new(false, false);
+    let url = "https://fake.test/book/694/";
+
+    let first = crate::download::process_novel_download(&events, &source, &root, fixture_request(url)).await.unwrap();
+    assert_eq!((first.success, first.failed, first.unavailable, first.skipped), (1, 2, 1, 0));
+    assert_eq!(
+        completed_message("夹具之书").as_deref(),
+        Some("下载完成《夹具之书》: 成功 1 / 失败 2 / 已下架/不可用 1 / 跳过 0")
+    );
+
+    let novel_dir = crate::library::downloads_dir(&root).join("夹具之书");
+    assert!(novel_dir.join(crate::library::chapter_file_name(1)).exists());
+    assert!(!novel_dir.join(crate::library::chapter_file_name(2)).exists());
+    assert!(!novel_dir.join(crate::library::chapter_file_name(4)).exists(), "付费章节只有试读段落，不应保存");
+
+    // 再跑一次：已保存的跳过，超时的重试成功，已下架的不再请求
+    let second = crate::download::process_novel_download(&events, &source, &root, fixture_request(url)).await.unwrap();
+    assert_eq!((second.success, second.failed, second.unavailable, second.skipped), (1, 1, 1, 1));
+    assert_eq!(source.attempts("https://fake.test/chapter/694/1/"), 1);
+    assert_eq!(source.attempts("https://fake.test/chapter/694/2/"), 2);
+    assert_eq!(source.attempts("https://fake.test/chapter/694/3/"), 1);
+
+    let text = std::fs::read_to_string(novel_dir.join(crate::library::chapter_file_name(2))).unwrap();
+    let chapter = crate::library::ChapterFile::parse(&text).unwrap();
+    assert_eq!(chapter.title, "第2章 风起", "目录只有章节号时取章节页标题");
+    let index = crate::chapter_index::ChapterIndex::load(&novel_dir);
+    assert_eq!(index.get(2).and_then(|r| r.source), Some(ChapterSource::Api));
+    assert!(index.get(3).is_some_and(|r| r.unavailable));
+    let info = crate::novel_info::read_info(&novel_dir).unwrap();
+    assert_eq!(info["tags"], serde_json::json!(["玄幻", "测试"]));
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn fixture_rank_scan_reads_pages_through_fetcher() {
+    let source = FixtureSource::load();
+    let scan = crate::analysis_engine::fetch_rank(&source, "https://fake.test/rank/", "fixture").await.unwrap();
+    assert_eq!(scan.entries[0].title, "夹具之书");
+
+    // 起点榜单经 PageFetcher 翻页，解析逻辑与真实窗口抓取相同
+    let pages = FixturePages {
+        pages: HashMap::from([
+            ("https://www.qidian.com/rank/yuepiao/", include_str!("spiders/fixtures/qidian_rank_desktop.html")),
+            ("https://www.qidian.com/rank/yuepiao/page2/", include_str!("spiders/fixtures/qidian_rank_desktop_page2.html")),
+        ]),
+        requested: Mutex::new(Vec::new()),
+    };
+    let scan = LiveSource::new(&pages).fetch_rank_list("qidian", "https://www.qidian.com/rank/yuepiao/", 5).await.unwrap();
+    assert_eq!(scan.entries.iter().map(|e| e.position).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(scan.entries[3].title, "玄鉴仙族");
+    assert_eq!(pages.requested.lock().unwrap().len(), 2);
+
+    // 第 3 页取不到：停止翻页，保留前两页的结果
+    let scan = LiveSource::new(&pages).fetch_rank_list("qidian", "https://www.qidian.com/rank/yuepiao/", 10).await.unwrap();
+    assert_eq!(scan.entries.len(), 5);
+    assert_eq!(pages.requested.lock().unwrap().last().unwrap(), "https://www.qidian.com/rank/yuepiao/page3/");
+
+    // 从第 2 页的 URL 开始时接着翻第 3 页，而不是回到第 2 页
+    pages.requested.lock().unwrap().clear();
+    let scan = LiveSource::new(&pages).fetch_rank_list("qidian", "https://www.qidian.com/rank/yuepiao/page2/", 10).await.unwrap();
+    assert_eq!(scan.entries.iter().map(|e| e.position).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(
+        *pages.requested.lock().unwrap(),
+        vec!["https://www.qidian.com/rank/yuepiao/page2/", "https://www.qidian.com/rank/yuepiao/page3/"]
+    );
+}
+
+#[tokio::test]
+async fn concurrent_catalog_misses_share_one_fetch() {
+    let root = std::env::temp_dir().join(format!("test_catalog_cache_{}", std::process::id()));
+    let source = FixtureSource::load();
+    let events = MockSink::new(false, false);
+    let url = "https://fake.test/book/697/";
+    let (source, events, root) = (&source, &events, &root);
+    let fetch = move || crate::download::get_catalog_cached(events, source, root, url, "fixture", false, || {});
+
+    let (a, b) = tokio::join!(fetch(), fetch());
+    let (a, b) = (a.unwrap(), b.unwrap());
+    assert_eq!(source.catalog_fetches.load(Ordering::SeqCst), 1);
+    assert_eq!((a.1, b.1), (false, false), "两个调用方都等的是同一次抓取");
+    assert_eq!(a.0.chapters.len(), b.0.chapters.len());
+
+    // 移动站链接命中同一条缓存；失效后重新抓取
+    let (cached, hit) =
+        crate::download::get_catalog_cached(events, source, root, "https://fake.test/book/697?from=m", "fixture", false, || {})
+            .await
+            .unwrap();
+    assert!(hit);
+    assert_eq!(cached.novel_title, "夹具之书");
+    assert!(crate::download::catalog_cache_stats().size >= 1);
+    assert!(crate::download::invalidate_catalog(url));
+    assert!(!fetch().await.unwrap().1);
+    assert_eq!(source.catalog_fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn redownload_replaces_chapters_and_reports_per_chapter() {
+    use crate::redownload::{ChapterStatus, RedownloadItem, RedownloadOptions};
+
+    let root = std::env::temp_dir().join(format!("test_redownload_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let source = FixtureSource::load();
+    let events = MockSink::new(false, false);
+    crate::download::process_novel_download(&events, &source, &root, fixture_request("https://fake.test/book/702/")).await.unwrap();
+
+    // 模拟被验证页污染的章节
+    let novel_dir = crate::library::downloads_dir(&root).join("夹具之书");
+    std::fs::write(novel_dir.join("01.txt"), "Just a moment...").unwrap();
+
+    let item = RedownloadItem {
+        dir_name: crate::library::DOWNLOADS_DIR.to_string(),
+        novel_name: "夹具之书".to_string(),
+        chapter_files: vec!["01.txt".into(), "03.txt".into(), "09.txt".into()],
+    };
+    let options = RedownloadOptions { min_chapter_chars: Some(crate::library::MIN_CHAPTER_BODY_CHARS), ..Default::default() };
+    let job = crate::redownload::prepare(&root, vec![item], None).unwrap();
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let job = crate::redownload::run(&events, &source, &root, job, cancel, &options).await;
+
+    let statuses: Vec<_> = job.items[0].chapters.iter().map(|c| (c.file.as_str(), c.status)).collect();
+    assert_eq!(
+        statuses,
+        vec![("01.txt", ChapterStatus::Completed), ("03.txt", ChapterStatus::Failed), ("09.txt", ChapterStatus::Failed)]
+    );
+    assert!(job.items[0].chapters[2].error.as_deref().is_some_and(|e| e.contains("没有该章的链接")));
+    assert!(job.completed_at.is_some());
+    assert_eq!(source.attempts("https://fake.test/chapter/694/3/"), 2, "强制重下时已下架的章节也再试一次");
+
+    let text = std::fs::read_to_string(novel_dir.join("01.txt")).unwrap();
+    assert!(text.contains("清晨的雾气"));
+    let versions = crate::versions::list_versions(&novel_dir, "01.txt").unwrap();
+    assert_eq!(versions.len(), 2, "设置未开启版本保留时也留下被替换的旧文件");
+    assert_eq!(std::fs::read_to_string(novel_dir.join(&versions[1].file)).unwrap(), "Just a moment...");
+    assert_eq!(crate::redownload::load(&root, &job.id).unwrap().items, job.items);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn author_works_download_isolates_failures_and_skips_existing() {
+    use crate::author_works::{AuthorDownloadRequest, WorkOutcome};
+
+    let root = std::env::temp_dir().join(format!("test_author_works_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let source = FixtureSource::load();
+    let events = MockSink::new(false, false);
+    let req = AuthorDownloadRequest {
+        author_page_url: "https://fake.test/author/705/".to_string(),
+        platform: "fixture".to_string(),
+        count_per_novel: 2,
+        min_chapter_chars: Some(crate::library::MIN_CHAPTER_BODY_CHARS),
+        ..Default::default()
+    };
+    let (source, events, req, root) = (&source, &events, &req, &root);
+    let run = || async move {
+        let works = crate::author_works::fetch_works(source, req).await.unwrap();
+        crate::author_works::download_works(events, source, root, req, works).await.unwrap()
+    };
+
+    let first = run().await;
+    let outcomes: Vec<_> = first.works.iter().map(|w| (w.work.title.as_str(), w.outcome)).collect();
+    assert_eq!(outcomes, vec![("夹具之书", WorkOutcome::Downloaded), ("下架之书", WorkOutcome::Failed)]);
+    assert!(first.works[1].error.as_deref().is_some_and(|e| e.contains("目录为空")));
+    let novel_dir = crate::library::downloads_dir(root).join("夹具之书");
+    let info = crate::novel_info::read_info(&novel_dir).unwrap();
+    assert_eq!(info["author"], "夹具作者");
+
+    let second = run().await;
+    assert_eq!(second.works[0].outcome, WorkOutcome::Existing, "书库中已有的书不再下载");
+    assert_eq!(second.works[1].outcome, WorkOutcome::Failed);
+    let _ = std::fs::remove_dir_all(root);
+}
+
+/// 一页放多章的镜像站：目录两条链接，第一页三章，第二页一章
+struct MirrorSource;
+
+impl NovelSource for MirrorSource {
+    async fn fetch_rank_list(&self, platform: &str, _url: &str, _max_entries: usize) -> Result<RankScan, String> {
+        Err(format!("不支持的平台: {}", platform))
+    }
+
+    async fn fetch_metadata(&self, _platform: &str, url: &str, _debug_visible: bool) -> Result<NovelMetadata, String> {
+        Ok(NovelMetadata { url: url.to_string(), title: "合页之书".to_string(), ..Default::default() })
+    }
+
+    async fn fetch_author_works(&self, platform: &str, _url: &str, _debug_visible: bool) -> Result<AuthorWorks, String> {
+        Err(format!("不支持的平台: {}", platform))
+    }
+
+    async fn fetch_catalog(&self, _platform: &str, _url: &str, _debug_visible: bool) -> Result<Vec<CatalogChapter>, SpiderError> {
+        Ok(vec![
+            CatalogChapter::new("第一页", "https://mirror.test/710/1.html".to_string(), false),
+            CatalogChapter::new("第二页", "https://mirror.test/710/2.html".to_string(), false),
+        ])
+    }
+
+    async fn download_chapter(
+        &self,
+        _platform: &str,
+        url: &str,
+        _debug_visible: bool,
+    ) -> Result<(String, String, ChapterSource), SpiderError> {
+        let content = if url.ends_with("1.html") {
+            "第一章 开端\n第一章的正文内容足够长。\n第二章 转折\n第二章的正文内容足够长。\n第三章 高潮\n第三章的正文内容足够长。"
+        } else {
+            "第四章 结局\n只有一章的页面，正文同样足够长，不需要拆分。"
+        };
+        Ok((String::new(), content.to_string(), ChapterSource::Html))
+    }
+}
+
+#[tokio::test]
+async fn multi_chapter_pages_are_split_into_sequential_files() {
+    let root = std::env::temp_dir().join(format!("test_segmented_download_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    crate::settings::update(&root, |s| {
+        s.segment_pages.insert("mirror".to_string(), true);
+        Ok(())
+    })
+    .unwrap();
+    let events = MockSink::new(false, false);
+    let req = crate::download::DownloadRequest { platform: "mirror".to_string(), ..fixture_request("https://mirror.test/710/") };
+
+    let summary = crate::download::process_novel_download(&events, &MirrorSource, &root, req.clone()).await.unwrap();
+    assert_eq!(summary.success, 2);
+    assert_eq!(summary.segmented, Some(crate::segmentation::SegmentStats { catalog_entries: 2, chapters: 4 }));
+    assert!(completed_message("合页之书").is_some_and(|m| m.ends_with("（目录 2 条 → 实际 4 章）")));
+
+    let novel_dir = crate::library::downloads_dir(&root).join("合页之书");
+    let index = crate::chapter_index::ChapterIndex::load(&novel_dir);
+    let segments: Vec<_> = index.records().map(|r| (r.index, r.title.as_str(), r.segment.map(|s| (s.page, s.part)))).collect();
+    assert_eq!(
+        segments,
+        vec![(1, "第一章 开端", Some((1, 1))), (2, "第二章 转折", Some((1, 2))), (3, "第三章 高潮", Some((1, 3))), (4, "第二页", None)]
+    );
+    assert!(index.records().take(3).all(|r| r.url == "https://mirror.test/710/1.html"));
+    let text = std::fs::read_to_string(novel_dir.join(crate::library::chapter_file_name(2))).unwrap();
+    assert_eq!(crate::library::ChapterFile::parse(&text).unwrap().body, "第二章的正文内容足够长。");
+
+    // 再跑一次：页面都已下载，章节编号不变
+    let again = crate::download::process_novel_download(&events, &MirrorSource, &root, req).await.unwrap();
+    assert_eq!((again.success, again.skipped), (0, 2));
+    assert_eq!(again.segmented.map(|s| s.chapters), Some(4));
+    assert!(!novel_dir.join(crate::library::chapter_file_name(5)).exists());
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn timed_out_download_stops_between_chapters_and_resumes() {
+    let root = std::env::temp_dir().join(format!("test_timed_out_download_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let source = FixtureSource::load();
+    let events = MockSink::new(true, false);
+    let url = "https://fake.test/book/736/";
+    let req = crate::download::DownloadRequest { time_budget: Some(std::time::Duration::ZERO), ..fixture_request(url) };
+
+    let stopped = crate::download::process_novel_download(&events, &source, &root, req).await.unwrap();
+    assert!(stopped.timed_out);
+    assert_eq!((stopped.success, stopped.failed, stopped.skipped), (0, 0, 0));
+    assert!(events.delivered.lock().unwrap().iter().any(|kind| kind == crate::download::TIMED_OUT_EVENT));
+    let novel_dir = crate::library::downloads_dir(&root).join("夹具之书");
+    let index = crate::chapter_index::ChapterIndex::load(&novel_dir);
+    assert!(index.get(1).is_some_and(|r| !r.downloaded), "目录已记入 chapters.json");
+    let history = crate::download_history::load(&root).unwrap();
+    assert!(history.last().is_some_and(|r| r.timed_out && !r.cancelled));
+
+    // 不带时限再下载一次即从头接着下
+    let resumed = crate::download::process_novel_download(&events, &source, &root, fixture_request(url)).await.unwrap();
+    assert!(!resumed.timed_out);
+    assert!(resumed.success > 0);
+    assert!(!crate::download_history::load(&root).unwrap().last().unwrap().timed_out);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn updating_a_removed_novel_marks_it_and_emits_event() {
+    let root = std::env::temp_dir().join(format!("test_removed_update_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let library_dir = crate::library::downloads_dir(&root);
+    let novel_dir = library_dir.join("下架之书");
+    std::fs::create_dir_all(&novel_dir).unwrap();
+    let url = "https://fake.test/book/removed/";
+    std::fs::write(novel_dir.join("info.json"), serde_json::json!({ "title": "下架之书", "url": url }).to_string()).unwrap();
+    let events = MockSink::new(true, false);
+    let req = crate::download::DownloadRequest { novel_dir: Some(novel_dir.clone()), ..fixture_request(url) };
+
+    let err = crate::download::process_novel_download(&events, &FixtureSource::load(), &root, req).await.unwrap_err();
+    assert!(err.contains("作品已下架"), "{}", err);
+    assert!(crate::source_check::is_removed(&novel_dir));
+    assert!(events.delivered.lock().unwrap().iter().any(|kind| kind == crate::source_check::REMOVED_EVENT));
+
+    let removed = crate::source_check::list_removed(&root, &library_dir);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].title, "下架之书");
+    assert_eq!(removed[0].url.as_deref(), Some(url));
+    assert!(removed[0].status.error.as_deref().is_some_and(|e| e.contains("作品不存在")));
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test]
+async fn large_catalogs_download_into_chapter_subdirectories() {
+    let root = std::env::temp_dir().join(format!("test_sharded_download_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    crate::settings::update(&root, |s| {
+        s.chapters_per_dir = Some(2);
+        Ok(())
+    })
+    .unwrap();
+    let source = FixtureSource::load();
+    let events = MockSink::new(false, false);
+    let url = "https://fake.test/book/694/";
+
+    let first = crate::download::process_novel_download(&events, &source, &root, fixture_request(url)).await.unwrap();
+    assert_eq!(first.success, 1);
+    let novel_dir = crate::library::downloads_dir(&root).join("夹具之书");
+    assert!(!novel_dir.join(crate::library::chapter_file_name(1)).exists());
+    assert!(novel_dir.join("chapters/000/01.txt").exists());
+    let index = crate::chapter_index::ChapterIndex::load(&novel_dir);
+    assert_eq!(index.get(3).and_then(|r| r.path.as_deref()), Some("chapters/001/03.txt"));
+    assert_eq!(crate::analysis_batch::chapter_files(&novel_dir), vec!["chapters/000/01.txt"]);
+    assert!(crate::library::is_novel_dir(&novel_dir));
+
+    // 再跑一次：分目录中的章节按索引找到并跳过
+    let second = crate::download::process_novel_download(&events, &source, &root, fixture_request(url)).await.unwrap();
+    assert_eq!(second.skipped, 1);
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,358 @@
+//! 章节正文的读取时规范化：繁体转简体、标点全半角统一、合并连续空行。
+//!
+//! 只处理正文，章节头部（标题 / 链接 / 分隔线）和 front matter 原样保留。阅读和 AI 输入时按请求
+//! 临时转换，不改动文件；小说在 info.json 中设置了 `text_normalize` 后，下载时写入的就是规范化后的正文，
+//! 也可以通过 `set_novel_normalization` 对已下载章节一次性改写。
+//! 换行符和 BOM 不属于正文规范化，按设置在写入时统一（见 [`crate::storage::write_text`]），
+//! 旧文件用 [`normalize_line_endings`] 批量修复。
+//! 繁简转换用内置的逐字对照表，只收录一对一的常用字，一繁对多简的字（如"乾"、"著"）不转换。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::chapter_index::ChapterIndex;
+use crate::edit_journal::{EditKind, Recorder};
+use crate::storage;
+
+/// info.json 中按小说保存的规范化选项
+pub const INFO_KEY: &str = "text_normalize";
+
+/// 繁体 → 简体，每两个字符为一对
+const T2S_PAIRS: &[&str] = &[
+    "個个們们這这來来說说時时為为國国會会對对過过還还後后發发開开從从當当經经現现間间長长點点動动樣样兒儿見见問问聲声與与無无體体進进裏里裡里麼么學学頭头氣气門门於于",
+    "讓让隻只邊边關关應应變变話话車车東东亞亚書书買买賣卖殺杀劍剑龍龙鳳凤馬马鳥鸟魚鱼雞鸡飛飞風风雲云電电號号萬万億亿錢钱銀银鐵铁師师帥帅將将軍军戰战爭争勝胜敗败滅灭",
+    "華华葉叶藥药蘭兰蘇苏紅红綠绿藍蓝黃黄顏颜陽阳陰阴靈灵緣缘結结紀纪練练組组線线終终給给絕绝統统維维續续總总網网羅罗義义習习聽听聖圣職职腦脑腳脚臉脸興兴舊旧艱艰莊庄",
+    "處处蟲虫術术衛卫衝冲補补裝装覺觉親亲觀观計计訊讯記记許许訴诉試试詩诗該该認认語语誤误請请誰谁調调談谈論论諸诸謝谢識识護护讀读貝贝負负財财責责貨货貴贵費费資资賊贼",
+    "賓宾質质賞赏賢贤購购趕赶趙赵躍跃輕轻較较載载輔辅輪轮轉转辦办辭辞農农連连運运達达遠远遲迟選选遺遗鄉乡醫医釋释針针鈴铃鋒锋錯错鍾钟鐘钟鏡镜閃闪閉闭閒闲閣阁隊队陣阵",
+    "陳陈陸陆隨随險险隱隐雖虽雙双雜杂離离難难靜静韓韩頁页頂顶項项順顺須须預预領领頓顿題题顯显飯饭飲饮飽饱餓饿館馆驗验驚惊鬥斗鬧闹麗丽黨党齊齐齒齿龜龟壽寿壞坏塊块場场",
+    "報报墳坟壓压夢梦夥伙奪夺奮奋婦妇媽妈孫孙實实寫写寶宝導导尋寻屆届屬属歲岁島岛幫帮廣广廳厅張张彈弹強强徑径復复徵征憶忆懷怀戲戏戶户擇择擊击擔担據据擴扩擁拥擋挡擺摆",
+    "攜携敵敌數数斷断晉晋曉晓曆历歷历棄弃極极構构槍枪樂乐標标機机權权橫横檢检歡欢殘残沒没況况淚泪淺浅渾浑溫温滿满漢汉潛潜濟济濃浓灣湾災灾烏乌煉炼煙烟熱热爺爷爾尔牆墙",
+    "狀状獨独獄狱獲获獸兽環环產产畫画疊叠療疗盡尽監监盤盘眾众衆众睜睁礎础確确禮礼禍祸種种稱称穩稳窮穷競竞筆笔節节築筑簡简糧粮純纯紙纸細细級级緊紧縣县縮缩繼继聞闻聯联",
+    "肅肃脈脉腸肠膽胆臨临舉举艦舰蓋盖蝦虾複复規规視视覽览訓训設设證证議议豐丰豬猪貓猫貧贫賴赖賺赚贏赢蹤踪軟软輩辈輸输辯辩鄭郑鄰邻醜丑鋼钢錄录鎮镇闖闯際际響响願愿類类",
+    "颳刮飄飘養养餅饼髒脏鬆松鮮鲜鳴鸣麥麦齡龄劉刘剛刚創创劃划劇剧勞劳勢势勵励區区協协卻却參参叢丛員员啟启喪丧單单嚴严團团圍围圓圆圖图壯壮備备傳传傷伤價价儀仪優优儲储",
+    "兩两內内則则側侧務务勁劲勸劝喚唤嗎吗嘆叹嘗尝嚇吓囑嘱嬰婴寬宽審审層层嶺岭巔巅幣币幹干庫库廢废彌弥徹彻態态慮虑憂忧懼惧戀恋揚扬換换損损搖摇攝摄斬斩條条榮荣樓楼樹树",
+    "橋桥歸归毀毁漸渐潔洁澤泽灑洒燈灯燒烧爐炉犧牺猶犹獻献畢毕異异盜盗碼码禪禅穀谷積积窩窝竊窃簽签紛纷紹绍繞绕繩绳罰罚罷罢膚肤臟脏蒼苍蓮莲虛虚蝕蚀蠻蛮襲袭詞词詢询誇夸",
+    "誠诚誕诞謀谋謊谎謎谜譯译讚赞賀贺賭赌趨趋跡迹踐践軌轨迴回遞递遙遥適适遷迁郵邮醬酱銅铜鋪铺錦锦鍵键鎖锁鑰钥閱阅闊阔隸隶霧雾頸颈頻频顆颗顧顾饒饶駕驾駐驻騎骑騙骗驅驱",
+    "驟骤髮发鬍胡魯鲁鷹鹰鹽盐麵面龐庞愛爱壇坛釘钉鈔钞鉤钩閑闲闡阐韻韵鐲镯嶽岳嘯啸囂嚣黴霉傑杰偉伟債债傾倾僅仅僕仆僑侨儘尽兇凶劊刽勳勋匯汇厲厉啞哑噴喷嚨咙壺壶奧奥孿孪",
+    "嫻娴寧宁屍尸巒峦帳帐帶带幀帧廬庐彎弯彙汇悅悦惡恶惱恼惻恻愴怆慘惨慣惯憑凭懶懒捨舍掃扫掙挣搶抢摯挚撲扑撥拨擬拟擠挤攔拦敘叙斃毙暈晕暫暂曬晒朧胧桿杆棟栋殲歼毆殴氈毡",
+    "洶汹涼凉淵渊湧涌滄沧滲渗漁渔潑泼澀涩濕湿瀟潇灘滩灤滦燦灿牽牵猙狰獰狞瑣琐瘋疯瘡疮癡痴皺皱盞盏矚瞩砲炮碩硕礦矿稅税穌稣窺窥筧笕籃篮籠笼紋纹絲丝綁绑綢绸緒绪締缔編编",
+    "縫缝繃绷織织繪绘纏缠罵骂羨羡翹翘聰聪脅胁脫脱膠胶臥卧艙舱芻刍莖茎蔥葱薦荐蘆芦蠟蜡褲裤襯衬訂订訝讶詐诈誘诱諒谅謂谓諾诺謹谨譏讥讒谗豈岂貪贪販贩貫贯貼贴賄贿賜赐賤贱",
+    "贈赠贊赞贖赎蹌跄軀躯輛辆轟轰辮辫邁迈違违遜逊遼辽邏逻酈郦釀酿鈍钝鉅巨銳锐鋤锄鍋锅鍛锻鎧铠鏈链鐮镰鑄铸鑒鉴鑽钻閘闸闕阙隕陨隴陇雛雏靄霭鞏巩韋韦頌颂頹颓顫颤颶飓飢饥",
+    "飾饰餵喂饑饥駁驳駭骇騰腾驕骄骯肮鬢鬓鯨鲸鴻鸿鵬鹏鶴鹤鸞鸾黷黩鼴鼹齣出龕龛",
+];
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NormalizeOptions {
+    pub to_simplified: bool,
+    /// 中文语境中的半角标点转全角，全角字母数字转半角，「」『』转为“”‘’
+    pub normalize_punctuation: bool,
+    /// 连续多个空行合并为一个
+    pub collapse_blank_lines: bool,
+}
+
+impl NormalizeOptions {
+    pub fn is_empty(&self) -> bool {
+        !self.to_simplified && !self.normalize_punctuation && !self.collapse_blank_lines
+    }
+}
+
+fn t2s_table() -> &'static HashMap<char, char> {
+    static TABLE: OnceLock<HashMap<char, char>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let chars: Vec<char> = T2S_PAIRS.concat().chars().collect();
+        chars.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+    })
+}
+
+pub fn to_simplified(text: &str) -> String {
+    let table = t2s_table();
+    text.chars().map(|c| *table.get(&c).unwrap_or(&c)).collect()
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '\u{3000}'..='\u{303f}' | '\u{ff00}'..='\u{ffef}' | '“' | '”' | '‘' | '’' | '…' | '—'
+    )
+}
+
+fn full_width_punct(c: char) -> Option<char> {
+    Some(match c {
+        ',' => '，',
+        '?' => '？',
+        '!' => '！',
+        ':' => '：',
+        ';' => '；',
+        '(' => '（',
+        ')' => '）',
+        _ => return None,
+    })
+}
+
+pub fn normalize_punctuation(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let next_to_cjk = i.checked_sub(1).is_some_and(|p| is_cjk(chars[p])) || chars.get(i + 1).is_some_and(|&n| is_cjk(n));
+        let c = match c {
+            // 全角字母数字 → 半角
+            '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            '「' => '“',
+            '」' => '”',
+            '『' => '‘',
+            '』' => '’',
+            // 只转换紧挨中文的半角标点，英文和数字中的保持不变
+            _ if next_to_cjk => full_width_punct(c).unwrap_or(c),
+            _ => c,
+        };
+        out.push(c);
+    }
+    out
+}
+
+pub fn collapse_blank_lines(text: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let blank = line.trim().is_empty();
+        if blank && out.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        out.push(if blank { "" } else { line });
+    }
+    while out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    let mut joined = out.join("\n");
+    if text.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+/// 按选项规范化正文（不区分头部，调用方负责只传正文）
+pub fn normalize_body(body: &str, options: NormalizeOptions) -> String {
+    let mut text = body.to_string();
+    if options.to_simplified {
+        text = to_simplified(&text);
+    }
+    if options.normalize_punctuation {
+        text = normalize_punctuation(&text);
+    }
+    if options.collapse_blank_lines {
+        text = collapse_blank_lines(&text);
+    }
+    text
+}
+
+/// 章节文件的头部长度：front matter 加上 标题/链接/分隔线 三行（含其后的换行）
+pub(crate) fn header_len(text: &str) -> usize {
+    let front = text.len() - crate::provenance::strip_front_matter(text).len();
+    let rest = &text[front..];
+    let mut offset = 0;
+    for prefix in ["标题:", "链接:", "====="] {
+        if !rest[offset..].starts_with(prefix) {
+            return front;
+        }
+        offset = rest[offset..].find('\n').map_or(rest.len(), |i| offset + i + 1);
+    }
+    front + offset
+}
+
+/// 规范化章节文件内容，头部原样保留
+pub fn normalize_chapter(text: &str, options: NormalizeOptions) -> String {
+    if options.is_empty() {
+        return text.to_string();
+    }
+    let split = header_len(text);
+    format!("{}{}", &text[..split], normalize_body(&text[split..], options))
+}
+
+/// info.json 中保存的选项，未设置或全部关闭时为 None
+pub fn from_info(info: &Map<String, Value>) -> Option<NormalizeOptions> {
+    info.get(INFO_KEY)
+        .and_then(|v| serde_json::from_value::<NormalizeOptions>(v.clone()).ok())
+        .filter(|o| !o.is_empty())
+}
+
+/// 按选项改写小说目录下已有的章节文件，同步更新 chapters.json 中的哈希，返回改动的文件数。
+/// 旧内容记入修改日志，可经 [`crate::edit_journal::undo`] 撤销
+pub fn rewrite_chapters(novel_dir: &Path, options: NormalizeOptions, history_max_bytes: u64) -> Result<usize, String> {
+    let mut edits = Recorder::begin(novel_dir, EditKind::Normalize, history_max_bytes)?;
+    let changed = ChapterIndex::update(novel_dir, |index| {
+        let mut changed = 0;
+        for file in crate::analysis_batch::chapter_files(novel_dir) {
+            let path = novel_dir.join(&file);
+            let text = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", file, e))?;
+            let normalized = normalize_chapter(storage::strip_bom(&text), options);
+            let bytes = storage::encode_text(&path, &normalized);
+            if bytes == text.as_bytes() {
+                continue;
+            }
+            edits.write(&file, &bytes).map_err(|e| format!("写入 {} 失败: {}", file, e))?;
+            if let Some(n) = crate::library::chapter_index_of(&file) {
+                index.mark_downloaded(n, &bytes);
+            }
+            changed += 1;
+        }
+        Ok(changed)
+    })?;
+    edits.finish()?;
+    Ok(changed)
+}
+
+/// 换行 / BOM 修复的结果
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct LineEndingReport {
+    /// 检查过的文件数
+    pub checked: usize,
+    /// 改写过的章节文件（相对小说目录）
+    pub chapters: Vec<String>,
+    /// 改写过的分析结果（`result/<书名>/` 下的文件名）
+    pub analyses: Vec<String>,
+    /// 章节改动记入的修改日志条目，没有改写章节时为 None
+    pub edit_id: Option<String>,
+}
+
+/// 按当前的换行 / BOM 设置改写一本书已有的章节文件和分析结果（`result/<书名>/*.md`），正文不变。
+/// 章节的旧内容记入修改日志（可撤销），同步更新 chapters.json 中的哈希；分析结果直接覆盖
+pub fn normalize_line_endings(novel_dir: &Path, result_dir: &Path, history_max_bytes: u64) -> Result<LineEndingReport, String> {
+    let mut report = LineEndingReport::default();
+    let mut edits = Recorder::begin(novel_dir, EditKind::LineEndings, history_max_bytes)?;
+    ChapterIndex::update(novel_dir, |index| {
+        for file in crate::analysis_batch::chapter_files(novel_dir) {
+            let path = novel_dir.join(&file);
+            let text = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", file, e))?;
+            report.checked += 1;
+            let bytes = storage::encode_text(&path, &text);
+            if bytes == text.as_bytes() {
+                continue;
+            }
+            edits.write(&file, &bytes).map_err(|e| format!("写入 {} 失败: {}", file, e))?;
+            if let Some(n) = crate::library::chapter_index_of(&file) {
+                index.mark_downloaded(n, &bytes);
+            }
+            report.chapters.push(file);
+        }
+        Ok(())
+    })?;
+    report.edit_id = edits.finish()?.map(|entry| entry.id);
+
+    let mut results: Vec<PathBuf> = fs::read_dir(result_dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    results.retain(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "md"));
+    results.sort();
+    for path in results {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let text = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", name, e))?;
+        report.checked += 1;
+        let bytes = storage::encode_text(&path, &text);
+        if bytes == text.as_bytes() {
+            continue;
+        }
+        storage::write_atomic(&path, &bytes).map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+        report.analyses.push(name);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: NormalizeOptions = NormalizeOptions { to_simplified: true, normalize_punctuation: true, collapse_blank_lines: true };
+
+    #[test]
+    fn table_pairs_are_well_formed() {
+        assert_eq!(T2S_PAIRS.concat().chars().count() % 2, 0);
+        let simplified: std::collections::HashSet<char> = t2s_table().values().copied().collect();
+        assert!(t2s_table().keys().all(|k| !simplified.contains(k)), "简体字不应再被转换");
+    }
+
+    #[test]
+    fn traditional_converts_and_simplified_passes_through() {
+        let samples = [
+            ("這個世界對他來說，還有什麼意義？", "这个世界对他来说，还有什么意义？"),
+            ("龍傲天拔出長劍，體內靈氣翻湧。", "龙傲天拔出长剑，体内灵气翻涌。"),
+            ("「你們從哪裡來？」師父問道。", "“你们从哪里来？”师父问道。"),
+        ];
+        let options = NormalizeOptions { to_simplified: true, normalize_punctuation: true, ..Default::default() };
+        for (traditional, simplified) in samples {
+            assert_eq!(normalize_body(traditional, options), simplified);
+            assert_eq!(normalize_body(simplified, options), simplified);
+        }
+    }
+
+    #[test]
+    fn punctuation_only_changes_next_to_chinese() {
+        assert_eq!(normalize_punctuation("他说,好吧!第１２３章"), "他说，好吧！第123章");
+        assert_eq!(normalize_punctuation("Hello, world! 3,000"), "Hello, world! 3,000");
+        assert_eq!(collapse_blank_lines("一\n\n\n\n二\n  \n\n三\n\n"), "一\n\n二\n\n三\n");
+    }
+
+    #[test]
+    fn chapter_header_is_untouched() {
+        let text = crate::library::render_chapter_file("第一章 開始", "https://example.com/書", "這裡\n\n\n\n結束");
+        let normalized = normalize_chapter(&text, ALL);
+        assert!(normalized.starts_with("标题: 第一章 開始\n链接: https://example.com/書\n"));
+        assert!(normalized.ends_with("这里\n\n结束"));
+        assert_eq!(normalize_chapter(&text, NormalizeOptions::default()), text);
+        // 没有头部的文本整体处理
+        assert_eq!(normalize_chapter("這裡", ALL), "这里");
+    }
+
+    #[test]
+    fn rewrite_updates_files_and_hashes() {
+        let dir = std::env::temp_dir().join(format!("test_text_normalize_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let traditional = crate::library::render_chapter_file("第一章", "u", &"這個世界".repeat(10));
+        let simplified = crate::library::render_chapter_file("第二章", "u", &"这个世界".repeat(10));
+        fs::write(dir.join("01.txt"), &traditional).unwrap();
+        fs::write(dir.join("02.txt"), &simplified).unwrap();
+
+        let options = NormalizeOptions { to_simplified: true, ..Default::default() };
+        assert_eq!(rewrite_chapters(&dir, options, u64::MAX).unwrap(), 1);
+        let rewritten = fs::read_to_string(dir.join("01.txt")).unwrap();
+        assert!(rewritten.ends_with(&"这个世界".repeat(10)));
+        assert!(ChapterIndex::load(&dir).check(1).can_skip());
+        assert_eq!(fs::read_to_string(dir.join("02.txt")).unwrap(), simplified);
+        // 撤销后恢复繁体原文
+        let edit = crate::edit_journal::list(&dir).remove(0);
+        assert_eq!((edit.kind, edit.chapters), (EditKind::Normalize, vec![1]));
+        crate::edit_journal::undo(&dir, &edit.id).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("01.txt")).unwrap(), traditional);
+        assert!(ChapterIndex::load(&dir).check(1).can_skip());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn line_endings_are_fixed_in_chapters_and_results() {
+        let dir = std::env::temp_dir().join(format!("test_line_endings_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (novel_dir, results) = (dir.join("novel"), dir.join("result"));
+        fs::create_dir_all(&novel_dir).unwrap();
+        fs::create_dir_all(&results).unwrap();
+        let clean = crate::library::render_chapter_file("第一章", "u", "第一段\n第二段");
+        fs::write(novel_dir.join("01.txt"), format!("\u{feff}{}", clean.replace('\n', "\r\n"))).unwrap();
+        fs::write(novel_dir.join("02.txt"), &clean).unwrap();
+        fs::write(results.join("1.md"), "# 细纲\r\n内容\r").unwrap();
+
+        let report = normalize_line_endings(&novel_dir, &results, u64::MAX).unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.chapters, vec!["01.txt".to_string()]);
+        assert_eq!(report.analyses, vec!["1.md".to_string()]);
+        assert_eq!(fs::read_to_string(novel_dir.join("01.txt")).unwrap(), clean);
+        assert!(ChapterIndex::load(&novel_dir).check(1).can_skip());
+        assert_eq!(fs::read_to_string(results.join("1.md")).unwrap(), "# 细纲\n内容\n");
+        assert!(report.edit_id.is_some());
+        let again = normalize_line_endings(&novel_dir, &results, u64::MAX).unwrap();
+        assert_eq!((again.chapters.len(), again.edit_id), (0, None));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,157 @@
+//! 章节翻译：分析外文小说前，先把章节译成指定语言，译文与原章节放在同一目录。
+//!
+//! 译文保存为原章节旁边的 `NN.<语言代码>.txt`（如 `05.en.txt`），沿用章节文件的标题 / 链接头部，
+//! 不会被 [`library::is_chapter_file_name`] 当作章节。译文长度与原文相当，所以每次请求的原文
+//! 只占请求体上限的一半、估算 token 数不超过 [`MAX_OUTPUT_TOKENS`]，超出时按段落分块逐块翻译。
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::errors::AppError;
+use crate::library::{self, ChapterFile};
+use crate::prompts::OutputLanguage;
+use crate::{ai, ai_context, ai_limits, storage};
+
+/// 单次请求允许的输出 token 估算上限（常见模型默认输出上限 4096，留出余量）
+pub const MAX_OUTPUT_TOKENS: usize = 3500;
+
+/// 一章译文的保存结果
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TranslationResult {
+    /// 译文文件名，如 `05.en.txt`
+    pub file: String,
+    pub language: String,
+    /// 分块请求的次数
+    pub chunks: usize,
+    pub tokens: Option<u64>,
+}
+
+/// 第 n 章的译文文件名
+pub fn translation_file_name(n: usize, language: &str) -> String {
+    format!("{:02}.{}.txt", n, language)
+}
+
+/// 翻译用的提示词：只要译文，不加解释
+pub fn translation_prompt(language: OutputLanguage) -> String {
+    format!(
+        "你是一名专业的文学译者。请把用户提供的小说正文完整翻译成{}，保持原有的分段和对话格式，\
+人名、地名前后译法一致。只输出译文，不要添加解释、注释或标题之外的任何内容。",
+        language.name
+    )
+}
+
+/// 粗略估算 token 数：非 ASCII 字符（中日韩文字）按一个 token，ASCII 按四个字符一个 token
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(a, o), c| if c.is_ascii() { (a + 1, o) } else { (a, o + 1) });
+    other + ascii.div_ceil(4)
+}
+
+/// 单块原文的字节上限：请求体里还要给同样大小的译文留出空间
+pub fn chunk_byte_limit(prompt: &str) -> usize {
+    ai_context::body_budget(prompt) / 2
+}
+
+fn fits(text: &str, max_bytes: usize, max_tokens: usize) -> bool {
+    text.len() <= max_bytes && estimate_tokens(text) <= max_tokens
+}
+
+/// 按行把正文分成不超过上限的块；单行超限时按字符硬切
+pub fn split_for_translation(text: &str, max_bytes: usize, max_tokens: usize) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let candidate = if current.is_empty() { line.to_string() } else { format!("{}\n{}", current, line) };
+        if fits(&candidate, max_bytes, max_tokens) {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if fits(line, max_bytes, max_tokens) {
+            current = line.to_string();
+            continue;
+        }
+        for c in line.chars() {
+            current.push(c);
+            if !fits(&current, max_bytes, max_tokens) {
+                current.pop();
+                chunks.push(std::mem::take(&mut current));
+                current.push(c);
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 翻译一段正文：必要时分块，逐块请求后按原顺序拼接。返回 (译文, 分块数, token 用量)
+pub async fn translate(config: &ai::AiConfig, content: &str, language: OutputLanguage) -> Result<(String, usize, Option<u64>), AppError> {
+    let prompt = translation_prompt(language);
+    let chunks = split_for_translation(content, chunk_byte_limit(&prompt), MAX_OUTPUT_TOKENS);
+    let mut parts = Vec::with_capacity(chunks.len());
+    let mut tokens: Option<u64> = None;
+    for (i, chunk) in chunks.iter().enumerate() {
+        ai_limits::pace(config, |wait, reason| {
+            crate::log_to_file(&format!("[Translate] AI 接口{}，{} 后继续第 {} 块", reason, ai_limits::resume_time(wait), i + 1));
+        })
+        .await;
+        let (text, used) = ai::call_ai_with_usage(config.clone(), prompt.clone(), chunk.clone(), false).await?;
+        parts.push(text.trim().to_string());
+        if let Some(used) = used {
+            tokens = Some(tokens.unwrap_or(0) + used);
+        }
+    }
+    Ok((parts.join("\n"), chunks.len(), tokens))
+}
+
+/// 翻译第 index 章并保存为 `NN.<语言>.txt`。标题放在正文首行一起翻译，译文首行作为译文标题。
+pub async fn translate_chapter(
+    novel_dir: &Path,
+    index: usize,
+    config: &ai::AiConfig,
+    language: OutputLanguage,
+) -> Result<TranslationResult, AppError> {
+    let source = crate::sharding::chapter_path(novel_dir, index);
+    let text = storage::read_to_string(&source).map_err(|e| AppError::io("读取章节失败", &e))?;
+    let chapter = ChapterFile::parse(&text)
+        .ok_or_else(|| AppError::invalid_input(format!("章节文件缺少标题头部: {}", source.display())))?;
+    if chapter.body.trim().is_empty() {
+        return Err(AppError::invalid_input(format!("第 {} 章正文为空", index)));
+    }
+
+    let (translated, chunks, tokens) = translate(config, &format!("{}\n{}", chapter.title, chapter.body), language).await?;
+    let (title, body) = match translated.split_once('\n') {
+        Some((title, body)) if !title.trim().is_empty() => (title.trim().to_string(), body.trim_start_matches('\n').to_string()),
+        _ => (chapter.title.clone(), translated),
+    };
+    let file = translation_file_name(index, language.code);
+    let target = source.with_file_name(&file);
+    let rendered = library::render_chapter_file(&title, &chapter.url, &body);
+    storage::write_text(&target, &rendered).map_err(|e| AppError::io("写入译文失败", &e))?;
+    Ok(TranslationResult { file, language: language.code.to_string(), chunks, tokens })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_leave_room_for_output_and_names_are_not_chapters() {
+        let text = format!("{}\n{}\n{}", "甲".repeat(30), "乙".repeat(30), "丙".repeat(80));
+        let chunks = split_for_translation(&text, usize::MAX, 50);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| estimate_tokens(c) <= 50));
+        assert_eq!(chunks.concat().replace('\n', ""), text.replace('\n', ""));
+
+        assert_eq!(estimate_tokens("abcdefgh中文"), 4);
+        let prompt = translation_prompt(crate::prompts::output_language(Some("en")).unwrap().unwrap());
+        assert!(chunk_byte_limit(&prompt) * 2 <= ai_context::body_budget(&prompt));
+
+        let name = translation_file_name(5, "en");
+        assert_eq!(name, "05.en.txt");
+        assert!(!library::is_chapter_file_name(&name));
+    }
+}
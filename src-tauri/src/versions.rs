@@ -0,0 +1,229 @@
+//! 章节历史版本：改写章节的操作经 [`crate::edit_journal`] 备份的旧内容，按章查看和对比。
+//!
+//! 早先版本在章节旁另存的 `NN.txt.v<时间戳>` 仍一并列出（只读，不再产生新的）。
+//! 这些文件不匹配 [`library::is_chapter_file_name`]，不会被书库扫描或修复流程当成章节。
+
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::Path;
+
+use crate::{edit_journal, library};
+
+const VERSION_MARK: &str = ".v";
+/// 时间戳精确到毫秒，按字典序即按时间排序
+const VERSION_TS_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+/// diff 每个 hunk 前后保留的上下文行数
+const DIFF_CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChapterVersion {
+    pub file: String,
+    /// `YYYY-MM-DD HH:MM:SS`；当前版本为文件修改时间
+    pub timestamp: String,
+    pub size: u64,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DiffLine {
+    /// "equal" | "delete" | "insert"
+    pub tag: String,
+    pub text: String,
+}
+
+/// 一段改动，行号从 1 开始
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterDiff {
+    pub old_file: String,
+    pub new_file: String,
+    pub hunks: Vec<DiffHunk>,
+}
+
+fn check_chapter_file(chapter_file: &str) -> Result<(), String> {
+    if library::is_chapter_path(chapter_file) {
+        Ok(())
+    } else {
+        Err(format!("不是章节文件: {}", chapter_file))
+    }
+}
+
+/// 某章旁早先另存的 `.v<时间戳>` 文件，从新到旧。分目录存放的章节（`chapters/NNN/NN.txt`）返回同样带子目录的路径。
+fn legacy_version_files(novel_dir: &Path, chapter_file: &str) -> Vec<String> {
+    let (sub_dir, name) = match chapter_file.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, chapter_file),
+    };
+    let prefix = format!("{}{}", name, VERSION_MARK);
+    let Ok(entries) = fs::read_dir(sub_dir.map_or_else(|| novel_dir.to_path_buf(), |dir| novel_dir.join(dir))) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.strip_prefix(&prefix).is_some_and(|ts| !ts.is_empty() && ts.chars().all(|c| c.is_ascii_digit())))
+        .map(|name| match sub_dir {
+            Some(dir) => format!("{}/{}", dir, name),
+            None => name,
+        })
+        .collect();
+    files.sort_unstable_by(|a, b| b.cmp(a));
+    files
+}
+
+fn format_version_ts(file: &str) -> String {
+    file.rsplit_once(VERSION_MARK)
+        .and_then(|(_, ts)| NaiveDateTime::parse_from_str(ts, VERSION_TS_FORMAT).ok())
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// 某章的历史版本（相对小说目录的文件、`YYYY-MM-DD HH:MM:SS`），修改日志中的备份在前，均从新到旧
+fn history_files(novel_dir: &Path, chapter_file: &str) -> Vec<(String, String)> {
+    let mut files: Vec<(String, String)> = edit_journal::chapter_backups(novel_dir, chapter_file)
+        .into_iter()
+        .map(|(at, backup)| (backup, at))
+        .collect();
+    files.extend(legacy_version_files(novel_dir, chapter_file).into_iter().map(|file| {
+        let timestamp = format_version_ts(&file);
+        (file, timestamp)
+    }));
+    files
+}
+
+/// 列出某章的当前版本和所有历史版本，从新到旧。
+pub fn list_versions(novel_dir: &Path, chapter_file: &str) -> Result<Vec<ChapterVersion>, String> {
+    check_chapter_file(chapter_file)?;
+    let mut versions = Vec::new();
+
+    if let Ok(meta) = fs::metadata(novel_dir.join(chapter_file)) {
+        let modified = meta
+            .modified()
+            .map(|t| chrono::DateTime::<Local>::from(t).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        versions.push(ChapterVersion { file: chapter_file.to_string(), timestamp: modified, size: meta.len(), current: true });
+    }
+    for (file, timestamp) in history_files(novel_dir, chapter_file) {
+        let size = fs::metadata(novel_dir.join(&file)).map(|m| m.len()).unwrap_or(0);
+        versions.push(ChapterVersion { timestamp, file, size, current: false });
+    }
+    Ok(versions)
+}
+
+/// 按行比较两段文本，返回带上下文的 hunk 列表。
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(DIFF_CONTEXT_LINES)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let (old_range, new_range) = (first.old_range().start..last.old_range().end, first.new_range().start..last.new_range().end);
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    tag: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Delete => "delete",
+                        ChangeTag::Insert => "insert",
+                    }
+                    .to_string(),
+                    text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                })
+                .collect();
+            Some(DiffHunk {
+                old_start: old_range.start + 1,
+                old_len: old_range.len(),
+                new_start: new_range.start + 1,
+                new_len: new_range.len(),
+                lines,
+            })
+        })
+        .collect()
+}
+
+/// 比较某章最新的两个版本（当前文件 vs 最近一个历史版本）。
+pub fn diff_latest(novel_dir: &Path, chapter_file: &str) -> Result<ChapterDiff, String> {
+    check_chapter_file(chapter_file)?;
+    let (old_file, _) = history_files(novel_dir, chapter_file)
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} 没有历史版本", chapter_file))?;
+    let read = |file: &str| {
+        fs::read_to_string(novel_dir.join(file)).map_err(|e| format!("读取 {} 失败: {}", file, e))
+    };
+    let hunks = diff_lines(&read(&old_file)?, &read(chapter_file)?);
+    Ok(ChapterDiff { old_file, new_file: chapter_file.to_string(), hunks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_novel_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test_versions_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rewrite(dir: &Path, text: &str) {
+        let mut edits = edit_journal::Recorder::begin(dir, edit_journal::EditKind::Normalize, u64::MAX).unwrap();
+        edits.write("01.txt", text.as_bytes()).unwrap();
+        edits.finish().unwrap();
+    }
+
+    #[test]
+    fn journal_backups_and_legacy_files_are_listed_newest_first() {
+        let dir = temp_novel_dir("journal");
+        fs::write(dir.join("01.txt"), "第0版\n").unwrap();
+        fs::write(dir.join("01.txt.v20240101000000000"), "旧版\n").unwrap();
+        assert!(diff_latest(&dir, "02.txt").is_err());
+        for i in 1..3 {
+            rewrite(&dir, &format!("第{}版\n", i));
+        }
+        // 内容未变不产生版本
+        rewrite(&dir, "第2版\n");
+
+        let versions = list_versions(&dir, "01.txt").unwrap();
+        assert_eq!(versions.len(), 4);
+        assert!(versions[0].current);
+        assert_eq!(fs::read_to_string(dir.join(&versions[1].file)).unwrap(), "第1版\n");
+        assert_eq!(fs::read_to_string(dir.join(&versions[2].file)).unwrap(), "第0版\n");
+        assert_eq!((versions[3].file.as_str(), versions[3].timestamp.as_str()), ("01.txt.v20240101000000000", "2024-01-01 00:00:00"));
+        assert!(!library::is_chapter_path(&versions[1].file));
+        let diff = diff_latest(&dir, "01.txt").unwrap();
+        assert_eq!(diff.old_file, versions[1].file);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn diff_reports_changed_lines_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\ni\n";
+        let hunks = diff_lines(old, new);
+        assert_eq!(hunks.len(), 1);
+        let h = &hunks[0];
+        assert_eq!((h.old_start, h.old_len, h.new_start, h.new_len), (2, 7, 2, 7));
+        let changed: Vec<_> = h.lines.iter().filter(|l| l.tag != "equal").map(|l| (l.tag.as_str(), l.text.as_str())).collect();
+        assert_eq!(changed, vec![("delete", "e"), ("insert", "E")]);
+    }
+
+    #[test]
+    fn rejects_non_chapter_names() {
+        let dir = temp_novel_dir("names");
+        assert!(list_versions(&dir, "../info.json").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
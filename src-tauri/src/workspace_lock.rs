@@ -0,0 +1,326 @@
+//! 工作区锁：开发版和安装版同时打开同一个工作区时，两边都会跑调度、写历史和 JSON，互相覆盖。
+//!
+//! 首次使用工作区（启动或切换工作区）时对 `<workspace>/.lock.guard` 加操作系统的排他文件锁，
+//! 文件句柄在本进程内一直保持打开，实例崩溃时由系统释放。两个实例同时启动也只有一个能拿到。
+//! 拿到文件锁的实例再写 `<workspace>/.lock`，内容为本进程的 pid 和启动时间，供另一实例显示占用者；
+//! 先写临时文件再改名到位，其他实例不会读到写了一半的内容。
+//! 文件锁被占用时本实例进入"只读为主"模式：阅读和 AI 分析照常，下载、调度、清理和
+//! 写 JSON 的命令经 [`ensure_writable`] 返回 `WORKSPACE_LOCKED`。旧版本只写 `.lock` 不加文件锁，
+//! 因此拿到文件锁后 `.lock` 中的 pid 仍在运行（且启动时间一致）时同样视为被占用；pid 已不存在或被复用时
+//! 覆盖旧锁并接管。退出或切换工作区时经 [`release`] 删除自己的 `.lock` 并释放文件锁。
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::errors::{AppError, ErrorCode};
+
+pub const LOCK_FILE: &str = ".lock";
+/// 加操作系统文件锁的文件。释放时不删除：删掉后别的实例可能锁住旧文件、另一个锁住新文件
+pub const GUARD_FILE: &str = ".lock.guard";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub started_at: String,
+    /// 操作系统记录的进程启动时间，用来识别 pid 被其他进程复用；旧版本写的锁没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_start: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LockStatus {
+    pub workspace_root: String,
+    /// 本实例持有锁
+    pub owned: bool,
+    /// 被另一实例占用，本实例只读
+    pub read_only: bool,
+    /// 持有锁的实例（owned 时为本实例）
+    pub owner: Option<LockOwner>,
+    /// 接管时清除的崩溃实例留下的锁
+    pub broke_stale: Option<LockOwner>,
+}
+
+/// 本实例持有的一个工作区锁
+struct Held {
+    /// 加了排他锁的文件句柄，drop 时释放；文件系统不支持加锁时为 None
+    guard: Option<File>,
+    /// 接管时覆盖的崩溃实例留下的锁
+    broke_stale: Option<LockOwner>,
+}
+
+/// 本实例持有锁的工作区
+fn held() -> &'static Mutex<HashMap<PathBuf, Held>> {
+    static HELD: OnceLock<Mutex<HashMap<PathBuf, Held>>> = OnceLock::new();
+    HELD.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn me() -> &'static LockOwner {
+    static ME: OnceLock<LockOwner> = OnceLock::new();
+    ME.get_or_init(|| LockOwner {
+        pid: std::process::id(),
+        started_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        process_start: process_start(std::process::id()),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn pid_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn pid_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+#[cfg(windows)]
+fn pid_alive(pid: u32) -> bool {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).contains(&format!("\"{}\"", pid)))
+}
+
+/// 进程的启动时间（只用于和锁中记录的比较，格式因平台而异），查不到时为 None
+#[cfg(target_os = "linux")]
+fn process_start(pid: u32) -> Option<String> {
+    let stat = fs::read_to_string(Path::new("/proc").join(pid.to_string()).join("stat")).ok()?;
+    // 进程名可能含空格和括号，从最后一个 ')' 之后数：之后第 20 个字段是第 22 项 starttime
+    stat.rsplit_once(')')?.1.split_whitespace().nth(19).map(str::to_string)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_start(pid: u32) -> Option<String> {
+    let out = std::process::Command::new("ps").args(["-o", "lstart=", "-p", &pid.to_string()]).output().ok()?;
+    let start = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !start.is_empty()).then_some(start)
+}
+
+#[cfg(windows)]
+fn process_start(pid: u32) -> Option<String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let script = format!("(Get-Process -Id {}).StartTime.ToFileTimeUtc()", pid);
+    let out = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    let start = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !start.is_empty()).then_some(start)
+}
+
+/// 锁的持有者是否仍在运行：pid 存在，且两边都能查到启动时间时二者一致
+fn owner_alive(owner: &LockOwner) -> bool {
+    if !pid_alive(owner.pid) {
+        return false;
+    }
+    match (&owner.process_start, process_start(owner.pid)) {
+        (Some(recorded), Some(actual)) => *recorded == actual,
+        _ => true,
+    }
+}
+
+/// 写入本实例的锁：完整内容先写入临时文件，再改名为 `.lock`
+fn write_lock(path: &Path) -> io::Result<()> {
+    let tmp = path.with_file_name(format!("{}.{}.tmp", LOCK_FILE, me().pid));
+    fs::write(&tmp, serde_json::to_vec(me()).unwrap_or_default())?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// 另一实例持有文件锁、但还没写好 `.lock` 时报给调用方的占用者
+fn unknown_owner() -> LockOwner {
+    LockOwner { pid: 0, started_at: "未知".to_string(), process_start: None }
+}
+
+/// 尝试取得工作区锁。已持有时直接返回；被仍在运行的实例占用时返回该实例。
+/// 锁文件无法创建或加锁（如只读目录、不支持文件锁的网络盘）时不阻止使用，视为已取得。
+pub fn acquire(root: &Path) -> Result<(), LockOwner> {
+    let mut held = held().lock().unwrap_or_else(|e| e.into_inner());
+    if held.contains_key(root) {
+        return Ok(());
+    }
+    let path = root.join(LOCK_FILE);
+    let guard = match fs::OpenOptions::new().create(true).truncate(false).write(true).open(root.join(GUARD_FILE)) {
+        Ok(file) => match file.try_lock_exclusive() {
+            Ok(()) => Some(file),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                return Err(read_owner(&path).filter(|o| o.pid != me().pid).unwrap_or_else(unknown_owner));
+            }
+            Err(_) => None,
+        },
+        Err(_) => None,
+    };
+
+    // 文件锁在手，只剩旧版本实例可能仍在使用这个工作区
+    let previous = read_owner(&path).filter(|owner| owner.pid != me().pid);
+    if let Some(owner) = previous.as_ref().filter(|owner| owner_alive(owner)) {
+        return Err(owner.clone());
+    }
+    if previous.is_some() {
+        crate::log_to_file_with_root(&format!("[WorkspaceLock] 清除已退出实例的锁: {:?}", previous), Some(root));
+    }
+    let _ = write_lock(&path);
+    held.insert(root.to_path_buf(), Held { guard, broke_stale: previous });
+    Ok(())
+}
+
+/// 写操作前调用：工作区被另一实例占用时返回 `WORKSPACE_LOCKED`。占用者退出后会自动接管。
+pub fn ensure_writable(root: &Path) -> Result<(), AppError> {
+    acquire(root).map_err(|owner| {
+        AppError::new(
+            ErrorCode::WorkspaceLocked,
+            format!("工作区已被另一实例占用（pid {}，启动于 {}）", owner.pid, owner.started_at),
+        )
+    })
+}
+
+pub fn status(root: &Path) -> LockStatus {
+    let foreign = acquire(root).err();
+    let broke_stale = held().lock().unwrap_or_else(|e| e.into_inner()).get(root).and_then(|h| h.broke_stale.clone());
+    LockStatus {
+        workspace_root: root.to_string_lossy().to_string(),
+        owned: foreign.is_none(),
+        read_only: foreign.is_some(),
+        owner: Some(foreign.unwrap_or_else(|| me().clone())),
+        broke_stale,
+    }
+}
+
+/// 删除本实例持有的锁（退出或切换工作区时），最后才释放文件锁
+pub fn release(root: &Path) {
+    let mut held = held().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = held.remove(root) {
+        let path = root.join(LOCK_FILE);
+        if read_owner(&path).is_some_and(|owner| owner.pid == me().pid) {
+            let _ = fs::remove_file(path);
+        }
+        if let Some(guard) = entry.guard {
+            let _ = guard.unlock();
+        }
+    }
+}
+
+pub fn release_all() {
+    let roots: Vec<PathBuf> = held().lock().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect();
+    for root in roots {
+        release(&root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn live_lock_makes_workspace_read_only_and_stale_lock_is_broken() {
+        let root = std::env::temp_dir().join(format!("test_workspace_lock_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let lock = root.join(LOCK_FILE);
+
+        // 父进程一定还在运行，冒充另一实例
+        let parent_pid = std::os::unix::process::parent_id();
+        let parent = LockOwner {
+            pid: parent_pid,
+            started_at: "2026-01-01 00:00:00".into(),
+            process_start: process_start(parent_pid),
+        };
+        fs::write(&lock, serde_json::to_vec(&parent).unwrap()).unwrap();
+        let err = ensure_writable(&root).unwrap_err();
+        assert_eq!(err.code, ErrorCode::WorkspaceLocked);
+        assert!(err.detail.contains(&parent.pid.to_string()));
+        assert!(status(&root).read_only);
+
+        // pid 仍在运行但启动时间不符：pid 被复用，原实例已退出
+        let reused = LockOwner { process_start: Some("0".into()), ..parent.clone() };
+        fs::write(&lock, serde_json::to_vec(&reused).unwrap()).unwrap();
+        assert!(ensure_writable(&root).is_ok());
+        assert_eq!(status(&root).broke_stale, Some(reused));
+        release(&root);
+
+        // 已退出的实例：自动接管
+        let dead = LockOwner { pid: u32::MAX - 1, started_at: "2026-01-01 00:00:00".into(), process_start: None };
+        fs::write(&lock, serde_json::to_vec(&dead).unwrap()).unwrap();
+        assert!(ensure_writable(&root).is_ok());
+        let status = status(&root);
+        assert!(status.owned && !status.read_only);
+        assert_eq!(status.broke_stale, Some(dead));
+        assert_eq!(read_owner(&lock).map(|o| o.pid), Some(std::process::id()));
+
+        release(&root);
+        assert!(!lock.exists());
+        let leftovers = fs::read_dir(&root).unwrap().filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp"));
+        assert_eq!(leftovers.count(), 0, "临时锁文件应已删除");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn stale_lock_is_not_broken_while_another_instance_holds_the_file_lock() {
+        let root = std::env::temp_dir().join(format!("test_workspace_lock_guard_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let lock = root.join(LOCK_FILE);
+        // 另一实例刚拿到文件锁，还没来得及改写崩溃实例留下的 .lock
+        let other = File::create(root.join(GUARD_FILE)).unwrap();
+        other.try_lock_exclusive().unwrap();
+        let dead = LockOwner { pid: u32::MAX - 1, started_at: "2026-01-01 00:00:00".into(), process_start: None };
+        fs::write(&lock, serde_json::to_vec(&dead).unwrap()).unwrap();
+
+        assert!(ensure_writable(&root).is_err());
+        assert_eq!(read_owner(&lock), Some(dead), "不能删掉或覆盖别人正在接管的锁");
+
+        // 占用者退出（或崩溃，由系统释放文件锁）后本实例接管
+        drop(other);
+        assert!(ensure_writable(&root).is_ok());
+        assert_eq!(read_owner(&lock).map(|o| o.pid), Some(std::process::id()));
+        release(&root);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// 写磁盘（下载、调度、清理、写 JSON、打包）的命令，新增此类命令时加到这里
+    const WRITING_COMMANDS: &[&str] = &[
+        "update_novel_metadata", "rebuild_metadata_offline", "repair_novel", "retitle_chapters",
+        "shard_novel", "delete_chapter", "verify_novel", "update_novel", "prefetch_next_chapters",
+        "set_user_metadata", "set_novel_arcs", "archive_novel", "update_settings", "undo_edit",
+        "set_selector_override", "normalize_library_paths", "set_network_mode",
+        "recover_incomplete_operations", "set_resource_profile", "process_ai_retry_queue",
+        "generate_activity_digest", "trigger_full_scan", "save_rank_bookmark", "delete_rank_bookmark",
+        "rescan_bookmark", "download_and_analyze", "redownload_chapters", "build_search_index",
+        "check_all_sources", "extract_analysis_section", "import_analysis", "import_analysis_directory",
+        "backup_workspace", "restore_workspace", "create_scratch_document", "delete_scratch_document",
+        "add_bookmark", "delete_bookmark", "relink_result", "save_clean_rules", "start_download",
+        "download_author_works", "bootstrap_workspace", "clear_log", "set_novel_normalization",
+        "normalize_line_endings",
+    ];
+
+    #[test]
+    fn writing_commands_check_the_workspace_lock() {
+        let source = include_str!("lib.rs");
+        for name in WRITING_COMMANDS {
+            let start = source.find(&format!("fn {}(", name)).unwrap_or_else(|| panic!("找不到命令 {}", name));
+            let body = &source[start..];
+            let body = &body[..body.find("\n}\n").unwrap_or(body.len())];
+            assert!(body.contains("workspace_lock::ensure_writable("), "{} 写磁盘前没有检查工作区锁", name);
+        }
+    }
+}